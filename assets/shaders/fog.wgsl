@@ -0,0 +1,41 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+const RAY_COUNT: u32 = 128u;
+const TAU: f32 = 6.28318530718;
+
+struct FogUniform {
+    // xy: origin of the vision, z: vision radius, w: darkness
+    origin: vec4<f32>,
+    // x: edge softness
+    params: vec4<f32>,
+    rays: array<vec4<f32>, 32>,
+};
+
+@group(2) @binding(0) var<uniform> fog: FogUniform;
+
+fn ray_distance(index: u32) -> f32 {
+    let i = index % RAY_COUNT;
+    return fog.rays[i / 4u][i % 4u];
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let delta = in.world_position.xy - fog.origin.xy;
+    let distance = length(delta);
+
+    var angle = atan2(delta.y, delta.x);
+    if (angle < 0.0) {
+        angle = angle + TAU;
+    }
+
+    // Interpolate between the two closest rays to smooth the polygon edges
+    let position = angle / TAU * f32(RAY_COUNT);
+    let index = u32(floor(position));
+    let t = fract(position);
+    let visible_distance = mix(ray_distance(index), ray_distance(index + 1u), t);
+
+    let softness = max(fog.params.x, 0.001);
+    let alpha = smoothstep(visible_distance - softness, visible_distance + softness, distance) * fog.origin.w;
+
+    return vec4<f32>(0.0, 0.0, 0.0, alpha);
+}
@@ -0,0 +1,53 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+const MAX_LIGHTS: u32 = 64u;
+
+struct Light {
+    // xy: position, z: radius, w: intensity
+    position: vec4<f32>,
+    color: vec4<f32>,
+    // xy: direction of the cone, z: cos of the half angle, w: 1 for a cone
+    cone: vec4<f32>,
+};
+
+struct LightingUniform {
+    // rgb: color of the darkness, a: darkness
+    ambient: vec4<f32>,
+    // x: number of lights
+    params: vec4<f32>,
+    lights: array<Light, 64>,
+};
+
+@group(2) @binding(0) var<uniform> lighting: LightingUniform;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let count = min(u32(lighting.params.x), MAX_LIGHTS);
+    var light = vec3<f32>(0.0);
+
+    for (var i = 0u; i < count; i = i + 1u) {
+        let l = lighting.lights[i];
+        let delta = in.world_position.xy - l.position.xy;
+        let distance = length(delta);
+        let falloff = clamp(1.0 - distance / max(l.position.z, 0.001), 0.0, 1.0);
+        var strength = falloff * falloff * l.position.w;
+
+        if (l.cone.w > 0.5) {
+            let cos_angle = dot(delta / max(distance, 0.001), l.cone.xy);
+            // Soft edge on the side of the cone, small halo around the source
+            let edge = smoothstep(l.cone.z - 0.05, l.cone.z + 0.05, cos_angle);
+            let halo = clamp(1.0 - distance / 60.0, 0.0, 1.0);
+            strength = strength * max(edge, halo);
+        }
+
+        light = light + l.color.rgb * strength;
+    }
+
+    let brightness = clamp(max(light.r, max(light.g, light.b)), 0.0, 1.0);
+    let tint = light / max(brightness, 0.001);
+    let alpha = lighting.ambient.a * (1.0 - brightness);
+
+    // Darkness where there is no light, a faint tint of the light color elsewhere
+    let color = mix(lighting.ambient.rgb, tint, brightness);
+    return vec4<f32>(color, max(alpha, brightness * 0.15));
+}
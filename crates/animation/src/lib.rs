@@ -63,10 +63,111 @@ pub struct AnimationIndices {
     pub end: usize, // Inclusive end index
 }
 
+// Named trigger on a frame of an animation, the frame is counted from the start of the animation
+#[derive(Deserialize, Debug, Clone)]
+pub struct AnimationFrameTrigger {
+    pub frame: usize,
+    pub name: String,
+}
+
+// Minimum frames the `from` state play before switching to `to`, "*" match any state
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransitionRule {
+    pub from: String,
+    pub to: String,
+    pub frames: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AnimationTransitions {
+    // A state with a higher priority interrupt a lower one right away, missing states are 0.
+    // ex: Death > Hit > Melee > Run > Idle
+    #[serde(default)]
+    pub priorities: HashMap<String, i32>,
+    #[serde(default)]
+    pub min_frames: Vec<TransitionRule>,
+    // Duration of the fade out of the previous frame when switching, 0 to snap
+    #[serde(default)]
+    pub crossfade_ms: u64,
+}
+
+impl AnimationTransitions {
+    pub fn priority(&self, state: &str) -> i32 {
+        self.priorities.get(state).copied().unwrap_or_default()
+    }
+
+    // The most specific rule win: exact pair, then from the state, then to the state
+    pub fn min_frames(&self, from: &str, to: &str) -> u32 {
+        let find = |f: &str, t: &str| self.min_frames.iter().find(|rule| rule.from == f && rule.to == t).map(|rule| rule.frames);
+        find(from, to)
+            .or_else(|| find(from, "*"))
+            .or_else(|| find("*", to))
+            .or_else(|| find("*", "*"))
+            .unwrap_or(0)
+    }
+
+    pub fn can_switch(&self, from: &str, to: &str, frames_played: u32) -> bool {
+        self.priority(to) > self.priority(from) || frames_played >= self.min_frames(from, to)
+    }
+}
+
 #[derive(Asset, TypePath, Deserialize, Debug, Clone)]
 pub struct AnimationMapConfig {
     pub frame_duration: u64,
     pub animations: HashMap<String, AnimationIndices>,
+    // Optional per direction index ranges for a state, when a state+direction is present
+    // the sprite is not flipped. Assets without it fallback on `animations` with flip.
+    #[serde(default)]
+    pub directional: HashMap<String, HashMap<FacingDirection8, AnimationIndices>>,
+    // Triggers of each state, ex: "Reload": [(frame: 3, name: "mag_out"), (frame: 7, name: "mag_in")]
+    #[serde(default)]
+    pub events: HashMap<String, Vec<AnimationFrameTrigger>>,
+    #[serde(default)]
+    pub transitions: AnimationTransitions,
+}
+
+impl AnimationMapConfig {
+    // Return the indices for the state and if they come from a directional sheet
+    pub fn get_indices(&self, state: &str, direction: Option<&FacingDirection8>) -> Option<(&AnimationIndices, bool)> {
+        if let Some(direction) = direction {
+            if let Some(indices) = self.directional.get(state).and_then(|m| m.get(direction)) {
+                return Some((indices, true));
+            }
+        }
+        self.animations.get(state).map(|indices| (indices, false))
+    }
+
+    pub fn has_direction(&self, state: &str, direction: &FacingDirection8) -> bool {
+        self.directional.get(state).map_or(false, |m| m.contains_key(direction))
+    }
+
+    // Names of the triggers on a frame of the state
+    pub fn triggers_at<'a>(&'a self, state: &str, frame: usize) -> impl Iterator<Item = &'a str> {
+        self.events.get(state).into_iter().flatten()
+            .filter(move |trigger| trigger.frame == frame)
+            .map(|trigger| trigger.name.as_str())
+    }
+
+    // Where the trigger is in one loop of the state, between 0 and 1. For the gameplay that must
+    // happen on the frame without depending on the animation playing in the Update schedule,
+    // a reload of any length put the trigger at the same point as in the animation.
+    pub fn trigger_fraction(&self, state: &str, name: &str) -> Option<f32> {
+        let indices = self.animations.get(state)?;
+        let frame_count = indices.end.saturating_sub(indices.start) + 1;
+        self.events.get(state)?
+            .iter()
+            .find(|trigger| trigger.name == name)
+            .map(|trigger| (trigger.frame as f32 / frame_count as f32).min(1.0))
+    }
+}
+
+// Sent when an animation reach a frame with a trigger. Presentation only, it follow the
+// animation in the Update schedule and can fire for a frame that end up being rolled back.
+#[derive(Event, Debug, Clone)]
+pub struct AnimationFrameEvent {
+    pub entity: Entity,
+    pub state: String,
+    pub name: String,
 }
 
 // COMPONENT
@@ -75,11 +176,14 @@ pub struct LayerName {
     pub name: String
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct AnimatedLayer {}
 
-#[derive(Component)]
-pub struct ColoredLayer {}
+// Tint multiplied with the texture of a layer, like the color of the hair or the shirt
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ColoredLayer {
+    pub color: Color,
+}
 
 #[derive(Component, Clone)]
 pub struct ActiveLayers {
@@ -91,18 +195,33 @@ pub struct ActiveLayers {
 pub struct AnimationState(pub String);
 
 // Handles are loaded once, assume they don't change and don't need rollback/reflection
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct CharacterAnimationHandles {
     pub spritesheets: HashMap<String, Handle<SpriteSheetConfig>>,
     pub animations: Handle<AnimationMapConfig>,
     pub starting_index: usize
 }
 
-#[derive(Component)]
-struct AnimationTimer {
+#[derive(Component, Clone)]
+pub struct AnimationTimer {
     frame_timer: Timer,
 }
 
+// State on screen, follow the AnimationState with the transition rules. Presentation only,
+// the AnimationState stay the gameplay state and can flip every frame.
+#[derive(Component, Debug, Clone)]
+pub struct DisplayedAnimation {
+    pub state: String,
+    pub frames_played: u32,
+}
+
+// Copy of a layer showing the previous frame, fading out over the new one
+#[derive(Component)]
+struct FadingLayer {
+    timer: Timer,
+    alpha: f32,
+}
+
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FacingDirection {
     Left,
@@ -125,6 +244,59 @@ impl FacingDirection {
     
 }
 
+// tan(22.5°) scaled by 1000, boundary between a cardinal and a diagonal octant
+const TAN_22_5_MILLI: i64 = 414;
+// tan(67.5°) scaled by 1000
+const TAN_67_5_MILLI: i64 = 2414;
+
+// Optional 8 way facing, used by assets that provide directional sheets
+#[derive(Component, Reflect, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[reflect(Component, PartialEq)]
+pub enum FacingDirection8 {
+    N,
+    NE,
+    #[default]
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl FacingDirection8 {
+    // Compute the octant of an integer aim vector, only integer math so all peers agree.
+    // Return None for a null vector.
+    pub fn from_aim(x: i32, y: i32) -> Option<Self> {
+        if x == 0 && y == 0 {
+            return None;
+        }
+        let (ax, ay) = ((x as i64).abs(), (y as i64).abs());
+
+        // ay / ax compared to the tangent of the octant boundaries
+        let vertical = ay * 1000 > ax * TAN_67_5_MILLI;
+        let horizontal = ay * 1000 <= ax * TAN_22_5_MILLI;
+
+        Some(match (horizontal, vertical, x >= 0, y >= 0) {
+            (true, _, true, _) => FacingDirection8::E,
+            (true, _, false, _) => FacingDirection8::W,
+            (_, true, _, true) => FacingDirection8::N,
+            (_, true, _, false) => FacingDirection8::S,
+            (_, _, true, true) => FacingDirection8::NE,
+            (_, _, true, false) => FacingDirection8::SE,
+            (_, _, false, true) => FacingDirection8::NW,
+            (_, _, false, false) => FacingDirection8::SW,
+        })
+    }
+
+    pub fn to_facing_direction(&self) -> FacingDirection {
+        match self {
+            FacingDirection8::W | FacingDirection8::NW | FacingDirection8::SW => FacingDirection::Left,
+            _ => FacingDirection::Right,
+        }
+    }
+}
+
 // Bundle
 
 #[derive(Bundle)]
@@ -132,8 +304,10 @@ pub struct AnimationBundle {
     state: AnimationState,
     handles: CharacterAnimationHandles,
     timer: AnimationTimer,
+    displayed: DisplayedAnimation,
     active_layers: ActiveLayers,
     facing_direction: FacingDirection,
+    facing_direction_8: FacingDirection8,
 }
 
 impl AnimationBundle {
@@ -150,6 +324,7 @@ impl AnimationBundle {
             timer: AnimationTimer {
                 frame_timer: Timer::from_seconds(1., TimerMode::Repeating),
             },
+            displayed: DisplayedAnimation { state: "Idle".into(), frames_played: 0 },
             handles: CharacterAnimationHandles {
                 spritesheets,
                 animations,
@@ -158,7 +333,8 @@ impl AnimationBundle {
             active_layers: ActiveLayers {
                 layers: starting_layers,
             },
-            facing_direction: FacingDirection::default()
+            facing_direction: FacingDirection::default(),
+            facing_direction_8: FacingDirection8::default(),
         }
     }
 }
@@ -170,25 +346,57 @@ impl AnimationBundle {
 
 // Animates sprite based on AnimationState
 fn animate_sprite_system(
+    mut commands: Commands,
     time: Res<Time>,
     animation_configs: Res<Assets<AnimationMapConfig>>,
+    mut frame_events: EventWriter<AnimationFrameEvent>,
     mut query: Query<(
+        Entity,
         &Children,
         &CharacterAnimationHandles,
         &mut AnimationTimer,
+        &mut DisplayedAnimation,
         &AnimationState,
+        Option<&FacingDirection8>,
     )>,
-    mut query_sprites: Query<(&mut Sprite, &LayerName), With<AnimatedLayer>>,
+    mut query_sprites: Query<(&mut Sprite, &Transform, &LayerName), With<AnimatedLayer>>,
     
 ) {
-    for (childs, config_handles, mut timer, state) in query.iter_mut() {
+    for (entity, childs, config_handles, mut timer, mut displayed, requested, direction) in query.iter_mut() {
         if let Some(anim_config) = animation_configs.get(&config_handles.animations) {
+            let transitions = &anim_config.transitions;
+            if displayed.state != requested.0 && transitions.can_switch(&displayed.state, &requested.0, displayed.frames_played) {
+                if transitions.crossfade_ms > 0 {
+                    for child in childs.iter() {
+                        if let Ok((sprite, transform, _)) = query_sprites.get(*child) {
+                            let alpha = sprite.color.alpha();
+                            commands.spawn((
+                                sprite.clone(),
+                                transform.with_translation(transform.translation + Vec3::Z * 0.001),
+                                FadingLayer {
+                                    timer: Timer::new(bevy::utils::Duration::from_millis(transitions.crossfade_ms), TimerMode::Once),
+                                    alpha,
+                                },
+                            )).set_parent(entity);
+                        }
+                    }
+                }
+                displayed.state = requested.0.clone();
+                displayed.frames_played = 0;
+                // Show the first frame of the new state right away
+                timer.frame_timer.set_elapsed(timer.frame_timer.duration());
+            }
+            let state = AnimationState(displayed.state.clone());
+
             timer.frame_timer.tick(time.delta());
             if timer.frame_timer.just_finished() {
+                // Frame of the animation reached by the first animated layer, the layers move together
+                let mut reached_frame = None;
+                displayed.frames_played += 1;
                 for child in childs.iter() {
-                    if let Ok((mut sprite, _)) = query_sprites.get_mut(*child) {
+                    if let Ok((mut sprite, _, _)) = query_sprites.get_mut(*child) {
                         if let Some(atlas) = &mut sprite.texture_atlas {
-                            if let Some(indices) = anim_config.animations.get(&state.0) {
+                            if let Some((indices, _)) = anim_config.get_indices(&state.0, direction) {
                                 let start_index = indices.start;
                                 let end_index = indices.end;
                                 if atlas.index < start_index || atlas.index > end_index {
@@ -198,6 +406,7 @@ fn animate_sprite_system(
                                         % (end_index - start_index + 1)
                                         + start_index;
                                 }
+                                reached_frame.get_or_insert(atlas.index - start_index);
                             } else {
                                 atlas.index = anim_config
                                     .animations
@@ -207,11 +416,39 @@ fn animate_sprite_system(
                         }
                     }
                 }
+
+                if let Some(frame) = reached_frame {
+                    for name in anim_config.triggers_at(&state.0, frame) {
+                        frame_events.send(AnimationFrameEvent { entity, state: state.0.clone(), name: name.to_string() });
+                    }
+                }
             }
         }
     }
 }
 
+fn fade_layers_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut FadingLayer, &mut Sprite)>,
+) {
+    for (entity, mut fading, mut sprite) in query.iter_mut() {
+        fading.timer.tick(time.delta());
+        if fading.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        sprite.color.set_alpha(fading.alpha * (1.0 - fading.timer.fraction()));
+    }
+}
+
+// The tint is set once the layer is spawned, or changed
+fn apply_layer_colors_system(mut query: Query<(&ColoredLayer, &mut Sprite), Changed<ColoredLayer>>) {
+    for (layer, mut sprite) in query.iter_mut() {
+        sprite.color = layer.color;
+    }
+}
+
 // Updates animation timer duration if AnimationMapConfig reloads
 fn check_animation_config_reload_system(
     mut ev_asset: EventReader<AssetEvent<AnimationMapConfig>>,
@@ -315,12 +552,23 @@ fn character_visuals_update_system(
 // SYSTEM THAT RUN ON THE BEVY SCHEDULE FOR SYNCH
 
 pub fn set_sprite_flip(
-    query: Query<(&Children, &FacingDirection), With<Rollback>>,
+    animation_configs: Res<Assets<AnimationMapConfig>>,
+    query: Query<(&Children, &FacingDirection, Option<&FacingDirection8>, &DisplayedAnimation, &CharacterAnimationHandles), With<Rollback>>,
     mut sprite_query: Query<(&mut Sprite)>,
 ) {
-    for (childrens, direction) in query.iter() {
+    for (childrens, direction, direction_8, displayed, handles) in query.iter() {
+        // Directional sheets have their own frames for the left side, no flip needed
+        let directional = match (direction_8, animation_configs.get(&handles.animations)) {
+            (Some(direction_8), Some(config)) => config.has_direction(&displayed.state, direction_8),
+            _ => false,
+        };
+
         for child in childrens.iter() {
             if let Ok(mut sprite) = sprite_query.get_mut(*child) {
+                if directional {
+                    sprite.flip_x = false;
+                    continue;
+                }
                 match direction {
                     FacingDirection::Left => {
                         sprite.flip_x = true;
@@ -392,9 +640,11 @@ impl Plugin for D2AnimationPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(RonAssetPlugin::<SpriteSheetConfig>::new(&["ron"]));
         app.add_plugins(RonAssetPlugin::<AnimationMapConfig>::new(&["ron"]));
+        app.add_event::<AnimationFrameEvent>();
         
         app
             .rollback_component_with_reflect::<AnimationState>()
+            .rollback_component_with_copy::<FacingDirection8>()
             .rollback_component_with_clone::<LayerName>()
             .rollback_component_with_clone::<ActiveLayers>();
 
@@ -404,7 +654,92 @@ impl Plugin for D2AnimationPlugin {
                 character_visuals_update_system,
                 animate_sprite_system.after(character_visuals_update_system),
                 check_animation_config_reload_system.after(animate_sprite_system),
+                fade_layers_system,
+                apply_layer_colors_system,
             )
         );
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_aim_give_the_eight_sectors() {
+        let cases = [
+            ((10, 0), FacingDirection8::E),
+            ((10, 10), FacingDirection8::NE),
+            ((0, 10), FacingDirection8::N),
+            ((-10, 10), FacingDirection8::NW),
+            ((-10, 0), FacingDirection8::W),
+            ((-10, -10), FacingDirection8::SW),
+            ((0, -10), FacingDirection8::S),
+            ((10, -10), FacingDirection8::SE),
+        ];
+        for ((x, y), expected) in cases {
+            assert_eq!(FacingDirection8::from_aim(x, y), Some(expected), "aim ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn test_from_aim_boundaries() {
+        // On the 22.5 degrees boundary it is still horizontal, just above it is diagonal
+        assert_eq!(FacingDirection8::from_aim(1000, 414), Some(FacingDirection8::E));
+        assert_eq!(FacingDirection8::from_aim(1000, 415), Some(FacingDirection8::NE));
+        assert_eq!(FacingDirection8::from_aim(-1000, -414), Some(FacingDirection8::W));
+        assert_eq!(FacingDirection8::from_aim(-1000, -415), Some(FacingDirection8::SW));
+        // On the 67.5 degrees boundary it is still diagonal, just above it is vertical
+        assert_eq!(FacingDirection8::from_aim(1000, 2414), Some(FacingDirection8::NE));
+        assert_eq!(FacingDirection8::from_aim(1000, 2415), Some(FacingDirection8::N));
+        assert_eq!(FacingDirection8::from_aim(-1000, -2414), Some(FacingDirection8::SW));
+        assert_eq!(FacingDirection8::from_aim(-1000, -2415), Some(FacingDirection8::S));
+    }
+
+    #[test]
+    fn test_from_aim_zero_and_extremes() {
+        assert_eq!(FacingDirection8::from_aim(0, 0), None);
+        // No overflow, the comparison is done on i64
+        assert_eq!(FacingDirection8::from_aim(i32::MIN, 0), Some(FacingDirection8::W));
+        assert_eq!(FacingDirection8::from_aim(0, i32::MIN), Some(FacingDirection8::S));
+        assert_eq!(FacingDirection8::from_aim(i32::MAX, i32::MAX), Some(FacingDirection8::NE));
+    }
+
+    #[test]
+    fn test_to_facing_direction_follow_the_side() {
+        assert_eq!(FacingDirection8::NW.to_facing_direction(), FacingDirection::Left);
+        assert_eq!(FacingDirection8::SE.to_facing_direction(), FacingDirection::Right);
+        // Straight up or down keep the right side
+        assert_eq!(FacingDirection8::N.to_facing_direction(), FacingDirection::Right);
+    }
+
+    fn transitions() -> AnimationTransitions {
+        let rule = |from: &str, to: &str, frames| TransitionRule { from: from.to_string(), to: to.to_string(), frames };
+        AnimationTransitions {
+            priorities: [("Death", 5), ("Hit", 4), ("Run", 1)].into_iter().map(|(state, priority)| (state.to_string(), priority)).collect(),
+            min_frames: vec![rule("Run", "Idle", 4), rule("Run", "*", 3), rule("*", "Idle", 2), rule("*", "*", 1)],
+            crossfade_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_min_frames_use_the_most_specific_rule() {
+        let transitions = transitions();
+        assert_eq!(transitions.min_frames("Run", "Idle"), 4);
+        assert_eq!(transitions.min_frames("Run", "Reload"), 3);
+        assert_eq!(transitions.min_frames("Reload", "Idle"), 2);
+        assert_eq!(transitions.min_frames("Reload", "Melee"), 1);
+        assert_eq!(AnimationTransitions::default().min_frames("Run", "Idle"), 0);
+    }
+
+    #[test]
+    fn test_higher_priority_interrupt_right_away() {
+        let transitions = transitions();
+        assert!(transitions.can_switch("Run", "Hit", 0));
+        assert!(!transitions.can_switch("Run", "Idle", 3));
+        assert!(transitions.can_switch("Run", "Idle", 4));
+        // A lower priority wait its frames even from a higher state
+        assert!(!transitions.can_switch("Hit", "Run", 0));
+        assert!(transitions.can_switch("Hit", "Run", 1));
+    }
+}
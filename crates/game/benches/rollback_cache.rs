@@ -0,0 +1,110 @@
+#![feature(test)]
+extern crate test;
+
+// Cost of replaying the enemy AI during a rollback, with and without the frame caches.
+// Run with `cargo bench -p game`, each iteration simulate a rollback that replay
+// ROLLBACK_FRAMES frames where no enemy got a different input.
+
+use bevy::prelude::*;
+use game::character::enemy::ai::pathing::{compute_separations, PathfindingConfig};
+use map::game::nav::{NavGrid, NavObstacle};
+use test::{black_box, Bencher};
+use utils::cache::{CacheKey, FrameCache};
+
+const ENEMY_COUNT: u32 = 64;
+const ROLLBACK_FRAMES: u32 = 8;
+
+fn walls() -> Vec<NavObstacle> {
+    (0..8)
+        .map(|i| NavObstacle::Rect {
+            center: Vec2::new(-700.0 + i as f32 * 200.0, if i % 2 == 0 { 100.0 } else { -100.0 }),
+            half_size: Vec2::new(10.0, 400.0),
+        })
+        .collect()
+}
+
+fn enemies() -> Vec<(Entity, Vec2)> {
+    (0..ENEMY_COUNT)
+        .map(|i| (Entity::from_raw(i), Vec2::new(-800.0 + (i % 8) as f32 * 25.0, -300.0 + (i / 8) as f32 * 25.0)))
+        .collect()
+}
+
+fn path_key(grid: &NavGrid, position: Vec2, target: Vec2) -> CacheKey {
+    CacheKey::default()
+        .with_usize(grid.obstacle_count)
+        .with_f32(grid.cell_size)
+        .with_vec2(position)
+        .with_vec2(target)
+}
+
+#[bench]
+fn bench_paths_resimulated(b: &mut Bencher) {
+    let config = PathfindingConfig::default();
+    let grid = NavGrid::bake(&walls(), config.node_size, config.agent_radius);
+    let enemies = enemies();
+    let target = Vec2::new(800.0, 0.0);
+
+    b.iter(|| {
+        for _frame in 0..ROLLBACK_FRAMES {
+            for (_, position) in &enemies {
+                black_box(grid.find_path(*position, target, config.max_iterations));
+            }
+        }
+    });
+}
+
+#[bench]
+fn bench_paths_cached(b: &mut Bencher) {
+    let config = PathfindingConfig::default();
+    let grid = NavGrid::bake(&walls(), config.node_size, config.agent_radius);
+    let enemies = enemies();
+    let target = Vec2::new(800.0, 0.0);
+    let mut caches: Vec<FrameCache<Option<Vec<Vec2>>>> = enemies.iter().map(|_| FrameCache::default()).collect();
+
+    // First simulation of the frames fill the caches
+    for frame in 0..ROLLBACK_FRAMES {
+        for ((_, position), cache) in enemies.iter().zip(caches.iter_mut()) {
+            cache.insert(frame, path_key(&grid, *position, target), grid.find_path(*position, target, config.max_iterations));
+        }
+    }
+
+    b.iter(|| {
+        for frame in 0..ROLLBACK_FRAMES {
+            for ((_, position), cache) in enemies.iter().zip(caches.iter_mut()) {
+                let key = path_key(&grid, *position, target);
+                black_box(cache.get_or_insert_with(frame, key, || grid.find_path(*position, target, config.max_iterations)));
+            }
+        }
+    });
+}
+
+#[bench]
+fn bench_separation_resimulated(b: &mut Bencher) {
+    let config = PathfindingConfig::default();
+    let enemies = enemies();
+
+    b.iter(|| {
+        for _frame in 0..ROLLBACK_FRAMES {
+            black_box(compute_separations(&enemies, &config));
+        }
+    });
+}
+
+#[bench]
+fn bench_separation_cached(b: &mut Bencher) {
+    let config = PathfindingConfig::default();
+    let enemies = enemies();
+    let key = enemies.iter().fold(CacheKey::default(), |key, (entity, position)| key.with_u32(entity.index()).with_vec2(*position));
+    let mut cache = FrameCache::default();
+    for frame in 0..ROLLBACK_FRAMES {
+        cache.insert(frame, key, compute_separations(&enemies, &config));
+    }
+
+    b.iter(|| {
+        for frame in 0..ROLLBACK_FRAMES {
+            // The key is rebuilt every frame like in move_enemies
+            let key = enemies.iter().fold(CacheKey::default(), |key, (entity, position)| key.with_u32(entity.index()).with_vec2(*position));
+            black_box(cache.get_or_insert_with(frame, key, || compute_separations(&enemies, &config)));
+        }
+    });
+}
@@ -0,0 +1,185 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::ConfirmedFrameCount;
+use bevy_kira_audio::prelude::*;
+use map::game::entity::map::{ambient::{AmbientZoneComponent, AmbientZoneConfig}, zone::ZoneRect};
+
+use crate::{character::player::{LocalPlayer, Player}, frame::{confirmed_frame, FrameCount}, plugins::AppState};
+
+use super::mixer::{AudioMixer, MixerBus};
+
+// Ambient loops of the map zones. The zone is picked from the position of the local
+// player at the confirmed frame so a misprediction never swap the ambience back and
+// forth, the tracks are crossfaded on their own channel of the mixer.
+
+#[derive(Resource)]
+pub struct AmbientChannel;
+
+#[derive(Resource, Clone, Debug)]
+pub struct AmbientSettings {
+    pub enabled: bool,
+    // Volume of the channel, the zones are relative to it
+    pub volume: f32,
+    pub crossfade_seconds: f32,
+}
+
+impl Default for AmbientSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 0.5,
+            crossfade_seconds: 1.5,
+        }
+    }
+}
+
+// Looping track for each ambience name of the zones
+#[derive(Resource, Clone, Debug)]
+pub struct AmbientSounds(pub HashMap<String, String>);
+
+impl Default for AmbientSounds {
+    fn default() -> Self {
+        Self(HashMap::from([
+            ("wind".to_string(), "sounds/ambient/wind.wav".to_string()),
+            ("hum".to_string(), "sounds/ambient/hum.wav".to_string()),
+            ("drip".to_string(), "sounds/ambient/drip.wav".to_string()),
+        ]))
+    }
+}
+
+#[derive(Resource, Default)]
+struct AmbientPlayback {
+    // Positions of the local player by frame, kept until their frame is confirmed
+    positions: VecDeque<(u32, Vec2)>,
+    // Ambience and volume of the zone heard, the instance of its loop
+    current: Option<(String, f32, Handle<AudioInstance>)>,
+}
+
+
+pub fn spawn_ambient_zone(commands: &mut Commands, position: Vec2, config: AmbientZoneConfig) -> Entity {
+    commands.spawn((
+        Transform::from_translation(position.extend(0.0)),
+        AmbientZoneComponent { config },
+    )).id()
+}
+
+fn crossfade(settings: &AmbientSettings) -> AudioTween {
+    AudioTween::linear(Duration::from_secs_f32(settings.crossfade_seconds.max(0.0)))
+}
+
+
+// SYSTEMS
+
+fn record_local_position(
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    mut playback: ResMut<AmbientPlayback>,
+    player_query: Query<&Transform, (With<LocalPlayer>, With<Player>)>,
+) {
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+
+    // The frames resimulated by a rollback replace what was recorded for them
+    while playback.positions.back().map_or(false, |(f, _)| *f >= frame.frame) {
+        playback.positions.pop_back();
+    }
+    playback.positions.push_back((frame.frame, transform.translation.truncate()));
+
+    // Only the latest confirmed position is still needed
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    while playback.positions.get(1).map_or(false, |(f, _)| *f <= confirmed_frame) {
+        playback.positions.pop_front();
+    }
+}
+
+fn crossfade_ambient_zones(
+    settings: Res<AmbientSettings>,
+    sounds: Res<AmbientSounds>,
+    asset_server: Res<AssetServer>,
+    channel: Res<AudioChannel<AmbientChannel>>,
+    mut mixer: ResMut<AudioMixer>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    mut playback: ResMut<AmbientPlayback>,
+    zone_query: Query<(&GlobalTransform, &AmbientZoneComponent)>,
+) {
+    let wanted = match playback.positions.front() {
+        Some((_, position)) if settings.enabled => ZoneRect::smallest_containing(zone_query.iter().map(|(transform, zone)| (zone.config.rect(transform.translation().truncate()), &zone.config)), *position)
+            .filter(|zone| sounds.0.contains_key(&zone.ambience))
+            .map(|zone| (zone.ambience.clone(), zone.volume)),
+        _ => None,
+    };
+
+    match (&mut playback.current, &wanted) {
+        (Some((ambience, volume, handle)), Some((wanted_ambience, wanted_volume))) if ambience == wanted_ambience => {
+            if *volume != *wanted_volume {
+                if let Some(instance) = instances.get_mut(handle.id()) {
+                    instance.set_volume(*wanted_volume as f64, crossfade(&settings));
+                }
+                *volume = *wanted_volume;
+            }
+            return;
+        }
+        (None, None) => return,
+        _ => {}
+    }
+
+    if let Some((_, _, handle)) = playback.current.take() {
+        if let Some(instance) = instances.get_mut(handle.id()) {
+            instance.stop(crossfade(&settings));
+        }
+    }
+    if let Some((ambience, volume)) = wanted {
+        mixer.set_volume(MixerBus::Ambient, settings.volume);
+        let handle = channel.play(asset_server.load(sounds.0[&ambience].as_str()))
+            .looped()
+            .with_volume(volume as f64)
+            .fade_in(crossfade(&settings))
+            .handle();
+        playback.current = Some((ambience, volume, handle));
+    }
+}
+
+fn stop_ambient(
+    settings: Res<AmbientSettings>,
+    channel: Res<AudioChannel<AmbientChannel>>,
+    mut playback: ResMut<AmbientPlayback>,
+) {
+    channel.stop().fade_out(crossfade(&settings));
+    *playback = AmbientPlayback::default();
+}
+
+
+#[derive(Default)]
+pub struct AmbientAudioPlugin;
+
+impl Plugin for AmbientAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_channel::<AmbientChannel>();
+        app.init_resource::<AmbientSettings>();
+        app.init_resource::<AmbientSounds>();
+        app.init_resource::<AmbientPlayback>();
+        app.add_systems(Update, (
+            record_local_position,
+            crossfade_ambient_zones.after(record_local_position),
+        ).run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), stop_ambient);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ambient_sounds_exist() {
+        for track in AmbientSounds::default().0.values() {
+            let path = format!("{}/../../assets/{}", env!("CARGO_MANIFEST_DIR"), track);
+            assert!(std::path::Path::new(&path).exists(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_kira_audio::prelude::*;
+use rand::Rng;
+
+use crate::{character::{enemy::{attack::{AttackPhase, EnemyAttackState}, Enemy}, player::Player}, plugins::AppState};
+
+use super::AudioListener;
+
+// Vocalizations of the enemies, only presentation: they follow what the simulation show
+// on screen, a rollback that cancel an attack may leave its grunt playing. The idle
+// intervals are random on each peer without touching the rollback RNG streams. Only a few barks
+// play at once, the closest to the listener and the attacks first, so a horde stay a
+// few voices instead of a wall of noise.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BarkKind {
+    Attack,
+    Aggro,
+    Idle,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct BarkSettings {
+    pub max_simultaneous: usize,
+    // Barks farther than this from the listener are not played
+    pub hearing_distance: f32,
+    // A player closer than this make the enemy scream, it calms down past twice the distance
+    pub aggro_distance: f32,
+    // Seconds between two groans of an idle enemy
+    pub idle_interval: (f32, f32),
+    pub volume: f32,
+}
+
+impl Default for BarkSettings {
+    fn default() -> Self {
+        Self {
+            max_simultaneous: 4,
+            hearing_distance: 600.0,
+            aggro_distance: 250.0,
+            idle_interval: (4.0, 12.0),
+            volume: 0.8,
+        }
+    }
+}
+
+// Tracks of each bark, one picked at random each time
+#[derive(Resource, Clone, Debug)]
+pub struct BarkSounds(pub HashMap<BarkKind, Vec<String>>);
+
+impl Default for BarkSounds {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (BarkKind::Idle, vec!["sounds/enemy/groan-1.wav".to_string(), "sounds/enemy/groan-2.wav".to_string()]),
+            (BarkKind::Aggro, vec!["sounds/enemy/scream.wav".to_string()]),
+            (BarkKind::Attack, vec!["sounds/enemy/grunt.wav".to_string()]),
+        ]))
+    }
+}
+
+// What the enemy was doing the last time it was looked at
+#[derive(Component, Debug, Default)]
+pub struct EnemyBarkState {
+    aggro: bool,
+    phase: AttackPhase,
+    cooldown_start: Option<u32>,
+    // Time for the next idle groan
+    next_idle: f32,
+}
+
+// Instances of the barks playing
+#[derive(Resource, Default)]
+struct ActiveBarks(Vec<Handle<AudioInstance>>);
+
+
+fn random_idle_delay(settings: &BarkSettings) -> f32 {
+    let (min, max) = settings.idle_interval;
+    if max <= min {
+        return min;
+    }
+    rand::thread_rng().gen_range(min..max)
+}
+
+// Bark of the enemy for this frame, the state is updated even when none is played
+fn next_bark(state: &mut EnemyBarkState, attack: &EnemyAttackState, nearest_player: f32, now: f32, settings: &BarkSettings) -> Option<BarkKind> {
+    let cooldown_start = attack.cooldown.map(|cooldown| cooldown.start_frame);
+    let started_attack = match (state.phase, attack.phase) {
+        (AttackPhase::Idle, AttackPhase::Idle) => cooldown_start != state.cooldown_start && cooldown_start.is_some(),
+        (AttackPhase::Idle, _) => true,
+        _ => false,
+    };
+    state.phase = attack.phase;
+    state.cooldown_start = cooldown_start;
+
+    let was_aggro = state.aggro;
+    if nearest_player < settings.aggro_distance {
+        state.aggro = true;
+    } else if nearest_player > settings.aggro_distance * 2.0 {
+        state.aggro = false;
+    }
+
+    if started_attack {
+        return Some(BarkKind::Attack);
+    }
+    if state.aggro && !was_aggro {
+        return Some(BarkKind::Aggro);
+    }
+    if !state.aggro && now >= state.next_idle {
+        state.next_idle = now + random_idle_delay(settings);
+        return Some(BarkKind::Idle);
+    }
+    None
+}
+
+
+// SYSTEMS
+
+fn add_bark_state(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<BarkSettings>,
+    enemy_query: Query<Entity, (With<Enemy>, Without<EnemyBarkState>)>,
+) {
+    for entity in enemy_query.iter() {
+        // Spread the first groans of a wave
+        let next_idle = time.elapsed_secs() + random_idle_delay(&settings);
+        commands.entity(entity).insert(EnemyBarkState { next_idle, ..default() });
+    }
+}
+
+fn play_enemy_barks(
+    time: Res<Time>,
+    settings: Res<BarkSettings>,
+    sounds: Res<BarkSounds>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    instances: Res<Assets<AudioInstance>>,
+    mut active: ResMut<ActiveBarks>,
+    listener_query: Query<&Transform, With<AudioListener>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut enemy_query: Query<(Entity, &Transform, &EnemyAttackState, &mut EnemyBarkState, &mut SpatialAudioEmitter), With<Enemy>>,
+) {
+    active.0.retain(|handle| instances.get(handle.id()).map_or(false, |instance| instance.state() != PlaybackState::Stopped));
+
+    let now = time.elapsed_secs();
+    let listener = listener_query.get_single().ok().map(|transform| transform.translation.truncate());
+    let players: Vec<Vec2> = player_query.iter().map(|transform| transform.translation.truncate()).collect();
+
+    let mut barks: Vec<(BarkKind, f32, Entity)> = vec![];
+    for (entity, transform, attack, mut state, _) in enemy_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let nearest_player = players.iter().map(|player| player.distance(position)).fold(f32::MAX, f32::min);
+
+        let Some(kind) = next_bark(&mut state, attack, nearest_player, now, &settings) else {
+            continue;
+        };
+        let distance = listener.map_or(0.0, |listener| listener.distance(position));
+        if distance <= settings.hearing_distance {
+            barks.push((kind, distance, entity));
+        }
+    }
+
+    // The attacks before the screams before the groans, the closest first
+    barks.sort_by(|(a, a_distance, a_entity), (b, b_distance, b_entity)| a.cmp(b).then(a_distance.total_cmp(b_distance)).then(a_entity.cmp(b_entity)));
+
+    let free = settings.max_simultaneous.saturating_sub(active.0.len());
+    let mut rng = rand::thread_rng();
+    for (kind, _, entity) in barks.into_iter().take(free) {
+        let Some(paths) = sounds.0.get(&kind).filter(|paths| !paths.is_empty()) else {
+            continue;
+        };
+        let Ok((.., mut emitter)) = enemy_query.get_mut(entity) else {
+            continue;
+        };
+        let path = &paths[rng.gen_range(0..paths.len())];
+        let instance = audio.play(asset_server.load(path.as_str())).with_volume(settings.volume as f64).handle();
+        emitter.instances.push(instance.clone());
+        active.0.push(instance);
+    }
+}
+
+fn clear_barks(mut active: ResMut<ActiveBarks>) {
+    active.0.clear();
+}
+
+
+#[derive(Default)]
+pub struct EnemyBarkPlugin;
+
+impl Plugin for EnemyBarkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BarkSettings>();
+        app.init_resource::<BarkSounds>();
+        app.init_resource::<ActiveBarks>();
+        app.add_systems(Update, (
+            add_bark_state,
+            play_enemy_barks.after(add_bark_state),
+        ).run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), clear_barks);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bark_sounds_exist() {
+        for tracks in BarkSounds::default().0.values() {
+            for track in tracks {
+                let path = format!("{}/../../assets/{}", env!("CARGO_MANIFEST_DIR"), track);
+                assert!(std::path::Path::new(&path).exists(), "{}", path);
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+use bevy_kira_audio::prelude::*;
+use utils::frame::SimulationConfig;
+
+use crate::{bullet_time::{BulletTime, BulletTimeConfig}, frame::{confirmed_frame, FrameCount}, plugins::AppState, weapons::{near_miss::{confirmed_position, NearMissEvent}, Bullet}};
+
+use super::AudioListener;
+
+// Whizz of the fast projectiles passing by the listener, only presentation. A projectile
+// start to whizz with its near miss of a local player, the pitch of its sound then follow
+// its speed toward the listener and drop a bit with the distance. The sound stay on the
+// projectile so the spatial audio pan it as it pass. The pitch use the position of the
+// projectile on the confirmed frame, like its near miss.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct DopplerSettings {
+    pub enabled: bool,
+    pub whizz_sound: String,
+    // World units per second, lower than the real one so the effect is heard at the speed of the bullets
+    pub speed_of_sound: f32,
+    // Largest change of the pitch, up or down
+    pub max_pitch_shift: f32,
+    // Pitch lost at the drop distance, a far projectile sound duller
+    pub distance_pitch_drop: f32,
+    pub pitch_drop_distance: f32,
+    pub max_simultaneous: usize,
+    pub volume: f32,
+}
+
+impl Default for DopplerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            whizz_sound: "sounds/whizz.wav".to_string(),
+            speed_of_sound: 3000.0,
+            max_pitch_shift: 0.35,
+            distance_pitch_drop: 0.1,
+            pitch_drop_distance: 120.0,
+            max_simultaneous: 4,
+            volume: 0.6,
+        }
+    }
+}
+
+// Sound of a projectile passing by, its pitch updated every frame
+#[derive(Component)]
+struct Whizz(Handle<AudioInstance>);
+
+#[derive(Resource, Default)]
+struct DopplerState {
+    // To get its velocity, the listener is moved by the presentation
+    last_listener: Option<Vec2>,
+    listener_velocity: Vec2,
+}
+
+/// Pitch of a sound moving at `velocity` heard by a listener moving at `listener_velocity`,
+/// all in world units per second. Above 1 when they get closer.
+pub fn doppler_pitch(position: Vec2, velocity: Vec2, listener: Vec2, listener_velocity: Vec2, settings: &DopplerSettings) -> f32 {
+    let to_listener = (listener - position).normalize_or_zero();
+    let approach = velocity.dot(to_listener);
+    let listener_approach = -listener_velocity.dot(to_listener);
+    let speed = settings.speed_of_sound.max(1.0);
+    let pitch = (speed + listener_approach) / (speed - approach).max(speed * 0.1);
+
+    let distance = position.distance(listener) / settings.pitch_drop_distance.max(1.0);
+    let pitch = pitch - settings.distance_pitch_drop * distance.min(1.0);
+    pitch.clamp(1.0 - settings.max_pitch_shift, 1.0 + settings.max_pitch_shift)
+}
+
+
+// SYSTEMS
+
+fn track_listener_velocity(
+    time: Res<Time>,
+    mut state: ResMut<DopplerState>,
+    listener_query: Query<&GlobalTransform, With<AudioListener>>,
+) {
+    let Ok(transform) = listener_query.get_single() else {
+        return;
+    };
+    let position = transform.translation().truncate();
+    if let Some(last) = state.last_listener.replace(position) {
+        if time.delta_secs() > 0.0 {
+            state.listener_velocity = (position - last) / time.delta_secs();
+        }
+    }
+}
+
+fn play_projectile_whizz(
+    mut commands: Commands,
+    settings: Res<DopplerSettings>,
+    simulation: Res<SimulationConfig>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    bullet_time: Option<Res<BulletTime>>,
+    bullet_time_config: Option<Res<BulletTimeConfig>>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    state: Res<DopplerState>,
+    mut near_misses: EventReader<NearMissEvent>,
+    listener_query: Query<&GlobalTransform, With<AudioListener>>,
+    bullet_query: Query<(Entity, &Bullet, &GlobalTransform, Option<&Whizz>)>,
+) {
+    let missed: Vec<Entity> = near_misses.read().map(|event| event.bullet).collect();
+    if !settings.enabled {
+        return;
+    }
+    let Ok(listener) = listener_query.get_single() else {
+        return;
+    };
+    let listener = listener.translation().truncate();
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    let frames_ahead = frame.frame.saturating_sub(confirmed_frame);
+
+    let base_pitch = match (bullet_time, bullet_time_config) {
+        (Some(bullet_time), Some(config)) => bullet_time.pitch(&config),
+        _ => 1.0,
+    };
+    // From units per frame to units per second
+    let fps = simulation.rollback_fps() as f32;
+
+    let mut playing = bullet_query.iter().filter(|(.., whizz)| whizz.is_some()).count();
+    for (entity, bullet, transform, opt_whizz) in bullet_query.iter() {
+        // A ricochet bouncing in the predicted frames keep its last pitch
+        let Some(position) = confirmed_position(bullet, transform.translation().truncate(), frames_ahead) else {
+            continue;
+        };
+        let velocity = bullet.velocity * fps;
+        let pitch = doppler_pitch(position, velocity, listener, state.listener_velocity, &settings) as f64 * base_pitch;
+
+        if let Some(whizz) = opt_whizz {
+            if let Some(instance) = instances.get_mut(&whizz.0) {
+                instance.set_playback_rate(pitch, AudioTween::default());
+            }
+            continue;
+        }
+
+        if !missed.contains(&entity) || playing >= settings.max_simultaneous {
+            continue;
+        }
+        playing += 1;
+
+        let instance = audio.play(asset_server.load(settings.whizz_sound.as_str()))
+            .with_volume(settings.volume as f64)
+            .with_playback_rate(pitch)
+            .handle();
+        commands.entity(entity).insert((Whizz(instance.clone()), SpatialAudioEmitter { instances: vec![instance] }));
+    }
+}
+
+fn clear_doppler_state(mut state: ResMut<DopplerState>) {
+    *state = DopplerState::default();
+}
+
+
+#[derive(Default)]
+pub struct DopplerAudioPlugin;
+
+impl Plugin for DopplerAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DopplerSettings>();
+        app.register_type::<DopplerSettings>();
+        app.init_resource::<DopplerState>();
+        app.add_systems(Update, (
+            track_listener_velocity,
+            play_projectile_whizz.after(track_listener_velocity),
+        ).run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), clear_doppler_state);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> DopplerSettings {
+        DopplerSettings { distance_pitch_drop: 0.0, ..default() }
+    }
+
+    #[test]
+    fn test_pitch_up_when_coming_and_down_when_leaving() {
+        let settings = settings();
+        let coming = doppler_pitch(Vec2::ZERO, Vec2::new(1000.0, 0.0), Vec2::new(50.0, 0.0), Vec2::ZERO, &settings);
+        let leaving = doppler_pitch(Vec2::ZERO, Vec2::new(-1000.0, 0.0), Vec2::new(50.0, 0.0), Vec2::ZERO, &settings);
+        // 3000 / 2000 clamped to the largest shift, 3000 / 4000
+        assert_eq!(coming, 1.0 + settings.max_pitch_shift);
+        assert!((leaving - 0.75).abs() < 1e-4);
+        let slow = doppler_pitch(Vec2::ZERO, Vec2::new(300.0, 0.0), Vec2::new(50.0, 0.0), Vec2::ZERO, &settings);
+        assert!((slow - 3000.0 / 2700.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_pitch_unchanged_when_passing_across() {
+        let settings = settings();
+        let pitch = doppler_pitch(Vec2::ZERO, Vec2::new(0.0, 1000.0), Vec2::new(50.0, 0.0), Vec2::ZERO, &settings);
+        assert!((pitch - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_pitch_clamped_and_listener_moving() {
+        let settings = settings();
+        // Faster than the sound, the division is bounded and the pitch clamped
+        let pitch = doppler_pitch(Vec2::ZERO, Vec2::new(10_000.0, 0.0), Vec2::new(50.0, 0.0), Vec2::ZERO, &settings);
+        assert_eq!(pitch, 1.0 + settings.max_pitch_shift);
+        // The listener running to a still sound, (3000 + 300) / 3000
+        let pitch = doppler_pitch(Vec2::ZERO, Vec2::ZERO, Vec2::new(50.0, 0.0), Vec2::new(-300.0, 0.0), &settings);
+        assert!((pitch - 1.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_pitch_drop_with_the_distance() {
+        let settings = DopplerSettings::default();
+        let near = doppler_pitch(Vec2::ZERO, Vec2::ZERO, Vec2::new(12.0, 0.0), Vec2::ZERO, &settings);
+        let far = doppler_pitch(Vec2::ZERO, Vec2::ZERO, Vec2::new(500.0, 0.0), Vec2::ZERO, &settings);
+        assert!((near - 0.99).abs() < 1e-4);
+        // Past the drop distance it doesn't drop more
+        assert!((far - 0.9).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_whizz_sound_exists() {
+        let path = format!("{}/../../assets/{}", env!("CARGO_MANIFEST_DIR"), DopplerSettings::default().whizz_sound);
+        assert!(std::path::Path::new(&path).exists(), "{}", path);
+    }
+}
@@ -0,0 +1,249 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+use bevy_kira_audio::prelude::*;
+
+use crate::{character::{health::Health, player::{LocalPlayer, Player}}, frame::{confirmed_frame, FrameCount}, plugins::AppState};
+
+use super::mixer::{AudioMixer, MixerBus};
+
+// Audio of the local player at low health. Under the threshold a heartbeat loop start on
+// its own channel, the other channels are ducked and muffled by the mixer, and all of it
+// is released once healed. Read from the health at the confirmed frame, a predicted hit
+// that never happened doesn't start the heart.
+
+#[derive(Resource)]
+pub struct HeartbeatChannel;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct LowHealthAudioSettings {
+    pub enabled: bool,
+    // Part of the max health under which the heart is heard
+    pub threshold: f32,
+    // Part to heal back over before it is released, higher than the threshold so
+    // regeneration around it doesn't start and stop the loop
+    pub release_threshold: f32,
+    pub heartbeat: String,
+    pub heartbeat_volume: f32,
+    // Volume left to the other channels
+    pub duck: f32,
+    pub muffle: f32,
+    pub attack_seconds: f32,
+    pub release_seconds: f32,
+}
+
+impl Default for LowHealthAudioSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.3,
+            release_threshold: 0.4,
+            heartbeat: "sounds/heartbeat.wav".to_string(),
+            heartbeat_volume: 0.8,
+            duck: 0.75,
+            muffle: 0.7,
+            attack_seconds: 0.4,
+            release_seconds: 1.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LowHealthState {
+    #[default]
+    Healthy,
+    Critical,
+    // Healed or dead, the effect fade out
+    Releasing,
+}
+
+impl LowHealthState {
+    // Next state for the part of health left, none when there is no local player alive
+    fn next(self, ratio: Option<f32>, settings: &LowHealthAudioSettings) -> LowHealthState {
+        let Some(ratio) = ratio.filter(|_| settings.enabled) else {
+            return if self == LowHealthState::Healthy { self } else { LowHealthState::Releasing };
+        };
+        match self {
+            _ if ratio < settings.threshold => LowHealthState::Critical,
+            LowHealthState::Critical if ratio < settings.release_threshold => LowHealthState::Critical,
+            LowHealthState::Critical => LowHealthState::Releasing,
+            state => state,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct LowHealthPlayback {
+    // Health ratio of the local player by frame, kept until their frame is confirmed
+    ratios: VecDeque<(u32, Option<f32>)>,
+    state: LowHealthState,
+    // Strength of the effect, from 0 to 1
+    level: f32,
+    heartbeat: Option<Handle<AudioInstance>>,
+}
+
+fn health_ratio(health: &Health) -> Option<f32> {
+    (health.current > 0.0 && health.max > 0.0).then(|| health.current / health.max)
+}
+
+
+// SYSTEMS
+
+fn record_local_health(
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    mut playback: ResMut<LowHealthPlayback>,
+    player_query: Query<&Health, (With<LocalPlayer>, With<Player>)>,
+) {
+    let ratio = player_query.get_single().ok().and_then(health_ratio);
+
+    // The frames resimulated by a rollback replace what was recorded for them
+    while playback.ratios.back().map_or(false, |(f, _)| *f >= frame.frame) {
+        playback.ratios.pop_back();
+    }
+    playback.ratios.push_back((frame.frame, ratio));
+
+    // Only the latest confirmed health is still needed
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    while playback.ratios.get(1).map_or(false, |(f, _)| *f <= confirmed_frame) {
+        playback.ratios.pop_front();
+    }
+}
+
+fn update_low_health_audio(
+    time: Res<Time>,
+    settings: Res<LowHealthAudioSettings>,
+    asset_server: Res<AssetServer>,
+    channel: Res<AudioChannel<HeartbeatChannel>>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    mut mixer: ResMut<AudioMixer>,
+    mut playback: ResMut<LowHealthPlayback>,
+) {
+    let ratio = playback.ratios.front().and_then(|(_, ratio)| *ratio);
+    let previous = playback.state;
+    let mut state = previous.next(ratio, &settings);
+
+    // The level move to its target at the speed of the state
+    let (target, seconds) = match state {
+        LowHealthState::Critical => (1.0, settings.attack_seconds),
+        _ => (0.0, settings.release_seconds),
+    };
+    let step = time.delta_secs() / seconds.max(f32::EPSILON);
+    let level = if target > playback.level { (playback.level + step).min(target) } else { (playback.level - step).max(target) };
+    if state == LowHealthState::Releasing && level <= 0.0 {
+        state = LowHealthState::Healthy;
+    }
+
+    if state == LowHealthState::Critical && previous != LowHealthState::Critical {
+        if let Some(handle) = playback.heartbeat.take() {
+            if let Some(instance) = instances.get_mut(handle.id()) {
+                instance.stop(AudioTween::default());
+            }
+        }
+        mixer.set_volume(MixerBus::Heartbeat, settings.heartbeat_volume);
+        let handle = channel.play(asset_server.load(settings.heartbeat.as_str()))
+            .looped()
+            .fade_in(AudioTween::linear(Duration::from_secs_f32(settings.attack_seconds.max(0.0))))
+            .handle();
+        playback.heartbeat = Some(handle);
+    }
+    if state != LowHealthState::Critical && previous == LowHealthState::Critical {
+        if let Some(handle) = &playback.heartbeat {
+            if let Some(instance) = instances.get_mut(handle.id()) {
+                instance.stop(AudioTween::linear(Duration::from_secs_f32(settings.release_seconds.max(0.0))));
+            }
+        }
+    }
+    if state == LowHealthState::Healthy {
+        playback.heartbeat = None;
+    }
+
+    if level != playback.level {
+        mixer.set_duck(MixerBus::Main, 1.0 - (1.0 - settings.duck) * level);
+        mixer.set_duck(MixerBus::Ambient, 1.0 - (1.0 - settings.duck) * level);
+        mixer.set_muffle(settings.muffle * level);
+    }
+    playback.state = state;
+    playback.level = level;
+}
+
+fn stop_low_health_audio(
+    channel: Res<AudioChannel<HeartbeatChannel>>,
+    mut mixer: ResMut<AudioMixer>,
+    mut playback: ResMut<LowHealthPlayback>,
+) {
+    channel.stop();
+    mixer.set_duck(MixerBus::Main, 1.0);
+    mixer.set_duck(MixerBus::Ambient, 1.0);
+    mixer.set_muffle(0.0);
+    *playback = LowHealthPlayback::default();
+}
+
+
+#[derive(Default)]
+pub struct LowHealthAudioPlugin;
+
+impl Plugin for LowHealthAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_channel::<HeartbeatChannel>();
+        app.init_resource::<LowHealthAudioSettings>();
+        app.register_type::<LowHealthAudioSettings>();
+        app.init_resource::<LowHealthPlayback>();
+        app.add_systems(Update, (
+            record_local_health,
+            update_low_health_audio.after(record_local_health),
+        ).run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), stop_low_health_audio);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_critical_under_the_threshold() {
+        let settings = LowHealthAudioSettings::default();
+        assert_eq!(LowHealthState::Healthy.next(Some(0.5), &settings), LowHealthState::Healthy);
+        assert_eq!(LowHealthState::Healthy.next(Some(0.29), &settings), LowHealthState::Critical);
+        assert_eq!(LowHealthState::Releasing.next(Some(0.1), &settings), LowHealthState::Critical);
+    }
+
+    #[test]
+    fn test_release_over_the_release_threshold() {
+        let settings = LowHealthAudioSettings::default();
+        // Between the two thresholds it stay critical, no start and stop around the threshold
+        assert_eq!(LowHealthState::Critical.next(Some(0.35), &settings), LowHealthState::Critical);
+        assert_eq!(LowHealthState::Critical.next(Some(0.4), &settings), LowHealthState::Releasing);
+        // Healing again while releasing doesn't start it back
+        assert_eq!(LowHealthState::Releasing.next(Some(0.35), &settings), LowHealthState::Releasing);
+    }
+
+    #[test]
+    fn test_release_without_local_player_or_disabled() {
+        let mut settings = LowHealthAudioSettings::default();
+        assert_eq!(LowHealthState::Critical.next(None, &settings), LowHealthState::Releasing);
+        assert_eq!(LowHealthState::Healthy.next(None, &settings), LowHealthState::Healthy);
+        settings.enabled = false;
+        assert_eq!(LowHealthState::Critical.next(Some(0.1), &settings), LowHealthState::Releasing);
+        assert_eq!(LowHealthState::Healthy.next(Some(0.1), &settings), LowHealthState::Healthy);
+    }
+
+    #[test]
+    fn test_health_ratio_dead_is_none() {
+        assert_eq!(health_ratio(&Health { current: 0.0, max: 100.0, ..default() }), None);
+        assert_eq!(health_ratio(&Health { current: 25.0, max: 100.0, ..default() }), Some(0.25));
+    }
+
+    #[test]
+    fn test_heartbeat_sound_exists() {
+        let settings = LowHealthAudioSettings::default();
+        let path = format!("{}/../../assets/{}", env!("CARGO_MANIFEST_DIR"), settings.heartbeat);
+        assert!(std::path::Path::new(&path).exists(), "{}", path);
+    }
+}
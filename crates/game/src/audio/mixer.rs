@@ -0,0 +1,106 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_kira_audio::prelude::*;
+
+use super::{ambient::AmbientChannel, heartbeat::HeartbeatChannel};
+
+// Volume of the channels of the mixer. Each channel has the volume wanted by the system
+// that play on it, the states of the game duck it and muffle it on top, the result is
+// set on the channel once per frame when something changed. No system set the volume
+// of a channel itself.
+//
+// The kira plugin give no filter on its channels, the muffle is a low pass made by the
+// levels: the bright channels, the shots and the barks on the main one, are lowered
+// more than the ambience that is already dull.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MixerBus {
+    // Default channel, the weapons and the barks
+    Main,
+    Ambient,
+    Heartbeat,
+}
+
+impl MixerBus {
+    pub const ALL: [MixerBus; 3] = [MixerBus::Main, MixerBus::Ambient, MixerBus::Heartbeat];
+
+    // Part of the volume removed at full muffle
+    fn muffle_attenuation(&self) -> f32 {
+        match self {
+            MixerBus::Main => 0.6,
+            MixerBus::Ambient => 0.3,
+            // The state muffling the others is the one heard
+            MixerBus::Heartbeat => 0.0,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct AudioMixer {
+    volumes: HashMap<MixerBus, f32>,
+    // Multiplier of each bus, 1 when nothing duck it
+    ducks: HashMap<MixerBus, f32>,
+    // From 0, clear, to 1, fully muffled
+    muffle: f32,
+}
+
+impl Default for AudioMixer {
+    fn default() -> Self {
+        Self {
+            volumes: MixerBus::ALL.into_iter().map(|bus| (bus, 1.0)).collect(),
+            ducks: HashMap::default(),
+            muffle: 0.0,
+        }
+    }
+}
+
+impl AudioMixer {
+    pub fn set_volume(&mut self, bus: MixerBus, volume: f32) {
+        self.volumes.insert(bus, volume.max(0.0));
+    }
+
+    pub fn set_duck(&mut self, bus: MixerBus, duck: f32) {
+        self.ducks.insert(bus, duck.clamp(0.0, 1.0));
+    }
+
+    pub fn set_muffle(&mut self, muffle: f32) {
+        self.muffle = muffle.clamp(0.0, 1.0);
+    }
+
+    pub fn muffle(&self) -> f32 {
+        self.muffle
+    }
+
+    // Volume heard on the bus
+    pub fn level(&self, bus: MixerBus) -> f32 {
+        let volume = self.volumes.get(&bus).copied().unwrap_or(1.0);
+        let duck = self.ducks.get(&bus).copied().unwrap_or(1.0);
+        volume * duck * (1.0 - self.muffle * bus.muffle_attenuation())
+    }
+}
+
+
+// SYSTEMS
+
+fn apply_mixer_levels(
+    mixer: Res<AudioMixer>,
+    main: Res<AudioChannel<MainTrack>>,
+    ambient: Res<AudioChannel<AmbientChannel>>,
+    heartbeat: Res<AudioChannel<HeartbeatChannel>>,
+) {
+    if !mixer.is_changed() {
+        return;
+    }
+    main.set_volume(mixer.level(MixerBus::Main) as f64);
+    ambient.set_volume(mixer.level(MixerBus::Ambient) as f64);
+    heartbeat.set_volume(mixer.level(MixerBus::Heartbeat) as f64);
+}
+
+
+pub struct AudioMixerPlugin;
+
+impl Plugin for AudioMixerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AudioMixer>();
+        app.add_systems(PostUpdate, apply_mixer_levels);
+    }
+}
@@ -1,9 +1,18 @@
-
+pub mod ambient;
+pub mod barks;
+pub mod doppler;
+pub mod heartbeat;
+pub mod mixer;
+pub mod weapons;
 
 use std::io::Cursor;
 
-use bevy::prelude::*;
+use animation::AnimationFrameEvent;
+use bevy::{prelude::*, utils::HashMap};
 use bevy_kira_audio::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{camera::GameCamera, character::player::{LocalPlayer, Player}};
 
 
 
@@ -13,6 +22,40 @@ pub struct AudioState {
 
 }
 
+// Where the sounds are heard from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum ListenerPolicy {
+    // Center of the camera, what you hear change with the zoom of the PlayersLock mode
+    Camera,
+    // The local player, fallback on the camera when there is none
+    #[default]
+    LocalPlayer,
+    // Halfway between the camera and the local player
+    Midpoint,
+}
+
+#[derive(Resource, Clone, Debug, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+pub struct AudioSettings {
+    pub listener: ListenerPolicy,
+}
+
+// Sound played for each animation trigger name, on the entity of the animation
+#[derive(Resource, Clone, Debug)]
+pub struct AnimationTriggerSounds(pub HashMap<String, String>);
+
+impl Default for AnimationTriggerSounds {
+    fn default() -> Self {
+        Self(HashMap::from([
+            ("mag_in".to_string(), "sounds/machine-gun-reload.ogg".to_string()),
+        ]))
+    }
+}
+
+// Entity holding the spatial audio receiver, moved every frame based on the listener policy
+#[derive(Component)]
+pub struct AudioListener;
+
 
 pub struct ZAudioPlugin {}
 
@@ -20,11 +63,70 @@ impl Plugin for ZAudioPlugin {
    fn build(&self, app: &mut App) {
        app.add_plugins(AudioPlugin);
        app.add_plugins(SpatialAudioPlugin);
+       app.add_plugins(mixer::AudioMixerPlugin);
+       app.add_plugins(ambient::AmbientAudioPlugin);
+       app.add_plugins(heartbeat::LowHealthAudioPlugin);
+       app.add_plugins(barks::EnemyBarkPlugin);
+       app.add_plugins(weapons::WeaponAudioPlugin);
+       app.add_plugins(doppler::DopplerAudioPlugin);
+       app.init_resource::<AudioSettings>();
+       app.init_resource::<AnimationTriggerSounds>();
+       app.register_type::<AudioSettings>();
+       app.add_systems(Startup, spawn_audio_listener);
+       app.add_systems(PostUpdate, update_audio_listener.before(TransformSystem::TransformPropagate));
+       app.add_systems(Update, play_animation_trigger_sounds);
        //app.add_systems(Startup, play_loop);
    } 
     
 }
 
+fn spawn_audio_listener(mut commands: Commands) {
+    commands.spawn((AudioListener, SpatialAudioReceiver, Transform::default()));
+}
+
+fn update_audio_listener(
+    settings: Res<AudioSettings>,
+    camera_query: Query<&Transform, (With<GameCamera>, Without<AudioListener>)>,
+    local_player_query: Query<&Transform, (With<LocalPlayer>, With<Player>, Without<AudioListener>)>,
+    mut listener_query: Query<&mut Transform, With<AudioListener>>,
+) {
+    let Ok(mut listener) = listener_query.get_single_mut() else {
+        return;
+    };
+    let camera = camera_query.get_single().ok().map(|transform| transform.translation.truncate());
+    let player = local_player_query.get_single().ok().map(|transform| transform.translation.truncate());
+
+    let position = match (settings.listener, camera, player) {
+        (ListenerPolicy::LocalPlayer, _, Some(player)) => player,
+        (ListenerPolicy::Midpoint, Some(camera), Some(player)) => camera.lerp(player, 0.5),
+        (_, Some(camera), _) => camera,
+        (_, None, Some(player)) => player,
+        (_, None, None) => return,
+    };
+
+    listener.translation.x = position.x;
+    listener.translation.y = position.y;
+}
+
+fn play_animation_trigger_sounds(
+    mut events: EventReader<AnimationFrameEvent>,
+    sounds: Res<AnimationTriggerSounds>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut emitter_query: Query<&mut SpatialAudioEmitter>,
+) {
+    for event in events.read() {
+        let Some(path) = sounds.0.get(&event.name) else {
+            continue;
+        };
+        let Ok(mut emitter) = emitter_query.get_mut(event.entity) else {
+            continue;
+        };
+        let instance = audio.play(asset_server.load(path.as_str())).handle();
+        emitter.instances.push(instance);
+    }
+}
+
 fn play_loop(asset_server: Res<AssetServer>, audio: Res<Audio>) {
     audio.play(asset_server.load("sounds/loop.ogg")).looped();
 }
@@ -0,0 +1,134 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::ConfirmedFrameCount;
+use bevy_kira_audio::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::events::RollbackEvents;
+
+use crate::{frame::{confirmed_frame, FrameCount}, plugins::AppState, weapons::{Weapon, WeaponFiredEvent, WeaponState}};
+
+// Sounds of the weapons, defined in the weapons RON next to their stats. The weapon
+// system send its sounds on the rollback events and they are only played once their
+// frame is confirmed, a mispredicted shot is never heard. Each sound is a list of
+// variants, one is picked for every play so a fast weapon doesn't repeat the same sample.
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WeaponAudioConfig {
+    #[serde(default)]
+    pub fire: Vec<String>,
+    #[serde(default)]
+    pub reload_start: Vec<String>,
+    #[serde(default)]
+    pub reload_end: Vec<String>,
+    // Trigger pulled with an empty mag
+    #[serde(default)]
+    pub empty: Vec<String>,
+    #[serde(default)]
+    pub mode_switch: Vec<String>,
+    // Weapon locked by the heat
+    #[serde(default)]
+    pub overheat: Vec<String>,
+    // Sounds of a firing mode, a list left empty use the one of the weapon
+    #[serde(default)]
+    pub modes: HashMap<String, WeaponAudioConfig>,
+}
+
+impl WeaponAudioConfig {
+    fn own(&self, kind: WeaponSoundKind) -> &[String] {
+        match kind {
+            WeaponSoundKind::Fire => &self.fire,
+            WeaponSoundKind::ReloadStart => &self.reload_start,
+            WeaponSoundKind::ReloadEnd => &self.reload_end,
+            WeaponSoundKind::Empty => &self.empty,
+            WeaponSoundKind::ModeSwitch => &self.mode_switch,
+            WeaponSoundKind::Overheat => &self.overheat,
+        }
+    }
+
+    pub fn variants(&self, kind: WeaponSoundKind, mode: &str) -> &[String] {
+        match self.modes.get(mode).map(|mode| mode.own(kind)) {
+            Some(variants) if !variants.is_empty() => variants,
+            _ => self.own(kind),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeaponSoundKind {
+    Fire,
+    ReloadStart,
+    ReloadEnd,
+    Empty,
+    ModeSwitch,
+    Overheat,
+}
+
+// Sounds other than the shots, those come from the WeaponFiredEvent
+#[derive(Clone, Debug)]
+pub struct WeaponSoundEvent {
+    pub weapon_entity: Entity,
+    pub kind: WeaponSoundKind,
+}
+
+#[derive(Resource, Default)]
+struct WeaponSoundCursor {
+    last_read_frame: Option<u32>,
+}
+
+
+// SYSTEMS
+
+fn play_confirmed_weapon_sounds(
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    mut cursor: ResMut<WeaponSoundCursor>,
+    fired_events: Res<RollbackEvents<WeaponFiredEvent>>,
+    sound_events: Res<RollbackEvents<WeaponSoundEvent>>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    weapon_query: Query<(&Weapon, &WeaponState, &Parent)>,
+    mut emitter_query: Query<&mut SpatialAudioEmitter>,
+) {
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    if cursor.last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+
+    let fired = fired_events.read_after(cursor.last_read_frame)
+        .filter(|(f, _)| *f <= confirmed_frame)
+        .map(|(f, event)| (f, event.weapon_entity, WeaponSoundKind::Fire));
+    let others = sound_events.read_after(cursor.last_read_frame)
+        .filter(|(f, _)| *f <= confirmed_frame)
+        .map(|(f, event)| (f, event.weapon_entity, event.kind));
+
+    for (event_frame, weapon_entity, kind) in fired.chain(others) {
+        // A weapon dropped since is not heard
+        let Ok((weapon, state, parent)) = weapon_query.get(weapon_entity) else {
+            continue;
+        };
+        let variants = weapon.audio_config.variants(kind, &state.active_mode);
+        if variants.is_empty() {
+            continue;
+        }
+        let path = &variants[(event_frame as usize + weapon_entity.index() as usize) % variants.len()];
+
+        let instance = audio.play(asset_server.load(path.as_str())).handle();
+        // On the emitter of the player holding it, heard from where it is
+        if let Ok(mut emitter) = emitter_query.get_mut(parent.get()) {
+            emitter.instances.push(instance);
+        }
+    }
+
+    cursor.last_read_frame = Some(confirmed_frame);
+}
+
+
+pub struct WeaponAudioPlugin;
+
+impl Plugin for WeaponAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WeaponSoundCursor>();
+        app.add_systems(Update, play_confirmed_weapon_sounds.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+use utils::frame::SimulationConfig;
+
+use crate::{character::{enemy::Enemy, player::{input::PreviousInput, jjrs::PeerConfig, Player}}, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings, Wall}, frame::FrameCount, interaction::{find_interactable_in_range, Interactable}, localization::LocalizedText, plugins::AppState, points::PlayerPoints};
+
+// Planks across an opening. The zombies next to it tear it down, it stop blocking once
+// broken and the players repair it plank by plank for a few points.
+
+// Distance from the side of the barricade where a zombie can tear it
+const BARRICADE_ATTACK_REACH: f32 = 30.0;
+const BARRICADE_INTERACTION_RADIUS: f32 = 90.0;
+
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Barricade {
+    pub health: f32,
+    pub max_health: f32,
+    pub size: Vec2,
+    // Health given back by one interaction
+    pub repair_amount: f32,
+    pub repair_points: u32,
+    // Damage of each zombie next to it, every attack interval
+    pub attack_damage: f32,
+    pub attack_interval_frames: u32,
+}
+
+impl Barricade {
+    pub fn new(size: Vec2, max_health: f32) -> Self {
+        Self {
+            health: max_health,
+            max_health,
+            size,
+            repair_amount: max_health / 5.0,
+            repair_points: 10,
+            attack_damage: 5.0,
+            attack_interval_frames: 30,
+        }
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.health <= 0.0
+    }
+
+    pub fn is_repaired(&self) -> bool {
+        self.health >= self.max_health
+    }
+}
+
+pub fn spawn_barricade(
+    commands: &mut Commands,
+    position: Vec3,
+    barricade: Barricade,
+    collision_settings: &CollisionSettings,
+) -> Entity {
+    let mut entity_commands = commands.spawn((
+        Sprite::from_color(Color::srgb(0.55, 0.35, 0.15), barricade.size),
+        Transform::from_translation(position),
+        barricade,
+        Interactable {
+            radius: BARRICADE_INTERACTION_RADIUS,
+            prompt: LocalizedText::new("interaction.repair_barricade"),
+        },
+        CollisionLayer(collision_settings.wall_layer),
+    ));
+    if !barricade.is_broken() {
+        entity_commands.insert(blocking_components(&barricade));
+    }
+    entity_commands.add_rollback().id()
+}
+
+fn blocking_components(barricade: &Barricade) -> (Wall, Collider) {
+    (
+        Wall,
+        Collider {
+            shape: ColliderShape::Rectangle { width: barricade.size.x, height: barricade.size.y },
+            offset: Vec2::ZERO,
+        },
+    )
+}
+
+
+// SYSTEMS
+
+// Each press of the interaction next to a damaged barricade put a plank back
+pub fn rollback_repair_barricades(
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    mut barricade_query: Query<(Entity, &Transform, &Interactable, &mut Barricade), With<Rollback>>,
+    mut player_query: Query<(&Transform, &Player, &mut PlayerPoints, &PreviousInput), With<Rollback>>,
+) {
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.handle);
+
+    for (transform, player, points, previous_input) in players.iter_mut() {
+        let (input, _input_status) = inputs[player.handle];
+        if !previous_input.interact_just_pressed(&input) {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let Some(entity) = find_interactable_in_range(position, barricade_query.iter().map(|(entity, transform, interactable, _)| (entity, transform, interactable))) else {
+            continue;
+        };
+        let Ok((_, _, _, mut barricade)) = barricade_query.get_mut(entity) else {
+            continue;
+        };
+        if barricade.is_repaired() {
+            continue;
+        }
+
+        barricade.health = (barricade.health + barricade.repair_amount).min(barricade.max_health);
+        points.add(barricade.repair_points);
+    }
+}
+
+// Zombies next to a barricade tear it down, a broken barricade stop blocking until repaired
+pub fn rollback_barricade_system(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    enemy_query: Query<&Transform, (With<Enemy>, With<Rollback>)>,
+    mut barricade_query: Query<(Entity, &Transform, &mut Barricade, Has<Wall>), (Without<Enemy>, With<Rollback>)>,
+) {
+    let mut barricades: Vec<_> = barricade_query.iter_mut().collect();
+    barricades.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, mut barricade, blocking) in barricades {
+        let attack_interval = simulation.frames(barricade.attack_interval_frames);
+        if attack_interval > 0 && frame.frame % attack_interval == 0 && !barricade.is_broken() {
+            let center = transform.translation.truncate();
+            let reach = barricade.size.max_element() / 2.0 + BARRICADE_ATTACK_REACH;
+            let attackers = enemy_query.iter()
+                .filter(|enemy| enemy.translation.truncate().distance(center) <= reach)
+                .count();
+            barricade.health = (barricade.health - barricade.attack_damage * attackers as f32).max(0.0);
+        }
+
+        if barricade.is_broken() && blocking {
+            commands.entity(entity).remove::<(Wall, Collider)>();
+        } else if !barricade.is_broken() && !blocking {
+            commands.entity(entity).insert(blocking_components(&barricade));
+        }
+    }
+}
+
+// The planks fade with the health of the barricade
+fn update_barricade_sprites(mut query: Query<(&Barricade, &mut Sprite), Changed<Barricade>>) {
+    for (barricade, mut sprite) in query.iter_mut() {
+        let ratio = (barricade.health / barricade.max_health.max(1.0)).clamp(0.0, 1.0);
+        sprite.color = Color::srgba(0.55, 0.35, 0.15, 0.15 + 0.85 * ratio);
+    }
+}
+
+#[derive(Default)]
+pub struct BarricadePlugin;
+
+impl Plugin for BarricadePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_barricade_sprites.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,117 @@
+pub mod ui;
+
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use serde::{Deserialize, Serialize};
+
+use crate::weapons::Bullet;
+
+// Hard caps on what the simulation can hold at once. Every entity of the rollback state
+// is resimulated on a misprediction, the caps keep the worst frame bounded whatever
+// happen in game: the spawners wait for a free slot, the oldest bullets are removed
+// and the drops are skipped. The caps are given to the game plugin, like the tick rate
+// they must be the same on every peer.
+
+#[derive(Resource, Reflect, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct SimulationBudget {
+    pub max_enemies: usize,
+    pub max_bullets: usize,
+    pub max_pickups: usize,
+}
+
+impl Default for SimulationBudget {
+    fn default() -> Self {
+        Self {
+            max_enemies: 20,
+            max_bullets: 200,
+            max_pickups: 10,
+        }
+    }
+}
+
+impl SimulationBudget {
+    pub fn has_room_for_enemy(&self, enemies: usize) -> bool {
+        enemies < self.max_enemies
+    }
+
+    pub fn has_room_for_pickup(&self, pickups: usize) -> bool {
+        pickups < self.max_pickups
+    }
+
+    pub fn bullets_over(&self, bullets: usize) -> usize {
+        bullets.saturating_sub(self.max_bullets)
+    }
+}
+
+// The `excess` oldest bullets, the ones of the same frame by entity
+pub fn oldest_bullets(mut bullets: Vec<(Entity, u32)>, excess: usize) -> Vec<Entity> {
+    bullets.sort_by_key(|(entity, created_at)| (*created_at, entity.index()));
+    bullets.into_iter().take(excess).map(|(entity, _)| entity).collect()
+}
+
+// What the caps prevented since the start of the match, rollback so it is counted once
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct BudgetStats {
+    // Frames a spawner was ready but the enemy cap was reached
+    pub deferred_spawns: u32,
+    pub culled_bullets: u32,
+    pub skipped_drops: u32,
+}
+
+
+// SYSTEMS
+
+// Remove the oldest bullets over the cap, the bullets of the same frame by entity
+pub fn rollback_cull_bullets(
+    mut commands: Commands,
+    budget: Res<SimulationBudget>,
+    mut stats: ResMut<BudgetStats>,
+    bullet_query: Query<(Entity, &Bullet), With<Rollback>>,
+) {
+    let excess = budget.bullets_over(bullet_query.iter().count());
+    if excess == 0 {
+        return;
+    }
+
+    let bullets = bullet_query.iter().map(|(entity, bullet)| (entity, bullet.created_at)).collect();
+    for entity in oldest_bullets(bullets, excess) {
+        commands.entity(entity).despawn();
+    }
+    stats.culled_bullets += excess as u32;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caps_are_reached_at_the_limit() {
+        let budget = SimulationBudget { max_enemies: 2, max_bullets: 3, max_pickups: 1 };
+        assert!(budget.has_room_for_enemy(1));
+        assert!(!budget.has_room_for_enemy(2));
+        assert!(budget.has_room_for_pickup(0));
+        assert!(!budget.has_room_for_pickup(1));
+        assert_eq!(budget.bullets_over(3), 0);
+        assert_eq!(budget.bullets_over(5), 2);
+    }
+
+    #[test]
+    fn test_oldest_bullets_are_culled_first() {
+        let bullets = vec![
+            (Entity::from_raw(4), 12),
+            (Entity::from_raw(7), 10),
+            (Entity::from_raw(2), 12),
+            (Entity::from_raw(1), 15),
+        ];
+        assert_eq!(oldest_bullets(bullets, 2), vec![Entity::from_raw(7), Entity::from_raw(2)]);
+    }
+
+    #[test]
+    fn test_budget_missing_caps_keep_the_defaults() {
+        let budget: SimulationBudget = ron::de::from_str("(max_enemies: 60)").unwrap();
+        assert_eq!(budget, SimulationBudget { max_enemies: 60, ..default() });
+    }
+}
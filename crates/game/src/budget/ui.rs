@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+
+use crate::{character::enemy::Enemy, hud::{HudAnchor, HudSlot}, plugins::AppState, powerup::PowerUpPickup, weapons::Bullet};
+
+use super::{BudgetStats, SimulationBudget};
+
+
+#[derive(Component)]
+struct BudgetReadoutText;
+
+#[derive(Resource, Default)]
+struct BudgetReadoutState {
+    is_visible: bool,
+}
+
+
+fn setup_budget_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        BudgetReadoutText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 14.0,
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        HudSlot::new(HudAnchor::TopRight, 2),
+        Node {
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+fn toggle_budget_readout(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<BudgetReadoutState>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        state.is_visible = !state.is_visible;
+    }
+}
+
+fn update_budget_readout(
+    state: Res<BudgetReadoutState>,
+    budget: Res<SimulationBudget>,
+    stats: Res<BudgetStats>,
+    q_enemies: Query<(), With<Enemy>>,
+    q_bullets: Query<(), With<Bullet>>,
+    q_pickups: Query<(), With<PowerUpPickup>>,
+    mut q_text: Query<(&mut Text, &mut Visibility), With<BudgetReadoutText>>,
+) {
+    let Ok((mut text, mut visibility)) = q_text.get_single_mut() else {
+        return;
+    };
+
+    if !state.is_visible {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    text.0 = [
+        "Simulation budget [F6]".to_string(),
+        format!("enemies {:>4} / {:<4} deferred spawns {}", q_enemies.iter().count(), budget.max_enemies, stats.deferred_spawns),
+        format!("bullets {:>4} / {:<4} culled {}", q_bullets.iter().count(), budget.max_bullets, stats.culled_bullets),
+        format!("pickups {:>4} / {:<4} skipped drops {}", q_pickups.iter().count(), budget.max_pickups, stats.skipped_drops),
+    ].join("\n");
+}
+
+
+#[derive(Default)]
+pub struct BudgetUIPlugin;
+
+impl Plugin for BudgetUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BudgetReadoutState>();
+        app.add_systems(OnEnter(AppState::InGame), setup_budget_ui);
+        app.add_systems(Update, (toggle_budget_readout, update_budget_readout).run_if(in_state(AppState::InGame)));
+    }
+}
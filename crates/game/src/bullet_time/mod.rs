@@ -0,0 +1,147 @@
+pub mod trail;
+
+use bevy::prelude::*;
+use bevy_ggrs::{RollbackFrameRate, Session};
+use bevy_kira_audio::prelude::*;
+use utils::frame::SimulationConfig;
+
+use crate::{character::player::jjrs::PeerConfig, plugins::AppState, powerup::ActivePowerUps};
+
+// Slow motion of the offline games. A frame compute the same thing, only the rate GGRS
+// run the frames at is scaled with the time scale of the SimulationConfig, so it can't
+// work when the peers must stay at the same speed and is off in the P2P sessions.
+// Started by the bullet time power-up or the debug key, the sounds are pitched down
+// with the game and the players and the bullets leave trails behind them.
+
+pub const BULLET_TIME_DEBUG_KEY: KeyCode = KeyCode::KeyB;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct BulletTimeConfig {
+    // Off in the online games, the power-up is never dropped and the key does nothing
+    pub enabled: bool,
+    // Speed of the game during the slow motion
+    pub time_scale: f32,
+    // The sounds follow the time scale without going under this pitch
+    pub min_pitch: f32,
+    // Real time between two ghosts of a trail
+    pub trail_interval_seconds: f32,
+    pub trail_lifetime_seconds: f32,
+    pub trail_alpha: f32,
+}
+
+impl Default for BulletTimeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            time_scale: 0.35,
+            min_pitch: 0.6,
+            trail_interval_seconds: 0.05,
+            trail_lifetime_seconds: 0.3,
+            trail_alpha: 0.4,
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct BulletTime {
+    // Toggled by the debug key
+    pub forced: bool,
+    pub active: bool,
+    // Time scale of the game outside of the slow motion
+    pub base_scale: f32,
+}
+
+impl BulletTime {
+    pub fn pitch(&self, config: &BulletTimeConfig) -> f64 {
+        if self.active { config.time_scale.max(config.min_pitch) as f64 } else { 1.0 }
+    }
+}
+
+
+// SYSTEMS
+
+fn toggle_bullet_time_debug(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    config: Res<BulletTimeConfig>,
+    mut bullet_time: ResMut<BulletTime>,
+) {
+    if config.enabled && keyboard_input.just_pressed(BULLET_TIME_DEBUG_KEY) {
+        bullet_time.forced = !bullet_time.forced;
+    }
+}
+
+// Read the power-up from the rollback state, the frame rate given to GGRS is not part of it
+fn apply_bullet_time_scale(
+    config: Res<BulletTimeConfig>,
+    active: Res<ActivePowerUps>,
+    session: Option<Res<Session<PeerConfig>>>,
+    mut bullet_time: ResMut<BulletTime>,
+    mut simulation: ResMut<SimulationConfig>,
+    mut frame_rate: ResMut<RollbackFrameRate>,
+) {
+    let p2p = matches!(session.as_deref(), Some(Session::P2P(_)));
+    let slowed = config.enabled && !p2p && (bullet_time.forced || active.is_bullet_time());
+    if bullet_time.active != slowed {
+        bullet_time.active = slowed;
+        info!("bullet time {}", if slowed { "started" } else { "over" });
+    }
+
+    let time_scale = if slowed { bullet_time.base_scale * config.time_scale } else { bullet_time.base_scale };
+    if simulation.time_scale != time_scale {
+        simulation.time_scale = time_scale;
+        **frame_rate = simulation.rollback_fps();
+    }
+}
+
+// Every sound when the pitch change, then the new ones as they start
+fn pitch_bullet_time_sounds(
+    config: Res<BulletTimeConfig>,
+    bullet_time: Res<BulletTime>,
+    mut applied_pitch: Local<Option<f64>>,
+    mut events: EventReader<AssetEvent<AudioInstance>>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    let pitch = bullet_time.pitch(&config);
+    let added: Vec<AssetId<AudioInstance>> = events.read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    if *applied_pitch != Some(pitch) {
+        for (_, instance) in instances.iter_mut() {
+            instance.set_playback_rate(pitch, AudioTween::default());
+        }
+        *applied_pitch = Some(pitch);
+    } else if pitch != 1.0 {
+        for id in added {
+            if let Some(instance) = instances.get_mut(id) {
+                instance.set_playback_rate(pitch, AudioTween::default());
+            }
+        }
+    }
+}
+
+
+pub struct BulletTimePlugin {
+    pub enabled: bool,
+}
+
+impl Plugin for BulletTimePlugin {
+    fn build(&self, app: &mut App) {
+        let base_scale = app.world().get_resource::<SimulationConfig>().map_or(1.0, |simulation| simulation.time_scale);
+
+        app.insert_resource(BulletTimeConfig { enabled: self.enabled, ..default() });
+        app.insert_resource(BulletTime { forced: false, active: false, base_scale });
+        app.register_type::<BulletTimeConfig>();
+        app.add_systems(Update, (
+            toggle_bullet_time_debug,
+            apply_bullet_time_scale.after(toggle_bullet_time_debug),
+            pitch_bullet_time_sounds.after(apply_bullet_time_scale),
+            trail::spawn_trail_ghosts.after(apply_bullet_time_scale),
+            trail::fade_trail_ghosts,
+        ).run_if(in_state(AppState::InGame)));
+    }
+}
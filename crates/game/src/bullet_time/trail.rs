@@ -0,0 +1,80 @@
+use animation::AnimatedLayer;
+use bevy::prelude::*;
+
+use crate::{character::player::Player, weapons::Bullet};
+
+use super::{BulletTime, BulletTimeConfig};
+
+// Presentation only, copy of a sprite left behind that fade out in real time
+#[derive(Component)]
+pub struct TrailGhost {
+    timer: Timer,
+    alpha: f32,
+}
+
+fn spawn_ghost(commands: &mut Commands, config: &BulletTimeConfig, sprite: &Sprite, transform: &GlobalTransform) {
+    let mut transform = transform.compute_transform();
+    // Behind what left it
+    transform.translation.z -= 0.1;
+
+    let mut sprite = sprite.clone();
+    sprite.color = sprite.color.with_alpha(config.trail_alpha);
+
+    commands.spawn((
+        sprite,
+        transform,
+        TrailGhost {
+            timer: Timer::from_seconds(config.trail_lifetime_seconds, TimerMode::Once),
+            alpha: config.trail_alpha,
+        },
+    ));
+}
+
+pub fn spawn_trail_ghosts(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<BulletTimeConfig>,
+    bullet_time: Res<BulletTime>,
+    mut since_last: Local<f32>,
+    q_player: Query<(), With<Player>>,
+    q_layer: Query<(&Sprite, &GlobalTransform, &ViewVisibility, &Parent), With<AnimatedLayer>>,
+    q_bullet: Query<(&Sprite, &GlobalTransform), With<Bullet>>,
+) {
+    if !bullet_time.active {
+        *since_last = 0.0;
+        return;
+    }
+
+    *since_last += time.delta_secs();
+    if *since_last < config.trail_interval_seconds {
+        return;
+    }
+    *since_last = 0.0;
+
+    // Layers of the players, the weapons are their own parent and are left out
+    for (sprite, transform, visibility, parent) in q_layer.iter() {
+        if visibility.get() && q_player.contains(parent.get()) {
+            spawn_ghost(&mut commands, &config, sprite, transform);
+        }
+    }
+
+    for (sprite, transform) in q_bullet.iter() {
+        spawn_ghost(&mut commands, &config, sprite, transform);
+    }
+}
+
+pub fn fade_trail_ghosts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_ghost: Query<(Entity, &mut TrailGhost, &mut Sprite)>,
+) {
+    for (entity, mut ghost, mut sprite) in q_ghost.iter_mut() {
+        ghost.timer.tick(time.delta());
+        if ghost.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let alpha = ghost.alpha * ghost.timer.fraction_remaining();
+        sprite.color = sprite.color.with_alpha(alpha);
+    }
+}
@@ -0,0 +1,377 @@
+use bevy::{image::ImageSampler, prelude::*, render::{render_asset::RenderAssetUsages, render_resource::{Extent3d, TextureDimension, TextureFormat}}, sprite::Anchor, utils::HashMap};
+use map::game::tiles::MapTileLayers;
+
+use super::GameCamera;
+
+// Background is rendered by chunks of tiles, only the chunks touching the
+// camera view are spawned so the number of sprites stay bounded on big maps.
+// A chunk is a single sprite with a texture of one pixel per tile, a chunk of the
+// map has ten thousand tiles. Once a level is spawned its tile layers replace the
+// default checker pattern.
+
+// Side of a chunk in the world when the tiles come from the map
+const MAP_CHUNK_WORLD_SIZE: f32 = 1600.0;
+// Behind everything else like the default layer, each layer of the map over the previous
+const MAP_LAYER_Z: f32 = -10.0;
+const MAP_LAYER_Z_STEP: f32 = 0.1;
+
+#[derive(Clone, Debug)]
+pub struct TileGrid {
+    // Position of the first tile of the grid, in tile coordinate
+    pub origin: IVec2,
+    pub width: i32,
+    pub height: i32,
+    // Index in the palette, 0 is an empty tile
+    pub data: Vec<u8>,
+}
+
+impl TileGrid {
+    pub fn get(&self, tile: IVec2) -> Option<u8> {
+        let local = tile - self.origin;
+        if local.x < 0 || local.y < 0 || local.x >= self.width || local.y >= self.height {
+            return None;
+        }
+        self.data.get((local.y * self.width + local.x) as usize).copied().filter(|i| *i != 0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BackgroundLayer {
+    pub z: f32,
+    pub palette: Vec<Color>,
+    // Tiles coming from the map, when none the layer is an infinite checker pattern
+    // with the two first colors of the palette
+    pub tiles: Option<TileGrid>,
+}
+
+impl BackgroundLayer {
+    fn tile_color(&self, tile: IVec2) -> Option<Color> {
+        match &self.tiles {
+            Some(grid) => grid.get(tile).and_then(|index| self.palette.get(index as usize - 1).copied()),
+            None => {
+                let is_dark = (tile.x + tile.y).rem_euclid(2) == 0;
+                self.palette.get(if is_dark { 0 } else { 1 }).copied()
+            }
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct BackgroundSettings {
+    pub tile_size: f32,
+    // Number of tiles on each side of a chunk
+    pub chunk_size: i32,
+    // Extra world distance around the view where chunk are kept alive
+    pub view_margin: f32,
+    // Upper bound of chunks spawned at the same time, per layer
+    pub max_chunks_per_layer: usize,
+    pub layers: Vec<BackgroundLayer>,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        Self {
+            tile_size: 400.0,
+            chunk_size: 4,
+            view_margin: 400.0,
+            max_chunks_per_layer: 64,
+            layers: vec![BackgroundLayer {
+                z: -10.0, // Behind everything else
+                palette: vec![
+                    Color::srgb(0.2, 0.2, 0.25), // Dark blue-gray
+                    Color::srgb(0.3, 0.3, 0.35), // Lighter blue-gray
+                ],
+                tiles: None,
+            }],
+        }
+    }
+}
+
+impl BackgroundSettings {
+    // Layers of the map in place of the current ones, a map without tiles get back the checker
+    pub fn apply_map_tiles(&mut self, map_tiles: &MapTileLayers) {
+        let Some(tile_size) = map_tiles.layers.first().map(|layer| layer.tile_size) else {
+            let default = Self::default();
+            self.tile_size = default.tile_size;
+            self.chunk_size = default.chunk_size;
+            self.layers = default.layers;
+            return;
+        };
+        self.tile_size = tile_size;
+        self.chunk_size = (MAP_CHUNK_WORLD_SIZE / tile_size).ceil().max(1.0) as i32;
+        self.layers = map_tiles.layers.iter()
+            .filter(|layer| layer.tile_size == tile_size)
+            .enumerate()
+            .map(|(i, layer)| BackgroundLayer {
+                z: MAP_LAYER_Z + i as f32 * MAP_LAYER_Z_STEP,
+                palette: layer.palette.clone(),
+                tiles: Some(TileGrid {
+                    origin: layer.origin,
+                    width: layer.width,
+                    height: layer.height,
+                    data: layer.data.clone(),
+                }),
+            })
+            .collect();
+    }
+}
+
+#[derive(Component)]
+pub struct BackgroundChunk {
+    pub layer: usize,
+    pub coord: IVec2,
+}
+
+#[derive(Resource, Default)]
+pub struct BackgroundChunks {
+    pub spawned: HashMap<(usize, IVec2), Entity>,
+}
+
+pub struct ChunkedBackgroundPlugin;
+
+impl Plugin for ChunkedBackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BackgroundSettings>()
+            .init_resource::<BackgroundChunks>()
+            .init_resource::<MapTileLayers>()
+            .add_systems(Update, (
+                fill_background_from_map,
+                reset_background_on_settings_change,
+                update_background_chunks_system,
+            ).chain());
+    }
+}
+
+fn fill_background_from_map(
+    map_tiles: Res<MapTileLayers>,
+    mut settings: ResMut<BackgroundSettings>,
+) {
+    if map_tiles.is_changed() {
+        settings.apply_map_tiles(&map_tiles);
+    }
+}
+
+fn reset_background_on_settings_change(
+    mut commands: Commands,
+    settings: Res<BackgroundSettings>,
+    mut chunks: ResMut<BackgroundChunks>,
+) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+
+    for (_, entity) in chunks.spawned.drain() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update_background_chunks_system(
+    mut commands: Commands,
+    settings: Res<BackgroundSettings>,
+    mut chunks: ResMut<BackgroundChunks>,
+    mut images: ResMut<Assets<Image>>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+) {
+    let Ok((camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+
+    let chunk_world_size = settings.tile_size * settings.chunk_size as f32;
+    let center = camera_transform.translation.truncate();
+    let view_min = center + projection.area.min - Vec2::splat(settings.view_margin);
+    let view_max = center + projection.area.max + Vec2::splat(settings.view_margin);
+
+    let mut min_chunk = (view_min / chunk_world_size).floor().as_ivec2();
+    let mut max_chunk = (view_max / chunk_world_size).floor().as_ivec2();
+
+    // Keep the draw count bounded when zoomed far out, the chunks closest to the center win
+    let max_side = (settings.max_chunks_per_layer as f32).sqrt().floor().max(1.0) as i32;
+    let center_chunk = (center / chunk_world_size).floor().as_ivec2();
+    for axis in 0..2 {
+        if max_chunk[axis] - min_chunk[axis] + 1 > max_side {
+            min_chunk[axis] = center_chunk[axis] - max_side / 2;
+            max_chunk[axis] = min_chunk[axis] + max_side - 1;
+        }
+    }
+
+    let in_view = |coord: IVec2| coord.x >= min_chunk.x && coord.x <= max_chunk.x && coord.y >= min_chunk.y && coord.y <= max_chunk.y;
+
+    // Despawn chunks that left the view
+    chunks.spawned.retain(|(_, coord), entity| {
+        if in_view(*coord) {
+            true
+        } else {
+            commands.entity(*entity).despawn_recursive();
+            false
+        }
+    });
+
+    for (layer_index, layer) in settings.layers.iter().enumerate() {
+        for x in min_chunk.x..=max_chunk.x {
+            for y in min_chunk.y..=max_chunk.y {
+                let coord = IVec2::new(x, y);
+                if chunks.spawned.contains_key(&(layer_index, coord)) {
+                    continue;
+                }
+
+                let entity = spawn_chunk(&mut commands, &mut images, &settings, layer_index, layer, coord);
+                chunks.spawned.insert((layer_index, coord), entity);
+            }
+        }
+    }
+}
+
+// Colors of the tiles of a chunk, the rows of an image go from the top. None when
+// the chunk has no tile
+fn chunk_pixels(settings: &BackgroundSettings, layer: &BackgroundLayer, first_tile: IVec2) -> Option<Vec<u8>> {
+    let size = settings.chunk_size.max(1);
+    let mut pixels = vec![0; (size * size * 4) as usize];
+    let mut empty = true;
+    for i in 0..size {
+        for j in 0..size {
+            let Some(color) = layer.tile_color(first_tile + IVec2::new(i, j)) else {
+                continue;
+            };
+            let index = (((size - 1 - j) * size + i) * 4) as usize;
+            pixels[index..index + 4].copy_from_slice(&color.to_srgba().to_u8_array());
+            empty = false;
+        }
+    }
+    (!empty).then_some(pixels)
+}
+
+fn spawn_chunk(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    settings: &BackgroundSettings,
+    layer_index: usize,
+    layer: &BackgroundLayer,
+    coord: IVec2,
+) -> Entity {
+    let first_tile = coord * settings.chunk_size;
+
+    let mut entity_commands = commands.spawn((
+        Transform::from_translation(Vec3::new(
+            first_tile.x as f32 * settings.tile_size,
+            first_tile.y as f32 * settings.tile_size,
+            layer.z,
+        )),
+        Visibility::default(),
+        BackgroundChunk { layer: layer_index, coord },
+        Name::new(format!("BackgroundChunk {} ({}, {})", layer_index, coord.x, coord.y)),
+    ));
+
+    // Still tracked when empty so it is not built again each frame
+    if let Some(pixels) = chunk_pixels(settings, layer, first_tile) {
+        let size = settings.chunk_size.max(1) as u32;
+        let mut image = Image::new(
+            Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+            TextureDimension::D2,
+            pixels,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        // Sharp edges between the tiles
+        image.sampler = ImageSampler::nearest();
+
+        // A chunk cover its cells from its corner, like the tiles of the map
+        entity_commands.insert(Sprite {
+            image: images.add(image),
+            custom_size: Some(Vec2::splat(size as f32 * settings.tile_size)),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        });
+    }
+
+    entity_commands.id()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use map::game::tiles::MapTileLayer;
+
+    use super::*;
+
+    fn map_tiles() -> MapTileLayers {
+        MapTileLayers {
+            layers: vec![MapTileLayer {
+                name: "Walls".into(),
+                tile_size: 16.0,
+                origin: IVec2::new(-1, -2),
+                width: 2,
+                height: 2,
+                // Bottom row first
+                data: vec![1, 0, 0, 2],
+                palette: vec![Color::BLACK, Color::WHITE],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_map_tiles_replace_the_checker() {
+        let mut settings = BackgroundSettings::default();
+        settings.apply_map_tiles(&map_tiles());
+
+        assert_eq!(settings.tile_size, 16.0);
+        assert_eq!(settings.chunk_size, 100);
+        assert_eq!(settings.layers.len(), 1);
+        let layer = &settings.layers[0];
+        assert_eq!(layer.tile_color(IVec2::new(-1, -2)), Some(Color::BLACK));
+        assert_eq!(layer.tile_color(IVec2::new(0, -2)), None);
+        assert_eq!(layer.tile_color(IVec2::new(0, -1)), Some(Color::WHITE));
+        // Outside of the level nothing is drawn
+        assert_eq!(layer.tile_color(IVec2::new(1, -1)), None);
+    }
+
+    #[test]
+    fn test_map_without_tiles_keep_the_checker() {
+        let mut settings = BackgroundSettings::default();
+        settings.apply_map_tiles(&MapTileLayers::default());
+        assert_eq!(settings.tile_size, 400.0);
+        assert!(settings.layers[0].tiles.is_none());
+    }
+
+    #[test]
+    fn test_level_spawn_fill_the_background() {
+        let mut app = App::new();
+        app.init_resource::<BackgroundSettings>()
+            .init_resource::<MapTileLayers>()
+            .add_systems(Update, fill_background_from_map);
+        app.update();
+        assert!(app.world().resource::<BackgroundSettings>().layers[0].tiles.is_none());
+
+        // What the map crate write when a level spawn
+        *app.world_mut().resource_mut::<MapTileLayers>() = map_tiles();
+        app.update();
+        let settings = app.world().resource::<BackgroundSettings>();
+        assert_eq!(settings.layers[0].tiles.as_ref().map(|grid| grid.data.clone()), Some(vec![1, 0, 0, 2]));
+    }
+
+    #[test]
+    fn test_chunk_pixels_one_per_tile_from_the_top_row() {
+        let mut settings = BackgroundSettings::default();
+        settings.apply_map_tiles(&map_tiles());
+        settings.chunk_size = 2;
+        let layer = &settings.layers[0];
+
+        let pixels = chunk_pixels(&settings, layer, IVec2::new(-1, -2)).unwrap();
+        assert_eq!(pixels.len(), 2 * 2 * 4);
+        // Top row is the tile row -1, the bottom one is -2
+        assert_eq!(&pixels[0..4], &[0, 0, 0, 0]);
+        assert_eq!(&pixels[4..8], &[255, 255, 255, 255]);
+        assert_eq!(&pixels[8..12], &[0, 0, 0, 255]);
+        assert_eq!(&pixels[12..16], &[0, 0, 0, 0]);
+
+        // Away from the level
+        assert_eq!(chunk_pixels(&settings, layer, IVec2::new(10, 10)), None);
+    }
+
+    #[test]
+    fn test_checker_chunk_has_no_hole() {
+        let settings = BackgroundSettings::default();
+        let pixels = chunk_pixels(&settings, &settings.layers[0], IVec2::ZERO).unwrap();
+        assert_eq!(pixels.len(), (settings.chunk_size * settings.chunk_size * 4) as usize);
+        assert!(pixels.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+}
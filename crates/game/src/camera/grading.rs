@@ -0,0 +1,413 @@
+use bevy::{
+    core_pipeline::{bloom::Bloom, tonemapping::Tonemapping},
+    prelude::*,
+    render::{render_asset::RenderAssetUsages, render_resource::{Extent3d, TextureDimension, TextureFormat}, view::{ColorGrading, ColorGradingGlobal, ColorGradingSection}},
+    utils::HashMap,
+};
+use map::game::entity::map::{grading::{GradingZoneComponent, GradingZoneConfig}, zone::ZoneRect};
+use serde::Deserialize;
+use utils::schema::Versioned;
+
+use crate::{global_asset::GlobalAsset, plugins::AppState};
+
+use super::{options::CameraPreferences, GameCamera};
+
+// Post-processing of the camera, from the presets of `post_process.ron`. The map pick
+// them with its grading zones, the smallest zone around the camera win and the preset of
+// the file is used outside of them. Moving between zones blend the values over the
+// transition, only presentation and the neutral rendering of the camera options skip all
+// of it.
+
+pub const POST_PROCESS_SCHEMA_VERSION: u32 = 1;
+
+const VIGNETTE_TEXTURE_SIZE: u32 = 128;
+
+// The grading LUT is one of the tonemapping LUTs of bevy. Without tonemapping bevy skip
+// the whole pass, a preset with grading or bloom need one of the others.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GradingLut {
+    #[default]
+    Neutral,
+    TonyMcMapface,
+    AgX,
+    BlenderFilmic,
+}
+
+impl GradingLut {
+    fn tonemapping(self) -> Tonemapping {
+        match self {
+            GradingLut::Neutral => Tonemapping::None,
+            GradingLut::TonyMcMapface => Tonemapping::TonyMcMapface,
+            GradingLut::AgX => Tonemapping::AgX,
+            GradingLut::BlenderFilmic => Tonemapping::BlenderFilmic,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PostProcessPreset {
+    pub lut: GradingLut,
+    // In stops
+    pub exposure: f32,
+    // Warmer above 0, colder under
+    pub temperature: f32,
+    // Magenta above 0, green under
+    pub tint: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    // Darkness of the screen borders, from 0 to 1
+    pub vignette: f32,
+    pub bloom: f32,
+}
+
+impl Default for PostProcessPreset {
+    fn default() -> Self {
+        Self {
+            lut: GradingLut::Neutral,
+            exposure: 0.0,
+            temperature: 0.0,
+            tint: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            vignette: 0.0,
+            bloom: 0.0,
+        }
+    }
+}
+
+impl PostProcessPreset {
+    pub fn lerp(&self, other: &PostProcessPreset, t: f32) -> PostProcessPreset {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        PostProcessPreset {
+            // A LUT can't be blended, it is swapped in the middle
+            lut: if t < 0.5 { self.lut } else { other.lut },
+            exposure: mix(self.exposure, other.exposure),
+            temperature: mix(self.temperature, other.temperature),
+            tint: mix(self.tint, other.tint),
+            saturation: mix(self.saturation, other.saturation),
+            contrast: mix(self.contrast, other.contrast),
+            vignette: mix(self.vignette, other.vignette),
+            bloom: mix(self.bloom, other.bloom),
+        }
+    }
+
+    // Grading or bloom that would never be seen
+    fn is_lost(&self) -> bool {
+        let neutral = PostProcessPreset { vignette: self.vignette, ..Default::default() };
+        self.lut == GradingLut::Neutral && *self != neutral
+    }
+
+    fn color_grading(&self) -> ColorGrading {
+        ColorGrading::with_identical_sections(
+            ColorGradingGlobal {
+                exposure: self.exposure,
+                temperature: self.temperature,
+                tint: self.tint,
+                ..default()
+            },
+            ColorGradingSection {
+                saturation: self.saturation,
+                contrast: self.contrast,
+                ..default()
+            },
+        )
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct PostProcessPresets {
+    #[serde(default)]
+    pub schema_version: u32,
+    // Preset outside of the grading zones of the map
+    pub default: String,
+    pub transition_seconds: f32,
+    pub presets: HashMap<String, PostProcessPreset>,
+}
+
+impl Versioned for PostProcessPresets {
+    const ASSET_NAME: &'static str = "post-processing presets";
+    const SCHEMA_VERSION: u32 = POST_PROCESS_SCHEMA_VERSION;
+
+    fn parse_version(_version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_bytes(bytes)
+    }
+}
+
+#[derive(Component)]
+struct Vignette;
+
+#[derive(Resource, Default)]
+struct GradingBlend {
+    from: PostProcessPreset,
+    to: PostProcessPreset,
+    // From 0 to 1 between the two
+    progress: f32,
+    current: PostProcessPreset,
+    // Preset name not in the file, remembered so it is logged once
+    missing: Option<String>,
+}
+
+impl GradingBlend {
+    // Move the transition to `wanted` by `delta` seconds of the `seconds` it take
+    fn advance(&mut self, wanted: PostProcessPreset, delta: f32, seconds: f32) {
+        // A new target start from what is on screen, even in the middle of a transition
+        if wanted != self.to {
+            self.from = self.current.clone();
+            self.to = wanted;
+            self.progress = 0.0;
+        }
+        if self.progress >= 1.0 {
+            return;
+        }
+
+        self.progress = if seconds > 0.0 { (self.progress + delta / seconds).min(1.0) } else { 1.0 };
+        let t = self.progress * self.progress * (3.0 - 2.0 * self.progress);
+        self.current = self.from.lerp(&self.to, t);
+    }
+}
+
+// Short darkening of the borders over the vignette of the preset, for the near misses
+#[derive(Resource, Default)]
+pub struct VignettePulse {
+    strength: f32,
+}
+
+impl VignettePulse {
+    pub fn add(&mut self, strength: f32) {
+        self.strength = (self.strength + strength).clamp(0.0, 1.0);
+    }
+}
+
+const VIGNETTE_PULSE_DECAY_PER_SECOND: f32 = 2.0;
+
+
+pub fn spawn_grading_zone(commands: &mut Commands, position: Vec2, config: GradingZoneConfig) -> Entity {
+    commands.spawn((
+        Transform::from_translation(position.extend(0.0)),
+        GradingZoneComponent { config },
+    )).id()
+}
+
+// Black borders fading to a clear middle, its alpha is the strength of the vignette
+fn vignette_image() -> Image {
+    let size = VIGNETTE_TEXTURE_SIZE;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let offset = (Vec2::new(x as f32, y as f32) + 0.5) / size as f32 * 2.0 - 1.0;
+            let t = ((offset.length() - 0.5) / 0.9).clamp(0.0, 1.0);
+            let alpha = t * t * (3.0 - 2.0 * t);
+            data.extend_from_slice(&[0, 0, 0, (alpha * 255.0) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+
+// SYSTEMS
+
+fn setup_vignette(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.spawn((
+        Vignette,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        ImageNode::new(images.add(vignette_image())).with_color(Color::WHITE.with_alpha(0.0)),
+        // Under the hit flash and the other interfaces
+        GlobalZIndex(-2),
+    ));
+}
+
+// Bloom need the hdr target
+fn prepare_grading_camera(
+    mut commands: Commands,
+    mut camera_query: Query<(Entity, &mut Camera), Added<GameCamera>>,
+) {
+    for (entity, mut camera) in camera_query.iter_mut() {
+        camera.hdr = true;
+        commands.entity(entity).insert((
+            ColorGrading::default(),
+            Bloom { intensity: 0.0, ..Bloom::NATURAL },
+            Tonemapping::None,
+        ));
+    }
+}
+
+// Report the presets that would not look like they are written as soon as the file is loaded or edited
+fn post_process_presets_update_system(
+    mut ev_asset: EventReader<AssetEvent<PostProcessPresets>>,
+    presets_asset: Res<Assets<PostProcessPresets>>,
+) {
+    for event in ev_asset.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = event else {
+            continue;
+        };
+        let Some(presets) = presets_asset.get(*id) else {
+            continue;
+        };
+
+        if !presets.presets.contains_key(&presets.default) {
+            error!("the default post-processing preset `{}` is not in the presets", presets.default);
+        }
+        let mut names: Vec<&String> = presets.presets.keys().collect();
+        names.sort();
+        for name in names.into_iter().filter(|name| presets.presets[*name].is_lost()) {
+            warn!("the post-processing preset `{}` has grading or bloom without a lut, only its vignette is seen", name);
+        }
+    }
+}
+
+fn blend_post_process(
+    time: Res<Time>,
+    app_state: Res<State<AppState>>,
+    preferences: Option<Res<CameraPreferences>>,
+    global_assets: Option<Res<GlobalAsset>>,
+    presets_asset: Res<Assets<PostProcessPresets>>,
+    mut blend: ResMut<GradingBlend>,
+    zone_query: Query<(&GlobalTransform, &GradingZoneComponent)>,
+    camera_query: Query<&Transform, With<GameCamera>>,
+) {
+    let presets = global_assets.as_ref().and_then(|global_assets| presets_asset.get(&global_assets.post_process));
+    let neutral = preferences.map_or(false, |preferences| preferences.neutral_rendering);
+
+    let wanted = match (presets, camera_query.get_single()) {
+        (Some(presets), Ok(transform)) if !neutral && *app_state.get() == AppState::InGame => {
+            let zones = zone_query.iter().map(|(zone_transform, zone)| (zone.config.rect(zone_transform.translation().truncate()), &zone.config));
+            let name = ZoneRect::smallest_containing(zones, transform.translation.truncate())
+                .map_or(&presets.default, |zone| &zone.preset);
+            match presets.presets.get(name) {
+                Some(preset) => preset.clone(),
+                None => {
+                    if blend.missing.as_ref() != Some(name) {
+                        error!("there is no post-processing preset `{}`, rendering it neutral", name);
+                        blend.missing = Some(name.clone());
+                    }
+                    PostProcessPreset::default()
+                }
+            }
+        }
+        _ => PostProcessPreset::default(),
+    };
+
+    let seconds = presets.map_or(0.0, |presets| presets.transition_seconds);
+    if wanted != blend.to || blend.progress < 1.0 {
+        blend.advance(wanted, time.delta_secs(), seconds);
+    }
+}
+
+fn decay_vignette_pulse(time: Res<Time>, mut pulse: ResMut<VignettePulse>) {
+    if pulse.strength > 0.0 {
+        pulse.strength = (pulse.strength - VIGNETTE_PULSE_DECAY_PER_SECOND * time.delta_secs()).max(0.0);
+    }
+}
+
+fn apply_post_process(
+    blend: Res<GradingBlend>,
+    pulse: Res<VignettePulse>,
+    mut camera_query: Query<(&mut ColorGrading, &mut Bloom, &mut Tonemapping), With<GameCamera>>,
+    mut vignette_query: Query<&mut ImageNode, With<Vignette>>,
+) {
+    if !blend.is_changed() && !pulse.is_changed() {
+        return;
+    }
+    let preset = &blend.current;
+
+    for (mut grading, mut bloom, mut tonemapping) in camera_query.iter_mut() {
+        *grading = preset.color_grading();
+        bloom.intensity = preset.bloom;
+        if *tonemapping != preset.lut.tonemapping() {
+            *tonemapping = preset.lut.tonemapping();
+        }
+    }
+    for mut image in vignette_query.iter_mut() {
+        image.color = Color::WHITE.with_alpha((preset.vignette + pulse.strength).clamp(0.0, 1.0));
+    }
+}
+
+
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GradingBlend>();
+        app.init_resource::<VignettePulse>();
+        app.add_systems(Startup, setup_vignette);
+        app.add_systems(Update, (
+            prepare_grading_camera,
+            post_process_presets_update_system,
+            blend_post_process,
+            decay_vignette_pulse,
+            apply_post_process.after(blend_post_process).after(decay_vignette_pulse).after(prepare_grading_camera),
+        ));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dark() -> PostProcessPreset {
+        PostProcessPreset { lut: GradingLut::AgX, exposure: -2.0, saturation: 0.5, vignette: 0.8, bloom: 0.2, ..default() }
+    }
+
+    #[test]
+    fn test_lerp_mix_the_values() {
+        let neutral = PostProcessPreset::default();
+        assert_eq!(neutral.lerp(&dark(), 0.0), neutral);
+        assert_eq!(neutral.lerp(&dark(), 1.0), dark());
+
+        let half = neutral.lerp(&dark(), 0.5);
+        assert_eq!(half.exposure, -1.0);
+        assert_eq!(half.saturation, 0.75);
+        assert_eq!(half.vignette, 0.4);
+    }
+
+    #[test]
+    fn test_lerp_swap_the_lut_in_the_middle() {
+        let neutral = PostProcessPreset::default();
+        assert_eq!(neutral.lerp(&dark(), 0.49).lut, GradingLut::Neutral);
+        assert_eq!(neutral.lerp(&dark(), 0.5).lut, GradingLut::AgX);
+    }
+
+    #[test]
+    fn test_blend_reach_the_target_over_the_transition() {
+        let mut blend = GradingBlend::default();
+        blend.advance(dark(), 0.5, 2.0);
+        assert!(blend.current.exposure < 0.0 && blend.current.exposure > -2.0);
+        blend.advance(dark(), 2.0, 2.0);
+        assert_eq!(blend.progress, 1.0);
+        assert_eq!(blend.current, dark());
+    }
+
+    #[test]
+    fn test_blend_without_transition_is_instant() {
+        let mut blend = GradingBlend::default();
+        blend.advance(dark(), 0.016, 0.0);
+        assert_eq!(blend.current, dark());
+    }
+
+    #[test]
+    fn test_blend_new_target_start_from_the_screen() {
+        let mut blend = GradingBlend::default();
+        blend.advance(dark(), 1.0, 2.0);
+        let on_screen = blend.current.clone();
+
+        // Back to neutral in the middle of the transition, nothing jump
+        blend.advance(PostProcessPreset::default(), 0.0, 2.0);
+        assert_eq!(blend.from, on_screen);
+        assert_eq!(blend.current, on_screen);
+        blend.advance(PostProcessPreset::default(), 2.0, 2.0);
+        assert_eq!(blend.current, PostProcessPreset::default());
+    }
+}
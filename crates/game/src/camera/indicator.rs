@@ -0,0 +1,322 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{hud::{options::HudPreferences, window_to_ui}, character::{health::Health, player::{LocalPlayer, Player}}, lobby::{identity::PlayerIdentity, voice::{VoiceActivity, VoiceState}}, rules::deathmatch::Respawning};
+
+use super::{CameraMode, CameraSettings, GameCamera, Rect};
+
+// Status of the other players, an arrow on the edge of the screen in world space
+// when they are off-screen and a nameplate in screen space with their name, health
+// and distance. The nameplate follow the player and stick to the edge of the screen.
+// A speaker next to the name when the voice activity say the player is talking.
+
+const NAMEPLATE_WIDTH: f32 = 90.0;
+const NAMEPLATE_HEIGHT: f32 = 36.0;
+const HEALTH_BAR_HEIGHT: f32 = 5.0;
+// World units shown as one meter in the distance indicator
+pub(super) const UNITS_PER_METER: f32 = 32.0;
+
+// Marker for indicator arrows
+#[derive(Component)]
+pub struct PlayerIndicator {
+    pub player_entity: Entity,
+}
+
+#[derive(Component)]
+pub struct Nameplate {
+    pub player_entity: Entity,
+    pub alpha: f32,
+    name: Entity,
+    voice: Entity,
+    health_background: Entity,
+    health_fill: Entity,
+    distance: Entity,
+}
+
+fn spawn_player_status(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    settings: &CameraSettings,
+    player_entity: Entity,
+    player: &Player,
+    name: String,
+) {
+    commands.spawn((
+        Sprite {
+            color: player.color,
+            custom_size: Some(Vec2::splat(settings.indicator_size)),
+            ..default()
+        },
+        Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
+        Visibility::Hidden,
+        PlayerIndicator { player_entity },
+    ));
+
+    let text_font = TextFont { font: font.clone(), font_size: 12.0, ..default() };
+
+    let root = commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(NAMEPLATE_WIDTH),
+            height: Val::Px(NAMEPLATE_HEIGHT),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        Visibility::Hidden,
+    )).id();
+
+    let name_row = commands.spawn(Node { column_gap: Val::Px(4.0), ..default() }).id();
+    let name = commands.spawn((Text::new(name), text_font.clone(), TextColor(player.color))).id();
+    let voice = commands.spawn((Text::new(""), text_font.clone(), TextColor(Color::WHITE))).id();
+    let health_background = commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Px(HEALTH_BAR_HEIGHT),
+            ..default()
+        },
+        BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+    )).id();
+    let health_fill = commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgb(0.2, 0.9, 0.2)),
+    )).id();
+    let distance = commands.spawn((Text::new(""), text_font, TextColor(Color::WHITE))).id();
+
+    commands.entity(name_row).add_children(&[name, voice]);
+    commands.entity(health_background).add_child(health_fill);
+    commands.entity(root)
+        .add_children(&[name_row, health_background, distance])
+        .insert(Nameplate { player_entity, alpha: 1.0, name, voice, health_background, health_fill, distance });
+}
+
+// Create the status of the players that joined and remove the one of the players that are gone
+pub fn sync_player_status_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<CameraSettings>,
+    identities: Res<PlayerIdentity>,
+    player_query: Query<(Entity, &Player), Without<LocalPlayer>>,
+    nameplate_query: Query<(Entity, &Nameplate)>,
+    indicator_query: Query<(Entity, &PlayerIndicator)>,
+) {
+    let mut tracked = HashSet::new();
+    for (entity, nameplate) in nameplate_query.iter() {
+        if player_query.contains(nameplate.player_entity) {
+            tracked.insert(nameplate.player_entity);
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for (entity, indicator) in indicator_query.iter() {
+        if !player_query.contains(indicator.player_entity) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    for (player_entity, player) in player_query.iter() {
+        if tracked.contains(&player_entity) {
+            continue;
+        }
+        spawn_player_status(&mut commands, &font, &settings, player_entity, player, identities.name(player.handle));
+    }
+}
+
+// Visible screen rectangle in world space
+pub(super) fn visible_world_rect(window: &Window, camera_transform: &Transform, projection: &OrthographicProjection) -> Rect {
+    let camera_pos = camera_transform.translation.truncate();
+    let half_size = Vec2::new(window.width(), window.height()) * projection.scale / 2.0;
+    Rect { min: camera_pos - half_size, max: camera_pos + half_size }
+}
+
+// Screen position of a widget of `half_size` kept inside the window
+pub(super) fn clamp_to_screen(viewport_pos: Vec2, window: &Window, half_size: Vec2, edge_margin: f32) -> Vec2 {
+    let screen_size = Vec2::new(window.width(), window.height());
+    let margin = half_size + edge_margin;
+    viewport_pos.clamp(margin, (screen_size - margin).max(margin))
+}
+
+// Position of the arrow on the edge of the visible area, pointing toward the player
+pub(super) fn edge_indicator_position(visible_rect: &Rect, camera_pos: Vec2, player_pos: Vec2, edge_distance: f32) -> (Vec2, f32) {
+    let relative_pos = player_pos - camera_pos;
+    let angle_to_player = relative_pos.y.atan2(relative_pos.x);
+    let angle_tangent = angle_to_player.tan();
+
+    let indicator_pos = if angle_to_player.abs() < std::f32::consts::PI / 4.0 {
+        // Right side of screen
+        Vec2::new(visible_rect.max.x - edge_distance, camera_pos.y + (visible_rect.max.x - camera_pos.x) * angle_tangent)
+    } else if angle_to_player.abs() > 3.0 * std::f32::consts::PI / 4.0 {
+        // Left side of screen
+        Vec2::new(visible_rect.min.x + edge_distance, camera_pos.y + (camera_pos.x - visible_rect.min.x) * angle_tangent)
+    } else if angle_to_player > 0.0 {
+        // Top side of screen
+        Vec2::new(camera_pos.x + (visible_rect.max.y - camera_pos.y) / angle_tangent, visible_rect.max.y - edge_distance)
+    } else {
+        // Bottom side of screen
+        Vec2::new(camera_pos.x + (camera_pos.y - visible_rect.min.y) / angle_tangent, visible_rect.min.y + edge_distance)
+    };
+
+    // Make sure the indicator is within the screen bounds
+    let clamped_pos = Vec2::new(
+        indicator_pos.x.clamp(visible_rect.min.x + edge_distance, visible_rect.max.x - edge_distance),
+        indicator_pos.y.clamp(visible_rect.min.y + edge_distance, visible_rect.max.y - edge_distance),
+    );
+    (clamped_pos, angle_to_player)
+}
+
+// Move the arrows and the nameplates of the other players
+pub fn player_status_system(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    identities: Res<PlayerIdentity>,
+    voice_activity: Res<VoiceActivity>,
+    hud_preferences: Res<HudPreferences>,
+    ui_scale: Res<UiScale>,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform, &GameCamera, &Transform, &OrthographicProjection)>,
+    local_query: Query<&Transform, (With<LocalPlayer>, With<Player>)>,
+    player_query: Query<(&Transform, &Health, &Player, Has<Respawning>)>,
+    mut indicator_query: Query<(&PlayerIndicator, &mut Transform, &mut Visibility), (Without<Player>, Without<GameCamera>)>,
+    mut nameplate_query: Query<(&mut Nameplate, &mut Node, &mut Visibility), Without<PlayerIndicator>>,
+    mut node_query: Query<&mut Node, Without<Nameplate>>,
+    mut text_query: Query<(&mut Text, &mut TextColor)>,
+    mut background_query: Query<&mut BackgroundColor>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_global, game_camera, camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+
+    let camera_pos = camera_transform.translation.truncate();
+    let visible_rect = visible_world_rect(window, camera_transform, projection);
+
+    for (indicator, mut transform, mut visibility) in indicator_query.iter_mut() {
+        let Ok((player_transform, _, _, respawning)) = player_query.get(indicator.player_entity) else {
+            continue;
+        };
+        let player_pos = player_transform.translation.truncate();
+
+        // Skip the currently targeted player in player lock mode
+        let targeted = game_camera.mode == CameraMode::PlayerLock && game_camera.target_player_id == Some(indicator.player_entity);
+        if targeted || respawning || visible_rect.contains(player_pos) {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        let (position, angle) = edge_indicator_position(&visible_rect, camera_pos, player_pos, settings.indicator_edge_distance);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        transform.rotation = Quat::from_rotation_z(angle);
+        *visibility = Visibility::Inherited;
+    }
+
+    let local_pos = local_query.get_single().ok().map(|transform| transform.translation.truncate());
+    // In pixels of the window like the viewport position
+    let nameplate_half_size = Vec2::new(NAMEPLATE_WIDTH, NAMEPLATE_HEIGHT) / 2.0 * ui_scale.0;
+
+    for (mut nameplate, mut node, mut visibility) in nameplate_query.iter_mut() {
+        let Ok((player_transform, health, player, respawning)) = player_query.get(nameplate.player_entity) else {
+            continue;
+        };
+        if respawning {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        let player_pos = player_transform.translation.truncate();
+        let head = player_pos + Vec2::Y * settings.nameplate_offset;
+        let Ok(viewport_pos) = camera.world_to_viewport(camera_global, head.extend(0.0)) else {
+            continue;
+        };
+        let on_screen = visible_rect.contains(player_pos);
+        let screen_pos = clamp_to_screen(viewport_pos, window, nameplate_half_size, settings.nameplate_edge_margin);
+
+        let ui_pos = window_to_ui(screen_pos, &ui_scale);
+        node.left = Val::Px(ui_pos.x - NAMEPLATE_WIDTH / 2.0);
+        node.top = Val::Px(ui_pos.y - NAMEPLATE_HEIGHT / 2.0);
+
+        // Fade the teammates that don't need attention
+        let ratio = if health.max > 0.0 { (health.current / health.max).clamp(0.0, 1.0) } else { 0.0 };
+        let target_alpha = if on_screen && ratio >= 1.0 { settings.nameplate_full_health_alpha } else { 1.0 };
+        let step = settings.nameplate_fade_speed * time.delta().as_secs_f32();
+        nameplate.alpha += (target_alpha - nameplate.alpha).clamp(-step, step);
+        let alpha = nameplate.alpha;
+
+        if let Ok(mut fill_node) = node_query.get_mut(nameplate.health_fill) {
+            fill_node.width = Val::Percent(ratio * 100.0);
+        }
+        if let Ok(mut fill) = background_query.get_mut(nameplate.health_fill) {
+            fill.0 = Color::srgb(0.9 - 0.7 * ratio, 0.2 + 0.7 * ratio, 0.2).with_alpha(alpha);
+        }
+        if let Ok(mut background) = background_query.get_mut(nameplate.health_background) {
+            background.0 = background.0.with_alpha(alpha * 0.8);
+        }
+        if let Ok((_, mut color)) = text_query.get_mut(nameplate.name) {
+            color.0 = color.0.with_alpha(alpha);
+        }
+        if let Ok((mut text, mut color)) = text_query.get_mut(nameplate.voice) {
+            let state = VoiceState::of(player.handle, &voice_activity, &hud_preferences.voice, &identities);
+            text.0 = state.mark().to_string();
+            // Always readable, someone talking is worth the attention
+            color.0 = if state == VoiceState::Muted { Color::srgb(0.6, 0.6, 0.6) } else { Color::WHITE };
+        }
+        if let Ok((mut text, mut color)) = text_query.get_mut(nameplate.distance) {
+            text.0 = local_pos.map_or(String::new(), |local_pos| format!("{:.0}m", local_pos.distance(player_pos) / UNITS_PER_METER));
+            color.0 = color.0.with_alpha(alpha);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::window::WindowResolution;
+
+    use super::*;
+
+    fn window(width: f32, height: f32) -> Window {
+        Window { resolution: WindowResolution::new(width, height), ..default() }
+    }
+
+    #[test]
+    fn test_clamp_to_screen_keep_the_widget_inside() {
+        let window = window(1280.0, 720.0);
+        let half_size = Vec2::new(40.0, 20.0);
+        // Already inside
+        assert_eq!(clamp_to_screen(Vec2::new(640.0, 360.0), &window, half_size, 10.0), Vec2::new(640.0, 360.0));
+        // Past the corners, the margin is the half size and the edge margin
+        assert_eq!(clamp_to_screen(Vec2::new(-500.0, -500.0), &window, half_size, 10.0), Vec2::new(50.0, 30.0));
+        assert_eq!(clamp_to_screen(Vec2::new(5000.0, 5000.0), &window, half_size, 10.0), Vec2::new(1230.0, 690.0));
+    }
+
+    #[test]
+    fn test_clamp_to_screen_smaller_than_the_widget() {
+        // No room, the widget stick to the top left margin instead of panicking
+        let window = window(60.0, 30.0);
+        assert_eq!(clamp_to_screen(Vec2::new(30.0, 15.0), &window, Vec2::new(40.0, 20.0), 10.0), Vec2::new(50.0, 30.0));
+    }
+
+    #[test]
+    fn test_visible_world_rect_follow_the_camera_and_the_zoom() {
+        let window = window(1280.0, 720.0);
+        let camera = Transform::from_xyz(100.0, -50.0, 0.0);
+        let rect = visible_world_rect(&window, &camera, &OrthographicProjection::default_2d());
+        assert_eq!(rect.min, Vec2::new(-540.0, -410.0));
+        assert_eq!(rect.max, Vec2::new(740.0, 310.0));
+
+        // Zoomed out, twice the world is visible
+        let projection = OrthographicProjection { scale: 2.0, ..OrthographicProjection::default_2d() };
+        let rect = visible_world_rect(&window, &camera, &projection);
+        assert_eq!(rect.min, Vec2::new(-1180.0, -770.0));
+        assert_eq!(rect.max, Vec2::new(1380.0, 670.0));
+        assert!(rect.contains(Vec2::new(1000.0, 0.0)));
+        assert!(!rect.contains(Vec2::new(1400.0, 0.0)));
+    }
+}
@@ -0,0 +1,245 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{character::player::LocalPlayer, door::Door, hud::window_to_ui, plugins::AppState, points::PlayerPoints, rules::objective::Generator};
+
+use super::{camera_control_system, indicator::{clamp_to_screen, edge_indicator_position, visible_world_rect, UNITS_PER_METER}, CameraSettings, GameCamera};
+
+// Markers of the objectives in the world, only presentation. Any entity with a transform
+// can carry an `ObjectiveMarker`, an icon with its distance follow it on screen and stick
+// to the edges with an arrow like the ones of the other players when it is off-screen.
+// The game modes put them on their objectives, removing the component remove the marker.
+
+const MARKER_ICON_SIZE: f32 = 22.0;
+const MARKER_WIDTH: f32 = 80.0;
+const MARKER_HEIGHT: f32 = 40.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum ObjectiveKind {
+    Generator,
+    Door,
+}
+
+impl ObjectiveKind {
+    fn glyph(&self) -> &'static str {
+        match self {
+            ObjectiveKind::Generator => "G",
+            ObjectiveKind::Door => "O",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            ObjectiveKind::Generator => Color::srgb(0.9, 0.8, 0.2),
+            ObjectiveKind::Door => Color::srgb(0.35, 0.7, 0.95),
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct ObjectiveMarker {
+    pub kind: ObjectiveKind,
+    // World units above the anchor
+    pub offset: f32,
+}
+
+impl ObjectiveMarker {
+    pub fn new(kind: ObjectiveKind) -> Self {
+        Self { kind, offset: 0.0 }
+    }
+
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+// Put by the game modes, they remove only their own
+#[derive(Component)]
+struct ModeMarker;
+
+// On screen part of a marker, with the arrow in world space
+#[derive(Component)]
+struct MarkerWidget {
+    anchor: Entity,
+    arrow: Entity,
+    distance: Entity,
+}
+
+fn spawn_marker_widget(commands: &mut Commands, font: &Handle<Font>, settings: &CameraSettings, anchor: Entity, marker: &ObjectiveMarker) {
+    let color = marker.kind.color();
+    let arrow = commands.spawn((
+        Sprite {
+            color,
+            custom_size: Some(Vec2::splat(settings.indicator_size)),
+            ..default()
+        },
+        Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
+        Visibility::Hidden,
+    )).id();
+
+    let text_font = TextFont { font: font.clone(), font_size: 12.0, ..default() };
+    let root = commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(MARKER_WIDTH),
+            height: Val::Px(MARKER_HEIGHT),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        Visibility::Hidden,
+    )).id();
+    let icon = commands.spawn((
+        Node {
+            width: Val::Px(MARKER_ICON_SIZE),
+            height: Val::Px(MARKER_ICON_SIZE),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(color.with_alpha(0.8)),
+        BorderRadius::all(Val::Px(MARKER_ICON_SIZE / 2.0)),
+    )).id();
+    let glyph = commands.spawn((Text::new(marker.kind.glyph()), text_font.clone(), TextColor(Color::BLACK))).id();
+    let distance = commands.spawn((Text::new(""), text_font, TextColor(Color::WHITE))).id();
+
+    commands.entity(icon).add_child(glyph);
+    commands.entity(root)
+        .add_children(&[icon, distance])
+        .insert(MarkerWidget { anchor, arrow, distance });
+}
+
+
+// SYSTEMS
+
+// Objectives of the current mode, the generator to defend and the closed doors the local player can pay
+fn sync_mode_markers(
+    mut commands: Commands,
+    generator_query: Query<Entity, (With<Generator>, Without<ObjectiveMarker>)>,
+    door_query: Query<(Entity, &Door, Has<ObjectiveMarker>, Has<ModeMarker>)>,
+    local_query: Query<&PlayerPoints, With<LocalPlayer>>,
+) {
+    for entity in generator_query.iter() {
+        commands.entity(entity).insert((ObjectiveMarker::new(ObjectiveKind::Generator), ModeMarker));
+    }
+
+    let points = local_query.iter().map(|points| points.current).max();
+    for (entity, door, marked, ours) in door_query.iter() {
+        let wanted = !door.open && points.map_or(false, |points| points >= door.cost);
+        if wanted && !marked {
+            commands.entity(entity).insert((ObjectiveMarker::new(ObjectiveKind::Door), ModeMarker));
+        } else if !wanted && ours {
+            commands.entity(entity).remove::<(ObjectiveMarker, ModeMarker)>();
+        }
+    }
+}
+
+// Create the widgets of the new markers and remove the ones of the markers that are gone
+fn sync_marker_widgets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<CameraSettings>,
+    marker_query: Query<(Entity, &ObjectiveMarker)>,
+    widget_query: Query<(Entity, &MarkerWidget)>,
+) {
+    let mut tracked = HashSet::new();
+    for (entity, widget) in widget_query.iter() {
+        if marker_query.contains(widget.anchor) {
+            tracked.insert(widget.anchor);
+        } else {
+            commands.entity(entity).despawn_recursive();
+            commands.entity(widget.arrow).despawn();
+        }
+    }
+
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    for (entity, marker) in marker_query.iter() {
+        if !tracked.contains(&entity) {
+            spawn_marker_widget(&mut commands, &font, &settings, entity, marker);
+        }
+    }
+}
+
+fn marker_widget_system(
+    settings: Res<CameraSettings>,
+    ui_scale: Res<UiScale>,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform, &Transform, &OrthographicProjection), With<GameCamera>>,
+    local_query: Query<&GlobalTransform, With<LocalPlayer>>,
+    marker_query: Query<(&ObjectiveMarker, &GlobalTransform)>,
+    mut widget_query: Query<(&MarkerWidget, &mut Node, &mut Visibility)>,
+    mut arrow_query: Query<(&mut Transform, &mut Visibility), (Without<MarkerWidget>, Without<GameCamera>)>,
+    mut text_query: Query<&mut Text>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_global, camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+    let camera_pos = camera_transform.translation.truncate();
+    let visible_rect = visible_world_rect(window, camera_transform, projection);
+    // From the local player, from the middle of the screen for a spectator
+    let origin = local_query.get_single().map_or(camera_pos, |transform| transform.translation().truncate());
+    // In pixels of the window like the viewport position
+    let half_size = Vec2::new(MARKER_WIDTH, MARKER_HEIGHT) / 2.0 * ui_scale.0;
+
+    for (widget, mut node, mut visibility) in widget_query.iter_mut() {
+        let Ok((marker, transform)) = marker_query.get(widget.anchor) else {
+            continue;
+        };
+        let position = transform.translation().truncate() + Vec2::Y * marker.offset;
+        let Ok(viewport_pos) = camera.world_to_viewport(camera_global, position.extend(0.0)) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+
+        let screen_pos = clamp_to_screen(viewport_pos, window, half_size, settings.nameplate_edge_margin);
+        let ui_pos = window_to_ui(screen_pos, &ui_scale);
+        node.left = Val::Px(ui_pos.x - MARKER_WIDTH / 2.0);
+        node.top = Val::Px(ui_pos.y - MARKER_ICON_SIZE / 2.0);
+
+        if let Ok(mut text) = text_query.get_mut(widget.distance) {
+            text.0 = format!("{:.0}m", origin.distance(position) / UNITS_PER_METER);
+        }
+
+        let Ok((mut arrow_transform, mut arrow_visibility)) = arrow_query.get_mut(widget.arrow) else {
+            continue;
+        };
+        if visible_rect.contains(position) {
+            *arrow_visibility = Visibility::Hidden;
+            continue;
+        }
+        let (edge, angle) = edge_indicator_position(&visible_rect, camera_pos, position, settings.indicator_edge_distance);
+        arrow_transform.translation.x = edge.x;
+        arrow_transform.translation.y = edge.y;
+        arrow_transform.rotation = Quat::from_rotation_z(angle);
+        *arrow_visibility = Visibility::Inherited;
+    }
+}
+
+// The widgets belong to the match
+fn clear_objective_markers(
+    mut commands: Commands,
+    widget_query: Query<(Entity, &MarkerWidget)>,
+) {
+    for (entity, widget) in widget_query.iter() {
+        commands.entity(entity).despawn_recursive();
+        commands.entity(widget.arrow).despawn();
+    }
+}
+
+
+pub struct ObjectiveMarkerPlugin;
+
+impl Plugin for ObjectiveMarkerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+            sync_mode_markers,
+            sync_marker_widgets.after(sync_mode_markers),
+            marker_widget_system.after(sync_marker_widgets).after(camera_control_system),
+        ).run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), clear_objective_markers);
+    }
+}
@@ -1,14 +1,28 @@
+pub mod background;
+pub mod grading;
+pub mod indicator;
+pub mod marker;
+pub mod options;
+pub mod shake;
+pub mod spectator;
 pub mod ui;
 
 
 use bevy::prelude::*;
 use bevy_common_assets::ron::RonAssetPlugin;
-use bevy_kira_audio::SpatialAudioReceiver;
 use leafwing_input_manager::prelude::*;
 use serde::{Deserialize, Serialize};
+use utils::aim::CameraView;
+use background::ChunkedBackgroundPlugin;
+use grading::PostProcessPlugin;
+use indicator::{player_status_system, sync_player_status_system};
+use marker::ObjectiveMarkerPlugin;
+use options::{CameraOptionsPlugin, CameraPreferences};
+use shake::CameraShakePlugin;
+use spectator::{Spectating, SpectatorCameraPlugin};
 use ui::CameraDebugUIPlugin;
 
-use crate::{character::player::{control::PlayerAction, LocalPlayer, Player}, plugins::AppState};
+use crate::{character::player::{control::PlayerAction, LocalPlayer, Player}, plugins::AppState, practice::killcam::KillCamReplay, web::{pointer_position, PointerLock}};
 
 #[derive(Asset, TypePath, Debug, Clone, Deserialize, Serialize)]
 pub struct CameraSettingsAsset(pub CameraSettings);
@@ -20,12 +34,19 @@ impl Plugin for CameraControlPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CameraSettings>()
             .add_plugins(CameraDebugUIPlugin)
+            .add_plugins(ChunkedBackgroundPlugin)
+            .add_plugins(SpectatorCameraPlugin)
+            .add_plugins(CameraOptionsPlugin)
+            .add_plugins(PostProcessPlugin)
+            .add_plugins(CameraShakePlugin)
+            .add_plugins(ObjectiveMarkerPlugin)
             .add_plugins(RonAssetPlugin::<CameraSettingsAsset>::new(&[".ron"]))
-            .add_systems( Startup, (setup_camera, setup_simple_background))
+            .add_systems( Startup, setup_camera)
             .add_systems(Update, (
                 character_visuals_update_system,
-                camera_control_system,
-                player_indicator_system,
+                camera_control_system.run_if(not(resource_exists::<Spectating>)).run_if(not(resource_exists::<KillCamReplay>)),
+                sync_player_status_system,
+                player_status_system.after(sync_player_status_system).after(camera_control_system),
                 camera_input_system,
                 
             ));
@@ -54,6 +75,14 @@ pub struct CameraSettings {
     pub indicator_edge_distance: f32,
     // Whether to use screen edge detection for camera movement
     pub use_edge_detection: bool,
+    // Height of the nameplates above the other players, in world units
+    pub nameplate_offset: f32,
+    // Opacity of the nameplate of a visible player at full health
+    pub nameplate_full_health_alpha: f32,
+    // Opacity change per second
+    pub nameplate_fade_speed: f32,
+    // Distance in pixels between an off-screen nameplate and the screen edge
+    pub nameplate_edge_margin: f32,
 }
 
 impl Default for CameraSettings {
@@ -69,6 +98,10 @@ impl Default for CameraSettings {
             indicator_size: 20.0,
             indicator_edge_distance: 20.0,
             use_edge_detection: true,
+            nameplate_offset: 40.0,
+            nameplate_full_health_alpha: 0.3,
+            nameplate_fade_speed: 3.0,
+            nameplate_edge_margin: 10.0,
         }
     }
 }
@@ -96,10 +129,14 @@ pub struct GameCamera {
     pub target_zoom: f32,
 }
 
-// Marker for indicator arrows
-#[derive(Component)]
-pub struct PlayerIndicator {
-    pub player_entity: Entity,
+// What the game camera show, the 2D projection keep its default of a world unit per
+// logical pixel at scale 1 so the scale is the zoom
+pub fn camera_view(window: &Window, transform: &Transform, projection: &OrthographicProjection) -> CameraView {
+    CameraView {
+        center: transform.translation.truncate(),
+        scale: projection.scale,
+        window_size: window.size(),
+    }
 }
 
 // System to handle camera input 
@@ -165,10 +202,11 @@ fn camera_input_system(
 
 
 // Main camera control system
-fn camera_control_system(
+pub fn camera_control_system(
     time: Res<Time>,
     settings: Res<CameraSettings>,
     windows: Query<&Window>,
+    pointer_lock: Res<PointerLock>,
     action_query: Query<&ActionState<PlayerAction>>,
     mut camera_query: Query<(&mut GameCamera, &mut Transform, &mut OrthographicProjection), Without<Player>>,
     player_query: Query<(Entity, &Transform, &Player, Option<&LocalPlayer>), Without<GameCamera>>,
@@ -178,7 +216,7 @@ fn camera_control_system(
     let window_size = Vec2::new(window.width(), window.height());
     
     // Get mouse position normalized to -1.0 to 1.0 range
-    let mouse_position = if let Some(position) = window.cursor_position() {
+    let mouse_position = if let Some(position) = pointer_position(window, &pointer_lock) {
         Vec2::new(
             (position.x / window.width()) * 2.0 - 1.0,
             ((window.height() - position.y) / window.height()) * 2.0 - 1.0,
@@ -334,134 +372,12 @@ fn camera_control_system(
     projection.scale = new_zoom;
 }
 
-// System to handle player indicators for off-screen players
-fn player_indicator_system(
-    mut commands: Commands,
-    settings: Res<CameraSettings>,
-    windows: Query<&Window>,
-    camera_query: Query<(&GameCamera, &Transform, &OrthographicProjection)>,
-    player_query: Query<(Entity, &Transform, &Player)>,
-    indicator_query: Query<Entity, With<PlayerIndicator>>,
-) {
-    // Remove all existing indicators
-    for entity in indicator_query.iter() {
-        commands.entity(entity).despawn();
-    }
-
-    let window = windows.get_single().unwrap();
-    let (camera, camera_transform, projection) = if let Ok(cam) = camera_query.get_single() {
-        cam
-    } else {
-        return;
-    };
-
-    // Calculate visible screen rectangle in world space
-    let camera_pos = camera_transform.translation.truncate();
-    let half_width = (window.width() * projection.scale) / 2.0;
-    let half_height = (window.height() * projection.scale) / 2.0;
-    
-    let visible_rect = Rect {
-        min: Vec2::new(camera_pos.x - half_width, camera_pos.y - half_height),
-        max: Vec2::new(camera_pos.x + half_width, camera_pos.y + half_height),
-    };
-
-    // Check each player and create indicators for those off-screen
-    for (player_entity, player_transform, player_info) in player_query.iter() {
-        // Skip the currently targeted player in player lock mode
-        if camera.mode == CameraMode::PlayerLock && camera.target_player_id == Some(player_entity) {
-            continue;
-        }
-
-        let player_pos = player_transform.translation.truncate();
-
-        // Check if player is outside the visible area
-        if !visible_rect.contains(player_pos) {
-            // Determine where to place the indicator based on player position relative to screen
-            
-            // Calculate relative position to screen
-            let relative_pos = player_pos - camera_pos;
-            let angle_to_player = relative_pos.y.atan2(relative_pos.x);
-            
-            // Determine which edge to place the indicator on
-            // We'll use the angle to determine the closest edge
-            
-            // Calculate aspect ratio to handle rectangular screens properly
-            let aspect_ratio = window.width() / window.height();
-            
-            // Calculate normalized slope based on angle
-            let angle_tangent = angle_to_player.tan();
-            let normalized_tangent = angle_tangent / aspect_ratio;
-            
-            // Determine which side of the screen to place the indicator
-            let (indicator_pos, final_angle) = if angle_to_player.abs() < std::f32::consts::PI / 4.0 {
-                // Right side of screen
-                (
-                    Vec2::new(
-                        visible_rect.max.x - settings.indicator_edge_distance,
-                        camera_pos.y + (visible_rect.max.x - camera_pos.x) * angle_tangent
-                    ),
-                    angle_to_player
-                )
-            } else if angle_to_player.abs() > 3.0 * std::f32::consts::PI / 4.0 {
-                // Left side of screen
-                (
-                    Vec2::new(
-                        visible_rect.min.x + settings.indicator_edge_distance,
-                        camera_pos.y + (camera_pos.x - visible_rect.min.x) * angle_tangent
-                    ),
-                    angle_to_player
-                )
-            } else if angle_to_player > 0.0 {
-                // Top side of screen
-                (
-                    Vec2::new(
-                        camera_pos.x + (visible_rect.max.y - camera_pos.y) / angle_tangent,
-                        visible_rect.max.y - settings.indicator_edge_distance
-                    ),
-                    angle_to_player
-                )
-            } else {
-                // Bottom side of screen
-                (
-                    Vec2::new(
-                        camera_pos.x + (camera_pos.y - visible_rect.min.y) / angle_tangent,
-                        visible_rect.min.y + settings.indicator_edge_distance
-                    ),
-                    angle_to_player
-                )
-            };
-            
-            // Make sure the indicator is within the screen bounds
-            let clamped_pos = Vec2::new(
-                indicator_pos.x.clamp(visible_rect.min.x + settings.indicator_edge_distance, visible_rect.max.x - settings.indicator_edge_distance),
-                indicator_pos.y.clamp(visible_rect.min.y + settings.indicator_edge_distance, visible_rect.max.y - settings.indicator_edge_distance)
-            );
-            
-            // Spawn the indicator
-            commands.spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        color: player_info.color,
-                        custom_size: Some(Vec2::splat(settings.indicator_size)),
-                        ..default()
-                    },
-                    transform: Transform::from_translation(Vec3::new(clamped_pos.x, clamped_pos.y, 10.0))
-                        .with_rotation(Quat::from_rotation_z(final_angle)),
-                    ..default()
-                },
-                PlayerIndicator {
-                    player_entity,
-                },
-            ));
-        }
-    }
-}
-
 fn character_visuals_update_system(
     mut ev_asset: EventReader<AssetEvent<CameraSettingsAsset>>,
     asset_server: Res<AssetServer>,
     camera_asset: Res<Assets<CameraSettingsAsset>>,
     mut r_camera: ResMut<CameraSettings>,
+    preferences: Option<Res<CameraPreferences>>,
     mut camera_query: Query<(&mut GameCamera, &mut Transform, &mut OrthographicProjection)>,
 ) {
     for event in ev_asset.read() {
@@ -475,6 +391,10 @@ fn character_visuals_update_system(
                 *r_camera = camera_settings.0.clone();
             }
         }
+        // The options of the player stay over the asset
+        if let Some(preferences) = preferences.as_ref() {
+            preferences.apply(&mut r_camera);
+        }
     }
 }
 
@@ -498,7 +418,6 @@ pub fn setup_camera(mut commands: Commands, settings: Res<CameraSettings>) {
     println!("CREATING CAMERA");
     commands.spawn((
         Camera2dBundle::default(),
-        SpatialAudioReceiver,
         GameCamera {
             mode: CameraMode::PlayerLock,
             target_player_id: None,
@@ -507,43 +426,3 @@ pub fn setup_camera(mut commands: Commands, settings: Res<CameraSettings>) {
         },
     ));
 }
-
-
-fn setup_simple_background(mut commands: Commands) {
-    // Background parameters
-    let tile_size = 400.0;
-    let grid_size = 20; // This creates a 20x20 grid of tiles
-    
-    // Create a parent entity for all background tiles
-    commands.spawn(SpatialBundle::default())
-        .insert(Name::new("Background"))
-        .with_children(|parent| {
-            // Create a simple checkered pattern
-            for i in -grid_size/2..grid_size/2 {
-                for j in -grid_size/2..grid_size/2 {
-                    // Alternate colors in a checkered pattern
-                    let is_dark = (i + j) % 2 == 0;
-                    let color = if is_dark {
-                        Color::rgb(0.2, 0.2, 0.25) // Dark blue-gray
-                    } else {
-                        Color::rgb(0.3, 0.3, 0.35) // Lighter blue-gray
-                    };
-                    
-                    // Spawn a square sprite
-                    parent.spawn(SpriteBundle {
-                        sprite: Sprite {
-                            color,
-                            custom_size: Some(Vec2::new(tile_size, tile_size)),
-                            ..default()
-                        },
-                        transform: Transform::from_translation(Vec3::new(
-                            i as f32 * tile_size, 
-                            j as f32 * tile_size, 
-                            -10.0 // Behind everything else
-                        )),
-                        ..default()
-                    });
-                }
-            }
-        });
-}
\ No newline at end of file
@@ -0,0 +1,297 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::{EguiContexts, EguiPlugin}, egui};
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::persistence;
+
+use crate::{character::player::{control::PlayerAction, LocalPlayer}, plugins::AppState};
+
+use super::CameraSettings;
+
+// Camera options of the player, saved with the persistence module. They are applied
+// over the settings of the camera asset every time it is loaded, the asset stay the
+// defaults of the game and the panel is what a player change.
+
+pub const CAMERA_OPTIONS_KEY: KeyCode = KeyCode::KeyV;
+const CAMERA_PREFERENCES_SAVE_KEY: &str = "camera";
+
+// Bounds of the sliders
+const MAX_LERP_SPEED: f32 = 20.0;
+const ZOOM_RANGE: (f32, f32) = (1.0, 30.0);
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraPreferences {
+    pub pan_up: KeyCode,
+    pub pan_down: KeyCode,
+    pub pan_left: KeyCode,
+    pub pan_right: KeyCode,
+    pub use_edge_detection: bool,
+    pub lerp_speed: f32,
+    pub min_zoom: f32,
+    pub max_zoom_out: f32,
+    // No color grading, vignette or bloom of the map
+    #[serde(default)]
+    pub neutral_rendering: bool,
+    // No shake of the camera
+    #[serde(default)]
+    pub reduce_motion: bool,
+}
+
+impl Default for CameraPreferences {
+    fn default() -> Self {
+        let settings = CameraSettings::default();
+        Self {
+            pan_up: KeyCode::ArrowUp,
+            pan_down: KeyCode::ArrowDown,
+            pan_left: KeyCode::ArrowLeft,
+            pan_right: KeyCode::ArrowRight,
+            use_edge_detection: settings.use_edge_detection,
+            lerp_speed: settings.lerp_speed,
+            min_zoom: settings.min_zoom,
+            max_zoom_out: settings.max_zoom_out,
+            neutral_rendering: false,
+            reduce_motion: false,
+        }
+    }
+}
+
+impl CameraPreferences {
+    pub fn apply(&self, settings: &mut CameraSettings) {
+        settings.use_edge_detection = self.use_edge_detection;
+        settings.lerp_speed = self.lerp_speed;
+        settings.min_zoom = self.min_zoom;
+        settings.max_zoom_out = self.max_zoom_out.max(self.min_zoom);
+    }
+
+    pub fn pan_bindings(&self) -> [(PlayerAction, KeyCode); 4] {
+        [
+            (PlayerAction::MoveCameraUp, self.pan_up),
+            (PlayerAction::MoveCameraDown, self.pan_down),
+            (PlayerAction::MoveCameraLeft, self.pan_left),
+            (PlayerAction::MoveCameraRight, self.pan_right),
+        ]
+    }
+
+    fn pan_key_mut(&mut self, action: PlayerAction) -> Option<&mut KeyCode> {
+        match action {
+            PlayerAction::MoveCameraUp => Some(&mut self.pan_up),
+            PlayerAction::MoveCameraDown => Some(&mut self.pan_down),
+            PlayerAction::MoveCameraLeft => Some(&mut self.pan_left),
+            PlayerAction::MoveCameraRight => Some(&mut self.pan_right),
+            _ => None,
+        }
+    }
+
+    // Other action already using the key, a pan of the preferences or a binding of the
+    // map that isn't a pan, those are replaced by the preferences
+    pub fn binding_conflict(&self, action: PlayerAction, key: KeyCode, map: Option<&InputMap<PlayerAction>>) -> Option<PlayerAction> {
+        let pans = self.pan_bindings();
+        if let Some((other, _)) = pans.iter().find(|(other, bound)| *other != action && *bound == key) {
+            return Some(*other);
+        }
+        map?.iter_buttonlike()
+            .filter(|(other, _)| !pans.iter().any(|(pan, _)| pan == *other))
+            .find(|(_, bindings)| bindings.iter().any(|binding| binding.as_reflect().downcast_ref::<KeyCode>() == Some(&key)))
+            .map(|(other, _)| *other)
+    }
+
+    // Replace the camera keys of the map, the other bindings are kept
+    pub fn apply_bindings(&self, map: &mut InputMap<PlayerAction>) {
+        for (action, key) in self.pan_bindings() {
+            map.clear_action(&action);
+            map.insert(action, key);
+        }
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = persistence::save(CAMERA_PREFERENCES_SAVE_KEY, self) {
+            error!("failed to save the camera options: {}", err);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct CameraOptionsState {
+    is_open: bool,
+    // Waiting for the key of this action
+    rebinding: Option<PlayerAction>,
+    // Last key refused, with the action that has it
+    conflict: Option<(KeyCode, PlayerAction)>,
+}
+
+fn action_label(action: PlayerAction) -> &'static str {
+    match action {
+        PlayerAction::MoveCameraUp => "Pan up",
+        PlayerAction::MoveCameraDown => "Pan down",
+        PlayerAction::MoveCameraLeft => "Pan left",
+        PlayerAction::MoveCameraRight => "Pan right",
+        _ => "",
+    }
+}
+
+
+// SYSTEMS
+
+fn load_camera_preferences(mut commands: Commands) {
+    let preferences = match persistence::load::<CameraPreferences>(CAMERA_PREFERENCES_SAVE_KEY) {
+        Ok(preferences) => preferences.unwrap_or_default(),
+        Err(err) => {
+            error!("failed to load the camera options: {}", err);
+            CameraPreferences::default()
+        }
+    };
+    commands.insert_resource(preferences);
+}
+
+fn toggle_camera_options(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<CameraOptionsState>,
+) {
+    if state.rebinding.is_none() && keyboard_input.just_pressed(CAMERA_OPTIONS_KEY) {
+        state.is_open = !state.is_open;
+    }
+}
+
+fn rebind_camera_key(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<CameraOptionsState>,
+    mut preferences: ResMut<CameraPreferences>,
+    q_map: Query<&InputMap<PlayerAction>, With<LocalPlayer>>,
+) {
+    let Some(action) = state.rebinding else {
+        return;
+    };
+    let Some(key) = keyboard_input.get_just_pressed().next().copied() else {
+        return;
+    };
+
+    state.rebinding = None;
+    state.conflict = None;
+    // Escape cancel
+    if key == KeyCode::Escape {
+        return;
+    }
+    // The same key would do two things, the binding is kept
+    if let Some(other) = preferences.binding_conflict(action, key, q_map.get_single().ok()) {
+        state.conflict = Some((key, other));
+        return;
+    }
+    if let Some(binding) = preferences.pan_key_mut(action) {
+        *binding = key;
+    }
+    preferences.save();
+}
+
+fn camera_options_panel(
+    mut contexts: EguiContexts,
+    mut state: ResMut<CameraOptionsState>,
+    mut preferences: ResMut<CameraPreferences>,
+) {
+    if !state.is_open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut edited = preferences.clone();
+    let mut is_open = state.is_open;
+    egui::Window::new("Camera").open(&mut is_open).default_pos((10.0, 120.0)).show(ctx, |ui| {
+        for (action, key) in edited.pan_bindings() {
+            ui.horizontal(|ui| {
+                ui.label(action_label(action));
+                let text = if state.rebinding == Some(action) { "press a key...".to_string() } else { format!("{:?}", key) };
+                if ui.button(text).clicked() {
+                    state.rebinding = Some(action);
+                }
+            });
+        }
+        if let Some((key, other)) = state.conflict {
+            ui.colored_label(egui::Color32::YELLOW, format!("{:?} is already used by {:?}", key, other));
+        }
+        ui.separator();
+        ui.checkbox(&mut edited.use_edge_detection, "edge scrolling");
+        ui.add(egui::Slider::new(&mut edited.lerp_speed, 0.5..=MAX_LERP_SPEED).text("follow speed"));
+        ui.add(egui::Slider::new(&mut edited.min_zoom, ZOOM_RANGE.0..=ZOOM_RANGE.1).text("closest zoom"));
+        ui.add(egui::Slider::new(&mut edited.max_zoom_out, ZOOM_RANGE.0..=ZOOM_RANGE.1).text("farthest zoom"));
+        edited.max_zoom_out = edited.max_zoom_out.max(edited.min_zoom);
+        ui.checkbox(&mut edited.neutral_rendering, "neutral rendering");
+        ui.checkbox(&mut edited.reduce_motion, "reduce motion");
+        ui.separator();
+        if ui.button("Reset to default").clicked() {
+            edited = CameraPreferences::default();
+            state.rebinding = None;
+            state.conflict = None;
+        }
+    });
+    state.is_open = is_open;
+
+    if edited != *preferences {
+        *preferences = edited;
+        preferences.save();
+    }
+}
+
+// Live, on the settings and the input map of the local player
+fn apply_camera_preferences(
+    preferences: Res<CameraPreferences>,
+    mut settings: ResMut<CameraSettings>,
+    mut q_maps: Query<&mut InputMap<PlayerAction>, With<LocalPlayer>>,
+) {
+    if preferences.is_changed() {
+        preferences.apply(&mut settings);
+    }
+    for mut map in q_maps.iter_mut() {
+        if preferences.is_changed() || map.is_added() {
+            preferences.apply_bindings(&mut map);
+        }
+    }
+}
+
+
+pub struct CameraOptionsPlugin;
+
+impl Plugin for CameraOptionsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<CameraOptionsState>();
+        app.add_systems(Startup, load_camera_preferences);
+        app.add_systems(Update, apply_camera_preferences);
+        app.add_systems(Update, (toggle_camera_options, rebind_camera_key, camera_options_panel).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::character::player::control::get_input_map;
+
+    use super::*;
+
+    #[test]
+    fn test_rebinding_on_another_pan_is_a_conflict() {
+        let preferences = CameraPreferences::default();
+        assert_eq!(preferences.binding_conflict(PlayerAction::MoveCameraUp, KeyCode::ArrowDown, None), Some(PlayerAction::MoveCameraDown));
+        // Its own key again
+        assert_eq!(preferences.binding_conflict(PlayerAction::MoveCameraUp, KeyCode::ArrowUp, None), None);
+    }
+
+    #[test]
+    fn test_rebinding_on_an_action_of_the_map_is_a_conflict() {
+        let preferences = CameraPreferences::default();
+        let map = get_input_map();
+        assert_eq!(preferences.binding_conflict(PlayerAction::MoveCameraUp, KeyCode::KeyR, Some(&map)), Some(PlayerAction::Reload));
+        assert_eq!(preferences.binding_conflict(PlayerAction::MoveCameraUp, KeyCode::KeyI, Some(&map)), None);
+    }
+
+    #[test]
+    fn test_old_pan_keys_of_the_map_are_not_a_conflict() {
+        // The map still has the keys of the pans before the preferences were applied
+        let preferences = CameraPreferences { pan_up: KeyCode::KeyI, ..default() };
+        let map = get_input_map();
+        assert_eq!(preferences.binding_conflict(PlayerAction::MoveCameraDown, KeyCode::ArrowUp, Some(&map)), None);
+    }
+}
@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+
+use crate::plugins::AppState;
+
+use super::{camera_control_system, options::CameraPreferences, spectator::spectator_camera_system, GameCamera};
+
+// Shake of the camera, presentation only. The effects add trauma, the offset grow with
+// its square so a small hit is barely felt and it decay with the time. The offset is
+// removed before the camera follow its target and put back after, the follow never see it.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct CameraShakeSettings {
+    // World units at a trauma of 1
+    pub max_offset: f32,
+    pub decay_per_second: f32,
+    // Changes of direction per second
+    pub frequency: f32,
+}
+
+impl Default for CameraShakeSettings {
+    fn default() -> Self {
+        Self {
+            max_offset: 12.0,
+            decay_per_second: 1.5,
+            frequency: 25.0,
+        }
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct CameraShake {
+    // From 0 to 1
+    trauma: f32,
+    applied: Vec2,
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+}
+
+
+// SYSTEMS
+
+fn remove_camera_shake(
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<GameCamera>>,
+) {
+    let applied = std::mem::take(&mut shake.applied);
+    if applied == Vec2::ZERO {
+        return;
+    }
+    for mut transform in camera_query.iter_mut() {
+        transform.translation -= applied.extend(0.0);
+    }
+}
+
+fn apply_camera_shake(
+    time: Res<Time>,
+    settings: Res<CameraShakeSettings>,
+    preferences: Option<Res<CameraPreferences>>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<GameCamera>>,
+) {
+    shake.trauma = (shake.trauma - settings.decay_per_second * time.delta_secs()).max(0.0);
+    if shake.trauma <= 0.0 || preferences.map_or(false, |preferences| preferences.reduce_motion) {
+        return;
+    }
+
+    // Two sines out of phase, smooth enough without a noise texture
+    let t = time.elapsed_secs() * settings.frequency;
+    let direction = Vec2::new((t * 1.13).sin() + (t * 2.71).sin() * 0.5, (t * 0.97).cos() + (t * 2.31).cos() * 0.5) / 1.5;
+    let offset = direction * settings.max_offset * shake.trauma * shake.trauma;
+
+    for mut transform in camera_query.iter_mut() {
+        transform.translation += offset.extend(0.0);
+    }
+    shake.applied = offset;
+}
+
+fn clear_camera_shake(mut shake: ResMut<CameraShake>) {
+    shake.trauma = 0.0;
+}
+
+
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShakeSettings>()
+            .register_type::<CameraShakeSettings>()
+            .init_resource::<CameraShake>()
+            .add_systems(Update, (
+                remove_camera_shake.before(camera_control_system).before(spectator_camera_system),
+                apply_camera_shake.after(camera_control_system).after(spectator_camera_system),
+            ))
+            .add_systems(OnExit(AppState::InGame), clear_camera_shake);
+    }
+}
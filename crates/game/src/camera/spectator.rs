@@ -0,0 +1,301 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+
+use crate::{character::{enemy::Enemy, health::Health, player::{LocalPlayer, Player}}, hud::{HudAnchor, HudSlot}, lobby::identity::PlayerIdentity, localization::Localization, plugins::AppState, rules::deathmatch::Respawning, weapons::{WeaponInventory, WeaponModesState, WeaponState}};
+
+use super::{CameraSettings, GameCamera};
+
+// Camera of a peer without a player, like a GGRS spectator. It replace the player camera
+// as soon as there is no local player in game: the number keys follow a player, 0 is a
+// free camera and G the director that keep switching to the player in the most danger.
+// Presentation only, nothing here touch the rollback state.
+
+const NUMBER_KEYS: [KeyCode; 8] = [
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8,
+];
+// F already toggle the fog of war
+const FREE_CAMERA_KEY: KeyCode = KeyCode::Digit0;
+const DIRECTOR_KEY: KeyCode = KeyCode::KeyG;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpectatorView {
+    // Follow the player with this handle
+    Follow(usize),
+    FreeCam,
+    Director,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct SpectatorSettings {
+    // Enemies closer than this to a player count toward its danger
+    pub danger_radius: f32,
+    // The director stay at least this long on a player before switching
+    pub director_min_seconds: f32,
+    pub zoom_step: f32,
+}
+
+impl Default for SpectatorSettings {
+    fn default() -> Self {
+        Self {
+            danger_radius: 300.0,
+            director_min_seconds: 3.0,
+            zoom_step: 0.5,
+        }
+    }
+}
+
+// Present while the local peer is spectating
+#[derive(Resource, Debug)]
+pub struct Spectating {
+    pub view: SpectatorView,
+    // Player followed by the director and since when
+    director_target: Option<usize>,
+    director_timer: Timer,
+    free_zoom: Option<f32>,
+}
+
+impl Default for Spectating {
+    fn default() -> Self {
+        Self {
+            view: SpectatorView::Director,
+            director_target: None,
+            director_timer: Timer::from_seconds(0.0, TimerMode::Once),
+            free_zoom: None,
+        }
+    }
+}
+
+impl Spectating {
+    // Handle of the player on screen, none in free camera
+    pub fn watched(&self) -> Option<usize> {
+        match self.view {
+            SpectatorView::Follow(handle) => Some(handle),
+            SpectatorView::Director => self.director_target,
+            SpectatorView::FreeCam => None,
+        }
+    }
+}
+
+// Number of enemies around each player
+pub fn danger_scores(players: &[(usize, Vec2)], enemies: &[Vec2], radius: f32) -> Vec<(usize, usize)> {
+    players.iter()
+        .map(|(handle, position)| (*handle, enemies.iter().filter(|enemy| enemy.distance(*position) <= radius).count()))
+        .collect()
+}
+
+// Most dangerous player, the lowest handle on a tie
+pub fn most_in_danger(scores: &[(usize, usize)]) -> Option<usize> {
+    scores.iter()
+        .max_by(|(handle_a, score_a), (handle_b, score_b)| score_a.cmp(score_b).then(handle_b.cmp(handle_a)))
+        .map(|(handle, _)| *handle)
+}
+
+fn detect_spectating(
+    mut commands: Commands,
+    spectating: Option<Res<Spectating>>,
+    q_local: Query<(), With<LocalPlayer>>,
+    q_players: Query<(), With<Player>>,
+) {
+    // Wait for the players of the session to exist
+    if q_players.is_empty() {
+        return;
+    }
+    let has_local = !q_local.is_empty();
+    match (has_local, spectating.is_some()) {
+        (false, false) => {
+            info!("no local player, switching to the spectator camera");
+            commands.insert_resource(Spectating::default());
+        }
+        (true, true) => commands.remove_resource::<Spectating>(),
+        _ => (),
+    }
+}
+
+fn spectator_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    settings: Res<SpectatorSettings>,
+    camera_settings: Res<CameraSettings>,
+    mut spectating: ResMut<Spectating>,
+    q_players: Query<&Player>,
+) {
+    for (handle, key) in NUMBER_KEYS.iter().enumerate() {
+        if keys.just_pressed(*key) && q_players.iter().any(|player| player.handle == handle) {
+            spectating.view = SpectatorView::Follow(handle);
+        }
+    }
+    if keys.just_pressed(FREE_CAMERA_KEY) {
+        spectating.view = SpectatorView::FreeCam;
+    }
+    if keys.just_pressed(DIRECTOR_KEY) {
+        spectating.view = SpectatorView::Director;
+        spectating.director_target = None;
+    }
+
+    let scroll: f32 = wheel_events.read().map(|event| event.y).sum();
+    if spectating.view == SpectatorView::FreeCam && scroll != 0.0 {
+        let zoom = spectating.free_zoom.unwrap_or(camera_settings.min_zoom) - scroll.signum() * settings.zoom_step;
+        spectating.free_zoom = Some(zoom.clamp(camera_settings.min_zoom, camera_settings.max_zoom_out));
+    }
+}
+
+fn spectator_director_system(
+    time: Res<Time>,
+    settings: Res<SpectatorSettings>,
+    mut spectating: ResMut<Spectating>,
+    // The players waiting to respawn are never picked
+    q_players: Query<(&Transform, &Player), Without<Respawning>>,
+    q_enemies: Query<&Transform, With<Enemy>>,
+) {
+    if spectating.view != SpectatorView::Director {
+        return;
+    }
+    spectating.director_timer.tick(time.delta());
+
+    let players: Vec<(usize, Vec2)> = q_players.iter().map(|(transform, player)| (player.handle, transform.translation.truncate())).collect();
+    let enemies: Vec<Vec2> = q_enemies.iter().map(|transform| transform.translation.truncate()).collect();
+    let scores = danger_scores(&players, &enemies, settings.danger_radius);
+
+    let current_alive = spectating.director_target.map_or(false, |target| players.iter().any(|(handle, _)| *handle == target));
+    let Some(best) = most_in_danger(&scores) else {
+        return;
+    };
+    let score_of = |handle: Option<usize>| scores.iter().find(|(h, _)| Some(*h) == handle).map_or(0, |(_, score)| *score);
+
+    // Switch right away when the current player is gone, otherwise only to someone in more danger after the minimum time
+    let switch = !current_alive || (spectating.director_timer.finished() && score_of(Some(best)) > score_of(spectating.director_target));
+    if switch && spectating.director_target != Some(best) {
+        spectating.director_target = Some(best);
+        spectating.director_timer = Timer::from_seconds(settings.director_min_seconds, TimerMode::Once);
+    }
+}
+
+pub fn spectator_camera_system(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    camera_settings: Res<CameraSettings>,
+    spectating: Res<Spectating>,
+    q_players: Query<(&Transform, &Player), Without<GameCamera>>,
+    mut q_camera: Query<(&mut GameCamera, &mut Transform, &mut OrthographicProjection), Without<Player>>,
+) {
+    let Ok((mut camera, mut camera_transform, mut projection)) = q_camera.get_single_mut() else {
+        return;
+    };
+
+    match spectating.view {
+        SpectatorView::FreeCam => {
+            let mut direction = Vec2::ZERO;
+            if keys.pressed(KeyCode::KeyW) || keys.pressed(KeyCode::ArrowUp) {
+                direction.y += 1.0;
+            }
+            if keys.pressed(KeyCode::KeyS) || keys.pressed(KeyCode::ArrowDown) {
+                direction.y -= 1.0;
+            }
+            if keys.pressed(KeyCode::KeyA) || keys.pressed(KeyCode::ArrowLeft) {
+                direction.x -= 1.0;
+            }
+            if keys.pressed(KeyCode::KeyD) || keys.pressed(KeyCode::ArrowRight) {
+                direction.x += 1.0;
+            }
+            camera.target_position += direction.normalize_or_zero() * camera_settings.free_move_speed * time.delta_secs();
+            camera.target_zoom = spectating.free_zoom.unwrap_or(camera_settings.min_zoom);
+        }
+        _ => {
+            if let Some((transform, _)) = spectating.watched().and_then(|handle| q_players.iter().find(|(_, player)| player.handle == handle)) {
+                camera.target_position = transform.translation.truncate();
+            }
+            camera.target_zoom = camera_settings.default_player_zoom;
+        }
+    }
+
+    let lerp_factor = (camera_settings.lerp_speed * time.delta_secs()).min(1.0);
+    let position = camera_transform.translation.truncate().lerp(camera.target_position, lerp_factor);
+    camera_transform.translation.x = position.x;
+    camera_transform.translation.y = position.y;
+    projection.scale += (camera.target_zoom - projection.scale) * lerp_factor;
+}
+
+
+#[derive(Component)]
+struct SpectatorHudText;
+
+fn setup_spectator_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        SpectatorHudText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 14.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::BottomLeft, 3),
+        Visibility::Hidden,
+    ));
+}
+
+fn update_spectator_hud(
+    spectating: Option<Res<Spectating>>,
+    identities: Res<PlayerIdentity>,
+    localization: Res<Localization>,
+    q_players: Query<(&Player, &Health, &WeaponInventory, Has<Respawning>)>,
+    q_weapons: Query<(&WeaponState, &WeaponModesState)>,
+    mut q_text: Query<(&mut Text, &mut Visibility), With<SpectatorHudText>>,
+) {
+    let Ok((mut text, mut visibility)) = q_text.get_single_mut() else {
+        return;
+    };
+    let Some(spectating) = spectating else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+
+    let view = match spectating.view {
+        SpectatorView::Follow(_) => "spectator.following",
+        SpectatorView::FreeCam => "spectator.free_camera",
+        SpectatorView::Director => "spectator.director",
+    };
+    let mut lines = vec![localization.format("spectator.title", &[("view", &localization.text(view))])];
+
+    let mut players: Vec<_> = q_players.iter().collect();
+    players.sort_by_key(|(player, ..)| player.handle);
+    for (player, health, inventory, respawning) in players {
+        let name = identities.name(player.handle);
+        let ammo = inventory.weapons.get(inventory.active_weapon_index)
+            .and_then(|(link, _)| link.get(&q_weapons))
+            .and_then(|(state, modes)| modes.modes.get(&state.active_mode))
+            .map_or("-".to_string(), |mode| format!("{} / {}", mode.mag_ammo, mode.mag_quantity));
+        let key = if respawning { "spectator.player_respawning" } else { "spectator.player" };
+        let marker = if spectating.watched() == Some(player.handle) { ">" } else { " " };
+        let line = localization.format(key, &[
+            ("player", &(player.handle + 1)),
+            ("name", &name),
+            ("health", &format!("{:.0}", health.current.max(0.0))),
+            ("max", &format!("{:.0}", health.max)),
+            ("ammo", &ammo),
+        ]);
+        lines.push(format!("{} {}", marker, line));
+    }
+    text.0 = lines.join("\n");
+}
+
+
+#[derive(Default)]
+pub struct SpectatorCameraPlugin;
+
+impl Plugin for SpectatorCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpectatorSettings>();
+        app.add_systems(OnEnter(AppState::InGame), setup_spectator_hud);
+        app.add_systems(Update, (
+            detect_spectating,
+            (
+                spectator_input_system,
+                spectator_director_system,
+                spectator_camera_system,
+            ).chain().after(detect_spectating).run_if(resource_exists::<Spectating>),
+            update_spectator_hud,
+        ).run_if(in_state(AppState::InGame)));
+    }
+}
+
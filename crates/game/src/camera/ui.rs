@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use bevy::text::JustifyText;
 
-use crate::camera::{GameCamera, CameraMode};
+use crate::{camera::{GameCamera, CameraMode}, hud::{HudAnchor, HudSlot}};
 
 // Marker component for camera debug text
 #[derive(Component)]
@@ -19,12 +19,7 @@ fn setup_camera_debug_ui(mut commands: Commands, asset_server: Res<AssetServer>)
             ..Default::default()
         },
         TextLayout::new_with_justify(JustifyText::Left),
-        Node {
-            position_type: PositionType::Absolute,
-            top: Val::Px(5.0),
-            left: Val::Px(5.0),
-            ..default()
-        },
+        HudSlot::new(HudAnchor::TopLeft, 0),
     ));
 }
 
@@ -0,0 +1,495 @@
+pub mod ui;
+
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, render::{render_asset::RenderAssetUsages, render_resource::{Extent3d, TextureDimension}, view::screenshot::{save_to_disk, Screenshot, ScreenshotCaptured}}, utils::HashMap};
+use bevy_ggrs::{PlayerInputs, Session};
+use ggrs::PlayerHandle;
+use utils::frame::SimulationConfig;
+
+use crate::{character::player::{input::BoxInput, jjrs::PeerConfig}, frame::FrameCount, localization::LocalizedText, plugins::AppState};
+
+// Screenshots and clips, only presentation. A screenshot wait for a render where every
+// input of the frame on screen is confirmed so a misprediction is never saved, a clip is
+// the last seconds of frames kept at a reduced resolution in a rolling buffer. Outside of
+// the P2P sessions the inputs of the local handles are logged too, exported next to the
+// clip they can be played back with the `replay:<path>` sources to render it again. The
+// log only keep a window of frames, a replay start at the first frame of the match so a
+// clip of a longer match is saved without them.
+
+pub const SCREENSHOT_KEY: KeyCode = KeyCode::KeyK;
+pub const CLIP_KEY: KeyCode = KeyCode::KeyJ;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct CaptureSettings {
+    // Folder where the screenshots and the clips are written
+    pub output_dir: String,
+    pub clip_enabled: bool,
+    pub clip_seconds: f32,
+    // Frames kept per second of clip
+    pub clip_fps: f32,
+    // Resolution of the clip frames relative to the window
+    pub clip_scale: f32,
+    // Renders a screenshot wait for a confirmed frame before taking the predicted one
+    pub max_confirm_wait: u32,
+    // Inputs kept in the log for the replays of the clips
+    pub replay_log_seconds: f32,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: "captures".into(),
+            clip_enabled: true,
+            clip_seconds: 10.0,
+            clip_fps: 10.0,
+            clip_scale: 0.25,
+            max_confirm_wait: 30,
+            replay_log_seconds: 300.0,
+        }
+    }
+}
+
+impl CaptureSettings {
+    pub fn clip_capacity(&self) -> usize {
+        (self.clip_seconds * self.clip_fps).ceil().max(1.0) as usize
+    }
+}
+
+// Screenshot asked with the key, taken once the frame on screen is confirmed
+#[derive(Debug, Clone, Copy)]
+struct PendingScreenshot {
+    waited: u32,
+}
+
+#[derive(Resource, Default)]
+pub struct CaptureState {
+    pending: Option<PendingScreenshot>,
+    since_clip_frame: f32,
+    // Clip frame asked to the renderer and not received yet
+    clip_frame_in_flight: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct ClipBuffer {
+    // Frame of the simulation and its image at the clip resolution, oldest first
+    pub frames: VecDeque<(u32, Image)>,
+}
+
+// Inputs of a handle from `first_frame` on
+#[derive(Default, Clone, Debug)]
+pub struct LoggedInputs {
+    pub first_frame: u32,
+    pub inputs: VecDeque<BoxInput>,
+}
+
+impl LoggedInputs {
+    pub fn get(&self, frame: u32) -> Option<BoxInput> {
+        frame.checked_sub(self.first_frame).and_then(|index| self.inputs.get(index as usize)).copied()
+    }
+
+    // Every frame from `from` to `to` is logged
+    pub fn covers(&self, from: u32, to: u32) -> bool {
+        from >= self.first_frame && to < self.first_frame + self.inputs.len() as u32
+    }
+}
+
+// Inputs played by each local handle in a window of the last frames. A recorder
+// spectate the session and log every handle with only confirmed inputs, it keep
+// the whole match.
+#[derive(Resource)]
+pub struct InputLog {
+    pub inputs: HashMap<PlayerHandle, LoggedInputs>,
+    // Frames kept for each handle, the features reading the log ask for what they need
+    pub window_frames: Option<u32>,
+}
+
+impl Default for InputLog {
+    fn default() -> Self {
+        Self { inputs: HashMap::default(), window_frames: Some(0) }
+    }
+}
+
+impl InputLog {
+    // Keep at least the last `frames`, nothing change when the whole match is kept
+    pub fn keep_frames(&mut self, frames: u32) {
+        if let Some(window) = self.window_frames.as_mut() {
+            *window = (*window).max(frames);
+        }
+    }
+
+    pub fn get(&self, handle: PlayerHandle, frame: u32) -> Option<BoxInput> {
+        self.inputs.get(&handle).and_then(|logged| logged.get(frame))
+    }
+
+    // A resimulated frame replace what was logged for it, the frames after are dropped
+    // when it changed
+    pub fn record(&mut self, handle: PlayerHandle, frame: u32, input: BoxInput) {
+        let window = self.window_frames;
+        let logged = self.inputs.entry(handle).or_insert_with(|| LoggedInputs {
+            // Frames before the log started are played without input
+            first_frame: window.map_or(0, |window| frame.saturating_sub(window)),
+            inputs: VecDeque::new(),
+        });
+        if frame < logged.first_frame || window.map_or(false, |window| frame - logged.first_frame > logged.inputs.len() as u32 + window) {
+            logged.first_frame = frame;
+            logged.inputs.clear();
+        }
+
+        let index = (frame - logged.first_frame) as usize;
+        match logged.inputs.get_mut(index) {
+            Some(logged_input) if *logged_input == input => {},
+            Some(logged_input) => {
+                *logged_input = input;
+                logged.inputs.truncate(index + 1);
+            },
+            None => {
+                logged.inputs.resize(index, BoxInput::default());
+                logged.inputs.push_back(input);
+            },
+        }
+
+        if let Some(window) = window {
+            let excess = logged.inputs.len().saturating_sub(window as usize);
+            logged.inputs.drain(..excess);
+            logged.first_frame += excess as u32;
+        }
+    }
+}
+
+// Sent when a capture is written, shown by the toast
+#[derive(Event, Debug, Clone)]
+pub struct CaptureSaved {
+    pub message: LocalizedText,
+}
+
+
+fn is_p2p(session: Option<&Session<PeerConfig>>) -> bool {
+    matches!(session, Some(Session::P2P(_)))
+}
+
+// The frame on screen is only simulated with confirmed inputs, always the case offline
+fn displayed_frame_confirmed(session: Option<&Session<PeerConfig>>, frame: &FrameCount) -> bool {
+    match session {
+        Some(Session::P2P(session)) => session.confirmed_frame() + 1 >= frame.frame as i32,
+        _ => true,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// No system time on the web, the frame is enough to tell the downloads apart
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn timestamp() -> u64 {
+    0
+}
+
+// Nearest pixel of the screenshot, the 4 bytes formats of the surfaces only
+fn downscale(image: &Image, scale: f32) -> Option<Image> {
+    let size = image.texture_descriptor.size;
+    let format = image.texture_descriptor.format;
+    if format.block_copy_size(None) != Some(4) {
+        return None;
+    }
+
+    let width = ((size.width as f32 * scale) as u32).max(1);
+    let height = ((size.height as f32 * scale) as u32).max(1);
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let source_y = (y * size.height / height).min(size.height - 1);
+        for x in 0..width {
+            let source_x = (x * size.width / width).min(size.width - 1);
+            let index = ((source_y * size.width + source_x) * 4) as usize;
+            data.extend_from_slice(image.data.get(index..index + 4)?);
+        }
+    }
+
+    Some(Image::new(
+        Extent3d { width, height, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        format,
+        RenderAssetUsages::MAIN_WORLD,
+    ))
+}
+
+
+// SYSTEMS
+
+// Part of the rollback schedule so a resimulated frame replace what was logged for it
+pub fn record_input_log(
+    frame: Res<FrameCount>,
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    session: Option<Res<Session<PeerConfig>>>,
+    mut log: ResMut<InputLog>,
+) {
+    if is_p2p(session.as_deref()) {
+        return;
+    }
+
+    for (handle, (input, _)) in inputs.iter().enumerate() {
+        log.record(handle, frame.frame, *input);
+    }
+}
+
+fn size_input_log(
+    settings: Res<CaptureSettings>,
+    simulation: Res<SimulationConfig>,
+    mut log: ResMut<InputLog>,
+) {
+    log.keep_frames(simulation.frames_from_seconds(settings.replay_log_seconds));
+}
+
+fn request_screenshot(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<CaptureState>,
+) {
+    if keyboard_input.just_pressed(SCREENSHOT_KEY) && state.pending.is_none() {
+        state.pending = Some(PendingScreenshot { waited: 0 });
+    }
+}
+
+fn take_screenshot(
+    mut commands: Commands,
+    settings: Res<CaptureSettings>,
+    frame: Res<FrameCount>,
+    session: Option<Res<Session<PeerConfig>>>,
+    mut state: ResMut<CaptureState>,
+    mut saved: EventWriter<CaptureSaved>,
+) {
+    let Some(pending) = state.pending.as_mut() else {
+        return;
+    };
+
+    let confirmed = displayed_frame_confirmed(session.as_deref(), &frame);
+    if !confirmed && pending.waited < settings.max_confirm_wait {
+        pending.waited += 1;
+        return;
+    }
+    state.pending = None;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Err(err) = std::fs::create_dir_all(&settings.output_dir) {
+        error!("failed to create the capture folder {}: {}", settings.output_dir, err);
+        saved.send(CaptureSaved { message: LocalizedText::new("capture.screenshot_failed").with_arg("error", err) });
+        return;
+    }
+
+    let path = std::path::Path::new(&settings.output_dir).join(format!("screenshot_{}_{}.png", timestamp(), frame.frame));
+    info!("screenshot of frame {}{} saved to {}", frame.frame, if confirmed { "" } else { " (predicted)" }, path.display());
+    saved.send(CaptureSaved { message: LocalizedText::new("capture.screenshot_saved").with_arg("path", path.display()) });
+    commands.spawn(Screenshot::primary_window()).observe(save_to_disk(path));
+}
+
+fn capture_clip_frame(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<CaptureSettings>,
+    mut state: ResMut<CaptureState>,
+) {
+    if !settings.clip_enabled || settings.clip_fps <= 0.0 {
+        return;
+    }
+
+    state.since_clip_frame += time.delta_secs();
+    if state.since_clip_frame < 1.0 / settings.clip_fps || state.clip_frame_in_flight {
+        return;
+    }
+    state.since_clip_frame = 0.0;
+    state.clip_frame_in_flight = true;
+
+    commands.spawn(Screenshot::primary_window()).observe(store_clip_frame);
+}
+
+fn store_clip_frame(
+    trigger: Trigger<ScreenshotCaptured>,
+    settings: Res<CaptureSettings>,
+    frame: Res<FrameCount>,
+    mut state: ResMut<CaptureState>,
+    mut clip: ResMut<ClipBuffer>,
+) {
+    state.clip_frame_in_flight = false;
+
+    let Some(image) = downscale(&trigger.event().0, settings.clip_scale) else {
+        warn!("clip frame skipped, unsupported screenshot format");
+        return;
+    };
+
+    clip.frames.push_back((frame.frame, image));
+    while clip.frames.len() > settings.clip_capacity() {
+        clip.frames.pop_front();
+    }
+}
+
+fn export_clip(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<CaptureSettings>,
+    frame: Res<FrameCount>,
+    session: Option<Res<Session<PeerConfig>>>,
+    clip: Res<ClipBuffer>,
+    log: Res<InputLog>,
+    mut saved: EventWriter<CaptureSaved>,
+) {
+    if !keyboard_input.just_pressed(CLIP_KEY) {
+        return;
+    }
+    if clip.frames.is_empty() {
+        saved.send(CaptureSaved { message: LocalizedText::new("capture.no_clip") });
+        return;
+    }
+
+    let frames: Vec<Image> = clip.frames.iter().map(|(_, image)| image.clone()).collect();
+    let replays = if is_p2p(session.as_deref()) { vec![] } else { replay_files(&log) };
+
+    let dir = std::path::Path::new(&settings.output_dir).join(format!("clip_{}_{}", timestamp(), frame.frame));
+    saved.send(CaptureSaved { message: LocalizedText::new("capture.saving_clip").with_arg("path", dir.display()) });
+    write_clip(dir, frames, replays);
+}
+
+// Replay of each local handle, in the format read by the `replay:<path>` sources. The
+// handles missing the start of the match can't be replayed
+pub(crate) fn replay_files(log: &InputLog) -> Vec<(String, String)> {
+    let mut handles: Vec<&PlayerHandle> = log.inputs.keys().collect();
+    handles.sort();
+    handles.into_iter()
+        .filter(|handle| {
+            let first_frame = log.inputs[*handle].first_frame;
+            if first_frame > 0 {
+                warn!("the inputs of player {} before frame {} are not logged anymore, no replay", *handle + 1, first_frame);
+            }
+            first_frame == 0
+        })
+        .filter_map(|handle| match serde_json::to_string(&log.inputs[handle].inputs) {
+            Ok(json) => Some((format!("replay_{}.json", handle), json)),
+            Err(err) => {
+                error!("failed to serialize the inputs of player {}: {}", handle, err);
+                None
+            }
+        })
+        .collect()
+}
+
+// Written on the IO pool, a clip is a hundred files
+#[cfg(not(target_arch = "wasm32"))]
+fn write_clip(dir: std::path::PathBuf, frames: Vec<Image>, replays: Vec<(String, String)>) {
+    bevy::tasks::IoTaskPool::get().spawn(async move {
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            error!("failed to create the clip folder {}: {}", dir.display(), err);
+            return;
+        }
+
+        for (index, image) in frames.into_iter().enumerate() {
+            let path = dir.join(format!("frame_{:04}.png", index));
+            let result = image.try_into_dynamic()
+                .map_err(|err| err.to_string())
+                .and_then(|image| image.to_rgba8().save(&path).map_err(|err| err.to_string()));
+            if let Err(err) = result {
+                error!("failed to write the clip frame {}: {}", path.display(), err);
+                return;
+            }
+        }
+
+        for (name, json) in replays {
+            let path = dir.join(name);
+            if let Err(err) = std::fs::write(&path, json) {
+                error!("failed to write the replay {}: {}", path.display(), err);
+            }
+        }
+
+        info!("clip written to {}", dir.display());
+    }).detach();
+}
+
+// No file system on the web, only the screenshots are downloaded by the browser
+#[cfg(target_arch = "wasm32")]
+fn write_clip(dir: std::path::PathBuf, _frames: Vec<Image>, _replays: Vec<(String, String)>) {
+    warn!("clips can't be saved on the web, {} not written", dir.display());
+}
+
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CaptureSettings>()
+            .register_type::<CaptureSettings>()
+            .init_resource::<CaptureState>()
+            .init_resource::<ClipBuffer>()
+            .add_event::<CaptureSaved>()
+            .add_plugins(ui::CaptureUIPlugin)
+            .add_systems(OnEnter(AppState::InGame), size_input_log)
+            .add_systems(Update, (
+                request_screenshot,
+                take_screenshot.after(request_screenshot),
+                capture_clip_frame,
+                export_clip,
+            ).run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(buttons: u16) -> BoxInput {
+        BoxInput { buttons, ..default() }
+    }
+
+    #[test]
+    fn test_log_keep_only_the_window() {
+        let mut log = InputLog { window_frames: Some(3), ..default() };
+        for frame in 0..10 {
+            log.record(0, frame, input(frame as u16));
+        }
+        assert_eq!(log.inputs[&0].first_frame, 7);
+        assert_eq!(log.inputs[&0].inputs.len(), 3);
+        assert_eq!(log.get(0, 6), None);
+        assert_eq!(log.get(0, 9), Some(input(9)));
+    }
+
+    #[test]
+    fn test_log_of_the_recorder_keep_the_match() {
+        let mut log = InputLog { window_frames: None, ..default() };
+        // A handle seen late is idle before
+        log.record(1, 4, input(1));
+        assert_eq!(log.inputs[&1].first_frame, 0);
+        assert_eq!(log.get(1, 2), Some(BoxInput::default()));
+        assert_eq!(log.get(1, 4), Some(input(1)));
+    }
+
+    #[test]
+    fn test_resimulated_frame_replace_the_log() {
+        let mut log = InputLog { window_frames: Some(10), ..default() };
+        for frame in 0..5 {
+            log.record(0, frame, input(1));
+        }
+
+        // Same input, the frames after stay for a replay reading them
+        log.record(0, 2, input(1));
+        assert!(log.inputs[&0].covers(0, 4));
+
+        // Corrected input, the frames after are simulated again
+        log.record(0, 2, input(2));
+        assert_eq!(log.get(0, 2), Some(input(2)));
+        assert!(!log.inputs[&0].covers(0, 3));
+    }
+
+    #[test]
+    fn test_keep_frames_only_grow_the_window() {
+        let mut log = InputLog::default();
+        log.keep_frames(60);
+        log.keep_frames(30);
+        assert_eq!(log.window_frames, Some(60));
+
+        log.window_frames = None;
+        log.keep_frames(30);
+        assert_eq!(log.window_frames, None);
+    }
+}
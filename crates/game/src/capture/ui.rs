@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+
+use crate::{hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState};
+
+use super::CaptureSaved;
+
+const TOAST_SECONDS: f32 = 3.0;
+
+
+#[derive(Component)]
+struct CaptureToastText;
+
+#[derive(Resource, Default)]
+struct CaptureToast {
+    timer: Option<Timer>,
+}
+
+
+fn setup_capture_toast(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        CaptureToastText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 14.0,
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        HudSlot::new(HudAnchor::BottomRight, 1),
+        Node {
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+// The last capture replace the one shown
+fn update_capture_toast(
+    time: Res<Time>,
+    localization: Res<Localization>,
+    mut events: EventReader<CaptureSaved>,
+    mut toast: ResMut<CaptureToast>,
+    mut q_text: Query<(&mut Text, &mut Visibility), With<CaptureToastText>>,
+) {
+    let Ok((mut text, mut visibility)) = q_text.get_single_mut() else {
+        return;
+    };
+
+    if let Some(event) = events.read().last() {
+        text.0 = localization.localize(&event.message);
+        toast.timer = Some(Timer::from_seconds(TOAST_SECONDS, TimerMode::Once));
+    }
+
+    let Some(timer) = toast.timer.as_mut() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    timer.tick(time.delta());
+    if timer.finished() {
+        toast.timer = None;
+        *visibility = Visibility::Hidden;
+    } else {
+        *visibility = Visibility::Inherited;
+    }
+}
+
+
+pub struct CaptureUIPlugin;
+
+impl Plugin for CaptureUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CaptureToast>();
+        app.add_systems(OnEnter(AppState::InGame), setup_capture_toast);
+        app.add_systems(Update, update_capture_toast.run_if(in_state(AppState::InGame)));
+    }
+}
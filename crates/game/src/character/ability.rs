@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+use ggrs::PlayerHandle;
+use leafwing_input_manager::prelude::InputMap;
+use serde::Deserialize;
+use utils::frame::{FrameTimer, SimulationConfig};
+
+use crate::{character::{config::{CharacterConfig, CharacterConfigHandles}, health::Health, player::{control::{keyboard_label, PlayerAction}, input::{PreviousInput, INPUT_ABILITY}, jjrs::PeerConfig, LocalPlayer, Player}, status::Stunned}, frame::FrameCount, hud::{HudAnchor, HudSlot}, plugins::AppState, rules::{deathmatch::Respawning, GameMode, GameRules}};
+
+// Active ability of a player class, used with its own input and put on cooldown.
+// The ability is in the character config, the cooldown in the rollback state of the
+// player. For now only the medic has one, a healing aura deployed on the ground.
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct AuraConfig {
+    pub radius: f32,
+    pub duration_frames: u32,
+    pub cooldown_frames: u32,
+    // Health given to each teammate inside every tick
+    pub heal_per_tick: f32,
+    pub tick_interval_frames: u32,
+    // Part of the incoming damage ignored inside, between 0 and 1
+    pub damage_reduction: f32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum ClassAbility {
+    HealingAura(AuraConfig),
+}
+
+impl ClassAbility {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClassAbility::HealingAura(_) => "Aura",
+        }
+    }
+
+    pub fn cooldown_frames(&self) -> u32 {
+        match self {
+            ClassAbility::HealingAura(config) => config.cooldown_frames,
+        }
+    }
+
+    // Text of the hud, the seconds left of the cooldown or ready when there is none
+    pub fn prompt(&self, key: &str, cooldown_seconds: Option<f32>) -> String {
+        match cooldown_seconds {
+            Some(seconds) => format!("[{}] {} {:.0}s", key, self.name(), seconds.ceil()),
+            None => format!("[{}] {} ready", key, self.name()),
+        }
+    }
+}
+
+// On every player, the ability is ready once the cooldown is done
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct AbilityState {
+    pub cooldown: FrameTimer,
+}
+
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct HealingAura {
+    pub owner: PlayerHandle,
+    pub timer: FrameTimer,
+    pub radius: f32,
+    pub heal_per_tick: f32,
+    pub tick_interval_frames: u32,
+    pub damage_multiplier: f32,
+}
+
+// Player standing in an aura, the damage it take is multiplied
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct AuraProtected {
+    pub damage_multiplier: f32,
+}
+
+#[derive(Component)]
+struct AuraVisual;
+
+#[derive(Component)]
+struct AbilityHud;
+
+
+// SYSTEMS
+
+pub fn rollback_use_abilities(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    mut query: Query<(&Transform, &Player, &PreviousInput, &CharacterConfigHandles, &mut AbilityState, Option<&Stunned>, Has<Respawning>), With<Rollback>>,
+) {
+    let mut players: Vec<_> = query.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.handle);
+
+    for (transform, player, previous_input, config_handles, mut state, opt_stunned, respawning) in players {
+        let (input, _input_status) = inputs[player.handle];
+        if !previous_input.just_pressed(&input, INPUT_ABILITY) || respawning {
+            continue;
+        }
+        if !state.cooldown.is_done(frame.frame) || opt_stunned.map_or(false, |stunned| stunned.is_active(frame.frame)) {
+            continue;
+        }
+        let Some(ability) = character_configs.get(&config_handles.config).and_then(|config| config.ability) else {
+            continue;
+        };
+
+        match ability {
+            ClassAbility::HealingAura(config) => {
+                commands.spawn((
+                    Transform::from_translation(transform.translation.truncate().extend(-1.0)),
+                    HealingAura {
+                        owner: player.handle,
+                        timer: FrameTimer::new(frame.frame, simulation.frames(config.duration_frames)),
+                        radius: config.radius,
+                        heal_per_tick: config.heal_per_tick,
+                        tick_interval_frames: simulation.frames(config.tick_interval_frames).max(1),
+                        damage_multiplier: 1.0 - config.damage_reduction.clamp(0.0, 1.0),
+                    },
+                )).add_rollback();
+            }
+        }
+        state.cooldown = FrameTimer::new(frame.frame, simulation.frames(ability.cooldown_frames()));
+    }
+}
+
+// Heal and protect the players inside the auras, must run before the damage is applied
+pub fn rollback_healing_auras(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    rules: Res<GameRules>,
+    aura_query: Query<(Entity, &Transform, &HealingAura), With<Rollback>>,
+    mut player_query: Query<(Entity, &Transform, &Player, &mut Health, Option<&AuraProtected>), With<Rollback>>,
+) {
+    let mut auras: Vec<_> = aura_query.iter().collect();
+    auras.sort_by_key(|(entity, ..)| entity.index());
+
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, player, mut health, opt_protected) in players {
+        let position = transform.translation.truncate();
+        let mut damage_multiplier: Option<f32> = None;
+
+        for (_, aura_transform, aura) in auras.iter() {
+            if aura.timer.is_done(frame.frame) || aura_transform.translation.truncate().distance(position) > aura.radius {
+                continue;
+            }
+            // No teammates in the versus mode, the aura is for its owner only
+            if rules.mode == GameMode::Deathmatch && aura.owner != player.handle {
+                continue;
+            }
+            // The auras don't stack, the strongest is kept
+            damage_multiplier = Some(damage_multiplier.map_or(aura.damage_multiplier, |multiplier| multiplier.min(aura.damage_multiplier)));
+            if aura.timer.elapsed(frame.frame) % aura.tick_interval_frames == 0 && health.current > 0.0 {
+                health.current = (health.current + aura.heal_per_tick).min(health.max);
+            }
+        }
+
+        match (damage_multiplier, opt_protected) {
+            (Some(damage_multiplier), Some(protected)) if protected.damage_multiplier == damage_multiplier => {}
+            (Some(damage_multiplier), _) => {
+                commands.entity(entity).insert(AuraProtected { damage_multiplier });
+            }
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<AuraProtected>();
+            }
+            (None, None) => {}
+        }
+    }
+
+    for (entity, _, aura) in auras {
+        if aura.timer.is_done(frame.frame) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Presentation only, the ground circle of the new auras
+fn add_aura_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    query: Query<(Entity, &HealingAura), Without<AuraVisual>>,
+) {
+    for (entity, aura) in query.iter() {
+        commands.entity(entity).insert((
+            AuraVisual,
+            Mesh2d(meshes.add(Circle::new(aura.radius))),
+            MeshMaterial2d(materials.add(Color::srgba(0.3, 1.0, 0.5, 0.25))),
+        ));
+    }
+}
+
+fn setup_ability_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        AbilityHud,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 14.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::BottomCenter, 2),
+        Visibility::Hidden,
+    ));
+}
+
+fn update_ability_hud(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    q_player: Query<(&AbilityState, &CharacterConfigHandles, &InputMap<PlayerAction>), With<LocalPlayer>>,
+    mut q_hud: Query<(&mut Text, &mut TextColor, &mut Visibility), With<AbilityHud>>,
+) {
+    let Ok((mut text, mut color, mut visibility)) = q_hud.get_single_mut() else {
+        return;
+    };
+    let ability = q_player.get_single().ok()
+        .and_then(|(state, handles, input_map)| character_configs.get(&handles.config).and_then(|config| config.ability).map(|ability| (state, ability, input_map)));
+    let Some((state, ability, input_map)) = ability else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+
+    // The key bound by the player, a gamepad only binding show the action name
+    let key = keyboard_label(input_map, PlayerAction::Ability).unwrap_or_else(|| format!("{:?}", PlayerAction::Ability));
+    if state.cooldown.is_done(frame.frame) {
+        text.0 = ability.prompt(&key, None);
+        color.0 = Color::srgb(0.4, 1.0, 0.5);
+    } else {
+        text.0 = ability.prompt(&key, Some(state.cooldown.remaining_seconds(frame.frame, &simulation)));
+        color.0 = Color::srgb(0.6, 0.6, 0.6);
+    }
+}
+
+
+pub struct AbilityUIPlugin;
+
+impl Plugin for AbilityUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_ability_hud);
+        app.add_systems(Update, (add_aura_visuals, update_ability_hud).run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::player::control::get_input_map;
+
+    fn aura() -> ClassAbility {
+        ClassAbility::HealingAura(AuraConfig {
+            radius: 80.0,
+            duration_frames: 300,
+            cooldown_frames: 1200,
+            heal_per_tick: 5.0,
+            tick_interval_frames: 30,
+            damage_reduction: 0.25,
+        })
+    }
+
+    #[test]
+    fn test_prompt_use_the_bound_key() {
+        let mut map = get_input_map();
+        assert_eq!(aura().prompt(&keyboard_label(&map, PlayerAction::Ability).unwrap(), None), "[E] Aura ready");
+
+        map.clear_action(&PlayerAction::Ability);
+        map.insert(PlayerAction::Ability, KeyCode::Digit5);
+        assert_eq!(aura().prompt(&keyboard_label(&map, PlayerAction::Ability).unwrap(), None), "[5] Aura ready");
+    }
+
+    #[test]
+    fn test_prompt_round_up_the_cooldown() {
+        assert_eq!(aura().prompt("E", Some(3.2)), "[E] Aura 4s");
+        assert_eq!(aura().prompt("E", Some(0.1)), "[E] Aura 1s");
+    }
+}
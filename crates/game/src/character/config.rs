@@ -1,18 +1,70 @@
+use std::collections::BTreeMap;
+
 use bevy::{prelude::*, reflect::TypePath, utils::HashMap};
 use serde::Deserialize;
+use utils::schema::Versioned;
 
-use crate::{character::movement::MovementConfig, collider::{Collider, ColliderConfig}};
+use crate::{character::{ability::ClassAbility, enemy::{attack::AttackConfig, exploder::ExploderConfig, flinch::FlinchConfig, shield::ShieldConfig}, movement::MovementConfig}, collider::{Collider, ColliderConfig, HitZoneConfig}};
 
 use super::health::HealthConfig;
 
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct LayerTint {
+    pub name: String,
+    pub color: (f32, f32, f32),
+}
+
+impl LayerTint {
+    pub fn to_color(&self) -> Color {
+        Color::srgb(self.color.0, self.color.1, self.color.2)
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct CharacterSkin {
-    pub layers: HashMap<String, String>
+    pub layers: HashMap<String, String>,
+    // Colors a player can pick for a layer, the first one is used by default
+    #[serde(default)]
+    pub tints: HashMap<String, Vec<LayerTint>>,
 }
 
+impl CharacterSkin {
+    // Color of each tinted layer for the chosen palette indexes, out of range fall back to the first
+    pub fn layer_colors(&self, chosen: &BTreeMap<String, usize>) -> HashMap<String, Color> {
+        self.tints.iter()
+            .filter_map(|(layer, tints)| {
+                let index = chosen.get(layer).copied().filter(|index| *index < tints.len()).unwrap_or(0);
+                tints.get(index).map(|tint| (layer.clone(), tint.to_color()))
+            })
+            .collect()
+    }
+
+    // Tinted layers sorted by name, the order they are shown in the lobby
+    pub fn tinted_layers(&self) -> Vec<(&String, &Vec<LayerTint>)> {
+        let mut layers: Vec<_> = self.tints.iter().filter(|(_, tints)| !tints.is_empty()).collect();
+        layers.sort_by_key(|(layer, _)| *layer);
+        layers
+    }
+}
+
+// Unique bonus of a player class
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum ClassPassive {
+    // Part of the incoming damage ignored, between 0 and 1
+    DamageResistance(f32),
+    // Multiplier on the dash distance
+    LongDash(f32),
+}
+
+pub const CHARACTER_SCHEMA_VERSION: u32 = 2;
+
 #[derive(Asset, TypePath, Deserialize, Debug, Clone)]
 pub struct CharacterConfig {
+    // Missing in the files written before the versioning
+    #[serde(default)]
+    pub schema_version: u32,
+
     pub movement: MovementConfig,
 
     pub asset_name_ref: String,
@@ -21,13 +73,81 @@ pub struct CharacterConfig {
 
     pub collider: ColliderConfig,
 
+    // Zones hit by the bullets, the collider is used when empty
+    #[serde(default)]
+    pub hit_zones: Vec<HitZoneConfig>,
+
     pub scale: f32,
 
     pub starting_skin: String,
-    pub skins: HashMap<String, CharacterSkin>
+    pub skins: HashMap<String, CharacterSkin>,
+
+    // Weapons given on spawn, in order, every starting weapon when empty
+    #[serde(default)]
+    pub starting_weapons: Vec<String>,
+
+    #[serde(default)]
+    pub passive: Option<ClassPassive>,
+
+    // Used with the ability input, on a cooldown
+    #[serde(default)]
+    pub ability: Option<ClassAbility>,
+
+    // Attacks of the enemies, by priority
+    #[serde(default)]
+    pub attacks: Vec<AttackConfig>,
+
+    // Stagger of the enemies taking too much damage at once, never without
+    #[serde(default)]
+    pub flinch: Option<FlinchConfig>,
+
+    // Blow up near the players and on death
+    #[serde(default)]
+    pub exploder: Option<ExploderConfig>,
+
+    // Block the frontal hits until it break
+    #[serde(default)]
+    pub shield: Option<ShieldConfig>,
+}
+
+// Version 1 was written before the classes, the files can't have a passive
+// or starting weapons and default to every weapon without passive
+fn migrate_character_v1(mut config: CharacterConfig) -> CharacterConfig {
+    config.schema_version = CHARACTER_SCHEMA_VERSION;
+    config.starting_weapons.clear();
+    config.passive = None;
+    config
+}
+
+impl Versioned for CharacterConfig {
+    const ASSET_NAME: &'static str = "character config";
+    const SCHEMA_VERSION: u32 = CHARACTER_SCHEMA_VERSION;
+
+    fn parse_version(version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        match version {
+            1 => ron::de::from_bytes::<CharacterConfig>(bytes).map(migrate_character_v1),
+            _ => ron::de::from_bytes(bytes),
+        }
+    }
+}
+
+impl CharacterConfig {
+    pub fn damage_multiplier(&self) -> f32 {
+        match self.passive {
+            Some(ClassPassive::DamageResistance(ratio)) => 1.0 - ratio.clamp(0.0, 1.0),
+            _ => 1.0,
+        }
+    }
+
+    pub fn dash_distance(&self) -> f32 {
+        match self.passive {
+            Some(ClassPassive::LongDash(multiplier)) => self.movement.dash_distance * multiplier,
+            _ => self.movement.dash_distance,
+        }
+    }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct CharacterConfigHandles {
     pub config: Handle<CharacterConfig>
 }
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+use rand::Rng;
+use utils::events::RollbackEvents;
+
+use crate::{character::health::DeathEvent, frame::{confirmed_frame, FrameCount}, hud::graphics::GraphicsPreferences, plugins::AppState};
+
+// What is left of the enemies, presentation only. A confirmed death leave a body on the
+// ground with gibs thrown around, it fade at the end of the lifetime of the graphics
+// options and the oldest one is removed when there is more than their cap.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct CorpseSettings {
+    pub body_size: Vec2,
+    pub body_color: Color,
+    // For a particle density of 1
+    pub gibs: f32,
+    pub gib_size: f32,
+    pub gib_color: Color,
+    // Distance from the body where the gibs land
+    pub gib_spread: f32,
+    // Last seconds of the lifetime where the corpse fade
+    pub fade_seconds: f32,
+    // Under the characters, over the footprints
+    pub z: f32,
+}
+
+impl Default for CorpseSettings {
+    fn default() -> Self {
+        Self {
+            body_size: Vec2::new(22.0, 12.0),
+            body_color: Color::srgba(0.3, 0.05, 0.04, 0.9),
+            gibs: 4.0,
+            gib_size: 4.0,
+            gib_color: Color::srgba(0.5, 0.08, 0.06, 0.9),
+            gib_spread: 18.0,
+            fade_seconds: 2.0,
+            z: -0.92,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Corpse {
+    age: f32,
+}
+
+// Corpses in the order they were left, the front is the oldest
+#[derive(Resource, Default)]
+struct CorpsePool {
+    corpses: VecDeque<Entity>,
+    last_read_frame: Option<u32>,
+}
+
+// Alpha of a corpse of `age` seconds, fading over the last `fade_seconds` of its
+// lifetime, None once it is too old
+fn corpse_alpha(age: f32, lifetime: f32, fade_seconds: f32) -> Option<f32> {
+    if age >= lifetime {
+        return None;
+    }
+    Some(((lifetime - age) / fade_seconds.max(f32::EPSILON)).min(1.0))
+}
+
+fn spawn_corpse(commands: &mut Commands, settings: &CorpseSettings, preferences: &GraphicsPreferences, position: Vec2) -> Entity {
+    let mut rng = rand::thread_rng();
+    let gibs = preferences.particles(settings.gibs);
+    // The fraction is a chance of one more
+    let gibs = gibs.floor() as u32 + rng.gen_bool(gibs.fract() as f64) as u32;
+
+    commands.spawn((
+        Corpse { age: 0.0 },
+        Sprite::from_color(settings.body_color, settings.body_size),
+        Transform::from_translation(position.extend(settings.z))
+            .with_rotation(Quat::from_rotation_z(rng.gen_range(0.0..std::f32::consts::TAU))),
+        Visibility::default(),
+    )).with_children(|parent| {
+        for _ in 0..gibs {
+            let offset = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)) * rng.gen_range(0.3..1.0) * settings.gib_spread;
+            parent.spawn((
+                Sprite::from_color(settings.gib_color, Vec2::splat(settings.gib_size * rng.gen_range(0.6..1.2))),
+                Transform::from_translation(offset.extend(0.01)),
+            ));
+        }
+    }).id()
+}
+
+
+// SYSTEMS
+
+fn spawn_confirmed_corpses(
+    mut commands: Commands,
+    settings: Res<CorpseSettings>,
+    preferences: Option<Res<GraphicsPreferences>>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    death_events: Res<RollbackEvents<DeathEvent>>,
+    mut pool: ResMut<CorpsePool>,
+) {
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    if pool.last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+
+    let preferences = preferences.map(|preferences| preferences.clone()).unwrap_or_default();
+    if preferences.corpse_seconds > 0.0 && preferences.max_corpses > 0 {
+        for (_, event) in death_events.read_after(pool.last_read_frame).filter(|(f, event)| *f <= confirmed_frame && event.enemy) {
+            let entity = spawn_corpse(&mut commands, &settings, &preferences, event.position);
+            pool.corpses.push_back(entity);
+        }
+    }
+
+    pool.last_read_frame = Some(confirmed_frame);
+}
+
+// Age the corpses, the ones too old or over the cap are removed
+fn clean_corpses(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<CorpseSettings>,
+    preferences: Option<Res<GraphicsPreferences>>,
+    mut pool: ResMut<CorpsePool>,
+    mut q_corpse: Query<(&mut Corpse, Option<&Children>)>,
+    mut q_sprite: Query<&mut Sprite>,
+) {
+    let (lifetime, max_corpses) = preferences.map_or((f32::MAX, usize::MAX), |preferences| (preferences.corpse_seconds, preferences.max_corpses));
+
+    while pool.corpses.len() > max_corpses {
+        if let Some(entity) = pool.corpses.pop_front() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    pool.corpses.retain(|entity| {
+        let Ok((mut corpse, opt_children)) = q_corpse.get_mut(*entity) else {
+            return true;
+        };
+        corpse.age += time.delta_secs();
+        let Some(alpha) = corpse_alpha(corpse.age, lifetime, settings.fade_seconds) else {
+            commands.entity(*entity).despawn_recursive();
+            return false;
+        };
+
+        let (body, gib) = (settings.body_color.alpha() * alpha, settings.gib_color.alpha() * alpha);
+        if let Ok(mut sprite) = q_sprite.get_mut(*entity) {
+            sprite.color = sprite.color.with_alpha(body);
+        }
+        for child in opt_children.into_iter().flatten() {
+            if let Ok(mut sprite) = q_sprite.get_mut(*child) {
+                sprite.color = sprite.color.with_alpha(gib);
+            }
+        }
+        true
+    });
+}
+
+// The corpses belong to the match, none are kept for the next one
+fn clear_corpses(
+    mut commands: Commands,
+    mut pool: ResMut<CorpsePool>,
+) {
+    for entity in pool.corpses.drain(..) {
+        commands.entity(entity).despawn_recursive();
+    }
+    pool.last_read_frame = None;
+}
+
+
+pub struct CorpsePlugin;
+
+impl Plugin for CorpsePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CorpseSettings>()
+            .register_type::<CorpseSettings>()
+            .init_resource::<CorpsePool>()
+            .add_systems(Update, (spawn_confirmed_corpses, clean_corpses).chain().run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), clear_corpses);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpse_fade_at_the_end_of_its_lifetime() {
+        assert_eq!(corpse_alpha(0.0, 20.0, 2.0), Some(1.0));
+        assert_eq!(corpse_alpha(17.0, 20.0, 2.0), Some(1.0));
+        assert_eq!(corpse_alpha(19.0, 20.0, 2.0), Some(0.5));
+        assert_eq!(corpse_alpha(20.0, 20.0, 2.0), None);
+    }
+
+    #[test]
+    fn test_corpse_without_lifetime_is_removed() {
+        assert_eq!(corpse_alpha(0.0, 0.0, 2.0), None);
+        assert_eq!(corpse_alpha(1.0, 2.0, 0.0), Some(1.0));
+    }
+}
@@ -1,10 +1,12 @@
 
-use animation::{create_child_sprite, AnimationBundle, SpriteSheetConfig};
+use std::collections::BTreeMap;
+
+use animation::{create_child_sprite, AnimationBundle, ColoredLayer, SpriteSheetConfig};
 use bevy::{prelude::*};
 use bevy_kira_audio::prelude::*;
 use utils::math::round_vec3;
 
-use crate::{character::{config::CharacterConfigHandles, movement::Velocity}, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings}, global_asset::GlobalAsset, weapons::{spawn_weapon_for_player, FiringMode, Weapon, WeaponInventory, WeaponsConfig}};
+use crate::{character::{config::CharacterConfigHandles, movement::Velocity}, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings, HitZones}, global_asset::GlobalAsset, weapons::{spawn_weapon_for_player, FiringMode, Weapon, WeaponInventory, WeaponsConfig}};
 
 use bevy_ggrs::AddRollbackCommandExtension;
 
@@ -21,6 +23,8 @@ pub fn create_character(
     config_name: String,
 
     skin: Option<String>,
+    // Index in the palette of the skin for each tinted layer
+    colors: &BTreeMap<String, usize>,
     color_health_bar: Color,
     translation: Vec3,
 
@@ -31,10 +35,10 @@ pub fn create_character(
 
     let map_layers = global_assets.spritesheets.get(&config.asset_name_ref).unwrap().clone();
     let animation_handle = global_assets.animations.get(&config.asset_name_ref).unwrap().clone();
-    let player_config_handle = global_assets.character_configs.get(&config.asset_name_ref).unwrap().clone();
 
-    let starting_layer = config.skins.get(skin.as_ref().unwrap_or(&config.starting_skin)).unwrap()
-        .layers.clone();
+    let character_skin = config.skins.get(skin.as_ref().unwrap_or(&config.starting_skin)).unwrap();
+    let starting_layer = character_skin.layers.clone();
+    let layer_colors = character_skin.layer_colors(colors);
 
     let animation_bundle =
         AnimationBundle::new(map_layers.clone(), animation_handle.clone(),0, starting_layer.clone());
@@ -53,7 +57,7 @@ pub fn create_character(
         collision_layer,
         Character::default(),
         CharacterConfigHandles {
-            config: player_config_handle.clone(),
+            config: handle.clone(),
         },
         animation_bundle,
     ));
@@ -70,16 +74,23 @@ pub fn create_character(
         )).add_rollback();
     });
 
+    if !config.hit_zones.is_empty() {
+        entity.insert(HitZones(config.hit_zones.iter().map(Into::into).collect()));
+    }
+
     let entity = entity.add_rollback().id();
 
 
     for k in starting_layer.keys() {
         let spritesheet_config = sprint_sheet_assets.get(map_layers.get(k).unwrap()).unwrap();
-        create_child_sprite(
+        let sprite = create_child_sprite(
             commands,
             &asset_server,
             texture_atlas_layouts,
             entity.clone(), &spritesheet_config, 0);
+        if let Some(color) = layer_colors.get(&spritesheet_config.name) {
+            commands.entity(sprite).insert(ColoredLayer { color: *color });
+        }
     }
 
     entity
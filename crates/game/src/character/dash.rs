@@ -1,46 +1,46 @@
 use bevy::prelude::*;
+use utils::frame::FrameTimer;
 
 
 #[derive(Component, Reflect, Default, Clone)]
 #[reflect(Component)]
 pub struct DashState {
-    pub is_dashing: bool,
+    pub dash_timer: Option<FrameTimer>,
+    pub cooldown_timer: Option<FrameTimer>,
     pub dash_direction: Vec2,
-    pub dash_frames_remaining: u32,
-    pub dash_cooldown_remaining: u32,
-    pub dash_distance_per_frame: f32,  // Distance to move each frame
     pub dash_start_position: Vec3,     // Starting position for the dash
     pub dash_total_distance: f32,      // Total distance for current dash
 }
 
 impl DashState {
-    pub fn can_dash(&self) -> bool {
-        !self.is_dashing && self.dash_cooldown_remaining == 0
+    pub fn is_dashing(&self) -> bool {
+        self.dash_timer.is_some()
     }
-    
-    pub fn start_dash(&mut self, direction: Vec2, start_position: Vec3, total_distance: f32, duration_frames: u32) {
-        self.is_dashing = true;
+
+    pub fn can_dash(&self, current_frame: u32) -> bool {
+        !self.is_dashing() && self.cooldown_timer.map_or(true, |timer| timer.is_done(current_frame))
+    }
+
+    pub fn start_dash(&mut self, direction: Vec2, start_position: Vec3, total_distance: f32, current_frame: u32, duration_frames: u32) {
+        self.dash_timer = Some(FrameTimer::new(current_frame, duration_frames));
         self.dash_direction = direction.normalize();
-        self.dash_frames_remaining = duration_frames;
         self.dash_start_position = start_position;
         self.dash_total_distance = total_distance;
-        self.dash_distance_per_frame = total_distance / duration_frames as f32;
     }
-    
-    pub fn update(&mut self) {
-        if self.is_dashing {
-            self.dash_frames_remaining = self.dash_frames_remaining.saturating_sub(1);
-            if self.dash_frames_remaining == 0 {
-                self.is_dashing = false;
-            }
-        }
-        
-        if self.dash_cooldown_remaining > 0 {
-            self.dash_cooldown_remaining = self.dash_cooldown_remaining.saturating_sub(1);
+
+    // Position along the dash for this frame, the dash end once the full distance is reached
+    pub fn advance(&mut self, current_frame: u32) -> Option<Vec3> {
+        let timer = self.dash_timer?;
+
+        let dash_offset = self.dash_direction * self.dash_total_distance * timer.fraction(current_frame);
+        if timer.is_done(current_frame) {
+            self.dash_timer = None;
         }
+
+        Some(self.dash_start_position + Vec3::new(dash_offset.x, dash_offset.y, 0.0))
     }
-    
-    pub fn set_cooldown(&mut self, cooldown_frames: u32) {
-        self.dash_cooldown_remaining = cooldown_frames;
+
+    pub fn set_cooldown(&mut self, current_frame: u32, cooldown_frames: u32) {
+        self.cooldown_timer = Some(FrameTimer::new(current_frame, cooldown_frames));
     }
-}
\ No newline at end of file
+}
@@ -1,15 +1,18 @@
-use animation::FacingDirection;
+use animation::{FacingDirection, FacingDirection8};
 // crates/game/src/enemy/path.rs
 use bevy::prelude::*;
-use utils::rng::RollbackRng;
 use std::collections::VecDeque;
 use crate::character::config::{CharacterConfig, CharacterConfigHandles};
-use crate::character::enemy::Enemy;
+use crate::character::enemy::{attack::EnemyAttackState, climb::Climbing, exploder::ExploderState, flinch::FlinchState, Enemy};
 use crate::character::movement::Velocity;
-use crate::character::player::input::FIXED_TIMESTEP;
 use crate::character::player::Player;
-use crate::collider::{Collider, is_colliding, Wall};
+use map::game::{entity::map::climb::ClimbableWallComponent, nav::{NavGrid, NavObstacle, STRAIGHT_COST}};
+use crate::barricade::Barricade;
+use crate::collider::{Collider, ColliderShape, is_colliding, Wall};
+use crate::collider::spatial::{ObstacleKind, SpatialHash};
 use crate::frame::FrameCount;
+use crate::rules::{objective::Generator, GameRules};
+use utils::{cache::{CacheKey, FrameCache}, frame::SimulationConfig};
 
 
 #[derive(Component, Debug, Clone, Reflect, Default)]
@@ -25,6 +28,17 @@ pub struct EnemyPath {
     pub path_status: PathStatus,
 }
 
+// Paths found during the last frames, reused when a rollback replay a frame with the
+// same position and target. Not registered for rollback, an enemy respawned by a
+// rollback simply start without it.
+#[derive(Component, Default, Clone)]
+pub struct PathCache(pub FrameCache<Option<Vec<Vec2>>>);
+
+// Separation force of every enemy, the whole horde is the input so it's only reused
+// when no enemy moved differently during the replay
+#[derive(Resource, Default)]
+pub struct SeparationCache(pub FrameCache<Vec<(Entity, Vec2)>>);
+
 #[derive(Debug, Clone, Reflect, PartialEq, Eq, Default)]
 pub enum PathStatus {
     #[default]
@@ -46,7 +60,7 @@ pub struct PathfindingConfig {
     pub max_path_length: usize,
     // Direct path threshold (distance at which to use direct path)
     pub direct_path_threshold: f32,
-    // Node size for discretization (if using grid-based approach)
+    // Cell size of the nav grid
     pub node_size: f32,
     // Movement speed fallback
     pub movement_speed: f32,
@@ -60,6 +74,43 @@ pub struct PathfindingConfig {
     pub enemy_separation_force: f32,
     // Separation distance between enemies
     pub enemy_separation_distance: f32,
+    // Radius of the enemies used to block the nav grid cells close to the walls
+    pub agent_radius: f32,
+    // Distance at which enemies start to steer around small obstacles
+    pub obstacle_avoidance_distance: f32,
+    // Avoidance weight for each kind of obstacle
+    pub pickup_avoidance_weight: f32,
+    pub prop_avoidance_weight: f32,
+    pub door_frame_avoidance_weight: f32,
+    // Extra cost of crossing an intact barricade in cells of detour, the enemies tear it
+    // down when it's cheaper than going around. The health part scale with what is left.
+    pub barricade_base_cost: f32,
+    pub barricade_health_cost: f32,
+    // Steps of health the cost is rounded to, the grid is baked again at each step
+    pub barricade_cost_steps: u32,
+}
+
+impl PathfindingConfig {
+    pub fn avoidance_weight(&self, kind: ObstacleKind) -> f32 {
+        match kind {
+            ObstacleKind::Pickup => self.pickup_avoidance_weight,
+            ObstacleKind::Prop => self.prop_avoidance_weight,
+            ObstacleKind::DoorFrame => self.door_frame_avoidance_weight,
+        }
+    }
+
+    // Extra cost of a cell of the barricade in the nav grid, from its health rounded to a step
+    pub fn barricade_cost(&self, barricade: &Barricade) -> u32 {
+        let steps = self.barricade_cost_steps.max(1) as f32;
+        let ratio = (barricade.health / barricade.max_health.max(1.0)).clamp(0.0, 1.0);
+        let ratio = (ratio * steps).ceil() / steps;
+        ((self.barricade_base_cost + self.barricade_health_cost * ratio) * STRAIGHT_COST as f32) as u32
+    }
+
+    // Extra cost of a cell of a climbable wall, the detour of the wall in cells
+    pub fn climb_cost(&self, wall: &ClimbableWallComponent) -> u32 {
+        (wall.config.cost.max(0.0) * STRAIGHT_COST as f32) as u32
+    }
 }
 
 impl Default for PathfindingConfig {
@@ -76,6 +127,14 @@ impl Default for PathfindingConfig {
             slow_down_distance: 150.0,          // Start slowing down at this distance
             enemy_separation_force: 2.0,        // Much stronger separation force
             enemy_separation_distance: 80.0,    // Larger separation distance
+            agent_radius: 15.0,
+            obstacle_avoidance_distance: 40.0,
+            pickup_avoidance_weight: 1.0,       // Pickups are small, just go around
+            prop_avoidance_weight: 3.0,
+            door_frame_avoidance_weight: 4.0,   // Avoid getting stuck on the frame
+            barricade_base_cost: 5.0,
+            barricade_health_cost: 30.0,
+            barricade_cost_steps: 4,
         }
     }
 }
@@ -83,9 +142,12 @@ impl Default for PathfindingConfig {
 // System to find closest player and set as target
 pub fn update_enemy_targets(
     player_query: Query<(&Transform, &Player)>,
+    generator_query: Query<&Transform, (With<Generator>, Without<Enemy>)>,
     mut enemy_query: Query<(&Transform, &mut EnemyPath), With<Enemy>>,
     frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
     config: Res<PathfindingConfig>,
+    rules: Res<GameRules>,
 ) {
     // Get all player positions
     let player_positions: Vec<Vec2> = player_query
@@ -97,10 +159,12 @@ pub fn update_enemy_targets(
         return;
     }
     
+    // Only update periodically to save performance
+    let recalculation_interval = simulation.frames(config.recalculation_interval).max(1);
+
     // Update each enemy's target
     for (transform, mut path) in enemy_query.iter_mut() {
-        // Only update periodically to save performance
-        if frame.frame % config.recalculation_interval != 0 {
+        if frame.frame % recalculation_interval != 0 {
             continue;
         }
         
@@ -117,8 +181,12 @@ pub fn update_enemy_targets(
             }
         }
         
-        // Set the target
-        path.target_position = closest_player;
+        // In the objective mode the generator come first, unless a player is in the way
+        let generator = generator_query.get_single().ok().filter(|_| rules.has_generator());
+        path.target_position = match generator {
+            Some(generator) if closest_distance > rules.player_aggro_distance => generator.translation.truncate(),
+            _ => closest_player,
+        };
         
         // Mark for path recalculation
         path.recalculate_ticks = frame.frame;
@@ -183,93 +251,90 @@ pub fn check_direct_paths(
     }
 }
 
-// System to calculate paths around obstacles when needed
+fn nav_obstacle(transform: &Transform, collider: &Collider) -> NavObstacle {
+    let center = transform.translation.truncate() + collider.offset;
+    match collider.shape {
+        ColliderShape::Circle { radius } => NavObstacle::Circle { center, radius },
+        ColliderShape::Rectangle { width, height } => NavObstacle::Rect { center, half_size: Vec2::new(width / 2.0, height / 2.0) },
+    }
+}
+
+// Bake the nav grid again when the walls changed, the walls are part of the
+// rollback world so every peer bake the same grid on the same frame. A closed door
+// is a wall until opened, an intact barricade can be crossed for the cost of tearing
+// it down and is a wall of the grid no more once broken, a climbable wall for the cost
+// of climbing it.
+pub fn rollback_bake_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    wall_query: Query<(Entity, &Transform, &Collider, Option<&Barricade>, Option<&ClimbableWallComponent>), With<Wall>>,
+    config: Res<PathfindingConfig>,
+) {
+    let mut walls: Vec<_> = wall_query.iter()
+        .map(|(entity, transform, collider, barricade, climbable)| {
+            let cost = barricade.map(|barricade| config.barricade_cost(barricade))
+                .or(climbable.map(|wall| config.climb_cost(wall)));
+            (entity, nav_obstacle(transform, collider), cost)
+        })
+        .collect();
+    walls.sort_by_key(|(entity, ..)| entity.index());
+
+    let signature = walls.iter().fold(
+        CacheKey::default().with_f32(config.node_size).with_f32(config.agent_radius),
+        |key, (entity, _, cost)| key.with_u32(entity.index()).with_u32(cost.map_or(0, |cost| cost + 1)),
+    );
+    if signature == nav_grid.signature {
+        return;
+    }
+
+    let obstacles: Vec<NavObstacle> = walls.iter().filter(|(.., cost)| cost.is_none()).map(|(_, obstacle, _)| *obstacle).collect();
+    let costly: Vec<(NavObstacle, u32)> = walls.iter().filter_map(|(_, obstacle, cost)| cost.map(|cost| (*obstacle, cost))).collect();
+
+    *nav_grid = NavGrid::bake_with_costs(&obstacles, &costly, config.node_size, config.agent_radius);
+    nav_grid.signature = signature;
+    info!("nav grid baked {}x{} from {} walls and {} barricades or climbable walls", nav_grid.width, nav_grid.height, obstacles.len(), costly.len());
+}
+
+// System to calculate paths around obstacles when needed, A* over the baked nav grid
 pub fn calculate_paths(
-    mut enemy_query: Query<(&Transform, &mut EnemyPath), With<Enemy>>,
-    wall_query: Query<(&Transform, &Collider), With<Wall>>,
-    mut rng: ResMut<RollbackRng>,
-    config: Res<PathfindingConfig>, 
+    mut enemy_query: Query<(&Transform, &mut EnemyPath, Option<&mut PathCache>), With<Enemy>>,
+    frame: Res<FrameCount>,
+    nav_grid: Res<NavGrid>,
+    config: Res<PathfindingConfig>,
 ) {
-    for (transform, mut path) in enemy_query.iter_mut() {
+    let grid_key = CacheKey::default()
+        .with_key(nav_grid.signature)
+        .with_f32(nav_grid.cell_size)
+        .with_i32(nav_grid.width)
+        .with_i32(nav_grid.height)
+        .with_u32(config.max_iterations);
+
+    for (transform, mut path, opt_cache) in enemy_query.iter_mut() {
         if path.path_status != PathStatus::CalculatingPath {
             continue;
         }
-        
+
         let enemy_pos = transform.translation.truncate();
         let target = path.target_position;
-        
-        // Simplified A* pathfinding using vector-based movement:
-        // This is a simplified version that works well for games with fewer obstacles
-        let mut waypoints = VecDeque::new();
-        let direct_dir = (target - enemy_pos).normalize();
-        
-        // Try several angles to find a clear path
-        let base_angles = [0.0, 0.5, -0.5, 1.0, -1.0, 1.5, -1.5];
-        let mut best_angle = 0.0;
-        let mut best_clearance = 0.0;
-        
-        for angle_offset in base_angles {
-            let angle = direct_dir.y.atan2(direct_dir.x) + angle_offset;
-            let test_dir = Vec2::new(angle.cos(), angle.sin());
-            
-            // Check how far we can go in this direction
-            let mut max_distance = 0.0;
-            let step_size = 20.0;
-            let test_collider = Collider {
-                shape: crate::collider::ColliderShape::Circle { radius: 15.0 },
-                offset: Vec2::ZERO,
-            };
-            
-            for i in 1..20 {
-                let test_dist = i as f32 * step_size;
-                let test_pos = enemy_pos + test_dir * test_dist;
-                let test_transform = Transform::from_translation(test_pos.extend(0.0));
-                
-                let mut collides = false;
-                for (wall_transform, wall_collider) in wall_query.iter() {
-                    if is_colliding(&test_transform, &test_collider, wall_transform, wall_collider) {
-                        collides = true;
-                        break;
-                    }
-                }
-                
-                if collides {
-                    max_distance = test_dist - step_size;
-                    break;
-                } else {
-                    max_distance = test_dist;
-                }
+
+        let found = match opt_cache {
+            Some(mut cache) => {
+                let key = grid_key.with_vec2(enemy_pos).with_vec2(target);
+                cache.0.get_or_insert_with(frame.frame, key, || nav_grid.find_path(enemy_pos, target, config.max_iterations)).clone()
             }
-            
-            // Consider distance to target along this path
-            let waypoint = enemy_pos + test_dir * max_distance;
-            let remaining = target.distance(waypoint);
-            let clearance = max_distance / (1.0 + remaining * 0.01);
-            
-            if clearance > best_clearance {
-                best_clearance = clearance;
-                best_angle = angle;
+            None => nav_grid.find_path(enemy_pos, target, config.max_iterations),
+        };
+
+        match found {
+            Some(waypoints) => {
+                path.waypoints = waypoints.into_iter().take(config.max_path_length).collect();
+                path.path_status = PathStatus::FollowingPath;
+            }
+            None => {
+                // No path in budget, walk straight and try again on the next recalculation
+                path.waypoints.clear();
+                path.path_status = PathStatus::DirectPath;
             }
         }
-        
-        // Found a good direction - create waypoint
-        let best_dir = Vec2::new(best_angle.cos(), best_angle.sin());
-        let distance = (best_clearance * 0.8).min(100.0); // Don't go too far at once
-        
-        // Add a slight random variation to prevent getting stuck in patterns
-        let jitter_angle = (rng.next_f32() - 0.5) * 0.2;
-        let jitter_dir = Vec2::new((best_angle + jitter_angle).cos(), (best_angle + jitter_angle).sin());
-        let waypoint = enemy_pos + jitter_dir * distance;
-        
-        waypoints.push_back(waypoint);
-        
-        // Optionally add the final target as the last waypoint
-        if waypoints.len() < config.max_path_length {
-            waypoints.push_back(target);
-        }
-        
-        path.waypoints = waypoints;
-        path.path_status = PathStatus::FollowingPath;
     }
 }
 
@@ -280,20 +345,43 @@ pub fn move_enemies(
         &mut Velocity, 
         &mut EnemyPath, 
         &mut FacingDirection,
-        &CharacterConfigHandles
+        &mut FacingDirection8,
+        &CharacterConfigHandles,
+        Option<&EnemyAttackState>,
+        Option<&FlinchState>,
+        Option<&ExploderState>,
+        Has<Climbing>,
     ), With<Enemy>>,
     player_query: Query<&Transform, (With<Player>, Without<Enemy>)>,
     character_configs: Res<Assets<CharacterConfig>>,
     config: Res<PathfindingConfig>,
+    spatial_hash: Res<SpatialHash>,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    mut separation_cache: ResMut<SeparationCache>,
 ) {
     // First pass - collect all enemy positions for separation calculation
-    let enemy_positions: Vec<(Entity, Vec2)> = enemy_query
+    let mut enemy_positions: Vec<(Entity, Vec2)> = enemy_query
         .iter()
         .map(|(entity, transform, ..)| (entity, transform.translation.truncate()))
         .collect();
+    enemy_positions.sort_by_key(|(entity, _)| entity.index());
+
+    let separation_key = enemy_positions.iter().fold(
+        CacheKey::default().with_f32(config.enemy_separation_distance).with_f32(config.enemy_separation_force),
+        |key, (entity, position)| key.with_u32(entity.index()).with_vec2(*position),
+    );
+    let separations = separation_cache.0
+        .get_or_insert_with(frame.frame, separation_key, || compute_separations(&enemy_positions, &config));
     
     // Second pass - calculate and apply movement
-    for (entity, mut transform, mut velocity, mut path, mut facing_direction, config_handles) in enemy_query.iter_mut() {
+    for (entity, mut transform, mut velocity, mut path, mut facing_direction, mut facing_direction_8, config_handles, attack_state, flinch, exploder, climbing) in enemy_query.iter_mut() {
+        // The attack and the climb move the enemy themselves, a staggered or lit enemy stand still
+        if climbing || attack_state.map_or(false, |state| state.is_busy()) || flinch.map_or(false, |flinch| flinch.is_staggered(frame.frame)) || exploder.map_or(false, |exploder| exploder.is_fusing()) {
+            velocity.0 = Vec2::ZERO;
+            continue;
+        }
+
         let enemy_pos = transform.translation.truncate();
         
         // Get character movement config
@@ -322,30 +410,29 @@ pub fn move_enemies(
             distance_to_nearest_player = distance_to_nearest_player.min(distance);
         }
         
-        // Calculate separation force (avoid other enemies)
-        let mut separation = Vec2::ZERO;
-        let mut separation_count = 0;
+        // Separation force (avoid other enemies), sorted by entity like the positions
+        let separation = separations
+            .binary_search_by_key(&entity.index(), |(other, _)| other.index())
+            .map_or(Vec2::ZERO, |i| separations[i].1);
         
-        for (other_entity, other_pos) in &enemy_positions {
-            // Skip self
-            if *other_entity == entity {
+        // Calculate avoidance force (steer around pickups, props and door frames)
+        let mut avoidance = Vec2::ZERO;
+        for obstacle in spatial_hash.query(enemy_pos, config.obstacle_avoidance_distance) {
+            let offset = enemy_pos - obstacle.position;
+            let distance = offset.length();
+            let range = config.obstacle_avoidance_distance + obstacle.radius;
+            if distance < 0.1 || distance >= range {
                 continue;
             }
-            
-            let distance = enemy_pos.distance(*other_pos);
-            if distance < config.enemy_separation_distance && distance > 0.1 {
-                // Calculate repulsion vector (away from other enemy)
-                let repulsion = (enemy_pos - *other_pos).normalize() / distance.max(1.0);
-                separation += repulsion;
-                separation_count += 1;
-            }
-        }
-        
-        // Normalize and scale separation force
-        if separation_count > 0 {
-            separation = (separation / separation_count as f32) * config.enemy_separation_force;
+
+            // Push sideway when the obstacle is in front so we go around instead of stopping
+            let away = offset / distance;
+            let side = if direction_to_target.perp_dot(away) >= 0.0 { direction_to_target.perp() } else { -direction_to_target.perp() };
+            let strength = (1.0 - distance / range) * config.avoidance_weight(obstacle.kind);
+            avoidance += (away + side) * strength;
         }
-        
+        avoidance *= movement_speed * 0.5;
+
         // Base movement velocity
         let mut move_velocity = Vec2::ZERO;
         
@@ -391,14 +478,14 @@ pub fn move_enemies(
             }
         }
         
-        // Combine movement and separation
-        let final_velocity = move_velocity + separation;
+        // Combine movement, separation and obstacle avoidance
+        let final_velocity = move_velocity + separation + avoidance;
         velocity.0 = final_velocity;
         
         // Apply movement
         if velocity.length_squared() > 0.01 {
-            transform.translation.x += velocity.x * FIXED_TIMESTEP;
-            transform.translation.y += velocity.y * FIXED_TIMESTEP;
+            transform.translation.x += velocity.x * simulation.timestep();
+            transform.translation.y += velocity.y * simulation.timestep();
             
             // Update facing direction based on movement
             if velocity.x > 0.1 {
@@ -406,10 +493,42 @@ pub fn move_enemies(
             } else if velocity.x < -0.1 {
                 *facing_direction = FacingDirection::Left;
             }
+            if let Some(direction_8) = FacingDirection8::from_aim(velocity.x.round() as i32, velocity.y.round() as i32) {
+                *facing_direction_8 = direction_8;
+            }
         }
     }
 }
 
+// Repulsion of every enemy from the others closer than the separation distance, O(n²)
+pub fn compute_separations(enemy_positions: &[(Entity, Vec2)], config: &PathfindingConfig) -> Vec<(Entity, Vec2)> {
+    enemy_positions.iter().map(|(entity, enemy_pos)| {
+        let mut separation = Vec2::ZERO;
+        let mut separation_count = 0;
+
+        for (other_entity, other_pos) in enemy_positions {
+            // Skip self
+            if other_entity == entity {
+                continue;
+            }
+
+            let distance = enemy_pos.distance(*other_pos);
+            if distance < config.enemy_separation_distance && distance > 0.1 {
+                // Calculate repulsion vector (away from other enemy)
+                let repulsion = (*enemy_pos - *other_pos).normalize() / distance.max(1.0);
+                separation += repulsion;
+                separation_count += 1;
+            }
+        }
+
+        // Normalize and scale separation force
+        if separation_count > 0 {
+            separation = (separation / separation_count as f32) * config.enemy_separation_force;
+        }
+        (*entity, separation)
+    }).collect()
+}
+
 fn update_facing_direction(facing_direction: &mut FacingDirection, velocity: &Velocity) {
     if velocity.x > 0.1 {
         *facing_direction = FacingDirection::Right;
@@ -0,0 +1,457 @@
+use bevy::{ecs::entity::{EntityMapper, MapEntities}, prelude::*};
+use bevy_ggrs::Rollback;
+use serde::Deserialize;
+use utils::{events::RollbackEvents, frame::{FrameTimer, SimulationConfig}, link::EntityLink, math::round_vec2, sweep::point_at_toi};
+
+use crate::{character::{config::{CharacterConfig, CharacterConfigHandles}, health::{accumulate_damage, DamageAccumulator, HitBy}, movement::Velocity, player::Player, status::{Grabbed, Knockback}}, collider::{sweep_collision, Collider, CollisionLayer, CollisionSettings, Wall}, frame::FrameCount, rng::AiRng, rules::deathmatch::Respawning};
+
+use super::{flinch::FlinchState, Enemy};
+
+// Attacks of the enemies against the players, configured per archetype in the character
+// config. The first attack of the list in range of the closest player is used. The damage
+// of a hit is rolled on the AI stream and can land as a heavy hit that push the player.
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum AttackKind {
+    // Hit the player in range right away
+    Melee,
+    // Stop for the telegraph, then dash toward where the player will be at the end of the dash
+    Lunge {
+        telegraph_frames: u32,
+        dash_frames: u32,
+        dash_distance: f32,
+        // Distance from the player during the dash to land the hit
+        hit_radius: f32,
+    },
+    // Root the player until the end of the grab or until it mash the dash key enough
+    Grab {
+        root_frames: u32,
+        mashes_to_escape: u32,
+    },
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct AttackConfig {
+    pub kind: AttackKind,
+    // Distance to the player to start the attack
+    pub range: f32,
+    pub damage: f32,
+    // Damage rolled between `damage` and this, always `damage` when not set
+    #[serde(default)]
+    pub max_damage: Option<f32>,
+    #[serde(default)]
+    pub heavy_hit: Option<HeavyHitConfig>,
+    pub cooldown_frames: u32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct HeavyHitConfig {
+    // Between 0 and 1, rolled on every hit
+    pub chance: f32,
+    pub damage_multiplier: f32,
+    // Push of the player away from the enemy, in units per second
+    pub knockback_speed: f32,
+    pub knockback_frames: u32,
+}
+
+// Sent when a heavy hit land, the victim see it on screen once confirmed
+#[derive(Clone, Debug)]
+pub struct HeavyHitEvent {
+    pub target: Entity,
+    pub attacker: Entity,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Hit {
+    target: Entity,
+    damage: f32,
+    attacker: Entity,
+    // Velocity of the push and its duration
+    knockback: Option<(Vec2, u32)>,
+}
+
+impl AttackConfig {
+    // Two rolls for every hit, even without variance, so adding one to an archetype
+    // doesn't shift the rolls of the others
+    fn roll_hit(&self, rng: &mut AiRng, target: Entity, target_position: Vec2, attacker: Entity, attacker_position: Vec2) -> Hit {
+        let damage_roll = rng.next_f32();
+        let heavy_roll = rng.next_f32();
+
+        let mut damage = match self.max_damage {
+            Some(max_damage) if max_damage > self.damage => self.damage + (max_damage - self.damage) * damage_roll,
+            _ => self.damage,
+        };
+        let mut knockback = None;
+        if let Some(heavy_hit) = self.heavy_hit.filter(|heavy_hit| heavy_roll < heavy_hit.chance) {
+            damage *= heavy_hit.damage_multiplier;
+            let direction = (target_position - attacker_position).normalize_or(Vec2::X);
+            knockback = Some((round_vec2(direction * heavy_hit.knockback_speed), heavy_hit.knockback_frames));
+        }
+        Hit { target, damage: damage.round(), attacker, knockback }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq)]
+pub enum AttackPhase {
+    #[default]
+    Idle,
+    Telegraph { attack: usize, target: EntityLink, timer: FrameTimer },
+    Lunge { attack: usize, target: EntityLink, timer: FrameTimer, start: Vec2, end: Vec2, hit: bool },
+    Grabbing { target: EntityLink },
+}
+
+impl AttackPhase {
+    // Player the attack is for, a gone one end the attack with its cooldown
+    pub fn target(&self) -> Option<EntityLink> {
+        match self {
+            AttackPhase::Idle => None,
+            AttackPhase::Telegraph { target, .. } | AttackPhase::Lunge { target, .. } | AttackPhase::Grabbing { target } => Some(*target),
+        }
+    }
+
+    fn target_mut(&mut self) -> Option<&mut EntityLink> {
+        match self {
+            AttackPhase::Idle => None,
+            AttackPhase::Telegraph { target, .. } | AttackPhase::Lunge { target, .. } | AttackPhase::Grabbing { target } => Some(target),
+        }
+    }
+}
+
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct EnemyAttackState {
+    pub phase: AttackPhase,
+    pub cooldown: Option<FrameTimer>,
+}
+
+impl MapEntities for EnemyAttackState {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        if let Some(target) = self.phase.target_mut() {
+            target.map_entities(entity_mapper);
+        }
+    }
+}
+
+impl EnemyAttackState {
+    // The enemy is in the middle of an attack and must not be moved by the pathing
+    pub fn is_busy(&self) -> bool {
+        self.phase != AttackPhase::Idle
+    }
+
+    fn is_ready(&self, frame: u32) -> bool {
+        self.cooldown.map_or(true, |cooldown| cooldown.is_done(frame))
+    }
+
+    fn finish(&mut self, frame: u32, cooldown_frames: u32) {
+        self.phase = AttackPhase::Idle;
+        self.cooldown = Some(FrameTimer::new(frame, cooldown_frames));
+    }
+
+    // Flinch in the middle of an attack, it is lost but the cooldown still apply
+    fn interrupt(&mut self, frame: u32, attacks: &[AttackConfig], simulation: &SimulationConfig) {
+        let attack = match self.phase {
+            AttackPhase::Idle => return,
+            AttackPhase::Telegraph { attack, .. } | AttackPhase::Lunge { attack, .. } => attacks.get(attack),
+            AttackPhase::Grabbing { .. } => attacks.iter().find(|attack| matches!(attack.kind, AttackKind::Grab { .. })),
+        };
+        self.finish(frame, attack.map_or(0, |attack| simulation.frames(attack.cooldown_frames)));
+    }
+}
+
+// Position of the target in `frames`, done in 1/1000 units so the lead is the same on all peers
+pub fn predict_position(position: Vec2, velocity: Vec2, frames: u32, tick_rate: u32) -> Vec2 {
+    let to_fixed = |v: f32| (v * 1000.0).round() as i64;
+    let lead = |p: f32, v: f32| (to_fixed(p) + to_fixed(v) * frames as i64 / tick_rate.max(1) as i64) as f32 / 1000.0;
+    Vec2::new(lead(position.x, velocity.x), lead(position.y, velocity.y))
+}
+
+// Distance kept from the wall that stopped a lunge, touching it would block the next move
+const LUNGE_WALL_GAP: f32 = 1.0;
+
+// End of a lunge cut at the first wall on the way
+pub fn clamp_lunge_end<'a>(start: Vec2, end: Vec2, collider: &Collider, walls: impl Iterator<Item = (&'a Transform, &'a Collider)>) -> Vec2 {
+    let Some(toi) = walls.filter_map(|(transform, wall)| sweep_collision(start, end, collider, transform, wall)).min() else {
+        return end;
+    };
+    let impact = point_at_toi(start, end, toi);
+    let back = (impact - start).normalize_or_zero() * impact.distance(start).min(LUNGE_WALL_GAP);
+    round_vec2(impact - back)
+}
+
+pub fn rollback_enemy_attacks(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    mut rng: ResMut<AiRng>,
+    settings: Res<CollisionSettings>,
+    mut heavy_hit_events: ResMut<RollbackEvents<HeavyHitEvent>>,
+    mut enemy_query: Query<(Entity, &mut Transform, &mut EnemyAttackState, &CharacterConfigHandles, Option<&FlinchState>, Option<&Collider>, Option<&CollisionLayer>), (With<Enemy>, With<Rollback>)>,
+    wall_query: Query<(&Transform, &Collider, &CollisionLayer), (With<Wall>, Without<Enemy>)>,
+    mut player_query: Query<(Entity, &Transform, &Velocity, &Player, Option<&Grabbed>, Option<&mut DamageAccumulator>), (Without<Enemy>, Without<Respawning>, With<Rollback>)>,
+) {
+    let mut players: Vec<(Entity, Vec2, Vec2, usize, Option<Grabbed>)> = player_query.iter()
+        .map(|(entity, transform, velocity, player, grabbed, _)| (entity, transform.translation.truncate(), velocity.0, player.handle, grabbed.copied()))
+        .collect();
+    players.sort_by_key(|(_, _, _, handle, _)| *handle);
+
+    let mut enemies: Vec<_> = enemy_query.iter_mut().collect();
+    enemies.sort_by_key(|(entity, ..)| entity.index());
+
+    // Damage of the frame per player, applied once per player at the end
+    let mut hits: Vec<Hit> = vec![];
+
+    for (entity, mut transform, mut state, config_handles, opt_flinch, opt_collider, opt_layer) in enemies {
+        let Some(config) = character_configs.get(&config_handles.config) else {
+            continue;
+        };
+        if opt_flinch.map_or(false, |flinch| flinch.is_staggered(frame.frame)) {
+            state.interrupt(frame.frame, &config.attacks, &simulation);
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let target_of = |target: Entity| players.iter().find(|(e, ..)| *e == target);
+
+        match state.phase {
+            AttackPhase::Idle => {
+                if !state.is_ready(frame.frame) {
+                    continue;
+                }
+                let closest = players.iter()
+                    .map(|player| (player, position.distance(player.1)))
+                    .min_by(|(a, da), (b, db)| da.total_cmp(db).then(a.3.cmp(&b.3)));
+                let Some(((target, target_position, _, _, grabbed), distance)) = closest else {
+                    continue;
+                };
+                // One grab at a time on a player
+                let Some((index, attack)) = config.attacks.iter().enumerate()
+                    .filter(|(_, attack)| grabbed.is_none() || !matches!(attack.kind, AttackKind::Grab { .. }))
+                    .find(|(_, attack)| distance <= attack.range) else {
+                    continue;
+                };
+
+                match attack.kind {
+                    AttackKind::Melee => {
+                        hits.push(attack.roll_hit(&mut rng, *target, *target_position, entity, position));
+                        state.finish(frame.frame, simulation.frames(attack.cooldown_frames));
+                    }
+                    AttackKind::Lunge { telegraph_frames, .. } => {
+                        state.phase = AttackPhase::Telegraph { attack: index, target: EntityLink::new(*target), timer: FrameTimer::new(frame.frame, simulation.frames(telegraph_frames)) };
+                    }
+                    AttackKind::Grab { root_frames, mashes_to_escape } => {
+                        hits.push(attack.roll_hit(&mut rng, *target, *target_position, entity, position));
+                        commands.entity(*target).insert(Grabbed { by: EntityLink::new(entity), timer: FrameTimer::new(frame.frame, simulation.frames(root_frames)), mashes_left: mashes_to_escape });
+                        state.phase = AttackPhase::Grabbing { target: EntityLink::new(*target) };
+                    }
+                }
+            }
+            AttackPhase::Telegraph { attack, target, timer } => {
+                let Some(AttackConfig { kind: AttackKind::Lunge { dash_frames, dash_distance, .. }, cooldown_frames, .. }) = config.attacks.get(attack).copied() else {
+                    state.phase = AttackPhase::Idle;
+                    continue;
+                };
+                let cooldown_frames = simulation.frames(cooldown_frames);
+                let dash_frames = simulation.frames(dash_frames);
+                let Some((_, target_position, target_velocity, ..)) = target_of(target.entity()) else {
+                    state.finish(frame.frame, cooldown_frames);
+                    continue;
+                };
+                if !timer.is_done(frame.frame) {
+                    continue;
+                }
+
+                // Aim where the player will be when the dash end
+                let predicted = predict_position(*target_position, *target_velocity, dash_frames, simulation.tick_rate);
+                let offset = predicted - position;
+                let mut end = position + offset.clamp_length_max(dash_distance);
+                // The dash stop at the walls, without collider nothing block it
+                if let (Some(collider), Some(layer)) = (opt_collider, opt_layer) {
+                    let walls = wall_query.iter()
+                        .filter(|(.., wall_layer)| settings.layer_matrix[layer.0 as usize][wall_layer.0 as usize])
+                        .map(|(wall_transform, wall_collider, _)| (wall_transform, wall_collider));
+                    end = clamp_lunge_end(position, end, collider, walls);
+                }
+                state.phase = AttackPhase::Lunge { attack, target, timer: FrameTimer::new(frame.frame, dash_frames), start: position, end: round_vec2(end), hit: false };
+            }
+            AttackPhase::Lunge { attack, target, timer, start, end, hit } => {
+                let Some(attack_config @ AttackConfig { kind: AttackKind::Lunge { hit_radius, .. }, cooldown_frames, .. }) = config.attacks.get(attack).copied() else {
+                    state.phase = AttackPhase::Idle;
+                    continue;
+                };
+
+                let new_position = round_vec2(start.lerp(end, (timer.elapsed(frame.frame) + 1) as f32 / timer.duration.max(1) as f32));
+                transform.translation.x = new_position.x;
+                transform.translation.y = new_position.y;
+
+                let mut hit = hit;
+                if !hit {
+                    if let Some((target, target_position, ..)) = target_of(target.entity()) {
+                        if new_position.distance(*target_position) <= hit_radius {
+                            hits.push(attack_config.roll_hit(&mut rng, *target, *target_position, entity, new_position));
+                            hit = true;
+                        }
+                    }
+                }
+
+                if timer.remaining(frame.frame) <= 1 {
+                    state.finish(frame.frame, simulation.frames(cooldown_frames));
+                } else {
+                    state.phase = AttackPhase::Lunge { attack, target, timer, start, end, hit };
+                }
+            }
+            AttackPhase::Grabbing { target } => {
+                // The grab end when the player escaped, the grab expired or the player is gone
+                let holding = target_of(target.entity()).map_or(false, |(.., grabbed)| grabbed.map_or(false, |grabbed| grabbed.by.entity() == entity));
+                if !holding {
+                    let cooldown_frames = config.attacks.iter()
+                        .find(|attack| matches!(attack.kind, AttackKind::Grab { .. }))
+                        .map_or(0, |attack| simulation.frames(attack.cooldown_frames));
+                    state.finish(frame.frame, cooldown_frames);
+                }
+            }
+        }
+    }
+
+    // Release the players held by an enemy that is gone or that is not grabbing anymore
+    for (player, _, _, _, grabbed) in players.iter() {
+        if let Some(grabbed) = grabbed {
+            let holding = grabbed.by.get(&enemy_query).map_or(false, |(_, _, state, ..)| state.phase == AttackPhase::Grabbing { target: EntityLink::new(*player) });
+            if !holding {
+                commands.entity(*player).remove::<Grabbed>();
+            }
+        }
+    }
+
+    hits.sort_by_key(|hit| (hit.target.index(), hit.attacker.index()));
+    let mut i = 0;
+    while i < hits.len() {
+        let target = hits[i].target;
+        let mut damage = 0.0;
+        let mut last_attacker = hits[i].attacker;
+        // The last heavy hit push the player
+        let mut knockback = None;
+        while i < hits.len() && hits[i].target == target {
+            damage += hits[i].damage;
+            last_attacker = hits[i].attacker;
+            if let Some(push) = hits[i].knockback {
+                knockback = Some(push);
+                heavy_hit_events.send(frame.frame, HeavyHitEvent { target, attacker: hits[i].attacker });
+            }
+            i += 1;
+        }
+        if let Ok((_, _, _, _, grabbed, opt_accumulator)) = player_query.get_mut(target) {
+            // A grabbed player stay in the hands of the enemy
+            if let Some((velocity, frames)) = knockback.filter(|_| grabbed.is_none()) {
+                commands.entity(target).insert(Knockback { velocity, timer: FrameTimer::new(frame.frame, simulation.frames(frames)) });
+            }
+            accumulate_damage(&mut commands, target, opt_accumulator, damage, Some(HitBy::Entity(last_attacker)));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::collider::ColliderShape;
+
+    use super::*;
+
+    fn circle(radius: f32) -> Collider {
+        Collider { shape: ColliderShape::Circle { radius }, offset: Vec2::ZERO }
+    }
+
+    fn wall(x: f32) -> (Transform, Collider) {
+        (Transform::from_xyz(x, 0.0, 0.0), Collider { shape: ColliderShape::Rectangle { width: 20.0, height: 200.0 }, offset: Vec2::ZERO })
+    }
+
+    #[test]
+    fn test_lunge_without_wall_keep_its_end() {
+        let end = clamp_lunge_end(Vec2::ZERO, Vec2::new(200.0, 0.0), &circle(10.0), std::iter::empty());
+        assert_eq!(end, Vec2::new(200.0, 0.0));
+
+        // Behind the enemy, not on the way
+        let walls = [wall(-100.0)];
+        let end = clamp_lunge_end(Vec2::ZERO, Vec2::new(200.0, 0.0), &circle(10.0), walls.iter().map(|(t, c)| (t, c)));
+        assert_eq!(end, Vec2::new(200.0, 0.0));
+    }
+
+    #[test]
+    fn test_lunge_stop_before_the_first_wall() {
+        // Faces at x 90 and 140, the circle of 10 touch the first one at 80
+        let walls = [wall(150.0), wall(100.0)];
+        let end = clamp_lunge_end(Vec2::ZERO, Vec2::new(200.0, 0.0), &circle(10.0), walls.iter().map(|(t, c)| (t, c)));
+        assert!(end.x < 80.0 && end.x >= 80.0 - LUNGE_WALL_GAP - 0.01, "end {:?}", end);
+        assert_eq!(end.y, 0.0);
+    }
+
+    #[test]
+    fn test_lunge_against_a_wall_dont_move() {
+        let walls = [wall(15.0)];
+        let end = clamp_lunge_end(Vec2::ZERO, Vec2::new(200.0, 0.0), &circle(10.0), walls.iter().map(|(t, c)| (t, c)));
+        assert_eq!(end, Vec2::ZERO);
+    }
+
+    fn attack(max_damage: Option<f32>, chance: f32) -> AttackConfig {
+        AttackConfig {
+            kind: AttackKind::Melee,
+            range: 30.0,
+            damage: 10.0,
+            max_damage,
+            heavy_hit: Some(HeavyHitConfig { chance, damage_multiplier: 2.0, knockback_speed: 300.0, knockback_frames: 8 }),
+            cooldown_frames: 60,
+        }
+    }
+
+    fn roll(attack: &AttackConfig, rng: &mut AiRng) -> Hit {
+        attack.roll_hit(rng, Entity::from_raw(1), Vec2::new(0.0, 50.0), Entity::from_raw(2), Vec2::ZERO)
+    }
+
+    #[test]
+    fn test_roll_hit_damage_between_min_and_max() {
+        let mut rng = AiRng::new(7);
+        let attack = attack(Some(20.0), 0.0);
+        for _ in 0..100 {
+            let hit = roll(&attack, &mut rng);
+            assert!(hit.damage >= 10.0 && hit.damage <= 20.0, "damage {}", hit.damage);
+            assert!(hit.knockback.is_none());
+        }
+        // Without a max the damage never change
+        let fixed = AttackConfig { max_damage: None, ..attack };
+        assert_eq!(roll(&fixed, &mut rng).damage, 10.0);
+    }
+
+    #[test]
+    fn test_heavy_hit_push_away_from_the_enemy() {
+        let mut rng = AiRng::new(7);
+        let hit = roll(&attack(None, 1.0), &mut rng);
+        assert_eq!(hit.damage, 20.0);
+        assert_eq!(hit.knockback, Some((Vec2::new(0.0, 300.0), 8)));
+    }
+
+    #[test]
+    fn test_roll_hit_same_for_the_same_seed() {
+        let attack = attack(Some(20.0), 0.5);
+        let (mut a, mut b) = (AiRng::new(3), AiRng::new(3));
+        for _ in 0..20 {
+            let (hit_a, hit_b) = (roll(&attack, &mut a), roll(&attack, &mut b));
+            assert_eq!((hit_a.damage, hit_a.knockback), (hit_b.damage, hit_b.knockback));
+        }
+    }
+
+    #[test]
+    fn test_roll_hit_always_take_two_rolls() {
+        // Variance or not, the next roll of the stream is the same
+        let (mut a, mut b) = (AiRng::new(5), AiRng::new(5));
+        roll(&attack(Some(20.0), 0.5), &mut a);
+        roll(&AttackConfig { heavy_hit: None, ..attack(None, 0.0) }, &mut b);
+        assert_eq!(a.next_f32(), b.next_f32());
+    }
+
+    #[test]
+    fn test_target_of_the_phases() {
+        let target = EntityLink::new(Entity::from_raw(4));
+        assert_eq!(AttackPhase::Idle.target(), None);
+        assert_eq!(AttackPhase::Grabbing { target }.target(), Some(target));
+        assert_eq!(AttackPhase::Telegraph { attack: 0, target, timer: FrameTimer::new(0, 10) }.target(), Some(target));
+    }
+}
@@ -0,0 +1,182 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use map::game::entity::map::climb::{ClimbableWallComponent, ClimbableWallConfig};
+use utils::frame::{FrameTimer, SimulationConfig};
+
+use crate::{collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings, Wall}, frame::FrameCount};
+
+use super::{ai::pathing::{EnemyPath, PathStatus, PathfindingConfig}, Enemy};
+
+// Climbing over the low walls of the map. The nav grid let the paths go through a
+// climbable wall for its cost, an enemy reaching one with its next waypoint on the other
+// side climb it: it leave the ground for a number of frames, without collider, and is
+// moved across to land on the other side where its path is calculated again.
+
+pub const CLIMB_ANIMATION: &str = "Climb";
+
+// Distance from the wall, over the radius of the enemy, where the climb start
+const CLIMB_REACH: f32 = 10.0;
+
+#[derive(Component, Clone, Debug)]
+pub struct Climbing {
+    pub from: Vec2,
+    pub to: Vec2,
+    pub timer: FrameTimer,
+    // Removed for the climb, put back at the landing
+    pub collider: Option<Collider>,
+}
+
+pub fn climbable_wall_color() -> Color {
+    Color::srgb(0.5, 0.5, 0.45)
+}
+
+pub fn spawn_climbable_wall(
+    commands: &mut Commands,
+    position: Vec3,
+    config: ClimbableWallConfig,
+    collision_settings: &CollisionSettings,
+) -> Entity {
+    let mut entity_commands = commands.spawn((
+        Sprite::from_color(climbable_wall_color(), config.size),
+        Transform::from_translation(position),
+    ));
+    insert_climbable_wall_components(&mut entity_commands, config, collision_settings);
+
+    entity_commands.add_rollback().id()
+}
+
+// Everything the simulation need on a climbable wall, the sprite and the position are
+// left to the caller, the code or the map
+fn insert_climbable_wall_components(
+    entity_commands: &mut EntityCommands,
+    config: ClimbableWallConfig,
+    collision_settings: &CollisionSettings,
+) {
+    entity_commands.insert((
+        Wall,
+        Collider {
+            shape: ColliderShape::Rectangle { width: config.size.x, height: config.size.y },
+            offset: Vec2::ZERO,
+        },
+        CollisionLayer(collision_settings.wall_layer),
+        ClimbableWallComponent { config },
+    ));
+}
+
+fn distance_to_wall(center: Vec2, config: &ClimbableWallConfig, point: Vec2) -> f32 {
+    ((point - center).abs() - config.size / 2.0).max(Vec2::ZERO).length()
+}
+
+
+// SYSTEMS
+
+// Climbable walls placed in the map only come with their config and their sprite, they
+// get the same components as the ones spawned by the code
+pub fn setup_map_climbable_walls(
+    mut commands: Commands,
+    collision_settings: Res<CollisionSettings>,
+    wall_query: Query<(Entity, &ClimbableWallComponent), Without<Wall>>,
+) {
+    let mut walls: Vec<_> = wall_query.iter().collect();
+    walls.sort_by_key(|(entity, _)| entity.index());
+
+    for (entity, wall) in walls {
+        let mut entity_commands = commands.entity(entity);
+        insert_climbable_wall_components(&mut entity_commands, wall.config.clone(), &collision_settings);
+        entity_commands.add_rollback();
+    }
+}
+
+// Between the paths and the movement, a climbing enemy is not moved by its path
+pub fn rollback_climb_walls(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    config: Res<PathfindingConfig>,
+    wall_query: Query<(Entity, &Transform, &ClimbableWallComponent), (Without<Enemy>, With<Rollback>)>,
+    mut enemy_query: Query<(Entity, &mut Transform, &mut EnemyPath, Option<&Collider>, Option<&Climbing>), (With<Enemy>, With<Rollback>)>,
+) {
+    let mut walls: Vec<_> = wall_query.iter()
+        .map(|(entity, transform, wall)| (entity, transform.translation.truncate(), &wall.config))
+        .collect();
+    walls.sort_by_key(|(entity, ..)| entity.index());
+
+    let mut enemies: Vec<_> = enemy_query.iter_mut().collect();
+    enemies.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, mut transform, mut path, collider, climbing) in enemies {
+        if let Some(climbing) = climbing {
+            let position = climbing.from.lerp(climbing.to, climbing.timer.fraction(frame.frame));
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+            if climbing.timer.is_done(frame.frame) {
+                let mut entity_commands = commands.entity(entity);
+                entity_commands.remove::<Climbing>();
+                if let Some(collider) = climbing.collider.clone() {
+                    entity_commands.insert(collider);
+                }
+                path.waypoints.clear();
+                path.path_status = PathStatus::CalculatingPath;
+            }
+            continue;
+        }
+
+        if !matches!(path.path_status, PathStatus::DirectPath | PathStatus::FollowingPath) {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let next = path.waypoints.front().copied().unwrap_or(path.target_position);
+        let reach = config.agent_radius + CLIMB_REACH;
+        let Some((_, center, wall)) = walls.iter()
+            .find(|(_, center, wall)| distance_to_wall(*center, wall, position) <= reach && wall.is_crossed(*center, position, next)) else {
+            continue;
+        };
+
+        let to = wall.landing(*center, position, config.agent_radius);
+        commands.entity(entity)
+            .insert(Climbing {
+                from: position,
+                to,
+                timer: FrameTimer::new(frame.frame, simulation.frames(wall.climb_frames).max(1)),
+                collider: collider.cloned(),
+            })
+            .remove::<Collider>();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use animation::AnimationMapConfig;
+
+    use super::*;
+
+    #[test]
+    fn test_map_climbable_wall_get_the_components_of_the_code() {
+        let mut app = App::new();
+        // Normally added by the GgrsPlugin, needed by add_rollback
+        app.init_resource::<bevy_ggrs::RollbackOrdered>();
+        app.init_resource::<CollisionSettings>();
+        app.add_systems(Update, setup_map_climbable_walls);
+        let config = ClimbableWallConfig { size: Vec2::new(200.0, 24.0), ..Default::default() };
+        let entity = app.world_mut().spawn((ClimbableWallComponent { config }, Sprite::default(), Transform::default())).id();
+        app.update();
+
+        let world = app.world();
+        assert!(world.get::<Rollback>(entity).is_some());
+        assert!(world.get::<Wall>(entity).is_some());
+        assert_eq!(world.get::<CollisionLayer>(entity).map(|layer| layer.0), Some(CollisionSettings::default().wall_layer));
+        assert!(matches!(
+            world.get::<Collider>(entity).map(|collider| &collider.shape),
+            Some(ColliderShape::Rectangle { width, height }) if *width == 200.0 && *height == 24.0
+        ));
+    }
+
+    #[test]
+    fn test_zombie_animation_has_the_climb_state() {
+        let path = format!("{}/../../assets/ZombieShooter/Sprites/Zombie/zombie_animation.ron", env!("CARGO_MANIFEST_DIR"));
+        let bytes = std::fs::read(&path).unwrap();
+        let config: AnimationMapConfig = ron::de::from_bytes(&bytes).unwrap();
+        assert!(config.animations.contains_key(CLIMB_ANIMATION));
+    }
+}
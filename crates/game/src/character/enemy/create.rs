@@ -1,9 +1,11 @@
+use std::collections::BTreeMap;
+
 use animation::SpriteSheetConfig;
 use bevy::prelude::*;
 
 use crate::{character::{config::{CharacterConfig, CharacterConfigHandles}, create::create_character, movement::Velocity, player::input::CursorPosition}, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings}, global_asset::GlobalAsset, weapons::{WeaponInventory, WeaponsConfig}};
 
-use super::{ai::pathing::EnemyPath, Enemy};
+use super::{ai::pathing::{EnemyPath, PathCache}, attack::EnemyAttackState, exploder::ExploderState, flinch::FlinchState, shield::ShieldState, Enemy};
 
 pub fn spawn_enemy(
     enemy_type_name: String,
@@ -21,7 +23,7 @@ pub fn spawn_enemy(
 
     let entity = create_character(
         commands, global_assets, characters_asset, asset_server, texture_atlas_layouts, sprint_sheet_assets,
-        enemy_type_name, None,
+        enemy_type_name, None, &BTreeMap::new(),
         (LinearRgba::RED).into(),position, CollisionLayer(collision_settings.enemy_layer)
     );
 
@@ -31,6 +33,11 @@ pub fn spawn_enemy(
         .insert((
             inventory,
             EnemyPath::default(),
+            PathCache::default(),
+            EnemyAttackState::default(),
+            FlinchState::default(),
+            ExploderState::default(),
+            ShieldState::default(),
             Enemy::default(),
         ));
 
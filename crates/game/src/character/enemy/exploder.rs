@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use bevy_kira_audio::prelude::*;
+use serde::Deserialize;
+use utils::{events::RollbackEvents, frame::{FrameTimer, SimulationConfig}};
+
+use crate::{character::{config::{CharacterConfig, CharacterConfigHandles}, health::{Death, HitBy}, player::Player}, frame::FrameCount, plugins::AppState, rules::deathmatch::Respawning, weapons::explosion::spawn_explosion};
+
+use super::Enemy;
+
+// Enemies that blow up, the archetype has an exploder config. A player in range light
+// the fuse, the enemy stop and detonate at the end of it. Killed before, the fuse or not,
+// it still explode and the blast is credited to its killer. The blast go through the
+// explosion pipeline so it hurt the players and the other enemies alike.
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct ExploderConfig {
+    // Distance to the closest player to light the fuse
+    pub trigger_range: f32,
+    pub fuse_frames: u32,
+    pub radius: f32,
+    pub damage: f32,
+}
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExploderPhase {
+    #[default]
+    Idle,
+    Fuse { timer: FrameTimer },
+    // Blown by its fuse, the death must not explode a second time
+    Detonated,
+}
+
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct ExploderState {
+    pub phase: ExploderPhase,
+}
+
+// What the fuse did on a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FuseStep {
+    Wait,
+    Lit,
+    Detonate,
+}
+
+impl ExploderState {
+    // The enemy stand still while its fuse burn
+    pub fn is_fusing(&self) -> bool {
+        matches!(self.phase, ExploderPhase::Fuse { .. })
+    }
+
+    // Move the fuse one frame, `fuse_frames` already scaled to the tick rate
+    pub fn advance(&mut self, player_in_range: bool, fuse_frames: u32, frame: u32) -> FuseStep {
+        match self.phase {
+            ExploderPhase::Idle if player_in_range => {
+                self.phase = ExploderPhase::Fuse { timer: FrameTimer::new(frame, fuse_frames) };
+                FuseStep::Lit
+            }
+            ExploderPhase::Fuse { timer } if timer.is_done(frame) => {
+                self.phase = ExploderPhase::Detonated;
+                FuseStep::Detonate
+            }
+            _ => FuseStep::Wait,
+        }
+    }
+
+    // Dead with its fuse lit or not it explode, unless the fuse already blew it
+    pub fn explode_on_death(&self) -> bool {
+        self.phase != ExploderPhase::Detonated
+    }
+}
+
+// Sent when a fuse is lit, for the sound
+#[derive(Clone, Debug)]
+pub struct FuseLitEvent {
+    pub entity: Entity,
+}
+
+
+pub fn rollback_exploder_fuse(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    mut fuse_events: ResMut<RollbackEvents<FuseLitEvent>>,
+    mut enemy_query: Query<(Entity, &Transform, &mut ExploderState, &CharacterConfigHandles), (With<Enemy>, With<Rollback>)>,
+    player_query: Query<&Transform, (With<Player>, Without<Enemy>, Without<Respawning>, With<Rollback>)>,
+) {
+    let players: Vec<Vec2> = player_query.iter().map(|transform| transform.translation.truncate()).collect();
+
+    let mut enemies: Vec<_> = enemy_query.iter_mut().collect();
+    enemies.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, mut state, config_handles) in enemies {
+        let Some(exploder) = character_configs.get(&config_handles.config).and_then(|config| config.exploder) else {
+            continue;
+        };
+        let position = transform.translation.truncate();
+
+        let in_range = players.iter().any(|player| player.distance(position) <= exploder.trigger_range);
+        match state.advance(in_range, simulation.frames(exploder.fuse_frames), frame.frame) {
+            FuseStep::Lit => fuse_events.send(frame.frame, FuseLitEvent { entity }),
+            FuseStep::Detonate => {
+                spawn_explosion(&mut commands, position, exploder.radius, exploder.damage, Some(HitBy::Entity(entity)), frame.frame);
+                commands.entity(entity).insert(Death { last_hit_by: Some(HitBy::Entity(entity)), last_hit_zone: None, weapon: None });
+            }
+            FuseStep::Wait => {}
+        }
+    }
+}
+
+// Killed before the end of its fuse, the explosion is credited to whoever killed it
+pub fn rollback_explode_dead_exploders(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    enemy_query: Query<(Entity, &Transform, &ExploderState, &CharacterConfigHandles, &Death), With<Rollback>>,
+) {
+    let mut enemies: Vec<_> = enemy_query.iter().collect();
+    enemies.sort_by_key(|(entity, ..)| entity.index());
+
+    for (_, transform, state, config_handles, death) in enemies {
+        if !state.explode_on_death() {
+            continue;
+        }
+        let Some(exploder) = character_configs.get(&config_handles.config).and_then(|config| config.exploder) else {
+            continue;
+        };
+        spawn_explosion(&mut commands, transform.translation.truncate(), exploder.radius, exploder.damage, death.last_hit_by.clone(), frame.frame);
+    }
+}
+
+
+// PRESENTATION
+
+#[derive(Resource, Clone, Debug)]
+pub struct ExploderCueSettings {
+    pub fuse_sound: String,
+    pub volume: f32,
+    pub color: Color,
+    // Blinks per second at the start and at the end of the fuse
+    pub blink_rate: (f32, f32),
+}
+
+impl Default for ExploderCueSettings {
+    fn default() -> Self {
+        Self {
+            fuse_sound: "sounds/enemy/fuse.wav".to_string(),
+            volume: 0.9,
+            color: Color::srgb(1.0, 0.25, 0.1),
+            blink_rate: (2.0, 10.0),
+        }
+    }
+}
+
+// Area of the blast shown under a lit exploder
+#[derive(Component)]
+struct FuseTelegraph {
+    anchor: Entity,
+}
+
+#[derive(Resource, Default)]
+struct FuseSoundCursor {
+    last_read_frame: Option<u32>,
+}
+
+fn play_fuse_sounds(
+    frame: Res<FrameCount>,
+    settings: Res<ExploderCueSettings>,
+    fuse_events: Res<RollbackEvents<FuseLitEvent>>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut cursor: ResMut<FuseSoundCursor>,
+    mut emitter_query: Query<&mut SpatialAudioEmitter>,
+) {
+    for (_, event) in fuse_events.read_after(cursor.last_read_frame) {
+        let Ok(mut emitter) = emitter_query.get_mut(event.entity) else {
+            continue;
+        };
+        let instance = audio.play(asset_server.load(settings.fuse_sound.as_str())).with_volume(settings.volume as f64).handle();
+        emitter.instances.push(instance);
+    }
+    cursor.last_read_frame = Some(frame.frame);
+}
+
+// Follow what the simulation show, a rollback that put out a fuse remove its telegraph.
+// The blast is round, the telegraph is a circle of its radius.
+fn update_fuse_telegraphs(
+    mut commands: Commands,
+    time: Res<Time>,
+    frame: Res<FrameCount>,
+    settings: Res<ExploderCueSettings>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    enemy_query: Query<(Entity, &Transform, &ExploderState, &CharacterConfigHandles)>,
+    mut telegraph_query: Query<(Entity, &FuseTelegraph, &mut Transform, &MeshMaterial2d<ColorMaterial>), Without<ExploderState>>,
+) {
+    let mut shown = vec![];
+    for (entity, telegraph, mut transform, material) in telegraph_query.iter_mut() {
+        let Ok((_, enemy_transform, state, _)) = enemy_query.get(telegraph.anchor) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let ExploderPhase::Fuse { timer } = state.phase else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        shown.push(telegraph.anchor);
+
+        transform.translation.x = enemy_transform.translation.x;
+        transform.translation.y = enemy_transform.translation.y;
+        // Faster near the end of the fuse
+        let (start_rate, end_rate) = settings.blink_rate;
+        let rate = start_rate + (end_rate - start_rate) * timer.fraction(frame.frame);
+        let blink = 0.5 + 0.5 * (time.elapsed_secs() * rate * std::f32::consts::TAU).sin();
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color = settings.color.with_alpha(0.15 + 0.3 * blink);
+        }
+    }
+
+    for (entity, transform, state, config_handles) in enemy_query.iter() {
+        if !state.is_fusing() || shown.contains(&entity) {
+            continue;
+        }
+        let Some(exploder) = character_configs.get(&config_handles.config).and_then(|config| config.exploder) else {
+            continue;
+        };
+        commands.spawn((
+            Mesh2d(meshes.add(Circle::new(exploder.radius))),
+            MeshMaterial2d(materials.add(settings.color.with_alpha(0.15))),
+            Transform::from_translation(transform.translation.truncate().extend(4.0)),
+            FuseTelegraph { anchor: entity },
+        ));
+    }
+}
+
+fn clear_fuse_cues(
+    mut commands: Commands,
+    mut cursor: ResMut<FuseSoundCursor>,
+    telegraph_query: Query<Entity, With<FuseTelegraph>>,
+) {
+    cursor.last_read_frame = None;
+    for entity in telegraph_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+
+#[derive(Default)]
+pub struct ExploderCuePlugin;
+
+impl Plugin for ExploderCuePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExploderCueSettings>();
+        app.init_resource::<FuseSoundCursor>();
+        app.add_systems(Update, (play_fuse_sounds, update_fuse_telegraphs).run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), clear_fuse_cues);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuse_lit_then_detonate() {
+        let mut state = ExploderState::default();
+        assert_eq!(state.advance(false, 30, 10), FuseStep::Wait);
+        assert_eq!(state.phase, ExploderPhase::Idle);
+
+        assert_eq!(state.advance(true, 30, 11), FuseStep::Lit);
+        assert!(state.is_fusing());
+        // The fuse burn even if the player leave the range
+        assert_eq!(state.advance(false, 30, 40), FuseStep::Wait);
+        assert_eq!(state.advance(false, 30, 41), FuseStep::Detonate);
+        assert_eq!(state.phase, ExploderPhase::Detonated);
+        assert_eq!(state.advance(true, 30, 42), FuseStep::Wait);
+    }
+
+    #[test]
+    fn test_death_during_the_fuse_explode_once() {
+        let mut state = ExploderState::default();
+        assert!(state.explode_on_death());
+        state.advance(true, 30, 0);
+        // Killed with the fuse lit, the death make the blast
+        assert!(state.explode_on_death());
+        // Blown by the fuse, the death that follow doesn't explode again
+        state.advance(false, 30, 30);
+        assert!(!state.explode_on_death());
+    }
+
+    #[test]
+    fn test_fuse_sound_exists() {
+        let path = format!("{}/../../assets/{}", env!("CARGO_MANIFEST_DIR"), ExploderCueSettings::default().fuse_sound);
+        assert!(std::path::Path::new(&path).exists(), "{}", path);
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use utils::{frame::{FrameTimer, SimulationConfig}, math::round};
+
+// Reaction of the enemies to the damage. The damage taken inside a short window is
+// summed and past the threshold of the archetype the enemy is staggered, it stop moving
+// and lose the attack in progress. After a stagger it can't flinch again for a while so
+// a fast weapon can't keep a big enemy locked.
+
+pub const HIT_ANIMATION: &str = "Hit";
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct FlinchConfig {
+    pub threshold: f32,
+    pub window_frames: u32,
+    pub stagger_frames: u32,
+    // Counted from the end of the stagger
+    pub immunity_frames: u32,
+}
+
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct FlinchState {
+    pub window: FrameTimer,
+    // Damage taken since the start of the window
+    pub damage: f32,
+    pub stagger: FrameTimer,
+    // Cover the stagger and the immunity after it
+    pub immune: FrameTimer,
+}
+
+impl FlinchState {
+    pub fn is_staggered(&self, frame: u32) -> bool {
+        self.stagger.is_active(frame)
+    }
+
+    // Damage applied on the frame, the enemy flinch once the threshold is reached
+    pub fn take_damage(&mut self, damage: f32, config: &FlinchConfig, frame: u32, simulation: &SimulationConfig) {
+        if self.immune.is_active(frame) {
+            return;
+        }
+        if self.window.is_done(frame) {
+            self.window = FrameTimer::new(frame, simulation.frames(config.window_frames));
+            self.damage = 0.0;
+        }
+        self.damage = round(self.damage + damage);
+        if self.damage < config.threshold {
+            return;
+        }
+
+        let stagger_frames = simulation.frames(config.stagger_frames);
+        self.stagger = FrameTimer::new(frame, stagger_frames);
+        self.immune = FrameTimer::new(frame, stagger_frames + simulation.frames(config.immunity_frames));
+        self.window = FrameTimer::default();
+        self.damage = 0.0;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use animation::AnimationMapConfig;
+
+    const CONFIG: FlinchConfig = FlinchConfig { threshold: 50.0, window_frames: 30, stagger_frames: 20, immunity_frames: 40 };
+
+    #[test]
+    fn test_damage_summed_inside_the_window() {
+        let simulation = SimulationConfig::default();
+        let mut flinch = FlinchState::default();
+        flinch.take_damage(30.0, &CONFIG, 100, &simulation);
+        assert!(!flinch.is_staggered(100));
+        flinch.take_damage(20.0, &CONFIG, 120, &simulation);
+        assert!(flinch.is_staggered(120));
+        assert!(flinch.is_staggered(139));
+        assert!(!flinch.is_staggered(140));
+    }
+
+    #[test]
+    fn test_window_reset_the_damage() {
+        let simulation = SimulationConfig::default();
+        let mut flinch = FlinchState::default();
+        flinch.take_damage(30.0, &CONFIG, 100, &simulation);
+        // The window of 30 frames is over, the first hit is forgotten
+        flinch.take_damage(30.0, &CONFIG, 130, &simulation);
+        assert!(!flinch.is_staggered(130));
+        assert_eq!(flinch.damage, 30.0);
+    }
+
+    #[test]
+    fn test_immune_after_the_stagger() {
+        let simulation = SimulationConfig::default();
+        let mut flinch = FlinchState::default();
+        flinch.take_damage(60.0, &CONFIG, 100, &simulation);
+        // The immunity last the stagger and 40 frames after it
+        flinch.take_damage(60.0, &CONFIG, 150, &simulation);
+        assert!(!flinch.is_staggered(150));
+        assert_eq!(flinch.damage, 0.0);
+        flinch.take_damage(60.0, &CONFIG, 160, &simulation);
+        assert!(flinch.is_staggered(160));
+    }
+
+    #[test]
+    fn test_stagger_scaled_by_the_tick_rate() {
+        let simulation = SimulationConfig { tick_rate: 30, ..default() };
+        let mut flinch = FlinchState::default();
+        flinch.take_damage(60.0, &CONFIG, 100, &simulation);
+        assert!(flinch.is_staggered(109));
+        assert!(!flinch.is_staggered(110));
+    }
+
+    #[test]
+    fn test_zombie_animation_has_the_hit_state() {
+        let path = format!("{}/../../assets/ZombieShooter/Sprites/Zombie/zombie_animation.ron", env!("CARGO_MANIFEST_DIR"));
+        let bytes = std::fs::read(&path).unwrap();
+        let config: AnimationMapConfig = ron::de::from_bytes(&bytes).unwrap();
+        let indices = config.animations.get(HIT_ANIMATION).unwrap();
+        // Inside the 10 columns of the sheet
+        assert!(indices.start <= indices.end && indices.end < 10);
+    }
+}
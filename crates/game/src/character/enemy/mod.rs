@@ -1,12 +1,17 @@
 pub mod create;
 pub mod spawning;
 pub mod ai;
+pub mod attack;
+pub mod climb;
+pub mod exploder;
+pub mod flinch;
+pub mod shield;
 
 
 use bevy::prelude::*;
 
 
-#[derive(Component, Reflect, Default, Debug)]
+#[derive(Component, Reflect, Default, Debug, Clone)]
 #[reflect(Component)]
 pub struct Enemy {
     
@@ -0,0 +1,304 @@
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use bevy_kira_audio::prelude::*;
+use serde::Deserialize;
+use utils::{events::RollbackEvents, math::{round, round_vec2}};
+
+use crate::{character::{config::{CharacterConfig, CharacterConfigHandles}, health::DamageAccumulator, movement::Velocity}, frame::FrameCount, plugins::AppState};
+
+use super::Enemy;
+
+// Enemies carrying a shield, the archetype has a shield config. The shield face where the
+// enemy walk and the hits coming from the front lose most of their damage, the players
+// must flank it. Only the hits with a direction, the bullets and the melee, can be
+// blocked, the explosions go around. Once it blocked enough the shield break and the
+// enemy take the full damage, its killer get a bonus.
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct ShieldConfig {
+    // Multiplier on the damage of a frontal hit
+    pub frontal_multiplier: f32,
+    // Half angle of the front, in degrees
+    pub half_arc_degrees: f32,
+    // Damage blocked before the shield break
+    pub break_threshold: f32,
+}
+
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ShieldState {
+    // Unit vector, the way the shield face
+    pub facing: Vec2,
+    pub blocked: f32,
+    pub broken: bool,
+}
+
+impl Default for ShieldState {
+    fn default() -> Self {
+        Self { facing: Vec2::X, blocked: 0.0, broken: false }
+    }
+}
+
+impl ShieldState {
+    // A hit travelling along `direction` come from the front when it go against the facing
+    pub fn is_frontal(&self, direction: Vec2, config: &ShieldConfig) -> bool {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return false;
+        }
+        -direction.dot(self.facing) >= config.half_arc_degrees.to_radians().cos()
+    }
+
+    // Damage of the frame left once the frontal hits are blocked, true when the shield just broke
+    pub fn block(&mut self, accumulator: &DamageAccumulator, config: &ShieldConfig) -> (f32, bool) {
+        if self.broken {
+            return (accumulator.total_damage, false);
+        }
+        let mut damage = accumulator.total_damage;
+        for &(direction, hit_damage) in accumulator.directional.iter() {
+            if !self.is_frontal(direction, config) {
+                continue;
+            }
+            let blocked = round(hit_damage * (1.0 - config.frontal_multiplier.clamp(0.0, 1.0)));
+            damage -= blocked;
+            self.blocked = round(self.blocked + blocked);
+        }
+        // The damage over the threshold still hit the shield, the next hits go through
+        self.broken = self.blocked >= config.break_threshold;
+        (round(damage.max(0.0)), self.broken)
+    }
+}
+
+// Sent when a shield break, for the feedback
+#[derive(Clone, Debug)]
+pub struct ShieldBrokenEvent {
+    pub entity: Entity,
+    pub position: Vec2,
+}
+
+
+// The shield follow the way the enemy walk, it keep its facing when the enemy stop
+pub fn rollback_turn_shields(
+    mut shield_query: Query<(&Velocity, &mut ShieldState), (With<Enemy>, With<Rollback>)>,
+) {
+    for (velocity, mut shield) in shield_query.iter_mut() {
+        if shield.broken || velocity.0 == Vec2::ZERO {
+            continue;
+        }
+        shield.facing = round_vec2(velocity.0.normalize());
+    }
+}
+
+
+// PRESENTATION
+
+#[derive(Resource, Clone, Debug)]
+pub struct ShieldCueSettings {
+    pub break_sound: String,
+    pub volume: f32,
+    pub color: Color,
+    // Size of the shield and its distance to the center of the enemy, in world units
+    pub size: Vec2,
+    pub distance: f32,
+    // Seconds the pieces of a broken shield stay on screen
+    pub break_seconds: f32,
+}
+
+impl Default for ShieldCueSettings {
+    fn default() -> Self {
+        Self {
+            break_sound: "sounds/enemy/shield-break.wav".to_string(),
+            volume: 0.9,
+            color: Color::srgb(0.55, 0.6, 0.7),
+            size: Vec2::new(14.0, 90.0),
+            distance: 40.0,
+            break_seconds: 0.4,
+        }
+    }
+}
+
+#[derive(Component)]
+struct ShieldSprite {
+    anchor: Entity,
+}
+
+// Flash of a broken shield, fading out
+#[derive(Component)]
+struct ShieldShards(Timer);
+
+#[derive(Resource, Default)]
+struct ShieldCueCursor {
+    last_read_frame: Option<u32>,
+}
+
+// Follow what the simulation show, a rollback that restore a shield show it again
+fn update_shield_sprites(
+    mut commands: Commands,
+    settings: Res<ShieldCueSettings>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    enemy_query: Query<(Entity, &Transform, &ShieldState, &CharacterConfigHandles)>,
+    mut sprite_query: Query<(Entity, &ShieldSprite, &mut Transform), Without<ShieldState>>,
+) {
+    let mut shown = vec![];
+    for (entity, sprite, mut transform) in sprite_query.iter_mut() {
+        let Some((_, enemy_transform, shield, _)) = enemy_query.get(sprite.anchor).ok().filter(|(_, _, shield, _)| !shield.broken) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        shown.push(sprite.anchor);
+
+        let position = enemy_transform.translation.truncate() + shield.facing * settings.distance;
+        transform.translation = position.extend(enemy_transform.translation.z + 0.5);
+        transform.rotation = Quat::from_rotation_z(shield.facing.to_angle());
+    }
+
+    for (entity, transform, shield, config_handles) in enemy_query.iter() {
+        if shield.broken || shown.contains(&entity) {
+            continue;
+        }
+        if character_configs.get(&config_handles.config).map_or(true, |config| config.shield.is_none()) {
+            continue;
+        }
+        commands.spawn((
+            Sprite::from_color(settings.color, settings.size),
+            Transform::from_translation(transform.translation),
+            ShieldSprite { anchor: entity },
+        ));
+    }
+}
+
+fn shield_break_feedback(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    settings: Res<ShieldCueSettings>,
+    broken_events: Res<RollbackEvents<ShieldBrokenEvent>>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut cursor: ResMut<ShieldCueCursor>,
+    mut emitter_query: Query<&mut SpatialAudioEmitter>,
+) {
+    for (_, event) in broken_events.read_after(cursor.last_read_frame) {
+        commands.spawn((
+            Sprite::from_color(Color::WHITE.with_alpha(0.8), settings.size * 1.5),
+            Transform::from_translation(event.position.extend(6.0)),
+            ShieldShards(Timer::from_seconds(settings.break_seconds, TimerMode::Once)),
+        ));
+        if let Ok(mut emitter) = emitter_query.get_mut(event.entity) {
+            let instance = audio.play(asset_server.load(settings.break_sound.as_str())).with_volume(settings.volume as f64).handle();
+            emitter.instances.push(instance);
+        }
+    }
+    cursor.last_read_frame = Some(frame.frame);
+}
+
+fn fade_shield_shards(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut shards_query: Query<(Entity, &mut ShieldShards, &mut Sprite, &mut Transform)>,
+) {
+    for (entity, mut shards, mut sprite, mut transform) in shards_query.iter_mut() {
+        if shards.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let left = 1.0 - shards.0.fraction();
+        sprite.color.set_alpha(0.8 * left);
+        transform.scale = Vec3::splat(1.0 + (1.0 - left));
+    }
+}
+
+fn clear_shield_cues(
+    mut commands: Commands,
+    mut cursor: ResMut<ShieldCueCursor>,
+    cue_query: Query<Entity, Or<(With<ShieldSprite>, With<ShieldShards>)>>,
+) {
+    cursor.last_read_frame = None;
+    for entity in cue_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+
+#[derive(Default)]
+pub struct ShieldCuePlugin;
+
+impl Plugin for ShieldCuePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShieldCueSettings>();
+        app.init_resource::<ShieldCueCursor>();
+        app.add_systems(Update, (update_shield_sprites, shield_break_feedback, fade_shield_shards).run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), clear_shield_cues);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: ShieldConfig = ShieldConfig { frontal_multiplier: 0.2, half_arc_degrees: 60.0, break_threshold: 100.0 };
+
+    // Direction of a hit coming at `degrees` from the front of a shield facing +x
+    fn from_angle(degrees: f32) -> Vec2 {
+        -Vec2::from_angle(degrees.to_radians())
+    }
+
+    fn hits(hits: &[(Vec2, f32)]) -> DamageAccumulator {
+        DamageAccumulator {
+            total_damage: hits.iter().map(|(_, damage)| damage).sum(),
+            hit_count: hits.len() as u32,
+            directional: hits.to_vec(),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn test_frontal_on_the_edge_of_the_arc() {
+        let shield = ShieldState::default();
+        assert!(shield.is_frontal(from_angle(0.0), &CONFIG));
+        assert!(shield.is_frontal(from_angle(59.0), &CONFIG));
+        assert!(shield.is_frontal(from_angle(-59.0), &CONFIG));
+        assert!(!shield.is_frontal(from_angle(61.0), &CONFIG));
+        assert!(!shield.is_frontal(from_angle(180.0), &CONFIG));
+        // Without a direction it can't be blocked
+        assert!(!shield.is_frontal(Vec2::ZERO, &CONFIG));
+    }
+
+    #[test]
+    fn test_block_only_the_frontal_hits() {
+        let mut shield = ShieldState::default();
+        let (damage, broke) = shield.block(&hits(&[(from_angle(0.0), 50.0), (from_angle(180.0), 10.0)]), &CONFIG);
+        // 40 of the frontal hit blocked
+        assert_eq!(damage, 20.0);
+        assert!(!broke);
+        assert_eq!(shield.blocked, 40.0);
+    }
+
+    #[test]
+    fn test_break_when_crossing_the_threshold() {
+        let mut shield = ShieldState::default();
+        let (_, broke) = shield.block(&hits(&[(from_angle(0.0), 100.0)]), &CONFIG);
+        assert!(!broke);
+        assert_eq!(shield.blocked, 80.0);
+        // The hit crossing the threshold is still blocked, the shield break with it
+        let (damage, broke) = shield.block(&hits(&[(from_angle(0.0), 50.0)]), &CONFIG);
+        assert_eq!(damage, 10.0);
+        assert!(broke && shield.broken);
+    }
+
+    #[test]
+    fn test_full_damage_after_the_break() {
+        let mut shield = ShieldState { broken: true, blocked: 120.0, ..default() };
+        let (damage, broke) = shield.block(&hits(&[(from_angle(0.0), 50.0)]), &CONFIG);
+        assert_eq!(damage, 50.0);
+        // It only break once
+        assert!(!broke);
+        assert_eq!(shield.blocked, 120.0);
+    }
+
+    #[test]
+    fn test_break_sound_exists() {
+        let path = format!("{}/../../assets/{}", env!("CARGO_MANIFEST_DIR"), ShieldCueSettings::default().break_sound);
+        assert!(std::path::Path::new(&path).exists(), "{}", path);
+    }
+}
@@ -1,18 +1,41 @@
 use animation::SpriteSheetConfig;
 use bevy::{prelude::*};
 use map::game::entity::map::enemy_spawn::EnemySpawnerComponent;
-use utils::rng::RollbackRng;
+use utils::frame::SimulationConfig;
 
-use crate::{character::{config::CharacterConfig, player::Player}, collider::{Collider, CollisionSettings, Wall}, frame::FrameCount, global_asset::GlobalAsset, weapons::WeaponsConfig};
+use crate::{budget::{BudgetStats, SimulationBudget}, character::{config::CharacterConfig, health::Death, player::Player}, collider::{sweep_collision_hit, Collider, ColliderShape, CollisionSettings, Wall}, frame::FrameCount, global_asset::GlobalAsset, rng::SpawningRng, rules::{GameRules, MatchState, WaveKind, WaveState}, weapons::WeaponsConfig};
 
 use super::{create::spawn_enemy, Enemy};
 
+// Spawning fairness. Every kill close to a spawner heat it up and the heat cool down
+// over time, the ready spawner that spawn is picked at random with a weight lowered by
+// its heat and by the players seeing it. A doorway farmed for a while end up starving
+// and the zombies come from somewhere else, out of sight when possible. The sight of a
+// spawner is swept against the walls every few frames only and kept in its state.
+
 #[derive(Component, Debug, Reflect, Clone)]
 #[reflect]
 pub struct EnemySpawnerState {
     pub cooldown_remaining: u32,
     pub last_spawn_frame: u32,
     pub active: bool,
+    // Recent kills around the spawner, integer so the weights are the same on every peer
+    pub heat: u32,
+    // A player could see it the last time it was looked at, on that frame
+    pub visible: bool,
+    pub visibility_frame: Option<u32>,
+}
+
+impl EnemySpawnerState {
+    // Sight of the players, swept again once the last one is `interval` frames old
+    pub fn is_visible(&mut self, frame: u32, interval: u32, sweep: impl FnOnce() -> bool) -> bool {
+        let stale = self.visibility_frame.map_or(true, |checked| frame.saturating_sub(checked) >= interval);
+        if stale {
+            self.visible = sweep();
+            self.visibility_frame = Some(frame);
+        }
+        self.visible
+    }
 }
 
 
@@ -22,17 +45,97 @@ impl Default for EnemySpawnerState {
             cooldown_remaining: 0,
             last_spawn_frame: 0,
             active: true,
+            heat: 0,
+            visible: false,
+            visibility_frame: None,
         }
     } 
 }
 
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct SpawnFairnessConfig {
+    // Kills closer than this from a spawner heat it
+    pub kill_radius: f32,
+    pub heat_per_kill: u32,
+    // Every interval the heat keep this percent of itself
+    pub heat_decay_interval_frames: u32,
+    pub heat_decay_percent: u32,
+    // Heat where the weight of a spawner is halved
+    pub heat_half_weight: u32,
+    // Percent of the weight kept by a spawner a player can see
+    pub visible_weight_percent: u32,
+    // Frames the sight of a spawner is kept before being swept again
+    pub visibility_interval_frames: u32,
+}
+
+impl Default for SpawnFairnessConfig {
+    fn default() -> Self {
+        Self {
+            kill_radius: 500.0,
+            heat_per_kill: 100,
+            heat_decay_interval_frames: 60,
+            heat_decay_percent: 90,
+            heat_half_weight: 300,
+            visible_weight_percent: 25,
+            visibility_interval_frames: 15,
+        }
+    }
+}
+
+const BASE_SPAWN_WEIGHT: u32 = 1000;
+
+impl SpawnFairnessConfig {
+    pub fn spawn_weight(&self, heat: u32, visible: bool) -> u32 {
+        let half = self.heat_half_weight.max(1) as u64;
+        let mut weight = BASE_SPAWN_WEIGHT as u64 * half / (half + heat as u64);
+        if visible {
+            weight = weight * self.visible_weight_percent as u64 / 100;
+        }
+        // Never fully out of the rotation, it may be the only spawner left
+        (weight as u32).max(1)
+    }
+}
+
+// A wall between the player and the spawner, tested in fixed point like the bullets
+fn in_line_of_sight(from: Vec2, to: Vec2, walls: &[(&Transform, &Collider)]) -> bool {
+    let ray = Collider { shape: ColliderShape::Circle { radius: 0.0 }, offset: Vec2::ZERO };
+    !walls.iter().any(|(transform, collider)| sweep_collision_hit(from, to, &ray, transform, collider).is_some())
+}
+
+// Kills of the frame heat the spawners around them, before the dead are despawned
+pub fn rollback_track_spawner_heat(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    config: Res<SpawnFairnessConfig>,
+    dead_query: Query<&Transform, (With<Enemy>, With<Death>)>,
+    mut spawner_query: Query<(&Transform, &mut EnemySpawnerState)>,
+) {
+    let decay_interval = simulation.frames(config.heat_decay_interval_frames);
+    let decay = decay_interval > 0 && frame.frame % decay_interval == 0;
+    let deaths: Vec<Vec2> = dead_query.iter().map(|transform| transform.translation.truncate()).collect();
+
+    for (transform, mut state) in spawner_query.iter_mut() {
+        if decay {
+            state.heat = (state.heat as u64 * config.heat_decay_percent.min(100) as u64 / 100) as u32;
+        }
+
+        let position = transform.translation.truncate();
+        let kills = deaths.iter().filter(|death| death.distance(position) <= config.kill_radius).count() as u32;
+        state.heat = state.heat.saturating_add(kills * config.heat_per_kill);
+    }
+}
+
 pub fn enemy_spawn_from_spawners_system(
     mut commands: Commands,
     frame: Res<FrameCount>,
-    mut rng: ResMut<RollbackRng>,
+    simulation: Res<SimulationConfig>,
+    mut rng: ResMut<SpawningRng>,
     mut spawner_query: Query<(Entity, &EnemySpawnerComponent, &mut EnemySpawnerState, &Transform)>,
     enemy_query: Query<&Transform, With<Enemy>>,
     player_query: Query<&Transform, With<Player>>,
+    wall_query: Query<(&Transform, &Collider), With<Wall>>,
+    fairness: Res<SpawnFairnessConfig>,
 
     global_assets: Res<GlobalAsset>,
     collision_settings: Res<CollisionSettings>,
@@ -41,7 +144,21 @@ pub fn enemy_spawn_from_spawners_system(
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     sprint_sheet_assets: Res<Assets<SpriteSheetConfig>>,
+    match_state: Res<MatchState>,
+    rules: Res<GameRules>,
+    wave_state: Res<WaveState>,
+    budget: Res<SimulationBudget>,
+    mut budget_stats: ResMut<BudgetStats>,
 ) {
+    // No more zombies once the match is over, and none at all in the versus mode
+    if match_state.is_over() || !rules.enemies_enabled() {
+        return;
+    }
+    // The players joining get in first
+    if wave_state.join_window.is_active(frame.frame) {
+        return;
+    }
+
     // Get player positions for checking distance
     let player_positions: Vec<Vec2> = player_query
         .iter()
@@ -53,17 +170,25 @@ pub fn enemy_spawn_from_spawners_system(
         return; // No players, don't spawn
     }
     
-    // Count current enemies (global count)
+    // At the cap the spawn wait for a free slot, the ready spawners keep their turn
     let current_enemies = enemy_query.iter().count();
-    let global_max_enemies = 20; // This could be a global config
-    
-    if current_enemies >= global_max_enemies {
-        println!("Frame {}: Global max enemies reached", frame.frame);
-        return; // Already at global max enemies
+    if !budget.has_room_for_enemy(current_enemies) {
+        if spawner_query.iter().any(|(_, _, state, _)| state.active && state.cooldown_remaining == 0) {
+            budget_stats.deferred_spawns += 1;
+        }
+        return;
     }
     
-    // Process each spawner
-    for (_, config, mut state, transform) in spawner_query.iter_mut() {
+    let mut spawners: Vec<_> = spawner_query.iter_mut().collect();
+    spawners.sort_by_key(|(entity, ..)| entity.index());
+
+    // Only tested with any, the order doesn't matter
+    let walls: Vec<_> = wall_query.iter().collect();
+    let visibility_interval = simulation.frames(fairness.visibility_interval_frames);
+
+    // Ready spawners far enough from the players, with their weight
+    let mut candidates = vec![];
+    for (index, (_, config, state, transform)) in spawners.iter_mut().enumerate() {
         // Skip inactive spawners or those on cooldown
         if !state.active || state.cooldown_remaining > 0 {
             // Decrease cooldown
@@ -85,48 +210,112 @@ pub fn enemy_spawn_from_spawners_system(
         if min_distance_to_player < config.min_spawn_distance {
             continue;
         }
+
+        let visible = state.is_visible(frame.frame, visibility_interval, || player_positions.iter().any(|player| in_line_of_sight(*player, spawner_pos, &walls)));
+        candidates.push((index, fairness.spawn_weight(state.heat, visible)));
+    }
+
+    // One enemy per frame, from a spawner picked with the weights
+    let total: u32 = candidates.iter().map(|(_, weight)| weight).sum();
+    if total == 0 {
+        return;
+    }
+    let mut roll = rng.next_u32() % total;
+    let picked = candidates.iter()
+        .find(|(_, weight)| {
+            if roll < *weight {
+                return true;
+            }
+            roll -= *weight;
+            false
+        })
+        .map_or(candidates[0].0, |(index, _)| *index);
+
+    let (_, config, state, transform) = &mut spawners[picked];
+    let spawner_pos = transform.translation.truncate();
+
+    // Calculate final spawn position (with optional small random offset)
+    let spawn_pos = if config.spawn_radius > 0.0 {
+        // Create deterministic offset using RNG
+        let angle = rng.next_f32() * std::f32::consts::TAU;
+        let distance = rng.next_f32() * config.spawn_radius;
+        let offset = Vec2::new(angle.cos(), angle.sin()) * distance;
         
-        // Calculate final spawn position (with optional small random offset)
-        let spawn_pos = if config.spawn_radius > 0.0 {
-            // Create deterministic offset using RNG
-            let angle = rng.next_f32() * std::f32::consts::TAU;
-            let distance = rng.next_f32() * config.spawn_radius;
-            let offset = Vec2::new(angle.cos(), angle.sin()) * distance;
-            
-            // Apply the offset
-            Vec3::new(
-                spawner_pos.x + offset.x,
-                spawner_pos.y + offset.y,
-                0.0
-            )
-        } else {
-            // Use exact spawner position
-            transform.translation
-        };
-        
-        // Select enemy type deterministically
-        let type_index = (rng.next_u32() as usize) % config.enemy_types.len();
-        let enemy_type_name = config.enemy_types[type_index].clone();
-        
-        // Spawn the enemy
-        spawn_enemy(
-            enemy_type_name,
-            spawn_pos,
-            &mut commands,
-            &weapons_asset,
-            &characters_asset,
-            &asset_server,
-            &mut texture_atlas_layouts,
-            &sprint_sheet_assets,
-            &global_assets,
-            &collision_settings,
-        );
-        
-        // Update state
-        state.cooldown_remaining = config.max_cooldown;
-        state.last_spawn_frame = frame.frame;
-        
-        // We only spawn one enemy per system call
-        break;
+        // Apply the offset
+        Vec3::new(
+            spawner_pos.x + offset.x,
+            spawner_pos.y + offset.y,
+            0.0
+        )
+    } else {
+        // Use exact spawner position
+        transform.translation
+    };
+    
+    // Select enemy type deterministically
+    let type_index = (rng.next_u32() as usize) % config.enemy_types.len();
+    let enemy_type_name = config.enemy_types[type_index].clone();
+    
+    // Spawn the enemy
+    spawn_enemy(
+        enemy_type_name,
+        spawn_pos,
+        &mut commands,
+        &weapons_asset,
+        &characters_asset,
+        &asset_server,
+        &mut texture_atlas_layouts,
+        &sprint_sheet_assets,
+        &global_assets,
+        &collision_settings,
+    );
+    
+    // Update state
+    state.cooldown_remaining = simulation.frames(config.max_cooldown);
+    if wave_state.kind == WaveKind::Horde {
+        state.cooldown_remaining /= rules.horde_spawn_rate.max(1);
+    }
+    state.last_spawn_frame = frame.frame;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall(position: Vec2) -> (Transform, Collider) {
+        (
+            Transform::from_translation(position.extend(0.0)),
+            Collider { shape: ColliderShape::Rectangle { width: 20.0, height: 100.0 }, offset: Vec2::ZERO },
+        )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_line_of_sight_blocked_by_a_wall() {
+        let (transform, collider) = wall(Vec2::new(100.0, 0.0));
+        let walls = [(&transform, &collider)];
+        assert!(!in_line_of_sight(Vec2::ZERO, Vec2::new(200.0, 0.0), &walls));
+        assert!(in_line_of_sight(Vec2::ZERO, Vec2::new(0.0, 200.0), &walls));
+        assert!(in_line_of_sight(Vec2::ZERO, Vec2::new(200.0, 0.0), &[]));
+    }
+
+    #[test]
+    fn test_visibility_swept_once_per_interval() {
+        let mut state = EnemySpawnerState::default();
+        assert!(state.is_visible(10, 15, || true));
+        // Kept until the interval is over, even when the players moved
+        assert!(state.is_visible(24, 15, || false));
+        assert!(!state.is_visible(25, 15, || false));
+        assert_eq!(state.visibility_frame, Some(25));
+    }
+
+    #[test]
+    fn test_spawn_weight_lowered_by_heat_and_sight() {
+        let config = SpawnFairnessConfig::default();
+        assert_eq!(config.spawn_weight(0, false), BASE_SPAWN_WEIGHT);
+        assert_eq!(config.spawn_weight(config.heat_half_weight, false), BASE_SPAWN_WEIGHT / 2);
+        assert_eq!(config.spawn_weight(0, true), BASE_SPAWN_WEIGHT * config.visible_weight_percent / 100);
+        // Never out of the rotation
+        assert_eq!(config.spawn_weight(u32::MAX, true), 1);
+    }
+}
@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+use map::game::entity::map::surface::{surface_at, SurfaceKind, SurfaceZoneComponent, SurfaceZoneConfig};
+
+use crate::{character::player::Player, hud::graphics::GraphicsPreferences, plugins::AppState};
+
+// Footprints of the players, presentation only. Walking in a sticky surface of the map
+// put it on the feet, the next steps leave prints lighter and lighter that fade after a
+// few seconds. Read from the positions shown, a rollback moving a player far is not a step.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct FootprintSettings {
+    pub enabled: bool,
+    // Distance walked between two prints
+    pub stride: f32,
+    // Prints left after walking out of the surface
+    pub carry_steps: u32,
+    pub fade_seconds: f32,
+    pub max_prints: usize,
+    pub print_size: Vec2,
+    // Distance of each foot from the middle of the player
+    pub foot_offset: f32,
+    // Over the ground, under the bullet marks
+    pub z: f32,
+}
+
+impl Default for FootprintSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stride: 22.0,
+            carry_steps: 10,
+            fade_seconds: 5.0,
+            max_prints: 200,
+            print_size: Vec2::new(7.0, 4.0),
+            foot_offset: 4.0,
+            z: -0.95,
+        }
+    }
+}
+
+// Longer than a step, a respawn or a rollback
+const MAX_STEP_DISTANCE: f32 = 60.0;
+
+// Color of the prints of a surface, none for the surfaces that don't stick
+pub fn footprint_color(surface: SurfaceKind) -> Option<Color> {
+    match surface {
+        SurfaceKind::Blood => Some(Color::srgba(0.45, 0.02, 0.02, 0.8)),
+        SurfaceKind::Mud => Some(Color::srgba(0.28, 0.19, 0.1, 0.8)),
+        _ => None,
+    }
+}
+
+fn surface_color(surface: SurfaceKind) -> Color {
+    match surface {
+        SurfaceKind::Ground => Color::srgba(0.3, 0.3, 0.3, 0.3),
+        SurfaceKind::Blood => Color::srgba(0.5, 0.0, 0.0, 0.7),
+        SurfaceKind::Mud => Color::srgba(0.3, 0.2, 0.1, 0.7),
+        SurfaceKind::Water => Color::srgba(0.2, 0.4, 0.7, 0.5),
+        SurfaceKind::Metal => Color::srgba(0.5, 0.55, 0.6, 0.4),
+    }
+}
+
+// For the test map, the maps draw their surfaces in their tiles
+pub fn spawn_surface_zone(commands: &mut Commands, position: Vec2, config: SurfaceZoneConfig) -> Entity {
+    commands.spawn((
+        Sprite::from_color(surface_color(config.surface), config.size),
+        Transform::from_translation(position.extend(-1.8)),
+        SurfaceZoneComponent { config },
+    )).id()
+}
+
+// On every player, what stick to its feet
+#[derive(Component, Default)]
+struct FootprintState {
+    last: Option<Vec2>,
+    walked: f32,
+    surface: SurfaceKind,
+    steps_left: u32,
+    left_foot: bool,
+}
+
+#[derive(Component)]
+struct Footprint {
+    timer: Timer,
+    alpha: f32,
+}
+
+
+// SYSTEMS
+
+fn leave_footprints(
+    mut commands: Commands,
+    settings: Res<FootprintSettings>,
+    preferences: Option<Res<GraphicsPreferences>>,
+    zone_query: Query<(&GlobalTransform, &SurfaceZoneComponent)>,
+    mut player_query: Query<(Entity, &GlobalTransform, Option<&mut FootprintState>), With<Player>>,
+    print_query: Query<(), With<Footprint>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let mut prints = print_query.iter().count();
+    let max_prints = preferences.map_or(settings.max_prints, |preferences| settings.max_prints.min(preferences.max_decals));
+
+    for (entity, transform, opt_state) in player_query.iter_mut() {
+        let position = transform.translation().truncate();
+        let Some(mut state) = opt_state else {
+            commands.entity(entity).insert(FootprintState { last: Some(position), ..Default::default() });
+            continue;
+        };
+
+        let surface = surface_at(zone_query.iter().map(|(transform, zone)| (transform.translation().truncate(), &zone.config)), position);
+        if surface.is_sticky() {
+            state.surface = surface;
+            state.steps_left = settings.carry_steps;
+        }
+
+        let Some(last) = state.last.replace(position) else {
+            continue;
+        };
+        let offset = position - last;
+        if offset.length() > MAX_STEP_DISTANCE {
+            state.walked = 0.0;
+            continue;
+        }
+        state.walked += offset.length();
+        if state.walked < settings.stride {
+            continue;
+        }
+        state.walked = 0.0;
+
+        if state.steps_left == 0 {
+            continue;
+        }
+        let Some(color) = footprint_color(state.surface) else {
+            continue;
+        };
+        // Full while in it, then lighter at every step
+        let strength = state.steps_left as f32 / settings.carry_steps.max(1) as f32;
+        if !surface.is_sticky() {
+            state.steps_left -= 1;
+        }
+        state.left_foot = !state.left_foot;
+        if prints >= max_prints {
+            continue;
+        }
+        prints += 1;
+
+        let direction = offset.normalize_or_zero();
+        let side = if state.left_foot { 1.0 } else { -1.0 };
+        let foot = position + direction.perp() * settings.foot_offset * side;
+        let alpha = color.alpha() * strength;
+        commands.spawn((
+            Sprite::from_color(color.with_alpha(alpha), settings.print_size),
+            Transform::from_translation(foot.extend(settings.z)).with_rotation(Quat::from_rotation_z(direction.to_angle())),
+            Footprint {
+                timer: Timer::from_seconds(settings.fade_seconds, TimerMode::Once),
+                alpha,
+            },
+        ));
+    }
+}
+
+fn fade_footprints(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_print: Query<(Entity, &mut Footprint, &mut Sprite)>,
+) {
+    for (entity, mut print, mut sprite) in q_print.iter_mut() {
+        print.timer.tick(time.delta());
+        if print.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        sprite.color = sprite.color.with_alpha(print.alpha * print.timer.fraction_remaining());
+    }
+}
+
+fn clear_footprints(
+    mut commands: Commands,
+    q_print: Query<Entity, With<Footprint>>,
+) {
+    for entity in q_print.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+
+pub struct FootprintPlugin;
+
+impl Plugin for FootprintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FootprintSettings>()
+            .register_type::<FootprintSettings>()
+            .add_systems(Update, (leave_footprints, fade_footprints).run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), clear_footprints);
+    }
+}
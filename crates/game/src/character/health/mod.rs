@@ -6,6 +6,10 @@ use ggrs::PlayerHandle;
 use pathfinding::matrix::directions::N;
 use serde::{Deserialize, Serialize};
 
+use utils::{events::RollbackEvents, frame::{FrameTimer, SimulationConfig}, math::round};
+
+use crate::{character::{ability::AuraProtected, config::{CharacterConfig, CharacterConfigHandles}, enemy::{flinch::FlinchState, shield::{ShieldBrokenEvent, ShieldState}, Enemy}}, collider::HitZoneKind, equipment::Armor, weapons::KillWeapon, frame::FrameCount, powerup::ActivePowerUps};
+
 
 #[derive(Component, Reflect, Debug, Clone, Serialize, Deserialize)]
 pub enum HitBy {
@@ -24,13 +28,47 @@ pub struct HealthConfig {
 pub struct Health {
     pub current: f32,
     pub max: f32,
-    pub invulnerable_until_frame: Option<u32>,  // Optional invulnerability window
+    pub invulnerable: Option<FrameTimer>,  // Optional invulnerability window, spawn protection
 }
 
 
 #[derive(Component, Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Death {
     pub last_hit_by: Option<HitBy>,
+    // Zone of the killing hit, None when not killed by a bullet
+    pub last_hit_zone: Option<HitZoneKind>,
+    // Weapon of the killer when it was a player
+    pub weapon: Option<KillWeapon>,
+}
+
+impl Death {
+    pub fn is_headshot(&self) -> bool {
+        self.last_hit_zone == Some(HitZoneKind::Head)
+    }
+}
+
+// Notifications sent on the rollback events, for the systems that need to react
+// to a hit or a kill without owning the damage pipeline (audio, ui, stats)
+#[derive(Clone, Debug)]
+pub struct DamageEvent {
+    pub entity: Entity,
+    // Where the entity was, it can be despawned before the event is read
+    pub position: Vec2,
+    pub damage: f32,
+    pub hit_count: u32,
+    pub hit_by: Option<HitBy>,
+    pub hit_zone: Option<HitZoneKind>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub position: Vec2,
+    // Leave a corpse
+    pub enemy: bool,
+    pub last_hit_by: Option<HitBy>,
+    pub headshot: bool,
+    pub weapon: Option<KillWeapon>,
 }
 
 #[derive(Component, Reflect, Clone, Serialize, Deserialize, Default)]
@@ -38,29 +76,217 @@ pub struct DamageAccumulator {
     pub total_damage: f32,
     pub hit_count: u32,
     pub last_hit_by: Option<HitBy>,
+    pub last_hit_zone: Option<HitZoneKind>,
+    // Damage of each player in this frame, before the multipliers
+    pub by_player: Vec<(PlayerHandle, f32)>,
+    // Last weapon each player hit with this frame
+    pub weapons: Vec<(PlayerHandle, KillWeapon)>,
+    // Hits travelling in a direction with their damage, the shields block the frontal ones
+    pub directional: Vec<(Vec2, f32)>,
+}
+
+impl DamageAccumulator {
+    pub fn add_player_damage(&mut self, handle: PlayerHandle, damage: f32) {
+        match self.by_player.iter_mut().find(|(h, _)| *h == handle) {
+            Some((_, total)) => *total += damage,
+            None => self.by_player.push((handle, damage)),
+        }
+    }
+
+    pub fn add_player_weapon(&mut self, handle: PlayerHandle, weapon: &KillWeapon) {
+        match self.weapons.iter_mut().find(|(h, _)| *h == handle) {
+            Some((_, last)) => *last = weapon.clone(),
+            None => self.weapons.push((handle, weapon.clone())),
+        }
+    }
+
+    // Weapon of the killer, None for a kill that is not from a player
+    pub fn killer_weapon(&self) -> Option<KillWeapon> {
+        match self.killer() {
+            Some(HitBy::Player(killer)) => self.weapons.iter().find(|(h, _)| *h == killer).map(|(_, weapon)| weapon.clone()),
+            _ => None,
+        }
+    }
+
+    // The player that did the most damage this frame get the kill, not the one hit
+    // last, the order of two explosions landing together doesn't pick the killer
+    pub fn killer(&self) -> Option<HitBy> {
+        let best = self.by_player.iter()
+            .fold(None, |best: Option<(PlayerHandle, f32)>, (handle, damage)| match best {
+                Some((best_handle, best_damage)) if best_damage > *damage || (best_damage == *damage && best_handle < *handle) => best,
+                _ => Some((*handle, *damage)),
+            });
+        best.map(|(handle, _)| HitBy::Player(handle)).or_else(|| self.last_hit_by.clone())
+    }
+}
+
+// Damage each player did to the entity over its life, sorted by handle. Give the
+// assists when it die and the damage share of the scoreboard.
+#[derive(Component, Clone, Debug, Default)]
+pub struct DamageContributions {
+    pub damage: Vec<(PlayerHandle, f32)>,
+}
+
+impl DamageContributions {
+    pub fn add(&mut self, handle: PlayerHandle, damage: f32) {
+        match self.damage.binary_search_by_key(&handle, |(h, _)| *h) {
+            Ok(index) => self.damage[index].1 += damage,
+            Err(index) => self.damage.insert(index, (handle, damage)),
+        }
+    }
+
+    pub fn of(&self, handle: PlayerHandle) -> f32 {
+        self.damage.iter().find(|(h, _)| *h == handle).map_or(0.0, |(_, damage)| *damage)
+    }
+
+    // Split the damage dealt between the players of the frame, by what each one did
+    pub fn record(&mut self, accumulator: &DamageAccumulator, dealt: f32) {
+        let total: f32 = accumulator.by_player.iter().map(|(_, damage)| *damage).sum();
+        if total <= 0.0 || dealt <= 0.0 {
+            return;
+        }
+        let mut by_player = accumulator.by_player.clone();
+        by_player.sort_by_key(|(handle, _)| *handle);
+        for (handle, damage) in by_player {
+            self.add(handle, dealt * damage / total);
+        }
+    }
+}
+
+// Add damage to the accumulator of the entity, creating it if this is the first hit of the frame
+pub fn accumulate_damage(
+    commands: &mut Commands,
+    entity: Entity,
+    opt_accumulator: Option<Mut<DamageAccumulator>>,
+    damage: f32,
+    hit_by: Option<HitBy>,
+) {
+    if let Some(mut accumulator) = opt_accumulator {
+        accumulator.total_damage += damage;
+        accumulator.hit_count += 1;
+        if let Some(HitBy::Player(handle)) = hit_by {
+            accumulator.add_player_damage(handle, damage);
+        }
+        accumulator.last_hit_by = hit_by;
+        accumulator.last_hit_zone = None;
+    } else {
+        let mut accumulator = DamageAccumulator {
+            total_damage: damage,
+            hit_count: 1,
+            last_hit_by: hit_by.clone(),
+            last_hit_zone: None,
+            by_player: vec![],
+            weapons: vec![],
+            directional: vec![],
+        };
+        if let Some(HitBy::Player(handle)) = hit_by {
+            accumulator.add_player_damage(handle, damage);
+        }
+        commands.entity(entity).insert(accumulator);
+    }
+}
+
+impl Health {
+    pub fn is_invulnerable(&self, current_frame: u32) -> bool {
+        self.invulnerable.map_or(false, |timer| timer.is_active(current_frame))
+    }
 }
 
 impl From<HealthConfig> for Health {
     fn from(value: HealthConfig) -> Self {
-       Self { current: value.max, max: value.max, invulnerable_until_frame: None } 
+       Self { current: value.max, max: value.max, invulnerable: None } 
     } 
 }
 
 
 pub fn rollback_apply_accumulated_damage(
     mut commands: Commands,
-    mut query: Query<(Entity, &DamageAccumulator, &mut Health), With<Rollback>>,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    power_ups: Res<ActivePowerUps>,
+    mut damage_events: ResMut<RollbackEvents<DamageEvent>>,
+    mut shield_events: ResMut<RollbackEvents<ShieldBrokenEvent>>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    mut query: Query<(Entity, &Transform, &DamageAccumulator, &mut Health, Option<&mut Armor>, Option<&Enemy>, Option<&CharacterConfigHandles>, Option<&AuraProtected>, Option<&mut FlinchState>, Option<&mut DamageContributions>, Option<&mut ShieldState>), With<Rollback>>,
 ) {
-    for (entity, accumulator, mut health) in query.iter_mut() {
+    let mut query: Vec<_> = query.iter_mut().collect();
+    query.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, accumulator, mut health, opt_armor, opt_enemy, opt_config, opt_protected, opt_flinch, opt_contributions, opt_shield) in query {
+
+        if health.is_invulnerable(frame.frame) {
+            commands.entity(entity).remove::<DamageAccumulator>();
+            continue;
+        }
 
         if accumulator.total_damage > 0. {
 
-            health.current -= accumulator.total_damage;
+            // Insta-kill power-up, any damage from a player kill the enemy
+            let insta_kill = power_ups.is_insta_kill() && opt_enemy.is_some()
+                && matches!(accumulator.last_hit_by, Some(HitBy::Player(_)));
+
+            let config = opt_config.and_then(|handles| character_configs.get(&handles.config));
+
+            // The shield block the frontal hits before anything else
+            let damage = match (opt_shield, config.and_then(|config| config.shield)) {
+                (Some(mut shield), Some(shield_config)) => {
+                    let (damage, broke) = shield.block(accumulator, &shield_config);
+                    if broke {
+                        shield_events.send(frame.frame, ShieldBrokenEvent { entity, position: transform.translation.truncate() });
+                    }
+                    damage
+                }
+                _ => accumulator.total_damage,
+            };
+
+            // Class passive, like the damage resistance of the heavy
+            let damage = config.map_or(damage, |config| round(damage * config.damage_multiplier()));
+            // Standing in the aura of a medic
+            let damage = opt_protected.map_or(damage, |protected| round(damage * protected.damage_multiplier));
+
+            // The armor of the player take the damage first
+            let damage = opt_armor.map_or(damage, |mut armor| armor.absorb(damage));
+
+            let health_before = health.current.max(0.);
+            if insta_kill {
+                health.current = 0.;
+            } else {
+                health.current -= damage;
+            }
+
+            // Only the health really taken count, not the overkill
+            let dealt = health_before - health.current.max(0.);
+            if let Some(mut contributions) = opt_contributions {
+                contributions.record(accumulator, dealt);
+            } else if !accumulator.by_player.is_empty() {
+                let mut contributions = DamageContributions::default();
+                contributions.record(accumulator, dealt);
+                commands.entity(entity).insert(contributions);
+            }
+
+            if let (Some(mut flinch), Some(flinch_config)) = (opt_flinch, config.and_then(|config| config.flinch)) {
+                flinch.take_damage(damage, &flinch_config, frame.frame, &simulation);
+            }
 
             commands.entity(entity).remove::<DamageAccumulator>();
 
+            damage_events.send(frame.frame, DamageEvent {
+                entity,
+                position: transform.translation.truncate(),
+                damage,
+                hit_count: accumulator.hit_count,
+                hit_by: accumulator.last_hit_by.clone(),
+                hit_zone: accumulator.last_hit_zone,
+            });
+
             if health.current <= 0. {
-                commands.entity(entity).insert(Death{ last_hit_by: accumulator.last_hit_by.clone( )});
+                let killer = accumulator.killer();
+                // The zone is the one of the last hit, a headshot only if it was the killer
+                let last_hit_zone = match (&killer, &accumulator.last_hit_by) {
+                    (Some(HitBy::Player(killer)), Some(HitBy::Player(last))) if killer != last => None,
+                    _ => accumulator.last_hit_zone,
+                };
+                commands.entity(entity).insert(Death{ last_hit_by: killer, last_hit_zone, weapon: accumulator.killer_weapon() });
             }
         }
     }
@@ -69,11 +295,95 @@ pub fn rollback_apply_accumulated_damage(
 
 pub fn rollback_apply_death(
     mut commands: Commands,
-    mut query: Query<(Entity, &Death), With<Rollback>>,
+    frame: Res<FrameCount>,
+    mut death_events: ResMut<RollbackEvents<DeathEvent>>,
+    query: Query<(Entity, &Transform, &Death, Has<Enemy>), With<Rollback>>,
 ) {
-    for (entity, death) in query.iter_mut() {
+    let mut query: Vec<_> = query.iter().collect();
+    query.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, death, enemy) in query {
         info!("Entity {} killed by {:?}", entity, death.last_hit_by);
+        death_events.send(frame.frame, DeathEvent { entity, position: transform.translation.truncate(), enemy, last_hit_by: death.last_hit_by.clone(), headshot: death.is_headshot(), weapon: death.weapon.clone() });
         commands.entity(entity).try_despawn_recursive();
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapons::WeaponRarity;
+
+    fn weapon(name: &str, rarity: WeaponRarity) -> KillWeapon {
+        KillWeapon { name: name.to_string(), rarity }
+    }
+
+    #[test]
+    fn test_killer_weapon_is_the_last_one_of_the_killer() {
+        let mut accumulator = DamageAccumulator::default();
+        accumulator.add_player_damage(0, 10.0);
+        accumulator.add_player_weapon(0, &weapon("Pistol", WeaponRarity::Common));
+        accumulator.add_player_damage(0, 10.0);
+        accumulator.add_player_weapon(0, &weapon("Knife", WeaponRarity::Epic));
+        accumulator.add_player_damage(1, 15.0);
+        accumulator.add_player_weapon(1, &weapon("Shotgun", WeaponRarity::Rare));
+
+        assert_eq!(accumulator.killer_weapon(), Some(weapon("Knife", WeaponRarity::Epic)));
+    }
+
+    #[test]
+    fn test_no_killer_weapon_when_not_killed_by_a_player() {
+        let accumulator = DamageAccumulator { last_hit_by: Some(HitBy::Entity(Entity::from_raw(3))), ..default() };
+        assert_eq!(accumulator.killer_weapon(), None);
+    }
+
+    #[test]
+    fn test_killer_did_the_most_damage_of_the_frame() {
+        let mut accumulator = DamageAccumulator { last_hit_by: Some(HitBy::Player(1)), ..default() };
+        accumulator.add_player_damage(0, 30.0);
+        accumulator.add_player_damage(1, 20.0);
+        accumulator.add_player_damage(1, 5.0);
+        assert!(matches!(accumulator.killer(), Some(HitBy::Player(0))));
+    }
+
+    #[test]
+    fn test_killer_on_a_tie_is_the_lowest_handle() {
+        let mut accumulator = DamageAccumulator::default();
+        accumulator.add_player_damage(2, 30.0);
+        accumulator.add_player_damage(1, 30.0);
+        assert!(matches!(accumulator.killer(), Some(HitBy::Player(1))));
+    }
+
+    #[test]
+    fn test_killer_without_player_damage_is_the_last_hit() {
+        let accumulator = DamageAccumulator { last_hit_by: Some(HitBy::Entity(Entity::from_raw(2))), ..default() };
+        assert!(matches!(accumulator.killer(), Some(HitBy::Entity(entity)) if entity == Entity::from_raw(2)));
+        assert!(DamageAccumulator::default().killer().is_none());
+    }
+
+    #[test]
+    fn test_record_split_the_dealt_damage_by_share() {
+        let mut accumulator = DamageAccumulator::default();
+        accumulator.add_player_damage(1, 30.0);
+        accumulator.add_player_damage(0, 10.0);
+
+        let mut contributions = DamageContributions::default();
+        // Only 20 of the 40 went through, the armor or the shield took the rest
+        contributions.record(&accumulator, 20.0);
+        assert_eq!(contributions.damage, vec![(0, 5.0), (1, 15.0)]);
+        assert_eq!(contributions.of(1), 15.0);
+        assert_eq!(contributions.of(2), 0.0);
+    }
+
+    #[test]
+    fn test_record_nothing_without_damage() {
+        let mut accumulator = DamageAccumulator::default();
+        accumulator.add_player_damage(0, 10.0);
+        let mut contributions = DamageContributions::default();
+        contributions.record(&accumulator, 0.0);
+        contributions.record(&DamageAccumulator::default(), 10.0);
+        assert!(contributions.damage.is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+use utils::events::RollbackEvents;
 
-use crate::character::enemy::Enemy;
+use crate::{character::{enemy::{attack::HeavyHitEvent, Enemy}, player::LocalPlayer}, collider::HitZoneKind, frame::{confirmed_frame, FrameCount}, hud::graphics::GraphicsPreferences, plugins::AppState};
 
-use super::Health;
+use super::{DamageEvent, Health};
 
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct HealthBar;
 
 /*
@@ -43,4 +45,177 @@ pub fn update_health_bars(
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// Red flash over the screen when the local player take a heavy hit, once its frame is confirmed
+const HEAVY_HIT_FLASH_SECONDS: f32 = 0.35;
+const HEAVY_HIT_FLASH_ALPHA: f32 = 0.35;
+
+#[derive(Component)]
+struct HeavyHitFlash;
+
+#[derive(Resource, Default)]
+struct HeavyHitFlashState {
+    timer: Option<Timer>,
+    last_read_frame: Option<u32>,
+}
+
+fn setup_heavy_hit_flash(mut commands: Commands) {
+    commands.spawn((
+        HeavyHitFlash,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::NONE),
+        // Under the other interfaces
+        GlobalZIndex(-1),
+    ));
+}
+
+fn update_heavy_hit_flash(
+    time: Res<Time>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    heavy_hit_events: Res<RollbackEvents<HeavyHitEvent>>,
+    q_local: Query<Entity, With<LocalPlayer>>,
+    mut state: ResMut<HeavyHitFlashState>,
+    mut q_flash: Query<&mut BackgroundColor, With<HeavyHitFlash>>,
+) {
+    if let Some(confirmed_frame) = confirmed_frame(&frame, confirmed).filter(|confirmed_frame| state.last_read_frame.map_or(true, |last| last < *confirmed_frame)) {
+        let hit = heavy_hit_events.read_after(state.last_read_frame)
+            .filter(|(f, _)| *f <= confirmed_frame)
+            .any(|(_, event)| q_local.contains(event.target));
+        if hit {
+            state.timer = Some(Timer::from_seconds(HEAVY_HIT_FLASH_SECONDS, TimerMode::Once));
+        }
+        state.last_read_frame = Some(confirmed_frame);
+    }
+
+    let alpha = match state.timer.as_mut() {
+        Some(timer) => {
+            timer.tick(time.delta());
+            HEAVY_HIT_FLASH_ALPHA * (1.0 - timer.fraction())
+        }
+        None => 0.0,
+    };
+    if state.timer.as_ref().map_or(false, |timer| timer.finished()) {
+        state.timer = None;
+    }
+    if let Ok(mut background) = q_flash.get_single_mut() {
+        background.0 = Color::srgba(0.8, 0.0, 0.0, alpha);
+    }
+}
+
+
+pub struct HeavyHitUIPlugin;
+
+impl Plugin for HeavyHitUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HeavyHitFlashState>();
+        app.add_systems(OnEnter(AppState::InGame), setup_heavy_hit_flash);
+        app.add_systems(Update, update_heavy_hit_flash.run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+// Damage over the hit characters, rising and fading. Read once their frame is
+// confirmed, a mispredicted hit never show a number.
+const DAMAGE_NUMBER_SECONDS: f32 = 0.8;
+const DAMAGE_NUMBER_RISE: f32 = 24.0;
+// Over the head, and over everything of the map
+const DAMAGE_NUMBER_OFFSET: Vec3 = Vec3::new(0.0, 16.0, 5.0);
+
+#[derive(Component)]
+struct DamageNumber {
+    timer: Timer,
+    origin: Vec3,
+}
+
+#[derive(Resource, Default)]
+struct DamageNumbersState {
+    last_read_frame: Option<u32>,
+}
+
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    preferences: Option<Res<GraphicsPreferences>>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    damage_events: Res<RollbackEvents<DamageEvent>>,
+    mut state: ResMut<DamageNumbersState>,
+) {
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    let last_read_frame = state.last_read_frame;
+    if last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+    state.last_read_frame = Some(confirmed_frame);
+    if !preferences.map_or(true, |preferences| preferences.damage_numbers) {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+    for (_, event) in damage_events.read_after(last_read_frame).filter(|(f, event)| *f <= confirmed_frame && event.damage > 0.0) {
+        let origin = event.position.extend(0.0) + DAMAGE_NUMBER_OFFSET;
+        let color = if event.hit_zone == Some(HitZoneKind::Head) {
+            Color::srgb(1.0, 0.8, 0.1)
+        } else {
+            Color::WHITE
+        };
+        commands.spawn((
+            DamageNumber { timer: Timer::from_seconds(DAMAGE_NUMBER_SECONDS, TimerMode::Once), origin },
+            Text2d::new(format!("{}", event.damage.round() as i32)),
+            TextFont {
+                font: font.clone(),
+                font_size: 12.0,
+                ..Default::default()
+            },
+            TextColor(color),
+            Transform::from_translation(origin),
+        ));
+    }
+}
+
+fn animate_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_number: Query<(Entity, &mut DamageNumber, &mut Transform, &mut TextColor)>,
+) {
+    for (entity, mut number, mut transform, mut color) in q_number.iter_mut() {
+        number.timer.tick(time.delta());
+        if number.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation = number.origin + Vec3::Y * DAMAGE_NUMBER_RISE * number.timer.fraction();
+        color.0 = color.0.with_alpha(number.timer.fraction_remaining());
+    }
+}
+
+fn clear_damage_numbers(
+    mut commands: Commands,
+    mut state: ResMut<DamageNumbersState>,
+    q_number: Query<Entity, With<DamageNumber>>,
+) {
+    for entity in q_number.iter() {
+        commands.entity(entity).despawn();
+    }
+    state.last_read_frame = None;
+}
+
+
+pub struct DamageNumbersPlugin;
+
+impl Plugin for DamageNumbersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DamageNumbersState>();
+        app.add_systems(Update, (spawn_damage_numbers, animate_damage_numbers).run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), clear_damage_numbers);
+    }
+}
@@ -5,6 +5,10 @@ pub mod player;
 pub mod health;
 pub mod create;
 pub mod dash;
+pub mod footprint;
+pub mod corpse;
+pub mod status;
+pub mod ability;
 
 
 use bevy::prelude::*;
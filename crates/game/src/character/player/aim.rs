@@ -0,0 +1,329 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use utils::aim::AIM_MAX_DISTANCE;
+
+use crate::{camera::GameCamera, plugins::AppState, weapons::ActiveWeapon, web::PointerLock};
+
+use super::{input::{local_pointer_offset, CursorPosition}, LocalPlayer, Player};
+
+// Local only aim smoothing, it move the reticle shown to the player but the value
+// sent in the inputs is always the raw pointer.
+//
+// The aim of the remote players arrive with the input delay, frame by frame, and
+// their weapon jump from one angle to the next. Their weapon sprite is turned toward
+// a smoothed angle, the weapon entity keep the simulated rotation the bullets use.
+
+#[derive(Resource, Clone, Debug)]
+pub struct AimSmoothingSettings {
+    pub enabled: bool,
+    // How fast the smoothed aim catch up with the pointer, per second
+    pub rate: f32,
+}
+
+impl Default for AimSmoothingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rate: 20.0,
+        }
+    }
+}
+
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct SmoothedLocalAim(pub Vec2);
+
+// Aim assist of the gamepads, applied by the source before the aim is encoded so the
+// assisted aim is what the peers receive. The stick is pulled toward the closest enemy
+// in a cone around it and turn slower while it is already on an enemy.
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct AimAssistSettings {
+    pub enabled: bool,
+    // From 0, no assist, to 1, full magnetism and slowdown
+    pub strength: f32,
+    // Half angle of the cone around the stick where an enemy pull the aim, in radians
+    pub cone: f32,
+    pub range: f32,
+    // Part of the angle to the enemy the aim is pulled by at full strength
+    pub magnetism: f32,
+    // Half angle where the aim is on an enemy, in radians
+    pub target_angle: f32,
+    // Part of the stick movement lost over an enemy at full strength
+    pub slowdown: f32,
+}
+
+impl Default for AimAssistSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.5,
+            cone: 0.35,
+            range: AIM_MAX_DISTANCE as f32,
+            magnetism: 0.6,
+            target_angle: 0.1,
+            slowdown: 0.6,
+        }
+    }
+}
+
+// Direction of the stick once assisted, `previous` is the direction sent the frame before
+pub fn assist_aim(settings: &AimAssistSettings, position: Vec2, stick: Vec2, previous: Option<Vec2>, enemies: &[Vec2]) -> Vec2 {
+    let strength = settings.strength.clamp(0.0, 1.0);
+    if !settings.enabled || strength <= 0.0 || stick == Vec2::ZERO {
+        return stick;
+    }
+    let stick_angle = stick.to_angle();
+
+    // Angle to each enemy in range, from the stick
+    let offsets: Vec<(f32, f32)> = enemies.iter()
+        .map(|enemy| *enemy - position)
+        .filter(|offset| *offset != Vec2::ZERO && offset.length() <= settings.range)
+        .map(|offset| (wrap_angle(offset.to_angle() - stick_angle), offset.length()))
+        .collect();
+
+    let mut angle = stick_angle;
+
+    // Slowdown, the turn from the previous aim is damped when it was on an enemy
+    if let Some(previous) = previous.filter(|previous| *previous != Vec2::ZERO) {
+        let previous_angle = previous.to_angle();
+        let on_target = offsets.iter()
+            .any(|(relative, _)| wrap_angle(stick_angle + relative - previous_angle).abs() <= settings.target_angle);
+        if on_target {
+            angle = previous_angle + wrap_angle(stick_angle - previous_angle) * (1.0 - settings.slowdown * strength);
+        }
+    }
+
+    // Magnetism toward the closest enemy in the cone of the stick
+    let closest = offsets.iter()
+        .filter(|(relative, _)| relative.abs() <= settings.cone)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    if let Some((relative, _)) = closest {
+        let target = stick_angle + relative;
+        angle += wrap_angle(target - angle) * settings.magnetism * strength;
+    }
+
+    Vec2::from_angle(angle) * stick.length()
+}
+
+#[derive(Component)]
+pub struct AimReticle;
+
+#[derive(Resource, Clone, Debug)]
+pub struct RemoteAimSettings {
+    pub enabled: bool,
+    // Time for the shown angle to catch up most of the way with the received one, in seconds
+    pub smoothing_window: f32,
+    // Difference in radians over which the shown angle jump to the received one, like a flick behind
+    pub snap_threshold: f32,
+    // How far ahead the turning speed of the aim is followed, in seconds
+    pub extrapolation: f32,
+}
+
+impl Default for RemoteAimSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            smoothing_window: 0.08,
+            snap_threshold: FRAC_PI_2,
+            extrapolation: 0.05,
+        }
+    }
+}
+
+// Angle shown for the weapon of a remote player
+#[derive(Component, Default, Debug)]
+struct RemoteAim {
+    displayed: f32,
+    received: f32,
+    // Radians per second
+    turn_speed: f32,
+}
+
+// Translation of a weapon sprite before it was turned by the remote aim
+#[derive(Component)]
+struct RemoteAimSprite {
+    base_translation: Vec3,
+}
+
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+pub struct AimPlugin;
+
+impl Plugin for AimPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AimSmoothingSettings>()
+            .init_resource::<AimAssistSettings>()
+            .register_type::<AimAssistSettings>()
+            .init_resource::<SmoothedLocalAim>()
+            .init_resource::<RemoteAimSettings>()
+            .add_systems(OnEnter(AppState::InGame), setup_aim_reticle)
+            .add_systems(Update, (smooth_local_aim_system, update_aim_reticle_system).chain().run_if(in_state(AppState::InGame)))
+            .add_systems(Update, (track_remote_aim_system, turn_remote_weapon_sprites).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+fn setup_aim_reticle(mut commands: Commands) {
+    commands.spawn((
+        AimReticle,
+        Sprite::from_color(Color::srgba(1.0, 1.0, 1.0, 0.6), Vec2::splat(6.0)),
+        Transform::from_xyz(0.0, 0.0, 20.0),
+    ));
+}
+
+fn smooth_local_aim_system(
+    time: Res<Time>,
+    settings: Res<AimSmoothingSettings>,
+    mut smoothed: ResMut<SmoothedLocalAim>,
+    q_player: Query<&Transform, With<LocalPlayer>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    pointer_lock: Res<PointerLock>,
+) {
+    let Ok(transform) = q_player.get_single() else {
+        return;
+    };
+    // Clamped like the transmitted aim so the reticle match where the bullets go
+    let Some(target) = local_pointer_offset(&q_window, &q_camera, &pointer_lock, transform) else {
+        return;
+    };
+
+    smoothed.0 = if settings.enabled {
+        let t = (settings.rate * time.delta_secs()).clamp(0.0, 1.0);
+        smoothed.0.lerp(target, t)
+    } else {
+        target
+    };
+}
+
+fn update_aim_reticle_system(
+    smoothed: Res<SmoothedLocalAim>,
+    q_player: Query<&Transform, (With<LocalPlayer>, Without<AimReticle>)>,
+    mut q_reticle: Query<&mut Transform, With<AimReticle>>,
+) {
+    let (Ok(player_transform), Ok(mut transform)) = (q_player.get_single(), q_reticle.get_single_mut()) else {
+        return;
+    };
+
+    let position = player_transform.translation.truncate() + smoothed.0;
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+}
+
+fn track_remote_aim_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<RemoteAimSettings>,
+    mut q_player: Query<(Entity, &CursorPosition, Option<&mut RemoteAim>), (With<Player>, Without<LocalPlayer>)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, cursor, opt_aim) in q_player.iter_mut() {
+        if cursor.x == 0 && cursor.y == 0 {
+            continue;
+        }
+        let received = (cursor.y as f32).atan2(cursor.x as f32);
+
+        let Some(mut aim) = opt_aim else {
+            commands.entity(entity).insert(RemoteAim { displayed: received, received, turn_speed: 0.0 });
+            continue;
+        };
+        if dt <= 0.0 {
+            continue;
+        }
+
+        // Half of the new speed each render frame, the aim only change on the simulation frames
+        let turn = wrap_angle(received - aim.received);
+        aim.turn_speed += (turn / dt - aim.turn_speed) * 0.5;
+        aim.received = received;
+
+        let goal = wrap_angle(received + aim.turn_speed * settings.extrapolation);
+        let difference = wrap_angle(goal - aim.displayed);
+        aim.displayed = if difference.abs() > settings.snap_threshold || settings.smoothing_window <= 0.0 {
+            goal
+        } else {
+            wrap_angle(aim.displayed + difference * (1.0 - (-dt / settings.smoothing_window).exp()))
+        };
+    }
+}
+
+// Turn the sprite of the active weapon by the difference between the shown and the simulated angle
+fn turn_remote_weapon_sprites(
+    mut commands: Commands,
+    settings: Res<RemoteAimSettings>,
+    q_player: Query<(&Children, &RemoteAim)>,
+    q_weapon: Query<(&Children, &Transform), With<ActiveWeapon>>,
+    mut q_sprite: Query<(Entity, &mut Transform, Option<&RemoteAimSprite>), (With<Sprite>, Without<ActiveWeapon>)>,
+) {
+    for (children, aim) in q_player.iter() {
+        for (weapon_children, weapon_transform) in children.iter().filter_map(|child| q_weapon.get(*child).ok()) {
+            let simulated = weapon_transform.rotation.to_euler(EulerRot::XYZ).2;
+            let offset = if settings.enabled { Quat::from_rotation_z(wrap_angle(aim.displayed - simulated)) } else { Quat::IDENTITY };
+
+            for child in weapon_children.iter() {
+                let Ok((entity, mut transform, opt_sprite)) = q_sprite.get_mut(*child) else {
+                    continue;
+                };
+                let Some(sprite) = opt_sprite else {
+                    commands.entity(entity).insert(RemoteAimSprite { base_translation: transform.translation });
+                    continue;
+                };
+                // Around the weapon origin, not the sprite one
+                transform.rotation = offset;
+                transform.translation = offset * sprite.base_translation;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assist() -> AimAssistSettings {
+        AimAssistSettings { enabled: true, strength: 1.0, range: 500.0, ..Default::default() }
+    }
+
+    fn angle_of(settings: &AimAssistSettings, stick: Vec2, previous: Option<Vec2>, enemies: &[Vec2]) -> f32 {
+        assist_aim(settings, Vec2::ZERO, stick, previous, enemies).to_angle()
+    }
+
+    #[test]
+    fn test_disabled_assist_keep_the_stick() {
+        let enemies = [Vec2::from_angle(0.2) * 100.0];
+        let settings = AimAssistSettings { enabled: false, ..assist() };
+        assert_eq!(assist_aim(&settings, Vec2::ZERO, Vec2::X, None, &enemies), Vec2::X);
+        let settings = AimAssistSettings { strength: 0.0, ..assist() };
+        assert_eq!(assist_aim(&settings, Vec2::ZERO, Vec2::X, None, &enemies), Vec2::X);
+    }
+
+    #[test]
+    fn test_magnetism_pull_toward_the_enemy_in_the_cone() {
+        let settings = assist();
+        let angle = angle_of(&settings, Vec2::X, None, &[Vec2::from_angle(0.2) * 100.0]);
+        assert!((angle - 0.2 * settings.magnetism).abs() < 1e-4);
+        // Outside of the cone or too far
+        assert!(angle_of(&settings, Vec2::X, None, &[Vec2::from_angle(0.5) * 100.0]).abs() < 1e-4);
+        assert!(angle_of(&settings, Vec2::X, None, &[Vec2::from_angle(0.2) * 1000.0]).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_slowdown_damp_the_turn_over_an_enemy() {
+        let settings = AimAssistSettings { magnetism: 0.0, ..assist() };
+        let stick = Vec2::from_angle(0.05);
+        let angle = angle_of(&settings, stick, Some(Vec2::X), &[Vec2::X * 100.0]);
+        assert!((angle - 0.05 * (1.0 - settings.slowdown)).abs() < 1e-4);
+        // The previous aim was not on the enemy
+        let angle = angle_of(&settings, stick, Some(Vec2::from_angle(-0.3)), &[Vec2::X * 100.0]);
+        assert!((angle - 0.05).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_assist_keep_the_stick_length() {
+        let stick = Vec2::from_angle(0.1) * 0.7;
+        let assisted = assist_aim(&assist(), Vec2::ZERO, stick, None, &[Vec2::from_angle(0.2) * 100.0]);
+        assert!((assisted.length() - 0.7).abs() < 1e-4);
+    }
+}
@@ -15,6 +15,7 @@ pub enum PlayerAction {
     Interaction,
     Sprint,
     Dash,
+    Ability,
 
     SwitchWeapon,
     SwitchWeaponMode,
@@ -48,13 +49,14 @@ pub fn get_input_map() -> InputMap<PlayerAction> {
         (PlayerAction::MoveRight, KeyCode::KeyD),
         (PlayerAction::MoveRight, KeyCode::ArrowRight),
         (PlayerAction::Interaction, KeyCode::KeyH),
-        (PlayerAction::SwitchWeapon, KeyCode::Tab),
+        (PlayerAction::SwitchWeapon, KeyCode::KeyQ),
         (PlayerAction::SwitchWeaponMode, KeyCode::KeyZ),
         (PlayerAction::Reload, KeyCode::KeyR),
         (PlayerAction::MoveCameraRight, KeyCode::ArrowRight),
         (PlayerAction::Interaction, KeyCode::KeyH),
         (PlayerAction::Sprint, KeyCode::ShiftLeft),
         (PlayerAction::Dash, KeyCode::KeyC),
+        (PlayerAction::Ability, KeyCode::KeyE),
         (PlayerAction::Modifier, KeyCode::ControlLeft),
     ]);
     // Add gamepad support if needed
@@ -64,6 +66,7 @@ pub fn get_input_map() -> InputMap<PlayerAction> {
     map.insert(PlayerAction::MoveRight, GamepadButton::DPadRight);
     map.insert(PlayerAction::Interaction, GamepadButton::North);
     map.insert(PlayerAction::Reload, GamepadButton::West);
+    map.insert(PlayerAction::Ability, GamepadButton::East);
     // Add more bindings...
     map.insert(PlayerAction::PointerClick, MouseButton::Left);
 
@@ -74,4 +77,37 @@ pub fn get_input_map() -> InputMap<PlayerAction> {
 
 }
 
+// Label of the first key bound to the action, for the prompts. The letters and the
+// digits without the `Key` or `Digit` of the key code, the other keys as they are named.
+pub fn keyboard_label(map: &InputMap<PlayerAction>, action: PlayerAction) -> Option<String> {
+    let key = map.get_buttonlike(&action)?
+        .iter()
+        .find_map(|binding| binding.as_reflect().downcast_ref::<KeyCode>().copied())?;
+    let name = format!("{:?}", key);
+    let label = name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")).unwrap_or(&name);
+    Some(label.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn test_keyboard_label_of_the_default_map() {
+        let map = get_input_map();
+        assert_eq!(keyboard_label(&map, PlayerAction::Interaction), Some("H".to_string()));
+        assert_eq!(keyboard_label(&map, PlayerAction::Sprint), Some("ShiftLeft".to_string()));
+        // Only a mouse button
+        assert_eq!(keyboard_label(&map, PlayerAction::PointerClick), None);
+    }
+
+    #[test]
+    fn test_keyboard_label_follow_the_binding() {
+        let mut map = get_input_map();
+        map.clear_action(&PlayerAction::Interaction);
+        map.insert(PlayerAction::Interaction, GamepadButton::North);
+        map.insert(PlayerAction::Interaction, KeyCode::Digit4);
+        assert_eq!(keyboard_label(&map, PlayerAction::Interaction), Some("4".to_string()));
+    }
+}
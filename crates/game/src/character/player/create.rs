@@ -5,17 +5,13 @@ use leafwing_input_manager::{prelude::ActionState, InputManagerBundle};
 use utils::bmap;
 use bevy_kira_audio::prelude::*;
 
-use crate::{character::{config::CharacterConfig, create::create_character, dash::DashState, movement::{SprintState, Velocity}}, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings}, global_asset::GlobalAsset, weapons::{spawn_weapon_for_player, FiringMode, Weapon, WeaponInventory, WeaponsConfig}};
+use crate::{character::ability::AbilityState, points::{PlayerPoints, PlayerScore}, trade::TradeState, character::player::input::PreviousInput, character::{config::CharacterConfig, create::create_character, dash::DashState, movement::{SprintState, Velocity}}, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings}, global_asset::GlobalAsset, progression::PlayerLoadout, weapons::{spawn_weapon_for_player, FiringMode, Weapon, WeaponInventory, WeaponsConfig}};
 
 use bevy_ggrs::AddRollbackCommandExtension;
 use super::{control::{get_input_map, PlayerAction}, input::CursorPosition, LocalPlayer, Player};
 
-const PLAYER_COLORS: &'static [LinearRgba] = &[
-    LinearRgba::RED,
-    LinearRgba::BLUE,
-    LinearRgba::GREEN,
-    LinearRgba::BLACK,
-];
+// Config used when no class is chosen
+pub const DEFAULT_PLAYER_CLASS: &str = "player";
 
 pub fn create_player(
     commands: &mut Commands,
@@ -30,12 +26,29 @@ pub fn create_player(
 
     local: bool,
     handle: usize,
-) {
+    // Color of its identity, for the indicators
+    color: Color,
+    class: &str,
+    loadout: &PlayerLoadout,
+) -> Entity {
+    let class = if global_assets.character_configs.contains_key(class) {
+        class.to_string()
+    } else {
+        warn!("unknown player class {}, using the default one", class);
+        DEFAULT_PLAYER_CLASS.to_string()
+    };
 
+    let config = global_assets.character_configs.get(&class).and_then(|handle| character_asset.get(handle));
+    let starting_weapons = config.map(|config| config.starting_weapons.clone()).unwrap_or_default();
+
+    // The skin of the loadout when the class has it, otherwise one per handle to tell the players apart
+    let skin = loadout.skin.clone()
+        .filter(|skin| config.map_or(false, |config| config.skins.contains_key(skin)))
+        .unwrap_or_else(|| if handle == 0 { "1" } else { "2" }.into());
 
     let entity = create_character(
         commands, global_assets, character_asset, asset_server, texture_atlas_layouts, sprint_sheet_assets,
-        "player".into(), Some(if handle == 0 { "1" } else { "2" }.into()),
+        class, Some(skin), &loadout.colors,
          (LinearRgba::GREEN).into(),Vec3::new(-50.0 * handle as f32, 0.0, 0.0),
         CollisionLayer(collision_settings.player_layer),
     );
@@ -53,10 +66,18 @@ pub fn create_player(
     let mut inventory = WeaponInventory::default();
 
     if let Some(weapons_config) = weapons_asset.get(&global_assets.weapons) {
-        let mut keys: Vec<&String> = weapons_config.0.keys().collect();
-        keys.sort();
+        let mut keys = weapons_config.starting_weapons();
+        // The class choose its weapons, in its own order
+        if !starting_weapons.is_empty() {
+            keys = starting_weapons.iter().filter(|k| weapons_config.weapons.contains_key(*k)).collect();
+        }
+        // The starting weapon of the loadout is equipped first
+        if let Some(weapon) = loadout.weapon.as_ref().filter(|k| weapons_config.weapons.contains_key(*k)) {
+            keys.retain(|k| *k != weapon);
+            keys.insert(0, weapon);
+        }
         for (i, k) in keys.iter().enumerate() {
-            spawn_weapon_for_player(commands, global_assets, asset_server, texture_atlas_layouts, sprint_sheet_assets, i == 0, entity, weapons_config.0.get(*k).unwrap().clone(), &mut inventory);
+            spawn_weapon_for_player(commands, global_assets, asset_server, texture_atlas_layouts, sprint_sheet_assets, i == 0, entity, weapons_config.weapons.get(*k).unwrap().clone(), &mut inventory);
         }
     }
     
@@ -64,10 +85,16 @@ pub fn create_player(
         .insert((
             inventory,
             CursorPosition::default(),
+            PlayerPoints::default(),
+            PlayerScore::default(),
+            TradeState::default(),
+            PreviousInput::default(),
+            AbilityState::default(),
             Player {
                 handle,
-                color: PLAYER_COLORS[handle].into(),
+                color,
             }
         ));
 
+    entity
 }
\ No newline at end of file
@@ -1,54 +1,234 @@
 
-use animation::{ActiveLayers, FacingDirection};
+use animation::{ActiveLayers, FacingDirection, FacingDirection8};
 use animation::{AnimationState, CharacterAnimationHandles};
 use bevy::window::PrimaryWindow;
 use bevy::{prelude::*, time::Time, utils::HashMap};
 use leafwing_input_manager::prelude::*;
 use bevy_ggrs::prelude::*;
 use bevy_ggrs::LocalInputs;
-use serde::{Serialize, Deserialize};
-use utils::math::round_vec3; 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use utils::{aim::{decode_aim, pointer_aim}, frame::SimulationConfig, math::round_vec3};
 
+use crate::camera::{camera_view, GameCamera};
 use crate::character::config::{CharacterConfig, CharacterConfigHandles};
 use crate::character::dash::DashState;
 use crate::character::movement::{MovementConfig, SprintState, Velocity};
+use crate::character::enemy::{climb::{Climbing, CLIMB_ANIMATION}, flinch::{FlinchState, HIT_ANIMATION}, Enemy};
+use crate::character::status::{Grabbed, Knockback, Stunned};
+use crate::equipment::SpeedBoost;
 use crate::character::player::{control::PlayerAction, Player};
 use crate::collider::{is_colliding, Collider, CollisionLayer, CollisionSettings, Wall};
 use crate::frame::FrameCount;
-use crate::weapons::WeaponInventory;
+use crate::network::compression::{InputCompression, InputCompressionStats};
+use crate::rules::dropin::DropInRequest;
+use crate::weapons::{wheel::WeaponWheelState, WeaponInventory, RELOAD_ANIMATION};
+use crate::web::{focus::{FocusSettings, WindowFocus}, pointer_position, touch::TouchControls, PointerLock};
 
 use super::jjrs::PeerConfig;
+use super::aim::AimAssistSettings;
+use super::source::{InputContext, LocalInputSources};
 use super::LocalPlayer;
 
-pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0; // 60 FPS fixed timestep
 
 
-const INPUT_UP: u16 = 1 << 0;
-const INPUT_DOWN: u16 = 1 << 1;
-const INPUT_LEFT: u16 = 1 << 2;
-const INPUT_RIGHT: u16 = 1 << 3;
+pub const INPUT_UP: u16 = 1 << 0;
+pub const INPUT_DOWN: u16 = 1 << 1;
+pub const INPUT_LEFT: u16 = 1 << 2;
+pub const INPUT_RIGHT: u16 = 1 << 3;
 pub const INPUT_RELOAD: u16 = 1 << 4;
 pub const INPUT_SWITCH_WEAPON_MODE: u16 = 1 << 5;
 pub const INPUT_SPRINT: u16 = 1 << 6;
 pub const INPUT_DASH: u16 = 1 << 7;
 pub const INPUT_MODIFIER: u16 = 1 << 8;
+pub const INPUT_INTERACTION: u16 = 1 << 9;
 
-const PAN_FACING_THRESHOLD: i16 = 5;
+// Direct weapon slot selection, slot + 1 on 3 bits, 0 when nothing is selected
+const INPUT_SLOT_SHIFT: u16 = 10;
+const INPUT_SLOT_MASK: u16 = 0b111 << INPUT_SLOT_SHIFT;
+pub const MAX_SELECTABLE_SLOTS: usize = 7;
+
+// Sent by a drop-in place without a player, see rules::dropin
+pub const INPUT_JOIN: u16 = 1 << 13;
+// Active ability of the class, see character::ability
+pub const INPUT_ABILITY: u16 = 1 << 14;
+
+// Every bit with a meaning, a peer sending the others is not running this game
+pub const INPUT_KNOWN_BITS: u16 = INPUT_UP | INPUT_DOWN | INPUT_LEFT | INPUT_RIGHT | INPUT_RELOAD | INPUT_SWITCH_WEAPON_MODE
+    | INPUT_SPRINT | INPUT_DASH | INPUT_MODIFIER | INPUT_INTERACTION | INPUT_SLOT_MASK | INPUT_JOIN | INPUT_ABILITY;
+
+const PAN_FACING_THRESHOLD: i32 = 5;
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
 pub struct BoxInput{
     pub buttons: u16,
-    pub pan_x: i16,
-    pub pan_y: i16,
+    // Packed angle and magnitude of the aim, see utils::aim
+    pub aim: u16,
 
     pub fire: bool,
     pub switch_weapon: bool,
+    // Reorder of the inventory, slot + 1 of the weapon moved on the low 3 bits and of
+    // its new slot on the next 3, 0 when nothing is moved
+    pub slot_move: u8,
+}
+
+impl BoxInput {
+    // World offset from the player to where he aims, same on all peers
+    pub fn aim_offset(&self) -> IVec2 {
+        decode_aim(self.aim)
+    }
+
+    pub fn selected_slot(&self) -> Option<usize> {
+        match (self.buttons & INPUT_SLOT_MASK) >> INPUT_SLOT_SHIFT {
+            0 => None,
+            value => Some(value as usize - 1),
+        }
+    }
+
+    pub fn set_selected_slot(&mut self, slot: usize) {
+        if slot < MAX_SELECTABLE_SLOTS {
+            self.buttons = (self.buttons & !INPUT_SLOT_MASK) | (((slot + 1) as u16) << INPUT_SLOT_SHIFT);
+        }
+    }
+
+    pub fn slot_move(&self) -> Option<(usize, usize)> {
+        let (from, to) = (self.slot_move & SLOT_MOVE_MASK, (self.slot_move >> SLOT_MOVE_SHIFT) & SLOT_MOVE_MASK);
+        (from > 0 && to > 0).then(|| (from as usize - 1, to as usize - 1))
+    }
+
+    pub fn set_slot_move(&mut self, from: usize, to: usize) {
+        if from < MAX_SELECTABLE_SLOTS && to < MAX_SELECTABLE_SLOTS {
+            self.slot_move = (from + 1) as u8 | (((to + 1) as u8) << SLOT_MOVE_SHIFT);
+        }
+    }
+}
+
+const SLOT_MOVE_SHIFT: u8 = 3;
+const SLOT_MOVE_MASK: u8 = 0b111;
+pub const SLOT_MOVE_BITS: u8 = SLOT_MOVE_MASK | (SLOT_MOVE_MASK << SLOT_MOVE_SHIFT);
+
+const PACKED_FIRE: u8 = 1 << 0;
+const PACKED_SWITCH_WEAPON: u8 = 1 << 1;
+// The slot move take the 6 bits left of the byte
+const PACKED_SLOT_MOVE_SHIFT: u8 = 2;
+
+// Input as sent to the peers. GGRS XOR it with the last acknowledged input and run
+// length encode the result, an unchanged field cost almost nothing but every input
+// must have the same size. The booleans share one byte, 5 bytes instead of 6
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackedInput(u16, u16, u8);
+
+impl From<BoxInput> for PackedInput {
+    fn from(input: BoxInput) -> Self {
+        let mut flags = 0;
+        if input.fire {
+            flags |= PACKED_FIRE;
+        }
+        if input.switch_weapon {
+            flags |= PACKED_SWITCH_WEAPON;
+        }
+        flags |= input.slot_move << PACKED_SLOT_MOVE_SHIFT;
+        PackedInput(input.buttons, input.aim, flags)
+    }
+}
+
+impl From<PackedInput> for BoxInput {
+    fn from(packed: PackedInput) -> Self {
+        BoxInput {
+            buttons: packed.0,
+            aim: packed.1,
+            fire: packed.2 & PACKED_FIRE != 0,
+            switch_weapon: packed.2 & PACKED_SWITCH_WEAPON != 0,
+            slot_move: packed.2 >> PACKED_SLOT_MOVE_SHIFT,
+        }
+    }
+}
+
+impl PackedInput {
+    pub const BYTES: usize = 5;
+
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let [b0, b1] = self.0.to_le_bytes();
+        let [a0, a1] = self.1.to_le_bytes();
+        [b0, b1, a0, a1, self.2]
+    }
+}
+
+// The input files and the capture logs keep the readable fields, only the binary
+// format of the GGRS messages is packed
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "BoxInput")]
+struct BoxInputFields {
+    buttons: u16,
+    aim: u16,
+    fire: bool,
+    switch_weapon: bool,
+    // Written after the files of the first captures
+    #[serde(default)]
+    slot_move: u8,
+}
+
+impl Serialize for BoxInput {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            BoxInputFields { buttons: self.buttons, aim: self.aim, fire: self.fire, switch_weapon: self.switch_weapon, slot_move: self.slot_move }.serialize(serializer)
+        } else {
+            PackedInput::from(*self).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BoxInput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let fields = BoxInputFields::deserialize(deserializer)?;
+            Ok(BoxInput { buttons: fields.buttons, aim: fields.aim, fire: fields.fire, switch_weapon: fields.switch_weapon, slot_move: fields.slot_move })
+        } else {
+            PackedInput::deserialize(deserializer).map(BoxInput::from)
+        }
+    }
 }
 
 #[derive(Resource, Default, Debug, Clone, Copy)]
 pub struct PointerWorldPosition(pub Vec2);
 
+// Rollback copy of the input of the previous frame of a player, used to detect
+// press and release instead of relying on cooldown between two actions
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct PreviousInput(pub BoxInput);
+
+impl PreviousInput {
+    pub fn just_pressed(&self, input: &BoxInput, button: u16) -> bool {
+        input.buttons & button != 0 && self.0.buttons & button == 0
+    }
+
+    pub fn just_released(&self, input: &BoxInput, button: u16) -> bool {
+        input.buttons & button == 0 && self.0.buttons & button != 0
+    }
+
+    // Interaction pressed without the modifier, modifier + interaction is the trade
+    pub fn interact_just_pressed(&self, input: &BoxInput) -> bool {
+        self.just_pressed(input, INPUT_INTERACTION) && input.buttons & INPUT_MODIFIER == 0
+    }
+
+    pub fn fire_just_pressed(&self, input: &BoxInput) -> bool {
+        input.fire && !self.0.fire
+    }
+
+    pub fn switch_weapon_just_pressed(&self, input: &BoxInput) -> bool {
+        input.switch_weapon && !self.0.switch_weapon
+    }
+
+    pub fn slot_just_selected(&self, input: &BoxInput) -> Option<usize> {
+        input.selected_slot().filter(|slot| self.0.selected_slot() != Some(*slot))
+    }
+
+    // A predicted frame repeat the last input, the move is only done once
+    pub fn slot_move_just_sent(&self, input: &BoxInput) -> Option<(usize, usize)> {
+        input.slot_move().filter(|slot_move| self.0.slot_move() != Some(*slot_move))
+    }
+}
+
 /// Component for the weapon sprite's position relative to player
 #[derive(Component, Clone, Copy, Default)]
 pub struct CursorPosition {
@@ -58,10 +238,11 @@ pub struct CursorPosition {
 
 
 fn get_facing_direction(input: &BoxInput) -> FacingDirection {
+    let aim = input.aim_offset();
 
-    if input.pan_x > PAN_FACING_THRESHOLD {
+    if aim.x > PAN_FACING_THRESHOLD {
         FacingDirection::Right
-    } else if input.pan_x < -PAN_FACING_THRESHOLD {
+    } else if aim.x < -PAN_FACING_THRESHOLD {
         FacingDirection::Left
     } else {
         if input.buttons & INPUT_RIGHT != 0 {
@@ -74,100 +255,161 @@ fn get_facing_direction(input: &BoxInput) -> FacingDirection {
     }
 }
 
+// Offset from the player to what is under the mouse pointer in world space, clamped to
+// what the input carry, only meaningful locally. From the position and the zoom of the
+// game camera, the same spot of the world is the same aim in every camera mode
+pub fn local_pointer_offset(
+    q_window: &Query<&Window, With<PrimaryWindow>>,
+    q_camera: &Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    pointer_lock: &PointerLock,
+    transform: &Transform,
+) -> Option<Vec2> {
+    let window = q_window.get_single().ok()?;
+    let (camera_transform, projection) = q_camera.get_single().ok()?;
+    let cursor_position = pointer_position(window, pointer_lock)?;
+
+    Some(pointer_aim(&camera_view(window, camera_transform, projection), cursor_position, transform.translation.truncate()))
+}
+
+// Ask the input source of each local handle for its input of this frame
 pub fn read_local_inputs(
     mut commands: Commands,
-    players: Query<(&ActionState<PlayerAction>, &Transform, &Player), With<LocalPlayer>>,
-    
+    mut sources: ResMut<LocalInputSources>,
+    mut weapon_wheel: ResMut<WeaponWheelState>,
+    players: Query<(&Transform, &Player, Option<&ActionState<PlayerAction>>, Has<LocalPlayer>)>,
+    enemies: Query<&Transform, With<Enemy>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    touch: Res<TouchControls>,
+    aim_assist: Res<AimAssistSettings>,
+    focus: Res<WindowFocus>,
+    focus_settings: Res<FocusSettings>,
+    drop_in: Res<DropInRequest>,
+    compression: Res<InputCompression>,
+    mut compression_stats: ResMut<InputCompressionStats>,
+
     q_window: Query<&Window, With<PrimaryWindow>>,
-    q_camera: Query<(&Camera, &GlobalTransform)>,
+    q_camera: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    pointer_lock: Res<PointerLock>,
 ) {
-
     let mut local_inputs = HashMap::new();
 
-    for (action_state, transform, player) in players.iter() {
-        let mut input = BoxInput::default();
-
-         if action_state.pressed(&PlayerAction::MoveUp) {
-            input.buttons |= INPUT_UP;
-         }
-         if action_state.pressed(&PlayerAction::MoveDown) {
-            input.buttons |= INPUT_DOWN;
-         }
-         if action_state.pressed(&PlayerAction::MoveLeft) {
-            input.buttons |= INPUT_LEFT;
-         }
-         if action_state.pressed(&PlayerAction::MoveRight) {
-            input.buttons |= INPUT_RIGHT;
-         }
-
-         if action_state.pressed(&PlayerAction::PointerClick) {
-            input.fire = true;
-         }
-
-         if action_state.pressed(&PlayerAction::SwitchWeapon) {
-            input.switch_weapon = true;
-         }
-         if action_state.pressed(&PlayerAction::SwitchWeaponMode) {
-            input.buttons |= INPUT_SWITCH_WEAPON_MODE;
-         }
-
-         if action_state.pressed(&PlayerAction::Reload) {
-            input.buttons |= INPUT_RELOAD;
-         }
-
-         if action_state.pressed(&PlayerAction::Sprint) {
-            input.buttons |= INPUT_SPRINT;
-         }
-
-        if action_state.pressed(&PlayerAction::Dash) {
-            input.buttons |= INPUT_DASH;
+    let enemies: Vec<Vec2> = enemies.iter().map(|t| t.translation.truncate()).collect();
+    let gamepads: Vec<(Entity, &Gamepad)> = gamepads.iter().collect();
+    let wheel_action = weapon_wheel.take_pending();
+
+    for (handle, source) in sources.sources.iter_mut() {
+        let player = players.iter().find(|(_, player, ..)| player.handle == *handle);
+
+        let context = InputContext {
+            action_state: player.and_then(|(_, _, action_state, _)| action_state),
+            player_position: player.map(|(transform, ..)| transform.translation.truncate()),
+            pointer_offset: player.and_then(|(transform, ..)| local_pointer_offset(&q_window, &q_camera, &pointer_lock, transform)),
+            gamepads: &gamepads,
+            enemies: &enemies,
+            // The wheel and the touch controls belong to the player controlled by the keyboard
+            weapon_wheel: wheel_action.filter(|_| player.map_or(false, |(.., is_local)| is_local)),
+            touch: Some(touch.as_ref()).filter(|_| player.map_or(false, |(.., is_local)| is_local)),
+            aim_assist: &aim_assist,
+            focus: focus.input(&focus_settings),
+        };
+
+        let mut input = source.read(&context);
+        // A drop-in place has no player to control, only the request to join
+        if player.is_none() && drop_in.0 {
+            input.buttons |= INPUT_JOIN;
         }
-
-        if action_state.pressed(&PlayerAction::Modifier) {
-            input.buttons |= INPUT_MODIFIER;
+        if source.compressible() {
+            compression_stats.compress(*handle, &mut input, &compression);
         }
+        local_inputs.insert(*handle, input);
+    }
 
+    commands.insert_resource(LocalInputs::<PeerConfig>(local_inputs));
+}
 
-        if let Ok(window) = q_window.get_single() {
-            if let Ok((camera, camera_transform)) = q_camera.get_single() {
-                if let Some(cursor_position) = window.cursor_position() {
-                    if let Ok(world_position) = camera.viewport_to_world_2d(camera_transform, cursor_position) {
-                        let player_position = transform.translation.truncate();
-                        let pointer_distance = world_position - player_position;
-
-                        input.pan_x = (pointer_distance.x).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-                        input.pan_y = (pointer_distance.y).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-                    }
-                }
-            }
-        }
-
-        local_inputs.insert(player.handle, input);
+// Let the sources remember taps happening between two ggrs frames
+pub fn buffer_local_inputs(
+    mut sources: ResMut<LocalInputSources>,
+    players: Query<(&Player, Option<&ActionState<PlayerAction>>)>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    aim_assist: Res<AimAssistSettings>,
+    focus: Res<WindowFocus>,
+    focus_settings: Res<FocusSettings>,
+) {
+    let gamepads: Vec<(Entity, &Gamepad)> = gamepads.iter().collect();
+
+    for (handle, source) in sources.sources.iter_mut() {
+        let context = InputContext {
+            action_state: players.iter().find(|(player, _)| player.handle == *handle).and_then(|(_, action_state)| action_state),
+            player_position: None,
+            pointer_offset: None,
+            gamepads: &gamepads,
+            enemies: &[],
+            weapon_wheel: None,
+            touch: None,
+            aim_assist: &aim_assist,
+            focus: focus.input(&focus_settings),
+        };
+
+        source.buffer(&context);
     }
+}
 
-    commands.insert_resource(LocalInputs::<PeerConfig>(local_inputs));
+// Must run after every system reading the edges of the inputs
+pub fn rollback_store_previous_inputs(
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    mut query: Query<(&Player, &mut PreviousInput), With<Rollback>>,
+) {
+    for (player, mut previous) in query.iter_mut() {
+        let (input, _input_status) = inputs[player.handle];
+        previous.0 = input;
+    }
 }
 
 pub fn apply_inputs(
     mut commands: Commands,
     inputs: Res<PlayerInputs<PeerConfig>>,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
     character_configs: Res<Assets<CharacterConfig>>,
-    mut query: Query<(Entity, &WeaponInventory, &mut Transform, &mut DashState, &mut Velocity, &mut ActiveLayers, &mut FacingDirection, &mut CursorPosition, &mut SprintState, &CharacterConfigHandles, &Player), With<Rollback>>,
+    mut query: Query<(Entity, &WeaponInventory, &mut Transform, &mut DashState, &mut Velocity, &mut ActiveLayers, &mut FacingDirection, &mut FacingDirection8, &mut CursorPosition, &mut SprintState, &CharacterConfigHandles, &Player, &PreviousInput, (Option<&Stunned>, Option<&mut Grabbed>, Option<&SpeedBoost>, Option<&Knockback>)), With<Rollback>>,
 ) {
-    for (entity, inventory, mut transform, mut dash_state, mut velocity, mut active_layers, mut facing_direction, mut cursor_position, mut sprint_state, config_handles, player) in query.iter_mut() {
+    for (entity, inventory, mut transform, mut dash_state, mut velocity, mut active_layers, mut facing_direction, mut facing_direction_8, mut cursor_position, mut sprint_state, config_handles, player, previous_input, (opt_stunned, opt_grabbed, opt_boost, opt_knockback)) in query.iter_mut() {
         if let Some(config) = character_configs.get(&config_handles.config) {
             let (input, _input_status) = inputs[player.handle];
-            
-            dash_state.update();
+
+            // Stunned player can't move, dash or aim
+            if opt_stunned.map_or(false, |stunned| stunned.is_active(frame.frame)) {
+                velocity.0 = Vec2::ZERO;
+                sprint_state.is_sprinting = false;
+                continue;
+            }
+
+            // Grabbed player can't move or dash, every dash press loosen the grab
+            if let Some(mut grabbed) = opt_grabbed {
+                if grabbed.is_active(frame.frame) {
+                    if previous_input.just_pressed(&input, INPUT_DASH) {
+                        grabbed.mashes_left = grabbed.mashes_left.saturating_sub(1);
+                        if grabbed.mashes_left == 0 {
+                            commands.entity(entity).remove::<Grabbed>();
+                        }
+                    }
+                    velocity.0 = Vec2::ZERO;
+                    sprint_state.is_sprinting = false;
+                    continue;
+                }
+            }
+
+            // Pushed player can't move or dash until the push is over
+            if let Some(knockback) = opt_knockback.filter(|knockback| knockback.is_active(frame.frame)) {
+                velocity.0 = knockback.velocity;
+                sprint_state.is_sprinting = false;
+                continue;
+            }
             
             // If currently dashing, directly update position
-            if dash_state.is_dashing {
-                // Calculate position based on remaining frames and distance
-                let completed_fraction = 1.0 - (dash_state.dash_frames_remaining as f32 / 
-                                              (config.movement.dash_duration_frames as f32));
-                
-                let dash_offset = dash_state.dash_direction * dash_state.dash_total_distance * completed_fraction;
-                transform.translation = dash_state.dash_start_position + Vec3::new(dash_offset.x, dash_offset.y, 0.0);
+            if let Some(position) = dash_state.advance(frame.frame) {
+                transform.translation = position;
                 
                 // Zero out velocity while dashing to prevent normal movement physics
                 velocity.0 = Vec2::ZERO;
@@ -175,9 +417,9 @@ pub fn apply_inputs(
             }
             
             // Check if player is trying to dash
-            if (input.buttons & INPUT_DASH != 0) && dash_state.can_dash() {
+            if previous_input.just_pressed(&input, INPUT_DASH) && dash_state.can_dash(frame.frame) {
                 // Get looking direction for dash
-                let look_direction = Vec2::new(input.pan_x as f32, input.pan_y as f32);
+                let look_direction = input.aim_offset().as_vec2();
 
                 let is_reverse_dash = (input.buttons & INPUT_MODIFIER) != 0;
                 
@@ -196,10 +438,11 @@ pub fn apply_inputs(
                 dash_state.start_dash(
                     dash_direction, 
                     transform.translation, 
-                    config.movement.dash_distance,
-                    config.movement.dash_duration_frames
+                    config.dash_distance(),
+                    frame.frame,
+                    simulation.frames(config.movement.dash_duration_frames)
                 );
-                dash_state.set_cooldown(config.movement.dash_cooldown_frames);
+                dash_state.set_cooldown(frame.frame, simulation.frames(config.movement.dash_cooldown_frames));
                 
                 // Zero out velocity to prevent normal movement physics
                 velocity.0 = Vec2::ZERO;
@@ -224,14 +467,19 @@ pub fn apply_inputs(
             if input.buttons & INPUT_RIGHT != 0 { direction.x += 1.0; }
 
             *facing_direction = get_facing_direction(&input);
+            let aim = input.aim_offset();
+            if let Some(direction_8) = FacingDirection8::from_aim(aim.x, aim.y) {
+                *facing_direction_8 = direction_8;
+            }
 
-            cursor_position.x = input.pan_x as i32;
-            cursor_position.y = input.pan_y as i32;
+            cursor_position.x = aim.x;
+            cursor_position.y = aim.y;
 
             if direction != Vec2::ZERO {
-                let sprint_multiplier = 1.0 + (config.movement.sprint_multiplier - 1.0) * sprint_state.sprint_factor;
-                // Using FIXED_TIMESTEP instead of time.delta()
-                let move_delta = direction.normalize() * config.movement.acceleration * sprint_multiplier * FIXED_TIMESTEP;
+                let boost = opt_boost.map_or(1.0, |boost| boost.multiplier(frame.frame));
+                let sprint_multiplier = (1.0 + (config.movement.sprint_multiplier - 1.0) * sprint_state.sprint_factor) * boost;
+                // Using the simulation timestep instead of time.delta()
+                let move_delta = direction.normalize() * config.movement.acceleration * sprint_multiplier * simulation.timestep();
                 velocity.0 += move_delta;
                 
                 let max_speed = config.movement.max_speed * sprint_multiplier;
@@ -243,6 +491,7 @@ pub fn apply_inputs(
 
 pub fn apply_friction(
     inputs: Res<PlayerInputs<PeerConfig>>,
+    simulation: Res<SimulationConfig>,
     movement_configs: Res<Assets<CharacterConfig>>,
     mut query: Query<(&mut Velocity, &CharacterConfigHandles, &Player), With<Rollback>>,
 ) {
@@ -253,7 +502,7 @@ pub fn apply_friction(
             let moving = input.buttons & INPUT_RIGHT != 0 || input.buttons & INPUT_LEFT != 0 || input.buttons & INPUT_UP != 0 || input.buttons & INPUT_DOWN != 0;
 
             if !moving && velocity.length_squared() > 0.1 {
-                velocity.0 *= (1.0 - config.movement.friction * FIXED_TIMESTEP).max(0.0);
+                velocity.0 *= (1.0 - config.movement.friction * simulation.timestep()).max(0.0);
                 if velocity.length_squared() < 1.0 {
                     velocity.0 = Vec2::ZERO;
                 }
@@ -266,11 +515,13 @@ pub fn move_characters(
     mut query: Query<(&mut Transform, &mut Velocity, &Collider, &CollisionLayer), (With<Rollback>, With<Player>)>,
     settings: Res<CollisionSettings>,
     collider_query: Query<(Entity, &Transform, &Collider, &CollisionLayer), (With<Wall>, Without<Player>)>,
+    simulation: Res<SimulationConfig>,
 ) {
+    let timestep = simulation.timestep();
     'mainloop: for (mut transform, mut velocity, player_collider, collision_layer) in query.iter_mut() {
         let mut new_transform = transform.clone();
-        new_transform.translation.x += velocity.x * FIXED_TIMESTEP;
-        new_transform.translation.y += velocity.y * FIXED_TIMESTEP;
+        new_transform.translation.x += velocity.x * timestep;
+        new_transform.translation.y += velocity.y * timestep;
         new_transform.translation = round_vec3(new_transform.translation);
 
         for (target_entity, target_transform, target_collider, target_layer) in collider_query.iter() {
@@ -286,10 +537,57 @@ pub fn move_characters(
     }
 }
 
-pub fn update_animation_state(mut query: Query<(&Velocity, &mut AnimationState), With<Rollback>>) {
-    for (velocity, mut state) in query.iter_mut() {
+pub fn update_animation_state(
+    frame: Res<FrameCount>,
+    mut query: Query<(&Velocity, &mut AnimationState, Option<&FlinchState>, Option<&WeaponInventory>, Has<Climbing>), With<Rollback>>,
+) {
+    for (velocity, mut state, opt_flinch, opt_inventory, climbing) in query.iter_mut() {
         let current_state_name = state.0.clone();
-        let new_state_name = if velocity.length_squared() > 0.5 { "Run" } else { "Idle" };
+        let new_state_name = if climbing {
+            CLIMB_ANIMATION
+        } else if opt_flinch.map_or(false, |flinch| flinch.is_staggered(frame.frame)) {
+            HIT_ANIMATION
+        } else if opt_inventory.map_or(false, |inventory| inventory.is_reloading()) {
+            RELOAD_ANIMATION
+        } else if velocity.length_squared() > 0.5 {
+            "Run"
+        } else {
+            "Idle"
+        };
         if current_state_name != new_state_name { state.0 = new_state_name.to_string(); }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_move_round_trip() {
+        let mut input = BoxInput::default();
+        input.set_slot_move(2, 0);
+        assert_eq!(input.slot_move(), Some((2, 0)));
+
+        // The last slot fill the 3 bits of both halves
+        input.set_slot_move(MAX_SELECTABLE_SLOTS - 1, MAX_SELECTABLE_SLOTS - 2);
+        assert_eq!(input.slot_move(), Some((MAX_SELECTABLE_SLOTS - 1, MAX_SELECTABLE_SLOTS - 2)));
+    }
+
+    #[test]
+    fn test_slot_move_out_of_range_is_ignored() {
+        let mut input = BoxInput::default();
+        assert_eq!(input.slot_move(), None);
+        input.set_slot_move(MAX_SELECTABLE_SLOTS, 0);
+        assert_eq!(input.slot_move(), None);
+    }
+
+    #[test]
+    fn test_slot_move_survive_the_packing() {
+        let mut input = BoxInput { buttons: INPUT_UP | INPUT_RELOAD, aim: 1234, fire: true, switch_weapon: true, slot_move: 0 };
+        for (from, to) in [(0, 1), (6, 5), (3, 0)] {
+            input.set_slot_move(from, to);
+            let unpacked = BoxInput::from(PackedInput::from(input));
+            assert_eq!(unpacked, input);
+            assert_eq!(unpacked.slot_move(), Some((from, to)));
+        }
+    }
+}
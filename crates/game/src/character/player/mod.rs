@@ -2,6 +2,8 @@ pub mod control;
 pub mod jjrs;
 pub mod input;
 pub mod create;
+pub mod aim;
+pub mod source;
 
 use bevy::prelude::*;
 use ggrs::PlayerHandle;
@@ -0,0 +1,468 @@
+use std::collections::BTreeMap;
+
+use bevy::{input::gamepad::{Gamepad, GamepadButton}, prelude::*};
+use ggrs::PlayerHandle;
+use leafwing_input_manager::prelude::ActionState;
+use thiserror::Error;
+use utils::aim::{encode_aim, AIM_MAX_DISTANCE};
+
+use crate::{weapons::wheel::WheelAction, web::{focus::FocusInput, touch::TouchControls}};
+
+use super::{aim::{assist_aim, AimAssistSettings}, control::PlayerAction, input::{BoxInput, INPUT_ABILITY, INPUT_DASH, INPUT_DOWN, INPUT_INTERACTION, INPUT_LEFT, INPUT_MODIFIER, INPUT_RELOAD, INPUT_RIGHT, INPUT_SPRINT, INPUT_SWITCH_WEAPON_MODE, INPUT_UP}};
+
+// Everything a source can look at to produce the input of its handle, only local state
+pub struct InputContext<'a> {
+    pub action_state: Option<&'a ActionState<PlayerAction>>,
+    pub player_position: Option<Vec2>,
+    pub pointer_offset: Option<Vec2>,
+    pub gamepads: &'a [(Entity, &'a Gamepad)],
+    pub enemies: &'a [Vec2],
+    pub weapon_wheel: Option<WheelAction>,
+    pub touch: Option<&'a TouchControls>,
+    pub aim_assist: &'a AimAssistSettings,
+    // Only the keyboard and the mouse depend on the focus of the window
+    pub focus: FocusInput,
+}
+
+// Produce the input of one local ggrs handle
+pub trait InputSource: Send + Sync + 'static {
+    // Called every render frame, for the sources that need to remember taps
+    // happening between two ggrs frames
+    fn buffer(&mut self, _context: &InputContext) {}
+
+    // Called once per ggrs frame
+    fn read(&mut self, context: &InputContext) -> BoxInput;
+
+    // The aim can be held inside the deadband of network::compression, a replay
+    // must send exactly what was recorded
+    fn compressible(&self) -> bool {
+        true
+    }
+}
+
+// Input source of each local handle, sorted by handle
+#[derive(Resource, Default)]
+pub struct LocalInputSources {
+    pub sources: BTreeMap<PlayerHandle, Box<dyn InputSource>>,
+}
+
+impl LocalInputSources {
+    pub fn insert(&mut self, handle: PlayerHandle, source: Box<dyn InputSource>) {
+        self.sources.insert(handle, source);
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InputSourceError {
+    #[error("invalid gamepad `{0}`, expected gamepad:<n>")]
+    InvalidGamepad(String),
+    #[error("failed to load the replay `{path}`: {reason}")]
+    Replay { path: String, reason: String },
+}
+
+// Create the source from the player entry of the session configuration,
+// return None for a remote player
+//   localhost       -> keyboard and mouse
+//   gamepad:<n>     -> n-th connected gamepad
+//   bot             -> shoot at the closest enemy
+//   replay:<path>   -> inputs from a json file
+// A local entry that can't be used is an error, the player would stand still
+pub fn input_source_from_config(entry: &str) -> Result<Option<Box<dyn InputSource>>, InputSourceError> {
+    if entry == "localhost" {
+        return Ok(Some(Box::new(KeyboardMouseSource::default())));
+    }
+    if entry == "bot" {
+        return Ok(Some(Box::new(BotSource::default())));
+    }
+    if let Some(index) = entry.strip_prefix("gamepad:") {
+        let index = index.parse().map_err(|_| InputSourceError::InvalidGamepad(index.to_string()))?;
+        return Ok(Some(Box::new(GamepadSource::new(index))));
+    }
+    if let Some(path) = entry.strip_prefix("replay:") {
+        return Ok(Some(Box::new(ReplaySource::from_file(path)?)));
+    }
+    Ok(None)
+}
+
+// Taps between two ggrs frames, kept until the next read so a press shorter than a frame is not lost
+#[derive(Default, Debug, Clone, Copy)]
+struct TapBuffer {
+    buttons: u16,
+    switch_weapon: bool,
+}
+
+impl TapBuffer {
+    fn flush(&mut self, input: &mut BoxInput) {
+        input.buttons |= self.buttons;
+        input.switch_weapon |= self.switch_weapon;
+        *self = TapBuffer::default();
+    }
+}
+
+
+#[derive(Default)]
+pub struct KeyboardMouseSource {
+    taps: TapBuffer,
+    // Last input read with the focus
+    last: BoxInput,
+}
+
+impl InputSource for KeyboardMouseSource {
+    fn buffer(&mut self, context: &InputContext) {
+        let Some(action_state) = context.action_state else {
+            return;
+        };
+        if matches!(context.focus, FocusInput::Unfocused(_)) {
+            return;
+        }
+
+        if action_state.just_pressed(&PlayerAction::SwitchWeaponMode) {
+            self.taps.buttons |= INPUT_SWITCH_WEAPON_MODE;
+        }
+        if action_state.just_pressed(&PlayerAction::Reload) {
+            self.taps.buttons |= INPUT_RELOAD;
+        }
+        if action_state.just_pressed(&PlayerAction::Dash) {
+            self.taps.buttons |= INPUT_DASH;
+        }
+        if action_state.just_pressed(&PlayerAction::Interaction) {
+            self.taps.buttons |= INPUT_INTERACTION;
+        }
+        if action_state.just_pressed(&PlayerAction::Ability) {
+            self.taps.buttons |= INPUT_ABILITY;
+        }
+    }
+
+    fn read(&mut self, context: &InputContext) -> BoxInput {
+        let mut input = BoxInput::default();
+
+        let Some(action_state) = context.action_state else {
+            return input;
+        };
+
+        // The keys held when the focus was lost are never released
+        if let FocusInput::Unfocused(policy) = context.focus {
+            self.taps = TapBuffer::default();
+            return policy.apply(&self.last);
+        }
+
+        let bindings = [
+            (PlayerAction::MoveUp, INPUT_UP),
+            (PlayerAction::MoveDown, INPUT_DOWN),
+            (PlayerAction::MoveLeft, INPUT_LEFT),
+            (PlayerAction::MoveRight, INPUT_RIGHT),
+            (PlayerAction::SwitchWeaponMode, INPUT_SWITCH_WEAPON_MODE),
+            (PlayerAction::Reload, INPUT_RELOAD),
+            (PlayerAction::Sprint, INPUT_SPRINT),
+            (PlayerAction::Dash, INPUT_DASH),
+            (PlayerAction::Modifier, INPUT_MODIFIER),
+            (PlayerAction::Interaction, INPUT_INTERACTION),
+            (PlayerAction::Ability, INPUT_ABILITY),
+        ];
+        for (action, button) in bindings {
+            if action_state.pressed(&action) {
+                input.buttons |= button;
+            }
+        }
+
+        input.fire = action_state.pressed(&PlayerAction::PointerClick);
+
+        // The switch key is handled by the weapon wheel, a tap cycle and a hold select a slot
+        match context.weapon_wheel {
+            Some(WheelAction::Cycle) => input.switch_weapon = true,
+            Some(WheelAction::Select(slot)) => input.set_selected_slot(slot),
+            Some(WheelAction::Move { from, to }) => input.set_slot_move(from, to),
+            None => {}
+        }
+
+        // Taps released before this frame was read
+        self.taps.flush(&mut input);
+
+        // The pointer moved while the window was unfocused
+        if context.focus == FocusInput::Settling {
+            input.aim = self.last.aim;
+        } else if let Some(pointer_offset) = context.pointer_offset {
+            input.aim = encode_aim(pointer_offset);
+        }
+
+        // The touch controls add to the keyboard, the stick aim while it's held
+        if let Some(touch) = context.touch.filter(|touch| touch.active) {
+            input.buttons |= stick_buttons(touch.stick);
+            input.fire |= touch.fire;
+            if touch.in_use() && touch.aim != Vec2::ZERO {
+                input.aim = encode_aim(touch.aim * AIM_MAX_DISTANCE as f32);
+            }
+        }
+
+        self.last = input;
+        input
+    }
+}
+
+
+const STICK_THRESHOLD: f32 = 0.5;
+
+// Direction buttons of an analog stick, y up
+fn stick_buttons(stick: Vec2) -> u16 {
+    let mut buttons = 0;
+    if stick.y > STICK_THRESHOLD { buttons |= INPUT_UP; }
+    if stick.y < -STICK_THRESHOLD { buttons |= INPUT_DOWN; }
+    if stick.x < -STICK_THRESHOLD { buttons |= INPUT_LEFT; }
+    if stick.x > STICK_THRESHOLD { buttons |= INPUT_RIGHT; }
+    buttons
+}
+
+pub struct GamepadSource {
+    // Index in the connected gamepads, sorted by entity
+    pub index: usize,
+    taps: TapBuffer,
+    // Direction sent the last frame, for the slowdown of the aim assist
+    last_aim: Option<Vec2>,
+}
+
+impl GamepadSource {
+    pub fn new(index: usize) -> Self {
+        Self { index, taps: TapBuffer::default(), last_aim: None }
+    }
+
+    fn gamepad<'a>(&self, context: &'a InputContext) -> Option<&'a Gamepad> {
+        let mut gamepads: Vec<&(Entity, &Gamepad)> = context.gamepads.iter().collect();
+        gamepads.sort_by_key(|(entity, _)| *entity);
+        gamepads.get(self.index).map(|(_, gamepad)| *gamepad)
+    }
+}
+
+const GAMEPAD_BINDINGS: [(GamepadButton, u16); 8] = [
+    (GamepadButton::DPadUp, INPUT_UP),
+    (GamepadButton::DPadDown, INPUT_DOWN),
+    (GamepadButton::DPadLeft, INPUT_LEFT),
+    (GamepadButton::DPadRight, INPUT_RIGHT),
+    (GamepadButton::North, INPUT_INTERACTION),
+    (GamepadButton::West, INPUT_RELOAD),
+    (GamepadButton::South, INPUT_DASH),
+    (GamepadButton::East, INPUT_ABILITY),
+];
+
+impl InputSource for GamepadSource {
+    fn buffer(&mut self, context: &InputContext) {
+        let Some(gamepad) = self.gamepad(context) else {
+            return;
+        };
+
+        for (button, bit) in GAMEPAD_BINDINGS {
+            if gamepad.just_pressed(button) {
+                self.taps.buttons |= bit;
+            }
+        }
+        if gamepad.just_pressed(GamepadButton::RightTrigger) {
+            self.taps.switch_weapon = true;
+        }
+    }
+
+    fn read(&mut self, context: &InputContext) -> BoxInput {
+        let mut input = BoxInput::default();
+
+        let Some(gamepad) = self.gamepad(context) else {
+            return input;
+        };
+
+        for (button, bit) in GAMEPAD_BINDINGS {
+            if gamepad.pressed(button) {
+                input.buttons |= bit;
+            }
+        }
+
+        input.buttons |= stick_buttons(gamepad.left_stick());
+
+        if gamepad.pressed(GamepadButton::LeftThumb) {
+            input.buttons |= INPUT_SPRINT;
+        }
+        if gamepad.pressed(GamepadButton::Select) {
+            input.buttons |= INPUT_SWITCH_WEAPON_MODE;
+        }
+        if gamepad.pressed(GamepadButton::LeftTrigger) {
+            input.buttons |= INPUT_MODIFIER;
+        }
+
+        input.fire = gamepad.pressed(GamepadButton::RightTrigger2);
+        input.switch_weapon = gamepad.pressed(GamepadButton::RightTrigger);
+
+        self.taps.flush(&mut input);
+
+        let right = gamepad.right_stick();
+        if right.length() > STICK_THRESHOLD {
+            let direction = match context.player_position {
+                Some(position) => assist_aim(context.aim_assist, position, right.normalize(), self.last_aim, context.enemies),
+                None => right.normalize(),
+            };
+            input.aim = encode_aim(direction * AIM_MAX_DISTANCE as f32);
+            self.last_aim = Some(direction);
+        } else {
+            self.last_aim = None;
+        }
+
+        input
+    }
+}
+
+
+// Simple local bot, stand still and shoot the closest enemy in range
+pub struct BotSource {
+    pub range: f32,
+}
+
+impl Default for BotSource {
+    fn default() -> Self {
+        Self { range: 400.0 }
+    }
+}
+
+impl InputSource for BotSource {
+    fn read(&mut self, context: &InputContext) -> BoxInput {
+        let mut input = BoxInput::default();
+
+        let Some(position) = context.player_position else {
+            return input;
+        };
+
+        let target = context.enemies.iter()
+            .map(|enemy| *enemy - position)
+            .filter(|offset| offset.length() <= self.range)
+            .min_by(|a, b| a.length().partial_cmp(&b.length()).unwrap_or(std::cmp::Ordering::Equal));
+
+        if let Some(offset) = target {
+            input.aim = encode_aim(offset);
+            input.fire = true;
+        }
+
+        input
+    }
+}
+
+
+// Play back inputs recorded in a json file, one entry per frame, idle when over
+#[derive(Default)]
+pub struct ReplaySource {
+    pub inputs: Vec<BoxInput>,
+    pub cursor: usize,
+}
+
+impl ReplaySource {
+    pub fn from_file(path: &str) -> Result<Self, InputSourceError> {
+        let error = |reason: String| InputSourceError::Replay { path: path.to_string(), reason };
+        let content = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let inputs: Vec<BoxInput> = serde_json::from_str(&content).map_err(|e| error(e.to_string()))?;
+        Ok(Self { inputs, cursor: 0 })
+    }
+}
+
+impl InputSource for ReplaySource {
+    fn read(&mut self, _context: &InputContext) -> BoxInput {
+        let input = self.inputs.get(self.cursor).copied().unwrap_or_default();
+        self.cursor += 1;
+        input
+    }
+
+    fn compressible(&self) -> bool {
+        false
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(source: &mut dyn InputSource, player_position: Option<Vec2>, enemies: &[Vec2]) -> BoxInput {
+        let aim_assist = AimAssistSettings::default();
+        let context = InputContext {
+            action_state: None,
+            player_position,
+            pointer_offset: None,
+            gamepads: &[],
+            enemies,
+            weapon_wheel: None,
+            touch: None,
+            aim_assist: &aim_assist,
+            focus: FocusInput::Focused,
+        };
+        source.read(&context)
+    }
+
+    fn replay_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("zrl_replay_{}_{}.json", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_bot_shoot_the_closest_enemy_in_range() {
+        let mut bot = BotSource::default();
+        let input = read(&mut bot, Some(Vec2::new(100.0, 0.0)), &[Vec2::new(400.0, 0.0), Vec2::new(100.0, 200.0), Vec2::new(1000.0, 0.0)]);
+        assert!(input.fire);
+        assert_eq!(input.aim, encode_aim(Vec2::new(0.0, 200.0)));
+    }
+
+    #[test]
+    fn test_bot_hold_fire_without_target() {
+        let mut bot = BotSource::default();
+        // Out of range, then no player to shoot from
+        assert_eq!(read(&mut bot, Some(Vec2::ZERO), &[Vec2::new(500.0, 0.0)]), BoxInput::default());
+        assert_eq!(read(&mut bot, None, &[Vec2::new(10.0, 0.0)]), BoxInput::default());
+    }
+
+    #[test]
+    fn test_replay_play_the_inputs_then_idle() {
+        let first = BoxInput { buttons: INPUT_UP, fire: true, ..default() };
+        let second = BoxInput { buttons: INPUT_RELOAD, aim: encode_aim(Vec2::new(50.0, 0.0)), ..default() };
+        let mut replay = ReplaySource { inputs: vec![first, second], cursor: 0 };
+
+        assert_eq!(read(&mut replay, None, &[]), first);
+        assert_eq!(read(&mut replay, None, &[]), second);
+        assert_eq!(read(&mut replay, None, &[]), BoxInput::default());
+        assert!(!replay.compressible());
+    }
+
+    #[test]
+    fn test_replay_from_file() {
+        let input = BoxInput { buttons: INPUT_DASH, ..default() };
+        let path = replay_file("valid", &serde_json::to_string(&vec![input]).unwrap());
+        let replay = ReplaySource::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.inputs, vec![input]);
+    }
+
+    #[test]
+    fn test_tap_buffer_flush_once() {
+        let mut taps = TapBuffer { buttons: INPUT_RELOAD | INPUT_DASH, switch_weapon: true };
+        let mut input = BoxInput { buttons: INPUT_UP, ..default() };
+        taps.flush(&mut input);
+        assert_eq!(input.buttons, INPUT_UP | INPUT_RELOAD | INPUT_DASH);
+        assert!(input.switch_weapon);
+
+        // Emptied by the flush, the next frame get nothing
+        let mut next = BoxInput::default();
+        taps.flush(&mut next);
+        assert_eq!(next, BoxInput::default());
+    }
+
+    #[test]
+    fn test_source_from_config() {
+        assert!(matches!(input_source_from_config("localhost"), Ok(Some(_))));
+        assert!(matches!(input_source_from_config("bot"), Ok(Some(_))));
+        assert!(matches!(input_source_from_config("gamepad:1"), Ok(Some(_))));
+        // A remote peer
+        assert!(matches!(input_source_from_config("127.0.0.1:7000"), Ok(None)));
+    }
+
+    #[test]
+    fn test_unusable_source_is_an_error() {
+        assert_eq!(input_source_from_config("gamepad:first").err(), Some(InputSourceError::InvalidGamepad("first".to_string())));
+        assert!(matches!(input_source_from_config("replay:/does/not/exist.json"), Err(InputSourceError::Replay { .. })));
+
+        let path = replay_file("invalid", "not json");
+        let result = input_source_from_config(&format!("replay:{}", path));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(InputSourceError::Replay { .. })));
+    }
+}
@@ -0,0 +1,108 @@
+use bevy::{ecs::entity::{EntityMapper, MapEntities}, prelude::*};
+use bevy_ggrs::Rollback;
+use utils::{frame::FrameTimer, link::{EntityLink, EntityLinks}};
+
+use crate::frame::FrameCount;
+
+
+// Character can't move or fire until the timer is done
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Stunned {
+    pub timer: FrameTimer,
+}
+
+impl Stunned {
+    pub fn is_active(&self, current_frame: u32) -> bool {
+        self.timer.is_active(current_frame)
+    }
+}
+
+// Character pushed by a heavy hit, the push replace its movement until the timer is done
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Knockback {
+    pub velocity: Vec2,
+    pub timer: FrameTimer,
+}
+
+impl Knockback {
+    pub fn is_active(&self, current_frame: u32) -> bool {
+        self.timer.is_active(current_frame)
+    }
+}
+
+// Character held by an enemy, can't move or dash until the timer is done or
+// the dash key was pressed `mashes_left` more times
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Grabbed {
+    pub by: EntityLink,
+    pub timer: FrameTimer,
+    pub mashes_left: u32,
+}
+
+impl MapEntities for Grabbed {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.by.map_entities(entity_mapper);
+    }
+}
+
+// Released when the enemy holding it is gone
+impl EntityLinks for Grabbed {
+    fn has_dead_links(&self, is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+        !is_alive(&self.by)
+    }
+
+    fn prune(&mut self, _is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+        false
+    }
+}
+
+impl Grabbed {
+    pub fn is_active(&self, current_frame: u32) -> bool {
+        self.mashes_left > 0 && self.timer.is_active(current_frame)
+    }
+}
+
+// Stun the entity, if already stunned keep the one ending last
+pub fn apply_stun(
+    commands: &mut Commands,
+    entity: Entity,
+    opt_stunned: Option<Mut<Stunned>>,
+    current_frame: u32,
+    duration_frames: u32,
+) {
+    let timer = FrameTimer::new(current_frame, duration_frames);
+    if let Some(mut stunned) = opt_stunned {
+        if timer.end_frame() > stunned.timer.end_frame() {
+            stunned.timer = timer;
+        }
+    } else {
+        commands.entity(entity).insert(Stunned { timer });
+    }
+}
+
+pub fn rollback_clear_expired_status(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    query: Query<(Entity, &Stunned), With<Rollback>>,
+    grabbed_query: Query<(Entity, &Grabbed), With<Rollback>>,
+    knockback_query: Query<(Entity, &Knockback), With<Rollback>>,
+) {
+    for (entity, stunned) in query.iter() {
+        if !stunned.is_active(frame.frame) {
+            commands.entity(entity).remove::<Stunned>();
+        }
+    }
+    for (entity, grabbed) in grabbed_query.iter() {
+        if !grabbed.is_active(frame.frame) {
+            commands.entity(entity).remove::<Grabbed>();
+        }
+    }
+    for (entity, knockback) in knockback_query.iter() {
+        if !knockback.is_active(frame.frame) {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
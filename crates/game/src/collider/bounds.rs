@@ -0,0 +1,118 @@
+use bevy::{prelude::*, utils::HashSet};
+use bevy_ggrs::{ConfirmedFrameCount, Rollback};
+use utils::{events::RollbackEvents, world::is_exact_coordinate};
+
+use crate::{character::{enemy::Enemy, player::Player}, frame::{confirmed_frame, FrameCount}, weapons::Bullet};
+
+// Audit of the positions against the exact range of the world coordinates, see
+// `utils::world`. The simulation is not chunked, a map must fit in the range: past it
+// the rounding start moving things by itself. Nothing is moved here, what leave the
+// range is reported so a map too large is seen instead of silently corrupting positions.
+
+// Rollback so the positions of a resimulated frame are not reported twice
+#[derive(Clone, Copy, Debug)]
+pub struct WorldRangeEvent {
+    pub entity: Entity,
+    pub position: Vec2,
+}
+
+// Entities remembered as already logged, past it the next ones are only counted
+const MAX_REPORTED_ENTITIES: usize = 256;
+
+// Presentation side, what was already logged. Cleared at the end of the match.
+#[derive(Resource, Default)]
+pub struct WorldRangeAudit {
+    last_read_frame: Option<u32>,
+    reported: HashSet<Entity>,
+    // Events not logged because `reported` is full
+    pub dropped: u32,
+}
+
+impl WorldRangeAudit {
+    // True the first time the entity is seen, a full audit stop logging instead of growing
+    fn report(&mut self, entity: Entity) -> bool {
+        if self.reported.contains(&entity) {
+            return false;
+        }
+        if self.reported.len() >= MAX_REPORTED_ENTITIES {
+            self.dropped += 1;
+            return false;
+        }
+        self.reported.insert(entity)
+    }
+}
+
+fn in_world_range(position: Vec2) -> bool {
+    is_exact_coordinate(position.x) && is_exact_coordinate(position.y)
+}
+
+
+// SYSTEMS
+
+pub fn rollback_audit_world_range(
+    frame: Res<FrameCount>,
+    mut events: ResMut<RollbackEvents<WorldRangeEvent>>,
+    query: Query<(Entity, &Transform), (With<Rollback>, Or<(With<Player>, With<Enemy>, With<Bullet>)>)>,
+) {
+    let mut outside: Vec<_> = query.iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate()))
+        .filter(|(_, position)| !in_world_range(*position))
+        .collect();
+    outside.sort_by_key(|(entity, _)| entity.index());
+    for (entity, position) in outside {
+        events.send(frame.frame, WorldRangeEvent { entity, position });
+    }
+}
+
+// Logged once the frame is confirmed and once per entity, a rollback would warn again
+// for each resimulated frame
+pub fn log_confirmed_world_range(
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    events: Res<RollbackEvents<WorldRangeEvent>>,
+    mut audit: ResMut<WorldRangeAudit>,
+) {
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    if audit.last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+
+    let audit = audit.as_mut();
+    for (f, event) in events.read_after(audit.last_read_frame).filter(|(f, _)| *f <= confirmed_frame) {
+        if audit.report(event.entity) {
+            warn!("{} left the exact range of the world at {} on frame {}", event.entity, event.position, f);
+        }
+    }
+    audit.last_read_frame = Some(confirmed_frame);
+}
+
+pub fn clear_world_range_audit(mut audit: ResMut<WorldRangeAudit>) {
+    if audit.dropped > 0 {
+        warn!("{} more positions left the exact range of the world and were not logged", audit.dropped);
+    }
+    *audit = WorldRangeAudit::default();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_report_once_and_stop_growing() {
+        let mut audit = WorldRangeAudit::default();
+        assert!(audit.report(Entity::from_raw(1)));
+        assert!(!audit.report(Entity::from_raw(1)));
+
+        for index in 2..(MAX_REPORTED_ENTITIES as u32 + 10) {
+            audit.report(Entity::from_raw(index));
+        }
+        assert_eq!(audit.reported.len(), MAX_REPORTED_ENTITIES);
+        assert_eq!(audit.dropped, 9);
+        // Already reported ones are not counted as dropped
+        assert!(!audit.report(Entity::from_raw(1)));
+        assert_eq!(audit.dropped, 9);
+    }
+}
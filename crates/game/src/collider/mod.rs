@@ -1,7 +1,11 @@
+pub mod bounds;
+pub mod preset;
+pub mod spatial;
+
 use bevy::prelude::*;
 use bevy_ggrs::AddRollbackCommandExtension;
 use serde::{Deserialize, Serialize};
-use utils::math::round_vec2;
+use utils::{contact::{aabb_aabb, aabb_circle, circle_aabb, circle_circle, Contact}, math::round_vec2, sweep::{segment_aabb_hit, segment_circle_hit, SweepHit}};
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -31,6 +35,56 @@ pub struct Collider {
 }
 
 
+// Part of a character that can be hit, each with its own shape and damage multiplier
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum HitZoneKind {
+    Body,
+    Head,
+}
+
+// A zone in the `hit_zones` of a character config, the damage of a bullet landing in it
+// is multiplied, a head at 2.0 take double damage
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HitZoneConfig {
+    pub zone: HitZoneKind,
+    pub shape: ColliderShape,
+    // From the transform of the character, like the offset of the collider
+    pub offset: (f32, f32),
+    pub damage_multiplier: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct HitZone {
+    pub zone: HitZoneKind,
+    pub collider: Collider,
+    pub damage_multiplier: f32,
+}
+
+impl From<&HitZoneConfig> for HitZone {
+    fn from(config: &HitZoneConfig) -> Self {
+        Self {
+            zone: config.zone,
+            collider: Collider { shape: config.shape.clone(), offset: Vec2::new(config.offset.0, config.offset.1) },
+            damage_multiplier: config.damage_multiplier,
+        }
+    }
+}
+
+// Shapes used instead of the Collider for the bullets, the Collider is still
+// used for the movement. The zones should not overlap.
+#[derive(Component, Clone, Debug)]
+pub struct HitZones(pub Vec<HitZone>);
+
+impl HitZones {
+    // First zone hit along the segment, on the same time of impact the zone listed first win
+    pub fn sweep(&self, start: Vec2, end: Vec2, collider_a: &Collider, transform_b: &Transform) -> Option<(u32, &HitZone)> {
+        self.0.iter()
+            .filter_map(|zone| sweep_collision(start, end, collider_a, transform_b, &zone.collider).map(|toi| (toi, zone)))
+            .min_by_key(|(toi, _)| *toi)
+    }
+}
+
+
 #[derive(Component, Clone)]
 pub struct Wall;
 
@@ -39,43 +93,59 @@ pub struct Wall;
 pub struct CollisionLayer(pub usize);
 
 
-#[derive(Resource)]
+#[derive(Resource, Clone, Debug)]
 pub struct CollisionSettings {
     pub enemy_layer: usize,
     pub environment_layer: usize,
     pub player_layer: usize,
     pub wall_layer: usize,
+    pub objective_layer: usize,
     pub layer_matrix: [[bool; 8]; 8], // Collision matrix for which layers collide
+    // Name of the preset the matrix come from, see `preset::CollisionPresets`
+    pub preset: String,
 }
 
+// The layers are fixed, the matrix is the coop one until the preset of the rules is
+// applied, and stay it when the preset can't be used so the collisions never go away
 impl Default for CollisionSettings {
     fn default() -> Self {
-        // Initialize empty collision matrix
-        let mut layer_matrix = [[false; 8]; 8];
-        
-        // Define collision layers
-        let enemy_layer = 1;
-        let environment_layer = 2;
-        let player_layer = 3;
-        let wall_layer = 4;
-        
-        // Set up collision relationships
-        layer_matrix[enemy_layer][wall_layer] = true; // Symmetric for simplicity
-        layer_matrix[enemy_layer][player_layer] = true; // Symmetric for simplicity
-        layer_matrix[player_layer][enemy_layer] = true;
-
-        layer_matrix[wall_layer][enemy_layer] = true;
-        layer_matrix[wall_layer][player_layer] = true;
-        layer_matrix[player_layer][wall_layer] = true;
-        
-        // Player bullets shouldn't hit players
-        
-        Self {
-            enemy_layer,
-            environment_layer,
-            player_layer,
-            wall_layer,
-            layer_matrix,
+        let mut settings = Self {
+            enemy_layer: 1,
+            environment_layer: 2,
+            player_layer: 3,
+            wall_layer: 4,
+            objective_layer: 5,
+            layer_matrix: [[false; 8]; 8],
+            preset: String::new(),
+        };
+        let pairs = [
+            (settings.enemy_layer, settings.wall_layer),
+            (settings.enemy_layer, settings.player_layer),
+            (settings.player_layer, settings.wall_layer),
+            // Environment props (barrels, ...) can be shot by everyone
+            (settings.player_layer, settings.environment_layer),
+            (settings.enemy_layer, settings.environment_layer),
+            // Objectives are only hit by the enemies, no friendly fire on the generator
+            (settings.enemy_layer, settings.objective_layer),
+        ];
+        for (a, b) in pairs {
+            settings.layer_matrix[a][b] = true;
+            settings.layer_matrix[b][a] = true;
+        }
+        settings
+    }
+}
+
+impl CollisionSettings {
+    // Layer of a name used in the presets
+    pub fn layer(&self, name: &str) -> Option<usize> {
+        match name {
+            "enemy" => Some(self.enemy_layer),
+            "environment" => Some(self.environment_layer),
+            "player" => Some(self.player_layer),
+            "wall" => Some(self.wall_layer),
+            "objective" => Some(self.objective_layer),
+            _ => None,
         }
     }
 }
@@ -130,6 +200,78 @@ pub fn is_colliding(
     }
 }
 
+// Same test as is_colliding with the contact, the normal push A out of B.
+// Rectangles are axis aligned, the rotation of the transforms is ignored.
+pub fn collide(
+    transform_a: &Transform,
+    collider_a: &Collider,
+    transform_b: &Transform,
+    collider_b: &Collider,
+) -> Option<Contact> {
+    let pos_a = round_vec2(transform_a.translation.truncate() + collider_a.offset);
+    let pos_b = round_vec2(transform_b.translation.truncate() + collider_b.offset);
+
+    match (&collider_a.shape, &collider_b.shape) {
+        (ColliderShape::Circle { radius: radius_a }, ColliderShape::Circle { radius: radius_b }) => {
+            circle_circle(pos_a, *radius_a, pos_b, *radius_b)
+        },
+        (ColliderShape::Rectangle { width: width_a, height: height_a },
+         ColliderShape::Rectangle { width: width_b, height: height_b }) => {
+            aabb_aabb(pos_a, Vec2::new(width_a / 2.0, height_a / 2.0), pos_b, Vec2::new(width_b / 2.0, height_b / 2.0))
+        },
+        (ColliderShape::Circle { radius }, ColliderShape::Rectangle { width, height }) => {
+            circle_aabb(pos_a, *radius, pos_b, Vec2::new(width / 2.0, height / 2.0))
+        },
+        (ColliderShape::Rectangle { width, height }, ColliderShape::Circle { radius }) => {
+            aabb_circle(pos_a, Vec2::new(width / 2.0, height / 2.0), pos_b, *radius)
+        },
+    }
+}
+
+// Swept version of is_colliding for a collider moving from start to end during the frame,
+// return the time of impact (see utils::sweep::TOI_SCALE) so nothing is skipped at high speed.
+// The moving collider is inflated into the target shape, a rectangle moving against a
+// circle is approximated by its bounding circle.
+pub fn sweep_collision(
+    start: Vec2,
+    end: Vec2,
+    collider_a: &Collider,
+    transform_b: &Transform,
+    collider_b: &Collider,
+) -> Option<u32> {
+    sweep_collision_hit(start, end, collider_a, transform_b, collider_b).map(|hit| hit.toi)
+}
+
+// Same as sweep_collision with the normal of the surface at the impact, used to bounce
+pub fn sweep_collision_hit(
+    start: Vec2,
+    end: Vec2,
+    collider_a: &Collider,
+    transform_b: &Transform,
+    collider_b: &Collider,
+) -> Option<SweepHit> {
+    let start = round_vec2(start + collider_a.offset);
+    let end = round_vec2(end + collider_a.offset);
+    let pos_b = round_vec2(transform_b.translation.truncate() + collider_b.offset);
+
+    match (&collider_a.shape, &collider_b.shape) {
+        (ColliderShape::Circle { radius: radius_a }, ColliderShape::Circle { radius: radius_b }) => {
+            segment_circle_hit(start, end, pos_b, radius_a + radius_b)
+        },
+        (ColliderShape::Rectangle { width, height }, ColliderShape::Circle { radius }) => {
+            let bounding_radius = Vec2::new(width / 2.0, height / 2.0).length();
+            segment_circle_hit(start, end, pos_b, radius + bounding_radius)
+        },
+        (ColliderShape::Circle { radius }, ColliderShape::Rectangle { width, height }) => {
+            segment_aabb_hit(start, end, pos_b, Vec2::new(width / 2.0 + radius, height / 2.0 + radius))
+        },
+        (ColliderShape::Rectangle { width: width_a, height: height_a },
+         ColliderShape::Rectangle { width: width_b, height: height_b }) => {
+            segment_aabb_hit(start, end, pos_b, Vec2::new((width_a + width_b) / 2.0, (height_a + height_b) / 2.0))
+        },
+    }
+}
+
 // Helper function for circle-to-rectangle collision
 pub fn circle_rect_collision(
     circle_pos: Vec2,
@@ -0,0 +1,236 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+use thiserror::Error;
+use utils::schema::Versioned;
+
+use crate::{global_asset::GlobalAsset, jjrs::GggrsSessionConfiguration, rules::GameMode};
+
+use super::CollisionSettings;
+
+// Layer matrices of the game modes, from `collision_presets.ron`. The rules of the match
+// pick the preset by name. An entry is read as "the row layer is blocked by or hit the
+// column layer", most of them are wanted both ways so an entry without its mirror must
+// be listed in `one_way` or the preset is refused. A refused or missing preset keep the
+// matrix of `CollisionSettings::default`, the one of the coop. The mode choose the
+// preset, `--collisions` or the lobby option pick another one like the sandbox.
+
+// Preset not used by default by a mode, only picked by name
+pub const SANDBOX_PRESET: &str = "sandbox";
+
+pub const COLLISION_PRESETS_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CollisionPreset {
+    // Pairs of layer names
+    pub collisions: Vec<(String, String)>,
+    // Entries of `collisions` meant without their mirror
+    #[serde(default)]
+    pub one_way: Vec<(String, String)>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct CollisionPresets {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub presets: HashMap<String, CollisionPreset>,
+}
+
+impl Versioned for CollisionPresets {
+    const ASSET_NAME: &'static str = "collision presets";
+    const SCHEMA_VERSION: u32 = COLLISION_PRESETS_SCHEMA_VERSION;
+
+    fn parse_version(_version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_bytes(bytes)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CollisionPresetError {
+    #[error("there is no collision preset `{0}`")]
+    UnknownPreset(String),
+    #[error("unknown layer `{layer}` in the collision preset `{preset}`")]
+    UnknownLayer { preset: String, layer: String },
+    #[error("`{from}` collide with `{to}` in the collision preset `{preset}` but not the other way, add the mirror entry or list it in one_way")]
+    MissingMirror { preset: String, from: String, to: String },
+    #[error("the one way entry `{from}` -> `{to}` of the collision preset `{preset}` is not in its collisions")]
+    UnusedOneWay { preset: String, from: String, to: String },
+}
+
+impl CollisionPreset {
+    // Every problem of the preset, empty when it can be used
+    pub fn validate(&self, name: &str, settings: &CollisionSettings) -> Vec<CollisionPresetError> {
+        let mut errors = vec![];
+
+        for (from, to) in self.collisions.iter().chain(self.one_way.iter()) {
+            for layer in [from, to] {
+                if settings.layer(layer).is_none() {
+                    errors.push(CollisionPresetError::UnknownLayer { preset: name.to_string(), layer: layer.clone() });
+                }
+            }
+        }
+
+        let has = |entries: &Vec<(String, String)>, from: &String, to: &String| entries.iter().any(|(f, t)| f == from && t == to);
+        for (from, to) in self.collisions.iter() {
+            if from != to && !has(&self.collisions, to, from) && !has(&self.one_way, from, to) {
+                errors.push(CollisionPresetError::MissingMirror { preset: name.to_string(), from: from.clone(), to: to.clone() });
+            }
+        }
+        for (from, to) in self.one_way.iter() {
+            if !has(&self.collisions, from, to) {
+                errors.push(CollisionPresetError::UnusedOneWay { preset: name.to_string(), from: from.clone(), to: to.clone() });
+            }
+        }
+
+        errors
+    }
+}
+
+impl CollisionPresets {
+    // Settings with the matrix of the preset, the first problem when it is not valid
+    pub fn settings(&self, name: &str) -> Result<CollisionSettings, CollisionPresetError> {
+        let preset = self.presets.get(name).ok_or_else(|| CollisionPresetError::UnknownPreset(name.to_string()))?;
+
+        let mut settings = CollisionSettings { layer_matrix: [[false; 8]; 8], ..Default::default() };
+        if let Some(error) = preset.validate(name, &settings).into_iter().next() {
+            return Err(error);
+        }
+        for (from, to) in preset.collisions.iter() {
+            // Validated above
+            if let (Some(from), Some(to)) = (settings.layer(from), settings.layer(to)) {
+                settings.layer_matrix[from][to] = true;
+            }
+        }
+        settings.preset = name.to_string();
+        Ok(settings)
+    }
+}
+
+
+// SYSTEMS
+
+// Report the invalid presets as soon as the file is loaded or edited
+pub fn collision_presets_update_system(
+    mut ev_asset: EventReader<AssetEvent<CollisionPresets>>,
+    presets_asset: Res<Assets<CollisionPresets>>,
+) {
+    for event in ev_asset.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = event else {
+            continue;
+        };
+        let Some(presets) = presets_asset.get(*id) else {
+            continue;
+        };
+
+        let mut names: Vec<&String> = presets.presets.keys().collect();
+        names.sort();
+        for name in names {
+            for error in presets.presets[name].validate(name, &CollisionSettings::default()) {
+                error!("{}", error);
+            }
+        }
+    }
+}
+
+// Use the preset of the rules, before anything of the match is spawned
+pub fn apply_collision_preset(
+    session_config: Res<GggrsSessionConfiguration>,
+    global_assets: Res<GlobalAsset>,
+    presets_asset: Res<Assets<CollisionPresets>>,
+    mut settings: ResMut<CollisionSettings>,
+) {
+    let name = &session_config.rules.collision_preset;
+    if settings.preset == *name {
+        return;
+    }
+    let Some(presets) = presets_asset.get(&global_assets.collision_presets) else {
+        return;
+    };
+
+    match presets.settings(name) {
+        Ok(preset_settings) => {
+            info!("using the collision preset {}", name);
+            *settings = preset_settings;
+        }
+        Err(err) => {
+            // Remembered so the error is not logged every frame of the lobby
+            error!("{}, using the default collisions", err);
+            *settings = CollisionSettings { preset: name.clone(), ..Default::default() };
+        }
+    }
+}
+
+// Name of a preset for the rules, the mode one when it is not given
+pub fn preset_for(mode: GameMode, name: Option<&str>) -> String {
+    name.filter(|name| !name.is_empty()).unwrap_or(mode.collision_preset()).to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presets_file() -> CollisionPresets {
+        let path = format!("{}/../../assets/collision_presets.ron", env!("CARGO_MANIFEST_DIR"));
+        let bytes = std::fs::read(&path).unwrap();
+        CollisionPresets::parse_version(COLLISION_PRESETS_SCHEMA_VERSION, &bytes).unwrap()
+    }
+
+    fn pair(from: &str, to: &str) -> (String, String) {
+        (from.to_string(), to.to_string())
+    }
+
+    #[test]
+    fn test_presets_of_the_file_are_valid() {
+        let presets = presets_file();
+        for (name, preset) in presets.presets.iter() {
+            assert_eq!(preset.validate(name, &CollisionSettings::default()), vec![], "preset {}", name);
+        }
+        // Every mode and the sandbox resolve
+        for mode in [GameMode::Survival, GameMode::DefendGenerator, GameMode::Deathmatch, GameMode::Tutorial] {
+            assert!(presets.settings(mode.collision_preset()).is_ok(), "{:?}", mode);
+        }
+        let sandbox = presets.settings(SANDBOX_PRESET).unwrap();
+        assert_eq!(sandbox.preset, SANDBOX_PRESET);
+        assert!(sandbox.layer_matrix[sandbox.player_layer][sandbox.objective_layer]);
+        assert!(!sandbox.layer_matrix[sandbox.objective_layer][sandbox.player_layer]);
+    }
+
+    #[test]
+    fn test_coop_preset_is_the_default_matrix() {
+        let coop = presets_file().settings("coop").unwrap();
+        assert_eq!(coop.layer_matrix, CollisionSettings::default().layer_matrix);
+    }
+
+    #[test]
+    fn test_settings_use_only_the_preset_entries() {
+        let presets = CollisionPresets {
+            schema_version: COLLISION_PRESETS_SCHEMA_VERSION,
+            presets: [("walls".to_string(), CollisionPreset { collisions: vec![pair("player", "wall"), pair("wall", "player")], one_way: vec![] })].into_iter().collect(),
+        };
+        let settings = presets.settings("walls").unwrap();
+        let enabled = settings.layer_matrix.iter().flatten().filter(|enabled| **enabled).count();
+        assert_eq!(enabled, 2);
+        assert!(settings.layer_matrix[settings.player_layer][settings.wall_layer]);
+    }
+
+    #[test]
+    fn test_settings_refuse_the_invalid_presets() {
+        let presets = CollisionPresets {
+            schema_version: COLLISION_PRESETS_SCHEMA_VERSION,
+            presets: [
+                ("layer".to_string(), CollisionPreset { collisions: vec![pair("player", "ghost")], one_way: vec![pair("player", "ghost")] }),
+                ("mirror".to_string(), CollisionPreset { collisions: vec![pair("player", "wall")], one_way: vec![] }),
+            ].into_iter().collect(),
+        };
+        assert_eq!(presets.settings("missing").unwrap_err(), CollisionPresetError::UnknownPreset("missing".into()));
+        assert_eq!(presets.settings("layer").unwrap_err(), CollisionPresetError::UnknownLayer { preset: "layer".into(), layer: "ghost".into() });
+        assert_eq!(presets.settings("mirror").unwrap_err(), CollisionPresetError::MissingMirror { preset: "mirror".into(), from: "player".into(), to: "wall".into() });
+    }
+
+    #[test]
+    fn test_preset_for_the_mode() {
+        assert_eq!(preset_for(GameMode::Deathmatch, None), "pvp");
+        assert_eq!(preset_for(GameMode::Survival, Some("")), "coop");
+        assert_eq!(preset_for(GameMode::Survival, Some(SANDBOX_PRESET)), SANDBOX_PRESET);
+    }
+}
@@ -0,0 +1,150 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::Rollback;
+use serde::{Deserialize, Serialize};
+use utils::math::round_vec2;
+
+// Grid bucketing the small static obstacles so the steering only look at the
+// cells around an enemy. Rebuilt from the rollback state at each simulated frame
+// before being read, so it doesn't need to be part of the snapshot.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum ObstacleKind {
+    Pickup,
+    Prop,
+    // Sides of a door, the enemies go through the middle
+    DoorFrame,
+}
+
+// Something enemies steer around instead of walking through
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct SteeringObstacle {
+    pub kind: ObstacleKind,
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpatialEntry {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub radius: f32,
+    pub kind: ObstacleKind,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct SpatialHash {
+    pub cell_size: f32,
+    cells: HashMap<IVec2, Vec<SpatialEntry>>,
+    // Entries are bucketed by their center, the queries look this far around to not miss
+    // an obstacle larger than a cell
+    max_radius: f32,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(128.0)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self { cell_size, cells: HashMap::default(), max_radius: 0.0 }
+    }
+
+    pub fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.max_radius = 0.0;
+    }
+
+    pub fn insert(&mut self, entry: SpatialEntry) {
+        let cell = self.cell(entry.position);
+        self.max_radius = self.max_radius.max(entry.radius);
+        let bucket = self.cells.entry(cell).or_default();
+        bucket.push(entry);
+        // Keep a stable order in each bucket whatever the insertion order was
+        bucket.sort_by_key(|e| e.entity.index());
+    }
+
+    // All entries which can be within `radius` of the position, sorted by entity
+    pub fn query(&self, position: Vec2, radius: f32) -> Vec<SpatialEntry> {
+        let reach = Vec2::splat(radius + self.max_radius);
+        let min = self.cell(position - reach);
+        let max = self.cell(position + reach);
+
+        let mut result = vec![];
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                if let Some(bucket) = self.cells.get(&IVec2::new(x, y)) {
+                    result.extend(bucket.iter().filter(|e| e.position.distance(position) <= radius + e.radius));
+                }
+            }
+        }
+        result.sort_by_key(|e| e.entity.index());
+        result
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.values().map(|b| b.len()).sum()
+    }
+}
+
+pub fn rollback_update_spatial_hash(
+    mut spatial_hash: ResMut<SpatialHash>,
+    query: Query<(Entity, &Transform, &SteeringObstacle), With<Rollback>>,
+) {
+    spatial_hash.clear();
+    for (entity, transform, obstacle) in query.iter() {
+        spatial_hash.insert(SpatialEntry {
+            entity,
+            position: round_vec2(transform.translation.truncate()),
+            radius: obstacle.radius,
+            kind: obstacle.kind,
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(index: u32, position: Vec2, radius: f32) -> SpatialEntry {
+        SpatialEntry { entity: Entity::from_raw(index), position, radius, kind: ObstacleKind::Prop }
+    }
+
+    #[test]
+    fn test_large_obstacle_is_found_from_a_far_cell() {
+        let mut hash = SpatialHash::new(128.0);
+        hash.insert(entry(1, Vec2::ZERO, 300.0));
+
+        // Two cells away from the center but inside the obstacle
+        let found = hash.query(Vec2::new(290.0, 0.0), 10.0);
+        assert_eq!(found.len(), 1);
+        assert!(hash.query(Vec2::new(400.0, 0.0), 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_query_is_sorted_by_entity() {
+        let mut hash = SpatialHash::new(128.0);
+        hash.insert(entry(3, Vec2::new(200.0, 0.0), 10.0));
+        hash.insert(entry(1, Vec2::new(-200.0, 0.0), 10.0));
+        hash.insert(entry(2, Vec2::ZERO, 10.0));
+
+        let found: Vec<_> = hash.query(Vec2::ZERO, 250.0).iter().map(|e| e.entity.index()).collect();
+        assert_eq!(found, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_clear_reset_the_reach() {
+        let mut hash = SpatialHash::new(128.0);
+        hash.insert(entry(1, Vec2::ZERO, 300.0));
+        hash.clear();
+        hash.insert(entry(2, Vec2::ZERO, 10.0));
+
+        assert_eq!(hash.max_radius, 10.0);
+        assert_eq!(hash.len(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ggrs::Rollback;
+use bevy_inspector_egui::{bevy_egui::{EguiContexts, EguiPlugin}, egui};
+
+use crate::{camera::GameCamera, character::{enemy::{ai::pathing::EnemyPath, Enemy}, health::Health, movement::Velocity, player::Player}, frame::FrameCount, plugins::AppState, weapons::{trigger::TriggerState, Bullet, Weapon, WeaponState}, web::{pointer_position, PointerLock}};
+
+// Panel listing the rollback entities with the values of their simulation components,
+// refreshed every frame to follow what the simulation does without a debugger. A right
+// click in the game select the entity closest to the pointer.
+
+pub const INSPECTOR_KEY: KeyCode = KeyCode::KeyI;
+pub const INSPECTOR_PICK_BUTTON: MouseButton = MouseButton::Right;
+// Farthest an entity can be from the pointer to be picked, in world units
+const PICK_RADIUS: f32 = 64.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InspectedKind {
+    Player,
+    Enemy,
+    Bullet,
+    Weapon,
+    Other,
+}
+
+impl InspectedKind {
+    const ALL: [InspectedKind; 5] = [InspectedKind::Player, InspectedKind::Enemy, InspectedKind::Bullet, InspectedKind::Weapon, InspectedKind::Other];
+
+    fn from_markers(player: bool, enemy: bool, bullet: bool, weapon: bool) -> Self {
+        match (player, enemy, bullet, weapon) {
+            (true, ..) => InspectedKind::Player,
+            (_, true, ..) => InspectedKind::Enemy,
+            (_, _, true, _) => InspectedKind::Bullet,
+            (.., true) => InspectedKind::Weapon,
+            _ => InspectedKind::Other,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            InspectedKind::Player => "player",
+            InspectedKind::Enemy => "enemy",
+            InspectedKind::Bullet => "bullet",
+            InspectedKind::Weapon => "weapon",
+            InspectedKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct InspectorState {
+    pub open: bool,
+    // Kinds listed, indexed by `InspectedKind`
+    pub shown: [bool; 5],
+    pub selected: Option<Entity>,
+}
+
+impl Default for InspectorState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            shown: [true; 5],
+            selected: None,
+        }
+    }
+}
+
+type InspectedEntities<'w, 's> = Query<'w, 's, (Entity, &'static GlobalTransform, Has<Player>, Has<Enemy>, Has<Bullet>, Has<Weapon>), With<Rollback>>;
+
+type InspectedDetails<'w, 's> = Query<'w, 's, (
+    &'static Transform,
+    Option<&'static Health>,
+    Option<&'static Velocity>,
+    Option<&'static Player>,
+    Option<&'static WeaponState>,
+    Option<&'static TriggerState>,
+    Option<&'static EnemyPath>,
+)>;
+
+type InspectedDetailsItem<'a> = (&'a Transform, Option<&'a Health>, Option<&'a Velocity>, Option<&'a Player>, Option<&'a WeaponState>, Option<&'a TriggerState>, Option<&'a EnemyPath>);
+
+fn egui_wants_pointer(contexts: &mut EguiContexts) -> bool {
+    contexts.try_ctx_mut().map_or(false, |ctx| ctx.wants_pointer_input() || ctx.is_pointer_over_area())
+}
+
+fn show_details(ui: &mut egui::Ui, entity: Entity, details: InspectedDetailsItem) {
+    let (transform, health, velocity, player, weapon_state, trigger, path) = details;
+
+    ui.heading(format!("{}", entity));
+    egui::Grid::new("inspector_details").num_columns(2).striped(true).show(ui, |ui| {
+        let row = |ui: &mut egui::Ui, name: &str, value: String| {
+            ui.label(name);
+            ui.monospace(value);
+            ui.end_row();
+        };
+
+        row(ui, "translation", format!("{:.3}, {:.3}, {:.3}", transform.translation.x, transform.translation.y, transform.translation.z));
+        row(ui, "rotation", format!("{:.4} rad", transform.rotation.to_euler(EulerRot::ZYX).0));
+        if let Some(player) = player {
+            row(ui, "handle", player.handle.to_string());
+        }
+        if let Some(health) = health {
+            row(ui, "health", format!("{:.1} / {:.1}", health.current, health.max));
+            row(ui, "invulnerable", health.invulnerable.is_some().to_string());
+        }
+        if let Some(velocity) = velocity {
+            row(ui, "velocity", format!("{:.3}, {:.3}", velocity.x, velocity.y));
+        }
+        if let Some(weapon_state) = weapon_state {
+            row(ui, "mode", weapon_state.active_mode.clone());
+            row(ui, "last fire frame", weapon_state.last_fire_frame.to_string());
+        }
+        if let Some(trigger) = trigger {
+            row(ui, "trigger", format!("{:?}", trigger.phase));
+            row(ui, "trigger held", trigger.held.to_string());
+            row(ui, "trigger queued", trigger.queued.to_string());
+        }
+        if let Some(path) = path {
+            row(ui, "path status", format!("{:?}", path.path_status));
+            row(ui, "path target", format!("{:.1}, {:.1}", path.target_position.x, path.target_position.y));
+            row(ui, "waypoints", path.waypoints.len().to_string());
+            row(ui, "recalculate in", format!("{} ticks", path.recalculate_ticks));
+        }
+    });
+}
+
+
+// SYSTEMS
+
+fn toggle_inspector(keys: Res<ButtonInput<KeyCode>>, mut state: ResMut<InspectorState>) {
+    if keys.just_pressed(INSPECTOR_KEY) {
+        state.open = !state.open;
+    }
+}
+
+fn pick_inspected_entity(
+    mouse: Res<ButtonInput<MouseButton>>,
+    pointer_lock: Res<PointerLock>,
+    mut contexts: EguiContexts,
+    mut state: ResMut<InspectorState>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    q_entities: InspectedEntities,
+) {
+    if !state.open || !mouse.just_pressed(INSPECTOR_PICK_BUTTON) || egui_wants_pointer(&mut contexts) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (q_window.get_single(), q_camera.get_single()) else {
+        return;
+    };
+    let Some(pointer) = pointer_position(window, &pointer_lock)
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok()) else {
+        return;
+    };
+
+    let shown = state.shown;
+    state.selected = q_entities.iter()
+        .filter(|(_, _, player, enemy, bullet, weapon)| shown[InspectedKind::from_markers(*player, *enemy, *bullet, *weapon) as usize])
+        .map(|(entity, transform, ..)| (entity, transform.translation().truncate().distance(pointer)))
+        .filter(|(_, distance)| *distance <= PICK_RADIUS)
+        .min_by(|(a, a_distance), (b, b_distance)| a_distance.total_cmp(b_distance).then(a.index().cmp(&b.index())))
+        .map(|(entity, _)| entity);
+}
+
+fn inspector_panel(
+    mut contexts: EguiContexts,
+    mut state: ResMut<InspectorState>,
+    frame: Res<FrameCount>,
+    q_entities: InspectedEntities,
+    q_details: InspectedDetails,
+) {
+    if !state.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut entities: Vec<(Entity, Vec2, InspectedKind)> = q_entities.iter()
+        .map(|(entity, transform, player, enemy, bullet, weapon)| (entity, transform.translation().truncate(), InspectedKind::from_markers(player, enemy, bullet, weapon)))
+        .collect();
+    entities.sort_by_key(|(entity, ..)| entity.index());
+
+    egui::Window::new("Inspector").default_pos((10.0, 80.0)).show(ctx, |ui| {
+        ui.label(format!("frame {} - {} rollback entities", frame.frame, entities.len()));
+        ui.horizontal(|ui| {
+            for kind in InspectedKind::ALL {
+                ui.checkbox(&mut state.shown[kind as usize], kind.label());
+            }
+        });
+        ui.separator();
+
+        let shown = state.shown;
+        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            for (entity, position, kind) in entities.iter().filter(|(_, _, kind)| shown[*kind as usize]) {
+                let selected = state.selected == Some(*entity);
+                let label = format!("{} {} ({:.0}, {:.0})", kind.label(), entity, position.x, position.y);
+                if ui.selectable_label(selected, label).clicked() {
+                    state.selected = Some(*entity);
+                }
+            }
+        });
+        ui.separator();
+
+        match state.selected.and_then(|entity| q_details.get(entity).ok().map(|details| (entity, details))) {
+            Some((entity, details)) => show_details(ui, entity, details),
+            None => {
+                ui.label("Nothing selected, right click an entity in the game");
+            }
+        }
+    });
+}
+
+fn highlight_inspected_entity(
+    mut gizmos: Gizmos,
+    state: Res<InspectorState>,
+    q_transform: Query<&GlobalTransform>,
+) {
+    let Some(transform) = state.selected.filter(|_| state.open).and_then(|entity| q_transform.get(entity).ok()) else {
+        return;
+    };
+    gizmos.circle_2d(transform.translation().truncate(), 24.0, Color::srgb(1.0, 1.0, 0.0));
+}
+
+
+#[derive(Default)]
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<InspectorState>();
+        app.add_systems(Update, (
+            toggle_inspector,
+            pick_inspected_entity.after(toggle_inspector),
+            inspector_panel.after(pick_inspected_entity),
+            highlight_inspected_entity,
+        ).run_if(in_state(AppState::InGame)));
+    }
+}
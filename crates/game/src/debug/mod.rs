@@ -1,6 +1,8 @@
+pub mod inspector;
+
 use bevy::prelude::*;
 
-use crate::collider::{Collider, ColliderShape, CollisionLayer};
+use crate::collider::{Collider, ColliderShape, CollisionLayer, HitZoneKind, HitZones};
 
 #[derive(Resource, Default)]
 struct DebugOverlayState {
@@ -67,9 +69,25 @@ fn draw_sprite_debug_rects_system(
     }
 }
 
+fn draw_collider(gizmos: &mut Gizmos, transform: &Transform, collider: &Collider, color: LinearRgba) {
+    match collider.shape {
+        ColliderShape::Circle { radius } => {
+            gizmos.circle_2d(
+                transform.translation.truncate() + collider.offset,
+                radius,
+                color,
+            );
+        },
+        ColliderShape::Rectangle { width, height } => {
+            gizmos.rect_2d(transform.translation.truncate() + collider.offset, Vec2::new(width, height), color);
+        }
+    }
+}
+
 pub fn debug_draw_colliders_system(
     mut gizmos: Gizmos,
     collider_query: Query<(&Transform, &Collider, &CollisionLayer)>,
+    zones_query: Query<(&Transform, &HitZones)>,
 ) {
     // Draw regular colliders
     for (transform, collider, layer) in collider_query.iter() {
@@ -81,19 +99,18 @@ pub fn debug_draw_colliders_system(
             _ => LinearRgba::BLACK,       // Unknown
         };
 
-        match collider.shape {
-            ColliderShape::Circle { radius } => {
-                gizmos.circle_2d(
-                    transform.translation.truncate() + collider.offset,
-                    radius,
-                    color,
-                );
-            },
-            ColliderShape::Rectangle { width, height } => {
-                gizmos.rect_2d(transform.translation.truncate() + collider.offset, Vec2::new(width, height), color);
-            }
+        draw_collider(&mut gizmos, transform, collider, color);
+    }
+
+    // Draw the hit zones over the colliders
+    for (transform, zones) in zones_query.iter() {
+        for zone in zones.0.iter() {
+            let color = match zone.zone {
+                HitZoneKind::Head => LinearRgba::rgb(1.0, 0.8, 0.0),
+                HitZoneKind::Body => LinearRgba::rgb(1.0, 0.4, 0.0),
+            };
+            draw_collider(&mut gizmos, transform, &zone.collider, color);
         }
-        
     }
 }
 
@@ -0,0 +1,191 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use map::game::entity::map::destructible::{DestructibleComponent, DestructibleConfig, DestructibleKind};
+
+use crate::{character::health::{Death, Health}, hazard::fire::Flammable, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings, Wall}, plugins::{AppState, MapSetupSet}};
+
+// Crates and weak walls from the map. They block the movement and the bullets like any
+// wall, the destructible ones crumble when their health is gone: the entity and its
+// collider are despawned with the other deaths of the frame, the nav grid is baked
+// again without it and a pile of debris is left where it stood.
+
+// Pile left by a destroyed crate or wall, nothing collide with it
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Debris {
+    pub kind: DestructibleKind,
+}
+
+pub fn destructible_color(kind: DestructibleKind) -> Color {
+    match kind {
+        DestructibleKind::Crate => Color::srgb(0.6, 0.45, 0.25),
+        DestructibleKind::WeakWall => Color::srgb(0.45, 0.42, 0.4),
+    }
+}
+
+pub fn spawn_destructible(
+    commands: &mut Commands,
+    position: Vec3,
+    config: DestructibleConfig,
+    collision_settings: &CollisionSettings,
+) -> Entity {
+    let mut entity_commands = commands.spawn((
+        Sprite::from_color(destructible_color(config.kind), config.size),
+        Transform::from_translation(position),
+    ));
+    insert_destructible_components(&mut entity_commands, config, collision_settings);
+
+    entity_commands.add_rollback().id()
+}
+
+// Everything the simulation need on a crate or a wall, the sprite and the position are
+// left to the caller, the code or the map
+fn insert_destructible_components(
+    entity_commands: &mut EntityCommands,
+    config: DestructibleConfig,
+    collision_settings: &CollisionSettings,
+) {
+    entity_commands.insert((
+        Wall,
+        Collider {
+            shape: ColliderShape::Rectangle { width: config.size.x, height: config.size.y },
+            offset: Vec2::ZERO,
+        },
+        CollisionLayer(collision_settings.wall_layer),
+    ));
+    if config.destructible {
+        entity_commands.insert(Health { current: config.health, max: config.health, invulnerable: None });
+    }
+    if config.flammable {
+        entity_commands.insert(Flammable::default());
+    }
+    entity_commands.insert(DestructibleComponent { config });
+}
+
+fn spawn_debris(commands: &mut Commands, position: Vec3, config: &DestructibleConfig) -> Entity {
+    // Flatter than what it was, under the characters
+    let size = Vec2::new(config.size.x, config.size.y * 0.4);
+    commands.spawn((
+        Sprite::from_color(destructible_color(config.kind).darker(0.2), size),
+        Transform::from_translation(position.with_z(-1.0)),
+        Debris { kind: config.kind },
+    )).add_rollback().id()
+}
+
+
+// SYSTEMS
+
+// Crates and walls placed in the map only come with their config and their sprite, they
+// get the same components as the ones spawned by the code
+pub fn setup_map_destructibles(
+    mut commands: Commands,
+    collision_settings: Res<CollisionSettings>,
+    destructible_query: Query<(Entity, &DestructibleComponent), Without<Wall>>,
+) {
+    let mut destructibles: Vec<_> = destructible_query.iter().collect();
+    destructibles.sort_by_key(|(entity, _)| entity.index());
+
+    for (entity, destructible) in destructibles {
+        let mut entity_commands = commands.entity(entity);
+        insert_destructible_components(&mut entity_commands, destructible.config.clone(), &collision_settings);
+        entity_commands.add_rollback();
+    }
+}
+
+// Must run before the deaths are applied, the despawn remove the collider
+pub fn rollback_crumble_destructibles(
+    mut commands: Commands,
+    query: Query<(Entity, &Transform, &DestructibleComponent), (With<Death>, With<Rollback>)>,
+) {
+    let mut destroyed: Vec<_> = query.iter().collect();
+    destroyed.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, destructible) in destroyed {
+        spawn_debris(&mut commands, transform.translation, &destructible.config);
+        info!("{} {} crumbled", destructible.config.kind.name(), entity);
+    }
+}
+
+// Darker as the health goes down
+fn update_destructible_sprites(mut query: Query<(&DestructibleComponent, &Health, &mut Sprite), Changed<Health>>) {
+    for (destructible, health, mut sprite) in query.iter_mut() {
+        let ratio = (health.current / health.max.max(1.0)).clamp(0.0, 1.0);
+        sprite.color = destructible_color(destructible.config.kind).darker(0.3 * (1.0 - ratio));
+    }
+}
+
+#[derive(Default)]
+pub struct DestructiblePlugin;
+
+impl Plugin for DestructiblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, setup_map_destructibles.in_set(MapSetupSet));
+        app.add_systems(Update, update_destructible_sprites.run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        // Normally added by the GgrsPlugin, needed by add_rollback
+        app.init_resource::<bevy_ggrs::RollbackOrdered>();
+        app.init_resource::<CollisionSettings>();
+        app.add_systems(Update, setup_map_destructibles);
+        app
+    }
+
+    #[test]
+    fn test_map_crate_get_the_components_of_the_code() {
+        let mut app = app();
+        let config = DestructibleConfig { kind: DestructibleKind::Crate, destructible: true, health: 80.0, size: Vec2::new(40.0, 30.0), flammable: true };
+        // What the bundle of the map spawn, the config read from the fields and a sprite
+        let entity = app.world_mut().spawn((
+            DestructibleComponent { config },
+            Sprite::default(),
+            Transform::from_xyz(64.0, 32.0, 0.0),
+        )).id();
+        app.update();
+
+        let world = app.world();
+        assert!(world.get::<Rollback>(entity).is_some());
+        assert!(world.get::<Wall>(entity).is_some());
+        // The flammable field of the map is live
+        assert!(world.get::<Flammable>(entity).is_some());
+        assert_eq!(world.get::<Health>(entity).map(|health| health.current), Some(80.0));
+        assert_eq!(world.get::<CollisionLayer>(entity).map(|layer| layer.0), Some(CollisionSettings::default().wall_layer));
+        assert!(matches!(
+            world.get::<Collider>(entity).map(|collider| &collider.shape),
+            Some(ColliderShape::Rectangle { width, height }) if *width == 40.0 && *height == 30.0
+        ));
+        assert_eq!(world.get::<Transform>(entity).map(|transform| transform.translation), Some(Vec3::new(64.0, 32.0, 0.0)));
+    }
+
+    #[test]
+    fn test_map_indestructible_wall_has_no_health() {
+        let mut app = app();
+        let config = DestructibleConfig { destructible: false, flammable: false, ..Default::default() };
+        let entity = app.world_mut().spawn((DestructibleComponent { config }, Transform::default())).id();
+        app.update();
+
+        let world = app.world();
+        assert!(world.get::<Wall>(entity).is_some());
+        assert!(world.get::<Health>(entity).is_none());
+        assert!(world.get::<Flammable>(entity).is_none());
+    }
+
+    #[test]
+    fn test_destructible_is_set_up_once() {
+        let mut app = app();
+        let mut commands = app.world_mut().commands();
+        let entity = spawn_destructible(&mut commands, Vec3::ZERO, DestructibleConfig::default(), &CollisionSettings::default());
+        app.world_mut().flush();
+        app.world_mut().entity_mut(entity).get_mut::<Health>().unwrap().current = 1.0;
+        app.update();
+
+        // Spawned by the code, the system leave it as it is
+        assert_eq!(app.world().get::<Health>(entity).map(|health| health.current), Some(1.0));
+    }
+}
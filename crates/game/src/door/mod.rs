@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+
+use crate::{character::player::{input::PreviousInput, jjrs::PeerConfig, Player}, collider::{spatial::{ObstacleKind, SteeringObstacle}, Collider, ColliderShape, CollisionLayer, CollisionSettings, Wall}, interaction::{find_interactable_in_range, Interactable}, localization::LocalizedText, plugins::AppState, points::PlayerPoints};
+
+// Door bought with points. Closed it block everyone like a wall, the zombies can't tear
+// it down and path around it. Once opened it stay open for the rest of the game.
+
+const DOOR_INTERACTION_RADIUS: f32 = 110.0;
+// Steering radius of each side of the frame
+const DOOR_FRAME_RADIUS: f32 = 12.0;
+
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Door {
+    pub cost: u32,
+    pub size: Vec2,
+    pub open: bool,
+}
+
+impl Door {
+    pub fn new(size: Vec2, cost: u32) -> Self {
+        Self { cost, size, open: false }
+    }
+}
+
+pub fn spawn_door(
+    commands: &mut Commands,
+    position: Vec3,
+    door: Door,
+    collision_settings: &CollisionSettings,
+) -> Entity {
+    let mut entity_commands = commands.spawn((
+        Sprite::from_color(Color::srgb(0.35, 0.3, 0.25), door.size),
+        Transform::from_translation(position),
+        door,
+        Interactable {
+            radius: DOOR_INTERACTION_RADIUS,
+            prompt: LocalizedText::new("interaction.open_door").with_arg("cost", door.cost),
+        },
+        CollisionLayer(collision_settings.wall_layer),
+    ));
+    if !door.open {
+        entity_commands.insert((
+            Wall,
+            Collider {
+                shape: ColliderShape::Rectangle { width: door.size.x, height: door.size.y },
+                offset: Vec2::ZERO,
+            },
+        ));
+    }
+    let entity = entity_commands.add_rollback().id();
+
+    for frame_position in door_frame_positions(position.truncate(), door.size) {
+        commands.spawn((
+            Transform::from_translation(frame_position.extend(position.z)),
+            SteeringObstacle { kind: ObstacleKind::DoorFrame, radius: DOOR_FRAME_RADIUS },
+        )).add_rollback();
+    }
+    entity
+}
+
+// Both ends of the door along its long side, the passage is across the short one
+pub fn door_frame_positions(center: Vec2, size: Vec2) -> [Vec2; 2] {
+    let half = if size.x >= size.y { Vec2::new(size.x / 2.0, 0.0) } else { Vec2::new(0.0, size.y / 2.0) };
+    [center - half, center + half]
+}
+
+
+// SYSTEMS
+
+// The interaction next to a closed door open it when the player has the points
+pub fn rollback_open_doors(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    mut door_query: Query<(Entity, &Transform, &Interactable, &mut Door), With<Rollback>>,
+    mut player_query: Query<(&Transform, &Player, &mut PlayerPoints, &PreviousInput), With<Rollback>>,
+) {
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.handle);
+
+    for (transform, player, points, previous_input) in players.iter_mut() {
+        let (input, _input_status) = inputs[player.handle];
+        if !previous_input.interact_just_pressed(&input) {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let closed = door_query.iter()
+            .filter(|(.., door)| !door.open)
+            .map(|(entity, transform, interactable, _)| (entity, transform, interactable));
+        let Some(entity) = find_interactable_in_range(position, closed) else {
+            continue;
+        };
+        let Ok((_, _, _, mut door)) = door_query.get_mut(entity) else {
+            continue;
+        };
+        if !points.spend(door.cost) {
+            continue;
+        }
+
+        door.open = true;
+        commands.entity(entity).remove::<(Wall, Collider, Interactable)>();
+    }
+}
+
+// An open door is only its frame on the floor
+fn update_door_sprites(mut query: Query<(&Door, &mut Sprite), Changed<Door>>) {
+    for (door, mut sprite) in query.iter_mut() {
+        sprite.color = Color::srgba(0.35, 0.3, 0.25, if door.open { 0.15 } else { 1.0 });
+    }
+}
+
+#[derive(Default)]
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_door_sprites.run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_door_frame_on_the_long_side() {
+        // In a vertical wall the enemies cross along x, the frame is above and under
+        assert_eq!(door_frame_positions(Vec2::new(-500.0, -75.0), Vec2::new(125.0, 150.0)), [Vec2::new(-500.0, -150.0), Vec2::new(-500.0, 0.0)]);
+        assert_eq!(door_frame_positions(Vec2::ZERO, Vec2::new(200.0, 20.0)), [Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0)]);
+    }
+}
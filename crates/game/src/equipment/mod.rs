@@ -0,0 +1,321 @@
+pub mod ui;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use map::game::entity::map::equipment::{EquipmentKind, EquipmentSpawnComponent};
+use utils::{frame::FrameTimer, math::round};
+
+use crate::{budget::{BudgetStats, SimulationBudget}, character::{enemy::Enemy, health::Death, player::Player}, collider::spatial::{ObstacleKind, SteeringObstacle}, frame::FrameCount, powerup::PowerUpPickup, rng::DropsRng, weapons::{MagBulletConfig, Weapon, WeaponInventory, WeaponModesState}};
+
+
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct EquipmentConfig {
+    // Chance between 0 and 1 that a killed enemy drops an equipment
+    pub drop_chance: f32,
+    // How long a dropped equipment stay on the ground, the ones of the map stay forever
+    pub pickup_lifetime_frames: u32,
+    pub pickup_radius: f32,
+    // Armor given by a vest
+    pub armor_amount: f32,
+    pub speed_multiplier: f32,
+    pub speed_duration_frames: u32,
+    // Reserve mags added on top of the limit of each weapon by the backpack
+    pub backpack_extra_mags_percent: u32,
+}
+
+impl Default for EquipmentConfig {
+    fn default() -> Self {
+        Self {
+            drop_chance: 0.02,
+            pickup_lifetime_frames: 60 * 20,
+            pickup_radius: 40.0,
+            armor_amount: 50.0,
+            speed_multiplier: 1.3,
+            speed_duration_frames: 60 * 20,
+            backpack_extra_mags_percent: 50,
+        }
+    }
+}
+
+pub fn equipment_color(kind: EquipmentKind) -> Color {
+    match kind {
+        EquipmentKind::ArmorVest => Color::srgb(0.3, 0.4, 0.8),
+        EquipmentKind::SpeedBoots => Color::srgb(0.2, 0.8, 0.9),
+        EquipmentKind::Backpack => Color::srgb(0.6, 0.4, 0.2),
+    }
+}
+
+pub fn equipment_label(kind: EquipmentKind) -> &'static str {
+    match kind {
+        EquipmentKind::ArmorVest => "Armor Vest",
+        EquipmentKind::SpeedBoots => "Speed Boots",
+        EquipmentKind::Backpack => "Backpack",
+    }
+}
+
+// Rollback component of the armor of a player, it takes the damage before the health
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Armor {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Armor {
+    // Take what the armor can of the damage, return what is left for the health
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        let absorbed = damage.min(self.current).max(0.0);
+        self.current = round(self.current - absorbed);
+        round(damage - absorbed)
+    }
+}
+
+// Movement buff of the speed boots, removed when the timer is done
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct SpeedBoost {
+    pub timer: FrameTimer,
+    pub multiplier: f32,
+}
+
+impl SpeedBoost {
+    pub fn multiplier(&self, current_frame: u32) -> f32 {
+        if self.timer.is_active(current_frame) { self.multiplier } else { 1.0 }
+    }
+}
+
+// Raise the reserve ammo limit of every weapon of the player
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Backpack {
+    pub extra_mags_percent: u32,
+}
+
+// Reserve mags a player can carry for a weapon mode
+pub fn mag_limit(base: u32, backpack: Option<&Backpack>) -> u32 {
+    backpack.map_or(base, |backpack| base + base * backpack.extra_mags_percent / 100)
+}
+
+// Equipment waiting on the ground to be collected
+#[derive(Component, Clone, Debug)]
+pub struct EquipmentPickup {
+    pub kind: EquipmentKind,
+    // None for the equipment placed on the map
+    pub despawn_at_frame: Option<u32>,
+}
+
+
+pub fn spawn_equipment_pickup(
+    commands: &mut Commands,
+    kind: EquipmentKind,
+    position: Vec3,
+    despawn_at_frame: Option<u32>,
+) -> Entity {
+    let mut entity_commands = commands.spawn((
+        Sprite::from_color(equipment_color(kind), Vec2::new(20.0, 20.0)),
+        Transform::from_translation(Vec3::new(position.x, position.y, 1.0)),
+    ));
+    insert_equipment_pickup_components(&mut entity_commands, kind, despawn_at_frame);
+
+    entity_commands.add_rollback().id()
+}
+
+// Everything the simulation need on a pickup, the sprite and the position are left to
+// the caller, the code or the map
+fn insert_equipment_pickup_components(entity_commands: &mut EntityCommands, kind: EquipmentKind, despawn_at_frame: Option<u32>) {
+    entity_commands.insert((
+        EquipmentPickup { kind, despawn_at_frame },
+        SteeringObstacle { kind: ObstacleKind::Pickup, radius: 10.0 },
+    ));
+}
+
+
+// SYSTEMS
+
+// Equipment placed in the map only come with its kind and its sprite, it get the same
+// components as a dropped one and stay until collected
+pub fn setup_map_equipment(
+    mut commands: Commands,
+    spawn_query: Query<(Entity, &EquipmentSpawnComponent), Without<EquipmentPickup>>,
+) {
+    let mut spawns: Vec<_> = spawn_query.iter().collect();
+    spawns.sort_by_key(|(entity, _)| entity.index());
+
+    for (entity, spawn) in spawns {
+        let mut entity_commands = commands.entity(entity);
+        insert_equipment_pickup_components(&mut entity_commands, spawn.kind, None);
+        entity_commands.add_rollback();
+    }
+}
+
+// Roll a drop for each enemy that died this frame, after the power-ups so they keep their rolls
+pub fn rollback_drop_equipment(
+    mut commands: Commands,
+    mut rng: ResMut<DropsRng>,
+    frame: Res<FrameCount>,
+    config: Res<EquipmentConfig>,
+    budget: Res<SimulationBudget>,
+    mut budget_stats: ResMut<BudgetStats>,
+    enemy_query: Query<(Entity, &Transform), (With<Death>, With<Enemy>, With<Rollback>)>,
+    pickup_query: Query<(), (Or<(With<PowerUpPickup>, With<EquipmentPickup>)>, With<Rollback>)>,
+) {
+    let mut deaths: Vec<(Entity, &Transform)> = enemy_query.iter().collect();
+    deaths.sort_by_key(|(entity, _)| entity.index());
+
+    let mut pickups = pickup_query.iter().count();
+    for (_, transform) in deaths {
+        if rng.next_f32() >= config.drop_chance {
+            continue;
+        }
+        let kind = EquipmentKind::ALL[(rng.next_u32() as usize) % EquipmentKind::ALL.len()];
+        if !budget.has_room_for_pickup(pickups) {
+            budget_stats.skipped_drops += 1;
+            continue;
+        }
+        spawn_equipment_pickup(&mut commands, kind, transform.translation, Some(frame.frame + config.pickup_lifetime_frames));
+        pickups += 1;
+    }
+}
+
+pub fn rollback_collect_equipment(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    config: Res<EquipmentConfig>,
+    pickup_query: Query<(Entity, &Transform, &EquipmentPickup), With<Rollback>>,
+    mut player_query: Query<(Entity, &Transform, &Player, &WeaponInventory, Option<&mut Armor>, Option<&Backpack>), With<Rollback>>,
+    mut weapon_query: Query<(&Weapon, &mut WeaponModesState)>,
+) {
+    let mut pickups: Vec<(Entity, &Transform, &EquipmentPickup)> = pickup_query.iter().collect();
+    pickups.sort_by_key(|(entity, ..)| entity.index());
+
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, _, player, ..)| player.handle);
+
+    // Inserted by the commands, a second one in the same frame is left on the ground
+    let mut new_backpacks = vec![];
+
+    for (entity, transform, pickup) in pickups {
+        if pickup.despawn_at_frame.map_or(false, |despawn_at_frame| frame.frame >= despawn_at_frame) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let pickup_pos = transform.translation.truncate();
+        for (player_entity, player_transform, player, inventory, armor, backpack) in players.iter_mut() {
+            if player_transform.translation.truncate().distance(pickup_pos) >= config.pickup_radius {
+                continue;
+            }
+
+            // An equipment the player can't use is left for the others
+            let collected = match pickup.kind {
+                EquipmentKind::ArmorVest => match armor {
+                    Some(armor) if armor.current >= config.armor_amount => false,
+                    Some(armor) => {
+                        armor.current = config.armor_amount;
+                        armor.max = config.armor_amount;
+                        true
+                    }
+                    None => {
+                        commands.entity(*player_entity).insert(Armor { current: config.armor_amount, max: config.armor_amount });
+                        true
+                    }
+                },
+                EquipmentKind::SpeedBoots => {
+                    commands.entity(*player_entity).insert(SpeedBoost {
+                        timer: FrameTimer::new(frame.frame, config.speed_duration_frames),
+                        multiplier: config.speed_multiplier,
+                    });
+                    true
+                }
+                EquipmentKind::Backpack => {
+                    if backpack.is_some() || new_backpacks.contains(player_entity) {
+                        false
+                    } else {
+                        let backpack = Backpack { extra_mags_percent: config.backpack_extra_mags_percent };
+                        // The extra room come filled
+                        for (weapon_entity, _) in inventory.weapons.iter() {
+                            let Some((weapon, mut modes_state)) = weapon_entity.get_mut(&mut weapon_query) else {
+                                continue;
+                            };
+                            for (name, mode_config) in weapon.config.firing_modes.iter() {
+                                let (MagBulletConfig::Mag { mag_limit: base, .. }, Some(mode_state)) = (&mode_config.mag, modes_state.modes.get_mut(name)) else {
+                                    continue;
+                                };
+                                mode_state.mag_quantity += mag_limit(*base, Some(&backpack)) - base;
+                            }
+                        }
+                        commands.entity(*player_entity).insert(backpack);
+                        new_backpacks.push(*player_entity);
+                        true
+                    }
+                }
+            };
+
+            if collected {
+                info!("Player {} collected {}", player.handle, equipment_label(pickup.kind));
+                commands.entity(entity).despawn();
+                break;
+            }
+        }
+    }
+}
+
+pub fn rollback_expire_equipment(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    query: Query<(Entity, &SpeedBoost), With<Rollback>>,
+) {
+    for (entity, boost) in query.iter() {
+        if boost.timer.is_done(frame.frame) {
+            commands.entity(entity).remove::<SpeedBoost>();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mag_limit_with_backpack() {
+        assert_eq!(mag_limit(4, None), 4);
+        assert_eq!(mag_limit(4, Some(&Backpack { extra_mags_percent: 50 })), 6);
+        // Rounded down
+        assert_eq!(mag_limit(3, Some(&Backpack { extra_mags_percent: 50 })), 4);
+        assert_eq!(mag_limit(0, Some(&Backpack { extra_mags_percent: 50 })), 0);
+    }
+
+    #[test]
+    fn test_armor_absorb_before_the_health() {
+        let mut armor = Armor { current: 30.0, max: 50.0 };
+        assert_eq!(armor.absorb(20.0), 0.0);
+        assert_eq!(armor.current, 10.0);
+        assert_eq!(armor.absorb(25.0), 15.0);
+        assert_eq!(armor.current, 0.0);
+        assert_eq!(armor.absorb(5.0), 5.0);
+    }
+
+    #[test]
+    fn test_map_equipment_become_a_pickup() {
+        let mut app = App::new();
+        // Normally added by the GgrsPlugin, needed by add_rollback
+        app.init_resource::<bevy_ggrs::RollbackOrdered>();
+        app.add_systems(Update, setup_map_equipment);
+        let entity = app.world_mut().spawn((
+            EquipmentSpawnComponent { kind: EquipmentKind::Backpack },
+            Sprite::default(),
+            Transform::from_xyz(64.0, 32.0, 0.0),
+        )).id();
+        app.update();
+
+        let world = app.world();
+        assert!(world.get::<Rollback>(entity).is_some());
+        assert!(world.get::<SteeringObstacle>(entity).is_some());
+        let pickup = world.get::<EquipmentPickup>(entity).unwrap();
+        assert_eq!(pickup.kind, EquipmentKind::Backpack);
+        // The ones of the map stay forever
+        assert_eq!(pickup.despawn_at_frame, None);
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use map::game::entity::map::equipment::EquipmentKind;
+use utils::frame::SimulationConfig;
+
+use crate::{character::player::LocalPlayer, frame::FrameCount, hud::{HudAnchor, HudSlot}, plugins::AppState};
+
+use super::{equipment_color, Armor, Backpack, SpeedBoost};
+
+
+#[derive(Component)]
+struct EquipmentIcon(EquipmentKind);
+
+#[derive(Component)]
+struct EquipmentText(EquipmentKind);
+
+
+fn setup_equipment_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    // Over the power-ups, same look
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(28.0),
+            ..default()
+        },
+        HudSlot::new(HudAnchor::BottomCenter, 1),
+    )).with_children(|row| {
+        for kind in EquipmentKind::ALL.iter() {
+            row.spawn((
+                EquipmentIcon(*kind),
+                Node {
+                    width: Val::Px(32.0),
+                    height: Val::Px(32.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(equipment_color(*kind)),
+                Visibility::Hidden,
+            )).with_children(|parent| {
+                parent.spawn((
+                    EquipmentText(*kind),
+                    Text::new(""),
+                    TextFont {
+                        font: font.clone(),
+                        font_size: 14.0,
+                        ..Default::default()
+                    },
+                    TextColor(Color::BLACK),
+                    TextLayout::new_with_justify(JustifyText::Center),
+                ));
+            });
+        }
+    });
+}
+
+fn update_equipment_ui(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    q_player: Query<(Option<&Armor>, Option<&SpeedBoost>, Has<Backpack>), With<LocalPlayer>>,
+    mut q_icon: Query<(&EquipmentIcon, &mut Visibility)>,
+    mut q_text: Query<(&EquipmentText, &mut Text)>,
+) {
+    let (armor, boost, backpack) = q_player.get_single().unwrap_or((None, None, false));
+
+    // Text of each equipment worn, None when not worn
+    let value = |kind: EquipmentKind| -> Option<String> {
+        match kind {
+            EquipmentKind::ArmorVest => armor.filter(|armor| armor.current > 0.0).map(|armor| format!("{:.0}", armor.current)),
+            EquipmentKind::SpeedBoots => boost
+                .filter(|boost| boost.timer.is_active(frame.frame))
+                .map(|boost| format!("{:.0}", boost.timer.remaining_seconds(frame.frame, &simulation).ceil())),
+            EquipmentKind::Backpack => backpack.then(|| "+".to_string()),
+        }
+    };
+
+    for (icon, mut visibility) in q_icon.iter_mut() {
+        *visibility = if value(icon.0).is_some() { Visibility::Inherited } else { Visibility::Hidden };
+    }
+
+    for (equipment, mut text) in q_text.iter_mut() {
+        text.0 = value(equipment.0).unwrap_or_default();
+    }
+}
+
+
+#[derive(Default)]
+pub struct EquipmentUIPlugin;
+
+impl Plugin for EquipmentUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_equipment_ui);
+        app.add_systems(Update, update_equipment_ui.run_if(in_state(AppState::InGame)));
+    }
+}
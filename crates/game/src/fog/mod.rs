@@ -0,0 +1,257 @@
+use bevy::{prelude::*, render::render_resource::{AsBindGroup, ShaderRef, ShaderType}, sprite::{AlphaMode2d, Material2d, Material2dPlugin}};
+
+use crate::{camera::GameCamera, character::{enemy::Enemy, player::LocalPlayer}, collider::{Collider, ColliderShape, Wall}, plugins::AppState};
+
+// Fog of war is presentation only, nothing here touch the rollback state.
+// The visibility polygon is a fan of rays casted from the local player
+// against the walls, the shader interpolate between rays to smooth the mask.
+
+pub const FOG_RAY_COUNT: usize = 128;
+const FOG_RAY_GROUPS: usize = FOG_RAY_COUNT / 4;
+
+const FOG_SHADER_PATH: &str = "shaders/fog.wgsl";
+
+#[derive(Resource, Clone, Debug)]
+pub struct FogOfWarSettings {
+    pub enabled: bool,
+    // Max distance the player can see when nothing block the view
+    pub vision_radius: f32,
+    // Alpha of the fog in hidden area
+    pub darkness: f32,
+    // Width of the fade between visible and hidden area
+    pub edge_softness: f32,
+}
+
+impl Default for FogOfWarSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vision_radius: 900.0,
+            darkness: 0.85,
+            edge_softness: 40.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ShaderType)]
+pub struct FogUniform {
+    // xy: origin of the vision, z: vision radius, w: darkness
+    pub origin: Vec4,
+    // x: edge softness, yzw unused
+    pub params: Vec4,
+    // Distance of each ray, packed by 4
+    pub rays: [Vec4; FOG_RAY_GROUPS],
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct FogMaterial {
+    #[uniform(0)]
+    pub uniform: FogUniform,
+}
+
+impl Material2d for FogMaterial {
+    fn fragment_shader() -> ShaderRef {
+        FOG_SHADER_PATH.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+}
+
+#[derive(Component)]
+pub struct FogOverlay;
+
+// Visibility polygon of the local player, recomputed every frame
+#[derive(Resource, Default)]
+pub struct VisibilityPolygon {
+    pub origin: Vec2,
+    pub rays: Vec<f32>,
+}
+
+impl VisibilityPolygon {
+    pub fn is_visible(&self, point: Vec2) -> bool {
+        if self.rays.is_empty() {
+            return true;
+        }
+
+        let delta = point - self.origin;
+        let distance = delta.length();
+        let angle = delta.y.atan2(delta.x).rem_euclid(std::f32::consts::TAU);
+
+        let step = std::f32::consts::TAU / self.rays.len() as f32;
+        let position = angle / step;
+        let index = position.floor() as usize % self.rays.len();
+        let next = (index + 1) % self.rays.len();
+        let t = position.fract();
+
+        distance <= self.rays[index] * (1.0 - t) + self.rays[next] * t
+    }
+}
+
+pub struct FogOfWarPlugin;
+
+impl Plugin for FogOfWarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FogOfWarSettings>()
+            .init_resource::<VisibilityPolygon>()
+            .add_plugins(Material2dPlugin::<FogMaterial>::default())
+            .add_systems(OnEnter(AppState::InGame), setup_fog_overlay)
+            .add_systems(Update, (
+                toggle_fog_system,
+                compute_visibility_polygon_system,
+                update_fog_overlay_system,
+                hide_enemies_in_fog_system,
+            ).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+fn setup_fog_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<FogMaterial>>,
+) {
+    commands.spawn((
+        FogOverlay,
+        // Big enough to cover the screen at max zoom out, follow the camera
+        Mesh2d(meshes.add(Rectangle::new(20000.0, 20000.0))),
+        MeshMaterial2d(materials.add(FogMaterial {
+            uniform: FogUniform { origin: Vec4::ZERO, params: Vec4::ZERO, rays: [Vec4::ZERO; FOG_RAY_GROUPS] },
+        })),
+        Transform::from_xyz(0.0, 0.0, 50.0),
+        Visibility::Hidden,
+    ));
+}
+
+fn toggle_fog_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<FogOfWarSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+fn compute_visibility_polygon_system(
+    settings: Res<FogOfWarSettings>,
+    mut polygon: ResMut<VisibilityPolygon>,
+    q_player: Query<&GlobalTransform, With<LocalPlayer>>,
+    q_walls: Query<(&GlobalTransform, &Collider), With<Wall>>,
+) {
+    polygon.rays.clear();
+
+    if !settings.enabled {
+        return;
+    }
+
+    let Ok(player_transform) = q_player.get_single() else {
+        return;
+    };
+
+    let origin = player_transform.translation().truncate();
+    let walls: Vec<(Vec2, &Collider)> = q_walls.iter()
+        .map(|(transform, collider)| (transform.translation().truncate() + collider.offset, collider))
+        .collect();
+
+    polygon.origin = origin;
+    for i in 0..FOG_RAY_COUNT {
+        let angle = i as f32 / FOG_RAY_COUNT as f32 * std::f32::consts::TAU;
+        let direction = Vec2::new(angle.cos(), angle.sin());
+
+        let mut distance = settings.vision_radius;
+        for (center, collider) in walls.iter() {
+            if let Some(hit) = ray_intersect(origin, direction, *center, &collider.shape) {
+                distance = distance.min(hit);
+            }
+        }
+        polygon.rays.push(distance);
+    }
+}
+
+fn update_fog_overlay_system(
+    settings: Res<FogOfWarSettings>,
+    polygon: Res<VisibilityPolygon>,
+    mut materials: ResMut<Assets<FogMaterial>>,
+    q_camera: Query<&Transform, (With<GameCamera>, Without<FogOverlay>)>,
+    mut q_overlay: Query<(&mut Transform, &mut Visibility, &MeshMaterial2d<FogMaterial>), With<FogOverlay>>,
+) {
+    let Ok((mut transform, mut visibility, material_handle)) = q_overlay.get_single_mut() else {
+        return;
+    };
+
+    if !settings.enabled || polygon.rays.len() != FOG_RAY_COUNT {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    if let Ok(camera_transform) = q_camera.get_single() {
+        transform.translation.x = camera_transform.translation.x;
+        transform.translation.y = camera_transform.translation.y;
+    }
+
+    if let Some(material) = materials.get_mut(&material_handle.0) {
+        material.uniform.origin = Vec4::new(polygon.origin.x, polygon.origin.y, settings.vision_radius, settings.darkness);
+        material.uniform.params = Vec4::new(settings.edge_softness, 0.0, 0.0, 0.0);
+        for (i, chunk) in polygon.rays.chunks(4).enumerate() {
+            material.uniform.rays[i] = Vec4::new(chunk[0], chunk[1], chunk[2], chunk[3]);
+        }
+    }
+}
+
+fn hide_enemies_in_fog_system(
+    settings: Res<FogOfWarSettings>,
+    polygon: Res<VisibilityPolygon>,
+    mut q_enemies: Query<(&GlobalTransform, &mut Visibility), With<Enemy>>,
+) {
+    for (transform, mut visibility) in q_enemies.iter_mut() {
+        let visible = !settings.enabled || polygon.is_visible(transform.translation().truncate());
+        let target = if visible { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+}
+
+// Return the distance along the ray to the first hit with the shape
+fn ray_intersect(origin: Vec2, direction: Vec2, center: Vec2, shape: &ColliderShape) -> Option<f32> {
+    match shape {
+        ColliderShape::Circle { radius } => {
+            let to_center = center - origin;
+            let projection = to_center.dot(direction);
+            let closest_sq = to_center.length_squared() - projection * projection;
+            let radius_sq = radius * radius;
+            if closest_sq > radius_sq {
+                return None;
+            }
+            let half_chord = (radius_sq - closest_sq).sqrt();
+            let hit = projection - half_chord;
+            if hit >= 0.0 { Some(hit) } else if projection + half_chord >= 0.0 { Some(0.0) } else { None }
+        }
+        ColliderShape::Rectangle { width, height } => {
+            let half = Vec2::new(width / 2.0, height / 2.0);
+            let min = center - half;
+            let max = center + half;
+
+            let mut t_min = f32::NEG_INFINITY;
+            let mut t_max = f32::INFINITY;
+            for axis in 0..2 {
+                if direction[axis].abs() < f32::EPSILON {
+                    if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                        return None;
+                    }
+                    continue;
+                }
+                let t1 = (min[axis] - origin[axis]) / direction[axis];
+                let t2 = (max[axis] - origin[axis]) / direction[axis];
+                t_min = t_min.max(t1.min(t2));
+                t_max = t_max.min(t1.max(t2));
+            }
+
+            if t_max < t_min.max(0.0) {
+                return None;
+            }
+            Some(t_min.max(0.0))
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
 
-use crate::plugins::GameInfo;
+use crate::{hud::{HudAnchor, HudSlot}, plugins::GameInfo};
 
 // You can also register resources.
 #[derive(Resource, Default, Reflect, Hash, Clone, Copy)]
@@ -13,6 +14,16 @@ pub fn increase_frame_system(mut frame_count: ResMut<FrameCount>) {
     frame_count.frame += 1;
 }
 
+// Last frame no rollback can change, for the effects shown once. Without a network
+// session every frame is confirmed, None when the session has not confirmed any yet.
+pub fn confirmed_frame(frame: &FrameCount, confirmed: Option<Res<ConfirmedFrameCount>>) -> Option<u32> {
+    match confirmed {
+        Some(confirmed) if confirmed.0 >= 0 => Some((confirmed.0 as u32).min(frame.frame)),
+        Some(_) => None,
+        None => Some(frame.frame),
+    }
+}
+
 
 
 // DEBUG
@@ -34,12 +45,7 @@ fn setup_frame_counter_ui(mut commands: Commands, asset_server: Res<AssetServer>
             ..Default::default()
         },
         TextLayout::new_with_justify(JustifyText::Center),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(5.0),
-            right: Val::Px(5.0),
-            ..default()
-        },
+        HudSlot::new(HudAnchor::BottomRight, 0),
     ));
 }
 
@@ -1,8 +1,8 @@
 use animation::{AnimationMapConfig, SpriteSheetConfig};
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{asset::LoadState, prelude::*, utils::HashMap};
 use utils::bmap;
 
-use crate::{camera::CameraSettingsAsset, character::config::CharacterConfig, plugins::AppState, weapons::WeaponsConfig};
+use crate::{camera::{grading::PostProcessPresets, CameraSettingsAsset}, character::config::CharacterConfig, collider::preset::CollisionPresets, hazard::fire::HazardSettings, localization::{LanguageFile, LANGUAGES}, plugins::AppState, weapons::WeaponsConfig};
 
 const PLAYER_SPRITESHEET_CONFIG_PATH: &str = "ZombieShooter/Sprites/Character/player_sheet.ron";
 const PLAYER_SHIRT_SPRITESHEET_CONFIG_PATH: &str = "ZombieShooter/Sprites/Character/shirt_1_sheet.ron";
@@ -18,6 +18,10 @@ pub struct GlobalAsset {
     pub character_configs: HashMap<String, Handle<CharacterConfig>>,
     pub weapons: Handle<WeaponsConfig>,
     pub camera: Handle<CameraSettingsAsset>,
+    pub collision_presets: Handle<CollisionPresets>,
+    pub post_process: Handle<PostProcessPresets>,
+    pub hazard: Handle<HazardSettings>,
+    pub locales: HashMap<String, Handle<LanguageFile>>,
 }
 
 impl GlobalAsset {
@@ -58,11 +62,22 @@ impl GlobalAsset {
             ),
             character_configs: bmap!(
                 "player" => asset_server.load(PLAYER_CONFIG_PATH),
+                "medic" => asset_server.load("ZombieShooter/Sprites/Character/medic_config.ron"),
+                "heavy" => asset_server.load("ZombieShooter/Sprites/Character/heavy_config.ron"),
+                "scout" => asset_server.load("ZombieShooter/Sprites/Character/scout_config.ron"),
                 "zombie_1" => asset_server.load("ZombieShooter/Sprites/Zombie/zombie_config.ron"),
-                "zombie_2" => asset_server.load("ZombieShooter/Sprites/Zombie/zombie_hard_config.ron")
+                "zombie_2" => asset_server.load("ZombieShooter/Sprites/Zombie/zombie_hard_config.ron"),
+                "zombie_exploder" => asset_server.load("ZombieShooter/Sprites/Zombie/zombie_exploder_config.ron"),
+                "zombie_shield" => asset_server.load("ZombieShooter/Sprites/Zombie/zombie_shield_config.ron")
             ),
             weapons: asset_server.load("ZombieShooter/Sprites/Character/weapons.ron"),
             camera: asset_server.load("camera.ron"),
+            collision_presets: asset_server.load("collision_presets.ron"),
+            post_process: asset_server.load("post_process.ron"),
+            hazard: asset_server.load("hazard.ron"),
+            locales: LANGUAGES.iter()
+                .map(|code| (code.to_string(), asset_server.load(format!("locales/{}.ron", code))))
+                .collect(),
         }
     }
 }
@@ -77,6 +92,7 @@ pub fn loading_asset_system(
     mut app_state: ResMut<NextState<AppState>>,
     global_assets: Res<GlobalAsset>,
     asset_server: Res<AssetServer>,
+    mut exit: EventWriter<AppExit>,
 ) {
 
     for (_, v) in global_assets.spritesheets.iter() {
@@ -105,6 +121,26 @@ pub fn loading_asset_system(
     if !asset_server.load_state(&global_assets.camera).is_loaded() {
         return;
     }
+    // A preset file that can't be read would keep the game loading forever
+    if let LoadState::Failed(err) = asset_server.load_state(&global_assets.collision_presets) {
+        error!("failed to load the collision presets: {}", err);
+        exit.send(AppExit::error());
+        return;
+    }
+    if !asset_server.load_state(&global_assets.collision_presets).is_loaded() {
+        return;
+    }
+    if !asset_server.load_state(&global_assets.post_process).is_loaded() {
+        return;
+    }
+    if !asset_server.load_state(&global_assets.hazard).is_loaded() {
+        return;
+    }
+    for (_, handle) in global_assets.locales.iter() {
+        if !asset_server.load_state(handle).is_loaded() {
+            return;
+        }
+    }
 
     app_state.set(AppState::Lobby);
     info!("loading of asset is done , now entering lobby");
@@ -0,0 +1,304 @@
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use serde::Deserialize;
+use map::game::entity::map::hazard::{HazardComponent, HazardConfig};
+use utils::{frame::{FrameTimer, SimulationConfig}, schema::Versioned};
+
+use crate::{character::health::{accumulate_damage, DamageAccumulator, Health, HitBy}, frame::FrameCount, global_asset::GlobalAsset, plugins::AppState};
+
+// Fire on the flammable props of the map, configured in `hazard.ron`. A fire patch light
+// the props in its radius and a burning prop light the ones around it, one hop every tick
+// so the fire crawl from a prop to the next. A prop burn once for a while, hurting
+// everything near it and itself, then it is burned out and can't be lit again.
+
+pub const HAZARD_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct FireConfig {
+    pub burn_frames: u32,
+    pub damage_per_tick: f32,
+    pub tick_interval_frames: u32,
+    // Everything with health closer than this to a burning prop is hurt
+    pub burn_radius: f32,
+    // A burning prop light the flammable props closer than this
+    pub spread_radius: f32,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct HazardSettings {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub fire: FireConfig,
+}
+
+impl Versioned for HazardSettings {
+    const ASSET_NAME: &'static str = "hazard settings";
+    const SCHEMA_VERSION: u32 = HAZARD_SCHEMA_VERSION;
+
+    fn parse_version(_version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_bytes(bytes)
+    }
+}
+
+// Prop that can catch fire
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Flammable {
+    pub burning: Option<FrameTimer>,
+    // Two props next to each other can't keep lighting themselves
+    pub burned_out: bool,
+}
+
+impl Flammable {
+    pub fn is_burning(&self, frame: u32) -> bool {
+        self.burning.map_or(false, |timer| timer.is_active(frame))
+    }
+
+    // At the end of its time the prop is burned out for good
+    pub fn burn_out(&mut self, frame: u32) {
+        if self.burning.map_or(false, |timer| timer.is_done(frame)) {
+            self.burning = None;
+            self.burned_out = true;
+        }
+    }
+}
+
+// The fire spread and burn on the ticks, the interval scaled to the tick rate
+pub fn is_fire_tick(frame: u32, fire: &FireConfig, simulation: &SimulationConfig) -> bool {
+    let interval = simulation.frames(fire.tick_interval_frames);
+    interval != 0 && frame % interval == 0
+}
+
+// Light the props in reach of the fires, the props in the order of the entities. Return
+// the entities lit.
+fn ignite<'a>(
+    fires: &[(Entity, Vec2, f32)],
+    props: impl Iterator<Item = (Entity, Vec2, &'a mut Flammable)>,
+    timer: FrameTimer,
+) -> Vec<Entity> {
+    let mut lit = vec![];
+    for (entity, position, flammable) in props {
+        if flammable.burned_out || flammable.burning.is_some() {
+            continue;
+        }
+        if fires.iter().any(|(source, center, radius)| *source != entity && center.distance(position) <= *radius) {
+            flammable.burning = Some(timer);
+            lit.push(entity);
+        }
+    }
+    lit
+}
+
+
+// SYSTEMS
+
+// Light the props near a fire, in the order of the entities so every peer agree
+pub fn rollback_spread_fire(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    global_assets: Res<GlobalAsset>,
+    hazard_settings: Res<Assets<HazardSettings>>,
+    hazard_query: Query<(Entity, &Transform, &HazardComponent), With<Rollback>>,
+    mut flammable_query: Query<(Entity, &Transform, &mut Flammable), With<Rollback>>,
+) {
+    let Some(settings) = hazard_settings.get(&global_assets.hazard) else {
+        return;
+    };
+    let fire = settings.fire;
+
+    // Burned out at the end of its time, the check of the tick doesn't wait for it
+    for (_, _, mut flammable) in flammable_query.iter_mut() {
+        flammable.burn_out(frame.frame);
+    }
+
+    if !is_fire_tick(frame.frame, &fire, &simulation) {
+        return;
+    }
+
+    // The fires of the frame before any prop is lit, a new fire spread only at the next tick
+    let mut fires: Vec<(Entity, Vec2, f32)> = hazard_query.iter()
+        .filter_map(|(entity, transform, hazard)| match hazard.config {
+            HazardConfig::FirePatch { radius, .. } => Some((entity, transform.translation.truncate(), radius)),
+            _ => None,
+        })
+        .chain(flammable_query.iter()
+            .filter(|(_, _, flammable)| flammable.is_burning(frame.frame))
+            .map(|(entity, transform, _)| (entity, transform.translation.truncate(), fire.spread_radius)))
+        .collect();
+    fires.sort_by_key(|(entity, ..)| entity.index());
+    if fires.is_empty() {
+        return;
+    }
+
+    let mut props: Vec<_> = flammable_query.iter_mut().collect();
+    props.sort_by_key(|(entity, ..)| entity.index());
+
+    let timer = FrameTimer::new(frame.frame, simulation.frames(fire.burn_frames));
+    let props = props.iter_mut().map(|(entity, transform, flammable)| (*entity, transform.translation.truncate(), &mut **flammable));
+    for entity in ignite(&fires, props, timer) {
+        info!("{} caught fire", entity);
+    }
+}
+
+// Burning props hurt everything with health around them each tick, themselves included
+pub fn rollback_burn_damage(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    global_assets: Res<GlobalAsset>,
+    hazard_settings: Res<Assets<HazardSettings>>,
+    flammable_query: Query<(Entity, &Transform, &Flammable), With<Rollback>>,
+    mut target_query: Query<(Entity, &Transform, Option<&mut DamageAccumulator>), (With<Health>, With<Rollback>)>,
+) {
+    let Some(settings) = hazard_settings.get(&global_assets.hazard) else {
+        return;
+    };
+    let fire = settings.fire;
+    if !is_fire_tick(frame.frame, &fire, &simulation) {
+        return;
+    }
+
+    let mut burning: Vec<_> = flammable_query.iter()
+        .filter(|(_, _, flammable)| flammable.is_burning(frame.frame))
+        .collect();
+    burning.sort_by_key(|(entity, ..)| entity.index());
+
+    for (prop_entity, prop_transform, _) in burning {
+        let center = prop_transform.translation.truncate();
+        for (target_entity, target_transform, opt_accumulator) in target_query.iter_mut() {
+            if target_transform.translation.truncate().distance(center) > fire.burn_radius {
+                continue;
+            }
+            accumulate_damage(&mut commands, target_entity, opt_accumulator, fire.damage_per_tick, Some(HitBy::Entity(prop_entity)));
+        }
+    }
+}
+
+
+// PRESENTATION
+
+// Flames over a burning prop
+#[derive(Component)]
+struct Flames {
+    anchor: Entity,
+}
+
+// Follow what the simulation show, a rollback that put out a fire remove its flames
+fn update_flames(
+    mut commands: Commands,
+    time: Res<Time>,
+    frame: Res<FrameCount>,
+    flammable_query: Query<(Entity, &Transform, &Flammable, Option<&Sprite>), Without<Flames>>,
+    mut flames_query: Query<(Entity, &Flames, &mut Transform, &mut Sprite)>,
+) {
+    let mut shown = vec![];
+    for (entity, flames, mut transform, mut sprite) in flames_query.iter_mut() {
+        let Some((_, prop_transform, ..)) = flammable_query.get(flames.anchor).ok().filter(|(_, _, flammable, _)| flammable.is_burning(frame.frame)) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        shown.push(flames.anchor);
+
+        transform.translation = prop_transform.translation.with_z(prop_transform.translation.z + 0.5);
+        // Two sines out of phase, the flames never flicker the same on two props
+        let t = time.elapsed_secs() * 12.0 + flames.anchor.index() as f32;
+        let flicker = 0.5 + 0.25 * t.sin() + 0.25 * (t * 2.3).sin();
+        sprite.color = Color::srgba(1.0, 0.45 + 0.2 * flicker, 0.1, 0.45 + 0.3 * flicker);
+    }
+
+    for (entity, transform, flammable, opt_sprite) in flammable_query.iter() {
+        if !flammable.is_burning(frame.frame) || shown.contains(&entity) {
+            continue;
+        }
+        let size = opt_sprite.and_then(|sprite| sprite.custom_size).unwrap_or(Vec2::splat(32.0));
+        commands.spawn((
+            Sprite::from_color(Color::srgba(1.0, 0.5, 0.1, 0.6), size * 1.2),
+            Transform::from_translation(transform.translation),
+            Flames { anchor: entity },
+        ));
+    }
+}
+
+fn clear_flames(mut commands: Commands, flames_query: Query<Entity, With<Flames>>) {
+    for entity in flames_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+
+#[derive(Default)]
+pub struct FirePlugin;
+
+impl Plugin for FirePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_flames.run_if(in_state(AppState::InGame)));
+        app.add_systems(OnExit(AppState::InGame), clear_flames);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRE: FireConfig = FireConfig { burn_frames: 300, damage_per_tick: 5.0, tick_interval_frames: 30, burn_radius: 40.0, spread_radius: 60.0 };
+
+    // Fires of a tick like the system, the patch and the burning props
+    fn fires_of(patch: (Entity, Vec2, f32), props: &[(Entity, Vec2, Flammable)], frame: u32) -> Vec<(Entity, Vec2, f32)> {
+        let mut fires: Vec<_> = std::iter::once(patch)
+            .chain(props.iter().filter(|(_, _, flammable)| flammable.is_burning(frame)).map(|(entity, position, _)| (*entity, *position, FIRE.spread_radius)))
+            .collect();
+        fires.sort_by_key(|(entity, ..)| entity.index());
+        fires
+    }
+
+    fn tick(patch: (Entity, Vec2, f32), props: &mut [(Entity, Vec2, Flammable)], frame: u32) -> Vec<Entity> {
+        for (_, _, flammable) in props.iter_mut() {
+            flammable.burn_out(frame);
+        }
+        let fires = fires_of(patch, props, frame);
+        ignite(&fires, props.iter_mut().map(|(entity, position, flammable)| (*entity, *position, flammable)), FrameTimer::new(frame, FIRE.burn_frames))
+    }
+
+    #[test]
+    fn test_fire_spread_one_hop_each_tick() {
+        let patch = (Entity::from_raw(10), Vec2::ZERO, 20.0);
+        // In a line, out of order of the entities
+        let mut props = [
+            (Entity::from_raw(1), Vec2::new(100.0, 0.0), Flammable::default()),
+            (Entity::from_raw(2), Vec2::new(50.0, 0.0), Flammable::default()),
+            (Entity::from_raw(3), Vec2::new(0.0, 10.0), Flammable::default()),
+        ];
+        assert_eq!(tick(patch, &mut props, 30), vec![Entity::from_raw(3)]);
+        // A prop lit on a tick only spread on the next one
+        assert_eq!(tick(patch, &mut props, 60), vec![Entity::from_raw(2)]);
+        assert_eq!(tick(patch, &mut props, 90), vec![Entity::from_raw(1)]);
+        assert!(tick(patch, &mut props, 120).is_empty());
+    }
+
+    #[test]
+    fn test_burned_out_props_never_light_again() {
+        let patch = (Entity::from_raw(10), Vec2::ZERO, 20.0);
+        let mut props = [
+            (Entity::from_raw(1), Vec2::new(0.0, 10.0), Flammable::default()),
+            (Entity::from_raw(2), Vec2::new(40.0, 10.0), Flammable::default()),
+        ];
+        tick(patch, &mut props, 0);
+        tick(patch, &mut props, 30);
+        assert!(props.iter().all(|(_, _, flammable)| flammable.is_burning(30)));
+
+        // Both burned out, still next to each other and to the patch
+        assert!(tick(patch, &mut props, 330).is_empty());
+        assert!(props.iter().all(|(_, _, flammable)| flammable.burned_out && !flammable.is_burning(330)));
+        assert!(tick(patch, &mut props, 360).is_empty());
+    }
+
+    #[test]
+    fn test_tick_interval_scaled() {
+        let simulation = SimulationConfig { tick_rate: 120, ..default() };
+        assert!(!is_fire_tick(30, &FIRE, &simulation));
+        assert!(is_fire_tick(60, &FIRE, &simulation));
+        assert!(is_fire_tick(30, &FIRE, &SimulationConfig::default()));
+        assert!(!is_fire_tick(30, &FireConfig { tick_interval_frames: 0, ..FIRE }, &simulation));
+    }
+}
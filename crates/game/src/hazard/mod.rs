@@ -0,0 +1,474 @@
+pub mod fire;
+pub mod switch;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use map::game::entity::map::{hazard::{HazardComponent, HazardConfig}, switch::SwitchComponent};
+use utils::{frame::{SimulationConfig, FRAME_RATE}, math::round_vec3};
+
+use crate::{plugins::MapSetupSet, character::{enemy::Enemy, health::{accumulate_damage, DamageAccumulator, Death, Health, HitBy}, player::Player, status::{apply_stun, Stunned}}, collider::{is_colliding, spatial::{ObstacleKind, SteeringObstacle}, Collider, ColliderShape, CollisionLayer, CollisionSettings, Wall}, frame::FrameCount, weapons::explosion::spawn_explosion};
+
+use fire::Flammable;
+use switch::{powered_circuits, SwitchState};
+
+
+// Rollback state shared by all the hazards
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct HazardState {
+    pub next_trigger_frame: u32,
+}
+
+
+pub fn spawn_hazard(
+    commands: &mut Commands,
+    position: Vec3,
+    config: HazardConfig,
+    collision_settings: &CollisionSettings,
+) -> Entity {
+    let mut entity_commands = match &config {
+        HazardConfig::FirePatch { radius, .. } => commands.spawn((
+            Sprite::from_color(Color::srgba(1.0, 0.3, 0.0, 0.5), Vec2::splat(radius * 2.0)),
+            Transform::from_translation(position.with_z(-1.0)),
+        )),
+        HazardConfig::ElectricTrap { radius, .. } => commands.spawn((
+            Sprite::from_color(Color::srgba(0.3, 0.6, 1.0, 0.5), Vec2::splat(radius * 2.0)),
+            Transform::from_translation(position.with_z(-1.0)),
+        )),
+        HazardConfig::ElectricFence { size, .. } => commands.spawn((
+            Sprite::from_color(Color::srgba(0.5, 0.8, 1.0, 0.15), *size),
+            Transform::from_translation(position.with_z(-1.0)),
+        )),
+        HazardConfig::FanTrap { size, .. } => commands.spawn((
+            Sprite::from_color(Color::srgba(0.8, 0.9, 0.9, 0.15), *size),
+            Transform::from_translation(position.with_z(-1.0)),
+        )),
+        HazardConfig::ExplosiveBarrel { .. } => commands.spawn((
+            Sprite::from_color(Color::srgb(0.8, 0.1, 0.1), Vec2::new(40.0, 60.0)),
+            Transform::from_translation(position),
+        )),
+    };
+
+    insert_hazard_components(&mut entity_commands, config, collision_settings);
+
+    entity_commands.add_rollback().id()
+}
+
+// Everything the simulation need on a hazard, the sprite and the position are left to
+// the caller, the code or the map
+fn insert_hazard_components(
+    entity_commands: &mut EntityCommands,
+    config: HazardConfig,
+    collision_settings: &CollisionSettings,
+) {
+    if let HazardConfig::ExplosiveBarrel { health, .. } = &config {
+        entity_commands.insert((
+            Health { current: *health, max: *health, invulnerable: None },
+            Collider {
+                shape: ColliderShape::Rectangle { width: 40.0, height: 60.0 },
+                offset: Vec2::ZERO,
+            },
+            CollisionLayer(collision_settings.environment_layer),
+            SteeringObstacle { kind: ObstacleKind::Prop, radius: 30.0 },
+            Flammable::default(),
+        ));
+    }
+
+    entity_commands.insert((
+        HazardComponent { config },
+        HazardState::default(),
+    ));
+}
+
+// Inside the rectangle of a fence or a fan
+fn in_area(center: Vec2, size: Vec2, position: Vec2) -> bool {
+    let offset = (position - center).abs();
+    offset.x <= size.x / 2.0 && offset.y <= size.y / 2.0
+}
+
+
+// SYSTEMS
+
+// Hazards placed in the map only come with their config and their sprite, they get the
+// same components as the ones spawned by the code
+pub fn setup_map_hazards(
+    mut commands: Commands,
+    collision_settings: Res<CollisionSettings>,
+    hazard_query: Query<(Entity, &HazardComponent), Without<HazardState>>,
+) {
+    let mut hazards: Vec<_> = hazard_query.iter().collect();
+    hazards.sort_by_key(|(entity, _)| entity.index());
+
+    for (entity, hazard) in hazards {
+        let mut entity_commands = commands.entity(entity);
+        insert_hazard_components(&mut entity_commands, hazard.config.clone(), &collision_settings);
+        entity_commands.add_rollback();
+    }
+}
+
+// Fire patch damage everything with health standing in it each tick
+pub fn rollback_fire_patch_system(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    hazard_query: Query<(Entity, &Transform, &HazardComponent), With<Rollback>>,
+    mut target_query: Query<(Entity, &Transform, Option<&mut DamageAccumulator>), (With<Health>, With<Rollback>)>,
+) {
+    let mut hazards: Vec<_> = hazard_query.iter().collect();
+    hazards.sort_by_key(|(entity, ..)| entity.index());
+
+    for (hazard_entity, hazard_transform, hazard) in hazards {
+        let HazardConfig::FirePatch { radius, damage_per_tick, tick_interval_frames } = hazard.config else {
+            continue;
+        };
+        let tick_interval_frames = simulation.frames(tick_interval_frames);
+        if tick_interval_frames == 0 || frame.frame % tick_interval_frames != 0 {
+            continue;
+        }
+
+        let center = hazard_transform.translation.truncate();
+        for (target_entity, target_transform, opt_accumulator) in target_query.iter_mut() {
+            if target_transform.translation.truncate().distance(center) > radius {
+                continue;
+            }
+            accumulate_damage(&mut commands, target_entity, opt_accumulator, damage_per_tick, Some(HitBy::Entity(hazard_entity)));
+        }
+    }
+}
+
+// Electric trap stun every player standing on it when it fire then recharge
+pub fn rollback_electric_trap_system(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    mut hazard_query: Query<(Entity, &Transform, &HazardComponent, &mut HazardState), With<Rollback>>,
+    mut player_query: Query<(Entity, &Transform, &Player, Option<&mut Stunned>), With<Rollback>>,
+) {
+    let mut hazards: Vec<_> = hazard_query.iter_mut().collect();
+    hazards.sort_by_key(|(entity, ..)| entity.index());
+
+    for (_, hazard_transform, hazard, state) in hazards.iter_mut() {
+        let HazardConfig::ElectricTrap { radius, stun_frames, cooldown_frames } = hazard.config else {
+            continue;
+        };
+        if frame.frame < state.next_trigger_frame {
+            continue;
+        }
+
+        let center = hazard_transform.translation.truncate();
+        let mut players: Vec<_> = player_query.iter_mut()
+            .filter(|(_, transform, ..)| transform.translation.truncate().distance(center) <= radius)
+            .collect();
+        players.sort_by_key(|(_, _, player, _)| player.handle);
+
+        if players.is_empty() {
+            continue;
+        }
+
+        for (player_entity, _, _, opt_stunned) in players {
+            apply_stun(&mut commands, player_entity, opt_stunned, frame.frame, simulation.frames(stun_frames));
+        }
+        state.next_trigger_frame = frame.frame + simulation.frames(cooldown_frames);
+    }
+}
+
+// Powered fence damage the enemies in it each tick, the players can walk through
+pub fn rollback_electric_fence_system(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    switch_query: Query<(&SwitchComponent, &SwitchState), With<Rollback>>,
+    hazard_query: Query<(Entity, &Transform, &HazardComponent), With<Rollback>>,
+    mut enemy_query: Query<(Entity, &Transform, Option<&mut DamageAccumulator>), (With<Enemy>, With<Health>, With<Rollback>)>,
+) {
+    let circuits = powered_circuits(frame.frame, switch_query.iter());
+    if circuits.is_empty() {
+        return;
+    }
+
+    let mut hazards: Vec<_> = hazard_query.iter().collect();
+    hazards.sort_by_key(|(entity, ..)| entity.index());
+
+    for (hazard_entity, hazard_transform, hazard) in hazards {
+        let HazardConfig::ElectricFence { size, damage_per_tick, tick_interval_frames, circuit } = hazard.config else {
+            continue;
+        };
+        let tick_interval_frames = simulation.frames(tick_interval_frames);
+        if !circuits.contains(&circuit) || tick_interval_frames == 0 || frame.frame % tick_interval_frames != 0 {
+            continue;
+        }
+
+        let center = hazard_transform.translation.truncate();
+        for (target_entity, target_transform, opt_accumulator) in enemy_query.iter_mut() {
+            if !in_area(center, size, target_transform.translation.truncate()) {
+                continue;
+            }
+            accumulate_damage(&mut commands, target_entity, opt_accumulator, damage_per_tick, Some(HitBy::Entity(hazard_entity)));
+        }
+    }
+}
+
+// Powered fan push the enemies in its area every frame, after they moved. The push of the
+// config is for a frame at FRAME_RATE, an enemy pushed into a wall stay where it is
+pub fn rollback_fan_trap_system(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    settings: Res<CollisionSettings>,
+    switch_query: Query<(&SwitchComponent, &SwitchState), With<Rollback>>,
+    hazard_query: Query<(Entity, &Transform, &HazardComponent), (With<Rollback>, Without<Enemy>)>,
+    wall_query: Query<(&Transform, &Collider, &CollisionLayer), (With<Wall>, Without<Enemy>)>,
+    mut enemy_query: Query<(Entity, &mut Transform, Option<&Collider>, Option<&CollisionLayer>), (With<Enemy>, With<Rollback>)>,
+) {
+    let circuits = powered_circuits(frame.frame, switch_query.iter());
+    if circuits.is_empty() {
+        return;
+    }
+
+    let mut hazards: Vec<_> = hazard_query.iter().collect();
+    hazards.sort_by_key(|(entity, ..)| entity.index());
+
+    let push_scale = FRAME_RATE as f32 * simulation.timestep();
+    for (_, hazard_transform, hazard) in hazards {
+        let HazardConfig::FanTrap { size, push, circuit } = hazard.config else {
+            continue;
+        };
+        if !circuits.contains(&circuit) {
+            continue;
+        }
+
+        let center = hazard_transform.translation.truncate();
+        let mut enemies: Vec<_> = enemy_query.iter_mut().collect();
+        enemies.sort_by_key(|(entity, ..)| entity.index());
+        for (_, mut transform, opt_collider, opt_layer) in enemies {
+            if !in_area(center, size, transform.translation.truncate()) {
+                continue;
+            }
+            let mut pushed = *transform;
+            pushed.translation = round_vec3(transform.translation + (push * push_scale).extend(0.0));
+
+            // Climbing enemies have no collider, nothing block them
+            let blocked = match (opt_collider, opt_layer) {
+                (Some(collider), Some(layer)) => wall_query.iter().any(|(wall_transform, wall_collider, wall_layer)| {
+                    settings.layer_matrix[layer.0 as usize][wall_layer.0 as usize]
+                        && is_colliding(&pushed, collider, wall_transform, wall_collider)
+                }),
+                _ => false,
+            };
+            if !blocked {
+                *transform = pushed;
+            }
+        }
+    }
+}
+
+// Destroyed barrel explode, the explosion is credited to whoever destroyed it
+pub fn rollback_explode_barrels(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    barrel_query: Query<(Entity, &Transform, &HazardComponent, &Death), With<Rollback>>,
+) {
+    let mut barrels: Vec<_> = barrel_query.iter().collect();
+    barrels.sort_by_key(|(entity, ..)| entity.index());
+
+    for (_, transform, hazard, death) in barrels {
+        if let HazardConfig::ExplosiveBarrel { blast_radius, blast_damage, .. } = hazard.config {
+            spawn_explosion(
+                &mut commands,
+                transform.translation.truncate(),
+                blast_radius,
+                blast_damage,
+                death.last_hit_by.clone(),
+                frame.frame,
+            );
+        }
+    }
+}
+
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, setup_map_hazards.in_set(MapSetupSet));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use utils::frame::FrameTimer;
+
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        // Normally added by the GgrsPlugin, needed by add_rollback
+        app.init_resource::<bevy_ggrs::RollbackOrdered>();
+        app.init_resource::<CollisionSettings>();
+        app.add_systems(Update, setup_map_hazards);
+        app
+    }
+
+    #[test]
+    fn test_map_barrel_get_the_components_of_the_code() {
+        let mut app = app();
+        let config = HazardConfig::ExplosiveBarrel { health: 20.0, blast_radius: 150.0, blast_damage: 60.0 };
+        // What the bundle of the map spawn, the config read from the fields and a sprite
+        let entity = app.world_mut().spawn((
+            HazardComponent { config },
+            Sprite::default(),
+            Transform::from_xyz(64.0, 32.0, 0.0),
+        )).id();
+        app.update();
+
+        let world = app.world();
+        assert!(world.get::<Rollback>(entity).is_some());
+        assert!(world.get::<HazardState>(entity).is_some());
+        assert!(world.get::<Collider>(entity).is_some());
+        assert!(world.get::<Flammable>(entity).is_some());
+        assert_eq!(world.get::<Health>(entity).map(|health| health.current), Some(20.0));
+        assert_eq!(world.get::<CollisionLayer>(entity).map(|layer| layer.0), Some(CollisionSettings::default().environment_layer));
+        // Kept from the map
+        assert_eq!(world.get::<Transform>(entity).map(|transform| transform.translation), Some(Vec3::new(64.0, 32.0, 0.0)));
+    }
+
+    #[test]
+    fn test_map_fire_patch_has_no_health() {
+        let mut app = app();
+        let entity = app.world_mut().spawn((HazardComponent::default(), Transform::default())).id();
+        app.update();
+
+        let world = app.world();
+        assert!(world.get::<Rollback>(entity).is_some());
+        assert!(world.get::<HazardState>(entity).is_some());
+        assert!(world.get::<Health>(entity).is_none());
+        assert!(world.get::<Collider>(entity).is_none());
+    }
+
+    #[test]
+    fn test_hazard_is_set_up_once() {
+        let mut app = app();
+        let mut commands = app.world_mut().commands();
+        let entity = spawn_hazard(&mut commands, Vec3::ZERO, HazardConfig::default(), &CollisionSettings::default());
+        app.world_mut().flush();
+        app.world_mut().entity_mut(entity).insert(HazardState { next_trigger_frame: 42 });
+        app.update();
+
+        // Spawned by the code, the system leave it as it is
+        assert_eq!(app.world().get::<HazardState>(entity).map(|state| state.next_trigger_frame), Some(42));
+    }
+
+    // Normally added by the GgrsPlugin, needed by add_rollback
+    fn spawn_rollback(app: &mut App, bundle: impl Bundle) -> Entity {
+        app.init_resource::<bevy_ggrs::RollbackOrdered>();
+        let entity = app.world_mut().commands().spawn(bundle).add_rollback().id();
+        app.world_mut().flush();
+        entity
+    }
+
+    fn powered_switch(app: &mut App, circuit: u32) {
+        spawn_rollback(app, (
+            SwitchComponent { config: map::game::entity::map::switch::SwitchConfig { circuit, ..Default::default() } },
+            SwitchState { powered: FrameTimer::new(0, 10_000), ready: FrameTimer::new(0, 10_000) },
+        ));
+    }
+
+    fn fan_app(tick_rate: u32) -> App {
+        let mut app = App::new();
+        app.insert_resource(FrameCount { frame: 10 });
+        app.insert_resource(SimulationConfig { tick_rate, ..Default::default() });
+        app.init_resource::<CollisionSettings>();
+        app.add_systems(Update, rollback_fan_trap_system);
+        powered_switch(&mut app, 1);
+        spawn_rollback(&mut app, (
+            HazardComponent { config: HazardConfig::FanTrap { size: Vec2::new(400.0, 400.0), push: Vec2::new(4.0, 0.0), circuit: 1 } },
+            Transform::default(),
+        ));
+        app
+    }
+
+    fn spawn_enemy(app: &mut App, position: Vec3) -> Entity {
+        let layer = CollisionSettings::default().enemy_layer;
+        spawn_rollback(app, (
+            Enemy::default(),
+            Transform::from_translation(position),
+            Collider { shape: ColliderShape::Circle { radius: 10.0 }, offset: Vec2::ZERO },
+            CollisionLayer(layer),
+        ))
+    }
+
+    fn position(app: &App, entity: Entity) -> Vec3 {
+        app.world().get::<Transform>(entity).unwrap().translation
+    }
+
+    #[test]
+    fn test_fan_push_is_scaled_to_the_tick_rate() {
+        let mut app = fan_app(FRAME_RATE);
+        let enemy = spawn_enemy(&mut app, Vec3::ZERO);
+        app.update();
+        assert_eq!(position(&app, enemy), Vec3::new(4.0, 0.0, 0.0));
+
+        // Twice the frames, half the push each frame
+        let mut app = fan_app(FRAME_RATE * 2);
+        let enemy = spawn_enemy(&mut app, Vec3::ZERO);
+        app.update();
+        assert_eq!(position(&app, enemy), Vec3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_fan_does_not_push_through_a_wall() {
+        let mut app = fan_app(FRAME_RATE);
+        spawn_rollback(&mut app, (
+            Wall,
+            Transform::from_xyz(18.0, 0.0, 0.0),
+            Collider { shape: ColliderShape::Rectangle { width: 10.0, height: 100.0 }, offset: Vec2::ZERO },
+            CollisionLayer(CollisionSettings::default().wall_layer),
+        ));
+        let blocked = spawn_enemy(&mut app, Vec3::ZERO);
+        // Not against the wall yet
+        let free = spawn_enemy(&mut app, Vec3::new(-100.0, 0.0, 0.0));
+        app.update();
+
+        assert_eq!(position(&app, blocked), Vec3::ZERO);
+        assert_eq!(position(&app, free), Vec3::new(-96.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_fan_off_circuit_does_nothing() {
+        let mut app = fan_app(FRAME_RATE);
+        let enemy = spawn_enemy(&mut app, Vec3::ZERO);
+        app.world_mut().query::<&mut HazardComponent>().single_mut(app.world_mut()).config = HazardConfig::FanTrap { size: Vec2::new(400.0, 400.0), push: Vec2::new(4.0, 0.0), circuit: 2 };
+        app.update();
+        assert_eq!(position(&app, enemy), Vec3::ZERO);
+    }
+
+    fn fence_damage(tick_rate: u32, frames: std::ops::Range<u32>) -> f32 {
+        let mut app = App::new();
+        app.insert_resource(FrameCount { frame: 0 });
+        app.insert_resource(SimulationConfig { tick_rate, ..Default::default() });
+        app.add_systems(Update, rollback_electric_fence_system);
+        powered_switch(&mut app, 1);
+        spawn_rollback(&mut app, (
+            HazardComponent { config: HazardConfig::ElectricFence { size: Vec2::new(100.0, 100.0), damage_per_tick: 10.0, tick_interval_frames: 15, circuit: 1 } },
+            Transform::default(),
+        ));
+        let enemy = spawn_rollback(&mut app, (
+            Enemy::default(),
+            Health { current: 100.0, max: 100.0, invulnerable: None },
+            Transform::default(),
+        ));
+
+        for frame in frames {
+            app.world_mut().resource_mut::<FrameCount>().frame = frame;
+            app.update();
+        }
+        app.world().get::<DamageAccumulator>(enemy).map_or(0.0, |accumulator| accumulator.total_damage)
+    }
+
+    #[test]
+    fn test_fence_interval_is_scaled_to_the_tick_rate() {
+        // A second of game time, 4 ticks at both rates
+        assert_eq!(fence_damage(FRAME_RATE, 1..61), 40.0);
+        assert_eq!(fence_damage(FRAME_RATE * 2, 1..121), 40.0);
+    }
+}
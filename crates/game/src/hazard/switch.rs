@@ -0,0 +1,223 @@
+use std::collections::BTreeSet;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+use map::game::entity::map::{hazard::HazardComponent, switch::{SwitchComponent, SwitchConfig}};
+use utils::frame::{FrameTimer, SimulationConfig};
+
+use crate::{character::player::{input::PreviousInput, jjrs::PeerConfig, Player}, frame::FrameCount, interaction::{find_interactable_in_range, Interactable}, localization::LocalizedText, plugins::{AppState, MapSetupSet}, points::PlayerPoints};
+
+// Switches of the map bought with points, they power the fences and fans of their
+// circuit for a while then need to cool down. Any player can pay for the whole team.
+
+const SWITCH_INTERACTION_RADIUS: f32 = 80.0;
+const SWITCH_SIZE: Vec2 = Vec2::new(24.0, 32.0);
+
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct SwitchState {
+    pub powered: FrameTimer,
+    // Cover the power and the cooldown after it
+    pub ready: FrameTimer,
+}
+
+impl SwitchState {
+    pub fn is_powered(&self, frame: u32) -> bool {
+        self.powered.is_active(frame)
+    }
+
+    pub fn is_ready(&self, frame: u32) -> bool {
+        self.ready.is_done(frame)
+    }
+}
+
+// Circuits with at least one switch powered this frame
+pub fn powered_circuits<'a>(frame: u32, switches: impl Iterator<Item = (&'a SwitchComponent, &'a SwitchState)>) -> BTreeSet<u32> {
+    switches
+        .filter(|(_, state)| state.is_powered(frame))
+        .map(|(switch, _)| switch.config.circuit)
+        .collect()
+}
+
+pub fn spawn_hazard_switch(
+    commands: &mut Commands,
+    position: Vec3,
+    config: SwitchConfig,
+) -> Entity {
+    let mut entity_commands = commands.spawn((
+        Sprite::from_color(Color::srgb(0.3, 0.8, 0.3), SWITCH_SIZE),
+        Transform::from_translation(position),
+    ));
+    insert_switch_components(&mut entity_commands, config);
+
+    entity_commands.add_rollback().id()
+}
+
+// Everything the simulation need on a switch, the sprite and the position are left to
+// the caller, the code or the map
+fn insert_switch_components(entity_commands: &mut EntityCommands, config: SwitchConfig) {
+    entity_commands.insert((
+        SwitchComponent { config },
+        SwitchState::default(),
+        Interactable {
+            radius: SWITCH_INTERACTION_RADIUS,
+            prompt: LocalizedText::new("interaction.power_traps").with_arg("cost", config.cost),
+        },
+    ));
+}
+
+
+// SYSTEMS
+
+// Switches placed in the map only come with their config and their sprite, they get the
+// same components as the ones spawned by the code
+pub fn setup_map_switches(
+    mut commands: Commands,
+    switch_query: Query<(Entity, &SwitchComponent), Without<SwitchState>>,
+) {
+    let mut switches: Vec<_> = switch_query.iter().collect();
+    switches.sort_by_key(|(entity, _)| entity.index());
+
+    for (entity, switch) in switches {
+        let mut entity_commands = commands.entity(entity);
+        insert_switch_components(&mut entity_commands, switch.config);
+        entity_commands.add_rollback();
+    }
+}
+
+// The interaction next to a ready switch power its circuit when the player has the points
+pub fn rollback_use_hazard_switches(
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    mut switch_query: Query<(Entity, &Transform, &Interactable, &SwitchComponent, &mut SwitchState), With<Rollback>>,
+    mut player_query: Query<(&Transform, &Player, &mut PlayerPoints, &PreviousInput), With<Rollback>>,
+) {
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.handle);
+
+    for (transform, player, points, previous_input) in players.iter_mut() {
+        let (input, _input_status) = inputs[player.handle];
+        if !previous_input.interact_just_pressed(&input) {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let ready = switch_query.iter()
+            .filter(|(.., state)| state.is_ready(frame.frame))
+            .map(|(entity, transform, interactable, ..)| (entity, transform, interactable));
+        let Some(entity) = find_interactable_in_range(position, ready) else {
+            continue;
+        };
+        let Ok((_, _, _, switch, mut state)) = switch_query.get_mut(entity) else {
+            continue;
+        };
+        if !points.spend(switch.config.cost) {
+            continue;
+        }
+
+        let powered_frames = simulation.frames(switch.config.powered_frames);
+        state.powered = FrameTimer::new(frame.frame, powered_frames);
+        state.ready = FrameTimer::new(frame.frame, powered_frames + simulation.frames(switch.config.cooldown_frames));
+        info!("player {} powered the circuit {}", player.handle, switch.config.circuit);
+    }
+}
+
+// Green when ready, yellow while powered, red while cooling down. The powered
+// hazards are faded when off
+fn update_switch_sprites(
+    frame: Res<FrameCount>,
+    switch_query: Query<(&SwitchComponent, &SwitchState)>,
+    mut switch_sprites: Query<(&SwitchState, &mut Sprite), Without<HazardComponent>>,
+    mut hazard_sprites: Query<(&HazardComponent, &mut Sprite), Without<SwitchState>>,
+) {
+    for (state, mut sprite) in switch_sprites.iter_mut() {
+        let color = if state.is_powered(frame.frame) {
+            Color::srgb(1.0, 0.85, 0.2)
+        } else if state.is_ready(frame.frame) {
+            Color::srgb(0.3, 0.8, 0.3)
+        } else {
+            Color::srgb(0.7, 0.2, 0.2)
+        };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+
+    let circuits = powered_circuits(frame.frame, switch_query.iter());
+    for (hazard, mut sprite) in hazard_sprites.iter_mut() {
+        let Some(circuit) = hazard.config.circuit() else {
+            continue;
+        };
+        let alpha = if circuits.contains(&circuit) { 0.7 } else { 0.15 };
+        if sprite.color.alpha() != alpha {
+            sprite.color.set_alpha(alpha);
+        }
+    }
+}
+
+
+#[derive(Default)]
+pub struct HazardSwitchPlugin;
+
+impl Plugin for HazardSwitchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, setup_map_switches.in_set(MapSetupSet));
+        app.add_systems(Update, update_switch_sprites.run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn powered_at(start_frame: u32, powered_frames: u32, cooldown_frames: u32) -> SwitchState {
+        SwitchState {
+            powered: FrameTimer::new(start_frame, powered_frames),
+            ready: FrameTimer::new(start_frame, powered_frames + cooldown_frames),
+        }
+    }
+
+    #[test]
+    fn test_switch_power_then_cool_down() {
+        let state = powered_at(100, 60, 120);
+        assert!(state.is_powered(100));
+        assert!(!state.is_ready(100));
+        assert!(state.is_powered(159));
+        // Off but still cooling down
+        assert!(!state.is_powered(160));
+        assert!(!state.is_ready(200));
+        assert!(state.is_ready(280));
+        assert!(SwitchState::default().is_ready(0));
+    }
+
+    #[test]
+    fn test_powered_circuits_only_keep_the_powered_switches() {
+        let on = (SwitchComponent { config: SwitchConfig { circuit: 2, ..Default::default() } }, powered_at(0, 60, 0));
+        let off = (SwitchComponent { config: SwitchConfig { circuit: 3, ..Default::default() } }, SwitchState::default());
+        let same = (SwitchComponent { config: SwitchConfig { circuit: 2, ..Default::default() } }, powered_at(10, 60, 0));
+        let switches = [on, off, same];
+
+        let circuits = powered_circuits(20, switches.iter().map(|(switch, state)| (switch, state)));
+        assert_eq!(circuits.into_iter().collect::<Vec<_>>(), vec![2]);
+        assert!(powered_circuits(100, switches.iter().map(|(switch, state)| (switch, state))).is_empty());
+    }
+
+    #[test]
+    fn test_map_switch_get_the_components_of_the_code() {
+        let mut app = App::new();
+        // Normally added by the GgrsPlugin, needed by add_rollback
+        app.init_resource::<bevy_ggrs::RollbackOrdered>();
+        app.add_systems(Update, setup_map_switches);
+        let config = SwitchConfig { circuit: 4, cost: 750, ..Default::default() };
+        let entity = app.world_mut().spawn((SwitchComponent { config }, Sprite::default(), Transform::default())).id();
+        app.update();
+
+        let world = app.world();
+        assert!(world.get::<Rollback>(entity).is_some());
+        assert!(world.get::<SwitchState>(entity).is_some_and(|state| state.is_ready(0)));
+        assert_eq!(world.get::<Interactable>(entity).map(|interactable| interactable.radius), Some(SWITCH_INTERACTION_RADIUS));
+        assert_eq!(world.get::<SwitchComponent>(entity).map(|switch| switch.config.circuit), Some(4));
+    }
+}
@@ -0,0 +1,335 @@
+pub mod ui;
+
+use std::collections::BTreeMap;
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use utils::{frame::SimulationConfig, persistence};
+
+use crate::{character::{health::Health, player::{control::PlayerAction, LocalPlayer}}, frame::FrameCount, interaction::{find_interactable_in_range, Interactable}, plugins::AppState, rules::{deathmatch::Respawning, GameRules, WaveKind, WaveState}, weapons::{WeaponInventory, WeaponModesState, WeaponState}};
+
+use ui::HintToastPlugin;
+
+// Hints for the new players, presentation only. Triggers look at the local player and
+// ask for a hint, the engine show it as a toast if it was not seen enough times and its
+// cooldown is over. What was seen is saved with the other settings, a veteran see
+// nothing and a new player see each hint a few times at most.
+
+const HINTS_SAVE_KEY: &str = "hints";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HintKind {
+    LowAmmo,
+    FirstDowned,
+    NearBuyable,
+    HordeApproaching,
+}
+
+impl HintKind {
+    pub const ALL: [HintKind; 4] = [HintKind::LowAmmo, HintKind::FirstDowned, HintKind::NearBuyable, HintKind::HordeApproaching];
+
+    // Saved in the seen set
+    pub fn id(&self) -> &'static str {
+        match self {
+            HintKind::LowAmmo => "low_ammo",
+            HintKind::FirstDowned => "first_downed",
+            HintKind::NearBuyable => "near_buyable",
+            HintKind::HordeApproaching => "horde_approaching",
+        }
+    }
+
+    // Key of its text in the language files
+    pub fn text_key(&self) -> &'static str {
+        match self {
+            HintKind::LowAmmo => "hint.low_ammo",
+            HintKind::FirstDowned => "hint.first_downed",
+            HintKind::NearBuyable => "hint.near_buyable",
+            HintKind::HordeApproaching => "hint.horde_approaching",
+        }
+    }
+
+    // Action whose key is put in the `{key}` of the text
+    pub fn action(&self) -> Option<PlayerAction> {
+        match self {
+            HintKind::LowAmmo => Some(PlayerAction::Reload),
+            HintKind::FirstDowned => Some(PlayerAction::Dash),
+            HintKind::NearBuyable => Some(PlayerAction::Interaction),
+            HintKind::HordeApproaching => None,
+        }
+    }
+
+    // Times the hint is shown before it is seen for good
+    pub fn shows(&self) -> u32 {
+        match self {
+            HintKind::LowAmmo | HintKind::NearBuyable => 2,
+            HintKind::FirstDowned | HintKind::HordeApproaching => 1,
+        }
+    }
+
+    // Seconds before the same hint can be shown again
+    pub fn cooldown_seconds(&self) -> f32 {
+        match self {
+            HintKind::LowAmmo => 90.0,
+            HintKind::NearBuyable => 120.0,
+            HintKind::FirstDowned | HintKind::HordeApproaching => 0.0,
+        }
+    }
+}
+
+// Saved, times each hint was shown by its id
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HintProgress {
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub seen: BTreeMap<String, u32>,
+}
+
+impl HintProgress {
+    pub fn is_seen(&self, kind: HintKind) -> bool {
+        self.seen.get(kind.id()).copied().unwrap_or(0) >= kind.shows()
+    }
+
+    pub fn mark_shown(&mut self, kind: HintKind) {
+        *self.seen.entry(kind.id().to_string()).or_default() += 1;
+    }
+
+    // Every hint shown again from the start
+    pub fn reset(&mut self) {
+        self.seen.clear();
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = persistence::save(HINTS_SAVE_KEY, self) {
+            error!("failed to save the hints: {}", err);
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct HintSettings {
+    // Part of the mag left under which the ammo is low
+    pub low_ammo_ratio: f32,
+    // Seconds before a horde wave the hint is given
+    pub horde_warning_seconds: f32,
+    // Seconds between two toasts, whatever the hint
+    pub min_gap_seconds: f32,
+    pub toast_seconds: f32,
+}
+
+impl Default for HintSettings {
+    fn default() -> Self {
+        Self {
+            low_ammo_ratio: 0.25,
+            horde_warning_seconds: 8.0,
+            min_gap_seconds: 6.0,
+            toast_seconds: 6.0,
+        }
+    }
+}
+
+// Sent by the triggers, the engine decide if it is shown
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HintTrigger(pub HintKind);
+
+// Sent by the engine, the toasts show it
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ShowHint(pub HintKind);
+
+// Session only, when each hint was last shown in seconds since the start
+#[derive(Resource, Default)]
+struct HintCooldowns {
+    last_shown: HashMap<HintKind, f32>,
+    last_toast: Option<f32>,
+}
+
+
+// SYSTEMS
+
+fn load_hint_progress(mut commands: Commands) {
+    let progress = match persistence::load::<HintProgress>(HINTS_SAVE_KEY) {
+        Ok(progress) => progress.unwrap_or_default(),
+        Err(err) => {
+            error!("failed to load the hints: {}", err);
+            HintProgress::default()
+        }
+    };
+    commands.insert_resource(progress);
+}
+
+fn trigger_low_ammo_hint(
+    settings: Res<HintSettings>,
+    q_player: Query<&WeaponInventory, With<LocalPlayer>>,
+    weapon_query: Query<(&WeaponState, &WeaponModesState)>,
+    mut triggers: EventWriter<HintTrigger>,
+) {
+    let Ok(inventory) = q_player.get_single() else {
+        return;
+    };
+    let Some((state, modes_state)) = inventory.active_weapon().and_then(|(link, _)| link.get(&weapon_query)) else {
+        return;
+    };
+    let Some(mode) = modes_state.modes.get(&state.active_mode) else {
+        return;
+    };
+    if mode.mag_size > 0 && !inventory.is_reloading() && (mode.mag_ammo as f32) <= mode.mag_size as f32 * settings.low_ammo_ratio {
+        triggers.send(HintTrigger(HintKind::LowAmmo));
+    }
+}
+
+fn trigger_downed_hint(
+    q_player: Query<(&Health, Has<Respawning>), With<LocalPlayer>>,
+    mut triggers: EventWriter<HintTrigger>,
+) {
+    if q_player.iter().any(|(health, respawning)| health.current <= 0.0 || respawning) {
+        triggers.send(HintTrigger(HintKind::FirstDowned));
+    }
+}
+
+fn trigger_buyable_hint(
+    q_player: Query<&Transform, With<LocalPlayer>>,
+    q_interactable: Query<(Entity, &Transform, &Interactable)>,
+    mut triggers: EventWriter<HintTrigger>,
+) {
+    let Ok(transform) = q_player.get_single() else {
+        return;
+    };
+    if find_interactable_in_range(transform.translation.truncate(), q_interactable.iter()).is_some() {
+        triggers.send(HintTrigger(HintKind::NearBuyable));
+    }
+}
+
+fn trigger_horde_hint(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    settings: Res<HintSettings>,
+    rules: Res<GameRules>,
+    wave_state: Res<WaveState>,
+    mut triggers: EventWriter<HintTrigger>,
+) {
+    if !rules.has_waves() || wave_state.wave == 0 || rules.wave_kind(wave_state.wave + 1) != WaveKind::Horde {
+        return;
+    }
+    let remaining = wave_state.timer.remaining_seconds(frame.frame, &simulation);
+    if remaining > 0.0 && remaining <= settings.horde_warning_seconds {
+        triggers.send(HintTrigger(HintKind::HordeApproaching));
+    }
+}
+
+// Hint to show among the triggered ones at `now`, marked as shown
+fn pick_hint(kinds: &[HintKind], now: f32, settings: &HintSettings, progress: &mut HintProgress, cooldowns: &mut HintCooldowns) -> Option<HintKind> {
+    if progress.disabled || kinds.is_empty() {
+        return None;
+    }
+    if cooldowns.last_toast.map_or(false, |last| now - last < settings.min_gap_seconds) {
+        return None;
+    }
+    // One at a time, the first of the list win
+    let kind = HintKind::ALL.into_iter()
+        .filter(|kind| kinds.contains(kind))
+        .find(|kind| !progress.is_seen(*kind) && cooldowns.last_shown.get(kind).map_or(true, |last| now - last >= kind.cooldown_seconds()))?;
+
+    cooldowns.last_shown.insert(kind, now);
+    cooldowns.last_toast = Some(now);
+    progress.mark_shown(kind);
+    Some(kind)
+}
+
+fn select_hints(
+    time: Res<Time<Real>>,
+    settings: Res<HintSettings>,
+    mut progress: ResMut<HintProgress>,
+    mut cooldowns: ResMut<HintCooldowns>,
+    mut triggers: EventReader<HintTrigger>,
+    mut show: EventWriter<ShowHint>,
+) {
+    let kinds: Vec<HintKind> = triggers.read().map(|trigger| trigger.0).collect();
+    if let Some(kind) = pick_hint(&kinds, time.elapsed_secs(), &settings, &mut progress, &mut cooldowns) {
+        progress.save();
+        show.send(ShowHint(kind));
+    }
+}
+
+
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HintSettings>()
+            .register_type::<HintSettings>()
+            .init_resource::<HintCooldowns>()
+            .add_event::<HintTrigger>()
+            .add_event::<ShowHint>()
+            .add_systems(Startup, load_hint_progress)
+            .add_systems(Update, (
+                (trigger_low_ammo_hint, trigger_downed_hint, trigger_buyable_hint, trigger_horde_hint),
+                select_hints,
+            ).chain().run_if(in_state(AppState::InGame)))
+            .add_plugins(HintToastPlugin);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pick(kinds: &[HintKind], now: f32, progress: &mut HintProgress, cooldowns: &mut HintCooldowns) -> Option<HintKind> {
+        pick_hint(kinds, now, &HintSettings::default(), progress, cooldowns)
+    }
+
+    #[test]
+    fn test_progress_seen_after_its_shows() {
+        let mut progress = HintProgress::default();
+        progress.mark_shown(HintKind::LowAmmo);
+        assert!(!progress.is_seen(HintKind::LowAmmo));
+        progress.mark_shown(HintKind::LowAmmo);
+        assert!(progress.is_seen(HintKind::LowAmmo));
+        progress.mark_shown(HintKind::FirstDowned);
+        assert!(progress.is_seen(HintKind::FirstDowned));
+
+        progress.reset();
+        assert!(!progress.is_seen(HintKind::LowAmmo));
+    }
+
+    #[test]
+    fn test_progress_saved_format() {
+        let mut progress = HintProgress::default();
+        progress.mark_shown(HintKind::NearBuyable);
+        let saved = ron::to_string(&progress).unwrap();
+        assert_eq!(ron::from_str::<HintProgress>(&saved).unwrap(), progress);
+        // An old save without the fields is still read
+        assert_eq!(ron::from_str::<HintProgress>("()").unwrap(), HintProgress::default());
+    }
+
+    #[test]
+    fn test_select_first_of_the_list_then_wait_the_gap() {
+        let mut progress = HintProgress::default();
+        let mut cooldowns = HintCooldowns::default();
+        assert_eq!(pick(&[HintKind::NearBuyable, HintKind::LowAmmo], 10.0, &mut progress, &mut cooldowns), Some(HintKind::LowAmmo));
+        // Under the 6 seconds between two toasts
+        assert_eq!(pick(&[HintKind::NearBuyable], 15.0, &mut progress, &mut cooldowns), None);
+        assert_eq!(pick(&[HintKind::NearBuyable], 16.0, &mut progress, &mut cooldowns), Some(HintKind::NearBuyable));
+    }
+
+    #[test]
+    fn test_select_respect_cooldown_and_seen() {
+        let mut progress = HintProgress::default();
+        let mut cooldowns = HintCooldowns::default();
+        assert_eq!(pick(&[HintKind::LowAmmo], 0.0, &mut progress, &mut cooldowns), Some(HintKind::LowAmmo));
+        // Its own cooldown of 90 seconds
+        assert_eq!(pick(&[HintKind::LowAmmo], 60.0, &mut progress, &mut cooldowns), None);
+        assert_eq!(pick(&[HintKind::LowAmmo], 90.0, &mut progress, &mut cooldowns), Some(HintKind::LowAmmo));
+        // Shown twice, seen for good
+        assert_eq!(pick(&[HintKind::LowAmmo], 500.0, &mut progress, &mut cooldowns), None);
+    }
+
+    #[test]
+    fn test_select_nothing_when_disabled() {
+        let mut progress = HintProgress { disabled: true, ..default() };
+        let mut cooldowns = HintCooldowns::default();
+        assert_eq!(pick(&[HintKind::LowAmmo], 0.0, &mut progress, &mut cooldowns), None);
+        assert!(progress.seen.is_empty());
+    }
+}
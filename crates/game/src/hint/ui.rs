@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::InputMap;
+
+use crate::{character::player::{control::{keyboard_label, PlayerAction}, LocalPlayer}, hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState};
+
+use super::{HintKind, HintSettings, ShowHint};
+
+// Toasts stacked under the wave in the top right corner, the oldest go away first
+const MAX_TOASTS: usize = 3;
+const FADE_SECONDS: f32 = 0.5;
+
+#[derive(Component)]
+struct HintToastList;
+
+#[derive(Component)]
+struct HintToast {
+    timer: Timer,
+}
+
+
+// Text of the hint with the key bound to its action by the local player
+pub fn hint_text(kind: HintKind, localization: &Localization, input_map: Option<&InputMap<PlayerAction>>) -> String {
+    let Some(action) = kind.action() else {
+        return localization.text(kind.text_key());
+    };
+    let key = input_map.and_then(|map| keyboard_label(map, action)).unwrap_or_else(|| format!("{:?}", action));
+    localization.format(kind.text_key(), &[("key", &key)])
+}
+
+
+// SYSTEMS
+
+fn setup_hint_toasts(mut commands: Commands) {
+    commands.spawn((
+        HintToastList,
+        HudSlot::new(HudAnchor::TopRight, 4),
+        Node {
+            width: Val::Px(300.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+    ));
+}
+
+fn spawn_hint_toasts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<HintSettings>,
+    localization: Res<Localization>,
+    mut events: EventReader<ShowHint>,
+    q_player: Query<&InputMap<PlayerAction>, With<LocalPlayer>>,
+    q_list: Query<(Entity, Option<&Children>), With<HintToastList>>,
+) {
+    let Ok((list, children)) = q_list.get_single() else {
+        events.clear();
+        return;
+    };
+    let mut count = children.map_or(0, |children| children.len());
+    let input_map = q_player.get_single().ok();
+
+    for event in events.read() {
+        if count >= MAX_TOASTS {
+            if let Some(oldest) = children.and_then(|children| children.first()) {
+                commands.entity(*oldest).despawn_recursive();
+            }
+        }
+        count += 1;
+
+        commands.entity(list).with_children(|parent| {
+            parent.spawn((
+                HintToast { timer: Timer::from_seconds(settings.toast_seconds, TimerMode::Once) },
+                Node {
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+            )).with_children(|toast| {
+                toast.spawn((
+                    Text::new(hint_text(event.0, &localization, input_map)),
+                    TextFont {
+                        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                        font_size: 14.0,
+                        ..Default::default()
+                    },
+                    TextColor(Color::srgb(1.0, 0.95, 0.7)),
+                ));
+            });
+        });
+    }
+}
+
+fn fade_hint_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_toast: Query<(Entity, &mut HintToast, &mut BackgroundColor, &Children)>,
+    mut q_text: Query<&mut TextColor>,
+) {
+    for (entity, mut toast, mut background, children) in q_toast.iter_mut() {
+        toast.timer.tick(time.delta());
+        if toast.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let alpha = (toast.timer.remaining_secs() / FADE_SECONDS).min(1.0);
+        background.0 = background.0.with_alpha(0.7 * alpha);
+        for child in children.iter() {
+            if let Ok(mut color) = q_text.get_mut(*child) {
+                color.0 = color.0.with_alpha(alpha);
+            }
+        }
+    }
+}
+
+
+pub struct HintToastPlugin;
+
+impl Plugin for HintToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_hint_toasts);
+        app.add_systems(Update, (spawn_hint_toasts, fade_hint_toasts).run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+
+    use super::*;
+    use crate::character::player::control::get_input_map;
+
+    fn localization() -> Localization {
+        let strings = HashMap::from([
+            ("hint.low_ammo".to_string(), "[{key}] to reload".to_string()),
+            ("hint.horde_approaching".to_string(), "A horde is coming".to_string()),
+        ]);
+        Localization::new("en".to_string(), strings, HashMap::default())
+    }
+
+    #[test]
+    fn test_hint_text_use_the_binding() {
+        let mut map = get_input_map();
+        let default_key = keyboard_label(&map, PlayerAction::Reload).unwrap();
+        assert_eq!(hint_text(HintKind::LowAmmo, &localization(), Some(&map)), format!("[{}] to reload", default_key));
+
+        map.clear_action(&PlayerAction::Reload);
+        map.insert(PlayerAction::Reload, KeyCode::KeyT);
+        assert_eq!(hint_text(HintKind::LowAmmo, &localization(), Some(&map)), "[T] to reload");
+    }
+
+    #[test]
+    fn test_hint_text_without_key() {
+        assert_eq!(hint_text(HintKind::LowAmmo, &localization(), None), "[Reload] to reload");
+        assert_eq!(hint_text(HintKind::HordeApproaching, &localization(), None), "A horde is coming");
+    }
+}
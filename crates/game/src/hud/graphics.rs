@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::{EguiContexts, EguiPlugin}, egui};
+use serde::{Deserialize, Serialize};
+use utils::persistence;
+
+use crate::{localization::Localization, plugins::AppState};
+
+// Options of the presentation that cost the most on a slow machine, saved with the
+// persistence module. The corpses, the marks on the map and the sparks read their caps
+// here, lowering them never touch the simulation.
+
+pub const GRAPHICS_OPTIONS_KEY: KeyCode = KeyCode::Backquote;
+const GRAPHICS_PREFERENCES_SAVE_KEY: &str = "graphics";
+
+// Bounds of the sliders
+const MAX_CORPSE_SECONDS: f32 = 120.0;
+const MAX_CORPSES: usize = 200;
+const MAX_DECALS: usize = 500;
+const MAX_PARTICLE_DENSITY: f32 = 2.0;
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsPreferences {
+    // Seconds a corpse stay on the ground, 0 remove them at once
+    pub corpse_seconds: f32,
+    pub max_corpses: usize,
+    // For each kind of mark, the bullet holes, the scorches and the footprints
+    pub max_decals: usize,
+    // Multiply the sparks and the gibs, 0 for none
+    pub particle_density: f32,
+    pub damage_numbers: bool,
+}
+
+impl Default for GraphicsPreferences {
+    fn default() -> Self {
+        Self {
+            corpse_seconds: 20.0,
+            max_corpses: 40,
+            max_decals: 200,
+            particle_density: 1.0,
+            damage_numbers: true,
+        }
+    }
+}
+
+impl GraphicsPreferences {
+    pub fn save(&self) {
+        if let Err(err) = persistence::save(GRAPHICS_PREFERENCES_SAVE_KEY, self) {
+            error!("failed to save the graphics options: {}", err);
+        }
+    }
+
+    // Count of things to spawn for a density of 1, the fraction is kept by the caller
+    pub fn particles(&self, count: f32) -> f32 {
+        count * self.particle_density.max(0.0)
+    }
+}
+
+#[derive(Resource, Default)]
+struct GraphicsOptionsState {
+    is_open: bool,
+}
+
+
+// SYSTEMS
+
+fn load_graphics_preferences(mut commands: Commands) {
+    let preferences = match persistence::load::<GraphicsPreferences>(GRAPHICS_PREFERENCES_SAVE_KEY) {
+        Ok(preferences) => preferences.unwrap_or_default(),
+        Err(err) => {
+            error!("failed to load the graphics options: {}", err);
+            GraphicsPreferences::default()
+        }
+    };
+    commands.insert_resource(preferences);
+}
+
+fn toggle_graphics_options(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<GraphicsOptionsState>,
+) {
+    if keyboard_input.just_pressed(GRAPHICS_OPTIONS_KEY) {
+        state.is_open = !state.is_open;
+    }
+}
+
+fn graphics_options_panel(
+    mut contexts: EguiContexts,
+    mut state: ResMut<GraphicsOptionsState>,
+    mut preferences: ResMut<GraphicsPreferences>,
+    localization: Res<Localization>,
+) {
+    if !state.is_open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut edited = preferences.clone();
+    let mut is_open = state.is_open;
+    egui::Window::new(localization.text("menu.graphics")).id(egui::Id::new("graphics_options")).open(&mut is_open).default_pos((10.0, 160.0)).show(ctx, |ui| {
+        ui.add(egui::Slider::new(&mut edited.corpse_seconds, 0.0..=MAX_CORPSE_SECONDS).text(localization.text("menu.corpse_seconds")));
+        ui.add(egui::Slider::new(&mut edited.max_corpses, 0..=MAX_CORPSES).text(localization.text("menu.max_corpses")));
+        ui.add(egui::Slider::new(&mut edited.max_decals, 0..=MAX_DECALS).text(localization.text("menu.max_decals")));
+        ui.add(egui::Slider::new(&mut edited.particle_density, 0.0..=MAX_PARTICLE_DENSITY).text(localization.text("menu.particle_density")));
+        ui.checkbox(&mut edited.damage_numbers, localization.text("menu.damage_numbers"));
+        ui.separator();
+        if ui.button(localization.text("menu.reset")).clicked() {
+            edited = GraphicsPreferences::default();
+        }
+    });
+    state.is_open = is_open;
+
+    if edited != *preferences {
+        *preferences = edited;
+        preferences.save();
+    }
+}
+
+
+pub struct GraphicsOptionsPlugin;
+
+impl Plugin for GraphicsOptionsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<GraphicsPreferences>();
+        app.init_resource::<GraphicsOptionsState>();
+        app.add_systems(Startup, load_graphics_preferences);
+        app.add_systems(Update, (toggle_graphics_options, graphics_options_panel).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particles_follow_the_density() {
+        let mut preferences = GraphicsPreferences { particle_density: 0.5, ..default() };
+        assert_eq!(preferences.particles(4.0), 2.0);
+        preferences.particle_density = -1.0;
+        assert_eq!(preferences.particles(4.0), 0.0);
+    }
+
+    #[test]
+    fn test_saved_options_missing_fields_keep_the_defaults() {
+        // Saved before an option existed
+        let preferences: GraphicsPreferences = ron::de::from_str("(max_corpses: 5, damage_numbers: false)").unwrap();
+        assert_eq!(preferences.max_corpses, 5);
+        assert!(!preferences.damage_numbers);
+        assert_eq!(preferences.max_decals, GraphicsPreferences::default().max_decals);
+    }
+}
@@ -0,0 +1,257 @@
+pub mod graphics;
+pub mod options;
+
+use bevy::{prelude::*, ui::UiSystem, window::PrimaryWindow};
+use serde::{Deserialize, Serialize};
+
+use graphics::GraphicsOptionsPlugin;
+use options::{HudOptionsPlugin, HudPreferences};
+
+// Layout of the HUD from the corners and the edges of the window instead of pixels
+// from the top left. An element say in which anchor it goes and its order from the
+// edge, the anchors are kept inside the safe area and the whole interface is scaled
+// with the size of the window and the scale chosen by the player.
+
+// Size the HUD was made for, at scale 1
+pub const REFERENCE_RESOLUTION: Vec2 = Vec2::new(1280.0, 720.0);
+// A small window still readable, an ultrawide not giant
+const MIN_RESOLUTION_SCALE: f32 = 0.6;
+const MAX_RESOLUTION_SCALE: f32 = 2.0;
+// Between two elements of an anchor
+const ANCHOR_GAP: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HudAnchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl HudAnchor {
+    pub const ALL: [HudAnchor; 7] = [
+        HudAnchor::TopLeft, HudAnchor::TopCenter, HudAnchor::TopRight,
+        HudAnchor::Center,
+        HudAnchor::BottomLeft, HudAnchor::BottomCenter, HudAnchor::BottomRight,
+    ];
+
+    fn is_bottom(&self) -> bool {
+        matches!(self, HudAnchor::BottomLeft | HudAnchor::BottomCenter | HudAnchor::BottomRight)
+    }
+
+    // Container of the anchor, the insets are set from the safe area after
+    fn node(&self) -> Node {
+        let (left, right) = match self {
+            HudAnchor::TopLeft | HudAnchor::BottomLeft => (Val::Px(0.0), Val::Auto),
+            HudAnchor::TopRight | HudAnchor::BottomRight => (Val::Auto, Val::Px(0.0)),
+            HudAnchor::TopCenter | HudAnchor::BottomCenter | HudAnchor::Center => (Val::Px(0.0), Val::Px(0.0)),
+        };
+        let (top, bottom) = match self {
+            HudAnchor::Center => (Val::Px(0.0), Val::Px(0.0)),
+            anchor if anchor.is_bottom() => (Val::Auto, Val::Px(0.0)),
+            _ => (Val::Px(0.0), Val::Auto),
+        };
+        let align_items = match self {
+            HudAnchor::TopLeft | HudAnchor::BottomLeft => AlignItems::FlexStart,
+            HudAnchor::TopRight | HudAnchor::BottomRight => AlignItems::FlexEnd,
+            _ => AlignItems::Center,
+        };
+        Node {
+            position_type: PositionType::Absolute,
+            left,
+            right,
+            top,
+            bottom,
+            // The first element is the closest to the edge
+            flex_direction: if self.is_bottom() { FlexDirection::ColumnReverse } else { FlexDirection::Column },
+            justify_content: if *self == HudAnchor::Center { JustifyContent::Center } else { JustifyContent::FlexStart },
+            align_items,
+            row_gap: Val::Px(ANCHOR_GAP),
+            ..default()
+        }
+    }
+}
+
+// Put on a HUD element instead of an absolute position, it is moved in its anchor
+#[derive(Component, Clone, Copy, Debug)]
+pub struct HudSlot {
+    pub anchor: HudAnchor,
+    // From the edge of the window, the lowest first
+    pub order: i32,
+}
+
+impl HudSlot {
+    pub fn new(anchor: HudAnchor, order: i32) -> Self {
+        Self { anchor, order }
+    }
+}
+
+// Part of the window that can be hidden, a notch or the border of a TV, in pixels of the
+// window. The margin of the player is added on top
+#[derive(Resource, Reflect, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[reflect(Resource)]
+pub struct SafeArea {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+// Scale of the interface for a window, 1 at the reference resolution. The smallest side
+// decide so an ultrawide is scaled by its height
+pub fn resolution_scale(size: Vec2) -> f32 {
+    let scale = (size / REFERENCE_RESOLUTION).min_element();
+    if !scale.is_finite() || scale <= 0.0 {
+        return 1.0;
+    }
+    scale.clamp(MIN_RESOLUTION_SCALE, MAX_RESOLUTION_SCALE)
+}
+
+// Position in pixels of the window to the pixels of the nodes, they are multiplied by UiScale
+pub fn window_to_ui(position: Vec2, ui_scale: &UiScale) -> Vec2 {
+    position / ui_scale.0.max(f32::EPSILON)
+}
+
+#[derive(Component)]
+struct HudAnchorRoot(HudAnchor);
+
+
+// SYSTEMS
+
+fn setup_hud_anchors(mut commands: Commands) {
+    for anchor in HudAnchor::ALL {
+        commands.spawn((HudAnchorRoot(anchor), anchor.node(), PickingBehavior::IGNORE));
+    }
+}
+
+fn attach_hud_slots(
+    mut commands: Commands,
+    q_roots: Query<(Entity, &HudAnchorRoot)>,
+    q_slots: Query<(Entity, &HudSlot), Added<HudSlot>>,
+) {
+    for (entity, slot) in q_slots.iter() {
+        if let Some((root, _)) = q_roots.iter().find(|(_, root)| root.0 == slot.anchor) {
+            commands.entity(entity).set_parent(root);
+        }
+    }
+}
+
+// In the order of the slots, the elements are spawned by many plugins in any order
+fn sort_hud_anchors(
+    mut q_roots: Query<&mut Children, (With<HudAnchorRoot>, Changed<Children>)>,
+    q_slots: Query<&HudSlot>,
+) {
+    for mut children in q_roots.iter_mut() {
+        let order = |entity: &Entity| q_slots.get(*entity).map_or(i32::MAX, |slot| slot.order);
+        if children.windows(2).all(|pair| order(&pair[0]) <= order(&pair[1])) {
+            continue;
+        }
+        children.sort_by_key(order);
+    }
+}
+
+// A hidden element keep its place in a flex container, it is taken out of the layout
+// so the others of the anchor close the gap
+fn collapse_hidden_slots(
+    mut q_slots: Query<(&Visibility, &mut Node), (With<HudSlot>, Changed<Visibility>)>,
+) {
+    for (visibility, mut node) in q_slots.iter_mut() {
+        let display = if *visibility == Visibility::Hidden { Display::None } else { Display::Flex };
+        if node.display != display {
+            node.display = display;
+        }
+    }
+}
+
+fn apply_ui_scale(
+    preferences: Res<HudPreferences>,
+    q_window: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
+    mut ui_scale: ResMut<UiScale>,
+    mut last_size: Local<Vec2>,
+) {
+    if let Ok(window) = q_window.get_single() {
+        *last_size = window.size();
+    }
+    let scale = preferences.ui_scale * resolution_scale(*last_size);
+    if (ui_scale.0 - scale).abs() > f32::EPSILON {
+        ui_scale.0 = scale;
+    }
+}
+
+// The insets are in pixels of the window, the nodes are scaled
+fn apply_safe_area(
+    safe_area: Res<SafeArea>,
+    preferences: Res<HudPreferences>,
+    ui_scale: Res<UiScale>,
+    mut q_roots: Query<(Ref<HudAnchorRoot>, &mut Node)>,
+) {
+    let changed = safe_area.is_changed() || preferences.is_changed() || ui_scale.is_changed();
+    let margin = preferences.safe_margin;
+    let inset = |side: f32| Val::Px((side + margin) / ui_scale.0.max(f32::EPSILON));
+
+    for (root, mut node) in q_roots.iter_mut() {
+        if !changed && !root.is_added() {
+            continue;
+        }
+        let base = root.0.node();
+        node.left = if base.left == Val::Auto { Val::Auto } else { inset(safe_area.left) };
+        node.right = if base.right == Val::Auto { Val::Auto } else { inset(safe_area.right) };
+        node.top = if base.top == Val::Auto { Val::Auto } else { inset(safe_area.top) };
+        node.bottom = if base.bottom == Val::Auto { Val::Auto } else { inset(safe_area.bottom) };
+    }
+}
+
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SafeArea>()
+            .register_type::<SafeArea>()
+            .add_plugins(HudOptionsPlugin)
+            .add_plugins(GraphicsOptionsPlugin)
+            .add_systems(Startup, setup_hud_anchors)
+            .add_systems(Update, apply_ui_scale)
+            .add_systems(PostUpdate, (attach_hud_slots, sort_hud_anchors, collapse_hidden_slots, apply_safe_area).chain().before(UiSystem::Layout));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolution_scale_is_1_at_the_reference() {
+        assert_eq!(resolution_scale(REFERENCE_RESOLUTION), 1.0);
+        assert_eq!(resolution_scale(REFERENCE_RESOLUTION * 1.5), 1.5);
+    }
+
+    #[test]
+    fn test_resolution_scale_use_the_smallest_side() {
+        // Ultrawide, the height decide
+        assert_eq!(resolution_scale(Vec2::new(3440.0, 1440.0)), 2.0);
+        assert_eq!(resolution_scale(Vec2::new(2560.0, 1080.0)), 1.5);
+        // Portrait phone, the width decide
+        assert_eq!(resolution_scale(Vec2::new(960.0, 2000.0)), 0.75);
+    }
+
+    #[test]
+    fn test_resolution_scale_is_clamped() {
+        assert_eq!(resolution_scale(Vec2::new(320.0, 180.0)), MIN_RESOLUTION_SCALE);
+        assert_eq!(resolution_scale(REFERENCE_RESOLUTION * 4.0), MAX_RESOLUTION_SCALE);
+        // Minimized window
+        assert_eq!(resolution_scale(Vec2::ZERO), 1.0);
+        assert_eq!(resolution_scale(Vec2::new(1280.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_window_to_ui_undo_the_scale() {
+        assert_eq!(window_to_ui(Vec2::new(200.0, 100.0), &UiScale(2.0)), Vec2::new(100.0, 50.0));
+        assert_eq!(window_to_ui(Vec2::new(200.0, 100.0), &UiScale(1.0)), Vec2::new(200.0, 100.0));
+        assert!(window_to_ui(Vec2::ONE, &UiScale(0.0)).is_finite());
+    }
+}
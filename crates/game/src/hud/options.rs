@@ -0,0 +1,168 @@
+use bevy::{prelude::*, window::{PrimaryWindow, WindowResolution}};
+use bevy_inspector_egui::{bevy_egui::{EguiContexts, EguiPlugin}, egui};
+use serde::{Deserialize, Serialize};
+use utils::persistence;
+
+use crate::{global_asset::GlobalAsset, lobby::voice::VoiceSettings, localization::{available_languages, LanguageFile, LanguagePreferences, Localization}, plugins::AppState};
+
+use super::resolution_scale;
+
+// Accessibility options of the interface, saved with the persistence module. The scale
+// multiply the one coming from the size of the window, the margin keep the HUD away
+// from the border for the screens that cut it. The language is chosen here too, it is
+// saved on its own by the localization module. The mutes of the voice chat are edited
+// in their own window but saved here.
+
+pub const HUD_OPTIONS_KEY: KeyCode = KeyCode::KeyY;
+const HUD_PREFERENCES_SAVE_KEY: &str = "hud";
+
+// Bounds of the sliders
+const UI_SCALE_RANGE: (f32, f32) = (0.5, 2.0);
+const MAX_SAFE_MARGIN: f32 = 100.0;
+
+// To check the layout, from the smallest supported to the ultrawide
+const PREVIEW_RESOLUTIONS: [(f32, f32); 4] = [
+    (1280.0, 720.0),
+    (1920.0, 1080.0),
+    (2560.0, 1440.0),
+    (3440.0, 1440.0),
+];
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HudPreferences {
+    pub ui_scale: f32,
+    // Pixels of the window between the HUD and the border, over the safe area
+    pub safe_margin: f32,
+    #[serde(default)]
+    pub voice: VoiceSettings,
+}
+
+impl Default for HudPreferences {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            safe_margin: 10.0,
+            voice: VoiceSettings::default(),
+        }
+    }
+}
+
+impl HudPreferences {
+    pub fn save(&self) {
+        if let Err(err) = persistence::save(HUD_PREFERENCES_SAVE_KEY, self) {
+            error!("failed to save the hud options: {}", err);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct HudOptionsState {
+    is_open: bool,
+}
+
+
+// SYSTEMS
+
+fn load_hud_preferences(mut commands: Commands) {
+    let preferences = match persistence::load::<HudPreferences>(HUD_PREFERENCES_SAVE_KEY) {
+        Ok(preferences) => preferences.unwrap_or_default(),
+        Err(err) => {
+            error!("failed to load the hud options: {}", err);
+            HudPreferences::default()
+        }
+    };
+    commands.insert_resource(preferences);
+}
+
+fn toggle_hud_options(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<HudOptionsState>,
+) {
+    if keyboard_input.just_pressed(HUD_OPTIONS_KEY) {
+        state.is_open = !state.is_open;
+    }
+}
+
+fn hud_options_panel(
+    mut contexts: EguiContexts,
+    mut state: ResMut<HudOptionsState>,
+    mut preferences: ResMut<HudPreferences>,
+    mut language: ResMut<LanguagePreferences>,
+    localization: Res<Localization>,
+    global_assets: Res<GlobalAsset>,
+    language_files: Res<Assets<LanguageFile>>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !state.is_open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut edited = preferences.clone();
+    let mut is_open = state.is_open;
+    let mut preview = None;
+    let window_size = q_window.get_single().map(|window| window.size()).unwrap_or_default();
+    let mut edited_language = language.language.clone();
+    let languages = available_languages(&global_assets, &language_files);
+    // The title change with the language, the id keep the window in place
+    egui::Window::new(localization.text("menu.interface")).id(egui::Id::new("interface_options")).open(&mut is_open).default_pos((10.0, 120.0)).show(ctx, |ui| {
+        ui.add(egui::Slider::new(&mut edited.ui_scale, UI_SCALE_RANGE.0..=UI_SCALE_RANGE.1).text(localization.text("menu.ui_scale")));
+        ui.add(egui::Slider::new(&mut edited.safe_margin, 0.0..=MAX_SAFE_MARGIN).text(localization.text("menu.safe_margin")));
+        let selected = edited_language.as_ref()
+            .and_then(|code| languages.iter().find(|(known, _)| known == code))
+            .map_or(localization.text("menu.language_auto"), |(_, name)| name.clone());
+        egui::ComboBox::from_label(localization.text("menu.language")).selected_text(selected).show_ui(ui, |ui| {
+            ui.selectable_value(&mut edited_language, None, localization.text("menu.language_auto"));
+            for (code, name) in languages.iter() {
+                ui.selectable_value(&mut edited_language, Some(code.clone()), name);
+            }
+        });
+        ui.separator();
+        ui.label(format!(
+            "window {}x{}, scale {:.2}",
+            window_size.x, window_size.y, edited.ui_scale * resolution_scale(window_size),
+        ));
+        ui.horizontal(|ui| {
+            for (width, height) in PREVIEW_RESOLUTIONS {
+                if ui.button(format!("{}x{}", width, height)).clicked() {
+                    preview = Some(WindowResolution::new(width, height));
+                }
+            }
+        });
+        ui.separator();
+        if ui.button(localization.text("menu.reset")).clicked() {
+            // Only the interface, the mutes stay
+            edited = HudPreferences { voice: edited.voice.clone(), ..Default::default() };
+        }
+    });
+    state.is_open = is_open;
+
+    if let (Some(resolution), Ok(mut window)) = (preview, q_window.get_single_mut()) {
+        window.resolution = resolution;
+    }
+    if edited != *preferences {
+        *preferences = edited;
+        preferences.save();
+    }
+    if edited_language != language.language {
+        language.language = edited_language;
+        language.save();
+    }
+}
+
+
+pub struct HudOptionsPlugin;
+
+impl Plugin for HudOptionsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<HudOptionsState>();
+        app.add_systems(Startup, load_hud_preferences);
+        app.add_systems(Update, (toggle_hud_options, hud_options_panel).chain().run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,33 @@
+pub mod ui;
+
+use bevy::prelude::*;
+
+use crate::localization::LocalizedText;
+
+
+// Something in the world the player can interact with when close enough
+#[derive(Component, Clone, Debug)]
+pub struct Interactable {
+    pub radius: f32,
+    // Formatted by the UI, the simulation doesn't pick the language
+    pub prompt: LocalizedText,
+}
+
+// Find the closest interactable in range of the position, ties are broken with the entity index
+// so all peers select the same one
+pub fn find_interactable_in_range<'a>(
+    position: Vec2,
+    interactables: impl Iterator<Item = (Entity, &'a Transform, &'a Interactable)>,
+) -> Option<Entity> {
+    interactables
+        .filter_map(|(entity, transform, interactable)| {
+            let distance = transform.translation.truncate().distance(position);
+            (distance <= interactable.radius).then_some((entity, distance))
+        })
+        .min_by(|(entity_a, distance_a), (entity_b, distance_b)| {
+            distance_a.partial_cmp(distance_b)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(entity_a.index().cmp(&entity_b.index()))
+        })
+        .map(|(entity, _)| entity)
+}
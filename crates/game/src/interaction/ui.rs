@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::InputMap;
+
+use crate::{character::player::{control::{keyboard_label, PlayerAction}, LocalPlayer}, hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState};
+
+use super::{find_interactable_in_range, Interactable};
+
+
+#[derive(Component)]
+struct InteractionPromptText;
+
+
+fn setup_interaction_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    commands.spawn((
+        InteractionPromptText,
+        Text::new(""),
+        TextFont {
+            font,
+            font_size: 18.0,
+            ..Default::default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        HudSlot::new(HudAnchor::BottomCenter, 4),
+    ));
+}
+
+fn update_interaction_prompt(
+    localization: Res<Localization>,
+    q_player: Query<(&Transform, &InputMap<PlayerAction>), With<LocalPlayer>>,
+    q_interactable: Query<(Entity, &Transform, &Interactable)>,
+    mut q_text: Query<&mut Text, With<InteractionPromptText>>,
+) {
+    let Ok(mut text) = q_text.get_single_mut() else {
+        return;
+    };
+
+    let Ok((player_transform, input_map)) = q_player.get_single() else {
+        text.0 = String::new();
+        return;
+    };
+
+    // The key bound by the player, a gamepad only binding show the action name
+    let key = keyboard_label(input_map, PlayerAction::Interaction).unwrap_or_else(|| format!("{:?}", PlayerAction::Interaction));
+    text.0 = find_interactable_in_range(player_transform.translation.truncate(), q_interactable.iter())
+        .and_then(|entity| q_interactable.get(entity).ok())
+        .map_or(String::new(), |(_, _, interactable)| localization.format("interaction.prompt", &[("key", &key), ("prompt", &localization.localize(&interactable.prompt))]));
+}
+
+
+#[derive(Default)]
+pub struct InteractionUIPlugin;
+
+impl Plugin for InteractionUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_interaction_ui);
+        app.add_systems(Update, update_interaction_prompt.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -3,16 +3,19 @@ use std::net::SocketAddr;
 use animation::SpriteSheetConfig;
 use bevy::prelude::*;
 use bevy_ggrs::{ggrs::PlayerType, prelude::*};
-use bevy_matchbox::{prelude::PeerState, MatchboxSocket};
+use bevy_matchbox::{prelude::{PeerId, PeerState, WebRtcSocketBuilder}, MatchboxSocket};
 use ggrs::UdpNonBlockingSocket;
-use map::game::entity::map::enemy_spawn::EnemySpawnerComponent;
-use utils::rng::RollbackRng;
+use map::game::entity::map::{ambient::AmbientZoneConfig, climb::ClimbableWallConfig, destructible::{DestructibleConfig, DestructibleKind}, enemy_spawn::EnemySpawnerComponent, equipment::EquipmentKind, grading::GradingZoneConfig, hazard::HazardConfig, surface::{SurfaceKind, SurfaceZoneConfig}, switch::SwitchConfig};
 
-use crate::{character::{config::CharacterConfig, enemy::{spawning::EnemySpawnerState}, player::{create::create_player, jjrs::PeerConfig}}, collider::{spawn_test_wall, CollisionSettings}, global_asset::GlobalAsset, plugins::AppState, weapons::{WeaponAsset, WeaponsConfig}};
+use crate::{audio::ambient::spawn_ambient_zone, camera::grading::spawn_grading_zone, destructible::spawn_destructible, door::{spawn_door, Door}, equipment::spawn_equipment_pickup, lobby::{identity::{Identity, PlayerIdentity}, moderation::LobbyModeration, resolve_room, LobbyRefused}, hazard::{spawn_hazard, switch::spawn_hazard_switch}, rules::{dropin::{DropInPlace, DropInPlaces}, objective::spawn_generator, GameMode, GameRules}, tutorial::spawn_tutorial_map, character::{config::CharacterConfig, footprint::spawn_surface_zone, enemy::{climb::spawn_climbable_wall, spawning::EnemySpawnerState}, player::{create::{create_player, DEFAULT_PLAYER_CLASS}, jjrs::PeerConfig, source::{input_source_from_config, KeyboardMouseSource, LocalInputSources}}}, collider::{spawn_test_wall, CollisionSettings}, global_asset::GlobalAsset, plugins::AppState, practice::{killcam::KillCamMode, PracticeMode}, rng::insert_rng_streams, progression::{PlayerLoadout, PlayerProgress, ProgressionConfig}, weapons::{upgrade::spawn_upgrade_station, WeaponAsset, WeaponsConfig}};
 
+#[derive(Clone, Debug)]
 pub struct GggrsConnectionConfiguration {
     pub max_player: usize,
     pub input_delay: usize,
+    // Frames a peer can run ahead of the confirmed inputs
+    pub max_prediction: usize,
+    // Frames between two checksum comparisons, 0 to disable them
     pub desync_interval: u32,
     pub socket: bool,
     pub udp_port: u16
@@ -25,6 +28,53 @@ pub struct GggrsSessionConfiguration {
     pub lobby: String,
     pub connection: GggrsConnectionConfiguration,
     pub players: Vec<String>,
+    // Class of each player handle, a missing entry use the default class. Online only
+    // the first one is used, the class of the local player sent in the lobby
+    pub classes: Vec<String>,
+    // Name and color of each player handle offline, online the first ones are the
+    // identity of the local player sent in the lobby
+    pub names: Vec<String>,
+    pub colors: Vec<usize>,
+    // Game mode and its rules, agreed on in the lobby
+    pub rules: GameRules,
+    // Map to play, one of the lobby KNOWN_MAPS
+    pub map: String,
+    // Seed of the rollback rng
+    pub seed: u32,
+    // Password checked by the host of the lobby, advisory only
+    pub password: Option<String>,
+    // Offline only, snapshots kept locally to rewind the game
+    pub practice: bool,
+    // Headless recorders expected in the room on top of the players
+    pub recorders: usize,
+    // This peer is a headless recorder, it spectate the session instead of playing
+    pub recorder: bool,
+    // Online only, this peer take its place as a spectator and drop in at a wave
+    pub dropin: bool,
+    // Offline only, the last seconds before a death of the local player are replayed
+    pub kill_cam: bool,
+}
+
+impl GggrsSessionConfiguration {
+    pub fn player_class(&self, handle: usize) -> &str {
+        self.classes.get(handle).map_or(DEFAULT_PLAYER_CLASS, |class| class.as_str())
+    }
+
+    pub fn player_identity(&self, handle: usize) -> Identity {
+        let fallback = Identity::fallback(handle);
+        Identity::new(
+            self.names.get(handle).filter(|name| !name.is_empty()).unwrap_or(&fallback.name),
+            self.colors.get(handle).copied().unwrap_or(fallback.color),
+        )
+    }
+
+    pub fn local_identity(&self) -> Identity {
+        self.player_identity(0)
+    }
+
+    pub fn local_class(&self) -> &str {
+        self.player_class(0)
+    }
 }
 
 
@@ -42,28 +92,68 @@ pub fn setup_ggrs_local(
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     sprint_sheet_assets: Res<Assets<SpriteSheetConfig>>,
     session_config: Res<GggrsSessionConfiguration>,
+    progression: Res<ProgressionConfig>,
+    progress: Res<PlayerProgress>,
+    mut exit: EventWriter<AppExit>,
 ) {
-
+    // Every local source before spawning anything, a player without its source would stand still
+    let sources: Result<Vec<_>, _> = session_config.players.iter().map(|addr| input_source_from_config(addr)).collect();
+    let sources = match sources {
+        Ok(sources) => sources,
+        Err(err) => {
+            error!("{}", err);
+            exit.send(AppExit::error());
+            return;
+        }
+    };
 
     let mut sess_build = SessionBuilder::<PeerConfig>::new()
         .with_num_players(session_config.connection.max_player)
-        .with_desync_detection_mode(ggrs::DesyncDetection::On { interval: session_config.connection.desync_interval })
+        .with_desync_detection_mode(session_config.connection.desync_detection())
+        .with_max_prediction_window(session_config.connection.max_prediction)
         .with_input_delay(session_config.connection.input_delay);
 
-    for (i, addr) in session_config.players.iter().enumerate() {
-        let local = addr == "localhost";
-        if local {
+    let mut input_sources = LocalInputSources::default();
+    let mut identities = PlayerIdentity::default();
+
+    for ((i, addr), source) in session_config.players.iter().enumerate().zip(sources) {
+        if let Some(source) = source {
             sess_build = sess_build
                 .add_player(PlayerType::Local, i)
                 .expect("Failed to add player");
+            input_sources.insert(i, source);
         } else {
             let remote_addr: SocketAddr = addr.parse().unwrap();
             //sess_build = sess_build.add_player(PlayerType::Remote(remote_addr), i).expect("Failed to add player");
         }
-        create_player(&mut commands, &global_assets, &weapons_asset,  &character_asset, &collision_settings, &asset_server, &mut texture_atlas_layouts, &sprint_sheet_assets, local, i);
+        // Only the keyboard player is controlled and followed by the camera, other local handles are feed by their source
+        let local = addr == "localhost";
+        let loadout = if local { progress.loadout(&progression) } else { PlayerLoadout::default() };
+        identities.insert(i, session_config.player_identity(i));
+        create_player(&mut commands, &global_assets, &weapons_asset,  &character_asset, &collision_settings, &asset_server, &mut texture_atlas_layouts, &sprint_sheet_assets, local, i, identities.color(i), session_config.player_class(i), &loadout);
     }
 
-    spawn_test_map(&mut commands, &collision_settings);
+    commands.insert_resource(input_sources);
+    commands.insert_resource(identities);
+    commands.insert_resource(session_config.rules.clone());
+
+    if session_config.rules.mode == GameMode::Tutorial {
+        spawn_tutorial_map(&mut commands, &collision_settings);
+    } else {
+        spawn_test_map(&mut commands, &collision_settings, &session_config.rules);
+    }
+
+    // The rewind and the kill cam put the state back without GGRS, a synctest rollback
+    // would load its own snapshot from before them
+    if session_config.practice || session_config.kill_cam {
+        sess_build = sess_build.with_check_distance(0);
+    }
+    if session_config.practice {
+        commands.insert_resource(PracticeMode::default());
+    }
+    if session_config.kill_cam {
+        commands.insert_resource(KillCamMode::default());
+    }
 
    // Start a synctest session
     let sess = if session_config.connection.socket == false {
@@ -81,7 +171,7 @@ pub fn setup_ggrs_local(
     };
 
     // Insert the GGRS session resource
-    commands.insert_resource(RollbackRng::new(12345));
+    insert_rng_streams(&mut commands, session_config.seed);
     commands.insert_resource(sess);
 
     app_state.set(AppState::InGame);
@@ -92,9 +182,31 @@ pub fn setup_ggrs_local(
 // For matchbox socket connection
 
 
-pub fn start_matchbox_socket(mut commands: Commands, ggrs_config: Res<GggrsSessionConfiguration>) {
-    let url = format!("{}/{}?next={}", ggrs_config.matchbox_url, ggrs_config.lobby, ggrs_config.connection.max_player);
-    commands.insert_resource(MatchboxSocket::new_unreliable(url));
+pub fn start_matchbox_socket(mut commands: Commands, mut ggrs_config: ResMut<GggrsSessionConfiguration>) {
+    if ggrs_config.practice {
+        warn!("ignoring the practice mode in an online game");
+    }
+    if ggrs_config.kill_cam {
+        warn!("ignoring the kill cam in an online game");
+    }
+    let (room, options) = match resolve_room(&ggrs_config) {
+        Ok(room) => room,
+        Err(err) => {
+            error!("refusing to join the lobby {}: {}", ggrs_config.lobby, err);
+            commands.insert_resource(LobbyRefused(err));
+            return;
+        }
+    };
+    options.apply(&mut ggrs_config);
+    ggrs_config.connection.clamp();
+    info!("joining room {}", room);
+
+    let url = format!("{}/{}?next={}", ggrs_config.matchbox_url, room, ggrs_config.connection.max_player + ggrs_config.recorders);
+    // Channel 0 for GGRS, LOBBY_CHANNEL for the moderation messages
+    let builder = WebRtcSocketBuilder::new(url)
+        .add_unreliable_channel()
+        .add_reliable_channel();
+    commands.insert_resource(MatchboxSocket::from(builder));
 
 }
 
@@ -109,8 +221,10 @@ pub fn wait_for_players(
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     sprint_sheet_assets: Res<Assets<SpriteSheetConfig>>,
     session_config: Res<GggrsSessionConfiguration>,
+    moderation: Res<LobbyModeration>,
 
-    mut commands: Commands, global_assets: Res<GlobalAsset>, weapons_asset: Res<Assets<WeaponsConfig>>, mut socket: ResMut<MatchboxSocket>, ggrs_config: Res<GggrsSessionConfiguration>
+    mut commands: Commands, global_assets: Res<GlobalAsset>, weapons_asset: Res<Assets<WeaponsConfig>>, mut socket: ResMut<MatchboxSocket>, ggrs_config: Res<GggrsSessionConfiguration>,
+    mut exit: EventWriter<AppExit>,
 ) {
     // regularly call update_peers to update the list of connected peers
     let Ok(peer_changes) = socket.try_update_peers() else {
@@ -126,13 +240,44 @@ pub fn wait_for_players(
             PeerState::Disconnected => info!("peer {peer} disconnected"),
         }
     }
-    let players = socket.players();
-
     let num_players = ggrs_config.connection.max_player;
-    if players.len() < num_players {
+    if socket.players().len() < num_players + ggrs_config.recorders {
         return; // wait for more players
     }
 
+    // Wait for the host to accept everyone
+    let Some(local) = socket.id() else {
+        return;
+    };
+    let connected: Vec<PeerId> = socket.connected_peers().collect();
+    if !moderation.is_ready(local, &connected) {
+        return;
+    }
+
+    // The recorders don't get a player handle, the first player send them the inputs
+    let players: Vec<PlayerType<PeerId>> = socket.players().into_iter()
+        .filter(|player| match player {
+            PlayerType::Remote(peer) => !moderation.is_recorder(*peer),
+            _ => !moderation.is_recorder(local),
+        })
+        .collect();
+    if players.len() != num_players {
+        warn!("{} players for {} places, waiting for the recorders to join", players.len(), num_players);
+        return;
+    }
+    let first_player = players.iter()
+        .map(|player| match player {
+            PlayerType::Remote(peer) => *peer,
+            _ => local,
+        })
+        .min();
+    // A recorder in a room without players has nothing to record
+    if session_config.recorder && first_player.is_none() {
+        error!("no player to spectate, the recorder stop");
+        exit.send(AppExit::error());
+        return;
+    }
+
     info!("All peers have joined, going in-game");
     // TODO
 
@@ -140,8 +285,22 @@ pub fn wait_for_players(
     // create a GGRS P2P session
     let mut session_builder = ggrs::SessionBuilder::<PeerConfig>::new()
         .with_num_players(num_players)
-        .with_max_prediction_window(12)
+        .with_desync_detection_mode(ggrs_config.connection.desync_detection())
+        .with_max_prediction_window(ggrs_config.connection.max_prediction)
         .with_input_delay(ggrs_config.connection.input_delay);
+    info!("starting the session with {:?}", ggrs_config.connection);
+
+    let mut input_sources = LocalInputSources::default();
+    let mut identities = PlayerIdentity::default();
+    let mut drop_ins = DropInPlaces::default();
+
+    if first_player == Some(local) {
+        for (i, recorder) in moderation.recorders.iter().enumerate() {
+            session_builder = session_builder
+                .add_player(PlayerType::Spectator(*recorder), num_players + i)
+                .expect("failed to add recorder");
+        }
+    }
 
     for (i, player) in players.into_iter().enumerate() {
         session_builder = session_builder
@@ -149,30 +308,70 @@ pub fn wait_for_players(
             .expect("failed to add player");
 
         let is_local = matches!(player, PlayerType::Local);
+        if is_local {
+            input_sources.insert(i, Box::new(KeyboardMouseSource::default()));
+        }
 
-        create_player(&mut commands, &global_assets, &weapons_asset,  &character_asset, &collision_settings, &asset_server, &mut texture_atlas_layouts, &sprint_sheet_assets, is_local, i);
+        // The loadouts, identities and classes of the host roster, the same on every peer
+        let peer = match player {
+            PlayerType::Remote(peer) => peer,
+            _ => local,
+        };
+        let loadout = moderation.loadout(peer);
+        identities.insert(i, moderation.identity(peer).unwrap_or_else(|| Identity::fallback(i)));
+        // The handle is kept for when the spectator join
+        if moderation.is_dropin(peer) {
+            drop_ins.places.insert(i, DropInPlace::from_roster(&moderation, peer));
+            if is_local {
+                drop_ins.local = Some(i);
+            }
+            continue;
+        }
+        create_player(&mut commands, &global_assets, &weapons_asset,  &character_asset, &collision_settings, &asset_server, &mut texture_atlas_layouts, &sprint_sheet_assets, is_local, i, identities.color(i), moderation.class(peer), &loadout);
     }
 
-    spawn_test_map(&mut commands, &collision_settings);
+    commands.insert_resource(input_sources);
+    commands.insert_resource(identities);
+    commands.insert_resource(drop_ins);
+    commands.insert_resource(session_config.rules.clone());
+
+    spawn_test_map(&mut commands, &collision_settings, &session_config.rules);
 
     // move the channel out of the socket (required because GGRS takes ownership of it)
     let channel = socket.take_channel(0).unwrap();
 
     // start the GGRS session
-    let ggrs_session = session_builder
-        .start_p2p_session(channel)
-        .expect("failed to start session");
+    let ggrs_session = match first_player.filter(|_| session_config.recorder) {
+        Some(host) => {
+            info!("recording the session from {}", host);
+            Session::Spectator(session_builder.start_spectator_session(host, channel))
+        }
+        None => Session::P2P(session_builder
+            .start_p2p_session(channel)
+            .expect("failed to start session")),
+    };
 
 
-    commands.insert_resource(RollbackRng::new(12345));
-    commands.insert_resource(bevy_ggrs::Session::P2P(ggrs_session));
+    insert_rng_streams(&mut commands, session_config.seed);
+    commands.insert_resource(ggrs_session);
 
     app_state.set(AppState::InGame);
 }
 
 pub fn log_ggrs_events(
     mut session: ResMut<bevy_ggrs::Session<PeerConfig>>,
+    mut exit: EventWriter<AppExit>,
 ) {
+        // A recorder stop with the player it spectate, what was recorded is written on exit
+        if let Session::Spectator(session) = session.as_mut() {
+            for event in session.events() {
+                info!("GGRS Event: {:?}", event);
+                if let GgrsEvent::Disconnected { addr } = event {
+                    error!("Spectated player@{:?} disconnected", addr);
+                    exit.send(AppExit::Success);
+                }
+            }
+        }
         if let Session::P2P(session) = session.as_mut() {
             for event in session.events() {
                 info!("GGRS Event: {:?}", event);
@@ -201,6 +400,7 @@ pub fn log_ggrs_events(
 fn spawn_test_map(
     commands: &mut Commands,
     collision_settings: &Res<CollisionSettings>,
+    rules: &GameRules,
 ) {
     spawn_test_wall(
         commands,
@@ -217,6 +417,50 @@ fn spawn_test_map(
         Color::rgb(0.6, 0.3, 0.3), // Reddish color
     );
 
+    spawn_upgrade_station(commands, Vec3::new(0.0, 400.0, 0.0), 5000);
+
+    if rules.has_generator() {
+        spawn_generator(commands, rules, collision_settings);
+    }
+
+    spawn_hazard(commands, Vec3::new(-250.0, -300.0, 0.0), HazardConfig::default(), &collision_settings);
+    spawn_hazard(commands, Vec3::new(250.0, -300.0, 0.0), HazardConfig::ElectricTrap { radius: 40.0, stun_frames: 60, cooldown_frames: 300 }, &collision_settings);
+    spawn_hazard(commands, Vec3::new(0.0, -450.0, 0.0), HazardConfig::ExplosiveBarrel { health: 20.0, blast_radius: 150.0, blast_damage: 60.0 }, &collision_settings);
+
+    // Fence and fan under the barrel, both on the circuit of the switch
+    spawn_hazard(commands, Vec3::new(0.0, -650.0, 0.0), HazardConfig::ElectricFence { size: Vec2::new(400.0, 30.0), damage_per_tick: 10.0, tick_interval_frames: 15, circuit: 1 }, &collision_settings);
+    spawn_hazard(commands, Vec3::new(0.0, -800.0, 0.0), HazardConfig::FanTrap { size: Vec2::new(400.0, 200.0), push: Vec2::new(0.0, -4.0), circuit: 1 }, &collision_settings);
+    spawn_hazard_switch(commands, Vec3::new(-250.0, -550.0, 0.0), SwitchConfig { circuit: 1, ..Default::default() });
+
+    // Weak part under the right wall and a bit of cover in the middle
+    spawn_destructible(commands, Vec3::new(500.0, -75.0, 0.0), DestructibleConfig { kind: DestructibleKind::WeakWall, destructible: true, health: 200.0, size: Vec2::new(125.0, 150.0), flammable: false }, &collision_settings);
+    spawn_destructible(commands, Vec3::new(-150.0, -150.0, 0.0), DestructibleConfig::default(), &collision_settings);
+    spawn_destructible(commands, Vec3::new(150.0, -150.0, 0.0), DestructibleConfig { destructible: false, ..Default::default() }, &collision_settings);
+    // Crate on the edge of the fire patch, the fire crawl from it to the cover
+    spawn_destructible(commands, Vec3::new(-250.0, -260.0, 0.0), DestructibleConfig::default(), &collision_settings);
+
+    // Door under the left wall, the weak wall on the right is the other way
+    spawn_door(commands, Vec3::new(-500.0, -75.0, 0.0), Door::new(Vec2::new(125.0, 150.0), 750), &collision_settings);
+
+    // Low wall closing the top of the two walls, the zombies from the north climb it
+    spawn_climbable_wall(commands, Vec3::new(0.0, 512.0, 0.0), ClimbableWallConfig { size: Vec2::new(1125.0, 24.0), ..Default::default() }, &collision_settings);
+
+    spawn_equipment_pickup(commands, EquipmentKind::ArmorVest, Vec3::new(-150.0, 150.0, 0.0), None);
+    spawn_equipment_pickup(commands, EquipmentKind::SpeedBoots, Vec3::new(0.0, 150.0, 0.0), None);
+    spawn_equipment_pickup(commands, EquipmentKind::Backpack, Vec3::new(150.0, 150.0, 0.0), None);
+
+    spawn_ambient_zone(commands, Vec2::ZERO, AmbientZoneConfig { ambience: "wind".to_string(), volume: 0.6, size: Vec2::new(3000.0, 3000.0) });
+    spawn_ambient_zone(commands, Vec2::new(0.0, 400.0), AmbientZoneConfig { ambience: "hum".to_string(), volume: 1.0, size: Vec2::new(300.0, 200.0) });
+    spawn_ambient_zone(commands, Vec2::new(0.0, -375.0), AmbientZoneConfig { ambience: "drip".to_string(), volume: 0.8, size: Vec2::new(700.0, 250.0) });
+
+    // Cold and dark in the drip tunnel, warm glow near the machines
+    spawn_grading_zone(commands, Vec2::new(0.0, -375.0), GradingZoneConfig { preset: "tunnel".to_string(), size: Vec2::new(700.0, 250.0) });
+    spawn_grading_zone(commands, Vec2::new(0.0, 400.0), GradingZoneConfig { preset: "machines".to_string(), size: Vec2::new(300.0, 200.0) });
+
+    // Puddles near the spawn to walk through
+    spawn_surface_zone(commands, Vec2::new(-300.0, 50.0), SurfaceZoneConfig { surface: SurfaceKind::Blood, size: Vec2::new(80.0, 60.0) });
+    spawn_surface_zone(commands, Vec2::new(300.0, 50.0), SurfaceZoneConfig { surface: SurfaceKind::Mud, size: Vec2::new(120.0, 90.0) });
+
     let spawn_positions = [
         Vec3::new(-1000., -1000., 0.0),
         Vec3::new(-1000., 1000., 0.0),
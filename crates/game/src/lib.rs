@@ -3,8 +3,35 @@ pub mod character;
 pub mod jjrs;
 pub mod camera;
 pub mod frame;
+pub mod rng;
 pub mod audio;
 pub mod global_asset;
 pub mod weapons;
 pub mod collider;
-pub mod debug;
\ No newline at end of file
+pub mod debug;
+pub mod points;
+pub mod powerup;
+pub mod bullet_time;
+pub mod capture;
+pub mod recorder;
+pub mod budget;
+pub mod trade;
+pub mod equipment;
+pub mod interaction;
+pub mod hazard;
+pub mod hint;
+pub mod hud;
+pub mod localization;
+pub mod barricade;
+pub mod door;
+pub mod destructible;
+pub mod fog;
+pub mod lighting;
+pub mod telemetry;
+pub mod rules;
+pub mod lobby;
+pub mod network;
+pub mod tutorial;
+pub mod progression;
+pub mod practice;
+pub mod web;
@@ -0,0 +1,357 @@
+use bevy::{prelude::*, render::render_resource::{AsBindGroup, ShaderRef, ShaderType}, sprite::{AlphaMode2d, Material2d, Material2dPlugin}};
+use bevy_ggrs::ConfirmedFrameCount;
+use map::game::entity::map::hazard::{HazardComponent, HazardConfig};
+use utils::events::RollbackEvents;
+
+use crate::{camera::GameCamera, character::player::{input::CursorPosition, Player}, frame::{confirmed_frame, FrameCount}, plugins::AppState, weapons::{explosion::ExplosionEvent, WeaponFiredEvent}};
+
+// Lighting is presentation only, nothing here touch the rollback state. A darkness
+// overlay follow the camera and the shader cut holes in it around the lights.
+// Flashes are spawned from the rollback events once their frame is confirmed so a
+// mispredicted shot never light the screen, the flashlights follow the aim.
+
+pub const MAX_LIGHTS: usize = 64;
+
+const LIGHTING_SHADER_PATH: &str = "shaders/lighting.wgsl";
+
+#[derive(Resource, Clone, Debug)]
+pub struct LightingSettings {
+    pub enabled: bool,
+    pub flashlights: bool,
+    // Color of the darkness, alpha is how dark the unlit areas are
+    pub ambient: Color,
+    pub flashlight_radius: f32,
+    // Half of the opening of the flashlight cone, in radians
+    pub flashlight_half_angle: f32,
+    pub flashlight_intensity: f32,
+    pub muzzle_flash_radius: f32,
+    pub muzzle_flash_seconds: f32,
+    // Radius of the explosion light relative to the explosion radius
+    pub explosion_light_scale: f32,
+    pub explosion_flash_seconds: f32,
+}
+
+impl Default for LightingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            flashlights: true,
+            ambient: Color::srgba(0.02, 0.02, 0.06, 0.9),
+            flashlight_radius: 700.0,
+            flashlight_half_angle: 0.45,
+            flashlight_intensity: 1.0,
+            muzzle_flash_radius: 220.0,
+            muzzle_flash_seconds: 0.06,
+            explosion_light_scale: 2.5,
+            explosion_flash_seconds: 0.4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LightCone {
+    // Angle of the center of the cone, in radians
+    pub direction: f32,
+    pub half_angle: f32,
+}
+
+// Light source, other systems spawn it with a Transform or add it to an existing entity
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Light2d {
+    pub color: Color,
+    pub radius: f32,
+    pub intensity: f32,
+    pub cone: Option<LightCone>,
+    // Amount of random variation of the intensity, 0 for a steady light
+    pub flicker: f32,
+}
+
+impl Light2d {
+    pub fn point(color: Color, radius: f32, intensity: f32) -> Self {
+        Self { color, radius, intensity, cone: None, flicker: 0.0 }
+    }
+}
+
+// Light that fade out and despawn with its entity when the timer finish
+#[derive(Component)]
+pub struct LightFlash {
+    pub timer: Timer,
+    pub intensity: f32,
+}
+
+// Flashlight of a player, the light follow the player and its aim
+#[derive(Component)]
+pub struct Flashlight {
+    pub player_entity: Entity,
+}
+
+pub fn spawn_light_flash(commands: &mut Commands, position: Vec2, light: Light2d, seconds: f32) -> Entity {
+    commands.spawn((
+        light,
+        LightFlash { timer: Timer::from_seconds(seconds, TimerMode::Once), intensity: light.intensity },
+        Transform::from_translation(position.extend(0.0)),
+    )).id()
+}
+
+#[derive(Clone, Copy, Debug, Default, ShaderType)]
+pub struct LightUniform {
+    // xy: position, z: radius, w: intensity
+    pub position: Vec4,
+    pub color: Vec4,
+    // xy: direction of the cone, z: cos of the half angle, w: 1 for a cone
+    pub cone: Vec4,
+}
+
+#[derive(Clone, Debug, ShaderType)]
+pub struct LightingUniform {
+    pub ambient: Vec4,
+    // x: number of lights, yzw unused
+    pub params: Vec4,
+    pub lights: [LightUniform; MAX_LIGHTS],
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct LightingMaterial {
+    #[uniform(0)]
+    pub uniform: LightingUniform,
+}
+
+impl Material2d for LightingMaterial {
+    fn fragment_shader() -> ShaderRef {
+        LIGHTING_SHADER_PATH.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+}
+
+#[derive(Component)]
+pub struct LightingOverlay;
+
+// Last frame whose rollback events were turned into flashes
+#[derive(Resource, Default)]
+pub struct LightEventCursor {
+    last_read_frame: Option<u32>,
+}
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightingSettings>()
+            .init_resource::<LightEventCursor>()
+            .add_plugins(Material2dPlugin::<LightingMaterial>::default())
+            .add_systems(OnEnter(AppState::InGame), setup_lighting_overlay)
+            .add_systems(Update, (
+                toggle_lighting_system,
+                spawn_confirmed_flashes_system,
+                sync_hazard_lights_system,
+                sync_flashlights_system,
+                update_flashlights_system,
+                fade_light_flashes_system,
+                update_lighting_overlay_system,
+            ).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+fn setup_lighting_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LightingMaterial>>,
+) {
+    commands.spawn((
+        LightingOverlay,
+        // Big enough to cover the screen at max zoom out, follow the camera
+        Mesh2d(meshes.add(Rectangle::new(20000.0, 20000.0))),
+        MeshMaterial2d(materials.add(LightingMaterial {
+            uniform: LightingUniform { ambient: Vec4::ZERO, params: Vec4::ZERO, lights: [LightUniform::default(); MAX_LIGHTS] },
+        })),
+        // Under the fog of war
+        Transform::from_xyz(0.0, 0.0, 49.0),
+        Visibility::Hidden,
+    ));
+}
+
+fn toggle_lighting_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<LightingSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        settings.enabled = !settings.enabled;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        settings.flashlights = !settings.flashlights;
+    }
+}
+
+fn spawn_confirmed_flashes_system(
+    mut commands: Commands,
+    settings: Res<LightingSettings>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    mut cursor: ResMut<LightEventCursor>,
+    fired_events: Res<RollbackEvents<WeaponFiredEvent>>,
+    explosion_events: Res<RollbackEvents<ExplosionEvent>>,
+) {
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    if cursor.last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+
+    if settings.enabled {
+        for (_, event) in fired_events.read_after(cursor.last_read_frame).filter(|(f, _)| *f <= confirmed_frame) {
+            spawn_light_flash(
+                &mut commands,
+                event.position,
+                Light2d::point(Color::srgb(1.0, 0.85, 0.5), settings.muzzle_flash_radius, 1.0),
+                settings.muzzle_flash_seconds,
+            );
+        }
+        for (_, event) in explosion_events.read_after(cursor.last_read_frame).filter(|(f, _)| *f <= confirmed_frame) {
+            spawn_light_flash(
+                &mut commands,
+                event.position,
+                Light2d::point(Color::srgb(1.0, 0.55, 0.2), event.radius * settings.explosion_light_scale, 1.5),
+                settings.explosion_flash_seconds,
+            );
+        }
+    }
+
+    cursor.last_read_frame = Some(confirmed_frame);
+}
+
+// Fire patches glow as long as they exist
+fn sync_hazard_lights_system(
+    mut commands: Commands,
+    hazard_query: Query<(Entity, &HazardComponent), Added<HazardComponent>>,
+) {
+    for (entity, hazard) in hazard_query.iter() {
+        if let HazardConfig::FirePatch { radius, .. } = hazard.config {
+            commands.entity(entity).insert(Light2d {
+                flicker: 0.2,
+                ..Light2d::point(Color::srgb(1.0, 0.45, 0.1), radius * 3.0, 0.8)
+            });
+        }
+    }
+}
+
+fn sync_flashlights_system(
+    mut commands: Commands,
+    player_query: Query<Entity, With<Player>>,
+    flashlight_query: Query<(Entity, &Flashlight)>,
+) {
+    for (entity, flashlight) in flashlight_query.iter() {
+        if !player_query.contains(flashlight.player_entity) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for player_entity in player_query.iter() {
+        if flashlight_query.iter().any(|(_, flashlight)| flashlight.player_entity == player_entity) {
+            continue;
+        }
+        commands.spawn((
+            Flashlight { player_entity },
+            Light2d::point(Color::srgb(1.0, 0.95, 0.8), 0.0, 0.0),
+            Transform::default(),
+        ));
+    }
+}
+
+fn update_flashlights_system(
+    settings: Res<LightingSettings>,
+    player_query: Query<(&Transform, &CursorPosition), (With<Player>, Without<Flashlight>)>,
+    mut flashlight_query: Query<(&Flashlight, &mut Light2d, &mut Transform)>,
+) {
+    for (flashlight, mut light, mut transform) in flashlight_query.iter_mut() {
+        let Ok((player_transform, cursor)) = player_query.get(flashlight.player_entity) else {
+            continue;
+        };
+        transform.translation = player_transform.translation;
+
+        let aim = Vec2::new(cursor.x as f32, cursor.y as f32);
+        light.radius = settings.flashlight_radius;
+        light.intensity = if settings.flashlights { settings.flashlight_intensity } else { 0.0 };
+        // Keep the last direction when the player is not aiming
+        if aim.length_squared() > 1.0 {
+            light.cone = Some(LightCone { direction: aim.y.atan2(aim.x), half_angle: settings.flashlight_half_angle });
+        } else if light.cone.is_none() {
+            light.cone = Some(LightCone { direction: 0.0, half_angle: settings.flashlight_half_angle });
+        }
+    }
+}
+
+fn fade_light_flashes_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Light2d, &mut LightFlash)>,
+) {
+    for (entity, mut light, mut flash) in query.iter_mut() {
+        flash.timer.tick(time.delta());
+        if flash.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        light.intensity = flash.intensity * flash.timer.fraction_remaining();
+    }
+}
+
+fn update_lighting_overlay_system(
+    settings: Res<LightingSettings>,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<LightingMaterial>>,
+    q_camera: Query<&Transform, (With<GameCamera>, Without<LightingOverlay>)>,
+    q_lights: Query<(&Light2d, &GlobalTransform)>,
+    mut q_overlay: Query<(&mut Transform, &mut Visibility, &MeshMaterial2d<LightingMaterial>), With<LightingOverlay>>,
+) {
+    let Ok((mut transform, mut visibility, material_handle)) = q_overlay.get_single_mut() else {
+        return;
+    };
+
+    if !settings.enabled {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    let Ok(camera_transform) = q_camera.get_single() else {
+        return;
+    };
+    transform.translation.x = camera_transform.translation.x;
+    transform.translation.y = camera_transform.translation.y;
+    let camera_pos = camera_transform.translation.truncate();
+
+    // Closest lights first when there is more than the shader can take
+    let mut lights: Vec<(f32, LightUniform)> = q_lights.iter()
+        .filter(|(light, _)| light.intensity > 0.0 && light.radius > 0.0)
+        .map(|(light, global_transform)| {
+            let position = global_transform.translation().truncate();
+            let flicker = if light.flicker > 0.0 {
+                1.0 - light.flicker * (0.5 + 0.5 * (time.elapsed_secs() * 17.0 + position.x * 0.13).sin() * (time.elapsed_secs() * 7.0).cos())
+            } else {
+                1.0
+            };
+            let cone = light.cone.map_or(Vec4::ZERO, |cone| {
+                Vec4::new(cone.direction.cos(), cone.direction.sin(), cone.half_angle.cos(), 1.0)
+            });
+            let uniform = LightUniform {
+                position: Vec4::new(position.x, position.y, light.radius, light.intensity * flicker),
+                color: light.color.to_linear().to_vec4(),
+                cone,
+            };
+            (position.distance_squared(camera_pos), uniform)
+        })
+        .collect();
+    lights.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    lights.truncate(MAX_LIGHTS);
+
+    if let Some(material) = materials.get_mut(&material_handle.0) {
+        material.uniform.ambient = settings.ambient.to_linear().to_vec4();
+        material.uniform.params = Vec4::new(lights.len() as f32, 0.0, 0.0, 0.0);
+        for (i, slot) in material.uniform.lights.iter_mut().enumerate() {
+            *slot = lights.get(i).map_or(LightUniform::default(), |(_, light)| *light);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::{prelude::*, utils::HashMap};
+use ggrs::PlayerHandle;
+use serde::{Deserialize, Serialize};
+
+// Name and color of each player, chosen before the game. Offline they come from the
+// session configuration, online every peer send its own to the host of the lobby and
+// get the one of everyone back with the roster. The skin stay in the loadout.
+
+pub const MAX_NAME_LENGTH: usize = 16;
+
+// Colors a player can pick, the index is what is exchanged
+pub const IDENTITY_COLORS: &[(f32, f32, f32)] = &[
+    (0.9, 0.25, 0.25),
+    (0.3, 0.5, 1.0),
+    (0.3, 0.85, 0.35),
+    (0.95, 0.85, 0.3),
+    (0.7, 0.4, 0.95),
+    (1.0, 0.6, 0.2),
+    (0.3, 0.9, 0.9),
+    (1.0, 0.5, 0.75),
+];
+
+pub fn identity_color(index: usize) -> Color {
+    let (r, g, b) = IDENTITY_COLORS[index % IDENTITY_COLORS.len()];
+    Color::srgb(r, g, b)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    // Index in the IDENTITY_COLORS
+    pub color: usize,
+}
+
+impl Identity {
+    // The name come from another peer, only its printable characters are kept
+    pub fn new(name: &str, color: usize) -> Self {
+        let name = name.chars().filter(|c| !c.is_control()).take(MAX_NAME_LENGTH).collect::<String>();
+        Self { name: name.trim().to_string(), color: color % IDENTITY_COLORS.len() }
+    }
+
+    // For a player that didn't choose one
+    pub fn fallback(handle: PlayerHandle) -> Self {
+        Self::new(&format!("Player {}", handle + 1), handle)
+    }
+
+    pub fn sanitized(&self) -> Self {
+        Self::new(&self.name, self.color)
+    }
+}
+
+// Identity of each player handle, the same on every peer once the game started
+#[derive(Resource, Default, Debug, Clone)]
+pub struct PlayerIdentity {
+    identities: HashMap<PlayerHandle, Identity>,
+}
+
+impl PlayerIdentity {
+    pub fn insert(&mut self, handle: PlayerHandle, identity: Identity) {
+        self.identities.insert(handle, identity);
+    }
+
+    pub fn get(&self, handle: PlayerHandle) -> Identity {
+        match self.identities.get(&handle) {
+            Some(identity) if identity.name.is_empty() => Identity { name: Identity::fallback(handle).name, color: identity.color },
+            Some(identity) => identity.clone(),
+            None => Identity::fallback(handle),
+        }
+    }
+
+    pub fn name(&self, handle: PlayerHandle) -> String {
+        self.get(handle).name
+    }
+
+    pub fn color(&self, handle: PlayerHandle) -> Color {
+        identity_color(self.get(handle).color)
+    }
+}
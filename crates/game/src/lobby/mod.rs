@@ -0,0 +1,204 @@
+pub mod identity;
+pub mod moderation;
+pub mod voice;
+
+use bevy::prelude::*;
+use thiserror::Error;
+
+use crate::{collider::preset::preset_for, jjrs::GggrsSessionConfiguration, rules::{GameMode, GameRules}};
+
+// Options of an ad-hoc lobby carried in the matchbox room name, until there is a real
+// matchmaking server. Everyone joining the same room string play with the same options,
+// the validation only check this build can actually play them.
+//
+// Room format: `<lobby>~map=test;players=2;mode=survival;seed=12345;version=0.2.0`,
+// with `;recorders=1` when headless recorders are expected on top of the players and
+// `;collisions=sandbox` when the collision preset is not the one of the mode
+
+const OPTIONS_SEPARATOR: char = '~';
+
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const DEFAULT_MAP: &str = "test";
+// Maps this build know how to spawn
+pub const KNOWN_MAPS: &[&str] = &[DEFAULT_MAP];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LobbyError {
+    #[error("malformed lobby option `{0}`, expected key=value")]
+    Malformed(String),
+    #[error("the lobby has no `{0}` option")]
+    Missing(&'static str),
+    #[error("invalid value `{value}` for the lobby option `{key}`")]
+    InvalidValue { key: &'static str, value: String },
+    #[error("the map `{0}` is not available in this build")]
+    MissingMap(String),
+    #[error("the lobby was created with version {lobby}, this build is version {local}")]
+    VersionMismatch { lobby: String, local: String },
+    #[error("the mode `{0}` can only be played offline")]
+    OfflineMode(&'static str),
+    #[error("kicked by the host: {0}")]
+    Kicked(String),
+    #[error("the lobby has no place for a recorder")]
+    NoRecorder,
+}
+
+// Set when the options of the room can't be played, the socket is never opened
+#[derive(Resource, Debug)]
+pub struct LobbyRefused(pub LobbyError);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LobbyOptions {
+    pub map: String,
+    pub max_player: usize,
+    pub mode: GameMode,
+    pub seed: u32,
+    pub version: String,
+    // Headless recorders joining as spectators, not counted in the players
+    pub recorders: usize,
+    // Collision preset of the match, the one of the mode by default
+    pub collisions: String,
+}
+
+impl LobbyOptions {
+    pub fn from_session(config: &GggrsSessionConfiguration) -> Self {
+        Self {
+            map: config.map.clone(),
+            max_player: config.connection.max_player,
+            mode: config.rules.mode,
+            seed: config.seed,
+            version: GAME_VERSION.to_string(),
+            recorders: config.recorders,
+            collisions: config.rules.collision_preset.clone(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut encoded = format!(
+            "map={};players={};mode={};seed={};version={}",
+            self.map, self.max_player, self.mode.name(), self.seed, self.version
+        );
+        // Left out when there is none so the rooms of the older builds keep their name
+        if self.recorders > 0 {
+            encoded.push_str(&format!(";recorders={}", self.recorders));
+        }
+        if self.collisions != self.mode.collision_preset() {
+            encoded.push_str(&format!(";collisions={}", self.collisions));
+        }
+        encoded
+    }
+
+    pub fn decode(text: &str) -> Result<Self, LobbyError> {
+        let mut map = None;
+        let mut max_player = None;
+        let mut mode = None;
+        let mut seed = None;
+        let mut version = None;
+        let mut recorders = 0;
+        let mut collisions = None;
+
+        for pair in text.split(';').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| LobbyError::Malformed(pair.to_string()))?;
+            let invalid = |key: &'static str| LobbyError::InvalidValue { key, value: value.to_string() };
+            match key {
+                "map" => map = Some(value.to_string()),
+                "players" => max_player = Some(value.parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(|| invalid("players"))?),
+                "mode" => mode = Some(GameMode::from_name(value).ok_or_else(|| invalid("mode"))?),
+                "seed" => seed = Some(value.parse::<u32>().map_err(|_| invalid("seed"))?),
+                "version" => version = Some(value.to_string()),
+                "recorders" => recorders = value.parse::<usize>().map_err(|_| invalid("recorders"))?,
+                "collisions" => collisions = Some(value),
+                // Options added by a newer build, the version check refuse the lobby anyway
+                _ => warn!("unknown lobby option {}", key),
+            }
+        }
+
+        let mode = mode.ok_or(LobbyError::Missing("mode"))?;
+        Ok(Self {
+            map: map.ok_or(LobbyError::Missing("map"))?,
+            max_player: max_player.ok_or(LobbyError::Missing("players"))?,
+            mode,
+            collisions: preset_for(mode, collisions),
+            seed: seed.ok_or(LobbyError::Missing("seed"))?,
+            version: version.ok_or(LobbyError::Missing("version"))?,
+            recorders,
+        })
+    }
+
+    // Can this build play the lobby
+    pub fn validate(&self) -> Result<(), LobbyError> {
+        if self.version != GAME_VERSION {
+            return Err(LobbyError::VersionMismatch { lobby: self.version.clone(), local: GAME_VERSION.to_string() });
+        }
+        if !KNOWN_MAPS.contains(&self.map.as_str()) {
+            return Err(LobbyError::MissingMap(self.map.clone()));
+        }
+        if self.mode.is_offline_only() {
+            return Err(LobbyError::OfflineMode(self.mode.name()));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, config: &mut GggrsSessionConfiguration) {
+        config.map = self.map.clone();
+        config.connection.max_player = self.max_player;
+        config.seed = self.seed;
+        config.recorders = self.recorders;
+        if config.rules.mode != self.mode {
+            config.rules = GameRules::from_mode(self.mode);
+        }
+        config.rules.collision_preset = self.collisions.clone();
+    }
+}
+
+// Name of the lobby and its encoded options, if any
+pub fn split_room(room: &str) -> (&str, Option<&str>) {
+    match room.split_once(OPTIONS_SEPARATOR) {
+        Some((lobby, options)) => (lobby, Some(options)),
+        None => (room, None),
+    }
+}
+
+pub fn room_name(lobby: &str, options: &LobbyOptions) -> String {
+    format!("{}{}{}", lobby, OPTIONS_SEPARATOR, options.encode())
+}
+
+// Options to use for the room, the one in the room string when joining a lobby
+// shared by someone else, otherwise the local ones
+pub fn resolve_room(config: &GggrsSessionConfiguration) -> Result<(String, LobbyOptions), LobbyError> {
+    let (lobby, encoded) = split_room(&config.lobby);
+    let options = match encoded {
+        Some(encoded) => LobbyOptions::decode(encoded)?,
+        None => LobbyOptions::from_session(config),
+    };
+    options.validate()?;
+    if config.recorder && options.recorders == 0 {
+        return Err(LobbyError::NoRecorder);
+    }
+    Ok((room_name(lobby, &options), options))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(mode: GameMode, collisions: &str) -> LobbyOptions {
+        LobbyOptions { map: DEFAULT_MAP.to_string(), max_player: 2, mode, seed: 7, version: GAME_VERSION.to_string(), recorders: 0, collisions: collisions.to_string() }
+    }
+
+    #[test]
+    fn test_options_round_trip() {
+        for options in [options(GameMode::Survival, "coop"), options(GameMode::Deathmatch, "pvp"), options(GameMode::Survival, "sandbox")] {
+            assert_eq!(LobbyOptions::decode(&options.encode()), Ok(options));
+        }
+    }
+
+    #[test]
+    fn test_collisions_of_the_mode_are_left_out() {
+        assert!(!options(GameMode::Survival, "coop").encode().contains("collisions"));
+        assert!(options(GameMode::Survival, "sandbox").encode().ends_with(";collisions=sandbox"));
+        // The rooms of the older builds have no collisions, they play the mode one
+        let decoded = LobbyOptions::decode(&format!("map=test;players=2;mode=deathmatch;seed=7;version={}", GAME_VERSION)).unwrap();
+        assert_eq!(decoded.collisions, "pvp");
+    }
+}
@@ -0,0 +1,465 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_matchbox::{prelude::PeerId, MatchboxSocket};
+use serde::{Deserialize, Serialize};
+
+use crate::{jjrs::GggrsSessionConfiguration, plugins::AppState, character::{config::CharacterConfig, player::create::DEFAULT_PLAYER_CLASS}, global_asset::GlobalAsset, hud::{HudAnchor, HudSlot}, progression::{customization::{customization_summary, LoadoutCustomization}, progress_summary, PlayerLoadout, PlayerProgress, ProgressionConfig}};
+
+use super::{identity::Identity, split_room, LobbyError, LobbyRefused};
+
+// Moderation of the lobby before the session start. The peer with the lowest id, the
+// one that get the handle 0, is the host: it check the password of the peers joining
+// and can kick them. The password is sent as a digest, see `password_digest`, it is
+// not a protection of the room. Everything is exchanged on a reliable channel next to the GGRS one.
+//
+// This is advisory only, in P2P nothing stop a modified client from ignoring a kick
+// or connecting directly to the other peers, and nothing is enforced once the session
+// started. The loadouts go through the host the same way, each peer only check its own
+// against its unlocks. The headless recorders join like the players and tell it to the
+// host, the roster carry them so every peer leave them out of the player handles. The
+// identities and the classes of the players are sent the same way as the loadouts, every
+// peer build the players from the roster and not from its own arguments. The peers taking
+// a drop-in place, they get a handle but spectate until they join.
+
+pub const LOBBY_CHANNEL: usize = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+enum LobbyMessage {
+    // Sent to the host by every other peer, again when the loadout change
+    Join { password: u64, loadout: PlayerLoadout, #[serde(default)] recorder: bool, #[serde(default)] identity: Identity, #[serde(default)] dropin: bool, #[serde(default)] class: String },
+    // Peers accepted by the host, the loadout, identity and class of everyone and the peers that are recorders or drop-ins
+    Roster { peers: Vec<PeerId>, loadouts: Vec<(PeerId, PlayerLoadout)>, #[serde(default)] recorders: Vec<PeerId>, #[serde(default)] identities: Vec<(PeerId, Identity)>, #[serde(default)] dropins: Vec<PeerId>, #[serde(default)] classes: Vec<(PeerId, String)> },
+    Kicked { reason: String },
+}
+
+// Ask the host to remove a peer from the lobby, ignored when the local peer is not the host
+#[derive(Event, Debug, Clone)]
+pub struct KickPeer {
+    pub peer: PeerId,
+    pub reason: String,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct LobbyModeration {
+    pub host: Option<PeerId>,
+    // Accepted peers, maintained by the host and copied from it by the others
+    pub roster: Vec<PeerId>,
+    // Loadout of every peer including the host, copied from the host by the others
+    pub loadouts: HashMap<PeerId, PlayerLoadout>,
+    // Headless recorders among the peers, maybe the host, copied from the host by the others
+    pub recorders: Vec<PeerId>,
+    // Name and color of every peer including the host, copied from the host by the others
+    pub identities: HashMap<PeerId, Identity>,
+    // Class of every peer including the host, copied from the host by the others
+    pub classes: HashMap<PeerId, String>,
+    // Players starting as spectators, maybe the host, copied from the host by the others
+    pub dropins: Vec<PeerId>,
+    kicked: HashSet<PeerId>,
+    // Last join message sent to the host
+    joined: Option<JoinSent>,
+}
+
+// What the host know of the local peer, the join is sent again when any of it change
+#[derive(Debug, Clone, PartialEq)]
+struct JoinSent {
+    host: PeerId,
+    loadout: PlayerLoadout,
+    identity: Identity,
+    class: String,
+}
+
+impl LobbyModeration {
+    // Remember the join, false when the host already has the same
+    fn join_changed(&mut self, join: JoinSent) -> bool {
+        if self.joined.as_ref() == Some(&join) {
+            return false;
+        }
+        self.joined = Some(join);
+        true
+    }
+
+    pub fn is_host(&self, local: PeerId) -> bool {
+        self.host == Some(local)
+    }
+
+    // Every connected peer, and ourself, was accepted by the host
+    pub fn is_ready(&self, local: PeerId, connected: &[PeerId]) -> bool {
+        let Some(host) = self.host else {
+            return false;
+        };
+        std::iter::once(local)
+            .chain(connected.iter().copied())
+            .filter(|peer| *peer != host)
+            .all(|peer| self.roster.contains(&peer))
+            && std::iter::once(local).chain(connected.iter().copied()).all(|peer| self.loadouts.contains_key(&peer))
+    }
+
+    pub fn loadout(&self, peer: PeerId) -> PlayerLoadout {
+        self.loadouts.get(&peer).cloned().unwrap_or_default()
+    }
+
+    pub fn identity(&self, peer: PeerId) -> Option<Identity> {
+        self.identities.get(&peer).cloned()
+    }
+
+    // The default class for a peer that sent none
+    pub fn class(&self, peer: PeerId) -> &str {
+        self.classes.get(&peer).filter(|class| !class.is_empty()).map_or(DEFAULT_PLAYER_CLASS, |class| class.as_str())
+    }
+
+    pub fn is_recorder(&self, peer: PeerId) -> bool {
+        self.recorders.contains(&peer)
+    }
+
+    pub fn is_dropin(&self, peer: PeerId) -> bool {
+        self.dropins.contains(&peer)
+    }
+}
+
+// FNV-1a of the room and the password, unsalted and fast: it is not a secret. Anyone
+// seeing the join message can replay the digest, and a weak password is found by brute
+// force in no time. It only keep the casual peers out of a room like the kick, the
+// password should never be one used elsewhere. Only the name of the lobby is hashed, the
+// host and a peer joining with the options in the room string must agree.
+fn password_digest(lobby: &str, password: Option<&str>) -> u64 {
+    let (room, _) = split_room(lobby);
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in room.bytes().chain([0]).chain(password.unwrap_or_default().bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn send(socket: &mut MatchboxSocket, peer: PeerId, message: &LobbyMessage) {
+    match serde_json::to_vec(message) {
+        Ok(bytes) => socket.channel_mut(LOBBY_CHANNEL).send(bytes.into_boxed_slice(), peer),
+        Err(err) => error!("failed to encode lobby message {:?}: {}", message, err),
+    }
+}
+
+fn broadcast_roster(socket: &mut MatchboxSocket, moderation: &LobbyModeration) {
+    let peers: Vec<PeerId> = socket.connected_peers().collect();
+    let mut loadouts: Vec<(PeerId, PlayerLoadout)> = moderation.loadouts.iter().map(|(peer, loadout)| (*peer, loadout.clone())).collect();
+    loadouts.sort_by_key(|(peer, _)| *peer);
+    let mut identities: Vec<(PeerId, Identity)> = moderation.identities.iter().map(|(peer, identity)| (*peer, identity.clone())).collect();
+    identities.sort_by_key(|(peer, _)| *peer);
+    let mut classes: Vec<(PeerId, String)> = moderation.classes.iter().map(|(peer, class)| (*peer, class.clone())).collect();
+    classes.sort_by_key(|(peer, _)| *peer);
+    for peer in peers {
+        send(socket, peer, &LobbyMessage::Roster { peers: moderation.roster.clone(), loadouts: loadouts.clone(), recorders: moderation.recorders.clone(), identities: identities.clone(), dropins: moderation.dropins.clone(), classes: classes.clone() });
+    }
+}
+
+fn kick(socket: &mut MatchboxSocket, moderation: &mut LobbyModeration, peer: PeerId, reason: &str) {
+    info!("kicking peer {} from the lobby: {}", peer, reason);
+    send(socket, peer, &LobbyMessage::Kicked { reason: reason.to_string() });
+    moderation.kicked.insert(peer);
+    moderation.roster.retain(|p| *p != peer);
+    moderation.loadouts.remove(&peer);
+    moderation.recorders.retain(|p| *p != peer);
+    moderation.dropins.retain(|p| *p != peer);
+    moderation.identities.remove(&peer);
+    moderation.classes.remove(&peer);
+    broadcast_roster(socket, moderation);
+}
+
+pub fn lobby_moderation_system(
+    mut commands: Commands,
+    session_config: Res<GggrsSessionConfiguration>,
+    progression: Res<ProgressionConfig>,
+    progress: Res<PlayerProgress>,
+    mut socket: ResMut<MatchboxSocket>,
+    mut moderation: ResMut<LobbyModeration>,
+    mut kick_events: EventReader<KickPeer>,
+) {
+    let Some(local) = socket.id() else {
+        return;
+    };
+    let digest = password_digest(&session_config.lobby, session_config.password.as_deref());
+    let local_loadout = progress.loadout(&progression);
+    let local_identity = session_config.local_identity();
+    let local_class = session_config.local_class().to_string();
+
+    // The host is the lowest id still connected, it change if the host leave
+    let connected: Vec<PeerId> = socket.connected_peers().filter(|peer| !moderation.kicked.contains(peer)).collect();
+    let host = connected.iter().copied().chain([local]).min();
+    if moderation.host != host {
+        moderation.host = host;
+        moderation.roster.clear();
+        moderation.loadouts.clear();
+        moderation.recorders.clear();
+        moderation.dropins.clear();
+        moderation.identities.clear();
+        moderation.classes.clear();
+        moderation.joined = None;
+    }
+    let is_host = moderation.is_host(local);
+
+    if is_host {
+        // Forget the peers that left
+        let before = moderation.roster.len();
+        moderation.roster.retain(|peer| connected.contains(peer));
+        moderation.loadouts.retain(|peer, _| *peer == local || connected.contains(peer));
+        moderation.recorders.retain(|peer| *peer == local || connected.contains(peer));
+        moderation.dropins.retain(|peer| *peer == local || connected.contains(peer));
+        moderation.identities.retain(|peer, _| *peer == local || connected.contains(peer));
+        moderation.classes.retain(|peer, _| *peer == local || connected.contains(peer));
+        let mut changed = moderation.loadouts.get(&local) != Some(&local_loadout)
+            || moderation.identities.get(&local) != Some(&local_identity)
+            || moderation.classes.get(&local) != Some(&local_class);
+        if changed {
+            moderation.loadouts.insert(local, local_loadout);
+            moderation.identities.insert(local, local_identity);
+            moderation.classes.insert(local, local_class);
+        }
+        if session_config.recorder && !moderation.is_recorder(local) {
+            moderation.recorders.push(local);
+            moderation.recorders.sort();
+        }
+        if session_config.dropin && !moderation.is_dropin(local) {
+            moderation.dropins.push(local);
+            moderation.dropins.sort();
+            changed = true;
+        }
+        if changed || moderation.roster.len() != before {
+            broadcast_roster(&mut socket, &moderation);
+        }
+    } else if let Some(host) = host {
+        let join = JoinSent { host, loadout: local_loadout, identity: local_identity, class: local_class };
+        if moderation.join_changed(join.clone()) {
+            send(&mut socket, host, &LobbyMessage::Join { password: digest, loadout: join.loadout, recorder: session_config.recorder, identity: join.identity, dropin: session_config.dropin, class: join.class });
+        }
+    }
+
+    for (peer, packet) in socket.channel_mut(LOBBY_CHANNEL).receive() {
+        if moderation.kicked.contains(&peer) {
+            continue;
+        }
+        let message: LobbyMessage = match serde_json::from_slice(&packet) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("invalid lobby message from {}: {}", peer, err);
+                continue;
+            }
+        };
+
+        match message {
+            LobbyMessage::Join { password, loadout, recorder, identity, dropin, class } if is_host => {
+                let identity = identity.sanitized();
+                if password != digest {
+                    kick(&mut socket, &mut moderation, peer, "wrong password");
+                } else if !moderation.roster.contains(&peer) {
+                    info!("{} {} joined the lobby", if recorder { "recorder" } else { "peer" }, peer);
+                    moderation.roster.push(peer);
+                    moderation.roster.sort();
+                    moderation.loadouts.insert(peer, loadout);
+                    moderation.identities.insert(peer, identity);
+                    moderation.classes.insert(peer, class);
+                    if recorder {
+                        moderation.recorders.push(peer);
+                        moderation.recorders.sort();
+                    }
+                    if dropin {
+                        moderation.dropins.push(peer);
+                        moderation.dropins.sort();
+                    }
+                    broadcast_roster(&mut socket, &moderation);
+                } else if moderation.loadouts.get(&peer) != Some(&loadout) || moderation.identities.get(&peer) != Some(&identity) || moderation.classes.get(&peer) != Some(&class) {
+                    moderation.loadouts.insert(peer, loadout);
+                    moderation.identities.insert(peer, identity);
+                    moderation.classes.insert(peer, class);
+                    broadcast_roster(&mut socket, &moderation);
+                }
+            }
+            LobbyMessage::Roster { peers, loadouts, recorders, identities, dropins, classes } if Some(peer) == moderation.host => {
+                moderation.roster = peers;
+                moderation.loadouts = loadouts.into_iter().collect();
+                moderation.recorders = recorders;
+                moderation.dropins = dropins;
+                moderation.classes = classes.into_iter().collect();
+                moderation.identities = identities.into_iter().map(|(peer, identity)| (peer, identity.sanitized())).collect();
+            }
+            LobbyMessage::Kicked { reason } if Some(peer) == moderation.host => {
+                error!("kicked from the lobby by the host: {}", reason);
+                commands.insert_resource(LobbyRefused(LobbyError::Kicked(reason)));
+                // Dropping the socket close the channels with every peer
+                commands.remove_resource::<MatchboxSocket>();
+                return;
+            }
+            message => warn!("ignoring lobby message {:?} from {}", message, peer),
+        }
+    }
+
+    for event in kick_events.read() {
+        if !is_host {
+            warn!("only the host can kick a peer");
+            continue;
+        }
+        kick(&mut socket, &mut moderation, event.peer, &event.reason);
+    }
+}
+
+// Host only, F1 to F8 kick the peers in the order of the lobby UI
+pub fn lobby_kick_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    socket: Option<ResMut<MatchboxSocket>>,
+    moderation: Res<LobbyModeration>,
+    mut kick_events: EventWriter<KickPeer>,
+) {
+    let Some(mut socket) = socket else {
+        return;
+    };
+    let Some(local) = socket.id() else {
+        return;
+    };
+    if !moderation.is_host(local) {
+        return;
+    }
+
+    let keys_order = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8];
+    let mut peers: Vec<PeerId> = socket.connected_peers().collect();
+    peers.sort();
+    for (key, peer) in keys_order.iter().zip(peers) {
+        if keys.just_pressed(*key) {
+            kick_events.send(KickPeer { peer, reason: "kicked by the host".into() });
+        }
+    }
+}
+
+#[derive(Component)]
+struct LobbyText;
+
+fn setup_lobby_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        LobbyText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 16.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::TopLeft, 1),
+    ));
+}
+
+fn update_lobby_ui(
+    mut socket: Option<ResMut<MatchboxSocket>>,
+    moderation: Res<LobbyModeration>,
+    refused: Option<Res<LobbyRefused>>,
+    session_config: Res<GggrsSessionConfiguration>,
+    progression: Res<ProgressionConfig>,
+    progress: Res<PlayerProgress>,
+    customization: Res<LoadoutCustomization>,
+    global_assets: Res<GlobalAsset>,
+    character_asset: Res<Assets<CharacterConfig>>,
+    mut query: Query<&mut Text, With<LobbyText>>,
+) {
+    let Ok(mut text) = query.get_single_mut() else {
+        return;
+    };
+    if let Some(refused) = refused {
+        text.0 = format!("Can't join the lobby: {}", refused.0);
+        return;
+    }
+    let Some(local) = socket.as_mut().and_then(|socket| socket.id()) else {
+        text.0 = "Connecting to the lobby...".into();
+        return;
+    };
+
+    let is_host = moderation.is_host(local);
+    let mut peers: Vec<PeerId> = socket.as_ref().map_or(vec![], |socket| socket.connected_peers().collect());
+    peers.sort();
+
+    let players = std::iter::once(local).chain(peers.iter().copied()).filter(|peer| !moderation.is_recorder(*peer)).count();
+    let mut lines = vec![format!(
+        "Lobby {} - {} / {} players{}",
+        session_config.lobby, players, session_config.connection.max_player,
+        if is_host { " - you are the host" } else { "" },
+    )];
+    for (i, peer) in peers.iter().enumerate() {
+        let status = if Some(*peer) == moderation.host {
+            "host"
+        } else if moderation.is_recorder(*peer) {
+            "recorder"
+        } else if moderation.is_dropin(*peer) {
+            "drop-in"
+        } else if moderation.roster.contains(peer) {
+            "ready"
+        } else {
+            "joining"
+        };
+        let kick = if is_host { format!(" [F{} kick]", i + 1) } else { String::new() };
+        let name = moderation.identity(*peer).map_or_else(|| peer.to_string(), |identity| identity.name);
+        lines.push(format!("{} {}{}", name, status, kick));
+    }
+    lines.push(String::new());
+    lines.extend(progress_summary(&progression, &progress));
+    lines.extend(customization_summary(&progression, &progress, &customization, &global_assets, &character_asset));
+    text.0 = lines.join("\n");
+}
+
+fn cleanup_lobby_ui(mut commands: Commands, query: Query<Entity, With<LobbyText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[derive(Default)]
+pub struct LobbyUIPlugin;
+
+impl Plugin for LobbyUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Lobby), setup_lobby_ui);
+        app.add_systems(OnExit(AppState::Lobby), cleanup_lobby_ui);
+        app.add_systems(Update, (update_lobby_ui, lobby_kick_input_system).run_if(in_state(AppState::Lobby)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The ids are uuids, read the way they come from another peer
+    fn peer(n: u8) -> PeerId {
+        serde_json::from_str(&format!("\"00000000-0000-0000-0000-0000000000{:02x}\"", n)).unwrap()
+    }
+
+    #[test]
+    fn test_password_digest_ignore_the_options() {
+        let digest = password_digest("room", Some("secret"));
+        assert_eq!(password_digest("room~map=test;players=2;mode=survival;seed=1;version=0.2.0", Some("secret")), digest);
+        assert_ne!(password_digest("room", Some("other")), digest);
+        assert_ne!(password_digest("other~map=test", Some("secret")), digest);
+        // No password and an empty one give the same digest
+        assert_ne!(password_digest("room", None), digest);
+        assert_eq!(password_digest("room", None), password_digest("room~seed=3", Some("")));
+    }
+
+    #[test]
+    fn test_join_is_sent_again_when_the_identity_change() {
+        let mut moderation = LobbyModeration::default();
+        let join = JoinSent { host: peer(1), loadout: PlayerLoadout::default(), identity: Identity::new("alice", 0), class: "heavy".to_string() };
+        assert!(moderation.join_changed(join.clone()));
+        assert!(!moderation.join_changed(join.clone()));
+
+        assert!(moderation.join_changed(JoinSent { identity: Identity::new("alice", 2), ..join.clone() }));
+        assert!(moderation.join_changed(JoinSent { class: "scout".to_string(), ..join.clone() }));
+        assert!(moderation.join_changed(JoinSent { host: peer(2), ..join }));
+    }
+
+    #[test]
+    fn test_class_of_the_roster() {
+        let chosen = peer(1);
+        let missing = peer(2);
+        let empty = peer(3);
+        let mut moderation = LobbyModeration::default();
+        moderation.classes.insert(chosen, "heavy".to_string());
+        moderation.classes.insert(empty, String::new());
+
+        assert_eq!(moderation.class(chosen), "heavy");
+        // A peer that sent no class play the default one on every peer
+        assert_eq!(moderation.class(missing), DEFAULT_PLAYER_CLASS);
+        assert_eq!(moderation.class(empty), DEFAULT_PLAYER_CLASS);
+    }
+}
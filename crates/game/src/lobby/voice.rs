@@ -0,0 +1,282 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_inspector_egui::{bevy_egui::{EguiContexts, EguiPlugin}, egui};
+use ggrs::PlayerHandle;
+use serde::{Deserialize, Serialize};
+
+use crate::{character::player::{LocalPlayer, Player}, hud::options::HudPreferences, plugins::AppState};
+
+use super::identity::{Identity, PlayerIdentity};
+
+// Plumbing for a voice chat that doesn't exist yet. Whatever carry the audio, a WebRTC
+// track next to the GGRS channel for example, report the level of each player to the
+// VoiceActivity and the nameplates and the scoreboard show who is talking. The mutes
+// are kept by identity, the handles change from a game to the other, and saved in the
+// HudPreferences with the other settings of the player. A muted player is never
+// reported as speaking, the feeder should also not play its audio.
+
+pub const VOICE_OPTIONS_KEY: KeyCode = KeyCode::KeyU;
+
+// Marks shown next to a name
+pub const SPEAKING_MARK: &str = "<))";
+pub const MUTED_MARK: &str = "<x";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoiceSettings {
+    // Name and color of the muted players, two players can have the same name
+    #[serde(default)]
+    pub muted: Vec<Identity>,
+    // Level from 0 to 1 above which a player is speaking
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+    // Seconds the icon stay after the last report above the threshold
+    #[serde(default = "default_hold_seconds")]
+    pub hold_seconds: f32,
+}
+
+fn default_threshold() -> f32 {
+    0.05
+}
+
+fn default_hold_seconds() -> f32 {
+    0.3
+}
+
+impl Default for VoiceSettings {
+    fn default() -> Self {
+        Self { muted: vec![], threshold: default_threshold(), hold_seconds: default_hold_seconds() }
+    }
+}
+
+impl VoiceSettings {
+    pub fn is_muted(&self, identity: &Identity) -> bool {
+        self.muted.contains(identity)
+    }
+
+    pub fn set_muted(&mut self, identity: &Identity, muted: bool) {
+        if muted == self.is_muted(identity) {
+            return;
+        }
+        if muted {
+            self.muted.push(identity.clone());
+        } else {
+            self.muted.retain(|other| other != identity);
+        }
+    }
+
+    pub fn toggle_mute(&mut self, identity: &Identity) {
+        let muted = self.is_muted(identity);
+        self.set_muted(identity, !muted);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct VoiceLevel {
+    level: f32,
+    // Seconds left before the player is not speaking anymore
+    hold: f32,
+}
+
+// Fed by the voice transport, read by the interface
+#[derive(Resource, Default, Debug)]
+pub struct VoiceActivity {
+    players: HashMap<PlayerHandle, VoiceLevel>,
+}
+
+impl VoiceActivity {
+    // Last level of the audio of a player, from 0 to 1
+    pub fn report(&mut self, handle: PlayerHandle, level: f32, settings: &VoiceSettings) {
+        let entry = self.players.entry(handle).or_default();
+        entry.level = level.clamp(0.0, 1.0);
+        if entry.level >= settings.threshold {
+            entry.hold = settings.hold_seconds;
+        }
+    }
+
+    // The player left or the track closed
+    pub fn clear(&mut self, handle: PlayerHandle) {
+        self.players.remove(&handle);
+    }
+
+    pub fn level(&self, handle: PlayerHandle) -> f32 {
+        self.players.get(&handle).map_or(0.0, |voice| voice.level)
+    }
+
+    pub fn is_speaking(&self, handle: PlayerHandle) -> bool {
+        self.players.get(&handle).map_or(false, |voice| voice.hold > 0.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoiceState {
+    Silent,
+    Speaking,
+    Muted,
+}
+
+impl VoiceState {
+    pub fn of(handle: PlayerHandle, activity: &VoiceActivity, settings: &VoiceSettings, identities: &PlayerIdentity) -> Self {
+        if settings.is_muted(&identities.get(handle)) {
+            VoiceState::Muted
+        } else if activity.is_speaking(handle) {
+            VoiceState::Speaking
+        } else {
+            VoiceState::Silent
+        }
+    }
+
+    pub fn mark(&self) -> &'static str {
+        match self {
+            VoiceState::Silent => "",
+            VoiceState::Speaking => SPEAKING_MARK,
+            VoiceState::Muted => MUTED_MARK,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct VoiceOptionsState {
+    is_open: bool,
+}
+
+
+// SYSTEMS
+
+// Without reports the icons go away, a muted player is silenced right away
+fn decay_voice_activity(
+    time: Res<Time>,
+    preferences: Res<HudPreferences>,
+    identities: Res<PlayerIdentity>,
+    mut activity: ResMut<VoiceActivity>,
+) {
+    let dt = time.delta_secs();
+    for (handle, voice) in activity.players.iter_mut() {
+        voice.hold = (voice.hold - dt).max(0.0);
+        if preferences.voice.is_muted(&identities.get(*handle)) {
+            *voice = VoiceLevel::default();
+        }
+    }
+}
+
+fn toggle_voice_options(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<VoiceOptionsState>,
+) {
+    if keyboard_input.just_pressed(VOICE_OPTIONS_KEY) {
+        state.is_open = !state.is_open;
+    }
+}
+
+fn voice_options_panel(
+    mut contexts: EguiContexts,
+    mut state: ResMut<VoiceOptionsState>,
+    mut preferences: ResMut<HudPreferences>,
+    activity: Res<VoiceActivity>,
+    identities: Res<PlayerIdentity>,
+    q_players: Query<&Player, Without<LocalPlayer>>,
+) {
+    if !state.is_open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut players: Vec<_> = q_players.iter().map(|player| player.handle).collect();
+    players.sort();
+
+    let mut edited = preferences.voice.clone();
+    let mut is_open = state.is_open;
+    egui::Window::new("Voice").open(&mut is_open).default_pos((10.0, 360.0)).show(ctx, |ui| {
+        if players.is_empty() {
+            ui.label("No other player");
+        }
+        for handle in players {
+            let identity = identities.get(handle);
+            let mut muted = edited.is_muted(&identity);
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut muted, "mute");
+                ui.label(format!("{} {}", identity.name, VoiceState::of(handle, &activity, &edited, &identities).mark()));
+            });
+            edited.set_muted(&identity, muted);
+        }
+    });
+    state.is_open = is_open;
+
+    if edited != preferences.voice {
+        preferences.voice = edited;
+        preferences.save();
+    }
+}
+
+
+pub struct VoiceActivityPlugin;
+
+impl Plugin for VoiceActivityPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<VoiceActivity>();
+        app.init_resource::<VoiceOptionsState>();
+        app.add_systems(Update, decay_voice_activity);
+        app.add_systems(Update, (toggle_voice_options, voice_options_panel).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mute_is_kept_by_identity() {
+        let mut settings = VoiceSettings::default();
+        settings.toggle_mute(&Identity::new("alice", 0));
+
+        assert!(settings.is_muted(&Identity::new("alice", 0)));
+        // Same name, another player
+        assert!(!settings.is_muted(&Identity::new("alice", 1)));
+
+        settings.set_muted(&Identity::new("alice", 0), true);
+        assert_eq!(settings.muted.len(), 1);
+        settings.toggle_mute(&Identity::new("alice", 0));
+        assert!(settings.muted.is_empty());
+    }
+
+    #[test]
+    fn test_muted_player_is_never_speaking() {
+        let mut identities = PlayerIdentity::default();
+        identities.insert(0, Identity::new("alice", 0));
+        identities.insert(1, Identity::new("bob", 1));
+        let mut settings = VoiceSettings::default();
+        let mut activity = VoiceActivity::default();
+        activity.report(0, 0.8, &settings);
+        activity.report(1, 0.8, &settings);
+        settings.set_muted(&Identity::new("bob", 1), true);
+
+        assert_eq!(VoiceState::of(0, &activity, &settings, &identities), VoiceState::Speaking);
+        assert_eq!(VoiceState::of(1, &activity, &settings, &identities), VoiceState::Muted);
+    }
+
+    #[test]
+    fn test_report_under_the_threshold_is_silent() {
+        let settings = VoiceSettings::default();
+        let mut activity = VoiceActivity::default();
+        activity.report(0, settings.threshold / 2.0, &settings);
+        assert!(!activity.is_speaking(0));
+
+        activity.report(0, 2.0, &settings);
+        assert!(activity.is_speaking(0));
+        assert_eq!(activity.level(0), 1.0);
+
+        activity.clear(0);
+        assert!(!activity.is_speaking(0));
+    }
+
+    #[test]
+    fn test_hud_save_without_voice_keep_the_defaults() {
+        let preferences: HudPreferences = ron::de::from_str("(ui_scale: 1.5, safe_margin: 20.0)").unwrap();
+        assert_eq!(preferences.voice, VoiceSettings::default());
+    }
+}
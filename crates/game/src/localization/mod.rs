@@ -0,0 +1,310 @@
+use std::fmt::Display;
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use utils::{persistence, schema::Versioned, web::browser_language};
+
+use crate::global_asset::GlobalAsset;
+
+// Texts of the interface by key, the strings come from a RON file per language in
+// `assets/locales`. A translation is a copy of `en.ron` with the values translated and a
+// line in `LANGUAGES`, the keys missing from it are shown in english. The texts can hold
+// arguments between braces, `{killer} killed {victim}`, filled by `Localization::format`.
+// The language is chosen in the interface options, by default the one of the browser.
+
+pub const LOCALIZATION_SCHEMA_VERSION: u32 = 1;
+
+// Files of `assets/locales`, without the extension
+pub const LANGUAGES: [&str; 2] = ["en", "fr"];
+// Reference of the keys, used for the ones a translation miss
+pub const FALLBACK_LANGUAGE: &str = "en";
+// Not a file, the fallback accented and made longer to find the texts still hardcoded
+// and the boxes too small for the verbose languages
+pub const PSEUDO_LANGUAGE: &str = "pseudo";
+
+const LANGUAGE_SAVE_KEY: &str = "language";
+// Part of the length added by the pseudo language, the german run about this much longer
+const PSEUDO_EXPANSION: f32 = 0.4;
+
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct LanguageFile {
+    #[serde(default)]
+    pub schema_version: u32,
+    // Name of the language in itself, shown in the selector
+    pub name: String,
+    pub strings: HashMap<String, String>,
+}
+
+impl Versioned for LanguageFile {
+    const ASSET_NAME: &'static str = "language file";
+    const SCHEMA_VERSION: u32 = LOCALIZATION_SCHEMA_VERSION;
+
+    fn parse_version(_version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_bytes(bytes)
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LanguagePreferences {
+    // None follow the browser, english on native
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl LanguagePreferences {
+    pub fn save(&self) {
+        if let Err(err) = persistence::save(LANGUAGE_SAVE_KEY, self) {
+            error!("failed to save the language: {}", err);
+        }
+    }
+
+    // Language shown, the chosen one or the closest to the one of the browser
+    pub fn resolve(&self) -> String {
+        if let Some(language) = self.language.as_ref().filter(|language| is_known(language)) {
+            return language.clone();
+        }
+        // `fr-CA` use the french file
+        browser_language()
+            .and_then(|language| language.split(['-', '_']).next().map(|code| code.to_lowercase()))
+            .filter(|code| is_known(code))
+            .unwrap_or(FALLBACK_LANGUAGE.to_string())
+    }
+}
+
+fn is_known(language: &str) -> bool {
+    language == PSEUDO_LANGUAGE || LANGUAGES.contains(&language)
+}
+
+// Text kept by the simulation as its key and arguments, formatted by the UI in the
+// language of the player
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalizedText {
+    pub key: &'static str,
+    pub args: Vec<(&'static str, String)>,
+}
+
+impl LocalizedText {
+    pub fn new(key: &'static str) -> Self {
+        Self { key, args: vec![] }
+    }
+
+    pub fn with_arg(mut self, name: &'static str, value: impl Display) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+}
+
+// Strings of the language shown, presentation only
+#[derive(Resource, Default, Debug)]
+pub struct Localization {
+    pub language: String,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Localization {
+    pub fn new(language: String, strings: HashMap<String, String>, fallback: HashMap<String, String>) -> Self {
+        Self { language, strings, fallback }
+    }
+
+    // The key itself when no file has it, a missing text is seen and not blank
+    pub fn text(&self, key: &str) -> String {
+        self.strings.get(key)
+            .or_else(|| self.fallback.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        interpolate(&self.text(key), args)
+    }
+
+    pub fn localize(&self, text: &LocalizedText) -> String {
+        let args: Vec<(&str, &dyn Display)> = text.args.iter().map(|(name, value)| (*name, value as &dyn Display)).collect();
+        self.format(text.key, &args)
+    }
+}
+
+// Replace each `{name}` by its argument, an unknown one stay as it is
+pub fn interpolate(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &rest[start + 1..end];
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, value)) => result.push_str(&value.to_string()),
+            None => result.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+// Accented, longer and between brackets, the arguments are kept for the interpolation
+pub fn pseudo_localize(text: &str) -> String {
+    let mut result = String::from("[");
+    let mut in_argument = false;
+    let mut letters = 0;
+    for c in text.chars() {
+        if c == '{' {
+            in_argument = true;
+        }
+        if in_argument {
+            result.push(c);
+            in_argument = c != '}';
+            continue;
+        }
+        if c.is_alphanumeric() {
+            letters += 1;
+        }
+        result.push(accented(c));
+    }
+    let padding = (letters as f32 * PSEUDO_EXPANSION).ceil() as usize;
+    result.push_str(&"~".repeat(padding));
+    result.push(']');
+    result
+}
+
+fn accented(c: char) -> char {
+    match c {
+        'a' => 'á', 'e' => 'é', 'i' => 'í', 'o' => 'ö', 'u' => 'ü', 'c' => 'ç', 'n' => 'ñ', 'y' => 'ý', 's' => 'š', 'z' => 'ž',
+        'A' => 'Å', 'E' => 'É', 'I' => 'Î', 'O' => 'Ø', 'U' => 'Ü', 'C' => 'Ç', 'N' => 'Ñ', 'Y' => 'Ý', 'S' => 'Š', 'Z' => 'Ž',
+        c => c,
+    }
+}
+
+// Every language that can be chosen with its name, the pseudo one last
+pub fn available_languages(global_assets: &GlobalAsset, language_files: &Assets<LanguageFile>) -> Vec<(String, String)> {
+    let mut languages: Vec<(String, String)> = LANGUAGES.iter()
+        .map(|code| {
+            let name = global_assets.locales.get(*code)
+                .and_then(|handle| language_files.get(handle))
+                .map_or(code.to_string(), |file| file.name.clone());
+            (code.to_string(), name)
+        })
+        .collect();
+    languages.push((PSEUDO_LANGUAGE.to_string(), "Pseudo".to_string()));
+    languages
+}
+
+
+// SYSTEMS
+
+fn load_language_preferences(mut commands: Commands) {
+    let preferences = match persistence::load::<LanguagePreferences>(LANGUAGE_SAVE_KEY) {
+        Ok(preferences) => preferences.unwrap_or_default(),
+        Err(err) => {
+            error!("failed to load the language: {}", err);
+            LanguagePreferences::default()
+        }
+    };
+    commands.insert_resource(preferences);
+}
+
+// Built again when the choice change and when a file is loaded or edited
+fn apply_language(
+    preferences: Res<LanguagePreferences>,
+    global_assets: Res<GlobalAsset>,
+    language_files: Res<Assets<LanguageFile>>,
+    mut file_events: EventReader<AssetEvent<LanguageFile>>,
+    mut localization: ResMut<Localization>,
+) {
+    let reloaded = file_events.read().count() > 0;
+    if !preferences.is_changed() && !reloaded {
+        return;
+    }
+
+    let strings_of = |code: &str| global_assets.locales.get(code)
+        .and_then(|handle| language_files.get(handle))
+        .map(|file| file.strings.clone())
+        .unwrap_or_default();
+
+    let language = preferences.resolve();
+    let fallback = strings_of(FALLBACK_LANGUAGE);
+    let strings = if language == PSEUDO_LANGUAGE {
+        fallback.iter().map(|(key, text)| (key.clone(), pseudo_localize(text))).collect()
+    } else {
+        strings_of(&language)
+    };
+    *localization = Localization::new(language, strings, fallback);
+}
+
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Localization>();
+        app.add_systems(Startup, load_language_preferences);
+        app.add_systems(Update, apply_language.run_if(resource_exists::<LanguagePreferences>).run_if(resource_exists::<GlobalAsset>));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language_file(code: &str) -> LanguageFile {
+        let path = format!("{}/../../assets/locales/{}.ron", env!("CARGO_MANIFEST_DIR"), code);
+        let bytes = std::fs::read(&path).unwrap();
+        LanguageFile::parse_version(LOCALIZATION_SCHEMA_VERSION, &bytes).unwrap()
+    }
+
+    #[test]
+    fn test_interpolate_fill_the_arguments() {
+        assert_eq!(interpolate("{killer} killed {victim}", &[("killer", &"Bob"), ("victim", &"zombie")]), "Bob killed zombie");
+        assert_eq!(interpolate("Wave {wave} - {wave}", &[("wave", &3)]), "Wave 3 - 3");
+        assert_eq!(interpolate("no argument", &[("wave", &3)]), "no argument");
+    }
+
+    #[test]
+    fn test_interpolate_keep_what_it_cant_fill() {
+        // Unknown argument and a brace never closed
+        assert_eq!(interpolate("{unknown} {wave}", &[("wave", &3)]), "{unknown} 3");
+        assert_eq!(interpolate("Wave {wave", &[("wave", &3)]), "Wave {wave");
+        // The value is not read again
+        assert_eq!(interpolate("{a}", &[("a", &"{b}"), ("b", &"no")]), "{b}");
+    }
+
+    #[test]
+    fn test_pseudo_localize_keep_the_arguments() {
+        let pseudo = pseudo_localize("Wave {wave}");
+        assert!(pseudo.starts_with("[Wávé {wave}"));
+        assert!(pseudo.ends_with(']'));
+        assert_eq!(interpolate(&pseudo, &[("wave", &3)]), pseudo.replace("{wave}", "3"));
+    }
+
+    #[test]
+    fn test_pseudo_localize_is_longer() {
+        // 4 letters, 40% more rounded up
+        assert_eq!(pseudo_localize("Draw"), "[Dráw~~]");
+        assert_eq!(pseudo_localize(""), "[]");
+    }
+
+    #[test]
+    fn test_localize_the_text_of_the_simulation() {
+        let strings = HashMap::from([("interaction.open_door".to_string(), "Open door ({cost} points)".to_string())]);
+        let localization = Localization::new("en".to_string(), strings, HashMap::default());
+        assert_eq!(localization.localize(&LocalizedText::new("interaction.open_door").with_arg("cost", 750)), "Open door (750 points)");
+        // A missing key is seen as the key
+        assert_eq!(localization.localize(&LocalizedText::new("interaction.missing")), "interaction.missing");
+    }
+
+    #[test]
+    fn test_translations_have_the_keys_of_the_reference() {
+        let reference = language_file(FALLBACK_LANGUAGE);
+        for code in LANGUAGES {
+            let file = language_file(code);
+            let mut missing: Vec<_> = reference.strings.keys().filter(|key| !file.strings.contains_key(*key)).collect();
+            missing.sort();
+            assert!(missing.is_empty(), "{} miss {:?}", code, missing);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use bevy::{prelude::*, utils::HashMap};
+use ggrs::PlayerHandle;
+use utils::aim::aim_close;
+
+use crate::{character::player::input::{BoxInput, PackedInput}, localization::Localization};
+
+// Bandwidth of the local inputs. GGRS only send well what doesn't change, so the aim is
+// held when it moved less than the deadband, the jitter of a mouse or a stick at rest
+// doesn't reach the network. The stats compare the raw inputs with what is left after
+// the packing and the delta of GGRS, shown in the network HUD.
+
+// Size of the input before the packing
+pub const RAW_INPUT_BYTES: usize = 7;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct InputCompression {
+    // Steps of utils::aim, zero send every change
+    pub aim_angle_deadband: u32,
+    pub aim_magnitude_deadband: u32,
+}
+
+impl Default for InputCompression {
+    fn default() -> Self {
+        Self { aim_angle_deadband: 1, aim_magnitude_deadband: 1 }
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct InputCompressionStats {
+    // Last input sent by each local handle
+    last: HashMap<PlayerHandle, BoxInput>,
+    pub inputs: u64,
+    pub aim_held: u64,
+    pub unchanged: u64,
+    // Bytes different from the previous input once packed, what the delta of GGRS keep
+    pub changed_bytes: u64,
+}
+
+impl InputCompressionStats {
+    // Hold the aim of the input inside the deadband and count what is sent
+    pub fn compress(&mut self, handle: PlayerHandle, input: &mut BoxInput, settings: &InputCompression) {
+        let last = self.last.get(&handle).copied().unwrap_or_default();
+        if input.aim != last.aim && aim_close(input.aim, last.aim, settings.aim_angle_deadband, settings.aim_magnitude_deadband) {
+            input.aim = last.aim;
+            self.aim_held += 1;
+        }
+
+        let (before, after) = (PackedInput::from(last).to_bytes(), PackedInput::from(*input).to_bytes());
+        let changed = before.iter().zip(after.iter()).filter(|(a, b)| a != b).count();
+        self.inputs += 1;
+        self.changed_bytes += changed as u64;
+        if changed == 0 {
+            self.unchanged += 1;
+        }
+        self.last.insert(handle, *input);
+    }
+
+    // Lines of the network HUD
+    pub fn summary(&self, localization: &Localization) -> Vec<String> {
+        if self.inputs == 0 {
+            return vec![];
+        }
+        let percent = |count: u64| count * 100 / self.inputs;
+        vec![
+            localization.format("network.input_size", &[
+                ("raw", &RAW_INPUT_BYTES), ("packed", &PackedInput::BYTES), ("changed", &format!("{:.2}", self.changed_bytes as f32 / self.inputs as f32)),
+            ]),
+            localization.format("network.input_unchanged", &[("unchanged", &percent(self.unchanged)), ("aim", &percent(self.aim_held))]),
+        ]
+    }
+}
@@ -0,0 +1,105 @@
+pub mod compression;
+pub mod ui;
+pub mod validation;
+
+use ggrs::DesyncDetection;
+use utils::{cache::CACHE_FRAMES, frame::SimulationConfig};
+
+use crate::{jjrs::GggrsConnectionConfiguration, localization::Localization};
+
+// Tuning of the GGRS session. The input delay hide the latency by playing the local
+// inputs a few frames late, the prediction window is how far a peer run ahead of the
+// confirmed inputs before waiting, the desync interval is how often the checksums are
+// compared. Only read when the session start, the lobby panel change them before.
+
+// Longest input delay and prediction window the panel and the arguments accept
+pub const MAX_INPUT_DELAY: usize = 10;
+pub const MAX_PREDICTION_WINDOW: usize = 16;
+// The frame caches of the simulation must outlive a rollback of the whole window
+const _: () = assert!(CACHE_FRAMES > MAX_PREDICTION_WINDOW, "CACHE_FRAMES must be above MAX_PREDICTION_WINDOW");
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NetworkPreset {
+    // Same network, almost no latency to hide
+    Lan,
+    #[default]
+    Internet,
+    // Far away peers or a bad connection, more delay so the rollbacks stay short
+    HighLatency,
+}
+
+impl NetworkPreset {
+    pub const ALL: [NetworkPreset; 3] = [NetworkPreset::Lan, NetworkPreset::Internet, NetworkPreset::HighLatency];
+
+    // Name used in the arguments
+    pub fn name(&self) -> &'static str {
+        match self {
+            NetworkPreset::Lan => "lan",
+            NetworkPreset::Internet => "internet",
+            NetworkPreset::HighLatency => "high-latency",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    // Key of the name shown in the language files
+    pub fn label_key(&self) -> &'static str {
+        match self {
+            NetworkPreset::Lan => "network.preset_lan",
+            NetworkPreset::Internet => "network.preset_internet",
+            NetworkPreset::HighLatency => "network.preset_high_latency",
+        }
+    }
+
+    // Input delay, prediction window and desync interval of the preset
+    pub fn values(&self) -> (usize, usize, u32) {
+        match self {
+            NetworkPreset::Lan => (1, 6, 10),
+            NetworkPreset::Internet => (2, 8, 30),
+            NetworkPreset::HighLatency => (4, 12, 60),
+        }
+    }
+}
+
+impl GggrsConnectionConfiguration {
+    pub fn apply_preset(&mut self, preset: NetworkPreset) {
+        (self.input_delay, self.max_prediction, self.desync_interval) = preset.values();
+    }
+
+    // The preset with the same values, None once a value was tuned by hand
+    pub fn preset(&self) -> Option<NetworkPreset> {
+        NetworkPreset::ALL.into_iter().find(|preset| preset.values() == (self.input_delay, self.max_prediction, self.desync_interval))
+    }
+
+    // Keep the values in what GGRS accept
+    pub fn clamp(&mut self) {
+        self.input_delay = self.input_delay.min(MAX_INPUT_DELAY);
+        self.max_prediction = self.max_prediction.clamp(1, MAX_PREDICTION_WINDOW);
+    }
+
+    // An interval of 0 disable the checks
+    pub fn desync_detection(&self) -> DesyncDetection {
+        if self.desync_interval == 0 {
+            DesyncDetection::Off
+        } else {
+            DesyncDetection::On { interval: self.desync_interval }
+        }
+    }
+
+    // What the values mean for the player, shown in the lobby panel and the network HUD
+    pub fn effects(&self, simulation: &SimulationConfig, localization: &Localization) -> Vec<String> {
+        let ms = |frames: usize| format!("{:.0}", simulation.seconds(frames as u32) * 1000.0);
+        let desync = if self.desync_interval == 0 {
+            localization.text("network.desync_off")
+        } else {
+            localization.format("network.desync_interval", &[("frames", &self.desync_interval), ("seconds", &format!("{:.1}", simulation.seconds(self.desync_interval)))])
+        };
+        vec![
+            localization.format("network.input_delay", &[("frames", &self.input_delay), ("ms", &ms(self.input_delay))]),
+            localization.format("network.prediction", &[("frames", &self.max_prediction), ("ms", &ms(self.input_delay + self.max_prediction))]),
+            desync,
+        ]
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use bevy_inspector_egui::{bevy_egui::{EguiContexts, EguiPlugin}, egui};
+use utils::frame::SimulationConfig;
+
+use crate::{character::player::jjrs::PeerConfig, jjrs::GggrsSessionConfiguration, hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState};
+
+use super::{compression::InputCompressionStats, validation::InputSuspicion, NetworkPreset, MAX_INPUT_DELAY, MAX_PREDICTION_WINDOW};
+
+pub const NETWORK_HUD_KEY: KeyCode = KeyCode::F3;
+// Longest desync interval of the panel, in frames
+const MAX_DESYNC_INTERVAL: u32 = 240;
+
+
+#[derive(Component)]
+struct NetworkHudText;
+
+#[derive(Resource, Default)]
+struct NetworkHudState {
+    is_visible: bool,
+}
+
+
+fn preset_label(preset: Option<NetworkPreset>, localization: &Localization) -> String {
+    localization.text(preset.map_or("network.preset_custom", |preset| preset.label_key()))
+}
+
+// Panel of the lobby, the values are used when the session start
+fn network_settings_panel(
+    mut contexts: EguiContexts,
+    simulation: Res<SimulationConfig>,
+    localization: Res<Localization>,
+    mut session_config: ResMut<GggrsSessionConfiguration>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let connection = &mut session_config.connection;
+    egui::Window::new("Network").default_pos((10.0, 320.0)).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            for preset in NetworkPreset::ALL {
+                if ui.selectable_label(connection.preset() == Some(preset), localization.text(preset.label_key())).clicked() {
+                    connection.apply_preset(preset);
+                }
+            }
+        });
+        ui.add(egui::Slider::new(&mut connection.input_delay, 0..=MAX_INPUT_DELAY).text("input delay"));
+        ui.add(egui::Slider::new(&mut connection.max_prediction, 1..=MAX_PREDICTION_WINDOW).text("prediction window"));
+        ui.add(egui::Slider::new(&mut connection.desync_interval, 0..=MAX_DESYNC_INTERVAL).text("desync interval"));
+        ui.separator();
+        ui.label(preset_label(connection.preset(), &localization));
+        for line in connection.effects(&simulation, &localization) {
+            ui.label(line);
+        }
+    });
+}
+
+fn setup_network_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        NetworkHudText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 14.0,
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        HudSlot::new(HudAnchor::TopLeft, 2),
+        Node {
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+fn toggle_network_hud(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<NetworkHudState>,
+) {
+    if keyboard_input.just_pressed(NETWORK_HUD_KEY) {
+        state.is_visible = !state.is_visible;
+    }
+}
+
+fn update_network_hud(
+    state: Res<NetworkHudState>,
+    simulation: Res<SimulationConfig>,
+    localization: Res<Localization>,
+    session_config: Res<GggrsSessionConfiguration>,
+    session: Option<Res<Session<PeerConfig>>>,
+    compression_stats: Res<InputCompressionStats>,
+    input_suspicion: Res<InputSuspicion>,
+    mut q_text: Query<(&mut Text, &mut Visibility), With<NetworkHudText>>,
+) {
+    let Ok((mut text, mut visibility)) = q_text.get_single_mut() else {
+        return;
+    };
+
+    if !state.is_visible {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    let connection = &session_config.connection;
+    let mut lines = vec![localization.format("network.title", &[("key", &format!("{:?}", NETWORK_HUD_KEY)), ("preset", &preset_label(connection.preset(), &localization))])];
+    lines.extend(connection.effects(&simulation, &localization));
+
+    match session.as_deref() {
+        Some(Session::P2P(session)) => {
+            lines.push(localization.format("network.frames_ahead", &[("frames", &session.frames_ahead())]));
+            for handle in session.remote_player_handles() {
+                match session.network_stats(handle) {
+                    Ok(stats) => lines.push(localization.format("network.peer", &[
+                        ("player", &(handle + 1)),
+                        ("ping", &format!("{:>4}", stats.ping)),
+                        ("local", &format!("{:>2}", stats.local_frames_behind)),
+                        ("remote", &format!("{:<2}", stats.remote_frames_behind)),
+                        ("queue", &format!("{:>3}", stats.send_queue_len)),
+                        ("kbps", &stats.kbps_sent),
+                    ])),
+                    Err(_) => lines.push(localization.format("network.peer_synchronizing", &[("player", &(handle + 1))])),
+                }
+            }
+        }
+        Some(Session::SyncTest(_)) => lines.push(localization.text("network.synctest")),
+        _ => lines.push(localization.text("network.no_session")),
+    }
+    lines.extend(compression_stats.summary(&localization));
+    lines.extend(input_suspicion.summary(&localization));
+
+    text.0 = lines.join("\n");
+}
+
+
+#[derive(Default)]
+pub struct NetworkUIPlugin;
+
+impl Plugin for NetworkUIPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<NetworkHudState>();
+        app.add_systems(Update, network_settings_panel.run_if(in_state(AppState::Lobby)).run_if(resource_exists::<GggrsSessionConfiguration>));
+        app.add_systems(OnEnter(AppState::InGame), setup_network_hud);
+        app.add_systems(Update, (toggle_network_hud, update_network_hud)
+            .run_if(in_state(AppState::InGame))
+            .run_if(resource_exists::<GggrsSessionConfiguration>));
+    }
+}
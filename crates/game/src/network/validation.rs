@@ -0,0 +1,297 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::PlayerInputs;
+use ggrs::{InputStatus, PlayerHandle};
+use utils::{aim::{aim_angle_step, aim_magnitude}, frame::SimulationConfig};
+
+use crate::{character::player::{input::{BoxInput, INPUT_DOWN, INPUT_KNOWN_BITS, INPUT_LEFT, INPUT_RIGHT, INPUT_UP, SLOT_MOVE_BITS}, jjrs::PeerConfig}, frame::FrameCount, localization::Localization, plugins::AppState};
+
+// Sanity of the inputs of the peers. Every input is cleaned at the start of the rollback
+// schedule, the same on every peer as they all get the same inputs, so a value our own
+// client never send can't reach the simulation. The confirmed inputs of each peer also
+// raise a suspicion score, decaying with time, shown in the network HUD and logged
+// when it get high. Nothing is done to the peer, it is only to know.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputAnomaly {
+    // Bits without a meaning in the buttons
+    UnknownButtons,
+    // A slot move missing one of its slots, or on the same slot
+    BrokenSlotMove,
+    // An angle with a magnitude of zero, the encoding never send it
+    LooseAim,
+    // Fire pressed and released faster than a hand can
+    FireFlipRate,
+}
+
+impl InputAnomaly {
+    pub fn label(&self) -> &'static str {
+        match self {
+            InputAnomaly::UnknownButtons => "unknown buttons",
+            InputAnomaly::BrokenSlotMove => "broken slot move",
+            InputAnomaly::LooseAim => "loose aim",
+            InputAnomaly::FireFlipRate => "fire flip rate",
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct InputValidationSettings {
+    // Press and release of the fire in a second, over it is a macro or a forged input
+    pub max_fire_flips_per_second: usize,
+    pub suspicion_per_anomaly: f32,
+    pub suspicion_decay_per_second: f32,
+    // Logged once each time the score go over it
+    pub warn_suspicion: f32,
+}
+
+impl Default for InputValidationSettings {
+    fn default() -> Self {
+        Self {
+            max_fire_flips_per_second: 30,
+            suspicion_per_anomaly: 1.0,
+            suspicion_decay_per_second: 0.5,
+            warn_suspicion: 20.0,
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct PeerSuspicion {
+    pub score: f32,
+    pub anomalies: u64,
+    pub last_anomaly: Option<InputAnomaly>,
+    // Last confirmed frame looked at, the frames are confirmed in order
+    last_frame: Option<u32>,
+    last_fire: bool,
+    fire_flips: VecDeque<u32>,
+    warned: bool,
+}
+
+// Not part of the rollback, only the confirmed inputs are counted and only once
+#[derive(Resource, Default, Debug)]
+pub struct InputSuspicion {
+    pub peers: HashMap<PlayerHandle, PeerSuspicion>,
+}
+
+impl InputSuspicion {
+    // Lines of the network HUD, only the peers with an anomaly
+    pub fn summary(&self, localization: &Localization) -> Vec<String> {
+        let mut handles: Vec<_> = self.peers.iter().filter(|(_, peer)| peer.anomalies > 0).collect();
+        handles.sort_by_key(|(handle, _)| **handle);
+        handles.into_iter().map(|(handle, peer)| localization.format("network.suspicion", &[
+            ("player", &(handle + 1)),
+            ("score", &format!("{:.1}", peer.score)),
+            ("anomalies", &peer.anomalies),
+            ("last", &peer.last_anomaly.map_or_else(|| localization.text("network.no_anomaly"), |anomaly| anomaly.label().to_string())),
+        ])).collect()
+    }
+}
+
+/// Clean the input in place, returning what was wrong with it
+pub fn sanitize_input(input: &mut BoxInput) -> Vec<InputAnomaly> {
+    let mut anomalies = vec![];
+
+    if input.buttons & !INPUT_KNOWN_BITS != 0 {
+        input.buttons &= INPUT_KNOWN_BITS;
+        anomalies.push(InputAnomaly::UnknownButtons);
+    }
+
+    // A keyboard can hold both, they cancel each other anyway so it is not suspicious
+    for pair in [INPUT_UP | INPUT_DOWN, INPUT_LEFT | INPUT_RIGHT] {
+        if input.buttons & pair == pair {
+            input.buttons &= !pair;
+        }
+    }
+
+    let broken_move = input.slot_move & !SLOT_MOVE_BITS != 0
+        || (input.slot_move != 0 && input.slot_move().map_or(true, |(from, to)| from == to));
+    if broken_move {
+        input.slot_move = 0;
+        anomalies.push(InputAnomaly::BrokenSlotMove);
+    }
+
+    if aim_magnitude(input.aim) == 0 && aim_angle_step(input.aim) != 0 {
+        input.aim = 0;
+        anomalies.push(InputAnomaly::LooseAim);
+    }
+
+    anomalies
+}
+
+impl PeerSuspicion {
+    fn record(&mut self, frame: u32, input: &BoxInput, mut anomalies: Vec<InputAnomaly>, settings: &InputValidationSettings, simulation: &SimulationConfig) {
+        let elapsed = self.last_frame.map_or(0, |last| frame.saturating_sub(last));
+        self.last_frame = Some(frame);
+        self.score = (self.score - settings.suspicion_decay_per_second * elapsed as f32 * simulation.timestep()).max(0.0);
+
+        let window = simulation.frames_from_seconds(1.0);
+        if input.fire != self.last_fire {
+            self.fire_flips.push_back(frame);
+        }
+        self.last_fire = input.fire;
+        while self.fire_flips.front().map_or(false, |flip| frame.saturating_sub(*flip) >= window) {
+            self.fire_flips.pop_front();
+        }
+        if self.fire_flips.len() > settings.max_fire_flips_per_second {
+            anomalies.push(InputAnomaly::FireFlipRate);
+        }
+
+        if let Some(last) = anomalies.last() {
+            self.anomalies += anomalies.len() as u64;
+            self.last_anomaly = Some(*last);
+            self.score += settings.suspicion_per_anomaly * anomalies.len() as f32;
+        }
+        if self.score < settings.warn_suspicion {
+            self.warned = false;
+        }
+    }
+}
+
+
+// SYSTEMS
+
+// First of the rollback schedule, before anything read the inputs
+pub fn rollback_validate_inputs(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    settings: Res<InputValidationSettings>,
+    mut inputs: ResMut<PlayerInputs<PeerConfig>>,
+    mut suspicion: ResMut<InputSuspicion>,
+) {
+    for (handle, (input, status)) in inputs.iter_mut().enumerate() {
+        let anomalies = sanitize_input(input);
+
+        if *status != InputStatus::Confirmed {
+            continue;
+        }
+        let peer = suspicion.peers.entry(handle).or_default();
+        if peer.last_frame.map_or(false, |last| frame.frame <= last) {
+            continue;
+        }
+        peer.record(frame.frame, input, anomalies, &settings, &simulation);
+
+        if peer.score >= settings.warn_suspicion && !peer.warned {
+            peer.warned = true;
+            warn!(
+                "inputs of player {} look forged, suspicion {:.1} after {} anomalies, last {}",
+                handle + 1, peer.score, peer.anomalies, peer.last_anomaly.map_or("none", |anomaly| anomaly.label()),
+            );
+        }
+    }
+}
+
+// The frames start again with the next match
+fn clear_input_suspicion(mut suspicion: ResMut<InputSuspicion>) {
+    suspicion.peers.clear();
+}
+
+
+pub struct InputValidationPlugin;
+
+impl Plugin for InputValidationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputValidationSettings>()
+            .register_type::<InputValidationSettings>()
+            .init_resource::<InputSuspicion>()
+            .add_systems(OnExit(AppState::InGame), clear_input_suspicion);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use utils::aim::{encode_aim, AIM_MAGNITUDE_BITS};
+
+    use crate::character::player::input::{INPUT_DASH, INPUT_RELOAD};
+
+    use super::*;
+
+    fn valid_input() -> BoxInput {
+        let mut input = BoxInput { buttons: INPUT_UP | INPUT_RIGHT | INPUT_RELOAD | INPUT_DASH, fire: true, ..default() };
+        input.aim = encode_aim(Vec2::new(120.0, -80.0));
+        input.set_selected_slot(2);
+        input.set_slot_move(0, 3);
+        input
+    }
+
+    #[test]
+    fn test_valid_input_is_unchanged() {
+        let mut input = valid_input();
+        assert!(sanitize_input(&mut input).is_empty());
+        assert_eq!(input, valid_input());
+
+        let mut idle = BoxInput::default();
+        assert!(sanitize_input(&mut idle).is_empty());
+        assert_eq!(idle, BoxInput::default());
+    }
+
+    #[test]
+    fn test_unknown_bits_are_cleared() {
+        let mut input = valid_input();
+        input.buttons |= 1 << 15;
+        assert_eq!(sanitize_input(&mut input), vec![InputAnomaly::UnknownButtons]);
+        assert_eq!(input, valid_input());
+    }
+
+    #[test]
+    fn test_out_of_range_slot_move_is_dropped() {
+        // Bits above the two slots
+        let mut input = valid_input();
+        input.slot_move |= 1 << 7;
+        assert_eq!(sanitize_input(&mut input), vec![InputAnomaly::BrokenSlotMove]);
+        assert_eq!(input.slot_move, 0);
+
+        // One slot missing, then on the same slot
+        for slot_move in [0b000_011, 0b011_011] {
+            let mut input = BoxInput { slot_move, ..default() };
+            assert_eq!(sanitize_input(&mut input), vec![InputAnomaly::BrokenSlotMove]);
+            assert_eq!(input.slot_move, 0);
+        }
+    }
+
+    #[test]
+    fn test_conflicting_buttons_cancel_without_anomaly() {
+        let mut input = BoxInput { buttons: INPUT_UP | INPUT_DOWN | INPUT_LEFT | INPUT_RIGHT | INPUT_RELOAD, ..default() };
+        assert!(sanitize_input(&mut input).is_empty());
+        assert_eq!(input.buttons, INPUT_RELOAD);
+
+        // Only the pair held together is dropped
+        let mut input = BoxInput { buttons: INPUT_LEFT | INPUT_RIGHT | INPUT_UP, ..default() };
+        sanitize_input(&mut input);
+        assert_eq!(input.buttons, INPUT_UP);
+    }
+
+    #[test]
+    fn test_loose_aim_is_cleared() {
+        // An angle without magnitude
+        let mut input = BoxInput { aim: 12 << AIM_MAGNITUDE_BITS, ..default() };
+        assert_eq!(sanitize_input(&mut input), vec![InputAnomaly::LooseAim]);
+        assert_eq!(input.aim, 0);
+    }
+
+    #[test]
+    fn test_non_finite_aim_is_encoded_in_range() {
+        // What a peer can send from a broken pointer, the packing keep it valid
+        for offset in [Vec2::NAN, Vec2::new(f32::INFINITY, 0.0), Vec2::new(1.0e9, -1.0e9)] {
+            let mut input = BoxInput { aim: encode_aim(offset), ..default() };
+            let aim = input.aim;
+            assert!(sanitize_input(&mut input).is_empty(), "{:?}", offset);
+            assert_eq!(input.aim, aim);
+        }
+        // The largest value of the field is a valid aim
+        let mut input = BoxInput { aim: u16::MAX, ..default() };
+        assert!(sanitize_input(&mut input).is_empty());
+        assert_eq!(input.aim, u16::MAX);
+    }
+
+    #[test]
+    fn test_every_anomaly_at_once() {
+        let mut input = BoxInput { buttons: (1 << 15) | INPUT_UP | INPUT_DOWN, slot_move: 0b001_001, aim: 1 << AIM_MAGNITUDE_BITS, ..default() };
+        assert_eq!(sanitize_input(&mut input), vec![InputAnomaly::UnknownButtons, InputAnomaly::BrokenSlotMove, InputAnomaly::LooseAim]);
+        assert_eq!(input, BoxInput::default());
+    }
+}
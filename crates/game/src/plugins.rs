@@ -1,40 +1,79 @@
 use bevy::{asset::AssetMetaCheck, prelude::*};
 use bevy_ggrs::{prelude::*, GgrsSchedule};
 use bevy_kira_audio::prelude::*;
+use bevy_matchbox::MatchboxSocket;
+use crate::lobby::moderation::{lobby_moderation_system, KickPeer, LobbyModeration, LobbyUIPlugin};
 use leafwing_input_manager::plugin::InputManagerPlugin;
-use map::game::entity::map::enemy_spawn::EnemySpawnerComponent;
-use utils::rng::RollbackRng;
+use map::game::{entity::map::{climb::ClimbableWallComponent, destructible::DestructibleComponent, enemy_spawn::EnemySpawnerComponent, hazard::HazardComponent, switch::SwitchComponent}, nav::NavGrid};
+use utils::{events::RollbackEventsAppExt, link::{prune_dead_links, EntityLinksAppExt}, rng::RngStreamAppExt, frame::SimulationConfig, schema::VersionedRonAssetPlugin};
 use std::hash::Hash;
-use bevy_common_assets::ron::RonAssetPlugin;
 
 use animation::{set_sprite_flip, D2AnimationPlugin};
 use bevy_ggrs::GgrsPlugin;
 
 use crate::{
-    audio::ZAudioPlugin,
-    camera::CameraControlPlugin,
+    audio::{weapons::WeaponSoundEvent, ZAudioPlugin},
+    bullet_time::BulletTimePlugin,
+    capture::{record_input_log, CapturePlugin, InputLog},
+    budget::{rollback_cull_bullets, ui::BudgetUIPlugin, BudgetStats, SimulationBudget},
+    equipment::{rollback_collect_equipment, rollback_drop_equipment, rollback_expire_equipment, setup_map_equipment, ui::EquipmentUIPlugin, Armor, Backpack, EquipmentConfig, EquipmentPickup, SpeedBoost},
+    network::{compression::{InputCompression, InputCompressionStats}, ui::NetworkUIPlugin, validation::{rollback_validate_inputs, InputValidationPlugin}},
+    trade::{rollback_collect_trades, rollback_trade_drops, ui::TradeUIPlugin, TradeConfig, TradePickup, TradeState},
+    practice::PracticePlugin,
+    lobby::{identity::PlayerIdentity, voice::VoiceActivityPlugin},
+    rng::{AiStream, DropsStream, SpawningStream, WeaponsStream},
+    web::WebInputPlugin,
+    fog::FogOfWarPlugin,
+    lighting::LightingPlugin,
+    telemetry::TelemetryPlugin,
+    progression::ProgressionPlugin,
+    destructible::{rollback_crumble_destructibles, Debris, DestructiblePlugin},
+    barricade::{rollback_barricade_system, rollback_repair_barricades, Barricade, BarricadePlugin},
+    door::{rollback_open_doors, Door, DoorPlugin},
+    tutorial::{rollback_tutorial_system, ui::TutorialUIPlugin, TutorialState},
+    rules::{announcement::AnnouncementUIPlugin, dropin::{rollback_queue_drop_ins, rollback_spawn_drop_ins, DropInPlaces, DropInQueue, DropInRequest, DropInUIPlugin}, deathmatch::{rollback_deathmatch_timer, rollback_intercept_player_deaths, rollback_respawn_players, Respawning}, objective::{rollback_check_generator, rollback_enemies_attack_generator, Generator}, rollback_advance_waves, ui::RulesUIPlugin, GameRules, MatchState, WaveStartedEvent, WaveState},
+    hazard::{fire::{rollback_burn_damage, rollback_spread_fire, FirePlugin, Flammable, HazardSettings}, rollback_electric_fence_system, rollback_electric_trap_system, rollback_explode_barrels, rollback_fan_trap_system, rollback_fire_patch_system, switch::{rollback_use_hazard_switches, HazardSwitchPlugin, SwitchState}, HazardPlugin, HazardState},
+    hint::HintPlugin,
+    hud::HudPlugin,
+    localization::{LanguageFile, LocalizationPlugin},
+    weapons::explosion::{rollback_process_explosions, ExplosionEvent, ExplosionMarker},
+    points::{rollback_award_kill_points, rollback_award_player_kills, ui::ScoreboardUIPlugin, PlayerPoints, PlayerScore, PointsConfig},
+    interaction::{ui::InteractionUIPlugin, Interactable},
+    powerup::{rollback_collect_power_ups, rollback_drop_power_ups, rollback_tick_power_ups, ui::PowerUpUIPlugin, ActivePowerUps, PowerUpConfig, PowerUpPickup},
+    camera::{grading::PostProcessPresets, CameraControlPlugin},
     character::{
         config::CharacterConfig,
         dash::DashState,
+        status::{rollback_clear_expired_status, Grabbed, Knockback, Stunned},
+        footprint::FootprintPlugin,
+        corpse::CorpsePlugin,
+        ability::{rollback_healing_auras, rollback_use_abilities, AbilityState, AbilityUIPlugin, AuraProtected, HealingAura},
         enemy::{
             ai::pathing::{
                 calculate_paths,
+                rollback_bake_nav_grid,
                 check_direct_paths,
                 move_enemies,
                 update_enemy_targets,
                 EnemyPath,
-                PathfindingConfig
+                PathfindingConfig,
+                SeparationCache
             },
+            attack::{rollback_enemy_attacks, EnemyAttackState, HeavyHitEvent},
+            climb::{rollback_climb_walls, setup_map_climbable_walls, Climbing},
+            exploder::{rollback_explode_dead_exploders, rollback_exploder_fuse, ExploderCuePlugin, ExploderState, FuseLitEvent},
+            flinch::FlinchState,
+            shield::{rollback_turn_shields, ShieldBrokenEvent, ShieldCuePlugin, ShieldState},
             spawning::{
-                enemy_spawn_from_spawners_system, EnemySpawnerState
+                enemy_spawn_from_spawners_system, rollback_track_spawner_heat, EnemySpawnerState, SpawnFairnessConfig
             },
             Enemy
         },
         health::{
             rollback_apply_accumulated_damage,
             rollback_apply_death,
-            ui::update_health_bars,
-            DamageAccumulator, Death, Health}, movement::{SprintState, Velocity}, player::{control::PlayerAction, input::{apply_friction, apply_inputs, move_characters, read_local_inputs, update_animation_state, PointerWorldPosition}, jjrs::PeerConfig, Player}}, collider::{Collider, CollisionLayer, CollisionSettings, Wall}, debug::SpriteDebugOverlayPlugin, frame::{increase_frame_system, FrameCount}, global_asset::{add_global_asset, loading_asset_system}, jjrs::{log_ggrs_events, setup_ggrs_local, start_matchbox_socket, wait_for_players, GggrsSessionConfiguration}, weapons::{bullet_rollback_collision_system, bullet_rollback_system, system_weapon_position, ui::WeaponDebugUIPlugin, weapon_inventory_system, weapon_rollback_system, weapons_config_update_system, Bullet, BulletRollbackState, WeaponInventory, WeaponModesState, WeaponState, WeaponsConfig}};
+            ui::{update_health_bars, DamageNumbersPlugin, HeavyHitUIPlugin},
+            DamageAccumulator, DamageContributions, DamageEvent, Death, DeathEvent, Health}, movement::{SprintState, Velocity}, player::{aim::AimPlugin, control::PlayerAction, input::{apply_friction, apply_inputs, buffer_local_inputs, move_characters, read_local_inputs, rollback_store_previous_inputs, update_animation_state, PointerWorldPosition, PreviousInput}, source::LocalInputSources, jjrs::PeerConfig, Player}}, collider::{bounds::{clear_world_range_audit, log_confirmed_world_range, rollback_audit_world_range, WorldRangeAudit, WorldRangeEvent}, preset::{apply_collision_preset, collision_presets_update_system, CollisionPresets}, spatial::{rollback_update_spatial_hash, SpatialHash, SteeringObstacle}, Collider, CollisionLayer, CollisionSettings, Wall}, debug::{inspector::InspectorPlugin, SpriteDebugOverlayPlugin}, frame::{increase_frame_system, FrameCount}, global_asset::{add_global_asset, loading_asset_system}, jjrs::{log_ggrs_events, setup_ggrs_local, start_matchbox_socket, wait_for_players, GggrsSessionConfiguration}, weapons::{melee::MeleeState, HitMarker, trigger::TriggerState, upgrade::{rollback_upgrade_station_system, UpgradeStation}, wall::{rollback_wall_weapon_system, WallWeapon}, weapon_tint_system, bullet_rollback_collision_system, bullet_rollback_system, system_weapon_position, decal::ImpactDecalPlugin, sway::WeaponSwayPlugin, trail::BulletTrailPlugin, near_miss::NearMissPlugin, pickup::WeaponPickupPlugin, ui::WeaponDebugUIPlugin, wheel::WeaponWheelUIPlugin, inventory::InventoryUIPlugin, weapon_inventory_system, weapon_rollback_system, rollback_dissipate_weapon_heat, weapons_config_update_system, Bullet, BulletImpactEvent, BulletRollbackState, WeaponFiredEvent, WeaponInventory, WeaponModesState, WeaponState, WeaponsConfig}};
 
 #[derive(Debug, Clone, Default, Eq, PartialEq, Hash, States)]
 pub enum AppState {
@@ -44,6 +83,11 @@ pub enum AppState {
     InGame,
 }
 
+// Entities placed in the map get their simulation components in the lobby, before the
+// session start. Never from a rollback frame, the snapshots before it would not know them
+#[derive(Debug, Clone, Eq, PartialEq, Hash, SystemSet)]
+pub struct MapSetupSet;
+
 #[derive(Debug, Clone, Resource)]
 pub struct GameInfo {
     pub version: String,
@@ -55,32 +99,107 @@ impl Default for GameInfo {
     } 
 }
 
-pub struct BaseZombieGamePlugin { online: bool }
+pub struct BaseZombieGamePlugin { online: bool, headless: bool, simulation: SimulationConfig, budget: SimulationBudget }
 
 impl BaseZombieGamePlugin {
     pub fn new(online: bool) -> Self {
-        Self { online: online }
+        Self { online: online, headless: false, simulation: SimulationConfig::default(), budget: SimulationBudget::default() }
+    }
+
+    // Only the simulation, without the rendering, the audio, the UI and the local inputs
+    pub fn headless(mut self) -> Self {
+        self.headless = true;
+        self
+    }
+
+    // Tick rate of the simulation, and a time scale for slow motion in local games
+    pub fn with_simulation(mut self, simulation: SimulationConfig) -> Self {
+        self.simulation = simulation;
+        self
+    }
+
+    // Caps on the enemies, the bullets and the pickups of the simulation
+    pub fn with_budget(mut self, budget: SimulationBudget) -> Self {
+        self.budget = budget;
+        self
     }
 }
 
 impl Plugin for BaseZombieGamePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<GameInfo>();
-        app.add_plugins(SpriteDebugOverlayPlugin{});
 
-        app.add_plugins(ZAudioPlugin {});
+        // Everything only seen or heard, a headless peer has no window and no audio device
+        if !self.headless {
+            app.add_plugins(SpriteDebugOverlayPlugin{});
+            app.add_plugins(InspectorPlugin);
+
+            app.add_plugins(ZAudioPlugin {});
+            app.add_plugins(LocalizationPlugin);
+            app.add_plugins(HudPlugin);
+
+            app.add_plugins(WeaponDebugUIPlugin);
+            app.add_plugins(WeaponWheelUIPlugin);
+            app.add_plugins(InventoryUIPlugin);
+            app.add_plugins(WeaponSwayPlugin);
+            app.add_plugins(ImpactDecalPlugin);
+            app.add_plugins(BulletTrailPlugin);
+            app.add_plugins(NearMissPlugin);
+            app.add_plugins(WeaponPickupPlugin);
+            app.add_plugins(PowerUpUIPlugin);
+            app.add_plugins(InteractionUIPlugin);
+            app.add_plugins(CameraControlPlugin);
+            app.add_plugins(FogOfWarPlugin);
+            app.add_plugins(LightingPlugin);
+            app.add_plugins(AimPlugin);
+            app.add_plugins(WebInputPlugin);
+            app.add_plugins(RulesUIPlugin);
+            app.add_plugins(AnnouncementUIPlugin);
+            app.add_plugins(DropInUIPlugin);
+            app.add_plugins(HeavyHitUIPlugin);
+            app.add_plugins(ExploderCuePlugin);
+            app.add_plugins(ShieldCuePlugin);
+            app.add_plugins(FirePlugin);
+            app.add_plugins(AbilityUIPlugin);
+            app.add_plugins(FootprintPlugin);
+            app.add_plugins(CorpsePlugin);
+            app.add_plugins(DamageNumbersPlugin);
+            app.add_plugins(HintPlugin);
+            app.add_plugins(ScoreboardUIPlugin);
+            app.add_plugins(VoiceActivityPlugin);
+            app.add_plugins(TutorialUIPlugin);
+            app.add_plugins(BudgetUIPlugin);
+            app.add_plugins(TradeUIPlugin);
+            app.add_plugins(EquipmentUIPlugin);
+            app.add_plugins(NetworkUIPlugin);
+            app.add_plugins(CapturePlugin);
+        }
 
         app.add_plugins(D2AnimationPlugin);
-        app.add_plugins(WeaponDebugUIPlugin);
-        app.add_plugins(CameraControlPlugin);
+        app.add_plugins(TelemetryPlugin);
+        app.add_plugins(ProgressionPlugin);
+        app.add_plugins(BarricadePlugin);
+        app.add_plugins(DoorPlugin);
+        app.add_plugins(HazardPlugin);
+        app.add_plugins(HazardSwitchPlugin);
+        app.add_plugins(DestructiblePlugin);
 
         app.add_plugins((
-            RonAssetPlugin::<CharacterConfig>::new(&["ron"]),
-            RonAssetPlugin::<WeaponsConfig>::new(&["ron"]),
+            VersionedRonAssetPlugin::<CharacterConfig>::default(),
+            VersionedRonAssetPlugin::<WeaponsConfig>::default(),
+            VersionedRonAssetPlugin::<CollisionPresets>::default(),
+            VersionedRonAssetPlugin::<PostProcessPresets>::default(),
+            VersionedRonAssetPlugin::<HazardSettings>::default(),
+            VersionedRonAssetPlugin::<LanguageFile>::default(),
         ));
 
         app.add_plugins(InputManagerPlugin::<PlayerAction>::default());
         app.init_resource::<PointerWorldPosition>();
+        app.init_resource::<LocalInputSources>();
+        app.init_resource::<InputCompression>();
+        app.register_type::<InputCompression>();
+        app.init_resource::<InputCompressionStats>();
+        app.add_plugins(InputValidationPlugin);
 
 
         app.init_resource::<CollisionSettings>();
@@ -88,20 +207,101 @@ impl Plugin for BaseZombieGamePlugin {
         app.init_state::<AppState>();
 
         app.init_resource::<PathfindingConfig>();
+        app.init_resource::<SpatialHash>();
+        app.init_resource::<NavGrid>();
+        app.init_resource::<SeparationCache>();
+        app.init_resource::<PointsConfig>();
+        app.init_resource::<PowerUpConfig>();
+        app.init_resource::<SpawnFairnessConfig>();
+        app.init_resource::<ActivePowerUps>();
+        app.init_resource::<GameRules>();
+        app.init_resource::<WaveState>();
+        app.init_resource::<DropInPlaces>();
+        app.init_resource::<DropInRequest>();
+        app.init_resource::<DropInQueue>();
+        app.init_resource::<MatchState>();
+        app.init_resource::<TutorialState>();
+        app.insert_resource(self.budget.clone());
+        app.register_type::<SimulationBudget>();
+        app.init_resource::<BudgetStats>();
+        app.init_resource::<WorldRangeAudit>();
+        app.init_resource::<TradeConfig>();
+        app.init_resource::<EquipmentConfig>();
+        // Also filled by the recorders, from the rollback schedule
+        app.init_resource::<InputLog>();
+        app.init_resource::<PlayerIdentity>();
 
-        app.set_rollback_schedule_fps(60);
+        // Every peer must run at the same speed, slow motion is only for local games
+        let mut simulation = self.simulation;
+        if self.online && simulation.time_scale != 1.0 {
+            warn!("ignoring the time scale {} in an online game", simulation.time_scale);
+            simulation.time_scale = 1.0;
+        }
+        app.insert_resource(simulation);
+        app.set_rollback_schedule_fps(simulation.rollback_fps());
+        app.add_plugins(BulletTimePlugin { enabled: !self.online });
         app.add_plugins(GgrsPlugin::<PeerConfig>::default())
-            .rollback_resource_with_copy::<RollbackRng>()
+            .add_rng_stream::<WeaponsStream>()
+            .add_rng_stream::<SpawningStream>()
+            .add_rng_stream::<DropsStream>()
+            .add_rng_stream::<AiStream>()
             .rollback_resource_with_reflect::<PathfindingConfig>()
             .rollback_resource_with_copy::<PointerWorldPosition>()
             .rollback_resource_with_copy::<FrameCount>()
+            .rollback_resource_with_copy::<ActivePowerUps>()
+            .rollback_resource_with_copy::<WaveState>()
+            .rollback_resource_with_clone::<DropInQueue>()
+            .rollback_resource_with_copy::<MatchState>()
+            .rollback_resource_with_clone::<TutorialState>()
+            .rollback_resource_with_copy::<BudgetStats>()
+            .rollback_component_with_copy::<Generator>()
+            .rollback_component_with_copy::<Respawning>()
+            .rollback_component_with_copy::<PlayerScore>()
+            .rollback_component_with_clone::<PowerUpPickup>()
+            .rollback_component_with_clone::<TradePickup>()
+            .rollback_component_with_copy::<TradeState>()
+            .rollback_component_with_clone::<EquipmentPickup>()
+            .rollback_component_with_copy::<Armor>()
+            .rollback_component_with_copy::<SpeedBoost>()
+            .rollback_component_with_copy::<Backpack>()
+            .rollback_component_with_copy::<PlayerPoints>()
+            .rollback_component_with_copy::<PreviousInput>()
+            .rollback_component_with_clone::<Interactable>()
+            .rollback_component_with_clone::<UpgradeStation>()
+            .rollback_component_with_clone::<WallWeapon>()
+            .rollback_component_with_copy::<Barricade>()
+            .rollback_component_with_copy::<Door>()
+            .rollback_component_with_clone::<DestructibleComponent>()
+            .rollback_component_with_clone::<ClimbableWallComponent>()
+            .rollback_component_with_copy::<Debris>()
+            .rollback_component_with_clone::<HazardComponent>()
+            .rollback_component_with_copy::<HazardState>()
+            .rollback_component_with_copy::<Flammable>()
+            .rollback_component_with_clone::<SwitchComponent>()
+            .rollback_component_with_copy::<SwitchState>()
+            .rollback_component_with_copy::<Stunned>()
+            .rollback_component_with_copy::<Knockback>()
+            .rollback_component_with_copy::<Grabbed>()
+            .rollback_component_with_copy::<AbilityState>()
+            .rollback_component_with_copy::<HealingAura>()
+            .rollback_component_with_copy::<AuraProtected>()
+            .rollback_component_with_copy::<EnemyAttackState>()
+            .rollback_component_with_copy::<FlinchState>()
+            .rollback_component_with_copy::<ExploderState>()
+            .rollback_component_with_copy::<ShieldState>()
+            .rollback_component_with_clone::<Climbing>()
+            .rollback_component_with_copy::<SteeringObstacle>()
+            .rollback_component_with_clone::<ExplosionMarker>()
             .rollback_component_with_clone::<EnemySpawnerComponent>()
             .rollback_component_with_reflect::<EnemySpawnerState>()
             .rollback_component_with_reflect::<Health>()
             .rollback_component_with_reflect::<DamageAccumulator>()
+            .rollback_component_with_clone::<DamageContributions>()
             .rollback_component_with_clone::<WeaponInventory>()
             .rollback_component_with_clone::<WeaponModesState>()
             .rollback_component_with_clone::<WeaponState>()
+            .rollback_component_with_clone::<TriggerState>()
+            .rollback_component_with_clone::<MeleeState>()
             .rollback_component_with_clone::<Bullet>()
             .rollback_component_with_clone::<BulletRollbackState>()
             .rollback_component_with_clone::<Collider>()
@@ -116,20 +316,47 @@ impl Plugin for BaseZombieGamePlugin {
             .rollback_component_with_reflect::<EnemyPath>()
             .rollback_component_with_reflect::<Enemy>();
 
+        app.add_rollback_events::<DamageEvent>()
+            .add_rollback_events::<DeathEvent>()
+            .add_rollback_events::<WeaponFiredEvent>()
+            .add_rollback_events::<WeaponSoundEvent>()
+            .add_rollback_events::<WaveStartedEvent>()
+            .add_rollback_events::<HeavyHitEvent>()
+            .add_rollback_events::<FuseLitEvent>()
+            .add_rollback_events::<ShieldBrokenEvent>()
+            .add_rollback_events::<ExplosionEvent>()
+            .add_rollback_events::<BulletImpactEvent>()
+            .add_rollback_events::<WorldRangeEvent>();
+
         app.add_systems(Startup, (add_global_asset));
         app.add_systems(Update, loading_asset_system.run_if(in_state(AppState::Loading)));
+        app.configure_sets(Update, MapSetupSet.run_if(in_state(AppState::Lobby)).run_if(resource_exists::<CollisionSettings>));
+        app.add_systems(Update, (setup_map_climbable_walls, setup_map_equipment).in_set(MapSetupSet));
         
 
         if self.online {
             app.add_systems(Startup, start_matchbox_socket.after(add_global_asset));
-            app.add_systems(Update, wait_for_players.run_if(in_state(AppState::Lobby)));
+            app.init_resource::<LobbyModeration>();
+            app.add_event::<KickPeer>();
+            if !self.headless {
+                app.add_plugins(LobbyUIPlugin);
+            }
+            app.add_systems(Update, (
+                lobby_moderation_system,
+                apply_collision_preset,
+                wait_for_players.after(lobby_moderation_system).after(apply_collision_preset).after(MapSetupSet),
+            ).run_if(in_state(AppState::Lobby)).run_if(resource_exists::<MatchboxSocket>));
             app.add_systems(Update, log_ggrs_events.run_if(in_state(AppState::InGame)));
         } else {
-            app.add_systems(OnEnter(AppState::Lobby), setup_ggrs_local.after(add_global_asset));
+            app.add_systems(OnEnter(AppState::Lobby), (apply_collision_preset, setup_ggrs_local.after(add_global_asset).after(apply_collision_preset)));
+            app.add_plugins(PracticePlugin);
         }
 
 
-        app.add_systems(ReadInputs, read_local_inputs);
+        if !self.headless {
+            app.add_systems(Update, buffer_local_inputs.run_if(in_state(AppState::InGame)));
+            app.add_systems(ReadInputs, read_local_inputs);
+        }
         app.insert_resource(FrameCount { frame: 0 });
         app.add_systems(
             GgrsSchedule, (
@@ -150,6 +377,7 @@ impl Plugin for BaseZombieGamePlugin {
                 update_animation_state.after(set_sprite_flip),
                 // SPAWING
                 enemy_spawn_from_spawners_system.after(update_animation_state),
+                rollback_track_spawner_heat.after(rollback_apply_accumulated_damage).before(rollback_apply_death),
                 // LOGIC OF ENEMY
                 update_enemy_targets.after(enemy_spawn_from_spawners_system),
                 check_direct_paths.after(update_enemy_targets),
@@ -158,11 +386,108 @@ impl Plugin for BaseZombieGamePlugin {
                 
                 increase_frame_system.after(move_enemies)
             ));
+        app.add_systems(
+            GgrsSchedule, (
+                // POWER-UPS AND POINTS
+                rollback_collect_power_ups.after(bullet_rollback_collision_system).before(rollback_apply_accumulated_damage),
+                rollback_award_kill_points.after(rollback_apply_accumulated_damage).before(rollback_apply_death),
+                rollback_drop_power_ups.after(rollback_award_kill_points).before(rollback_apply_death),
+                rollback_tick_power_ups.after(move_enemies).before(increase_frame_system),
+                // HAZARDS AND EXPLOSIONS
+                rollback_fire_patch_system.after(bullet_rollback_collision_system).before(rollback_apply_accumulated_damage),
+                rollback_spread_fire.after(rollback_fire_patch_system),
+                rollback_burn_damage.after(rollback_spread_fire).before(rollback_apply_accumulated_damage),
+                rollback_electric_trap_system.after(move_characters).before(system_weapon_position),
+                rollback_process_explosions.after(rollback_fire_patch_system).before(rollback_apply_accumulated_damage),
+                rollback_explode_barrels.after(rollback_apply_accumulated_damage).before(rollback_apply_death),
+                rollback_clear_expired_status.after(move_enemies).before(increase_frame_system),
+                // NAVIGATION
+                rollback_bake_nav_grid.after(check_direct_paths).before(calculate_paths),
+                // STEERING
+                rollback_update_spatial_hash.after(calculate_paths).before(move_enemies),
+                // INTERACTIONS
+                rollback_upgrade_station_system.after(weapon_rollback_system).before(bullet_rollback_system),
+                rollback_wall_weapon_system.after(weapon_rollback_system).before(bullet_rollback_system).before(rollback_store_previous_inputs),
+                rollback_repair_barricades.after(apply_inputs).before(rollback_store_previous_inputs),
+                // INPUTS
+                rollback_store_previous_inputs.after(rollback_upgrade_station_system).after(apply_inputs).before(increase_frame_system),
+                // GAME RULES
+                rollback_advance_waves.after(update_animation_state).before(enemy_spawn_from_spawners_system),
+                rollback_enemies_attack_generator.after(bullet_rollback_collision_system).before(rollback_apply_accumulated_damage),
+                rollback_check_generator.after(rollback_apply_death).before(increase_frame_system),
+                // DROP-IN
+                rollback_queue_drop_ins.after(apply_inputs).before(rollback_advance_waves),
+                rollback_spawn_drop_ins.after(rollback_advance_waves).before(enemy_spawn_from_spawners_system),
+            ));
+        app.add_systems(
+            GgrsSchedule, (
+                // VERSUS
+                rollback_award_player_kills.after(rollback_award_kill_points).before(rollback_intercept_player_deaths),
+                rollback_intercept_player_deaths.after(rollback_award_kill_points).before(rollback_apply_death),
+                rollback_respawn_players.after(rollback_apply_death).before(increase_frame_system),
+                rollback_deathmatch_timer.after(rollback_respawn_players).before(increase_frame_system),
+                // ENEMY ATTACKS
+                rollback_enemy_attacks.after(bullet_rollback_collision_system).before(rollback_apply_accumulated_damage),
+                // EXPLODERS
+                rollback_exploder_fuse.after(rollback_enemy_attacks).before(rollback_process_explosions),
+                rollback_explode_dead_exploders.after(rollback_apply_accumulated_damage).before(rollback_apply_death),
+                // SHIELDS
+                rollback_turn_shields.after(move_enemies).before(rollback_apply_accumulated_damage),
+                // BARRICADES
+                rollback_barricade_system.after(move_enemies).before(increase_frame_system),
+                // DOORS
+                rollback_open_doors.after(apply_inputs).before(rollback_store_previous_inputs),
+                // DESTRUCTIBLES
+                rollback_crumble_destructibles.after(rollback_apply_accumulated_damage).before(rollback_apply_death),
+                // TUTORIAL
+                rollback_tutorial_system.after(rollback_wall_weapon_system).after(rollback_repair_barricades).before(increase_frame_system),
+                // SIMULATION BUDGET
+                rollback_cull_bullets.after(weapon_rollback_system).after(rollback_wall_weapon_system).before(bullet_rollback_system),
+                // WORLD RANGE
+                rollback_audit_world_range.after(move_enemies).after(rollback_respawn_players).before(increase_frame_system),
+                // TRADE
+                rollback_trade_drops.after(apply_inputs).after(weapon_rollback_system).after(rollback_drop_power_ups).before(rollback_store_previous_inputs),
+                rollback_collect_trades.after(move_characters).after(rollback_trade_drops).after(rollback_collect_power_ups).before(increase_frame_system),
+                // EQUIPMENT
+                rollback_drop_equipment.after(rollback_drop_power_ups).before(rollback_apply_death),
+                rollback_collect_equipment.after(move_characters).after(rollback_collect_trades).before(rollback_apply_accumulated_damage),
+                rollback_expire_equipment.after(apply_inputs).before(increase_frame_system),
+                // CLASS ABILITIES
+                rollback_use_abilities.after(apply_inputs).before(rollback_store_previous_inputs),
+                rollback_healing_auras.after(rollback_use_abilities).after(bullet_rollback_collision_system).before(rollback_apply_accumulated_damage),
+                // OVERHEAT
+                rollback_dissipate_weapon_heat.after(system_weapon_position).before(weapon_rollback_system),
+                // CAPTURE
+                record_input_log.before(increase_frame_system),
+            ));
+        app.add_systems(
+            GgrsSchedule, (
+                // HAZARD SWITCHES
+                rollback_use_hazard_switches.after(apply_inputs).before(rollback_store_previous_inputs),
+                rollback_electric_fence_system.after(rollback_use_hazard_switches).after(bullet_rollback_collision_system).before(rollback_apply_accumulated_damage),
+                rollback_fan_trap_system.after(rollback_use_hazard_switches).after(move_enemies).before(increase_frame_system),
+                // CLIMBABLE WALLS
+                rollback_climb_walls.after(rollback_update_spatial_hash).before(move_enemies),
+                // ENTITY LINKS, once everything of the frame is despawned
+                prune_dead_links::<WeaponInventory>.after(rollback_apply_death).after(rollback_respawn_players).before(increase_frame_system),
+                prune_dead_links::<Grabbed>.after(rollback_apply_death).after(rollback_clear_expired_status).before(increase_frame_system),
+                // INPUT VALIDATION, before every system reading the inputs
+                rollback_validate_inputs.before(apply_inputs).before(record_input_log),
+            ));
+        app.prune_entity_links::<HitMarker>(Update);
         app.add_systems(Update, (
             weapon_inventory_system,
             weapons_config_update_system,
-
-            update_health_bars,
+            collision_presets_update_system,
+            log_confirmed_world_range.run_if(in_state(AppState::InGame)),
         ));
+        app.add_systems(OnExit(AppState::InGame), clear_world_range_audit);
+        if !self.headless {
+            app.add_systems(Update, (
+                weapon_tint_system,
+
+                update_health_bars,
+            ));
+        }
     }
 }
@@ -0,0 +1,179 @@
+pub mod ui;
+
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use ggrs::PlayerHandle;
+
+use crate::{character::{enemy::{shield::ShieldState, Enemy}, health::{DamageContributions, Death, Health, HitBy}, player::Player}, powerup::ActivePowerUps};
+
+
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct PointsConfig {
+    // Points given to the player that landed the killing blow
+    pub kill_points: u32,
+    // Points given to the other players that damaged the enemy enough
+    pub assist_points: u32,
+    // Part of the max health of the enemy a player must have taken for an assist
+    pub assist_share: f32,
+    // Added to the kill of an enemy whose shield was broken
+    pub shield_break_points: u32,
+}
+
+impl Default for PointsConfig {
+    fn default() -> Self {
+        Self {
+            kill_points: 100,
+            assist_points: 30,
+            assist_share: 0.25,
+            shield_break_points: 50,
+        }
+    }
+}
+
+// Rollback component holding the points of a player
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct PlayerPoints {
+    pub current: u32,
+    pub total_earned: u32,
+}
+
+impl PlayerPoints {
+    pub fn add(&mut self, amount: u32) {
+        self.current = self.current.saturating_add(amount);
+        self.total_earned = self.total_earned.saturating_add(amount);
+    }
+
+    // Points given by a teammate, already counted in what it earned
+    pub fn receive(&mut self, amount: u32) {
+        self.current = self.current.saturating_add(amount);
+    }
+
+    // Remove the amount if the player can afford it, return false otherwise
+    pub fn spend(&mut self, amount: u32) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.current -= amount;
+        true
+    }
+}
+
+// Kills and deaths of a player, for the scoreboards
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct PlayerScore {
+    // Players killed, in versus
+    pub kills: u32,
+    pub deaths: u32,
+    pub headshots: u32,
+    pub enemy_kills: u32,
+    // Teammates brought back up, stay at 0 until the players can be downed
+    pub revives: u32,
+    pub assists: u32,
+    // Damage done to the enemies that died, for the damage share
+    pub enemy_damage: f32,
+}
+
+
+// Players other than the killer that took enough of the health of the enemy
+pub fn assists(death: &Death, contributions: &DamageContributions, max_health: f32, config: &PointsConfig) -> Vec<PlayerHandle> {
+    let killer = match death.last_hit_by {
+        Some(HitBy::Player(handle)) => Some(handle),
+        _ => None,
+    };
+    contributions.damage.iter()
+        .filter(|(handle, damage)| Some(*handle) != killer && *damage >= max_health * config.assist_share)
+        .map(|(handle, _)| *handle)
+        .collect()
+}
+
+// Give the kill and assist points to the players that killed an enemy, must run before the death are applied
+pub fn rollback_award_kill_points(
+    config: Res<PointsConfig>,
+    power_ups: Res<ActivePowerUps>,
+    enemy_query: Query<(Entity, &Death, &Health, Option<&DamageContributions>, Option<&ShieldState>), (With<Enemy>, With<Rollback>)>,
+    mut player_query: Query<(&Player, &mut PlayerPoints, &mut PlayerScore), With<Rollback>>,
+) {
+    let mut deaths: Vec<_> = enemy_query.iter().collect();
+    deaths.sort_by_key(|(entity, ..)| entity.index());
+
+    for (_, death, health, opt_contributions, opt_shield) in deaths {
+        if let Some(HitBy::Player(handle)) = death.last_hit_by {
+            let shield_bonus = if opt_shield.map_or(false, |shield| shield.broken) { config.shield_break_points } else { 0 };
+            let amount = (config.kill_points + shield_bonus) * power_ups.points_multiplier();
+            for (player, mut points, mut score) in player_query.iter_mut() {
+                if player.handle == handle {
+                    points.add(amount);
+                    score.enemy_kills += 1;
+                }
+            }
+        }
+
+        let Some(contributions) = opt_contributions else {
+            continue;
+        };
+        let assists = assists(death, contributions, health.max, &config);
+        for (player, mut points, mut score) in player_query.iter_mut() {
+            score.enemy_damage += contributions.of(player.handle);
+            if assists.contains(&player.handle) {
+                points.add(config.assist_points * power_ups.points_multiplier());
+                score.assists += 1;
+            }
+        }
+    }
+}
+
+// Credit the kill to the player that killed another player, must run before the death are applied
+pub fn rollback_award_player_kills(
+    victim_query: Query<(Entity, &Player, &Death), With<Rollback>>,
+    mut score_query: Query<(&Player, &mut PlayerScore), With<Rollback>>,
+) {
+    let mut deaths: Vec<(Entity, &Player, &Death)> = victim_query.iter().collect();
+    deaths.sort_by_key(|(entity, ..)| entity.index());
+
+    for (_, victim, death) in deaths {
+        for (player, mut score) in score_query.iter_mut() {
+            if player.handle == victim.handle {
+                score.deaths += 1;
+            }
+            // Killing yourself with an explosion doesn't count
+            if matches!(death.last_hit_by, Some(HitBy::Player(handle)) if handle == player.handle && handle != victim.handle) {
+                score.kills += 1;
+                if death.is_headshot() {
+                    score.headshots += 1;
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contributions(damage: &[(PlayerHandle, f32)]) -> DamageContributions {
+        let mut contributions = DamageContributions::default();
+        for (handle, damage) in damage {
+            contributions.add(*handle, *damage);
+        }
+        contributions
+    }
+
+    #[test]
+    fn test_assists_leave_out_the_killer_and_the_small_shares() {
+        let death = Death { last_hit_by: Some(HitBy::Player(0)), ..default() };
+        let contributions = contributions(&[(0, 60.0), (1, 25.0), (2, 24.0), (3, 40.0)]);
+        assert_eq!(assists(&death, &contributions, 100.0, &PointsConfig::default()), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_assists_of_a_kill_by_an_enemy() {
+        // Everyone with enough damage get an assist when no player got the kill
+        let death = Death { last_hit_by: Some(HitBy::Entity(Entity::from_raw(5))), ..default() };
+        let contributions = contributions(&[(0, 60.0), (1, 10.0)]);
+        assert_eq!(assists(&death, &contributions, 100.0, &PointsConfig::default()), vec![0]);
+    }
+}
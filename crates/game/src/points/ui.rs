@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+
+use crate::{character::player::{jjrs::PeerConfig, LocalPlayer, Player}, hud::{options::HudPreferences, HudAnchor, HudSlot}, lobby::{identity::PlayerIdentity, voice::{VoiceActivity, VoiceState}}, localization::Localization, plugins::AppState};
+
+use super::{PlayerPoints, PlayerScore};
+
+// Held to show the scoreboard
+pub const SCOREBOARD_KEY: KeyCode = KeyCode::Tab;
+
+// Behind the row of the local player, the rows are in the color of the players
+const LOCAL_BACKGROUND: Color = Color::srgba(1.0, 1.0, 1.0, 0.15);
+
+
+#[derive(Component)]
+struct Scoreboard;
+
+// Line of the table, the header is the first one
+#[derive(Component)]
+struct ScoreboardRow;
+
+
+fn setup_scoreboard(mut commands: Commands) {
+    commands.spawn((
+        Scoreboard,
+        HudSlot::new(HudAnchor::Center, 1),
+        Node {
+            width: Val::Px(760.0),
+            max_width: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(12.0)),
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+        Visibility::Hidden,
+    ));
+}
+
+fn ping_label(session: Option<&Session<PeerConfig>>, handle: usize, localization: &Localization) -> String {
+    match session {
+        Some(Session::P2P(session)) if session.remote_player_handles().contains(&handle) => match session.network_stats(handle) {
+            Ok(stats) => format!("{}ms", stats.ping),
+            Err(_) => localization.text("scoreboard.syncing"),
+        },
+        _ => "-".to_string(),
+    }
+}
+
+fn update_scoreboard(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    localization: Res<Localization>,
+    identities: Res<PlayerIdentity>,
+    voice_activity: Res<VoiceActivity>,
+    hud_preferences: Res<HudPreferences>,
+    session: Option<Res<Session<PeerConfig>>>,
+    q_players: Query<(&Player, &PlayerScore, &PlayerPoints, Has<LocalPlayer>)>,
+    mut q_board: Query<(Entity, &mut Visibility, Option<&Children>), With<Scoreboard>>,
+    mut q_rows: Query<(&mut Text, &mut TextColor, &mut BackgroundColor), With<ScoreboardRow>>,
+) {
+    let Ok((board, mut visibility, children)) = q_board.get_single_mut() else {
+        return;
+    };
+
+    if !keyboard_input.pressed(SCOREBOARD_KEY) {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    let mut players: Vec<_> = q_players.iter().collect();
+    players.sort_by_key(|(player, ..)| player.handle);
+
+    // A row per player and the header, created again when a player join or leave
+    let row_count = children.map_or(0, |children| children.len());
+    if row_count != players.len() + 1 {
+        commands.entity(board).despawn_descendants().with_children(|parent| {
+            for _ in 0..players.len() + 1 {
+                parent.spawn((
+                    ScoreboardRow,
+                    Text::new(""),
+                    TextFont {
+                        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                        font_size: 16.0,
+                        ..Default::default()
+                    },
+                    TextColor(Color::WHITE),
+                    BackgroundColor(Color::NONE),
+                ));
+            }
+        });
+        return;
+    }
+
+    // Part of the damage done to the enemies by the whole team
+    let team_damage: f32 = players.iter().map(|(_, score, ..)| score.enemy_damage).sum();
+    let damage_share = |score: &PlayerScore| if team_damage > 0.0 { format!("{:.0}%", score.enemy_damage / team_damage * 100.0) } else { "-".to_string() };
+
+    // The first column is the speaker of the voice activity
+    let column = |key: &str| localization.text(key);
+    let header = format!(
+        "{:<3} {:<16} {:>6} {:>8} {:>7} {:>6} {:>8} {:>8} {:>7}",
+        "", column("scoreboard.player"), column("scoreboard.kills"), column("scoreboard.assists"), column("scoreboard.damage"),
+        column("scoreboard.deaths"), column("scoreboard.revives"), column("scoreboard.points"), column("scoreboard.ping"),
+    );
+    let lines = std::iter::once((header, Color::WHITE, false)).chain(players.iter().map(|(player, score, points, is_local)| {
+        (format!(
+            "{:<3} {:<16} {:>6} {:>8} {:>7} {:>6} {:>8} {:>8} {:>7}",
+            VoiceState::of(player.handle, &voice_activity, &hud_preferences.voice, &identities).mark(), identities.name(player.handle), score.enemy_kills + score.kills, score.assists, damage_share(score), score.deaths, score.revives, points.current, ping_label(session.as_deref(), player.handle, &localization),
+        ), identities.color(player.handle), *is_local)
+    }));
+
+    let Some(children) = children else {
+        return;
+    };
+    for (child, (line, color, is_local)) in children.iter().zip(lines) {
+        if let Ok((mut text, mut text_color, mut background)) = q_rows.get_mut(*child) {
+            text.0 = line;
+            text_color.0 = color;
+            background.0 = if is_local { LOCAL_BACKGROUND } else { Color::NONE };
+        }
+    }
+}
+
+
+#[derive(Default)]
+pub struct ScoreboardUIPlugin;
+
+impl Plugin for ScoreboardUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_scoreboard);
+        app.add_systems(Update, update_scoreboard.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,231 @@
+pub mod ui;
+
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use serde::{Deserialize, Serialize};
+use utils::frame::SimulationConfig;
+
+use crate::{bullet_time::BulletTimeConfig, budget::{BudgetStats, SimulationBudget}, character::{enemy::Enemy, health::{accumulate_damage, DamageAccumulator, Death, HitBy}, player::Player}, collider::spatial::{ObstacleKind, SteeringObstacle}, frame::FrameCount, rng::DropsRng};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    DoublePoints,
+    InstaKill,
+    Nuke,
+    // Slow motion, only dropped in the offline games
+    BulletTime,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 4] = [PowerUpKind::DoublePoints, PowerUpKind::InstaKill, PowerUpKind::Nuke, PowerUpKind::BulletTime];
+
+    // Kinds a killed enemy can drop
+    pub fn droppable(bullet_time: bool) -> &'static [PowerUpKind] {
+        if bullet_time { &Self::ALL } else { &Self::ALL[..3] }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUpKind::DoublePoints => Color::srgb(1.0, 0.85, 0.1),
+            PowerUpKind::InstaKill => Color::srgb(0.9, 0.1, 0.1),
+            PowerUpKind::Nuke => Color::srgb(0.2, 0.9, 0.2),
+            PowerUpKind::BulletTime => Color::srgb(0.5, 0.7, 1.0),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerUpKind::DoublePoints => "Double Points",
+            PowerUpKind::InstaKill => "Insta-Kill",
+            PowerUpKind::Nuke => "Nuke",
+            PowerUpKind::BulletTime => "Bullet Time",
+        }
+    }
+}
+
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct PowerUpConfig {
+    // Chance between 0 and 1 that a killed enemy drops a power-up
+    pub drop_chance: f32,
+    // How long a pickup stay on the ground before disappearing
+    pub pickup_lifetime_frames: u32,
+    // Distance at which a player collect the pickup
+    pub pickup_radius: f32,
+    pub double_points_duration_frames: u32,
+    pub insta_kill_duration_frames: u32,
+    // Frames of game time, last longer in real time as the game is slowed
+    pub bullet_time_duration_frames: u32,
+    // Damage applied to every enemy alive when a nuke is collected
+    pub nuke_damage: f32,
+}
+
+impl Default for PowerUpConfig {
+    fn default() -> Self {
+        Self {
+            drop_chance: 0.05,
+            pickup_lifetime_frames: 60 * 20,  // 20 seconds at 60fps
+            pickup_radius: 40.0,
+            double_points_duration_frames: 60 * 30,
+            insta_kill_duration_frames: 60 * 30,
+            bullet_time_duration_frames: 60 * 4,
+            nuke_damage: 10000.0,
+        }
+    }
+}
+
+// Rollback resource of the effects currently active for all the players
+#[derive(Resource, Reflect, Default, Debug, Hash, Clone, Copy)]
+#[reflect(Hash)]
+pub struct ActivePowerUps {
+    pub double_points_frames: u32,
+    pub insta_kill_frames: u32,
+    pub bullet_time_frames: u32,
+}
+
+impl ActivePowerUps {
+    pub fn is_double_points(&self) -> bool {
+        self.double_points_frames > 0
+    }
+
+    pub fn is_insta_kill(&self) -> bool {
+        self.insta_kill_frames > 0
+    }
+
+    pub fn is_bullet_time(&self) -> bool {
+        self.bullet_time_frames > 0
+    }
+
+    pub fn points_multiplier(&self) -> u32 {
+        if self.is_double_points() { 2 } else { 1 }
+    }
+
+    pub fn remaining_frames(&self, kind: PowerUpKind) -> u32 {
+        match kind {
+            PowerUpKind::DoublePoints => self.double_points_frames,
+            PowerUpKind::InstaKill => self.insta_kill_frames,
+            PowerUpKind::BulletTime => self.bullet_time_frames,
+            PowerUpKind::Nuke => 0,
+        }
+    }
+}
+
+// Power-up waiting on the ground to be collected
+#[derive(Component, Clone, Debug)]
+pub struct PowerUpPickup {
+    pub kind: PowerUpKind,
+    pub despawn_at_frame: u32,
+}
+
+
+pub fn spawn_power_up_pickup(
+    commands: &mut Commands,
+    kind: PowerUpKind,
+    position: Vec3,
+    current_frame: u32,
+    config: &PowerUpConfig,
+    simulation: &SimulationConfig,
+) -> Entity {
+    commands.spawn((
+        Sprite::from_color(kind.color(), Vec2::new(20.0, 20.0)),
+        Transform::from_translation(Vec3::new(position.x, position.y, 1.0)),
+        PowerUpPickup {
+            kind,
+            despawn_at_frame: current_frame + simulation.frames(config.pickup_lifetime_frames),
+        },
+        SteeringObstacle { kind: ObstacleKind::Pickup, radius: 10.0 },
+    )).add_rollback().id()
+}
+
+
+// SYSTEMS
+
+// Roll a drop for each enemy that died this frame
+pub fn rollback_drop_power_ups(
+    mut commands: Commands,
+    mut rng: ResMut<DropsRng>,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    config: Res<PowerUpConfig>,
+    bullet_time: Res<BulletTimeConfig>,
+    budget: Res<SimulationBudget>,
+    mut budget_stats: ResMut<BudgetStats>,
+    enemy_query: Query<(Entity, &Transform), (With<Death>, With<Enemy>, With<Rollback>)>,
+    pickup_query: Query<(), (With<PowerUpPickup>, With<Rollback>)>,
+) {
+    let mut deaths: Vec<(Entity, &Transform)> = enemy_query.iter().collect();
+    deaths.sort_by_key(|(entity, _)| entity.index());
+
+    let mut pickups = pickup_query.iter().count();
+    for (_, transform) in deaths {
+        if rng.next_f32() >= config.drop_chance {
+            continue;
+        }
+        let kinds = PowerUpKind::droppable(bullet_time.enabled);
+        let kind = kinds[(rng.next_u32() as usize) % kinds.len()];
+        // The roll is done anyway so the rng stay the same with or without the cap
+        if !budget.has_room_for_pickup(pickups) {
+            budget_stats.skipped_drops += 1;
+            continue;
+        }
+        spawn_power_up_pickup(&mut commands, kind, transform.translation, frame.frame, &config, &simulation);
+        pickups += 1;
+    }
+}
+
+pub fn rollback_collect_power_ups(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    config: Res<PowerUpConfig>,
+    mut active: ResMut<ActivePowerUps>,
+    pickup_query: Query<(Entity, &Transform, &PowerUpPickup), With<Rollback>>,
+    player_query: Query<(&Transform, &Player), With<Rollback>>,
+    mut enemy_query: Query<(Entity, Option<&mut DamageAccumulator>), (With<Enemy>, With<Rollback>)>,
+) {
+    let mut pickups: Vec<(Entity, &Transform, &PowerUpPickup)> = pickup_query.iter().collect();
+    pickups.sort_by_key(|(entity, ..)| entity.index());
+
+    let mut players: Vec<(&Transform, &Player)> = player_query.iter().collect();
+    players.sort_by_key(|(_, player)| player.handle);
+
+    for (entity, transform, pickup) in pickups {
+        if frame.frame >= pickup.despawn_at_frame {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let pickup_pos = transform.translation.truncate();
+        let Some((_, player)) = players.iter()
+            .find(|(player_transform, _)| player_transform.translation.truncate().distance(pickup_pos) < config.pickup_radius) else {
+            continue;
+        };
+
+        match pickup.kind {
+            PowerUpKind::DoublePoints => {
+                active.double_points_frames = simulation.frames(config.double_points_duration_frames);
+            },
+            PowerUpKind::InstaKill => {
+                active.insta_kill_frames = simulation.frames(config.insta_kill_duration_frames);
+            },
+            PowerUpKind::BulletTime => {
+                active.bullet_time_frames = simulation.frames(config.bullet_time_duration_frames);
+            },
+            PowerUpKind::Nuke => {
+                for (enemy_entity, opt_accumulator) in enemy_query.iter_mut() {
+                    accumulate_damage(&mut commands, enemy_entity, opt_accumulator, config.nuke_damage, Some(HitBy::Player(player.handle)));
+                }
+            },
+        }
+
+        info!("Player {} collected {}", player.handle, pickup.kind.label());
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn rollback_tick_power_ups(mut active: ResMut<ActivePowerUps>) {
+    active.double_points_frames = active.double_points_frames.saturating_sub(1);
+    active.insta_kill_frames = active.insta_kill_frames.saturating_sub(1);
+    active.bullet_time_frames = active.bullet_time_frames.saturating_sub(1);
+}
@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use utils::{frame::SimulationConfig, math::calculate_time_remaining_seconds};
+
+use crate::{hud::{HudAnchor, HudSlot}, plugins::AppState};
+
+use super::{ActivePowerUps, PowerUpKind};
+
+
+#[derive(Component)]
+struct PowerUpIcon(PowerUpKind);
+
+#[derive(Component)]
+struct PowerUpCountdownText(PowerUpKind);
+
+
+fn setup_power_up_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    // Only the timed power-ups are displayed, the nuke is instant
+    let timed = [PowerUpKind::DoublePoints, PowerUpKind::InstaKill, PowerUpKind::BulletTime];
+
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(28.0),
+            ..default()
+        },
+        HudSlot::new(HudAnchor::BottomCenter, 0),
+    )).with_children(|row| {
+        for kind in timed.iter() {
+            row.spawn((
+                PowerUpIcon(*kind),
+                Node {
+                    width: Val::Px(32.0),
+                    height: Val::Px(32.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(kind.color()),
+                Visibility::Hidden,
+            )).with_children(|parent| {
+                parent.spawn((
+                    PowerUpCountdownText(*kind),
+                    Text::new(""),
+                    TextFont {
+                        font: font.clone(),
+                        font_size: 14.0,
+                        ..Default::default()
+                    },
+                    TextColor(Color::BLACK),
+                    TextLayout::new_with_justify(JustifyText::Center),
+                ));
+            });
+        }
+    });
+}
+
+fn update_power_up_ui(
+    active: Res<ActivePowerUps>,
+    simulation: Res<SimulationConfig>,
+    mut q_icon: Query<(&PowerUpIcon, &mut Visibility)>,
+    mut q_text: Query<(&PowerUpCountdownText, &mut Text)>,
+) {
+    for (icon, mut visibility) in q_icon.iter_mut() {
+        *visibility = if active.remaining_frames(icon.0) > 0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    for (countdown, mut text) in q_text.iter_mut() {
+        let remaining = active.remaining_frames(countdown.0);
+        text.0 = format!("{:.0}", calculate_time_remaining_seconds(remaining, 0, &simulation).ceil());
+    }
+}
+
+
+#[derive(Default)]
+pub struct PowerUpUIPlugin;
+
+impl Plugin for PowerUpUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_power_up_ui);
+        app.add_systems(Update, update_power_up_ui.run_if(in_state(AppState::InGame)));
+    }
+}
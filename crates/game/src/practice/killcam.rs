@@ -0,0 +1,398 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::{GgrsSchedule, PlayerInputs, Session};
+use ggrs::{InputStatus, PlayerHandle};
+use utils::{events::RollbackEvents, frame::SimulationConfig, snapshot::GameStateSnapshot};
+
+use crate::{camera::GameCamera, capture::InputLog, character::{enemy::Enemy, health::{DamageEvent, DeathEvent, HitBy}, player::{input::BoxInput, jjrs::PeerConfig, LocalPlayer, Player}}, frame::FrameCount, hud::{HudAnchor, HudSlot}, lobby::identity::PlayerIdentity, localization::Localization, plugins::AppState};
+
+// Kill cam of the offline games. Like the practice rewind the rollback state is copied in
+// a ring of snapshots, when the local player die the state of the death is kept aside,
+// the snapshot from a few seconds before is restored and the frames are simulated again
+// with the inputs of the log, the camera on the killer. The state of the death is put
+// back at the end and a recap of the damage taken is shown. The virtual time is paused
+// for the replay so GGRS doesn't advance, a P2P session can't pause its peers and
+// never get it until the snapshots can be shared.
+
+pub const KILL_CAM_SKIP_KEY: KeyCode = KeyCode::Space;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct KillCamSettings {
+    pub replay_seconds: f32,
+    pub snapshot_interval_seconds: f32,
+    // Simulated frames per frame of the rollback rate, under 1 for a slow motion
+    pub playback_speed: f32,
+    // How fast the camera catch up with the killer
+    pub camera_lerp_speed: f32,
+    pub recap_seconds: f32,
+}
+
+impl Default for KillCamSettings {
+    fn default() -> Self {
+        Self {
+            replay_seconds: 5.0,
+            snapshot_interval_seconds: 1.0,
+            playback_speed: 0.75,
+            camera_lerp_speed: 8.0,
+            recap_seconds: 6.0,
+        }
+    }
+}
+
+// Present only when the kill cam is enabled for the session
+#[derive(Resource, Default)]
+pub struct KillCamMode {
+    snapshots: VecDeque<GameStateSnapshot>,
+    // Local players of the last frame, a death is one of them gone
+    locals: HashMap<Entity, PlayerHandle>,
+    pub replays: u32,
+}
+
+// Present during a replay, the normal camera stop following the local player
+#[derive(Resource)]
+pub struct KillCamReplay {
+    // State of the death, restored at the end
+    resume: GameStateSnapshot,
+    handle: PlayerHandle,
+    killer: Option<HitBy>,
+    killer_name: String,
+    // Frames simulated with the rollback rate, the fraction is kept
+    owed_frames: f32,
+    recap: DeathRecap,
+}
+
+// Damage taken by the local player during the replayed seconds
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DeathRecap {
+    pub killer: String,
+    pub headshot: bool,
+    pub damage: f32,
+    pub hits: u32,
+    // Damage of each source, the most first once the replay is over
+    pub sources: Vec<(String, f32)>,
+    timer: Timer,
+}
+
+impl DeathRecap {
+    fn add(&mut self, source: String, damage: f32, hits: u32) {
+        self.damage += damage;
+        self.hits += hits;
+        match self.sources.iter_mut().find(|(name, _)| *name == source) {
+            Some((_, total)) => *total += damage,
+            None => self.sources.push((source, damage)),
+        }
+    }
+
+    // Once the replay is over, the sources that did the most first
+    fn finish(&mut self, recap_seconds: f32) {
+        self.sources.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        self.timer = Timer::from_seconds(recap_seconds, TimerMode::Once);
+    }
+
+    pub fn lines(&self, localization: &Localization) -> Vec<String> {
+        let killed_by = if self.headshot { "killcam.killed_by_headshot" } else { "killcam.killed_by" };
+        let mut lines = vec![localization.format(killed_by, &[("killer", &self.killer)])];
+        lines.push(localization.format("killcam.damage_taken", &[("damage", &format!("{:.0}", self.damage)), ("hits", &self.hits)]));
+        lines.extend(self.sources.iter().map(|(name, damage)| format!("  {:<16} {:>5.0}", name, damage)));
+        lines
+    }
+}
+
+fn is_p2p(world: &World) -> bool {
+    matches!(world.get_resource::<Session<PeerConfig>>(), Some(Session::P2P(_)))
+}
+
+fn source_name(world: &World, hit_by: Option<&HitBy>) -> String {
+    let localization = world.resource::<Localization>();
+    match hit_by {
+        Some(HitBy::Player(handle)) => world.get_resource::<PlayerIdentity>()
+            .map_or_else(|| localization.format("killcam.player", &[("player", &(handle + 1))]), |identities| identities.name(*handle)),
+        Some(HitBy::Entity(entity)) if world.get::<Enemy>(*entity).is_some() => localization.text("killcam.zombie"),
+        _ => localization.text("killcam.world"),
+    }
+}
+
+// Where the camera look during the replay, the local player when the killer can't be found
+fn killer_position(world: &mut World, replay: &KillCamReplay) -> Option<Vec2> {
+    let killer = match &replay.killer {
+        Some(HitBy::Entity(entity)) => world.get::<Transform>(*entity).map(|transform| transform.translation.truncate()),
+        Some(HitBy::Player(handle)) => world.query::<(&Transform, &Player)>().iter(world)
+            .find(|(_, player)| player.handle == *handle)
+            .map(|(transform, _)| transform.translation.truncate()),
+        None => None,
+    };
+    killer.or_else(|| world.query::<(&Transform, &Player)>().iter(world)
+        .find(|(_, player)| player.handle == replay.handle)
+        .map(|(transform, _)| transform.translation.truncate()))
+}
+
+
+// SYSTEMS
+
+fn kill_cam_snapshot_system(world: &mut World) {
+    if is_p2p(world) || world.contains_resource::<KillCamReplay>() {
+        return;
+    }
+    let frame = world.resource::<FrameCount>().frame;
+    let settings = world.resource::<KillCamSettings>().clone();
+    let interval = world.resource::<SimulationConfig>().frames_from_seconds(settings.snapshot_interval_seconds).max(1);
+    // One more than the replay need, the oldest one is before its start
+    let kept = (settings.replay_seconds / settings.snapshot_interval_seconds.max(0.1)).ceil() as usize + 2;
+    // The inputs from the oldest snapshot on are needed for the replay
+    let window = (kept as u32 + 1) * interval;
+    if world.resource::<InputLog>().window_frames.map_or(false, |frames| frames < window) {
+        world.resource_mut::<InputLog>().keep_frames(window);
+    }
+
+    let locals: HashMap<Entity, PlayerHandle> = world.query_filtered::<(Entity, &Player), With<LocalPlayer>>().iter(world)
+        .map(|(entity, player)| (entity, player.handle))
+        .collect();
+
+    world.resource_scope(|world, mut mode: Mut<KillCamMode>| {
+        // A local player gone is a death when there is its event, not the end of the match
+        let gone: Vec<_> = mode.locals.iter().filter(|(entity, _)| !locals.contains_key(entity)).map(|(e, h)| (*e, *h)).collect();
+        mode.locals = locals;
+        let death = gone.into_iter().find_map(|(entity, handle)| {
+            world.resource::<RollbackEvents<DeathEvent>>().read_after(None)
+                .filter(|(_, event)| event.entity == entity)
+                .last()
+                .map(|(death_frame, event)| (handle, death_frame, event.clone()))
+        });
+
+        if let Some((handle, death_frame, event)) = death {
+            let start = death_frame.saturating_sub(world.resource::<SimulationConfig>().frames_from_seconds(settings.replay_seconds));
+            let Some(index) = mode.snapshots.iter().rposition(|snapshot| snapshot.frame <= start)
+                .or(if mode.snapshots.is_empty() { None } else { Some(0) }) else {
+                return;
+            };
+            let logged = world.resource::<InputLog>().inputs.get(&handle)
+                .map_or(false, |logged| logged.covers(mode.snapshots[index].frame, frame.saturating_sub(1)));
+            if !logged || !world.contains_resource::<PlayerInputs<PeerConfig>>() {
+                warn!("no kill cam for the death of player {}, the inputs are not logged", handle + 1);
+                return;
+            }
+
+            let killer_name = source_name(world, event.last_hit_by.as_ref());
+            let resume = GameStateSnapshot::save(world, frame);
+            let snapshot = &mode.snapshots[index];
+            info!("kill cam of player {} from frame {} to frame {}", handle + 1, snapshot.frame, frame);
+            snapshot.restore(world);
+
+            world.resource_mut::<Time<Virtual>>().pause();
+            world.insert_resource(KillCamReplay {
+                resume,
+                handle,
+                killer: event.last_hit_by.clone(),
+                killer_name: killer_name.clone(),
+                owed_frames: 0.0,
+                recap: DeathRecap { killer: killer_name, headshot: event.headshot, ..default() },
+            });
+            mode.snapshots.clear();
+            mode.replays += 1;
+            return;
+        }
+
+        let due = mode.snapshots.back().map_or(true, |last| frame >= last.frame + interval);
+        if !due {
+            return;
+        }
+        mode.snapshots.push_back(GameStateSnapshot::save(world, frame));
+        while mode.snapshots.len() > kept {
+            mode.snapshots.pop_front();
+        }
+    });
+}
+
+// Simulate the frames owed since the last render with the logged inputs
+fn kill_cam_replay_system(world: &mut World) {
+    let Some(mut replay) = world.remove_resource::<KillCamReplay>() else {
+        return;
+    };
+    let settings = world.resource::<KillCamSettings>().clone();
+    let fps = world.resource::<SimulationConfig>().rollback_fps() as f32;
+    let delta = world.resource::<Time<Real>>().delta_secs();
+    // The focus of the window can unpause it
+    world.resource_mut::<Time<Virtual>>().pause();
+
+    let skip = world.resource::<ButtonInput<KeyCode>>().just_pressed(KILL_CAM_SKIP_KEY);
+    replay.owed_frames += delta * fps * settings.playback_speed.max(0.0);
+    while !skip && replay.owed_frames >= 1.0 && world.resource::<FrameCount>().frame < replay.resume.frame {
+        replay.owed_frames -= 1.0;
+        let frame = world.resource::<FrameCount>().frame;
+
+        let logged: Vec<BoxInput> = {
+            let log = world.resource::<InputLog>();
+            let handles = world.resource::<PlayerInputs<PeerConfig>>().len();
+            (0..handles).map(|handle| log.get(handle, frame).unwrap_or_default()).collect()
+        };
+        for (slot, input) in world.resource_mut::<PlayerInputs<PeerConfig>>().iter_mut().zip(logged) {
+            *slot = (input, InputStatus::Confirmed);
+        }
+        world.run_schedule(GgrsSchedule);
+
+        let locals: Vec<Entity> = world.query::<(Entity, &Player)>().iter(world)
+            .filter(|(_, player)| player.handle == replay.handle)
+            .map(|(entity, _)| entity)
+            .collect();
+        let hits: Vec<DamageEvent> = world.resource::<RollbackEvents<DamageEvent>>().read(frame)
+            .filter(|event| locals.contains(&event.entity))
+            .cloned()
+            .collect();
+        for event in hits {
+            let source = source_name(world, event.hit_by.as_ref());
+            replay.recap.add(source, event.damage, event.hit_count);
+        }
+    }
+
+    if let Some(target) = killer_position(world, &replay) {
+        let t = (settings.camera_lerp_speed * delta).min(1.0);
+        for mut transform in world.query_filtered::<&mut Transform, With<GameCamera>>().iter_mut(world) {
+            let position = transform.translation.truncate().lerp(target, t);
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+
+    if !skip && world.resource::<FrameCount>().frame < replay.resume.frame {
+        world.insert_resource(replay);
+        return;
+    }
+
+    // Back to the death, the replay never change what happened
+    replay.resume.restore(world);
+    world.resource_mut::<Time<Virtual>>().unpause();
+    let mut recap = replay.recap;
+    recap.finish(settings.recap_seconds);
+    world.insert_resource(recap);
+}
+
+fn clear_kill_cam(world: &mut World) {
+    if world.remove_resource::<KillCamReplay>().is_some() {
+        world.resource_mut::<Time<Virtual>>().unpause();
+    }
+    world.remove_resource::<DeathRecap>();
+    if let Some(mut mode) = world.get_resource_mut::<KillCamMode>() {
+        mode.snapshots.clear();
+        mode.locals.clear();
+    }
+}
+
+
+#[derive(Component)]
+struct KillCamText;
+
+fn setup_kill_cam_ui(mut commands: Commands, asset_server: Res<AssetServer>, mode: Option<Res<KillCamMode>>) {
+    if mode.is_none() {
+        return;
+    }
+
+    commands.spawn((
+        KillCamText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 16.0,
+            ..Default::default()
+        },
+        TextColor(Color::srgb(1.0, 0.85, 0.8)),
+        HudSlot::new(HudAnchor::Center, 2),
+    ));
+}
+
+fn update_kill_cam_ui(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    localization: Res<Localization>,
+    replay: Option<Res<KillCamReplay>>,
+    recap: Option<ResMut<DeathRecap>>,
+    mut q_text: Query<&mut Text, With<KillCamText>>,
+) {
+    let Ok(mut text) = q_text.get_single_mut() else {
+        return;
+    };
+
+    text.0 = match (replay, recap) {
+        (Some(replay), _) => localization.format("killcam.replay", &[("killer", &replay.killer_name), ("key", &format!("{:?}", KILL_CAM_SKIP_KEY))]),
+        (None, Some(mut recap)) => {
+            recap.timer.tick(time.delta());
+            if recap.timer.finished() || keyboard_input.just_pressed(KILL_CAM_SKIP_KEY) {
+                commands.remove_resource::<DeathRecap>();
+            }
+            recap.lines(&localization).join("\n")
+        }
+        (None, None) => String::new(),
+    };
+}
+
+
+#[derive(Default)]
+pub struct KillCamPlugin;
+
+impl Plugin for KillCamPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KillCamSettings>();
+        // Only filled by the localization plugin, the keys are shown without it
+        app.init_resource::<Localization>();
+        app.register_type::<KillCamSettings>();
+        app.add_systems(OnEnter(AppState::InGame), setup_kill_cam_ui);
+        app.add_systems(Update, (
+            kill_cam_replay_system,
+            kill_cam_snapshot_system.after(kill_cam_replay_system),
+            update_kill_cam_ui.after(kill_cam_snapshot_system),
+        ).run_if(in_state(AppState::InGame)).run_if(resource_exists::<KillCamMode>));
+        app.add_systems(OnExit(AppState::InGame), clear_kill_cam);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localization() -> Localization {
+        let strings = HashMap::from([
+            ("killcam.killed_by".to_string(), "Killed by {killer}".to_string()),
+            ("killcam.killed_by_headshot".to_string(), "Killed by {killer} (headshot)".to_string()),
+            ("killcam.damage_taken".to_string(), "{damage} damage taken in {hits} hits".to_string()),
+        ]);
+        Localization::new("en".to_string(), strings, HashMap::default())
+    }
+
+    #[test]
+    fn test_add_sum_the_damage_of_a_source() {
+        let mut recap = DeathRecap::default();
+        recap.add("Zombie".to_string(), 10.0, 1);
+        recap.add("Player 2".to_string(), 25.0, 2);
+        recap.add("Zombie".to_string(), 20.0, 2);
+
+        assert_eq!(recap.damage, 55.0);
+        assert_eq!(recap.hits, 5);
+        assert_eq!(recap.sources, vec![("Zombie".to_string(), 30.0), ("Player 2".to_string(), 25.0)]);
+    }
+
+    #[test]
+    fn test_finish_put_the_most_damage_first() {
+        let mut recap = DeathRecap::default();
+        recap.add("Zombie".to_string(), 10.0, 1);
+        recap.add("Player 2".to_string(), 25.0, 2);
+        recap.finish(6.0);
+
+        assert_eq!(recap.sources[0].0, "Player 2");
+        assert_eq!(recap.timer.duration().as_secs_f32(), 6.0);
+    }
+
+    #[test]
+    fn test_lines_of_the_recap() {
+        let mut recap = DeathRecap { killer: "Player 2".to_string(), headshot: true, ..default() };
+        recap.add("Player 2".to_string(), 42.4, 3);
+
+        let lines = recap.lines(&localization());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "Killed by Player 2 (headshot)");
+        assert_eq!(lines[1], "42 damage taken in 3 hits");
+        assert_eq!(lines[2], format!("  {:<16} {:>5.0}", "Player 2", 42.4));
+    }
+}
@@ -0,0 +1,234 @@
+pub mod killcam;
+pub mod ui;
+
+use std::collections::VecDeque;
+
+use animation::{ActiveLayers, AnimatedLayer, AnimationState, AnimationTimer, CharacterAnimationHandles, ColoredLayer, DisplayedAnimation, FacingDirection, FacingDirection8, LayerName};
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::{ActionState, InputMap};
+use map::game::entity::map::{climb::ClimbableWallComponent, destructible::DestructibleComponent, enemy_spawn::EnemySpawnerComponent, hazard::HazardComponent, switch::SwitchComponent};
+use utils::{events::RollbackEvents, frame::SimulationConfig, snapshot::{GameStateSnapshot, SnapshotAppExt}};
+
+use crate::{audio::weapons::WeaponSoundEvent, barricade::Barricade, door::Door, destructible::Debris, budget::BudgetStats, character::{config::CharacterConfigHandles, dash::DashState, enemy::{ai::pathing::{EnemyPath, PathCache, PathfindingConfig}, attack::{EnemyAttackState, HeavyHitEvent}, climb::Climbing, exploder::{ExploderState, FuseLitEvent}, flinch::FlinchState, shield::{ShieldBrokenEvent, ShieldState}, spawning::EnemySpawnerState, Enemy}, health::{ui::HealthBar, DamageAccumulator, DamageContributions, DamageEvent, Death, DeathEvent, Health}, movement::{SprintState, Velocity}, player::{control::PlayerAction, input::{CursorPosition, PointerWorldPosition, PreviousInput}, LocalPlayer, Player}, status::{Grabbed, Knockback, Stunned}, ability::{AbilityState, AuraProtected, HealingAura}, Character}, equipment::{Armor, Backpack, EquipmentPickup, SpeedBoost}, collider::{bounds::WorldRangeEvent, spatial::SteeringObstacle, Collider, CollisionLayer, HitZones, Wall}, frame::FrameCount, hazard::{fire::Flammable, switch::SwitchState, HazardState}, interaction::Interactable, plugins::AppState, points::{PlayerPoints, PlayerScore}, powerup::{ActivePowerUps, PowerUpPickup}, rng::{AiRng, DropsRng, SpawningRng, WeaponsRng}, rules::{deathmatch::Respawning, dropin::DropInQueue, objective::Generator, MatchState, WaveStartedEvent, WaveState}, trade::{TradePickup, TradeState}, tutorial::TutorialState, weapons::{explosion::{ExplosionEvent, ExplosionMarker}, melee::MeleeState, trigger::TriggerState, upgrade::UpgradeStation, wall::WallWeapon, ActiveWeapon, Bullet, BulletImpactEvent, BulletRollbackState, ExplosiveTag, PiercingTag, Weapon, WeaponFiredEvent, WeaponInventory, WeaponModesState, WeaponRotationCache, WeaponState, WeaponTint}};
+
+// Practice rewind of the offline games. The whole rollback state is copied every few
+// seconds in a ring of snapshots, the rewind key put the game back to the snapshot from
+// before the rewind delay to retry a fight. GGRS is not told, the session of a practice
+// game run without checks so it never load a frame from before the rewind.
+
+pub const PRACTICE_REWIND_KEY: KeyCode = KeyCode::Backspace;
+
+#[derive(Resource, Clone, Debug)]
+pub struct PracticeSettings {
+    pub snapshot_interval_seconds: f32,
+    pub rewind_seconds: f32,
+    // Size of the ring, the oldest snapshot is dropped past it
+    pub kept_snapshots: usize,
+}
+
+impl Default for PracticeSettings {
+    fn default() -> Self {
+        Self {
+            snapshot_interval_seconds: 1.0,
+            rewind_seconds: 5.0,
+            kept_snapshots: 8,
+        }
+    }
+}
+
+// Present only in a practice game
+#[derive(Resource, Default)]
+pub struct PracticeMode {
+    pub snapshots: VecDeque<GameStateSnapshot>,
+    pub rewinds: u32,
+}
+
+impl PracticeMode {
+    // Latest snapshot at or before the frame, the oldest one when the ring doesn't go back that far
+    fn rewind_index(&self, frame: u32) -> Option<usize> {
+        self.snapshots.iter().rposition(|snapshot| snapshot.frame <= frame)
+            .or(if self.snapshots.is_empty() { None } else { Some(0) })
+    }
+}
+
+
+// SYSTEMS
+
+fn practice_snapshot_system(world: &mut World) {
+    let frame = world.resource::<FrameCount>().frame;
+    let settings = world.resource::<PracticeSettings>().clone();
+    let interval = world.resource::<SimulationConfig>().frames_from_seconds(settings.snapshot_interval_seconds).max(1);
+
+    world.resource_scope(|world, mut practice: Mut<PracticeMode>| {
+        let due = practice.snapshots.back().map_or(true, |last| frame >= last.frame + interval);
+        if !due {
+            return;
+        }
+        practice.snapshots.push_back(GameStateSnapshot::save(world, frame));
+        while practice.snapshots.len() > settings.kept_snapshots.max(1) {
+            practice.snapshots.pop_front();
+        }
+    });
+}
+
+fn practice_rewind_system(world: &mut World) {
+    if !world.resource::<ButtonInput<KeyCode>>().just_pressed(PRACTICE_REWIND_KEY) {
+        return;
+    }
+    let frame = world.resource::<FrameCount>().frame;
+    let rewind = world.resource::<SimulationConfig>().frames_from_seconds(world.resource::<PracticeSettings>().rewind_seconds);
+
+    world.resource_scope(|world, mut practice: Mut<PracticeMode>| {
+        let Some(index) = practice.rewind_index(frame.saturating_sub(rewind)) else {
+            return;
+        };
+        // The snapshots after it are from the timeline that is thrown away, the one
+        // restored is kept to rewind to it again
+        practice.snapshots.truncate(index + 1);
+        let snapshot = &practice.snapshots[index];
+        info!("practice rewind from frame {} to frame {} ({} entities)", frame, snapshot.frame, snapshot.entity_count());
+        snapshot.restore(world);
+        practice.rewinds += 1;
+    });
+}
+
+
+// Everything a snapshot copy, the rollback state and what the spawners put next to it
+// so an entity despawned after the snapshot come back complete
+fn register_practice_snapshots(app: &mut App) {
+    app.snapshot_resource::<WeaponsRng>()
+        .snapshot_resource::<SpawningRng>()
+        .snapshot_resource::<DropsRng>()
+        .snapshot_resource::<AiRng>()
+        .snapshot_resource::<PathfindingConfig>()
+        .snapshot_resource::<PointerWorldPosition>()
+        .snapshot_resource::<FrameCount>()
+        .snapshot_resource::<ActivePowerUps>()
+        .snapshot_resource::<WaveState>()
+        .snapshot_resource::<DropInQueue>()
+        .snapshot_resource::<MatchState>()
+        .snapshot_resource::<TutorialState>()
+        .snapshot_resource::<BudgetStats>()
+        .snapshot_resource::<RollbackEvents<DamageEvent>>()
+        .snapshot_resource::<RollbackEvents<DeathEvent>>()
+        .snapshot_resource::<RollbackEvents<WeaponFiredEvent>>()
+        .snapshot_resource::<RollbackEvents<WeaponSoundEvent>>()
+        .snapshot_resource::<RollbackEvents<WaveStartedEvent>>()
+        .snapshot_resource::<RollbackEvents<HeavyHitEvent>>()
+        .snapshot_resource::<RollbackEvents<FuseLitEvent>>()
+        .snapshot_resource::<RollbackEvents<ShieldBrokenEvent>>()
+        .snapshot_resource::<RollbackEvents<BulletImpactEvent>>()
+        .snapshot_resource::<RollbackEvents<ExplosionEvent>>()
+        .snapshot_resource::<RollbackEvents<WorldRangeEvent>>();
+
+    // Rollback components
+    app.snapshot_component::<Generator>()
+        .snapshot_component::<Respawning>()
+        .snapshot_component::<PlayerScore>()
+        .snapshot_component::<PowerUpPickup>()
+        .snapshot_component::<TradePickup>()
+        .snapshot_component::<TradeState>()
+        .snapshot_component::<EquipmentPickup>()
+        .snapshot_component::<Armor>()
+        .snapshot_component::<SpeedBoost>()
+        .snapshot_component::<Backpack>()
+        .snapshot_component::<PlayerPoints>()
+        .snapshot_component::<PreviousInput>()
+        .snapshot_component::<Interactable>()
+        .snapshot_component::<UpgradeStation>()
+        .snapshot_component::<WallWeapon>()
+        .snapshot_component::<Barricade>()
+        .snapshot_component::<Door>()
+        .snapshot_component::<HazardComponent>()
+        .snapshot_component::<DestructibleComponent>()
+        .snapshot_component::<ClimbableWallComponent>()
+        .snapshot_component::<Debris>()
+        .snapshot_component::<HazardState>()
+        .snapshot_component::<Flammable>()
+        .snapshot_component::<SwitchComponent>()
+        .snapshot_component::<SwitchState>()
+        .snapshot_component::<Stunned>()
+        .snapshot_component::<Knockback>()
+        .snapshot_component::<AbilityState>()
+        .snapshot_component::<HealingAura>()
+        .snapshot_component::<AuraProtected>()
+        .snapshot_component_mapped::<Grabbed>()
+        .snapshot_component_mapped::<EnemyAttackState>()
+        .snapshot_component::<FlinchState>()
+        .snapshot_component::<ExploderState>()
+        .snapshot_component::<ShieldState>()
+        .snapshot_component::<Climbing>()
+        .snapshot_component::<SteeringObstacle>()
+        .snapshot_component::<ExplosionMarker>()
+        .snapshot_component::<EnemySpawnerComponent>()
+        .snapshot_component::<EnemySpawnerState>()
+        .snapshot_component::<Health>()
+        .snapshot_component::<DamageAccumulator>()
+        .snapshot_component::<DamageContributions>()
+        .snapshot_component_mapped::<WeaponInventory>()
+        .snapshot_component::<WeaponModesState>()
+        .snapshot_component::<WeaponState>()
+        .snapshot_component::<TriggerState>()
+        .snapshot_component::<MeleeState>()
+        .snapshot_component::<Bullet>()
+        .snapshot_component::<BulletRollbackState>()
+        .snapshot_component::<Collider>()
+        .snapshot_component::<Wall>()
+        .snapshot_component::<CollisionLayer>()
+        .snapshot_component::<Transform>()
+        .snapshot_component::<DashState>()
+        .snapshot_component::<SprintState>()
+        .snapshot_component::<Velocity>()
+        .snapshot_component::<Death>()
+        .snapshot_component::<Player>()
+        .snapshot_component::<EnemyPath>()
+        .snapshot_component::<Enemy>()
+        .snapshot_component::<AnimationState>()
+        .snapshot_component::<FacingDirection8>()
+        .snapshot_component::<LayerName>()
+        .snapshot_component::<ActiveLayers>();
+
+    // Set once at spawn
+    app.snapshot_component::<Character>()
+        .snapshot_component::<CharacterConfigHandles>()
+        .snapshot_component::<HitZones>()
+        .snapshot_component::<HealthBar>()
+        .snapshot_component::<PathCache>()
+        .snapshot_component::<LocalPlayer>()
+        .snapshot_component::<ActionState<PlayerAction>>()
+        .snapshot_component::<InputMap<PlayerAction>>()
+        .snapshot_component::<CursorPosition>()
+        .snapshot_component::<Weapon>()
+        .snapshot_component::<ActiveWeapon>()
+        .snapshot_component::<WeaponTint>()
+        .snapshot_component::<WeaponRotationCache>()
+        .snapshot_component::<ExplosiveTag>()
+        .snapshot_component::<PiercingTag>();
+
+    // Visuals
+    app.snapshot_component::<Sprite>()
+        .snapshot_component::<Visibility>()
+        .snapshot_component::<CharacterAnimationHandles>()
+        .snapshot_component::<AnimationTimer>()
+        .snapshot_component::<DisplayedAnimation>()
+        .snapshot_component::<FacingDirection>()
+        .snapshot_component::<AnimatedLayer>()
+        .snapshot_component::<ColoredLayer>();
+}
+
+
+#[derive(Default)]
+pub struct PracticePlugin;
+
+impl Plugin for PracticePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PracticeSettings>();
+        register_practice_snapshots(app);
+        app.add_plugins(ui::PracticeUIPlugin);
+        app.add_plugins(killcam::KillCamPlugin);
+        app.add_systems(Update, (
+            practice_rewind_system,
+            practice_snapshot_system.after(practice_rewind_system),
+        ).run_if(in_state(AppState::InGame)).run_if(resource_exists::<PracticeMode>));
+    }
+}
@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+
+use crate::{hud::{HudAnchor, HudSlot}, plugins::AppState};
+
+use super::{PracticeMode, PracticeSettings};
+
+
+#[derive(Component)]
+struct PracticeHintText;
+
+
+fn setup_practice_ui(mut commands: Commands, asset_server: Res<AssetServer>, practice: Option<Res<PracticeMode>>) {
+    if practice.is_none() {
+        return;
+    }
+
+    commands.spawn((
+        PracticeHintText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 14.0,
+            ..Default::default()
+        },
+        TextColor(Color::srgb(0.7, 0.9, 1.0)),
+        HudSlot::new(HudAnchor::BottomLeft, 4),
+    ));
+}
+
+fn update_practice_ui(
+    practice: Option<Res<PracticeMode>>,
+    settings: Res<PracticeSettings>,
+    mut q_text: Query<&mut Text, With<PracticeHintText>>,
+) {
+    let (Some(practice), Ok(mut text)) = (practice, q_text.get_single_mut()) else {
+        return;
+    };
+
+    text.0 = format!(
+        "Practice - [Backspace] rewind {}s - {} rewinds",
+        settings.rewind_seconds, practice.rewinds,
+    );
+}
+
+
+#[derive(Default)]
+pub struct PracticeUIPlugin;
+
+impl Plugin for PracticeUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_practice_ui);
+        app.add_systems(Update, update_practice_ui.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use crate::{character::{config::{CharacterConfig, CharacterSkin}, player::create::DEFAULT_PLAYER_CLASS}, global_asset::GlobalAsset};
+
+use super::{PlayerProgress, ProgressionConfig};
+
+// Colors of the layers of the local player, picked in the lobby. The palette come from
+// the skin of the loadout in the default class, the other classes are expected to give
+// their skins the same tints. The chosen indexes are part of the loadout and reach the
+// other peers with it.
+
+// Tinted layer edited by the color key, index in `CharacterSkin::tinted_layers`
+#[derive(Resource, Default, Debug)]
+pub struct LoadoutCustomization {
+    pub layer: usize,
+}
+
+fn lobby_skin<'a>(
+    global_assets: &GlobalAsset,
+    character_asset: &'a Assets<CharacterConfig>,
+    skin: Option<&String>,
+) -> Option<&'a CharacterSkin> {
+    let config = global_assets.character_configs.get(DEFAULT_PLAYER_CLASS).and_then(|handle| character_asset.get(handle))?;
+    skin.and_then(|skin| config.skins.get(skin))
+        .or_else(|| config.skins.get(&config.starting_skin))
+}
+
+// [ change the layer to edit and ] its color, saved right away
+pub fn customize_colors_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    config: Res<ProgressionConfig>,
+    global_assets: Res<GlobalAsset>,
+    character_asset: Res<Assets<CharacterConfig>>,
+    mut customization: ResMut<LoadoutCustomization>,
+    mut progress: ResMut<PlayerProgress>,
+) {
+    let mut selected = progress.loadout(&config);
+    let Some(skin) = lobby_skin(&global_assets, &character_asset, selected.skin.as_ref()) else {
+        return;
+    };
+    let layers = skin.tinted_layers();
+    if layers.is_empty() {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        customization.layer = (customization.layer + 1) % layers.len();
+    }
+    let (layer, tints) = layers[customization.layer % layers.len()];
+    if keys.just_pressed(KeyCode::BracketRight) {
+        let current = selected.colors.get(layer).copied().unwrap_or(0);
+        selected.colors.insert(layer.clone(), (current + 1) % tints.len());
+    }
+
+    if selected != progress.selected {
+        progress.selected = selected;
+        progress.save();
+    }
+}
+
+// Line shown in the lobby, empty when the skin has no tinted layer
+pub fn customization_summary(
+    config: &ProgressionConfig,
+    progress: &PlayerProgress,
+    customization: &LoadoutCustomization,
+    global_assets: &GlobalAsset,
+    character_asset: &Assets<CharacterConfig>,
+) -> Option<String> {
+    let loadout = progress.loadout(config);
+    let skin = lobby_skin(global_assets, character_asset, loadout.skin.as_ref())?;
+    let layers = skin.tinted_layers();
+    if layers.is_empty() {
+        return None;
+    }
+
+    let edited = customization.layer % layers.len();
+    let colors: Vec<String> = layers.iter().enumerate().map(|(i, (layer, tints))| {
+        let index = loadout.colors.get(*layer).copied().filter(|index| *index < tints.len()).unwrap_or(0);
+        let marker = if i == edited { ">" } else { "" };
+        format!("{}{} {}", marker, layer, tints[index].name)
+    }).collect();
+    Some(format!("Colors: {} - [ change the layer, ] its color", colors.join(", ")))
+}
@@ -0,0 +1,358 @@
+pub mod customization;
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+use customization::{customize_colors_input, LoadoutCustomization};
+use serde::{Deserialize, Serialize};
+use utils::{events::RollbackEvents, persistence};
+
+use crate::{character::{health::{DeathEvent, HitBy}, player::{LocalPlayer, Player}}, frame::{confirmed_frame, FrameCount}, plugins::AppState, practice::PracticeMode, rules::{GameMode, GameRules, MatchOutcome, MatchState, WaveState}};
+
+// Progression of the local player across matches. The stats of the local player are
+// counted from the confirmed frames of the match, turned into XP at the end and saved
+// with the persistence module. The levels unlock skins and starting weapons chosen
+// in the lobby.
+//
+// Everything is local, the other peers trust the loadout they receive like they trust
+// the class. The save keep every match with its stats so a backend can later recompute
+// the XP with `ProgressionConfig::xp_for` and refuse a loadout that is not unlocked.
+
+pub const PROGRESS_SAVE_KEY: &str = "progress";
+pub const PROGRESS_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnlockItem {
+    // Key of a skin of the character configs
+    Skin(String),
+    // Key of the weapons config, given first when the match start
+    StartingWeapon(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Unlock {
+    pub level: u32,
+    pub item: UnlockItem,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct ProgressionConfig {
+    pub xp_per_kill: u32,
+    pub xp_per_headshot: u32,
+    pub xp_per_wave: u32,
+    pub xp_for_win: u32,
+    // XP needed to reach each level after the first one
+    pub level_xp: Vec<u32>,
+    pub unlocks: Vec<Unlock>,
+}
+
+impl Default for ProgressionConfig {
+    fn default() -> Self {
+        Self {
+            xp_per_kill: 10,
+            xp_per_headshot: 5,
+            xp_per_wave: 50,
+            xp_for_win: 250,
+            level_xp: vec![300, 800, 1600, 3000, 5000],
+            unlocks: vec![
+                Unlock { level: 2, item: UnlockItem::Skin("2".into()) },
+                Unlock { level: 3, item: UnlockItem::StartingWeapon("shotgun".into()) },
+                Unlock { level: 5, item: UnlockItem::StartingWeapon("machine_gun".into()) },
+            ],
+        }
+    }
+}
+
+impl ProgressionConfig {
+    // Must stay a pure function of the stats, a backend recompute it to validate a save
+    pub fn xp_for(&self, stats: &MatchStats) -> u32 {
+        stats.kills * self.xp_per_kill
+            + stats.headshots * self.xp_per_headshot
+            + stats.waves_survived * self.xp_per_wave
+            + if stats.won { self.xp_for_win } else { 0 }
+    }
+
+    // Start at level 1
+    pub fn level(&self, xp: u32) -> u32 {
+        1 + self.level_xp.iter().filter(|needed| xp >= **needed).count() as u32
+    }
+
+    // XP of the next level, none at the max level
+    pub fn next_level_xp(&self, xp: u32) -> Option<u32> {
+        self.level_xp.iter().copied().find(|needed| xp < *needed)
+    }
+
+    pub fn unlocked(&self, xp: u32) -> impl Iterator<Item = &UnlockItem> {
+        let level = self.level(xp);
+        self.unlocks.iter().filter(move |unlock| unlock.level <= level).map(|unlock| &unlock.item)
+    }
+
+    pub fn is_unlocked(&self, xp: u32, item: &UnlockItem) -> bool {
+        self.unlocked(xp).any(|unlocked| unlocked == item)
+    }
+
+    // Remove the parts of the loadout that are not unlocked
+    pub fn validate(&self, xp: u32, loadout: &PlayerLoadout) -> PlayerLoadout {
+        PlayerLoadout {
+            skin: loadout.skin.clone().filter(|skin| self.is_unlocked(xp, &UnlockItem::Skin(skin.clone()))),
+            weapon: loadout.weapon.clone().filter(|weapon| self.is_unlocked(xp, &UnlockItem::StartingWeapon(weapon.clone()))),
+            // The colors are not locked
+            colors: loadout.colors.clone(),
+        }
+    }
+}
+
+// Cosmetics and starting weapon of a player, exchanged in the lobby and identical on all
+// peers. `None` keep what the class give
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerLoadout {
+    pub skin: Option<String>,
+    pub weapon: Option<String>,
+    // Index in the palette of the skin for each tinted layer, the first color when missing
+    #[serde(default)]
+    pub colors: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchStats {
+    pub mode: GameMode,
+    pub kills: u32,
+    pub headshots: u32,
+    pub waves_survived: u32,
+    pub won: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchRecord {
+    pub stats: MatchStats,
+    pub xp: u32,
+}
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerProgress {
+    pub schema_version: u32,
+    pub xp: u32,
+    pub matches: Vec<MatchRecord>,
+    // Loadout chosen in the lobby, validated against the unlocks when used
+    pub selected: PlayerLoadout,
+}
+
+impl Default for PlayerProgress {
+    fn default() -> Self {
+        Self {
+            schema_version: PROGRESS_SCHEMA_VERSION,
+            xp: 0,
+            matches: vec![],
+            selected: PlayerLoadout::default(),
+        }
+    }
+}
+
+impl PlayerProgress {
+    pub fn grant(&mut self, config: &ProgressionConfig, stats: MatchStats) -> u32 {
+        let xp = config.xp_for(&stats);
+        self.xp = self.xp.saturating_add(xp);
+        self.matches.push(MatchRecord { stats, xp });
+        xp
+    }
+
+    // Loadout of the local player for the next match
+    pub fn loadout(&self, config: &ProgressionConfig) -> PlayerLoadout {
+        config.validate(self.xp, &self.selected)
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = persistence::save(PROGRESS_SAVE_KEY, self) {
+            error!("failed to save the progression: {}", err);
+        }
+    }
+}
+
+fn load_progress(mut commands: Commands) {
+    let progress = match persistence::load::<PlayerProgress>(PROGRESS_SAVE_KEY) {
+        Ok(Some(progress)) if progress.schema_version > PROGRESS_SCHEMA_VERSION => {
+            warn!("the progression was saved by a newer version {}, it is not loaded", progress.schema_version);
+            PlayerProgress::default()
+        }
+        Ok(progress) => progress.unwrap_or_default(),
+        Err(err) => {
+            error!("failed to load the progression: {}", err);
+            PlayerProgress::default()
+        }
+    };
+    commands.insert_resource(progress);
+}
+
+// Stats of the local player in the current match, presentation side only
+#[derive(Resource, Default, Debug)]
+struct MatchRecorder {
+    stats: MatchStats,
+    last_read_frame: Option<u32>,
+    // Frame the end of the match was seen, granted once it is confirmed
+    over_at: Option<u32>,
+    granted: bool,
+}
+
+fn record_match_stats(
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    rules: Res<GameRules>,
+    wave_state: Res<WaveState>,
+    match_state: Res<MatchState>,
+    death_events: Res<RollbackEvents<DeathEvent>>,
+    q_local: Query<(Entity, &Player), With<LocalPlayer>>,
+    mut recorder: ResMut<MatchRecorder>,
+) {
+    if recorder.granted {
+        return;
+    }
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    let Ok((local_entity, local)) = q_local.get_single() else {
+        return;
+    };
+    if recorder.last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+
+    for (_, event) in death_events.read_after(recorder.last_read_frame).filter(|(f, _)| *f <= confirmed_frame) {
+        // Killing yourself with an explosion doesn't count
+        if matches!(event.last_hit_by, Some(HitBy::Player(handle)) if handle == local.handle) && event.entity != local_entity {
+            recorder.stats.kills += 1;
+            if event.headshot {
+                recorder.stats.headshots += 1;
+            }
+        }
+    }
+    recorder.last_read_frame = Some(confirmed_frame);
+
+    recorder.stats.mode = rules.mode;
+    if rules.has_waves() {
+        recorder.stats.waves_survived = wave_state.wave.saturating_sub(1);
+    }
+    if let Some(outcome) = match_state.outcome {
+        recorder.over_at.get_or_insert(frame.frame);
+        recorder.stats.won = match outcome {
+            MatchOutcome::Victory => true,
+            MatchOutcome::PlayerWon(handle) => handle == local.handle,
+            MatchOutcome::Defeat | MatchOutcome::Draw => false,
+        };
+    }
+}
+
+fn grant_match_xp(recorder: &mut MatchRecorder, config: &ProgressionConfig, progress: &mut PlayerProgress) {
+    if recorder.granted {
+        return;
+    }
+    recorder.granted = true;
+
+    let level = config.level(progress.xp);
+    let xp = progress.grant(config, recorder.stats);
+    info!("match over, {} xp earned with {:?}", xp, recorder.stats);
+    if config.level(progress.xp) > level {
+        info!("level {} reached", config.level(progress.xp));
+    }
+    progress.save();
+}
+
+fn grant_on_match_over(
+    config: Res<ProgressionConfig>,
+    mut progress: ResMut<PlayerProgress>,
+    mut recorder: ResMut<MatchRecorder>,
+) {
+    let Some(over_at) = recorder.over_at else {
+        return;
+    };
+    if recorder.last_read_frame.map_or(false, |last| last >= over_at) {
+        grant_match_xp(&mut recorder, &config, &mut progress);
+    }
+}
+
+// The survival never end, what was played is granted when the game is closed
+fn grant_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    config: Res<ProgressionConfig>,
+    progress: Option<ResMut<PlayerProgress>>,
+    recorder: Option<ResMut<MatchRecorder>>,
+) {
+    if exit_events.read().next().is_none() {
+        return;
+    }
+    if let (Some(mut progress), Some(mut recorder)) = (progress, recorder) {
+        if recorder.last_read_frame.is_some() {
+            grant_match_xp(&mut recorder, &config, &mut progress);
+        }
+    }
+}
+
+// Cycle through the unlocked items, `None` first
+fn cycle<'a>(current: &Option<String>, options: impl Iterator<Item = &'a String>) -> Option<String> {
+    let options: Vec<Option<String>> = std::iter::once(None).chain(options.map(|o| Some(o.clone()))).collect();
+    let index = options.iter().position(|o| o == current).unwrap_or(0);
+    options[(index + 1) % options.len()].clone()
+}
+
+// F11 change the skin and F12 the starting weapon, saved right away
+fn select_loadout_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    config: Res<ProgressionConfig>,
+    mut progress: ResMut<PlayerProgress>,
+) {
+    let xp = progress.xp;
+    let mut selected = progress.loadout(&config);
+    if keys.just_pressed(KeyCode::F11) {
+        selected.skin = cycle(&selected.skin, config.unlocked(xp).filter_map(|item| match item {
+            UnlockItem::Skin(skin) => Some(skin),
+            _ => None,
+        }));
+    }
+    if keys.just_pressed(KeyCode::F12) {
+        selected.weapon = cycle(&selected.weapon, config.unlocked(xp).filter_map(|item| match item {
+            UnlockItem::StartingWeapon(weapon) => Some(weapon),
+            _ => None,
+        }));
+    }
+    if selected != progress.selected {
+        progress.selected = selected;
+        progress.save();
+    }
+}
+
+// Lines shown in the lobby
+pub fn progress_summary(config: &ProgressionConfig, progress: &PlayerProgress) -> Vec<String> {
+    let level = config.level(progress.xp);
+    let next = config.next_level_xp(progress.xp).map_or("max level".to_string(), |next| format!("{} / {} xp", progress.xp, next));
+    let loadout = progress.loadout(config);
+    let unlocked: Vec<String> = config.unlocked(progress.xp).map(|item| match item {
+        UnlockItem::Skin(skin) => format!("skin {}", skin),
+        UnlockItem::StartingWeapon(weapon) => weapon.clone(),
+    }).collect();
+
+    vec![
+        format!("Level {} - {} - {} matches played", level, next, progress.matches.len()),
+        format!("Unlocked: {}", if unlocked.is_empty() { "nothing yet".to_string() } else { unlocked.join(", ") }),
+        format!(
+            "Skin: {} [F11] - Starting weapon: {} [F12]",
+            loadout.skin.as_deref().unwrap_or("default"),
+            loadout.weapon.as_deref().unwrap_or("class default"),
+        ),
+    ]
+}
+
+#[derive(Default)]
+pub struct ProgressionPlugin;
+
+impl Plugin for ProgressionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProgressionConfig>();
+        app.init_resource::<MatchRecorder>();
+        app.init_resource::<LoadoutCustomization>();
+        app.add_systems(PreStartup, load_progress);
+        app.add_systems(Update, (select_loadout_input, customize_colors_input.after(select_loadout_input)).run_if(in_state(AppState::Lobby)));
+        // A rewound practice game doesn't count
+        app.add_systems(Update, (record_match_stats, grant_on_match_over.after(record_match_stats)).run_if(in_state(AppState::InGame)).run_if(not(resource_exists::<PracticeMode>)));
+        app.add_systems(Last, grant_on_exit);
+    }
+}
@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use serde::{Deserialize, Serialize};
+
+use crate::{capture::{replay_files, timestamp, InputLog}, character::player::jjrs::PeerConfig, frame::FrameCount, jjrs::GggrsSessionConfiguration, lobby::{identity::{Identity, PlayerIdentity}, moderation::LobbyModeration}, plugins::AppState, progression::PlayerLoadout};
+
+// Headless peer joining an online session as a GGRS spectator of the first player. It
+// only receive confirmed inputs, the log of the capture module is never rolled back and
+// is written regularly with what is needed to start the same game. Since the simulation
+// is deterministic these files are enough to rebuild the state of any frame, a peer
+// coming back can play them with the `replay:<path>` sources to catch up.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct RecorderSettings {
+    // Folder where each recorded session get its own folder
+    pub output_dir: String,
+    // Frames between two writes of the recording
+    pub flush_interval_frames: u32,
+}
+
+impl Default for RecorderSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: "recordings".into(),
+            flush_interval_frames: 600,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct RecorderState {
+    // Folder of the session, chosen when the game start
+    dir: Option<std::path::PathBuf>,
+    last_flush_frame: Option<u32>,
+}
+
+// Everything agreed on in the lobby that the inputs don't carry, written as `session.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordingInfo {
+    pub lobby: String,
+    pub map: String,
+    pub mode: String,
+    pub seed: u32,
+    pub players: usize,
+    pub classes: Vec<String>,
+    pub identities: Vec<Identity>,
+    // Loadout of each handle, in the order of the handles
+    pub loadouts: Vec<PlayerLoadout>,
+    // Last frame in the replays
+    pub frame: u32,
+}
+
+fn recording_info(config: &GggrsSessionConfiguration, moderation: &LobbyModeration, identities: &PlayerIdentity, frame: u32) -> RecordingInfo {
+    let players = config.connection.max_player;
+    // The handles are given to the players by order of their peer id
+    let mut peers: Vec<_> = moderation.loadouts.keys().copied().filter(|peer| !moderation.is_recorder(*peer)).collect();
+    peers.sort();
+
+    RecordingInfo {
+        lobby: config.lobby.clone(),
+        map: config.map.clone(),
+        mode: config.rules.mode.name().to_string(),
+        seed: config.seed,
+        players,
+        classes: peers.iter().map(|peer| moderation.class(*peer).to_string()).collect(),
+        identities: (0..players).map(|handle| identities.get(handle)).collect(),
+        loadouts: peers.iter().map(|peer| moderation.loadout(*peer)).collect(),
+        frame,
+    }
+}
+
+fn recording_files(config: &GggrsSessionConfiguration, moderation: &LobbyModeration, identities: &PlayerIdentity, log: &InputLog, frame: u32) -> Vec<(String, String)> {
+    let mut files = replay_files(log);
+    match serde_json::to_string_pretty(&recording_info(config, moderation, identities, frame)) {
+        Ok(json) => files.push(("session.json".into(), json)),
+        Err(err) => error!("failed to serialize the session info: {}", err),
+    }
+    files
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_recording(dir: &std::path::Path, files: Vec<(String, String)>) {
+    if let Err(err) = std::fs::create_dir_all(dir) {
+        error!("failed to create the recording folder {}: {}", dir.display(), err);
+        return;
+    }
+    for (name, content) in files {
+        let path = dir.join(name);
+        if let Err(err) = std::fs::write(&path, content) {
+            error!("failed to write the recording {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_recording(dir: &std::path::Path, _files: Vec<(String, String)>) {
+    warn!("recordings can't be saved on the web, {} not written", dir.display());
+}
+
+
+// SYSTEMS
+
+fn start_recording(
+    settings: Res<RecorderSettings>,
+    config: Res<GggrsSessionConfiguration>,
+    mut state: ResMut<RecorderState>,
+    mut log: ResMut<InputLog>,
+) {
+    // The recording is the whole match
+    log.window_frames = None;
+    let dir = std::path::Path::new(&settings.output_dir).join(format!("{}_{}", config.lobby.replace(['/', '\\', '~'], "_"), timestamp()));
+    info!("recording the session to {}", dir.display());
+    state.dir = Some(dir);
+    state.last_flush_frame = None;
+}
+
+fn flush_recording(
+    settings: Res<RecorderSettings>,
+    frame: Res<FrameCount>,
+    config: Res<GggrsSessionConfiguration>,
+    moderation: Res<LobbyModeration>,
+    identities: Res<PlayerIdentity>,
+    session: Option<Res<Session<PeerConfig>>>,
+    log: Res<InputLog>,
+    mut state: ResMut<RecorderState>,
+) {
+    // Only a spectator has nothing but confirmed inputs in the log
+    if !matches!(session.as_deref(), Some(Session::Spectator(_))) {
+        return;
+    }
+    if state.last_flush_frame.map_or(false, |last| frame.frame < last + settings.flush_interval_frames) {
+        return;
+    }
+    let Some(dir) = state.dir.clone() else {
+        return;
+    };
+
+    state.last_flush_frame = Some(frame.frame);
+    let files = recording_files(&config, &moderation, &identities, &log, frame.frame);
+    // On the IO pool, the replays grow with the game
+    bevy::tasks::IoTaskPool::get().spawn(async move {
+        write_recording(&dir, files);
+    }).detach();
+}
+
+// Last write when the session end or the recorder is stopped
+fn flush_recording_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    frame: Res<FrameCount>,
+    config: Res<GggrsSessionConfiguration>,
+    moderation: Res<LobbyModeration>,
+    identities: Res<PlayerIdentity>,
+    log: Res<InputLog>,
+    state: Res<RecorderState>,
+) {
+    if exit_events.read().last().is_none() {
+        return;
+    }
+    let Some(dir) = state.dir.as_ref() else {
+        return;
+    };
+
+    // Blocking, the IO pool may not run again
+    write_recording(dir, recording_files(&config, &moderation, &identities, &log, frame.frame));
+    info!("recording of {} frames written to {}", frame.frame, dir.display());
+}
+
+
+pub struct RecorderPlugin;
+
+impl Plugin for RecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RecorderSettings>()
+            .register_type::<RecorderSettings>()
+            .init_resource::<RecorderState>()
+            .add_systems(OnEnter(AppState::InGame), start_recording)
+            .add_systems(Update, flush_recording.run_if(in_state(AppState::InGame)))
+            .add_systems(Last, flush_recording_on_exit.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+use utils::rng::{RngStream, RollbackRngStream};
+
+// Streams of the rollback RNG, one per subsystem so their randomness stay independent
+
+pub struct WeaponsStream;
+impl RngStream for WeaponsStream {
+    const NAME: &'static str = "weapons";
+}
+
+pub struct SpawningStream;
+impl RngStream for SpawningStream {
+    const NAME: &'static str = "spawning";
+}
+
+// Power-ups and equipment dropped by the enemies
+pub struct DropsStream;
+impl RngStream for DropsStream {
+    const NAME: &'static str = "drops";
+}
+
+pub struct AiStream;
+impl RngStream for AiStream {
+    const NAME: &'static str = "ai";
+}
+
+pub type WeaponsRng = RollbackRngStream<WeaponsStream>;
+pub type SpawningRng = RollbackRngStream<SpawningStream>;
+pub type DropsRng = RollbackRngStream<DropsStream>;
+pub type AiRng = RollbackRngStream<AiStream>;
+
+// Every stream from the seed of the session
+pub fn insert_rng_streams(commands: &mut Commands, seed: u32) {
+    commands.insert_resource(WeaponsRng::new(seed));
+    commands.insert_resource(SpawningRng::new(seed));
+    commands.insert_resource(DropsRng::new(seed));
+    commands.insert_resource(AiRng::new(seed));
+}
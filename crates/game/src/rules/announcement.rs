@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+use bevy_kira_audio::prelude::*;
+use utils::events::RollbackEvents;
+
+use crate::{frame::{confirmed_frame, FrameCount}, hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState};
+
+use super::{WaveKind, WaveStartedEvent};
+
+// Banners of the wave director, presentation only. The waves are read once their frame
+// is confirmed so a mispredicted wave is never announced, and the banners are shown one
+// after the other with their stinger, a boss wave is its number and then the warning.
+
+#[derive(Resource, Clone, Debug)]
+pub struct AnnouncementSettings {
+    // Seconds a banner stay on screen, the last part fading out
+    pub banner_seconds: f32,
+    pub fade_seconds: f32,
+    pub wave_stinger: String,
+    pub horde_stinger: String,
+    pub boss_stinger: String,
+    pub volume: f64,
+}
+
+impl Default for AnnouncementSettings {
+    fn default() -> Self {
+        Self {
+            banner_seconds: 2.5,
+            fade_seconds: 0.5,
+            wave_stinger: "sounds/stingers/wave.wav".into(),
+            horde_stinger: "sounds/stingers/horde.wav".into(),
+            boss_stinger: "sounds/stingers/boss.wav".into(),
+            volume: 0.9,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Announcement {
+    text: String,
+    color: Color,
+    stinger: String,
+}
+
+#[derive(Resource, Default)]
+struct AnnouncementQueue {
+    pending: VecDeque<Announcement>,
+    // Banner on screen with the time it has left
+    current: Option<(Announcement, Timer)>,
+    last_read_frame: Option<u32>,
+}
+
+#[derive(Component)]
+struct AnnouncementBanner;
+
+
+fn wave_announcements(event: &WaveStartedEvent, settings: &AnnouncementSettings, localization: &Localization) -> Vec<Announcement> {
+    let mut announcements = vec![Announcement {
+        text: localization.format("announcement.wave", &[("wave", &event.wave)]),
+        color: Color::WHITE,
+        stinger: settings.wave_stinger.clone(),
+    }];
+    match event.kind {
+        WaveKind::Horde => announcements.push(Announcement {
+            text: localization.text("announcement.horde"),
+            color: Color::srgb(1.0, 0.6, 0.2),
+            stinger: settings.horde_stinger.clone(),
+        }),
+        WaveKind::Boss => announcements.push(Announcement {
+            text: localization.text("announcement.boss"),
+            color: Color::srgb(0.95, 0.2, 0.2),
+            stinger: settings.boss_stinger.clone(),
+        }),
+        WaveKind::Normal => {}
+    }
+    announcements
+}
+
+
+// SYSTEMS
+
+fn setup_announcement_banner(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        AnnouncementBanner,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 56.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::TopCenter, 2),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Visibility::Hidden,
+    ));
+}
+
+fn queue_wave_announcements(
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    settings: Res<AnnouncementSettings>,
+    localization: Res<Localization>,
+    wave_events: Res<RollbackEvents<WaveStartedEvent>>,
+    mut queue: ResMut<AnnouncementQueue>,
+) {
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    if queue.last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+
+    let announcements: Vec<_> = wave_events.read_after(queue.last_read_frame)
+        .filter(|(f, _)| *f <= confirmed_frame)
+        .flat_map(|(_, event)| wave_announcements(event, &settings, &localization))
+        .collect();
+    queue.pending.extend(announcements);
+    queue.last_read_frame = Some(confirmed_frame);
+}
+
+fn show_announcements(
+    time: Res<Time>,
+    settings: Res<AnnouncementSettings>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut queue: ResMut<AnnouncementQueue>,
+    mut q_banner: Query<(&mut Text, &mut TextColor, &mut Visibility), With<AnnouncementBanner>>,
+) {
+    let Ok((mut text, mut color, mut visibility)) = q_banner.get_single_mut() else {
+        return;
+    };
+
+    if let Some((_, timer)) = queue.current.as_mut() {
+        timer.tick(time.delta());
+        if timer.finished() {
+            queue.current = None;
+        }
+    }
+
+    // The next one only once the banner before is gone
+    if queue.current.is_none() {
+        if let Some(announcement) = queue.pending.pop_front() {
+            audio.play(asset_server.load(announcement.stinger.as_str())).with_volume(settings.volume);
+            queue.current = Some((announcement, Timer::from_seconds(settings.banner_seconds, TimerMode::Once)));
+        }
+    }
+
+    let Some((announcement, timer)) = queue.current.as_ref() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let alpha = (timer.remaining_secs() / settings.fade_seconds.max(f32::EPSILON)).min(1.0);
+    text.0 = announcement.text.clone();
+    color.0 = announcement.color.with_alpha(alpha);
+    *visibility = Visibility::Inherited;
+}
+
+
+pub struct AnnouncementUIPlugin;
+
+impl Plugin for AnnouncementUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AnnouncementSettings>();
+        app.init_resource::<AnnouncementQueue>();
+        app.add_systems(OnEnter(AppState::InGame), setup_announcement_banner);
+        app.add_systems(Update, (queue_wave_announcements, show_announcements).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stingers_exist() {
+        let settings = AnnouncementSettings::default();
+        for stinger in [&settings.wave_stinger, &settings.horde_stinger, &settings.boss_stinger] {
+            let path = format!("{}/../../assets/{}", env!("CARGO_MANIFEST_DIR"), stinger);
+            assert!(std::path::Path::new(&path).exists(), "{}", path);
+        }
+    }
+}
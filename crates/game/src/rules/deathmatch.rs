@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use utils::{events::RollbackEvents, frame::{FrameTimer, SimulationConfig}};
+
+use crate::{character::{health::{DamageAccumulator, DamageContributions, Death, DeathEvent, Health}, player::Player, status::Stunned}, frame::FrameCount, points::PlayerScore};
+
+use super::{GameMode, GameRules, MatchOutcome, MatchState};
+
+// Player waiting to come back in the versus mode, hidden and ignored by the bullets
+#[derive(Component, Reflect, Default, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Respawning {
+    pub timer: FrameTimer,
+}
+
+// In the versus mode a dead player is not despawned, it wait for its respawn instead.
+// Must run after the kills are awarded and before the death are applied.
+pub fn rollback_intercept_player_deaths(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    rules: Res<GameRules>,
+    simulation: Res<SimulationConfig>,
+    mut death_events: ResMut<RollbackEvents<DeathEvent>>,
+    player_query: Query<(Entity, &Transform, &Death), (With<Player>, With<Rollback>)>,
+) {
+    if rules.mode != GameMode::Deathmatch {
+        return;
+    }
+
+    let mut deaths: Vec<_> = player_query.iter().collect();
+    deaths.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, death) in deaths {
+        let timer = FrameTimer::from_seconds(frame.frame, rules.respawn_delay_seconds, &simulation);
+        death_events.send(frame.frame, DeathEvent { entity, position: transform.translation.truncate(), enemy: false, last_hit_by: death.last_hit_by.clone(), headshot: death.is_headshot(), weapon: death.weapon.clone() });
+        commands.entity(entity)
+            .remove::<(Death, DamageAccumulator, DamageContributions)>()
+            .insert((Respawning { timer }, Stunned { timer }));
+    }
+}
+
+// Spawn point the furthest from the other living players, first one on ties
+pub fn select_spawn_point(spawn_points: &[Vec2], others: &[Vec2]) -> Vec2 {
+    let mut best = (Vec2::ZERO, f32::MIN);
+    for point in spawn_points {
+        let distance = others.iter().map(|other| point.distance(*other)).fold(f32::MAX, f32::min);
+        if distance > best.1 {
+            best = (*point, distance);
+        }
+    }
+    best.0
+}
+
+pub fn rollback_respawn_players(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    rules: Res<GameRules>,
+    simulation: Res<SimulationConfig>,
+    mut player_query: Query<(Entity, &Player, &mut Transform, &mut Health, Option<&Respawning>), With<Rollback>>,
+) {
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.handle);
+
+    for i in 0..players.len() {
+        let Some(respawning) = players[i].4 else {
+            continue;
+        };
+        if !respawning.timer.is_done(frame.frame) {
+            continue;
+        }
+
+        let others: Vec<Vec2> = players.iter()
+            .filter(|(_, _, _, _, respawning)| respawning.is_none())
+            .map(|(_, _, transform, ..)| transform.translation.truncate())
+            .collect();
+        let position = select_spawn_point(&rules.spawn_points, &others);
+
+        let (entity, _, transform, health, _) = &mut players[i];
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        health.current = health.max;
+        health.invulnerable = Some(FrameTimer::from_seconds(frame.frame, rules.spawn_protection_seconds, &simulation));
+
+        commands.entity(*entity).remove::<Respawning>();
+    }
+}
+
+// End of the versus match, the player with the most kills win
+pub fn rollback_deathmatch_timer(
+    frame: Res<FrameCount>,
+    rules: Res<GameRules>,
+    simulation: Res<SimulationConfig>,
+    mut match_state: ResMut<MatchState>,
+    player_query: Query<(&Player, &PlayerScore), With<Rollback>>,
+) {
+    if rules.mode != GameMode::Deathmatch || match_state.is_over() || frame.frame < simulation.frames_from_seconds(rules.match_duration_seconds) {
+        return;
+    }
+
+    let mut scores: Vec<(usize, u32)> = player_query.iter().map(|(player, score)| (player.handle, score.kills)).collect();
+    scores.sort_by_key(|(handle, kills)| (std::cmp::Reverse(*kills), *handle));
+
+    match_state.outcome = Some(match scores.as_slice() {
+        [(_, best), (_, second), ..] if best == second => MatchOutcome::Draw,
+        [(handle, _), ..] => MatchOutcome::PlayerWon(*handle),
+        [] => MatchOutcome::Draw,
+    });
+}
+
+// Presentation only, hide the players waiting for their respawn
+pub fn update_respawning_visibility(
+    mut query: Query<(&mut Visibility, Has<Respawning>), With<Player>>,
+) {
+    for (mut visibility, respawning) in query.iter_mut() {
+        *visibility = if respawning { Visibility::Hidden } else { Visibility::Inherited };
+    }
+}
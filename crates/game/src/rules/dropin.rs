@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+
+use animation::SpriteSheetConfig;
+use bevy::prelude::*;
+use bevy_ggrs::{PlayerInputs, Rollback};
+use bevy_matchbox::prelude::PeerId;
+use ggrs::PlayerHandle;
+
+use crate::{character::{config::CharacterConfig, player::{create::create_player, input::INPUT_JOIN, jjrs::PeerConfig, LocalPlayer, Player}}, collider::CollisionSettings, frame::FrameCount, global_asset::GlobalAsset, hud::{HudAnchor, HudSlot}, lobby::{identity::PlayerIdentity, moderation::LobbyModeration}, localization::Localization, plugins::AppState, points::PlayerPoints, progression::PlayerLoadout, weapons::WeaponsConfig};
+
+use super::{GameRules, MatchState, WaveState};
+
+// Co-op drop-in. GGRS can't add a peer to a running session, so the place is taken in
+// the lobby: the peer get its handle like everyone but no player, it spectate. When it
+// ask to join the request travel in its input, every peer queue it on the same frame,
+// and the wave director open a join window at the next wave where the player is
+// spawned with the starting loadout of the rules. Without waves the join is immediate.
+
+pub const DROP_IN_KEY: KeyCode = KeyCode::Enter;
+
+#[derive(Clone, Debug)]
+pub struct DropInPlace {
+    pub class: String,
+    // Skin and colors of the peer, the weapon come from the rules
+    pub loadout: PlayerLoadout,
+}
+
+impl DropInPlace {
+    // Class and loadout of the host roster, the same on every peer
+    pub fn from_roster(moderation: &LobbyModeration, peer: PeerId) -> Self {
+        Self { class: moderation.class(peer).to_string(), loadout: moderation.loadout(peer) }
+    }
+
+    // The cosmetics of the peer with the weapon of the rules
+    pub fn join_loadout(&self, weapon: Option<String>) -> PlayerLoadout {
+        PlayerLoadout { weapon, ..self.loadout.clone() }
+    }
+}
+
+// Handles without a player at the start, the same on every peer. Never change during a match
+#[derive(Resource, Default, Debug, Clone)]
+pub struct DropInPlaces {
+    pub places: BTreeMap<PlayerHandle, DropInPlace>,
+    // Place of the local peer
+    pub local: Option<PlayerHandle>,
+}
+
+// Local only, the local peer want to join
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct DropInRequest(pub bool);
+
+// Requests agreed by all peers, waiting for the join window
+#[derive(Resource, Default, Debug, Clone, Reflect)]
+pub struct DropInQueue {
+    pub pending: Vec<PlayerHandle>,
+}
+
+// Closest spawn point to the team, first one on ties
+pub fn join_spawn_point(spawn_points: &[Vec2], others: &[Vec2]) -> Vec2 {
+    let mut best = (spawn_points.first().copied().unwrap_or(Vec2::ZERO), f32::MAX);
+    for point in spawn_points {
+        let distance = others.iter().map(|other| point.distance(*other)).fold(f32::MAX, f32::min);
+        if distance < best.1 {
+            best = (*point, distance);
+        }
+    }
+    best.0
+}
+
+#[derive(Component)]
+struct DropInPrompt;
+
+
+// SYSTEMS
+
+pub fn rollback_queue_drop_ins(
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    places: Res<DropInPlaces>,
+    match_state: Res<MatchState>,
+    mut queue: ResMut<DropInQueue>,
+    player_query: Query<&Player, With<Rollback>>,
+) {
+    if match_state.is_over() {
+        return;
+    }
+
+    for handle in places.places.keys() {
+        if *handle >= inputs.len() || queue.pending.contains(handle) || player_query.iter().any(|player| player.handle == *handle) {
+            continue;
+        }
+        let (input, _input_status) = inputs[*handle];
+        if input.buttons & INPUT_JOIN != 0 {
+            info!("player {} asked to join", handle);
+            queue.pending.push(*handle);
+        }
+    }
+}
+
+// Must run after the wave director and before the spawners
+pub fn rollback_spawn_drop_ins(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    rules: Res<GameRules>,
+    wave_state: Res<WaveState>,
+    places: Res<DropInPlaces>,
+    identities: Res<PlayerIdentity>,
+    mut queue: ResMut<DropInQueue>,
+    player_query: Query<(&Player, &Transform), With<Rollback>>,
+
+    global_assets: Res<GlobalAsset>,
+    collision_settings: Res<CollisionSettings>,
+    weapons_asset: Res<Assets<WeaponsConfig>>,
+    character_asset: Res<Assets<CharacterConfig>>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    sprint_sheet_assets: Res<Assets<SpriteSheetConfig>>,
+) {
+    if queue.pending.is_empty() {
+        return;
+    }
+    if rules.has_waves() && !wave_state.join_window.is_active(frame.frame) {
+        return;
+    }
+
+    let mut players: Vec<_> = player_query.iter().collect();
+    players.sort_by_key(|(player, _)| player.handle);
+    let others: Vec<Vec2> = players.iter().map(|(_, transform)| transform.translation.truncate()).collect();
+
+    let mut pending = std::mem::take(&mut queue.pending);
+    pending.sort();
+    for (i, handle) in pending.into_iter().enumerate() {
+        let Some(place) = places.places.get(&handle) else {
+            continue;
+        };
+        let loadout = place.join_loadout(rules.join_weapon.clone());
+        let position = join_spawn_point(&rules.spawn_points, &others) + Vec2::new(-50.0 * i as f32, 0.0);
+
+        let entity = create_player(&mut commands, &global_assets, &weapons_asset, &character_asset, &collision_settings, &asset_server, &mut texture_atlas_layouts, &sprint_sheet_assets, places.local == Some(handle), handle, identities.color(handle), &place.class, &loadout);
+        commands.entity(entity).insert((
+            Transform::from_translation(position.extend(0.0)),
+            PlayerPoints { current: rules.join_points, total_earned: 0 },
+        ));
+        info!("player {} joined at wave {}", handle, wave_state.wave);
+    }
+}
+
+fn setup_drop_in_prompt(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        DropInPrompt,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 20.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::BottomCenter, 3),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Visibility::Hidden,
+    ));
+}
+
+fn update_drop_in_prompt(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    localization: Res<Localization>,
+    rules: Res<GameRules>,
+    places: Res<DropInPlaces>,
+    queue: Res<DropInQueue>,
+    mut request: ResMut<DropInRequest>,
+    q_local: Query<(), With<LocalPlayer>>,
+    mut q_prompt: Query<(&mut Text, &mut Visibility), With<DropInPrompt>>,
+) {
+    let Ok((mut text, mut visibility)) = q_prompt.get_single_mut() else {
+        return;
+    };
+    let Some(handle) = places.local else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    if !q_local.is_empty() {
+        request.0 = false;
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    if keyboard_input.just_pressed(DROP_IN_KEY) {
+        request.0 = true;
+    }
+
+    let key = format!("{:?}", DROP_IN_KEY);
+    text.0 = if queue.pending.contains(&handle) {
+        localization.text(if rules.has_waves() { "dropin.joining_next_wave" } else { "dropin.joining_now" })
+    } else if request.0 {
+        localization.text("dropin.asking")
+    } else {
+        localization.format(if rules.has_waves() { "dropin.press_next_wave" } else { "dropin.press_now" }, &[("key", &key)])
+    };
+}
+
+
+pub struct DropInUIPlugin;
+
+impl Plugin for DropInUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_drop_in_prompt);
+        app.add_systems(Update, update_drop_in_prompt.run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::player::create::DEFAULT_PLAYER_CLASS;
+
+    fn peer(n: u8) -> PeerId {
+        serde_json::from_str(&format!("\"00000000-0000-0000-0000-0000000000{:02x}\"", n)).unwrap()
+    }
+
+    #[test]
+    fn test_place_take_the_class_of_the_roster() {
+        let mut moderation = LobbyModeration::default();
+        moderation.classes.insert(peer(1), "scout".to_string());
+
+        assert_eq!(DropInPlace::from_roster(&moderation, peer(1)).class, "scout");
+        // Not the default of the peer that create the place
+        assert_eq!(DropInPlace::from_roster(&moderation, peer(2)).class, DEFAULT_PLAYER_CLASS);
+    }
+
+    #[test]
+    fn test_join_loadout_keep_the_cosmetics() {
+        let loadout = PlayerLoadout { skin: Some("red".to_string()), weapon: Some("shotgun".to_string()), colors: BTreeMap::from([("shirt".to_string(), 2)]) };
+        let place = DropInPlace { class: "medic".to_string(), loadout: loadout.clone() };
+
+        let joined = place.join_loadout(Some("pistol".to_string()));
+        assert_eq!(joined.weapon.as_deref(), Some("pistol"));
+        assert_eq!(joined.skin, loadout.skin);
+        assert_eq!(joined.colors, loadout.colors);
+    }
+
+    #[test]
+    fn test_join_spawn_point_closest_to_the_team() {
+        let points = [Vec2::new(0.0, 0.0), Vec2::new(500.0, 0.0)];
+        assert_eq!(join_spawn_point(&points, &[Vec2::new(450.0, 0.0)]), Vec2::new(500.0, 0.0));
+        // Alone, the first one
+        assert_eq!(join_spawn_point(&points, &[]), Vec2::new(0.0, 0.0));
+    }
+}
@@ -0,0 +1,269 @@
+pub mod announcement;
+pub mod deathmatch;
+pub mod dropin;
+pub mod objective;
+pub mod ui;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::{events::RollbackEvents, frame::{FrameTimer, SimulationConfig}};
+
+use crate::frame::FrameCount;
+
+use dropin::DropInQueue;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum GameMode {
+    // Endless waves, the match never end
+    #[default]
+    Survival,
+    // Protect the generator until the last wave
+    DefendGenerator,
+    // Players against each other, no zombies, most kills at the end of the timer win
+    Deathmatch,
+    // Scripted steps on the tutorial map, offline only
+    Tutorial,
+}
+
+// Rules of the match, decided in the lobby and identical on all peers.
+// Not part of the rollback state, they never change during a match.
+// Durations are in seconds, turned in frames with the tick rate of `SimulationConfig`.
+#[derive(Resource, Clone, Debug, Reflect, Serialize, Deserialize)]
+pub struct GameRules {
+    pub mode: GameMode,
+    pub wave_duration_seconds: f32,
+    // Waves to survive to win in the objective modes
+    pub waves_to_survive: u32,
+    // Every Nth wave is a horde or a boss wave, 0 for never. The boss win when both match
+    pub horde_wave_interval: u32,
+    pub boss_wave_interval: u32,
+    // The cooldown of the spawners is divided by this during a horde
+    pub horde_spawn_rate: u32,
+    pub generator_health: f32,
+    pub generator_position: Vec2,
+    // A player closer than this to a zombie steal its attention from the generator
+    pub player_aggro_distance: f32,
+    // Damage dealt by a zombie next to the generator, every attack interval
+    pub generator_attack_damage: f32,
+    pub generator_attack_interval_seconds: f32,
+    // Bullets of a player can hurt the other players, always on in the versus mode
+    pub friendly_fire: bool,
+    pub match_duration_seconds: f32,
+    pub respawn_delay_seconds: f32,
+    pub spawn_protection_seconds: f32,
+    pub spawn_points: Vec<Vec2>,
+    // Time at the start of a wave the spawners wait when players are joining
+    pub join_window_seconds: f32,
+    // Starting loadout of a player joining during the match, the class weapons when none
+    pub join_weapon: Option<String>,
+    pub join_points: u32,
+    // Layer matrix of the match, a preset of `collision_presets.ron`
+    pub collision_preset: String,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::Survival,
+            wave_duration_seconds: 60.0,
+            waves_to_survive: 5,
+            horde_wave_interval: 3,
+            boss_wave_interval: 5,
+            horde_spawn_rate: 2,
+            generator_health: 500.0,
+            generator_position: Vec2::new(0.0, 150.0),
+            player_aggro_distance: 150.0,
+            generator_attack_damage: 5.0,
+            generator_attack_interval_seconds: 0.5,
+            friendly_fire: false,
+            match_duration_seconds: 60.0 * 5.0,
+            respawn_delay_seconds: 3.0,
+            spawn_protection_seconds: 2.0,
+            spawn_points: vec![
+                Vec2::new(-800.0, -600.0),
+                Vec2::new(800.0, -600.0),
+                Vec2::new(-800.0, 700.0),
+                Vec2::new(800.0, 700.0),
+            ],
+            join_window_seconds: 3.0,
+            join_weapon: None,
+            join_points: 500,
+            collision_preset: "coop".into(),
+        }
+    }
+}
+
+impl GameMode {
+    // Name used in the arguments and the lobby options
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::DefendGenerator => "defend",
+            GameMode::Deathmatch => "deathmatch",
+            GameMode::Tutorial => "tutorial",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "survival" => Some(GameMode::Survival),
+            "defend" => Some(GameMode::DefendGenerator),
+            "deathmatch" => Some(GameMode::Deathmatch),
+            "tutorial" => Some(GameMode::Tutorial),
+            _ => None,
+        }
+    }
+
+    // Preset of the layer matrix used by default
+    pub fn collision_preset(&self) -> &'static str {
+        match self {
+            GameMode::Deathmatch => "pvp",
+            _ => "coop",
+        }
+    }
+
+    // Modes that can't be played in an online lobby
+    pub fn is_offline_only(&self) -> bool {
+        *self == GameMode::Tutorial
+    }
+}
+
+impl GameRules {
+    pub fn from_mode(mode: GameMode) -> Self {
+        Self { mode, collision_preset: mode.collision_preset().into(), ..default() }
+    }
+
+    pub fn has_generator(&self) -> bool {
+        self.mode == GameMode::DefendGenerator
+    }
+
+    pub fn has_waves(&self) -> bool {
+        !matches!(self.mode, GameMode::Deathmatch | GameMode::Tutorial)
+    }
+
+    pub fn wave_kind(&self, wave: u32) -> WaveKind {
+        if self.boss_wave_interval > 0 && wave % self.boss_wave_interval == 0 {
+            WaveKind::Boss
+        } else if self.horde_wave_interval > 0 && wave % self.horde_wave_interval == 0 {
+            WaveKind::Horde
+        } else {
+            WaveKind::Normal
+        }
+    }
+
+    pub fn enemies_enabled(&self) -> bool {
+        !matches!(self.mode, GameMode::Deathmatch | GameMode::Tutorial)
+    }
+
+    // Players can drop points and ammo for their teammates
+    pub fn allows_trading(&self) -> bool {
+        self.mode != GameMode::Deathmatch
+    }
+
+    pub fn friendly_fire(&self) -> bool {
+        self.friendly_fire || self.mode == GameMode::Deathmatch
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum MatchOutcome {
+    Victory,
+    Defeat,
+    PlayerWon(usize),
+    Draw,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum WaveKind {
+    #[default]
+    Normal,
+    // The spawners run faster
+    Horde,
+    // Announced with its warning, the enemies are the ones of any wave
+    Boss,
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+pub struct WaveState {
+    // Start at 1 on the first frame
+    pub wave: u32,
+    pub timer: FrameTimer,
+    pub kind: WaveKind,
+    // Opened at the start of the wave for the queued drop-ins, no enemy spawn during it
+    pub join_window: FrameTimer,
+}
+
+// Sent by the wave director when a wave start, announced once confirmed
+#[derive(Clone, Debug)]
+pub struct WaveStartedEvent {
+    pub wave: u32,
+    pub kind: WaveKind,
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+pub struct MatchState {
+    pub outcome: Option<MatchOutcome>,
+}
+
+impl MatchState {
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+}
+
+pub fn rollback_advance_waves(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    rules: Res<GameRules>,
+    mut wave_state: ResMut<WaveState>,
+    mut match_state: ResMut<MatchState>,
+    drop_ins: Res<DropInQueue>,
+    mut wave_events: ResMut<RollbackEvents<WaveStartedEvent>>,
+) {
+    if match_state.is_over() || !rules.has_waves() {
+        return;
+    }
+
+    if wave_state.wave > 0 && !wave_state.timer.is_done(frame.frame) {
+        return;
+    }
+
+    if rules.has_generator() && wave_state.wave >= rules.waves_to_survive {
+        info!("wave {} survived, objective complete", wave_state.wave);
+        match_state.outcome = Some(MatchOutcome::Victory);
+        return;
+    }
+
+    wave_state.wave += 1;
+    wave_state.timer = FrameTimer::from_seconds(frame.frame, rules.wave_duration_seconds, &simulation);
+    wave_state.kind = rules.wave_kind(wave_state.wave);
+    wave_state.join_window = if drop_ins.pending.is_empty() {
+        FrameTimer::default()
+    } else {
+        FrameTimer::from_seconds(frame.frame, rules.join_window_seconds, &simulation)
+    };
+    wave_events.send(frame.frame, WaveStartedEvent { wave: wave_state.wave, kind: wave_state.kind });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wave_kind_of_the_intervals() {
+        let rules = GameRules { horde_wave_interval: 3, boss_wave_interval: 5, ..default() };
+        assert_eq!(rules.wave_kind(1), WaveKind::Normal);
+        assert_eq!(rules.wave_kind(3), WaveKind::Horde);
+        assert_eq!(rules.wave_kind(5), WaveKind::Boss);
+        // The boss win when both match
+        assert_eq!(rules.wave_kind(15), WaveKind::Boss);
+    }
+
+    #[test]
+    fn test_wave_kind_never_with_a_zero_interval() {
+        let rules = GameRules { horde_wave_interval: 0, boss_wave_interval: 0, ..default() };
+        assert_eq!(rules.wave_kind(3), WaveKind::Normal);
+        assert_eq!(rules.wave_kind(15), WaveKind::Normal);
+    }
+}
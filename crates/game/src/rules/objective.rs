@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use utils::frame::SimulationConfig;
+
+use crate::{character::{enemy::Enemy, health::{accumulate_damage, DamageAccumulator, Health, HitBy}}, collider::{Collider, ColliderShape, CollisionLayer, CollisionSettings}, frame::FrameCount};
+
+use super::{GameRules, MatchOutcome, MatchState};
+
+const GENERATOR_SIZE: f32 = 80.0;
+// Distance from the side of the generator where a zombie can hit it
+const GENERATOR_ATTACK_REACH: f32 = 40.0;
+
+// Objective of the defend mode, the match is lost when it's destroyed
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Generator;
+
+pub fn spawn_generator(
+    commands: &mut Commands,
+    rules: &GameRules,
+    collision_settings: &CollisionSettings,
+) {
+    commands.spawn((
+        Generator,
+        Transform::from_translation(rules.generator_position.extend(0.0)),
+        Sprite {
+            color: Color::srgb(0.9, 0.8, 0.2),
+            custom_size: Some(Vec2::splat(GENERATOR_SIZE)),
+            ..default()
+        },
+        Collider {
+            shape: ColliderShape::Rectangle { width: GENERATOR_SIZE, height: GENERATOR_SIZE },
+            offset: Vec2::ZERO,
+        },
+        CollisionLayer(collision_settings.objective_layer),
+        Health { current: rules.generator_health, max: rules.generator_health, invulnerable: None },
+    )).add_rollback();
+}
+
+// Zombies next to the generator hit it at a fixed interval
+pub fn rollback_enemies_attack_generator(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    rules: Res<GameRules>,
+    simulation: Res<SimulationConfig>,
+    match_state: Res<MatchState>,
+    enemy_query: Query<(Entity, &Transform), (With<Enemy>, With<Rollback>)>,
+    mut generator_query: Query<(Entity, &Transform, Option<&mut DamageAccumulator>), (With<Generator>, Without<Enemy>)>,
+) {
+    let interval = simulation.frames_from_seconds(rules.generator_attack_interval_seconds);
+    if match_state.is_over() || interval == 0 || frame.frame % interval != 0 {
+        return;
+    }
+
+    let Ok((generator_entity, generator_transform, opt_accumulator)) = generator_query.get_single_mut() else {
+        return;
+    };
+    let generator_position = generator_transform.translation.truncate();
+    let reach = GENERATOR_SIZE / 2.0 + GENERATOR_ATTACK_REACH;
+
+    let mut attackers: Vec<_> = enemy_query.iter()
+        .filter(|(_, transform)| transform.translation.truncate().distance(generator_position) <= reach)
+        .collect();
+    attackers.sort_by_key(|(entity, _)| entity.index());
+
+    // One accumulation for all the attackers, the last one get the credit
+    let Some((last_attacker, _)) = attackers.last() else {
+        return;
+    };
+    let damage = rules.generator_attack_damage * attackers.len() as f32;
+    accumulate_damage(&mut commands, generator_entity, opt_accumulator, damage, Some(HitBy::Entity(*last_attacker)));
+}
+
+// The match is lost as soon as the generator is gone
+pub fn rollback_check_generator(
+    rules: Res<GameRules>,
+    mut match_state: ResMut<MatchState>,
+    generator_query: Query<&Health, With<Generator>>,
+) {
+    if !rules.has_generator() || match_state.is_over() {
+        return;
+    }
+
+    let destroyed = generator_query.get_single().map_or(true, |health| health.current <= 0.0);
+    if destroyed {
+        info!("generator destroyed, match lost");
+        match_state.outcome = Some(MatchOutcome::Defeat);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+
+use utils::{events::RollbackEvents, frame::SimulationConfig};
+
+use crate::{character::{health::{DeathEvent, Health, HitBy}, player::Player}, frame::{confirmed_frame, FrameCount}, hud::{HudAnchor, HudSlot}, lobby::identity::PlayerIdentity, localization::Localization, plugins::AppState, points::PlayerScore};
+
+use super::{deathmatch::update_respawning_visibility, objective::Generator, GameMode, GameRules, MatchOutcome, MatchState, WaveState};
+
+
+#[derive(Component)]
+struct WaveText;
+
+#[derive(Component)]
+struct GeneratorText;
+
+#[derive(Component)]
+struct OutcomeText;
+
+#[derive(Component)]
+struct KillFeedText;
+
+const KILL_FEED_LINES: usize = 5;
+const KILL_FEED_SECONDS: f32 = 6.0;
+
+struct KillFeedLine {
+    text: String,
+    // Weapon of the killer, in the color of its rarity
+    weapon: Option<(String, Color)>,
+    timer: Timer,
+}
+
+// Last kills read from the rollback events, presentation only
+#[derive(Resource, Default)]
+struct KillFeed {
+    lines: VecDeque<KillFeedLine>,
+    last_read_frame: Option<u32>,
+}
+
+
+fn setup_rules_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    commands.spawn((
+        WaveText,
+        Text::new(""),
+        TextFont {
+            font: font.clone(),
+            font_size: 18.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::TopRight, 0),
+    ));
+
+    commands.spawn((
+        GeneratorText,
+        Text::new(""),
+        TextFont {
+            font: font.clone(),
+            font_size: 16.0,
+            ..Default::default()
+        },
+        TextColor(Color::srgb(0.9, 0.8, 0.2)),
+        HudSlot::new(HudAnchor::TopRight, 1),
+    ));
+
+    commands.spawn((
+        OutcomeText,
+        Text::new(""),
+        TextFont {
+            font,
+            font_size: 48.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::Center, 0),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Visibility::Hidden,
+    ));
+
+    commands.spawn((
+        KillFeedText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 14.0,
+            ..Default::default()
+        },
+        HudSlot::new(HudAnchor::TopRight, 3),
+        TextLayout::new_with_justify(JustifyText::Right),
+    ));
+}
+
+fn update_rules_ui(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    rules: Res<GameRules>,
+    wave_state: Res<WaveState>,
+    match_state: Res<MatchState>,
+    localization: Res<Localization>,
+    q_generator: Query<&Health, With<Generator>>,
+    q_scores: Query<(&Player, &PlayerScore)>,
+    mut q_wave: Query<&mut Text, (With<WaveText>, Without<GeneratorText>, Without<OutcomeText>)>,
+    mut q_generator_text: Query<&mut Text, (With<GeneratorText>, Without<WaveText>, Without<OutcomeText>)>,
+    mut q_outcome: Query<(&mut Text, &mut TextColor, &mut Visibility), (With<OutcomeText>, Without<WaveText>, Without<GeneratorText>)>,
+) {
+    if let Ok(mut text) = q_wave.get_single_mut() {
+        let remaining = wave_state.timer.remaining_seconds(frame.frame, &simulation).ceil();
+        text.0 = if rules.mode == GameMode::Tutorial {
+            String::new()
+        } else if rules.mode == GameMode::Deathmatch {
+            let remaining = simulation.frames_from_seconds(rules.match_duration_seconds).saturating_sub(frame.frame) / simulation.tick_rate.max(1);
+            localization.format("hud.time_left", &[("minutes", &(remaining / 60)), ("seconds", &format!("{:02}", remaining % 60))])
+        } else if rules.has_generator() {
+            localization.format("hud.wave_of", &[("wave", &wave_state.wave), ("waves", &rules.waves_to_survive), ("seconds", &remaining)])
+        } else {
+            localization.format("hud.wave", &[("wave", &wave_state.wave), ("seconds", &remaining)])
+        };
+    }
+
+    if let Ok(mut text) = q_generator_text.get_single_mut() {
+        text.0 = match q_generator.get_single() {
+            Ok(health) if rules.has_generator() => localization.format("hud.generator", &[("current", &health.current.max(0.0).round()), ("max", &health.max.round())]),
+            _ if rules.mode == GameMode::Deathmatch => {
+                let mut scores: Vec<_> = q_scores.iter().collect();
+                scores.sort_by_key(|(player, _)| player.handle);
+                scores.iter()
+                    .map(|(player, score)| localization.format("hud.score", &[
+                        ("player", &(player.handle + 1)), ("kills", &score.kills), ("headshots", &score.headshots), ("deaths", &score.deaths),
+                    ]))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            _ => String::new(),
+        };
+    }
+
+    if let Ok((mut text, mut color, mut visibility)) = q_outcome.get_single_mut() {
+        match match_state.outcome {
+            Some(MatchOutcome::Victory) => {
+                text.0 = localization.text("hud.victory");
+                color.0 = Color::srgb(0.3, 0.9, 0.3);
+                *visibility = Visibility::Inherited;
+            }
+            Some(MatchOutcome::Defeat) => {
+                text.0 = localization.text("hud.defeat");
+                color.0 = Color::srgb(0.9, 0.2, 0.2);
+                *visibility = Visibility::Inherited;
+            }
+            Some(MatchOutcome::PlayerWon(handle)) => {
+                text.0 = localization.format("hud.player_won", &[("player", &(handle + 1))]);
+                color.0 = Color::srgb(0.3, 0.9, 0.3);
+                *visibility = Visibility::Inherited;
+            }
+            Some(MatchOutcome::Draw) => {
+                text.0 = localization.text("hud.draw");
+                color.0 = Color::WHITE;
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+fn update_kill_feed(
+    time: Res<Time>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    identities: Res<PlayerIdentity>,
+    localization: Res<Localization>,
+    death_events: Res<RollbackEvents<DeathEvent>>,
+    q_players: Query<&Player>,
+    mut feed: ResMut<KillFeed>,
+    q_text: Query<(Entity, &TextFont), With<KillFeedText>>,
+    mut commands: Commands,
+) {
+    let name = |handle: usize| identities.name(handle);
+    let line_count = feed.lines.len();
+    let mut added = false;
+
+    // Only the confirmed deaths, a mispredicted kill is never shown
+    let last_read_frame = feed.last_read_frame;
+    let confirmed_frame = confirmed_frame(&frame, confirmed).filter(|confirmed_frame| last_read_frame.map_or(true, |last| last < *confirmed_frame));
+    let deaths = death_events.read_after(last_read_frame)
+        .filter(|(f, _)| confirmed_frame.map_or(false, |confirmed_frame| *f <= confirmed_frame));
+    for (_, event) in deaths {
+        let killer = match event.last_hit_by {
+            Some(HitBy::Player(handle)) => name(handle),
+            Some(HitBy::Entity(_)) => localization.text("killfeed.zombie_killer"),
+            None => localization.text("killfeed.world"),
+        };
+        // Dead enemies are already despawned, the players stay to respawn
+        let victim = q_players.get(event.entity).map_or_else(|_| localization.text("killfeed.zombie_victim"), |player| name(player.handle));
+        let template = if event.headshot { "killfeed.kill_headshot" } else { "killfeed.kill" };
+        let weapon = event.weapon.as_ref().map(|weapon| (weapon.name.clone(), weapon.rarity.color()));
+        feed.lines.push_back(KillFeedLine {
+            text: localization.format(template, &[("killer", &killer), ("victim", &victim)]),
+            weapon,
+            timer: Timer::from_seconds(KILL_FEED_SECONDS, TimerMode::Once),
+        });
+        if feed.lines.len() > KILL_FEED_LINES {
+            feed.lines.pop_front();
+        }
+        added = true;
+    }
+    if confirmed_frame.is_some() {
+        feed.last_read_frame = confirmed_frame;
+    }
+
+    for line in feed.lines.iter_mut() {
+        line.timer.tick(time.delta());
+    }
+    feed.lines.retain(|line| !line.timer.finished());
+
+    if !added && feed.lines.len() == line_count {
+        return;
+    }
+    let Ok((entity, font)) = q_text.get_single() else {
+        return;
+    };
+    // A span per part, the root text stay empty
+    commands.entity(entity).despawn_descendants().with_children(|parent| {
+        for (index, line) in feed.lines.iter().enumerate() {
+            let separator = if index > 0 { "\n" } else { "" };
+            parent.spawn((TextSpan::new(format!("{}{}", separator, line.text)), font.clone()));
+            if let Some((weapon, color)) = &line.weapon {
+                parent.spawn((TextSpan::new(format!(" [{}]", weapon)), font.clone(), TextColor(*color)));
+            }
+        }
+    });
+}
+
+
+#[derive(Default)]
+pub struct RulesUIPlugin;
+
+impl Plugin for RulesUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KillFeed>();
+        app.add_systems(OnEnter(AppState::InGame), setup_rules_ui);
+        app.add_systems(Update, (update_rules_ui, update_kill_feed, update_respawning_visibility).run_if(in_state(AppState::InGame)));
+    }
+}
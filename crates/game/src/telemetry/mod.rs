@@ -0,0 +1,307 @@
+pub mod ui;
+
+use std::collections::BTreeMap;
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::Serialize;
+use utils::{events::RollbackEvents, frame::SimulationConfig};
+
+use crate::{character::{config::{CharacterConfig, CharacterConfigHandles}, enemy::Enemy, health::{DeathEvent, HitBy}, player::Player}, frame::FrameCount, plugins::AppState, points::PlayerPoints, weapons::{Weapon, WeaponFiredEvent}};
+
+// Opt-in balance telemetry, everything stay local. The data is collected outside
+// of the rollback schedule from the rollback events, a mispredicted frame can be
+// counted, the numbers are for tuning not for gameplay.
+
+#[derive(Resource, Clone, Debug)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    // Folder where the json reports are written
+    pub output_dir: String,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            output_dir: "telemetry".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct WeaponUsage {
+    pub shots: u32,
+    pub kills: u32,
+    pub headshot_kills: u32,
+}
+
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct ArchetypeStats {
+    pub spawned: u32,
+    pub kills: u32,
+    pub total_time_to_kill_frames: u64,
+    pub min_time_to_kill_frames: Option<u32>,
+    pub max_time_to_kill_frames: Option<u32>,
+}
+
+impl ArchetypeStats {
+    pub fn average_time_to_kill_seconds(&self, simulation: &SimulationConfig) -> Option<f32> {
+        if self.kills == 0 {
+            return None;
+        }
+        Some(self.total_time_to_kill_frames as f32 / self.kills as f32 * simulation.timestep())
+    }
+
+    fn record_kill(&mut self, frames: u32) {
+        self.kills += 1;
+        self.total_time_to_kill_frames += frames as u64;
+        self.min_time_to_kill_frames = Some(self.min_time_to_kill_frames.map_or(frames, |m| m.min(frames)));
+        self.max_time_to_kill_frames = Some(self.max_time_to_kill_frames.map_or(frames, |m| m.max(frames)));
+    }
+}
+
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct SurvivalRecord {
+    // Anonymized, only the slot of the player in the session
+    pub player_slot: usize,
+    pub survived_frames: u32,
+    pub died: bool,
+}
+
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct PointsEconomy {
+    pub earned: u32,
+    pub spent: u32,
+    pub unspent: u32,
+}
+
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct BalanceReport {
+    pub game_version: String,
+    pub duration_frames: u32,
+    pub weapons: BTreeMap<String, WeaponUsage>,
+    pub archetypes: BTreeMap<String, ArchetypeStats>,
+    pub survival: Vec<SurvivalRecord>,
+    pub points: PointsEconomy,
+}
+
+#[derive(Resource, Default)]
+pub struct TelemetryRecorder {
+    pub report: BalanceReport,
+    last_read_frame: Option<u32>,
+    start_frame: Option<u32>,
+    // Enemy alive with their archetype and spawn frame
+    enemies: HashMap<Entity, (String, u32)>,
+    players: HashMap<Entity, (usize, u32)>,
+    pub last_export: Option<String>,
+}
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TelemetrySettings>()
+            .init_resource::<TelemetryRecorder>()
+            .add_plugins(ui::TelemetryUIPlugin)
+            .add_systems(Update, (
+                toggle_telemetry_system,
+                track_spawns_system,
+                record_events_system,
+                sample_points_system,
+                export_on_key_system,
+            ).chain().run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), export_report_system)
+            .add_systems(Last, export_on_app_exit_system);
+    }
+}
+
+fn toggle_telemetry_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<TelemetrySettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F8) {
+        settings.enabled = !settings.enabled;
+        info!("telemetry {}", if settings.enabled { "enabled" } else { "disabled" });
+    }
+}
+
+fn track_spawns_system(
+    settings: Res<TelemetrySettings>,
+    frame: Res<FrameCount>,
+    character_configs: Res<Assets<CharacterConfig>>,
+    mut recorder: ResMut<TelemetryRecorder>,
+    q_enemies: Query<(Entity, &CharacterConfigHandles), Added<Enemy>>,
+    q_players: Query<(Entity, &Player), Added<Player>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    if recorder.start_frame.is_none() {
+        recorder.start_frame = Some(frame.frame);
+    }
+
+    for (entity, handles) in q_enemies.iter() {
+        let archetype = character_configs.get(&handles.config)
+            .map_or("unknown".to_string(), |config| config.asset_name_ref.clone());
+        recorder.report.archetypes.entry(archetype.clone()).or_default().spawned += 1;
+        recorder.enemies.insert(entity, (archetype, frame.frame));
+    }
+
+    for (entity, player) in q_players.iter() {
+        recorder.players.insert(entity, (player.handle, frame.frame));
+    }
+}
+
+fn record_events_system(
+    settings: Res<TelemetrySettings>,
+    frame: Res<FrameCount>,
+    mut recorder: ResMut<TelemetryRecorder>,
+    fired_events: Res<RollbackEvents<WeaponFiredEvent>>,
+    death_events: Res<RollbackEvents<DeathEvent>>,
+    q_weapons: Query<&Weapon>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    let last_read_frame = recorder.last_read_frame;
+
+    for (_, event) in fired_events.read_after(last_read_frame) {
+        let name = q_weapons.get(event.weapon_entity)
+            .map_or("unknown".to_string(), |weapon| weapon.config.name.clone());
+        recorder.report.weapons.entry(name).or_default().shots += 1;
+    }
+
+    for (death_frame, event) in death_events.read_after(last_read_frame) {
+        if let Some((archetype, spawn_frame)) = recorder.enemies.remove(&event.entity) {
+            recorder.report.archetypes.entry(archetype).or_default().record_kill(death_frame.saturating_sub(spawn_frame));
+
+            // The weapon of the killing blow, not the last one the killer fired
+            if let (Some(HitBy::Player(_)), Some(weapon)) = (event.last_hit_by, event.weapon.as_ref()) {
+                let usage = recorder.report.weapons.entry(weapon.name.clone()).or_default();
+                usage.kills += 1;
+                if event.headshot {
+                    usage.headshot_kills += 1;
+                }
+            }
+        } else if let Some((slot, spawn_frame)) = recorder.players.remove(&event.entity) {
+            recorder.report.survival.push(SurvivalRecord {
+                player_slot: slot,
+                survived_frames: death_frame.saturating_sub(spawn_frame),
+                died: true,
+            });
+        }
+    }
+
+    recorder.last_read_frame = Some(frame.frame);
+    let start_frame = recorder.start_frame.unwrap_or(frame.frame);
+    recorder.report.duration_frames = frame.frame.saturating_sub(start_frame);
+}
+
+fn sample_points_system(
+    settings: Res<TelemetrySettings>,
+    mut recorder: ResMut<TelemetryRecorder>,
+    q_points: Query<&PlayerPoints>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    let mut economy = PointsEconomy::default();
+    for points in q_points.iter() {
+        economy.earned += points.total_earned;
+        economy.spent += points.total_earned.saturating_sub(points.current);
+        economy.unspent += points.current;
+    }
+    recorder.report.points = economy;
+}
+
+fn export_on_key_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<TelemetrySettings>,
+    frame: Res<FrameCount>,
+    mut recorder: ResMut<TelemetryRecorder>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        export_report(&settings, &frame, &mut recorder);
+    }
+}
+
+fn export_report_system(
+    settings: Res<TelemetrySettings>,
+    frame: Res<FrameCount>,
+    mut recorder: ResMut<TelemetryRecorder>,
+) {
+    export_report(&settings, &frame, &mut recorder);
+}
+
+fn export_on_app_exit_system(
+    mut exit_events: EventReader<AppExit>,
+    settings: Res<TelemetrySettings>,
+    frame: Res<FrameCount>,
+    mut recorder: ResMut<TelemetryRecorder>,
+) {
+    if exit_events.read().next().is_some() {
+        export_report(&settings, &frame, &mut recorder);
+    }
+}
+
+fn export_report(settings: &TelemetrySettings, frame: &FrameCount, recorder: &mut TelemetryRecorder) {
+    if !settings.enabled || recorder.start_frame.is_none() {
+        return;
+    }
+
+    let mut report = recorder.report.clone();
+    report.game_version = env!("APP_VERSION").into();
+
+    // Players still alive at the end of the match
+    let mut alive: Vec<(usize, u32)> = recorder.players.values().copied().collect();
+    alive.sort();
+    for (slot, spawn_frame) in alive {
+        report.survival.push(SurvivalRecord {
+            player_slot: slot,
+            survived_frames: frame.frame.saturating_sub(spawn_frame),
+            died: false,
+        });
+    }
+
+    let json = match serde_json::to_string_pretty(&report) {
+        Ok(json) => json,
+        Err(err) => {
+            error!("failed to serialize telemetry report: {}", err);
+            return;
+        }
+    };
+
+    recorder.last_export = Some(write_report(settings, frame.frame, &json));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_report(settings: &TelemetrySettings, frame: u32, json: &str) -> String {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = std::path::Path::new(&settings.output_dir).join(format!("balance_{}_{}.json", timestamp, frame));
+
+    let result = std::fs::create_dir_all(&settings.output_dir)
+        .and_then(|_| std::fs::write(&path, json));
+
+    match result {
+        Ok(_) => {
+            info!("telemetry report written to {}", path.display());
+            path.display().to_string()
+        },
+        Err(err) => {
+            error!("failed to write telemetry report {}: {}", path.display(), err);
+            format!("error: {}", err)
+        }
+    }
+}
+
+// No file system on the web, the report end up in the console
+#[cfg(target_arch = "wasm32")]
+fn write_report(_settings: &TelemetrySettings, _frame: u32, json: &str) -> String {
+    info!("telemetry report:\n{}", json);
+    "console".into()
+}
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use utils::frame::SimulationConfig;
+
+use crate::{hud::{HudAnchor, HudSlot}, plugins::AppState};
+
+use super::{TelemetryRecorder, TelemetrySettings};
+
+
+#[derive(Component)]
+struct TelemetrySummaryText;
+
+#[derive(Resource, Default)]
+struct TelemetrySummaryState {
+    is_visible: bool,
+}
+
+
+fn setup_telemetry_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    commands.spawn((
+        TelemetrySummaryText,
+        Text::new(""),
+        TextFont {
+            font,
+            font_size: 14.0,
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        HudSlot::new(HudAnchor::TopCenter, 0),
+        Node {
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+fn toggle_telemetry_summary(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<TelemetrySummaryState>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        state.is_visible = !state.is_visible;
+    }
+}
+
+fn update_telemetry_summary(
+    settings: Res<TelemetrySettings>,
+    state: Res<TelemetrySummaryState>,
+    recorder: Res<TelemetryRecorder>,
+    simulation: Res<SimulationConfig>,
+    mut q_text: Query<(&mut Text, &mut Visibility), With<TelemetrySummaryText>>,
+) {
+    let Ok((mut text, mut visibility)) = q_text.get_single_mut() else {
+        return;
+    };
+
+    if !state.is_visible {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    if !settings.enabled {
+        text.0 = "Telemetry disabled, [F8] to enable".into();
+        return;
+    }
+
+    let report = &recorder.report;
+    let mut lines = vec![
+        format!("Balance telemetry ({:.0}s)", simulation.seconds(report.duration_frames)),
+        String::new(),
+        "Weapons:".to_string(),
+    ];
+    for (name, usage) in report.weapons.iter() {
+        lines.push(format!("  {:<24} shots {:>5}  kills {:>4}  headshots {:>4}", name, usage.shots, usage.kills, usage.headshot_kills));
+    }
+
+    lines.push("Enemies:".into());
+    for (name, stats) in report.archetypes.iter() {
+        let ttk = stats.average_time_to_kill_seconds(&simulation).map_or("-".to_string(), |s| format!("{:.1}s", s));
+        lines.push(format!("  {:<24} spawned {:>4}  kills {:>4}  ttk {}", name, stats.spawned, stats.kills, ttk));
+    }
+
+    lines.push(format!("Points: earned {}  spent {}  unspent {}", report.points.earned, report.points.spent, report.points.unspent));
+
+    if let Some(last_export) = &recorder.last_export {
+        lines.push(format!("Last export: {}", last_export));
+    } else {
+        lines.push("[F9] export report".into());
+    }
+
+    text.0 = lines.join("\n");
+}
+
+
+#[derive(Default)]
+pub struct TelemetryUIPlugin;
+
+impl Plugin for TelemetryUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TelemetrySummaryState>();
+        app.add_systems(OnEnter(AppState::InGame), setup_telemetry_ui);
+        app.add_systems(Update, (toggle_telemetry_summary, update_telemetry_summary).chain().run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,345 @@
+pub mod ui;
+
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+use ggrs::PlayerHandle;
+use utils::frame::SimulationConfig;
+
+use crate::{budget::{BudgetStats, SimulationBudget}, character::player::{input::{INPUT_INTERACTION, INPUT_MODIFIER}, jjrs::PeerConfig, Player}, collider::spatial::{ObstacleKind, SteeringObstacle}, equipment::{mag_limit, Backpack, EquipmentPickup}, frame::FrameCount, points::PlayerPoints, powerup::PowerUpPickup, rules::GameRules, weapons::{MagBulletConfig, Weapon, WeaponInventory, WeaponModesState, WeaponRarity, WeaponState}};
+
+// Co-op economy, a player drop a share of its points or an ammo pack for a teammate.
+// Modifier + interaction tapped drop an ammo pack, held drop the points. The pickup is
+// for the nearest teammate or for anyone but the one that dropped it, the drops share
+// the pickup cap of the budget and each player wait a cooldown between two drops.
+
+pub const TRADE_BUTTONS: u16 = INPUT_MODIFIER | INPUT_INTERACTION;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum TradeRecipient {
+    // The teammate closest to the player when it drop
+    Nearest,
+    // Any teammate
+    Anyone,
+}
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct TradeConfig {
+    pub recipient: TradeRecipient,
+    // Frames the buttons are held to drop the points instead of an ammo pack
+    pub hold_frames: u32,
+    // Part of the current points that is dropped
+    pub points_share_percent: u32,
+    // Smallest amount of points worth dropping
+    pub min_points: u32,
+    pub cooldown_frames: u32,
+    // Drops of a player waiting on the ground at once
+    pub max_drops_per_player: usize,
+    pub pickup_lifetime_frames: u32,
+    pub pickup_radius: f32,
+    // Part of the bullets of a magless weapon given by an ammo pack
+    pub magless_share_percent: u32,
+}
+
+impl Default for TradeConfig {
+    fn default() -> Self {
+        Self {
+            recipient: TradeRecipient::Nearest,
+            hold_frames: 30,
+            points_share_percent: 25,
+            min_points: 50,
+            cooldown_frames: 60 * 3,
+            max_drops_per_player: 2,
+            pickup_lifetime_frames: 60 * 30,
+            pickup_radius: 40.0,
+            magless_share_percent: 25,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeKind {
+    Points(u32),
+    // One mag for the active weapon of whoever collect it
+    Ammo,
+}
+
+impl TradeKind {
+    pub fn color(&self) -> Color {
+        match self {
+            TradeKind::Points(_) => Color::srgb(1.0, 0.75, 0.2),
+            TradeKind::Ammo => Color::srgb(0.5, 0.6, 0.3),
+        }
+    }
+
+    // Glow of the drop on the ground, the points get the beam to be found from afar
+    pub fn rarity(&self) -> WeaponRarity {
+        match self {
+            TradeKind::Points(_) => WeaponRarity::Rare,
+            TradeKind::Ammo => WeaponRarity::Common,
+        }
+    }
+}
+
+// Rollback state of the trade buttons of a player
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct TradeState {
+    // Frames the trade buttons have been held
+    pub hold_frames: u32,
+    pub next_drop_frame: u32,
+}
+
+impl TradeState {
+    // Tapped drop on release, held drop as soon as it is long enough
+    pub fn step(&mut self, pressed: bool, hold_frames: u32, points_share: u32) -> Option<TradeKind> {
+        if pressed {
+            self.hold_frames = self.hold_frames.saturating_add(1);
+            return (self.hold_frames == hold_frames).then_some(TradeKind::Points(points_share));
+        }
+        let tapped = self.hold_frames > 0 && self.hold_frames < hold_frames;
+        self.hold_frames = 0;
+        tapped.then_some(TradeKind::Ammo)
+    }
+}
+
+// Drop waiting on the ground for a teammate
+#[derive(Component, Clone, Debug)]
+pub struct TradePickup {
+    pub kind: TradeKind,
+    pub from: PlayerHandle,
+    // Only this player can collect it, anyone but the dropper when none
+    pub to: Option<PlayerHandle>,
+    pub despawn_at_frame: u32,
+}
+
+impl TradePickup {
+    pub fn can_collect(&self, handle: PlayerHandle) -> bool {
+        self.to.map_or(handle != self.from, |to| to == handle)
+    }
+}
+
+
+pub fn spawn_trade_pickup(commands: &mut Commands, position: Vec2, pickup: TradePickup) -> Entity {
+    commands.spawn((
+        Sprite::from_color(pickup.kind.color(), Vec2::new(16.0, 16.0)),
+        Transform::from_translation(position.extend(1.0)),
+        SteeringObstacle { kind: ObstacleKind::Pickup, radius: 8.0 },
+        pickup,
+    )).add_rollback().id()
+}
+
+// Closest other player, the lowest handle between two at the same distance
+fn nearest_teammate(positions: &[(PlayerHandle, Vec2)], handle: PlayerHandle, position: Vec2) -> Option<PlayerHandle> {
+    positions.iter()
+        .filter(|(other, _)| *other != handle)
+        .min_by(|(handle_a, a), (handle_b, b)| a.distance(position).total_cmp(&b.distance(position)).then(handle_a.cmp(handle_b)))
+        .map(|(other, _)| *other)
+}
+
+// Mode state of the active weapon of the inventory with its mag config
+fn active_mode<'a>(
+    inventory: &WeaponInventory,
+    weapon_query: &'a mut Query<(&Weapon, &WeaponState, &mut WeaponModesState)>,
+) -> Option<(MagBulletConfig, Mut<'a, WeaponModesState>, String)> {
+    let (weapon, weapon_state, modes_state) = inventory.active_weapon()?.0.get_mut(weapon_query)?;
+    let mag = weapon.config.firing_modes.get(&weapon_state.active_mode)?.mag.clone();
+    Some((mag, modes_state, weapon_state.active_mode.clone()))
+}
+
+
+// SYSTEMS
+
+// Must run before the previous inputs are stored
+pub fn rollback_trade_drops(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    inputs: Res<PlayerInputs<PeerConfig>>,
+    simulation: Res<SimulationConfig>,
+    config: Res<TradeConfig>,
+    rules: Res<GameRules>,
+    budget: Res<SimulationBudget>,
+    mut budget_stats: ResMut<BudgetStats>,
+    mut player_query: Query<(&Transform, &Player, &mut TradeState, &mut PlayerPoints, &WeaponInventory), With<Rollback>>,
+    mut weapon_query: Query<(&Weapon, &WeaponState, &mut WeaponModesState)>,
+    trade_query: Query<&TradePickup, With<Rollback>>,
+    other_pickup_query: Query<(), (Or<(With<PowerUpPickup>, With<EquipmentPickup>)>, With<Rollback>)>,
+) {
+    if !rules.allows_trading() {
+        return;
+    }
+
+    let hold_frames = simulation.frames(config.hold_frames);
+    let positions: Vec<(PlayerHandle, Vec2)> = player_query.iter().map(|(transform, player, ..)| (player.handle, transform.translation.truncate())).collect();
+    let mut drops: Vec<PlayerHandle> = trade_query.iter().map(|pickup| pickup.from).collect();
+    let mut pickups = drops.len() + other_pickup_query.iter().count();
+
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.handle);
+
+    for (transform, player, state, points, inventory) in players.iter_mut() {
+        let (input, _input_status) = inputs[player.handle];
+
+        let pressed = input.buttons & TRADE_BUTTONS == TRADE_BUTTONS;
+        let Some(kind) = state.step(pressed, hold_frames, points.current * config.points_share_percent / 100) else {
+            continue;
+        };
+
+        if frame.frame < state.next_drop_frame || drops.iter().filter(|from| **from == player.handle).count() >= config.max_drops_per_player {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let to = match config.recipient {
+            TradeRecipient::Anyone => None,
+            TradeRecipient::Nearest => match nearest_teammate(&positions, player.handle, position) {
+                Some(handle) => Some(handle),
+                None => continue,
+            },
+        };
+        if to.is_none() && positions.len() < 2 {
+            continue;
+        }
+        if !budget.has_room_for_pickup(pickups) {
+            budget_stats.skipped_drops += 1;
+            continue;
+        }
+
+        // Taken from the player only once the drop is sure to happen
+        match kind {
+            TradeKind::Points(amount) => {
+                if amount < config.min_points || !points.spend(amount) {
+                    continue;
+                }
+            }
+            TradeKind::Ammo => {
+                let Some((MagBulletConfig::Mag { .. }, mut modes_state, mode)) = active_mode(inventory, &mut weapon_query) else {
+                    continue;
+                };
+                let Some(mode_state) = modes_state.modes.get_mut(&mode).filter(|mode_state| mode_state.mag_quantity > 0) else {
+                    continue;
+                };
+                mode_state.mag_quantity -= 1;
+            }
+        }
+
+        spawn_trade_pickup(&mut commands, position, TradePickup {
+            kind,
+            from: player.handle,
+            to,
+            despawn_at_frame: frame.frame + simulation.frames(config.pickup_lifetime_frames),
+        });
+        state.next_drop_frame = frame.frame + simulation.frames(config.cooldown_frames);
+        drops.push(player.handle);
+        pickups += 1;
+        info!("Player {} dropped {:?} for {:?}", player.handle, kind, to);
+    }
+}
+
+pub fn rollback_collect_trades(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    config: Res<TradeConfig>,
+    pickup_query: Query<(Entity, &Transform, &TradePickup), With<Rollback>>,
+    mut player_query: Query<(&Transform, &Player, &mut PlayerPoints, &WeaponInventory, Option<&Backpack>), With<Rollback>>,
+    mut weapon_query: Query<(&Weapon, &WeaponState, &mut WeaponModesState)>,
+) {
+    let mut pickups: Vec<(Entity, &Transform, &TradePickup)> = pickup_query.iter().collect();
+    pickups.sort_by_key(|(entity, ..)| entity.index());
+
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, player, ..)| player.handle);
+
+    for (entity, transform, pickup) in pickups {
+        if frame.frame >= pickup.despawn_at_frame {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        for (player_transform, player, points, inventory, backpack) in players.iter_mut() {
+            if !pickup.can_collect(player.handle) || player_transform.translation.truncate().distance(position) >= config.pickup_radius {
+                continue;
+            }
+
+            let collected = match pickup.kind {
+                TradeKind::Points(amount) => {
+                    points.receive(amount);
+                    true
+                }
+                // Left on the ground when the ammo of the active weapon is full
+                TradeKind::Ammo => match active_mode(inventory, &mut weapon_query) {
+                    Some((MagBulletConfig::Mag { mag_limit: base, .. }, mut modes_state, mode)) => {
+                        let limit = mag_limit(base, backpack.as_deref());
+                        modes_state.modes.get_mut(&mode)
+                            .filter(|mode_state| mode_state.mag_quantity < limit)
+                            .map(|mode_state| mode_state.mag_quantity += 1)
+                            .is_some()
+                    }
+                    Some((MagBulletConfig::Magless { bullet_limit }, mut modes_state, mode)) => {
+                        let share = (bullet_limit * config.magless_share_percent / 100).max(1);
+                        modes_state.modes.get_mut(&mode)
+                            .filter(|mode_state| mode_state.mag_ammo < bullet_limit)
+                            .map(|mode_state| mode_state.mag_ammo = (mode_state.mag_ammo + share).min(bullet_limit))
+                            .is_some()
+                    }
+                    None => false,
+                },
+            };
+
+            if collected {
+                info!("Player {} collected {:?} from player {}", player.handle, pickup.kind, pickup.from);
+                commands.entity(entity).despawn_recursive();
+                break;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_points_get_the_beam_of_a_rare_pickup() {
+        assert_eq!(TradeKind::Points(100).rarity(), WeaponRarity::Rare);
+        assert_eq!(TradeKind::Ammo.rarity(), WeaponRarity::Common);
+    }
+
+    #[test]
+    fn test_tap_drop_ammo_on_release() {
+        let mut state = TradeState::default();
+        assert_eq!(state.step(true, 30, 100), None);
+        assert_eq!(state.step(true, 30, 100), None);
+        assert_eq!(state.step(false, 30, 100), Some(TradeKind::Ammo));
+        // Nothing held before
+        assert_eq!(state.step(false, 30, 100), None);
+    }
+
+    #[test]
+    fn test_hold_drop_points_once() {
+        let mut state = TradeState::default();
+        let drops: Vec<_> = (0..40).filter_map(|_| state.step(true, 30, 100)).collect();
+        assert_eq!(drops, vec![TradeKind::Points(100)]);
+        // Released after the hold, not a tap
+        assert_eq!(state.step(false, 30, 100), None);
+    }
+
+    #[test]
+    fn test_nearest_teammate_skip_the_dropper() {
+        let positions = [(0, Vec2::ZERO), (1, Vec2::new(50.0, 0.0)), (2, Vec2::new(-50.0, 0.0)), (3, Vec2::new(0.0, 80.0))];
+        // Same distance, the lowest handle
+        assert_eq!(nearest_teammate(&positions, 0, Vec2::ZERO), Some(1));
+        assert_eq!(nearest_teammate(&positions, 1, Vec2::new(50.0, 0.0)), Some(0));
+        assert_eq!(nearest_teammate(&positions[..1], 0, Vec2::ZERO), None);
+    }
+
+    #[test]
+    fn test_pickup_collected_by_its_recipient() {
+        let pickup = |to| TradePickup { kind: TradeKind::Ammo, from: 0, to, despawn_at_frame: 100 };
+        assert!(pickup(Some(2)).can_collect(2));
+        assert!(!pickup(Some(2)).can_collect(1));
+        assert!(pickup(None).can_collect(1));
+        assert!(!pickup(None).can_collect(0));
+    }
+}
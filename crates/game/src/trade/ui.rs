@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use utils::frame::SimulationConfig;
+
+use crate::{character::player::{LocalPlayer, Player}, hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState, rules::GameRules};
+
+use super::{TradeConfig, TradeKind, TradePickup, TradeState};
+
+
+#[derive(Component)]
+struct TradePromptText;
+
+
+fn setup_trade_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    commands.spawn((
+        TradePromptText,
+        Text::new(""),
+        TextFont {
+            font,
+            font_size: 16.0,
+            ..Default::default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        HudSlot::new(HudAnchor::BottomCenter, 5),
+    ));
+}
+
+fn update_trade_prompt(
+    rules: Res<GameRules>,
+    simulation: Res<SimulationConfig>,
+    config: Res<TradeConfig>,
+    localization: Res<Localization>,
+    q_player: Query<(&Player, &TradeState), With<LocalPlayer>>,
+    q_pickups: Query<&TradePickup>,
+    mut q_text: Query<&mut Text, With<TradePromptText>>,
+) {
+    let Ok(mut text) = q_text.get_single_mut() else {
+        return;
+    };
+
+    let Some((player, state)) = q_player.get_single().ok().filter(|_| rules.allows_trading()) else {
+        text.0 = String::new();
+        return;
+    };
+
+    let waiting: Vec<String> = q_pickups.iter()
+        .filter(|pickup| pickup.can_collect(player.handle))
+        .map(|pickup| match pickup.kind {
+            TradeKind::Points(amount) => localization.format("trade.points", &[("amount", &amount)]),
+            TradeKind::Ammo => localization.text("trade.ammo"),
+        })
+        .collect();
+
+    let mut lines = vec![];
+    let hold_frames = simulation.frames(config.hold_frames);
+    if state.hold_frames > 0 && state.hold_frames < hold_frames {
+        let filled = (state.hold_frames * 10 / hold_frames.max(1)) as usize;
+        let bar = format!("{}{}", "#".repeat(filled), "-".repeat(10 - filled));
+        lines.push(localization.format("trade.share", &[("bar", &bar)]));
+    }
+    if !waiting.is_empty() {
+        lines.push(localization.format("trade.dropped", &[("pickups", &waiting.join(", "))]));
+    }
+    text.0 = lines.join("\n");
+}
+
+
+#[derive(Default)]
+pub struct TradeUIPlugin;
+
+impl Plugin for TradeUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_trade_ui);
+        app.add_systems(Update, update_trade_prompt.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,158 @@
+pub mod ui;
+
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use utils::events::RollbackEvents;
+
+use crate::{barricade::{spawn_barricade, Barricade}, character::{dash::DashState, player::Player}, collider::{spawn_test_wall, CollisionSettings}, frame::FrameCount, points::PlayerPoints, rules::{GameMode, GameRules}, weapons::{wall::{spawn_wall_weapon, WallWeapon}, WeaponFiredEvent, WeaponInventory, WeaponState}};
+
+// Scripted tutorial, played offline on its own small map. A state machine in the rollback
+// schedule check the state of the first player and move to the next step once the goal
+// of the current one is reached, the UI only show the prompt of the current step.
+
+const MOVE_DISTANCE: f32 = 200.0;
+const SHOTS_TO_FIRE: u32 = 10;
+const TUTORIAL_WALL_WEAPON: &str = "pistol_upgraded";
+const TUTORIAL_WALL_WEAPON_COST: u32 = 500;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum TutorialStep {
+    #[default]
+    Move,
+    Dash,
+    Shoot,
+    Reload,
+    SwitchMode,
+    BuyWeapon,
+    RepairBarricade,
+    Done,
+}
+
+impl TutorialStep {
+    pub fn next(&self) -> Self {
+        match self {
+            TutorialStep::Move => TutorialStep::Dash,
+            TutorialStep::Dash => TutorialStep::Shoot,
+            TutorialStep::Shoot => TutorialStep::Reload,
+            TutorialStep::Reload => TutorialStep::SwitchMode,
+            TutorialStep::SwitchMode => TutorialStep::BuyWeapon,
+            TutorialStep::BuyWeapon => TutorialStep::RepairBarricade,
+            TutorialStep::RepairBarricade => TutorialStep::Done,
+            TutorialStep::Done => TutorialStep::Done,
+        }
+    }
+
+    // Key of the prompt in the language files
+    pub fn prompt_key(&self) -> &'static str {
+        match self {
+            TutorialStep::Move => "tutorial.move",
+            TutorialStep::Dash => "tutorial.dash",
+            TutorialStep::Shoot => "tutorial.shoot",
+            TutorialStep::Reload => "tutorial.reload",
+            TutorialStep::SwitchMode => "tutorial.switch_mode",
+            TutorialStep::BuyWeapon => "tutorial.buy_weapon",
+            TutorialStep::RepairBarricade => "tutorial.repair_barricade",
+            TutorialStep::Done => "tutorial.done",
+        }
+    }
+
+    // Amount to reach for the steps that count something
+    pub fn goal(&self) -> Option<u32> {
+        match self {
+            TutorialStep::Move => Some(MOVE_DISTANCE as u32),
+            TutorialStep::Shoot => Some(SHOTS_TO_FIRE),
+            _ => None,
+        }
+    }
+}
+
+// Rollback resource, the steps are replayed with the frames
+#[derive(Resource, Clone, Debug, Default, Reflect)]
+pub struct TutorialState {
+    pub step: TutorialStep,
+    pub progress: u32,
+    // Taken when the step start, the goals are relative to it
+    entered: bool,
+    start_position: Vec2,
+    start_mode: String,
+}
+
+pub fn spawn_tutorial_map(
+    commands: &mut Commands,
+    collision_settings: &Res<CollisionSettings>,
+) {
+    // A closed room, the barricade is the only way out
+    let color = Color::srgb(0.4, 0.4, 0.45);
+    spawn_test_wall(commands, Vec3::new(0.0, 500.0, 0.0), Vec2::new(1200.0, 50.0), collision_settings, color);
+    spawn_test_wall(commands, Vec3::new(-600.0, 0.0, 0.0), Vec2::new(50.0, 1050.0), collision_settings, color);
+    spawn_test_wall(commands, Vec3::new(600.0, 0.0, 0.0), Vec2::new(50.0, 1050.0), collision_settings, color);
+    spawn_test_wall(commands, Vec3::new(-350.0, -500.0, 0.0), Vec2::new(500.0, 50.0), collision_settings, color);
+    spawn_test_wall(commands, Vec3::new(350.0, -500.0, 0.0), Vec2::new(500.0, 50.0), collision_settings, color);
+
+    spawn_wall_weapon(commands, Vec3::new(0.0, 460.0, 0.0), TUTORIAL_WALL_WEAPON, TUTORIAL_WALL_WEAPON_COST);
+
+    let mut barricade = Barricade::new(Vec2::new(200.0, 30.0), 100.0);
+    barricade.health = 0.0;
+    spawn_barricade(commands, Vec3::new(0.0, -500.0, 0.0), barricade, collision_settings);
+}
+
+pub fn rollback_tutorial_system(
+    frame: Res<FrameCount>,
+    rules: Res<GameRules>,
+    mut tutorial: ResMut<TutorialState>,
+    fired_events: Res<RollbackEvents<WeaponFiredEvent>>,
+    mut player_query: Query<(&Transform, &Player, &DashState, &WeaponInventory, &mut PlayerPoints), With<Rollback>>,
+    weapon_query: Query<&WeaponState>,
+    wall_query: Query<&WallWeapon>,
+    barricade_query: Query<&Barricade>,
+) {
+    if rules.mode != GameMode::Tutorial || tutorial.step == TutorialStep::Done {
+        return;
+    }
+
+    // The tutorial follow the first player
+    let Some((transform, player, dash_state, inventory, mut points)) = player_query.iter_mut().min_by_key(|(_, player, ..)| player.handle) else {
+        return;
+    };
+    if inventory.weapons.is_empty() {
+        return;
+    }
+    let position = transform.translation.truncate();
+    let active_mode = inventory.active_weapon().and_then(|(link, _)| link.get(&weapon_query)).map_or(String::new(), |state| state.active_mode.clone());
+
+    if !tutorial.entered {
+        tutorial.entered = true;
+        tutorial.start_position = position;
+        tutorial.start_mode = active_mode.clone();
+        // Enough points for the weapon on the wall
+        if tutorial.step == TutorialStep::BuyWeapon {
+            let cost = wall_query.iter().map(|wall| wall.cost).max().unwrap_or_default();
+            if points.current < cost {
+                points.add(cost - points.current);
+            }
+        }
+    }
+
+    let done = match tutorial.step {
+        TutorialStep::Move => {
+            tutorial.progress = position.distance(tutorial.start_position) as u32;
+            tutorial.progress >= MOVE_DISTANCE as u32
+        }
+        TutorialStep::Dash => dash_state.is_dashing(),
+        TutorialStep::Shoot => {
+            tutorial.progress += fired_events.read(frame.frame).filter(|event| event.player_handle == player.handle).count() as u32;
+            tutorial.progress >= SHOTS_TO_FIRE
+        }
+        TutorialStep::Reload => inventory.is_reloading(),
+        TutorialStep::SwitchMode => active_mode != tutorial.start_mode,
+        TutorialStep::BuyWeapon => wall_query.iter()
+            .any(|wall| inventory.weapons.iter().any(|(_, weapon)| weapon.config.name == wall.weapon)),
+        TutorialStep::RepairBarricade => barricade_query.iter().all(|barricade| barricade.is_repaired()),
+        TutorialStep::Done => false,
+    };
+
+    if done {
+        info!("tutorial step {:?} done", tutorial.step);
+        *tutorial = TutorialState { step: tutorial.step.next(), ..default() };
+    }
+}
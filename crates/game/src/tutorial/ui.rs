@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::{hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState, rules::{GameMode, GameRules}};
+
+use super::{TutorialState, TutorialStep};
+
+
+#[derive(Component)]
+struct TutorialPromptText;
+
+
+fn setup_tutorial_ui(mut commands: Commands, asset_server: Res<AssetServer>, rules: Res<GameRules>) {
+    if rules.mode != GameMode::Tutorial {
+        return;
+    }
+
+    commands.spawn((
+        TutorialPromptText,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+            font_size: 22.0,
+            ..Default::default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.6)),
+        HudSlot::new(HudAnchor::TopCenter, 1),
+        TextLayout::new_with_justify(JustifyText::Center),
+    ));
+}
+
+fn update_tutorial_ui(
+    tutorial: Res<TutorialState>,
+    localization: Res<Localization>,
+    mut q_text: Query<&mut Text, With<TutorialPromptText>>,
+) {
+    let Ok(mut text) = q_text.get_single_mut() else {
+        return;
+    };
+
+    let step = tutorial.step;
+    let prompt = localization.text(step.prompt_key());
+    let prompt = match step.goal() {
+        Some(goal) => localization.format("tutorial.progress", &[("prompt", &prompt), ("progress", &tutorial.progress.min(goal)), ("goal", &goal)]),
+        None => prompt,
+    };
+    text.0 = if step == TutorialStep::Done {
+        prompt
+    } else {
+        localization.format("tutorial.step", &[("step", &(step as u32 + 1)), ("steps", &(TutorialStep::Done as u32)), ("prompt", &prompt)])
+    };
+}
+
+
+#[derive(Default)]
+pub struct TutorialUIPlugin;
+
+impl Plugin for TutorialUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), setup_tutorial_ui);
+        app.add_systems(Update, update_tutorial_ui.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_ggrs::ConfirmedFrameCount;
+use utils::{events::RollbackEvents, sweep::NORMAL_SCALE};
+
+use crate::{frame::{confirmed_frame, FrameCount}, hud::graphics::GraphicsPreferences, plugins::AppState};
+
+use super::{explosion::ExplosionEvent, BulletImpactEvent};
+
+// Marks of the past fights, presentation only. The bullets hitting a wall leave a hole
+// and the explosions a scorch on the ground, read from the confirmed impacts so a
+// mispredicted shot leave nothing. Each kind is a pool of a fixed size, capped by the
+// graphics options, once full the oldest mark is moved to the new impact.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct DecalSettings {
+    pub enabled: bool,
+    pub max_bullet_marks: usize,
+    pub max_scorches: usize,
+    pub bullet_mark_size: f32,
+    // Size of the scorch relative to the blast radius
+    pub scorch_scale: f32,
+    // Under the characters, the scorches under the hazards
+    pub bullet_mark_z: f32,
+    pub scorch_z: f32,
+}
+
+impl Default for DecalSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bullet_marks: 200,
+            max_scorches: 30,
+            bullet_mark_size: 8.0,
+            scorch_scale: 1.2,
+            bullet_mark_z: -0.9,
+            scorch_z: -1.5,
+        }
+    }
+}
+
+#[derive(Component)]
+struct ImpactDecal;
+
+// Marks in the order they were made, the front is the oldest
+#[derive(Resource, Default)]
+struct DecalPool {
+    bullet_marks: VecDeque<Entity>,
+    scorches: VecDeque<Entity>,
+    last_read_frame: Option<u32>,
+}
+
+// New mark, or the oldest one of the pool moved there
+fn place_decal(commands: &mut Commands, pool: &mut VecDeque<Entity>, max: usize, sprite: Sprite, transform: Transform) {
+    if max == 0 {
+        return;
+    }
+    while pool.len() > max {
+        if let Some(entity) = pool.pop_front() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let entity = if pool.len() == max {
+        let entity = pool.pop_front().unwrap();
+        commands.entity(entity).insert((sprite, transform));
+        entity
+    } else {
+        commands.spawn((ImpactDecal, sprite, transform)).id()
+    };
+    pool.push_back(entity);
+}
+
+
+// SYSTEMS
+
+fn spawn_confirmed_decals(
+    mut commands: Commands,
+    settings: Res<DecalSettings>,
+    preferences: Option<Res<GraphicsPreferences>>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    impact_events: Res<RollbackEvents<BulletImpactEvent>>,
+    explosion_events: Res<RollbackEvents<ExplosionEvent>>,
+    mut pool: ResMut<DecalPool>,
+) {
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+    if pool.last_read_frame.map_or(false, |last| last >= confirmed_frame) {
+        return;
+    }
+
+    if settings.enabled {
+        let max_decals = preferences.map_or(usize::MAX, |preferences| preferences.max_decals);
+        let pool = pool.as_mut();
+        for (_, event) in impact_events.read_after(pool.last_read_frame).filter(|(f, _)| *f <= confirmed_frame) {
+            // Along the wall, a hit without normal is a round hole
+            let normal = event.normal.as_vec2() / NORMAL_SCALE as f32;
+            let rotation = if normal == Vec2::ZERO { Quat::IDENTITY } else { Quat::from_rotation_z(normal.to_angle()) };
+            let size = settings.bullet_mark_size;
+            place_decal(
+                &mut commands,
+                &mut pool.bullet_marks,
+                settings.max_bullet_marks.min(max_decals),
+                Sprite::from_color(Color::srgba(0.08, 0.07, 0.06, 0.8), Vec2::new(size * 0.6, size)),
+                Transform::from_translation(event.position.extend(settings.bullet_mark_z)).with_rotation(rotation),
+            );
+        }
+        for (_, event) in explosion_events.read_after(pool.last_read_frame).filter(|(f, _)| *f <= confirmed_frame) {
+            let size = event.radius * settings.scorch_scale;
+            place_decal(
+                &mut commands,
+                &mut pool.scorches,
+                settings.max_scorches.min(max_decals),
+                Sprite::from_color(Color::srgba(0.05, 0.04, 0.03, 0.45), Vec2::splat(size)),
+                // Turned a bit so the scorches don't line up
+                Transform::from_translation(event.position.extend(settings.scorch_z))
+                    .with_rotation(Quat::from_rotation_z(event.position.x + event.position.y)),
+            );
+        }
+    }
+
+    pool.last_read_frame = Some(confirmed_frame);
+}
+
+// The marks belong to the match, none are kept for the next one
+fn clear_decals(
+    mut commands: Commands,
+    mut pool: ResMut<DecalPool>,
+) {
+    let pool = pool.as_mut();
+    for entity in pool.bullet_marks.drain(..).chain(pool.scorches.drain(..)) {
+        commands.entity(entity).despawn_recursive();
+    }
+    pool.last_read_frame = None;
+}
+
+
+pub struct ImpactDecalPlugin;
+
+impl Plugin for ImpactDecalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DecalSettings>()
+            .register_type::<DecalSettings>()
+            .init_resource::<DecalPool>()
+            .add_systems(Update, spawn_confirmed_decals.run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), clear_decals);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Impacts placed in one update and the size of the pool
+    #[derive(Resource)]
+    struct Impacts(Vec<Vec2>, usize);
+
+    fn place_impacts(mut commands: Commands, impacts: Res<Impacts>, mut pool: ResMut<DecalPool>) {
+        for position in impacts.0.iter() {
+            place_decal(&mut commands, &mut pool.bullet_marks, impacts.1, Sprite::default(), Transform::from_translation(position.extend(0.0)));
+        }
+    }
+
+    fn app(impacts: Impacts) -> App {
+        let mut app = App::new();
+        app.init_resource::<DecalPool>();
+        app.insert_resource(impacts);
+        app.add_systems(Update, place_impacts);
+        app
+    }
+
+    fn positions(app: &App) -> Vec<Vec2> {
+        let world = app.world();
+        world.resource::<DecalPool>().bullet_marks.iter()
+            .map(|entity| world.get::<Transform>(*entity).unwrap().translation.truncate())
+            .collect()
+    }
+
+    fn decal_count(app: &mut App) -> usize {
+        app.world_mut().query::<&ImpactDecal>().iter(app.world()).count()
+    }
+
+    #[test]
+    fn test_full_pool_move_the_oldest_mark() {
+        let impacts: Vec<Vec2> = (0..5).map(|index| Vec2::new(index as f32, 0.0)).collect();
+        let mut app = app(Impacts(impacts.clone(), 3));
+        app.update();
+
+        assert_eq!(positions(&app), impacts[2..].to_vec());
+        assert_eq!(decal_count(&mut app), 3);
+    }
+
+    #[test]
+    fn test_smaller_cap_despawn_the_extra_marks() {
+        let mut app = app(Impacts(vec![Vec2::ZERO, Vec2::X, Vec2::Y], 3));
+        app.update();
+        app.world_mut().insert_resource(Impacts(vec![Vec2::ONE], 1));
+        app.update();
+
+        assert_eq!(positions(&app), vec![Vec2::ONE]);
+        assert_eq!(decal_count(&mut app), 1);
+    }
+
+    #[test]
+    fn test_no_mark_without_room() {
+        let mut app = app(Impacts(vec![Vec2::ZERO, Vec2::X], 0));
+        app.update();
+
+        assert!(positions(&app).is_empty());
+        assert_eq!(decal_count(&mut app), 0);
+    }
+}
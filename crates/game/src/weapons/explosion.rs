@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+use serde::{Deserialize, Serialize};
+use utils::events::RollbackEvents;
+
+use crate::{character::health::{accumulate_damage, DamageAccumulator, Health, HitBy}, frame::FrameCount};
+
+
+// How long the explosion stay visible after the damage was applied
+const EXPLOSION_VISIBLE_FRAMES: u32 = 12;
+
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct ExplosionMarker {
+    pub radius: f32,
+    pub damage: f32,
+    pub source: Option<HitBy>,
+    pub processed: bool, // Flag to ensure one-time processing
+    pub despawn_at_frame: u32,
+}
+
+// Sent on the rollback events when the damage of an explosion are applied
+#[derive(Clone, Debug)]
+pub struct ExplosionEvent {
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+
+// Request an explosion, the damage are applied by `rollback_process_explosions`
+pub fn spawn_explosion(
+    commands: &mut Commands,
+    position: Vec2,
+    radius: f32,
+    damage: f32,
+    source: Option<HitBy>,
+    current_frame: u32,
+) -> Entity {
+    commands.spawn((
+        Sprite::from_color(Color::srgba(1.0, 0.5, 0.1, 0.4), Vec2::splat(radius * 2.0)),
+        Transform::from_translation(position.extend(5.0)),
+        ExplosionMarker {
+            radius,
+            damage,
+            source,
+            processed: false,
+            despawn_at_frame: current_frame + EXPLOSION_VISIBLE_FRAMES,
+        },
+    )).add_rollback().id()
+}
+
+
+pub fn rollback_process_explosions(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    mut explosion_events: ResMut<RollbackEvents<ExplosionEvent>>,
+    mut explosion_query: Query<(Entity, &Transform, &mut ExplosionMarker), With<Rollback>>,
+    mut target_query: Query<(Entity, &Transform, Option<&mut DamageAccumulator>), (With<Health>, With<Rollback>, Without<ExplosionMarker>)>,
+) {
+    let mut explosions: Vec<_> = explosion_query.iter_mut().collect();
+    explosions.sort_by_key(|(entity, ..)| entity.index());
+
+    for (entity, transform, explosion) in explosions.iter_mut() {
+        if frame.frame >= explosion.despawn_at_frame {
+            commands.entity(*entity).despawn();
+            continue;
+        }
+
+        if explosion.processed {
+            continue;
+        }
+        explosion.processed = true;
+
+        let center = transform.translation.truncate();
+        explosion_events.send(frame.frame, ExplosionEvent { position: center, radius: explosion.radius });
+        for (target_entity, target_transform, opt_accumulator) in target_query.iter_mut() {
+            if target_transform.translation.truncate().distance(center) > explosion.radius {
+                continue;
+            }
+            accumulate_damage(&mut commands, target_entity, opt_accumulator, explosion.damage, explosion.source.clone());
+        }
+    }
+}
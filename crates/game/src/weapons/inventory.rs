@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::{EguiContexts, EguiPlugin}, egui};
+
+use crate::{character::player::{input::MAX_SELECTABLE_SLOTS, LocalPlayer}, hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState};
+
+use super::{melee::MeleeState, wheel::{WeaponWheelState, WheelAction}, Weapon, WeaponInventory, WeaponModesState, WeaponState};
+
+// Slots of the inventory in the HUD, the icon is the sprite of the weapon with a bar of
+// the ammo left in its magazine. The menu reorder the slots by dragging one on another,
+// the move is only sent in the inputs like the wheel so the indexes stay the same for
+// every peer, the HUD follow when the rollback apply it.
+
+pub const INVENTORY_KEY: KeyCode = KeyCode::KeyX;
+
+const SLOT_SIZE: f32 = 48.0;
+const AMMO_BAR_HEIGHT: f32 = 4.0;
+
+const ACTIVE_BORDER_COLOR: Color = Color::srgb(0.9, 0.7, 0.1);
+const SLOT_BORDER_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.2);
+const EMPTY_AMMO_COLOR: Color = Color::srgb(0.9, 0.2, 0.1);
+
+#[derive(Component)]
+struct InventorySlot(usize);
+
+#[derive(Component)]
+struct InventorySlotIcon(usize);
+
+#[derive(Component)]
+struct InventorySlotAmmo(usize);
+
+#[derive(Resource, Default)]
+struct InventoryMenuState {
+    is_open: bool,
+}
+
+// Filled part of the magazine, melee weapons are always full
+fn ammo_ratio(weapon_query: &Query<(&WeaponState, &WeaponModesState, Option<&MeleeState>, &Children), With<Weapon>>, entity: Entity) -> f32 {
+    let Ok((state, modes_state, opt_melee, _)) = weapon_query.get(entity) else {
+        return 0.0;
+    };
+    if opt_melee.is_some() {
+        return 1.0;
+    }
+    modes_state.modes.get(&state.active_mode)
+        .filter(|mode_state| mode_state.mag_size > 0)
+        .map_or(1.0, |mode_state| (mode_state.mag_ammo as f32 / mode_state.mag_size as f32).clamp(0.0, 1.0))
+}
+
+
+// SYSTEMS
+
+fn setup_inventory_ui(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(6.0),
+            ..default()
+        },
+        HudSlot::new(HudAnchor::BottomCenter, 6),
+    )).with_children(|row| {
+        for slot in 0..MAX_SELECTABLE_SLOTS {
+            row.spawn((
+                InventorySlot(slot),
+                Node {
+                    width: Val::Px(SLOT_SIZE),
+                    height: Val::Px(SLOT_SIZE),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceBetween,
+                    border: UiRect::all(Val::Px(2.0)),
+                    padding: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                BorderColor(SLOT_BORDER_COLOR),
+                BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                Visibility::Hidden,
+            )).with_children(|parent| {
+                parent.spawn((
+                    InventorySlotIcon(slot),
+                    Node {
+                        width: Val::Percent(100.0),
+                        flex_grow: 1.0,
+                        ..default()
+                    },
+                    ImageNode::default(),
+                ));
+                parent.spawn((
+                    InventorySlotAmmo(slot),
+                    Node {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(AMMO_BAR_HEIGHT),
+                        ..default()
+                    },
+                    BackgroundColor(Color::WHITE),
+                ));
+            });
+        }
+    });
+}
+
+fn update_inventory_slots(
+    q_player: Query<&WeaponInventory, With<LocalPlayer>>,
+    weapon_query: Query<(&WeaponState, &WeaponModesState, Option<&MeleeState>, &Children), With<Weapon>>,
+    sprite_query: Query<&Sprite>,
+    mut q_slots: Query<(&InventorySlot, &mut Visibility, &mut BorderColor)>,
+    mut q_icons: Query<(&InventorySlotIcon, &mut ImageNode)>,
+    mut q_ammo: Query<(&InventorySlotAmmo, &mut Node, &mut BackgroundColor)>,
+) {
+    let inventory = q_player.get_single().ok();
+    let weapon_at = |slot: usize| inventory.and_then(|inventory| inventory.weapons.get(slot));
+
+    for (slot, mut visibility, mut border) in q_slots.iter_mut() {
+        let Some(inventory) = inventory.filter(|inventory| slot.0 < inventory.weapons.len()) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+        border.0 = if inventory.active_weapon_index == slot.0 { ACTIVE_BORDER_COLOR } else { SLOT_BORDER_COLOR };
+    }
+
+    // The sprite of the weapon entity, the frame of its sheet included
+    for (icon, mut image) in q_icons.iter_mut() {
+        let sprite = weapon_at(icon.0)
+            .and_then(|(link, _)| link.get(&weapon_query))
+            .and_then(|(_, _, _, children)| children.iter().find_map(|child| sprite_query.get(*child).ok()));
+        let Some(sprite) = sprite else {
+            continue;
+        };
+        let frame = |atlas: &Option<TextureAtlas>| atlas.as_ref().map(|atlas| (atlas.layout.id(), atlas.index));
+        if image.image != sprite.image || frame(&image.texture_atlas) != frame(&sprite.texture_atlas) {
+            image.image = sprite.image.clone();
+            image.texture_atlas = sprite.texture_atlas.clone();
+        }
+    }
+
+    for (ammo, mut node, mut color) in q_ammo.iter_mut() {
+        let ratio = weapon_at(ammo.0).map_or(0.0, |(link, _)| ammo_ratio(&weapon_query, link.entity()));
+        node.width = Val::Percent(ratio * 100.0);
+        color.0 = EMPTY_AMMO_COLOR.mix(&Color::WHITE, ratio);
+    }
+}
+
+fn toggle_inventory_menu(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut state: ResMut<InventoryMenuState>,
+) {
+    if keyboard_input.just_pressed(INVENTORY_KEY) {
+        state.is_open = !state.is_open;
+    }
+}
+
+fn inventory_menu_panel(
+    mut contexts: EguiContexts,
+    mut state: ResMut<InventoryMenuState>,
+    mut wheel_state: ResMut<WeaponWheelState>,
+    localization: Res<Localization>,
+    q_player: Query<&WeaponInventory, With<LocalPlayer>>,
+) {
+    if !state.is_open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let Ok(inventory) = q_player.get_single() else {
+        return;
+    };
+
+    let mut is_open = state.is_open;
+    let mut moved = None;
+    egui::Window::new(localization.text("menu.inventory")).id(egui::Id::new("inventory")).open(&mut is_open).default_pos((10.0, 200.0)).show(ctx, |ui| {
+        ui.label(localization.text("menu.inventory_help"));
+        ui.separator();
+        for (slot, (_, weapon)) in inventory.weapons.iter().enumerate().take(MAX_SELECTABLE_SLOTS) {
+            let key = if inventory.active_weapon_index == slot { "menu.inventory_active_slot" } else { "menu.inventory_slot" };
+            let label = localization.format(key, &[("slot", &(slot + 1)), ("weapon", &weapon.config.name)]);
+            let response = ui.dnd_drag_source(egui::Id::new(("inventory_slot", slot)), slot, |ui| {
+                ui.label(label);
+            }).response;
+            if let Some(from) = response.dnd_release_payload::<usize>() {
+                if *from != slot {
+                    moved = Some(WheelAction::Move { from: *from, to: slot });
+                }
+            }
+        }
+    });
+    state.is_open = is_open;
+
+    if moved.is_some() {
+        wheel_state.pending = moved;
+    }
+}
+
+
+#[derive(Default)]
+pub struct InventoryUIPlugin;
+
+impl Plugin for InventoryUIPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.init_resource::<InventoryMenuState>();
+        app.add_systems(OnEnter(AppState::InGame), setup_inventory_ui);
+        app.add_systems(Update, (
+            update_inventory_slots,
+            (toggle_inventory_menu, inventory_menu_panel).chain(),
+        ).run_if(in_state(AppState::InGame)));
+    }
+}
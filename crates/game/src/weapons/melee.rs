@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::math::round;
+
+use crate::collider::{sweep_collision_hit, Collider, ColliderShape};
+
+// Melee weapons swing an arc in front of the player instead of firing bullets. The
+// trigger and the firing rate of the mode still pace the swings, the swing following
+// the first one of a combo come faster when it land in the combo window, and a weapon
+// with a durability break after that many swings that hit something. A wall between
+// the player and a target stop the swing from reaching it.
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MeleeConfig {
+    pub damage: f32,
+    // Full angle of the swing centered on the aim, in radians
+    pub arc: f32,
+    pub reach: f32,
+    // Targets hit by one swing, the closest first
+    pub max_targets: usize,
+    // Frames after a swing where the next one is the second of the combo
+    pub combo_window_frames: u32,
+    // Firing rate multiplier between the first and the second swing of a combo
+    pub combo_rate_multiplier: f32,
+    // Swings that hit before the weapon break, never break when None
+    #[serde(default)]
+    pub durability: Option<u32>,
+}
+
+// Rollback state of a melee weapon, on the weapon entity
+#[derive(Component, Reflect, Default, Clone, Debug)]
+pub struct MeleeState {
+    pub last_swing_frame: u32,
+    // 1 when the next swing in the window is the second of a combo
+    pub combo_step: u32,
+    pub durability: Option<u32>,
+}
+
+impl MeleeState {
+    pub fn new(config: &MeleeConfig) -> Self {
+        Self { last_swing_frame: 0, combo_step: 0, durability: config.durability }
+    }
+
+    // A swing on this frame would be the second of a combo
+    pub fn is_combo(&self, config: &MeleeConfig, current_frame: u32) -> bool {
+        self.combo_step == 1 && current_frame.saturating_sub(self.last_swing_frame) <= config.combo_window_frames
+    }
+
+    // Frames before the next swing if one start on this frame, shorter after the first swing of a combo
+    pub fn frames_per_swing(&self, config: &MeleeConfig, current_frame: u32, frames_per_shot: u32) -> u32 {
+        if self.is_combo(config, current_frame) {
+            frames_per_shot
+        } else {
+            ((frames_per_shot as f32 / config.combo_rate_multiplier.max(1.0)) as u32).max(1)
+        }
+    }
+
+    // Record a swing, true when it was the second of a combo
+    pub fn swing(&mut self, config: &MeleeConfig, current_frame: u32) -> bool {
+        let combo = self.is_combo(config, current_frame);
+        self.combo_step = if combo { 0 } else { 1 };
+        self.last_swing_frame = current_frame;
+        combo
+    }
+
+    pub fn wear(&mut self) {
+        if let Some(durability) = self.durability.as_mut() {
+            *durability = durability.saturating_sub(1);
+        }
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.durability == Some(0)
+    }
+}
+
+// Whether a target at `offset` from the player is inside the swing around the `aim` direction
+pub fn in_swing(config: &MeleeConfig, aim: Vec2, offset: Vec2) -> bool {
+    let distance = round(offset.length());
+    if distance > config.reach {
+        return false;
+    }
+    // Standing on the player, always hit
+    if distance < 1.0 {
+        return true;
+    }
+    round(aim.dot(offset / distance)) >= round((config.arc / 2.0).cos())
+}
+
+// A wall on the line from the player to the target, tested in fixed point like the bullets
+pub fn blocked_by_wall(origin: Vec2, target: Vec2, walls: &[(&Transform, &Collider)]) -> bool {
+    let ray = Collider { shape: ColliderShape::Circle { radius: 0.0 }, offset: Vec2::ZERO };
+    walls.iter().any(|(transform, collider)| sweep_collision_hit(origin, target, &ray, transform, collider).is_some())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MeleeConfig {
+        MeleeConfig { damage: 50.0, arc: std::f32::consts::FRAC_PI_2, reach: 60.0, max_targets: 2, combo_window_frames: 20, combo_rate_multiplier: 2.0, durability: Some(2) }
+    }
+
+    #[test]
+    fn test_in_swing_inside_the_arc_and_reach() {
+        let config = config();
+        assert!(in_swing(&config, Vec2::X, Vec2::new(50.0, 0.0)));
+        assert!(in_swing(&config, Vec2::X, Vec2::new(30.0, 25.0)));
+        // Too far, behind or outside of the arc
+        assert!(!in_swing(&config, Vec2::X, Vec2::new(70.0, 0.0)));
+        assert!(!in_swing(&config, Vec2::X, Vec2::new(-30.0, 0.0)));
+        assert!(!in_swing(&config, Vec2::X, Vec2::new(10.0, 40.0)));
+    }
+
+    #[test]
+    fn test_wall_between_stop_the_swing() {
+        let transform = Transform::from_xyz(25.0, 0.0, 0.0);
+        let collider = Collider { shape: ColliderShape::Rectangle { width: 10.0, height: 100.0 }, offset: Vec2::ZERO };
+        let walls = [(&transform, &collider)];
+        assert!(blocked_by_wall(Vec2::ZERO, Vec2::new(50.0, 0.0), &walls));
+        // Same side as the player
+        assert!(!blocked_by_wall(Vec2::ZERO, Vec2::new(15.0, 0.0), &walls));
+        assert!(!blocked_by_wall(Vec2::ZERO, Vec2::new(50.0, 0.0), &[]));
+    }
+
+    #[test]
+    fn test_combo_and_durability() {
+        let config = config();
+        let mut state = MeleeState::new(&config);
+        assert!(!state.swing(&config, 10));
+        assert!(state.swing(&config, 25));
+        // Out of the window the next one start a new combo
+        assert!(!state.swing(&config, 60));
+
+        state.wear();
+        assert!(!state.is_broken());
+        state.wear();
+        assert!(state.is_broken());
+    }
+}
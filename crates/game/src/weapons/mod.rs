@@ -1,13 +1,29 @@
+pub mod decal;
+pub mod explosion;
+pub mod inventory;
+pub mod melee;
+pub mod near_miss;
+pub mod pickup;
+pub mod sway;
+pub mod trail;
 pub mod ui;
+pub mod upgrade;
+pub mod trigger;
+pub mod wall;
+pub mod wheel;
 
-use animation::{create_child_sprite, AnimationBundle, FacingDirection, SpriteSheetConfig};
-use bevy::{math::VectorSpace, prelude::*, utils::{HashMap, HashSet}};
+use animation::{create_child_sprite, AnimationBundle, AnimationMapConfig, CharacterAnimationHandles, FacingDirection, SpriteSheetConfig};
+use bevy::{ecs::entity::{EntityMapper, MapEntities}, math::VectorSpace, prelude::*, utils::{HashMap, HashSet}};
 use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
 use ggrs::PlayerHandle;
 use serde::{Deserialize, Serialize};
-use utils::{bmap, math::{round, round_vec3}, rng::RollbackRng};
+use utils::{bmap, events::RollbackEvents, frame::{FrameTimer, SimulationConfig}, math::{round, round_vec2, round_vec3}, sweep::{point_at_toi, reflect, NORMAL_SCALE}, cache::{CacheKey, FrameCache}, link::{EntityLink, EntityLinks}, schema::Versioned};
 
-use crate::{character::{dash::DashState, health::{self, DamageAccumulator, Health}, movement::SprintState, player::{input::{CursorPosition, INPUT_DASH, INPUT_RELOAD, INPUT_SPRINT, INPUT_SWITCH_WEAPON_MODE}, jjrs::PeerConfig, Player}}, collider::{is_colliding, Collider, ColliderShape, CollisionLayer, CollisionSettings, Wall}, frame::FrameCount, global_asset::GlobalAsset};
+use explosion::spawn_explosion;
+use melee::{blocked_by_wall, in_swing, MeleeConfig, MeleeState};
+use trigger::TriggerState;
+
+use crate::{audio::weapons::{WeaponAudioConfig, WeaponSoundEvent, WeaponSoundKind}, character::{dash::DashState, health::{self, DamageAccumulator, Health}, movement::SprintState, status::Stunned, player::{input::{CursorPosition, PreviousInput, INPUT_DASH, INPUT_RELOAD, INPUT_SPRINT, INPUT_SWITCH_WEAPON_MODE}, jjrs::PeerConfig, Player}}, equipment::{self, Backpack}, collider::{sweep_collision_hit, Collider, ColliderShape, CollisionLayer, CollisionSettings, HitZoneKind, HitZones, Wall}, frame::FrameCount, global_asset::GlobalAsset, rng::WeaponsRng, rules::{deathmatch::Respawning, GameRules}};
 
 // ROOLBACL
 
@@ -49,34 +65,159 @@ pub enum BulletType {
         speed: f32,
         penetration: u8,
     },
+    // Bounce on the walls, keeping a part of its damage on each bounce
+    Ricochet {
+        damage: f32,
+        speed: f32,
+        bounces: u8,
+        damage_retention: f32,
+    },
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct ExplosiveTag;
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct PiercingTag;
 
+// Nothing to fire for the melee weapons, their modes can leave out the bullets
+impl Default for BulletType {
+    fn default() -> Self {
+        BulletType::Standard { damage: 0.0, speed: 0.0 }
+    }
+}
+
+impl Default for MagBulletConfig {
+    fn default() -> Self {
+        MagBulletConfig::Magless { bullet_limit: 0 }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FiringModeConfig {
     pub firing_rate: f32,
     pub firing_mode: FiringMode,
+    #[serde(default)]
     pub spread: f32,
+    #[serde(default)]
     pub recoil: f32,
+    #[serde(default)]
     pub bullet_type: BulletType,
+    #[serde(default)]
     pub range: f32,
 
+    #[serde(default)]
     pub reload_time_seconds: f32,
+    #[serde(default)]
     pub mag: MagBulletConfig,
+    // Without it the mode never overheat
+    #[serde(default)]
+    pub overheat: Option<OverheatConfig>,
+    // Look of the bullets of this mode, without it they leave no trail
+    #[serde(default)]
+    pub trail: Option<BulletTrailConfig>,
+}
+
+// Heat of a firing mode, the weapon overheat once it reach MAX_HEAT
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OverheatConfig {
+    pub heat_per_shot: f32,
+    // Heat lost every second, also while firing
+    pub dissipation_per_second: f32,
+    // Weapon locked once overheated, longer than a reload. The heat is back to 0 after
+    pub cooldown_seconds: f32,
+}
+
+pub const MAX_HEAT: f32 = 100.0;
+
+// Presentation only, the line left behind a bullet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BulletTrailConfig {
+    pub color: (f32, f32, f32),
+    #[serde(default = "default_trail_alpha")]
+    pub alpha: f32,
+    pub width: f32,
+    // Seconds a piece of the trail take to disappear
+    pub fade_seconds: f32,
+    #[serde(default)]
+    pub particles: Option<TrailParticleConfig>,
+}
+
+fn default_trail_alpha() -> f32 {
+    0.8
+}
+
+// Sparks thrown along the trail
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrailParticleConfig {
+    pub color: (f32, f32, f32),
+    pub per_second: f32,
+    pub size: f32,
+    pub lifetime_seconds: f32,
+    // Speed away from the trail, in units per second
+    pub speed: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum WeaponKind {
+    // Fire the bullets of its modes
+    #[default]
+    Firearm,
+    // Swing an arc in front of the player, the modes only give the trigger and the rate
+    Melee(MeleeConfig),
+}
+
+// How good a weapon is, only shown on its pickups and in the kill feed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Reflect)]
+pub enum WeaponRarity {
+    #[default]
+    Common,
+    Rare,
+    Epic,
+}
+
+impl WeaponRarity {
+    pub fn color(&self) -> Color {
+        match self {
+            WeaponRarity::Common => Color::srgb(0.8, 0.8, 0.8),
+            WeaponRarity::Rare => Color::srgb(0.25, 0.55, 1.0),
+            WeaponRarity::Epic => Color::srgb(0.7, 0.3, 1.0),
+        }
+    }
+}
+
+// Weapon a hit was done with, carried up to the death so the kill feed show the
+// weapon of the kill and not the one in hand when the event is read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct KillWeapon {
+    pub name: String,
+    pub rarity: WeaponRarity,
+}
+
+impl From<&Weapon> for KillWeapon {
+    fn from(weapon: &Weapon) -> Self {
+        Self { name: weapon.config.name.clone(), rarity: weapon.rarity }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct WeaponConfig {
     pub name: String,
+    #[serde(default)]
+    pub kind: WeaponKind,
     pub default_firing_mode: String,
     pub firing_modes: HashMap<String, FiringModeConfig>,
 }
 
+impl WeaponConfig {
+    pub fn melee(&self) -> Option<&MeleeConfig> {
+        match &self.kind {
+            WeaponKind::Melee(config) => Some(config),
+            WeaponKind::Firearm => None,
+        }
+    }
+}
+
 
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -88,12 +229,26 @@ pub struct WeaponSpriteConfig {
 
     pub bullet_offset_left: Vec2,
     pub bullet_offset_right: Vec2,
+
+    // Optional color applied on the weapon sprite, used to distinguish upgraded weapons
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32)>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct WeaponAsset {
     pub config: WeaponConfig,
     pub sprite_config: WeaponSpriteConfig,
+
+    // Key of the entry in the WeaponsConfig this weapon become at the upgrade station
+    #[serde(default)]
+    pub upgrade: Option<String>,
+
+    #[serde(default)]
+    pub audio_config: WeaponAudioConfig,
+
+    #[serde(default)]
+    pub rarity: WeaponRarity,
 }
 
 // Component for a weapon
@@ -101,20 +256,33 @@ pub struct WeaponAsset {
 pub struct Weapon {
     pub config: WeaponConfig,
     pub sprite_config: WeaponSpriteConfig,
+    pub audio_config: WeaponAudioConfig,
+    pub rarity: WeaponRarity,
 }
 
 impl From<WeaponAsset> for Weapon {
     fn from(value: WeaponAsset) -> Self {
-        Self { config: value.config, sprite_config: value.sprite_config }
+        Self { config: value.config, sprite_config: value.sprite_config, audio_config: value.audio_config, rarity: value.rarity }
     }
 }
 
 #[derive(Component, Clone, Serialize, Deserialize)]
 pub struct HitMarker {
-    pub target: Entity,
+    pub target: EntityLink,
     pub damage: f32,
 }
 
+// A marker of a despawned target is removed
+impl EntityLinks for HitMarker {
+    fn has_dead_links(&self, is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+        !is_alive(&self.target)
+    }
+
+    fn prune(&mut self, _is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+        false
+    }
+}
+
 
 #[derive(Component)]
 pub struct VisualEffectRequest {
@@ -130,20 +298,23 @@ pub enum EffectType {
     Piercing,
 }
 
-#[derive(Component, Clone, Serialize, Deserialize)]
-pub struct ExplosionMarker {
-    pub radius: f32,
-    pub damage: f32,
-    pub player_handle: PlayerHandle,
-    pub processed: bool, // Flag to ensure one-time processing
-}
-
 /// Component to mark an entity as the active weapon
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct ActiveWeapon;
 
+// Presentation only, color applied to the sprite of the weapon
+#[derive(Component, Clone, Copy)]
+pub struct WeaponTint(pub Color);
+
+// Rotation computed from the cursor during the last frames, not part of the rollback state
+#[derive(Component, Default, Clone)]
+pub struct WeaponRotationCache(pub FrameCache<Quat>);
+
 
 
+// Distance a ricochet bullet is put away from the wall it bounced on
+const RICOCHET_WALL_OFFSET: f32 = 1.0;
+
 /// Component for bullets
 #[derive(Component, Clone)]
 pub struct Bullet {
@@ -154,6 +325,10 @@ pub struct Bullet {
     pub distance_traveled: f32,
     pub player_handle: PlayerHandle,
     pub created_at: u32,
+    // Bounces left on the walls for the ricochet bullets
+    pub bounces_left: u8,
+    // Weapon that fired it, the player can switch before it land
+    pub weapon: KillWeapon,
 }
 
 
@@ -161,28 +336,59 @@ pub struct Bullet {
 #[derive(Component, Debug, Clone)]
 pub struct WeaponInventory {
     pub active_weapon_index: usize,
-    pub frame_switched: u32,
-    pub frame_switched_mode: u32,
-    pub weapons: Vec<(Entity, Weapon)>,  // Store entity handles and weapon data
+    pub weapons: Vec<(EntityLink, Weapon)>,  // Store entity handles and weapon data
+
+    pub reload_timer: Option<FrameTimer>,
+    // The mag of the current reload is already in, at the `mag_in` trigger of the reload animation
+    pub reload_refilled: bool,
+}
+
+// The weapons are entities of their own, spawned again under a new id by a snapshot restore
+impl MapEntities for WeaponInventory {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for (link, _) in self.weapons.iter_mut() {
+            link.map_entities(entity_mapper);
+        }
+    }
+}
+
+// A weapon despawned under the inventory is dropped from it, the active one move to the
+// weapon that take its slot
+impl EntityLinks for WeaponInventory {
+    fn has_dead_links(&self, is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+        self.weapons.iter().any(|(link, _)| !is_alive(link))
+    }
 
-    pub reloading_ending_frame: Option<u32>,
+    fn prune(&mut self, is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+        let removed_before_active = self.weapons.iter().take(self.active_weapon_index).filter(|(link, _)| !is_alive(link)).count();
+        let active_removed = self.active_weapon().map_or(false, |(link, _)| !is_alive(link));
+        self.weapons.retain(|(link, _)| is_alive(link));
+        self.active_weapon_index = self.active_weapon_index.saturating_sub(removed_before_active).min(self.weapons.len().saturating_sub(1));
+        if active_removed {
+            self.clear_reloading();
+        }
+        true
+    }
 }
 
 impl Default for WeaponInventory {
     fn default() -> Self {
         Self {
             active_weapon_index: 0,
-            frame_switched: 0,
-            frame_switched_mode: 0,
-            reloading_ending_frame: None,
+            reload_timer: None,
+            reload_refilled: false,
             weapons: Vec::new(),
         }
     }
 }
 
+// Animation of the character during a reload and its trigger for the moment the ammo is refilled
+pub const RELOAD_ANIMATION: &str = "Reload";
+pub const MAG_IN_TRIGGER: &str = "mag_in";
+
 impl WeaponInventory {
-    pub fn active_weapon(&self) -> &(Entity, Weapon) {
-        return self.weapons.get(self.active_weapon_index).unwrap();
+    pub fn active_weapon(&self) -> Option<&(EntityLink, Weapon)> {
+        self.weapons.get(self.active_weapon_index)
     }
 }
 
@@ -192,10 +398,11 @@ pub struct WeaponModeState {
     pub mag_ammo: u32,
     pub mag_quantity: u32,
 
-    pub burst_shots_left: u32,
-
     pub mag_size: u32,
-    pub burst_cooldown: bool,
+
+    // Only moved when the mode has an overheat config
+    pub heat: f32,
+    pub overheat: Option<FrameTimer>,
 }
 
 
@@ -208,7 +415,6 @@ pub struct WeaponModesState {
 #[derive(Component, Reflect, Default, Clone)]
 pub struct WeaponState {
     pub last_fire_frame: u32,
-    pub is_firing: bool,
     pub active_mode: String,
 }
 
@@ -220,15 +426,61 @@ pub struct BulletRollbackState {
     direction: Vec2,
 }
 
-#[derive(Event)]
-pub struct FireWeaponEvent {
-    pub player_entity: Entity,
+// Sent on the rollback events each time a weapon fire, one per trigger even for shotgun
+#[derive(Clone, Debug)]
+pub struct WeaponFiredEvent {
+    pub player_handle: usize,
+    pub weapon_entity: Entity,
+    pub position: Vec2,
+}
+
+// Sent when a bullet hit a wall, for the marks left on it
+#[derive(Clone, Debug)]
+pub struct BulletImpactEvent {
+    pub position: Vec2,
+    // Normal of the wall, scaled by NORMAL_SCALE
+    pub normal: IVec2,
 }
 
 // ASSETS
 
+pub const WEAPONS_SCHEMA_VERSION: u32 = 2;
+
 #[derive(Asset, TypePath, Serialize, Deserialize)]
-pub struct WeaponsConfig(pub HashMap<String, WeaponAsset>);
+pub struct WeaponsConfig {
+    pub schema_version: u32,
+    pub weapons: HashMap<String, WeaponAsset>,
+}
+
+// Version 1, only the map of the weapons
+#[derive(Deserialize)]
+struct WeaponsConfigV1(HashMap<String, WeaponAsset>);
+
+fn migrate_weapons_v1(config: WeaponsConfigV1) -> WeaponsConfig {
+    WeaponsConfig { schema_version: WEAPONS_SCHEMA_VERSION, weapons: config.0 }
+}
+
+impl Versioned for WeaponsConfig {
+    const ASSET_NAME: &'static str = "weapons config";
+    const SCHEMA_VERSION: u32 = WEAPONS_SCHEMA_VERSION;
+
+    fn parse_version(version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        match version {
+            1 => ron::de::from_bytes::<WeaponsConfigV1>(bytes).map(migrate_weapons_v1),
+            _ => ron::de::from_bytes(bytes),
+        }
+    }
+}
+
+impl WeaponsConfig {
+    // Sorted keys of the weapons given to a player on spawn, the upgraded variants are excluded
+    pub fn starting_weapons(&self) -> Vec<&String> {
+        let upgrades: HashSet<&String> = self.weapons.values().filter_map(|w| w.upgrade.as_ref()).collect();
+        let mut keys: Vec<&String> = self.weapons.keys().filter(|k| !upgrades.contains(k)).collect();
+        keys.sort();
+        keys
+    }
+}
 
 
 // UTILITY FUNCTION
@@ -246,6 +498,65 @@ impl WeaponModeState {
     pub fn is_mag_full(&self) -> bool {
         self.mag_ammo == self.mag_size
     }
+
+    pub fn is_overheated(&self, frame: u32) -> bool {
+        self.overheat.map_or(false, |timer| timer.is_active(frame))
+    }
+
+    // Heat of a shot, return true when the weapon just overheated
+    pub fn add_heat(&mut self, config: &OverheatConfig, frame: u32, simulation: &SimulationConfig) -> bool {
+        self.heat = round((self.heat + config.heat_per_shot).min(MAX_HEAT));
+        if self.heat < MAX_HEAT {
+            return false;
+        }
+        self.overheat = Some(FrameTimer::from_seconds(frame, config.cooldown_seconds, simulation));
+        true
+    }
+
+    // Every frame, nothing is lost during the forced cooldown
+    pub fn dissipate_heat(&mut self, config: &OverheatConfig, frame: u32, simulation: &SimulationConfig) {
+        match self.overheat {
+            Some(timer) if timer.is_active(frame) => {}
+            Some(_) => {
+                self.overheat = None;
+                self.heat = 0.0;
+            }
+            None => {
+                self.heat = round((self.heat - config.dissipation_per_second * simulation.timestep()).max(0.0));
+            }
+        }
+    }
+
+    pub fn heat_ratio(&self) -> f32 {
+        self.heat / MAX_HEAT
+    }
+
+    // Fraction of ammo left in the mag and in the reserve
+    pub fn ammo_ratio(&self, mag: &MagBulletConfig) -> (f32, f32) {
+        match mag {
+            MagBulletConfig::Mag { mag_size, mag_limit } => (
+                if *mag_size == 0 { 0. } else { self.mag_ammo as f32 / *mag_size as f32 },
+                if *mag_limit == 0 { 0. } else { self.mag_quantity as f32 / *mag_limit as f32 },
+            ),
+            MagBulletConfig::Magless { bullet_limit } => {
+                let ratio = if *bullet_limit == 0 { 0. } else { self.mag_ammo as f32 / *bullet_limit as f32 };
+                (ratio, ratio)
+            }
+        }
+    }
+
+    pub fn apply_ammo_ratio(&mut self, mag: &MagBulletConfig, mag_ratio: f32, reserve_ratio: f32) {
+        match mag {
+            MagBulletConfig::Mag { mag_size, mag_limit } => {
+                self.mag_size = *mag_size;
+                self.mag_ammo = (*mag_size as f32 * mag_ratio.clamp(0., 1.)).ceil() as u32;
+                self.mag_quantity = (*mag_limit as f32 * reserve_ratio.clamp(0., 1.)).ceil() as u32;
+            },
+            MagBulletConfig::Magless { bullet_limit } => {
+                self.mag_ammo = (*bullet_limit as f32 * mag_ratio.clamp(0., 1.)).ceil() as u32;
+            }
+        }
+    }
 }
 
 impl WeaponModesState {
@@ -260,33 +571,58 @@ impl WeaponModesState {
 impl WeaponInventory {
 
     pub fn is_reloading(&self) -> bool {
-        self.reloading_ending_frame.is_some()
+        self.reload_timer.is_some()
     }
 
     pub fn is_reloading_over(&self, current_frame: u32) -> bool {
-        self.reloading_ending_frame.map_or_else(|| true, |f| current_frame >= f)
+        self.reload_timer.map_or(true, |timer| timer.is_done(current_frame))
     }
 
     pub fn clear_reloading(&mut self) {
-        self.reloading_ending_frame = None;
+        self.reload_timer = None;
+        self.reload_refilled = false;
+    }
+
+    // The reload reached the `mag_in` trigger, at `fraction` of its duration
+    pub fn is_mag_in(&self, current_frame: u32, fraction: Option<f32>) -> bool {
+        match (fraction, self.reload_timer) {
+            (Some(fraction), Some(timer)) => timer.elapsed(current_frame) >= (timer.duration as f32 * fraction).round() as u32,
+            _ => false,
+        }
+    }
+
+    // Move the weapon of the slot `from` to `to`, the ones between shift by one and the
+    // active weapon stay the same
+    pub fn move_slot(&mut self, from: usize, to: usize) -> bool {
+        if from == to || from >= self.weapons.len() || to >= self.weapons.len() {
+            return false;
+        }
+        let active = self.active_weapon_index;
+        let weapon = self.weapons.remove(from);
+        self.weapons.insert(to, weapon);
+        self.active_weapon_index = if active == from {
+            to
+        } else if from < active && active <= to {
+            active - 1
+        } else if to <= active && active < from {
+            active + 1
+        } else {
+            active
+        };
+        true
     }
 
     pub fn start_reload(
         &mut self,
         current_game_frame: u32,
         reload_time_seconds: f32,
+        simulation: &SimulationConfig,
     ) {
-        self.reloading_ending_frame = {
-            if reload_time_seconds <= 0.0 {
-                None
-            } else {
-                let frames_to_reload = (reload_time_seconds * 60.0).ceil() as u32;
-                if frames_to_reload == 0 { // Ensure at least one frame for very short reload times
-                    Some(current_game_frame + 1)
-                } else {
-                    Some(current_game_frame + frames_to_reload)
-                }
-            }
+        self.reload_refilled = false;
+        self.reload_timer = if reload_time_seconds <= 0.0 {
+            None
+        } else {
+            Some(FrameTimer::from_seconds(current_game_frame, reload_time_seconds, simulation))
         };
     }
 }
@@ -295,8 +631,65 @@ impl WeaponInventory {
 // start the reload process
 
 
-// Function to spawn weapon , all weapon should be spawn on the user when they got them
-pub fn spawn_weapon_for_player(
+// Create the rollback state of a weapon with all its modes full of ammo
+pub fn create_weapon_state(config: &WeaponConfig) -> (WeaponState, WeaponModesState) {
+    let mut weapon_state = WeaponState::default();
+    let mut weapon_modes_state = WeaponModesState::default();
+    weapon_state.active_mode = config.default_firing_mode.clone();
+    for (k, v) in config.firing_modes.iter() {
+        weapon_modes_state.modes.insert(k.clone(), create_mode_state(v));
+    }
+
+    (weapon_state, weapon_modes_state)
+}
+
+// State of a firing mode with a full mag
+fn create_mode_state(config: &FiringModeConfig) -> WeaponModeState {
+    let mut weapon_mode_state = WeaponModeState::default();
+    match config.mag {
+        MagBulletConfig::Mag { mag_size, mag_limit } => {
+            weapon_mode_state.mag_ammo = mag_size;
+            weapon_mode_state.mag_quantity = mag_limit;
+            weapon_mode_state.mag_size = mag_size;
+        },
+        MagBulletConfig::Magless {  bullet_limit } => {
+            weapon_mode_state.mag_ammo = bullet_limit;
+        },
+    };
+    weapon_mode_state
+}
+
+// Make the state of a weapon match a new version of its config, the modes that
+// disappeared are removed, the new ones start full and the ammo is clamped to the new sizes.
+// The reserve keep the extra room of the backpack of the owner
+pub fn sync_weapon_state(config: &WeaponConfig, weapon_state: &mut WeaponState, modes_state: &mut WeaponModesState, backpack: Option<&Backpack>) {
+    modes_state.modes.retain(|name, _| config.firing_modes.contains_key(name));
+
+    for (name, mode_config) in config.firing_modes.iter() {
+        let Some(mode_state) = modes_state.modes.get_mut(name) else {
+            modes_state.modes.insert(name.clone(), create_mode_state(mode_config));
+            continue;
+        };
+
+        match mode_config.mag {
+            MagBulletConfig::Mag { mag_size, mag_limit } => {
+                mode_state.mag_size = mag_size;
+                mode_state.mag_ammo = mode_state.mag_ammo.min(mag_size);
+                mode_state.mag_quantity = mode_state.mag_quantity.min(equipment::mag_limit(mag_limit, backpack));
+            },
+            MagBulletConfig::Magless { bullet_limit } => {
+                mode_state.mag_ammo = mode_state.mag_ammo.min(bullet_limit);
+            },
+        }
+    }
+
+    if !config.firing_modes.contains_key(&weapon_state.active_mode) {
+        weapon_state.active_mode = config.default_firing_mode.clone();
+    }
+}
+
+// Spawn the weapon entity and its sprite as a child of the player, does not touch the inventory
+fn spawn_weapon_entity(
     commands: &mut Commands,
     global_assets: &Res<GlobalAsset>,
 
@@ -308,8 +701,9 @@ pub fn spawn_weapon_for_player(
 
     player_entity: Entity,
     weapon: WeaponAsset,
-    inventory: &mut WeaponInventory,
-) -> Entity {
+    weapon_state: WeaponState,
+    weapon_modes_state: WeaponModesState,
+) -> (Entity, Weapon) {
 
     let map_layers = global_assets.spritesheets.get(&weapon.sprite_config.name).unwrap().clone();
     let animation_handle = global_assets.animations.get(&weapon.sprite_config.name).unwrap().clone();
@@ -317,33 +711,16 @@ pub fn spawn_weapon_for_player(
     let animation_bundle =
         AnimationBundle::new(map_layers.clone(), animation_handle.clone(), weapon.sprite_config.index, bmap!("body" => String::new()));
 
-    let mut weapon_state = WeaponState::default();
-    let mut weapon_modes_state = WeaponModesState::default();
-    weapon_state.active_mode = weapon.config.default_firing_mode.clone();
-    for (k, v) in weapon.config.firing_modes.iter() {
-        let mut weapon_mode_state = WeaponModeState::default();
-        match v.mag {
-            MagBulletConfig::Mag { mag_size, mag_limit } => {
-                weapon_mode_state.mag_ammo = mag_size;
-                weapon_mode_state.mag_quantity = mag_limit;
-                weapon_mode_state.mag_size = mag_size;
-            },
-            MagBulletConfig::Magless {  bullet_limit } => {
-                weapon_mode_state.mag_ammo = bullet_limit;
-            },
-        };
-
-        weapon_modes_state.modes.insert(k.clone(), weapon_mode_state);
-    }
-
     let weapon: Weapon = weapon.into();
 
     let entity = commands.spawn((
         Transform::from_translation(Vec3::new(weapon.sprite_config.weapon_offset.x, weapon.sprite_config.weapon_offset.y, 0.0)).with_rotation(Quat::IDENTITY),
         weapon_state,
         weapon_modes_state,
+        TriggerState::default(),
         weapon.clone(),
-        animation_bundle
+        animation_bundle,
+        WeaponRotationCache::default(),
     )).add_rollback().id();
 
 
@@ -354,21 +731,115 @@ pub fn spawn_weapon_for_player(
         texture_atlas_layouts,
         entity.clone(), &spritesheet_config, 0);
 
-    inventory.weapons.push((entity.clone(), weapon));
+    if let Some((r, g, b)) = weapon.sprite_config.tint {
+        commands.entity(entity).insert(WeaponTint(Color::srgb(r, g, b)));
+    }
+
+    if let Some(melee) = weapon.config.melee() {
+        commands.entity(entity).insert(MeleeState::new(melee));
+    }
 
     if active {
         commands.entity(entity).insert((ActiveWeapon{}, Visibility::Inherited));
-        inventory.active_weapon_index = inventory.weapons.len() - 1;
     } else {
         commands.entity(entity).insert(Visibility::Hidden);
     }
 
     commands.entity(player_entity).add_child(entity);
 
+    (entity, weapon)
+}
+
+// Function to spawn weapon , all weapon should be spawn on the user when they got them
+pub fn spawn_weapon_for_player(
+    commands: &mut Commands,
+    global_assets: &Res<GlobalAsset>,
+
+    asset_server: &Res<AssetServer>,
+    texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    sprint_sheet_assets: &Res<Assets<SpriteSheetConfig>>,
+
+    active: bool,
+
+    player_entity: Entity,
+    weapon: WeaponAsset,
+    inventory: &mut WeaponInventory,
+) -> Entity {
+    let (weapon_state, weapon_modes_state) = create_weapon_state(&weapon.config);
+
+    let (entity, weapon) = spawn_weapon_entity(
+        commands, global_assets, asset_server, texture_atlas_layouts, sprint_sheet_assets,
+        active, player_entity, weapon, weapon_state, weapon_modes_state);
+
+    inventory.weapons.push((EntityLink::new(entity), weapon));
+
+    if active {
+        inventory.active_weapon_index = inventory.weapons.len() - 1;
+    }
+
+    entity
+}
+
+// Replace the weapon in the slot `index` by a new one, keeping the slot and the ammo percentages of
+// the modes that exist in both weapons
+pub fn replace_weapon_for_player(
+    commands: &mut Commands,
+    global_assets: &Res<GlobalAsset>,
+
+    asset_server: &Res<AssetServer>,
+    texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    sprint_sheet_assets: &Res<Assets<SpriteSheetConfig>>,
+
+    player_entity: Entity,
+    index: usize,
+    weapon: WeaponAsset,
+    previous_modes_state: &WeaponModesState,
+    inventory: &mut WeaponInventory,
+) -> Entity {
+    let (old_entity, old_weapon) = inventory.weapons[index].clone();
+
+    let (mut weapon_state, mut weapon_modes_state) = create_weapon_state(&weapon.config);
+    for (mode_name, mode_state) in weapon_modes_state.modes.iter_mut() {
+        let (Some(previous_state), Some(previous_config), Some(config)) = (
+            previous_modes_state.modes.get(mode_name),
+            old_weapon.config.firing_modes.get(mode_name),
+            weapon.config.firing_modes.get(mode_name),
+        ) else {
+            continue;
+        };
+        let (mag_ratio, reserve_ratio) = previous_state.ammo_ratio(&previous_config.mag);
+        mode_state.apply_ammo_ratio(&config.mag, mag_ratio, reserve_ratio);
+    }
+    if weapon.config.firing_modes.contains_key(&old_weapon.config.default_firing_mode) {
+        weapon_state.active_mode = old_weapon.config.default_firing_mode.clone();
+    }
+
+    let active = index == inventory.active_weapon_index;
+    let (entity, weapon) = spawn_weapon_entity(
+        commands, global_assets, asset_server, texture_atlas_layouts, sprint_sheet_assets,
+        active, player_entity, weapon, weapon_state, weapon_modes_state);
+
+    if let Some(old_entity) = old_entity.commands(commands) {
+        old_entity.despawn_recursive();
+    }
+    inventory.weapons[index] = (EntityLink::new(entity), weapon);
+    inventory.clear_reloading();
+
     entity
 }
 
 
+// Direction of a shot, the aim is zero with a gamepad stick at rest and the bullet then
+// go the way the player face instead of getting a NaN direction
+pub fn fire_direction(aim: IVec2, facing: &FacingDirection) -> Vec2 {
+    let direction = aim.as_vec2().normalize_or_zero();
+    if direction == Vec2::ZERO {
+        Vec2::new(facing.to_int() as f32, 0.0)
+    } else {
+        direction
+    }
+}
+
 fn spawn_bullet_rollback(
     commands: &mut Commands,
     weapon: &Weapon,
@@ -377,27 +848,39 @@ fn spawn_bullet_rollback(
     direction: Vec2,
     bullet_type: BulletType,
     range: f32,
+    trail: Option<&BulletTrailConfig>,
     player_handle: PlayerHandle,
     current_frame: u32,
     collision_settings: &Res<CollisionSettings>,
     parent_layer: &CollisionLayer,
+    simulation: &SimulationConfig,
 ) -> Entity {
+    // Bullet velocity is in units per frame
+    let timestep = simulation.timestep();
     let (velocity, damage, range, radius) = match &bullet_type {
         BulletType::Standard { speed, damage: damage_bullet } => {
-            (direction * (speed / 60.0 ), *damage_bullet, range, 5.0)
+            (direction * (speed * timestep), *damage_bullet, range, 5.0)
         },
         BulletType::Explosive { speed, damage: damage_bullet, blast_radius, explosive_damage_multiplier } => {
-            (direction * (speed / 60.0), *damage_bullet, range, 8.0)
+            (direction * (speed * timestep), *damage_bullet, range, 8.0)
         },
         BulletType::Piercing { speed, damage: damage_bullet, penetration } => {
-            (direction * (speed / 60.0), *damage_bullet, range, 5.0)
+            (direction * (speed * timestep), *damage_bullet, range, 5.0)
         }
+        BulletType::Ricochet { speed, damage: damage_bullet, .. } => {
+            (direction * (speed * timestep), *damage_bullet, range, 5.0)
+        }
+    };
+    let bounces_left = match &bullet_type {
+        BulletType::Ricochet { bounces, .. } => *bounces,
+        _ => 0,
     };
 
     let color = match &bullet_type {
         BulletType::Standard { .. } => Color::BLACK,
         BulletType::Explosive { .. } => Color::WHITE,
         BulletType::Piercing { .. } => Color::BLACK,
+        BulletType::Ricochet { .. } => Color::srgb(0.9, 0.7, 0.2),
     };
 
     let firing_position_v2 = if matches!(facing_direction, FacingDirection::Right) {
@@ -421,7 +904,9 @@ fn spawn_bullet_rollback(
             range,
             distance_traveled: 0.,
             player_handle,
-            created_at: current_frame
+            created_at: current_frame,
+            bounces_left,
+            weapon: KillWeapon::from(weapon),
         },
         BulletRollbackState {
             spawn_frame: current_frame,
@@ -441,6 +926,9 @@ fn spawn_bullet_rollback(
         BulletType::Piercing { .. } => { entity_commands.insert(PiercingTag); },
         _ => {}
     };
+    if let Some(trail) = trail {
+        entity_commands.insert(trail::BulletTrail(trail.clone()));
+    }
 
     entity_commands.add_rollback().id()
 
@@ -452,17 +940,34 @@ fn spawn_bullet_rollback(
 
 // Rollback system to correctly transform the weapon based on the position
 pub fn system_weapon_position(
+    frame: Res<FrameCount>,
     query: Query<(&Children, &CursorPosition, &FacingDirection), With<Rollback>>,
-    mut query_weapon: Query<(&Children, &mut Transform), With<ActiveWeapon>>,
+    mut query_weapon: Query<(&Children, &mut Transform, Option<&mut WeaponRotationCache>), With<ActiveWeapon>>,
     mut query_sprite: Query<(&mut Sprite)>,
 
 ) {
     for (childs, cursor_position, direction) in query.iter() {
         for child in childs.iter() {
-            if let Ok((childs, mut transform)) = query_weapon.get_mut(*child) {
+            if let Ok((childs, mut transform, mut opt_cache)) = query_weapon.get_mut(*child) {
+                let key = CacheKey::default().with_i32(cursor_position.x).with_i32(cursor_position.y);
                 for child in childs.iter() {
                     if let Ok((mut sprite)) = query_sprite.get_mut(*child) {
 
+                        match direction {
+                            FacingDirection::Left => {
+                                sprite.flip_y = true;
+                            }
+                            FacingDirection::Right => {
+                                sprite.flip_y = false;
+                            }
+                        };
+
+                        // Same cursor as the first simulation of this frame, same rotation
+                        if let Some(rotation) = opt_cache.as_mut().and_then(|cache| cache.0.get(frame.frame, key)) {
+                            transform.rotation = *rotation;
+                            continue;
+                        }
+
                         let vec = Vec2::new(cursor_position.x as f32, cursor_position.y as f32); // Ensure cursor_position fields are clean if f32
                         let angle_radians = vec.y.atan2(vec.x);
                         // Optional: You can round angle_radians here if it's used for other deterministic logic like deriving FacingDirection
@@ -487,14 +992,9 @@ pub fn system_weapon_position(
                         // the rounding factor is chosen such that normalization is stable.
                         transform.rotation = rounded_quat.normalize(); 
 
-                        match direction {
-                            FacingDirection::Left => {
-                                sprite.flip_y = true;
-                            }
-                            FacingDirection::Right => {
-                                sprite.flip_y = false;
-                            }
-                        };
+                        if let Some(cache) = opt_cache.as_mut() {
+                            cache.0.insert(frame.frame, key, transform.rotation);
+                        }
                     }
                 }
             }
@@ -505,27 +1005,44 @@ pub fn system_weapon_position(
 // rollback system for weapon action , firing and all
 pub fn weapon_rollback_system(
     mut commands: Commands,
-    mut rng: ResMut<RollbackRng>,
+    mut rng: ResMut<WeaponsRng>,
     inputs: Res<PlayerInputs<PeerConfig>>,
     frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    mut fired_events: ResMut<RollbackEvents<WeaponFiredEvent>>,
+    mut sound_events: ResMut<RollbackEvents<WeaponSoundEvent>>,
 
-    mut inventory_query: Query<(Entity, &mut WeaponInventory, &SprintState, &DashState, &CollisionLayer, &Player)>,
-    mut weapon_query: Query<(&mut Weapon, &mut WeaponState, &mut WeaponModesState, &GlobalTransform, &Parent)>,
+    animation_configs: Res<Assets<AnimationMapConfig>>,
+
+    mut inventory_query: Query<(Entity, &mut WeaponInventory, &SprintState, &DashState, &CollisionLayer, &Player, &PreviousInput, Option<&Stunned>, Option<&CharacterAnimationHandles>)>,
+    mut weapon_query: Query<(&mut Weapon, &mut WeaponState, &mut WeaponModesState, &mut TriggerState, Option<&mut MeleeState>, &GlobalTransform, &Parent)>,
 
     player_query: Query<(&GlobalTransform, &FacingDirection, &Player)>,
+    mut target_query: Query<(Entity, &GlobalTransform, &CollisionLayer, Option<&Player>, Has<Respawning>, Option<&mut DamageAccumulator>), (With<Health>, With<Rollback>)>,
+    wall_query: Query<(&Transform, &Collider), With<Wall>>,
 
     collision_settings: Res<CollisionSettings>,
+    rules: Res<GameRules>,
 ) {
     // Process weapon firing for all players
-    for (entity,  mut inventory, sprint_state, dash_state , collision_layer, player) in inventory_query.iter_mut() {
+    for (entity,  mut inventory, sprint_state, dash_state , collision_layer, player, previous_input, opt_stunned, opt_animation) in inventory_query.iter_mut() {
         let (input, _input_status) = inputs[player.handle];
 
+        // Order of the slots from the inventory menu, even stunned
+        if let Some((from, to)) = previous_input.slot_move_just_sent(&input) {
+            inventory.move_slot(from, to);
+        }
+
+        if opt_stunned.map_or(false, |stunned| stunned.is_active(frame.frame)) {
+            continue;
+        }
+
         // Do nothing if no weapons
         if inventory.weapons.is_empty() {
             continue;
         }
 
-        if sprint_state.is_sprinting || dash_state.is_dashing || input.buttons & INPUT_SPRINT != 0 || input.buttons & INPUT_DASH != 0 {
+        if sprint_state.is_sprinting || dash_state.is_dashing() || input.buttons & INPUT_SPRINT != 0 || input.buttons & INPUT_DASH != 0 {
             continue;
         }
 
@@ -533,23 +1050,23 @@ pub fn weapon_rollback_system(
         // Nothing to do for weapon if we are sprinting
         
         // Get active weapon
-        let (weapon_entity, _) = inventory.weapons[inventory.active_weapon_index];
+        let Some(weapon_entity) = inventory.active_weapon().map(|(link, _)| link.entity()) else {
+            continue;
+        };
 
 
         // Get the entity for the active weapon
-        if let Ok((mut weapon, mut weapon_state, mut weapon_modes_state, weapon_transform, parent)) = weapon_query.get_mut(weapon_entity) {
+        if let Ok((mut weapon, mut weapon_state, mut weapon_modes_state, mut trigger, mut opt_melee_state, weapon_transform, parent)) = weapon_query.get_mut(weapon_entity) {
             let active_mode = weapon_state.active_mode.clone();
             let weapon_config = weapon.config.firing_modes.get(&active_mode).unwrap();
 
-            if input.buttons & INPUT_SWITCH_WEAPON_MODE != 0 {
+            if previous_input.just_pressed(&input, INPUT_SWITCH_WEAPON_MODE) {
                 if let Some(new_mode) = weapon_modes_state.modes.keys().find(|&x| *x != weapon_state.active_mode) {
-                    if inventory.frame_switched_mode + 20 < frame.frame && inventory.frame_switched + 20 < frame.frame {
-                        inventory.frame_switched_mode = frame.frame;
-                        weapon_state.active_mode = new_mode.clone();
-
-                        continue;
-                    }
+                    weapon_state.active_mode = new_mode.clone();
+                    trigger.interrupt();
+                    sound_events.send(frame.frame, WeaponSoundEvent { weapon_entity, kind: WeaponSoundKind::ModeSwitch });
 
+                    continue;
                 }
             }
 
@@ -558,156 +1075,200 @@ pub fn weapon_rollback_system(
 
             // Check if reloading and update progress,
             if inventory.is_reloading() {
+                // Without a `mag_in` trigger the ammo is refilled at the end of the reload
+                let mag_in_fraction = opt_animation
+                    .and_then(|handles| animation_configs.get(&handles.animations))
+                    .and_then(|config| config.trigger_fraction(RELOAD_ANIMATION, MAG_IN_TRIGGER));
                 if inventory.is_reloading_over(frame.frame) {
-                    weapon_mode_state.reload();
+                    if !inventory.reload_refilled {
+                        weapon_mode_state.reload();
+                    }
                     inventory.clear_reloading();
+                    sound_events.send(frame.frame, WeaponSoundEvent { weapon_entity, kind: WeaponSoundKind::ReloadEnd });
                 } else {
+                    if inventory.is_mag_in(frame.frame, mag_in_fraction) && !inventory.reload_refilled {
+                        weapon_mode_state.reload();
+                        inventory.reload_refilled = true;
+                    }
                     continue;
                 }
-            } else if input.buttons & INPUT_RELOAD != 0  && !weapon_mode_state.is_mag_full() {
-                inventory.start_reload(frame.frame, weapon_config.reload_time_seconds);
+            } else if previous_input.just_pressed(&input, INPUT_RELOAD) && !weapon_mode_state.is_mag_full() {
+                inventory.start_reload(frame.frame, weapon_config.reload_time_seconds, &simulation);
+                sound_events.send(frame.frame, WeaponSoundEvent { weapon_entity, kind: WeaponSoundKind::ReloadStart });
+                trigger.interrupt();
                 continue;
             }
 
             // Handle switching of weapons, will start firing on the next frame
-            if input.switch_weapon && !inventory.weapons.is_empty(){
+            if previous_input.switch_weapon_just_pressed(&input) && !inventory.weapons.is_empty(){
                 let new_index = (inventory.active_weapon_index + 1) % inventory.weapons.len();
 
-                if new_index != inventory.active_weapon_index &&
-                    inventory.frame_switched + 20 < frame.frame &&
-                    inventory.frame_switched_mode + 20 < frame.frame  {
-
+                if new_index != inventory.active_weapon_index {
                     inventory.active_weapon_index = new_index;
-                    inventory.frame_switched = frame.frame;
+                    trigger.interrupt();
 
                     continue;
                 }
             }
 
-            // TODO: fix only support two mode, take the first that is not the current
-            if input.fire {
-                // Calculate fire rate in frames (60 FPS assumed) , need to be configure via ressource instead
-                let frame_per_shot = ((60. / weapon_config.firing_rate)) as u32;
-                let current_frame = frame.frame;
-                let frames_since_last_shot = current_frame - weapon_state.last_fire_frame;
+            // Direct selection from the weapon wheel
+            if let Some(slot) = previous_input.slot_just_selected(&input) {
+                if slot < inventory.weapons.len() && slot != inventory.active_weapon_index {
+                    inventory.active_weapon_index = slot;
+                    trigger.interrupt();
 
-               let (can_fire, empty) = match weapon_config.firing_mode {
-                    FiringMode::Automatic { .. } => {
-                        (frames_since_last_shot >= frame_per_shot, weapon_mode_state.mag_ammo == 0)
-                    },
-                    
-                    FiringMode::Manual { .. } => {
-                        (!weapon_state.is_firing && frames_since_last_shot >= frame_per_shot,
-                        weapon_mode_state.mag_ammo == 0)
-                    },
-                    
-                    FiringMode::Burst { pellets_per_shot, cooldown_frames } => {
-                        if weapon_mode_state.burst_shots_left > 0 && frames_since_last_shot >= frame_per_shot {
-                            // Continue ongoing burst
-                            (true, weapon_mode_state.mag_ammo == 0)
-                        } else if weapon_mode_state.burst_shots_left == 0 {
-                            if !weapon_state.is_firing && !weapon_mode_state.burst_cooldown && frames_since_last_shot >= cooldown_frames {
-                                // Start new burst when trigger is pulled
-                                weapon_mode_state.burst_shots_left = pellets_per_shot;
-                                (true, weapon_mode_state.mag_ammo == 0)
-                            } else if weapon_mode_state.burst_cooldown && frames_since_last_shot >= cooldown_frames {
-                                // Reset cooldown
-                                weapon_mode_state.burst_cooldown = false;
-                                (false, false)
-                            } else {
-                                (false, false)
-                            }
-                        } else {
-                            (false, false)
-                        }
-                    },
-                    
-                    FiringMode::Shotgun { pellet_count, spread_angle } => {
-                        if !weapon_state.is_firing && frames_since_last_shot >= frame_per_shot {
-                            // Shotgun fires all pellets at once, so we don't need burst_shots_left
-                            (true, weapon_mode_state.mag_ammo == 0)
-                        } else {
-                            (false, false)
-                        }
+                    continue;
+                }
+            }
+
+            // Fire rate is in shots per second of game time
+            let mut frame_per_shot = (simulation.tick_rate as f32 / weapon_config.firing_rate) as u32;
+
+            let melee = weapon.config.melee().cloned().zip(opt_melee_state.as_mut());
+            if let Some((melee_config, melee_state)) = melee {
+                // A broken weapon stay in the hands when it is the last one
+                if melee_state.is_broken() {
+                    continue;
+                }
+                frame_per_shot = melee_state.frames_per_swing(&melee_config, frame.frame, frame_per_shot);
+                if !trigger.step(&weapon_config.firing_mode, input.fire, frame.frame, frame_per_shot) {
+                    continue;
+                }
+                melee_state.swing(&melee_config, frame.frame);
+
+                let Ok((player_transform, ..)) = player_query.get(**parent) else {
+                    continue;
+                };
+                let origin = player_transform.translation().truncate();
+                let aim_dir = input.aim_offset().as_vec2().normalize_or_zero();
+                let walls: Vec<(&Transform, &Collider)> = wall_query.iter().collect();
+
+                // Closest targets in the arc first, by entity for the same distance
+                let mut hits: Vec<(f32, Entity)> = target_query.iter()
+                    .filter(|(target, _, target_layer, opt_player, respawning, _)| {
+                        let friendly_hit = rules.friendly_fire() && opt_player.map_or(false, |p| p.handle != player.handle);
+                        *target != entity && !respawning && (collision_settings.layer_matrix[collision_layer.0 as usize][target_layer.0 as usize] || friendly_hit)
+                    })
+                    .map(|(target, target_transform, ..)| (round_vec2(target_transform.translation().truncate() - origin), target))
+                    .filter(|(offset, _)| in_swing(&melee_config, aim_dir, *offset) && !blocked_by_wall(origin, origin + *offset, &walls))
+                    .map(|(offset, target)| (round(offset.length()), target))
+                    .collect();
+                hits.sort_by(|(distance_a, a), (distance_b, b)| distance_a.total_cmp(distance_b).then(a.index().cmp(&b.index())));
+                hits.truncate(melee_config.max_targets);
+
+                let kill_weapon = KillWeapon::from(&*weapon);
+                for (_, target) in hits.iter() {
+                    if let Ok((_, target_transform, .., opt_accumulator)) = target_query.get_mut(*target) {
+                        let direction = target_transform.translation().truncate() - origin;
+                        apply_player_damage(&mut commands, *target, melee_config.damage, player.handle, &kill_weapon, None, Some(direction), opt_accumulator);
                     }
-                }; 
+                }
+
+                weapon_state.last_fire_frame = frame.frame;
+                fired_events.send(frame.frame, WeaponFiredEvent {
+                    player_handle: player.handle,
+                    weapon_entity,
+                    position: weapon_transform.translation().truncate(),
+                });
+
+                if !hits.is_empty() {
+                    melee_state.wear();
+                }
+                if melee_state.is_broken() && inventory.weapons.len() > 1 {
+                    let index = inventory.active_weapon_index;
+                    inventory.weapons.remove(index);
+                    inventory.active_weapon_index = index % inventory.weapons.len();
+                    commands.entity(weapon_entity).despawn_recursive();
+                    info!("Player {} broke its {}", player.handle, weapon.config.name);
+                }
+                continue;
+            }
 
+            // Overheated, the trigger does nothing until the cooldown is over
+            if weapon_mode_state.is_overheated(frame.frame) {
+                trigger.interrupt();
+                continue;
+            }
 
-                if empty {
-                    inventory.start_reload(frame.frame, weapon_config.reload_time_seconds);
+            if trigger.step(&weapon_config.firing_mode, input.fire, frame.frame, frame_per_shot) {
+                if weapon_mode_state.mag_ammo == 0 {
+                    sound_events.send(frame.frame, WeaponSoundEvent { weapon_entity, kind: WeaponSoundKind::Empty });
+                    inventory.start_reload(frame.frame, weapon_config.reload_time_seconds, &simulation);
+                    sound_events.send(frame.frame, WeaponSoundEvent { weapon_entity, kind: WeaponSoundKind::ReloadStart });
+                    trigger.interrupt();
                     continue;
                 }
 
-                weapon_state.is_firing = true;
-
-                if can_fire {
-                    if let Ok((_, facing_direction, _)) = player_query.get(**parent) {
-                        let aim_dir = Vec2::new(
-                            input.pan_x as f32 / 127.0,
-                            input.pan_y as f32 / 127.0
-                        ).normalize();
-                                match weapon_config.firing_mode {
-                                    FiringMode::Shotgun { pellet_count, spread_angle } => {
-                                        // Fire multiple pellets in a spread pattern
-                                        for _ in 0..pellet_count {
-                                            // Calculate a random angle within the spread range
-                                            let pellet_angle = (round(rng.next_f32()) - 0.5) * spread_angle;
-                                            let spread_rotation = Mat2::from_angle(pellet_angle);
-                                            let direction = spread_rotation * aim_dir;
-
-                                            spawn_bullet_rollback(
-                                                &mut commands,
-                                                &weapon,
-                                                weapon_transform,
-                                                facing_direction,
-                                                direction,
-                                                weapon_config.bullet_type.clone(),
-                                                weapon_config.range,
-                                                player.handle,
-                                                frame.frame,
-                                                &collision_settings,
-                                                collision_layer,
-                                            );
-                                        }
-                                        weapon_mode_state.mag_ammo -= 1; // Shotgun uses one ammo for all pellets
-                                        inventory.start_reload(frame.frame, weapon_config.reload_time_seconds);
-                                    },
-                                    _ => {
-                                        // Standard firing for Automatic, Manual, and Burst
-                                        let spread_angle = (rng.next_f32_symmetric() - 0.5) * weapon_config.spread;
-                                        let spread_rotation = Mat2::from_angle(spread_angle);
-                                        let direction = spread_rotation * aim_dir;
-
-                                        spawn_bullet_rollback(
-                                            &mut commands,
-                                            &weapon,
-                                            weapon_transform,
-                                            facing_direction,
-                                            direction,
-                                            weapon_config.bullet_type.clone(),
-                                            weapon_config.range,
-                                            player.handle,
-                                            frame.frame,
-                                            &collision_settings,
-                                            collision_layer,
-                                        );
-                                        weapon_mode_state.mag_ammo -= 1;
-
-                                        if matches!(weapon_config.firing_mode, FiringMode::Burst { .. }) && weapon_mode_state.burst_shots_left > 0 {
-                                            weapon_mode_state.burst_shots_left -= 1;
-                                            
-                                            // Set cooldown when burst finishes
-                                            if weapon_mode_state.burst_shots_left == 0 {
-                                                weapon_mode_state.burst_cooldown = true;
-                                            }
-                                        }
-                                    }
-                                }
-                                weapon_state.last_fire_frame = frame.frame;
+                if let Ok((_, facing_direction, _)) = player_query.get(**parent) {
+                    let aim_dir = fire_direction(input.aim_offset(), facing_direction);
+                    match weapon_config.firing_mode {
+                        FiringMode::Shotgun { pellet_count, spread_angle } => {
+                            // Fire multiple pellets in a spread pattern
+                            for _ in 0..pellet_count {
+                                // Calculate a random angle within the spread range
+                                let pellet_angle = (round(rng.next_f32()) - 0.5) * spread_angle;
+                                let spread_rotation = Mat2::from_angle(pellet_angle);
+                                let direction = spread_rotation * aim_dir;
+
+                                spawn_bullet_rollback(
+                                    &mut commands,
+                                    &weapon,
+                                    weapon_transform,
+                                    facing_direction,
+                                    direction,
+                                    weapon_config.bullet_type.clone(),
+                                    weapon_config.range,
+                                    weapon_config.trail.as_ref(),
+                                    player.handle,
+                                    frame.frame,
+                                    &collision_settings,
+                                    collision_layer,
+                                    &simulation,
+                                );
+                            }
+                            weapon_mode_state.mag_ammo -= 1; // Shotgun uses one ammo for all pellets
+                            inventory.start_reload(frame.frame, weapon_config.reload_time_seconds, &simulation);
+                            sound_events.send(frame.frame, WeaponSoundEvent { weapon_entity, kind: WeaponSoundKind::ReloadStart });
+                        },
+                        _ => {
+                            // Standard firing for Automatic, Manual, and Burst
+                            let spread_angle = (rng.next_f32_symmetric() - 0.5) * weapon_config.spread;
+                            let spread_rotation = Mat2::from_angle(spread_angle);
+                            let direction = spread_rotation * aim_dir;
+
+                            spawn_bullet_rollback(
+                                &mut commands,
+                                &weapon,
+                                weapon_transform,
+                                facing_direction,
+                                direction,
+                                weapon_config.bullet_type.clone(),
+                                weapon_config.range,
+                                weapon_config.trail.as_ref(),
+                                player.handle,
+                                frame.frame,
+                                &collision_settings,
+                                collision_layer,
+                                &simulation,
+                            );
+                            weapon_mode_state.mag_ammo -= 1;
+                        }
+                    }
+                    weapon_state.last_fire_frame = frame.frame;
+
+                    if let Some(overheat) = &weapon_config.overheat {
+                        if weapon_mode_state.add_heat(overheat, frame.frame, &simulation) {
+                            trigger.interrupt();
+                            sound_events.send(frame.frame, WeaponSoundEvent { weapon_entity, kind: WeaponSoundKind::Overheat });
+                        }
                     }
+
+                    fired_events.send(frame.frame, WeaponFiredEvent {
+                        player_handle: player.handle,
+                        weapon_entity,
+                        position: weapon_transform.translation().truncate(),
+                    });
                 }
-            } else {
-                weapon_state.is_firing = false;
             }
         }
     }
@@ -715,6 +1276,23 @@ pub fn weapon_rollback_system(
 
 
 
+// Every mode of every weapon, the ones not in the hands also cool down.
+// Run before the weapon system so a shot is heated after the frame dissipation
+pub fn rollback_dissipate_weapon_heat(
+    frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    mut weapon_query: Query<(&Weapon, &mut WeaponModesState)>,
+) {
+    for (weapon, mut modes_state) in weapon_query.iter_mut() {
+        for (mode, state) in modes_state.modes.iter_mut() {
+            let Some(overheat) = weapon.config.firing_modes.get(mode).and_then(|config| config.overheat.as_ref()) else {
+                continue;
+            };
+            state.dissipate_heat(overheat, frame.frame, &simulation);
+        }
+    }
+}
+
 pub fn bullet_rollback_system(
     mut commands: Commands,
     frame: Res<FrameCount>,
@@ -750,23 +1328,50 @@ pub fn bullet_rollback_system(
 }
 
 
+// Damage of the bullet multiplied by the zone that was hit, if the target has hit zones
 fn apply_bullet_dommage(
     commands: &mut Commands,
     target_entity: Entity,
     bullet: &Bullet,
-    
+    opt_zone: Option<(HitZoneKind, f32)>,
+    mut opt_dmg_accumulator: Option<Mut<'_, DamageAccumulator, >>
+) {
+    let damage = opt_zone.map_or(bullet.damage, |(_, multiplier)| round(bullet.damage * multiplier));
+    apply_player_damage(commands, target_entity, damage, bullet.player_handle, &bullet.weapon, opt_zone.map(|(zone, _)| zone), Some(bullet.velocity), opt_dmg_accumulator);
+}
+
+// Add a hit of a player to the damage accumulated by the target this frame
+fn apply_player_damage(
+    commands: &mut Commands,
+    target_entity: Entity,
+    damage: f32,
+    player_handle: PlayerHandle,
+    weapon: &KillWeapon,
+    last_hit_zone: Option<HitZoneKind>,
+    // Way the hit travel, for the shields
+    direction: Option<Vec2>,
     mut opt_dmg_accumulator: Option<Mut<'_, DamageAccumulator, >>
 ) {
     if let Some(accumulator) = opt_dmg_accumulator.as_mut() {
         // Update existing accumulator
-        accumulator.total_damage += bullet.damage;
+        accumulator.total_damage += damage;
         accumulator.hit_count += 1;
-        accumulator.last_hit_by = Some(health::HitBy::Player(bullet.player_handle))
+        accumulator.last_hit_by = Some(health::HitBy::Player(player_handle));
+        accumulator.last_hit_zone = last_hit_zone;
+        accumulator.add_player_damage(player_handle, damage);
+        accumulator.add_player_weapon(player_handle, weapon);
+        if let Some(direction) = direction {
+            accumulator.directional.push((direction, damage));
+        }
     } else {
         commands.entity(target_entity).insert(DamageAccumulator{
             hit_count: 1,
-            total_damage: bullet.damage,
-            last_hit_by: Some(health::HitBy::Player(bullet.player_handle)),
+            total_damage: damage,
+            last_hit_by: Some(health::HitBy::Player(player_handle)),
+            last_hit_zone,
+            by_player: vec![(player_handle, damage)],
+            weapons: vec![(player_handle, weapon.clone())],
+            directional: direction.map(|direction| (direction, damage)).into_iter().collect(),
         });
     }
 }
@@ -774,14 +1379,17 @@ fn apply_bullet_dommage(
 
 pub fn bullet_rollback_collision_system(
     mut commands: Commands,
+    frame: Res<FrameCount>,
     settings: Res<CollisionSettings>,
-    bullet_query: Query<(Entity, &Transform, &Bullet, &Collider, &CollisionLayer), With<Rollback>>,
+    mut bullet_query: Query<(Entity, &mut Transform, &mut Bullet, &Collider, &CollisionLayer), With<Rollback>>,
     // Query for colliders, get mutable access later only when needed for a specific entity
-    mut collider_query: Query<(Entity, &Transform, &Collider, &CollisionLayer, Option<&Wall>, Option<&Health>, Option<&mut DamageAccumulator>), (Without<Bullet>, With<Rollback>)>,
+    rules: Res<GameRules>,
+    mut collider_query: Query<(Entity, &Transform, &Collider, &CollisionLayer, Option<&Wall>, Option<&Health>, Option<&mut DamageAccumulator>, Option<&Player>, Has<Respawning>, Option<&HitZones>), (Without<Bullet>, With<Rollback>)>,
+    mut impact_events: ResMut<RollbackEvents<BulletImpactEvent>>,
 ) {
     let mut bullets_to_despawn_set = HashSet::new(); // Use HashSet for efficient duplicate avoidance and checks
 
-    for (bullet_entity, bullet_transform, bullet, bullet_collider, bullet_layer) in bullet_query.iter() {
+    for (bullet_entity, mut bullet_transform, mut bullet, bullet_collider, bullet_layer) in bullet_query.iter_mut() {
         // Skip already processed bullets that are marked for despawn
         if bullets_to_despawn_set.contains(&bullet_entity) {
             continue;
@@ -789,40 +1397,42 @@ pub fn bullet_rollback_collision_system(
 
         let mut actual_collisions = Vec::new();
 
-        // Phase 1: Identify all entities this bullet is colliding with
-        for (target_entity, target_transform, target_collider, target_layer, _opt_wall, _opt_health, _opt_accumulator) in collider_query.iter() { // Note: iter() not iter_mut() for the broad phase
-            if !settings.layer_matrix[bullet_layer.0 as usize][target_layer.0 as usize] {
+        // Segment travelled by the bullet this frame, a new bullet has not moved yet
+        let end = bullet_transform.translation.truncate();
+        let start = if bullet.distance_traveled > 0. { round_vec2(end - bullet.velocity) } else { end };
+
+        // Phase 1: Identify all entities this bullet is colliding with along its path
+        for (target_entity, target_transform, target_collider, target_layer, _opt_wall, _opt_health, _opt_accumulator, opt_player, respawning, opt_zones) in collider_query.iter() { // Note: iter() not iter_mut() for the broad phase
+            // With friendly fire the bullets hit the other players, never the shooter
+            let friendly_hit = rules.friendly_fire() && opt_player.map_or(false, |p| p.handle != bullet.player_handle);
+            if respawning || (!settings.layer_matrix[bullet_layer.0 as usize][target_layer.0 as usize] && !friendly_hit) {
                 continue;
             }
 
-            if is_colliding(bullet_transform, bullet_collider, target_transform, target_collider) {
-                actual_collisions.push(target_entity); // Store only the entity ID for now
+            // Characters with hit zones are hit on the zones instead of their collider
+            if let Some(zones) = opt_zones {
+                if let Some((toi, zone)) = zones.sweep(start, end, bullet_collider, target_transform) {
+                    actual_collisions.push((toi, target_entity, Some((zone.zone, zone.damage_multiplier)), IVec2::ZERO));
+                }
+            } else if let Some(hit) = sweep_collision_hit(start, end, bullet_collider, target_transform, target_collider) {
+                actual_collisions.push((hit.toi, target_entity, None, hit.normal)); // Store only the entity ID for now
             }
         }
 
-        // Phase 2: Sort colliding entities by their Entity ID for deterministic processing
-        actual_collisions.sort_by_key(|e| e.index());
+        // Phase 2: Sort colliding entities by time of impact then Entity ID for deterministic processing
+        actual_collisions.sort_by_key(|(toi, e, ..)| (*toi, e.index()));
 
         // Phase 3: Process sorted collisions
-        for &collided_target_entity in actual_collisions.iter() {
+        let mut bounce = None;
+        for &(toi, collided_target_entity, opt_zone, normal) in actual_collisions.iter() {
             // Now, get mutable access to the components of the specific target entity
-            if let Ok((_, target_transform, _target_collider, _target_layer, opt_wall, opt_health, opt_accumulator_mut)) = collider_query.get_mut(collided_target_entity) {
+            if let Ok((_, target_transform, _target_collider, _target_layer, opt_wall, opt_health, opt_accumulator_mut, ..)) = collider_query.get_mut(collided_target_entity) {
                 
                 if opt_health.is_some() {
-                    // Apply damage (using the refactored logic from your apply_bullet_dommage function)
-                    if let Some(mut accumulator) = opt_accumulator_mut {
-                        // Update existing accumulator
-                        accumulator.total_damage += bullet.damage;
-                        accumulator.hit_count += 1;
-                        accumulator.last_hit_by = Some(health::HitBy::Player(bullet.player_handle));
-                    } else {
-                        // Insert new accumulator if it doesn't exist
-                        commands.entity(collided_target_entity).insert(DamageAccumulator {
-                            hit_count: 1,
-                            total_damage: bullet.damage,
-                            last_hit_by: Some(health::HitBy::Player(bullet.player_handle)),
-                        });
-                    }
+                    apply_bullet_dommage(&mut commands, collided_target_entity, &bullet, opt_zone, opt_accumulator_mut);
+                }
+                if opt_wall.is_some() {
+                    impact_events.send(frame.frame, BulletImpactEvent { position: round_vec2(point_at_toi(start, end, toi)), normal });
                 }
 
                 let mut should_bullet_despawn_now = false;
@@ -830,7 +1440,15 @@ pub fn bullet_rollback_collision_system(
                     BulletType::Standard { .. } => {
                         should_bullet_despawn_now = true;
                     },
-                    BulletType::Explosive { blast_radius, .. } => {
+                    BulletType::Explosive { blast_radius, damage, explosive_damage_multiplier, .. } => {
+                        spawn_explosion(
+                            &mut commands,
+                            round_vec2(point_at_toi(start, end, toi)),
+                            blast_radius,
+                            damage * explosive_damage_multiplier,
+                            Some(health::HitBy::Player(bullet.player_handle)),
+                            frame.frame,
+                        );
                         should_bullet_despawn_now = true;
                     },
                     BulletType::Piercing { .. } => {
@@ -838,6 +1456,13 @@ pub fn bullet_rollback_collision_system(
                             should_bullet_despawn_now = true;
                         }
                     },
+                    BulletType::Ricochet { damage_retention, .. } => {
+                        if opt_wall.is_some() && bullet.bounces_left > 0 {
+                            bounce = Some((toi, normal, damage_retention));
+                            break; // The rest of the segment is behind the wall
+                        }
+                        should_bullet_despawn_now = true;
+                    },
                 }
 
                 if should_bullet_despawn_now {
@@ -846,6 +1471,18 @@ pub fn bullet_rollback_collision_system(
                 }
             }
         }
+
+        // Back at the impact, a bit out of the wall so the next frame don't hit it again,
+        // the rest of the movement of this frame is lost
+        if let Some((toi, normal, damage_retention)) = bounce {
+            let impact = round_vec2(point_at_toi(start, end, toi));
+            let position = round_vec2(impact + normal.as_vec2() / NORMAL_SCALE as f32 * RICOCHET_WALL_OFFSET);
+            bullet_transform.translation.x = position.x;
+            bullet_transform.translation.y = position.y;
+            bullet.velocity = round_vec2(reflect(bullet.velocity, normal));
+            bullet.damage = round(bullet.damage * damage_retention.clamp(0.0, 1.0));
+            bullet.bounces_left -= 1;
+        }
     }
 
     // Convert HashSet to Vec and sort by entity ID for deterministic despawning
@@ -876,13 +1513,13 @@ pub fn weapon_inventory_system(
             
             // For simplicity, we're using commands to add/remove components
             // In a real implementation, you might want to use a Visibility component
-            if let Ok((_, mut visibility)) = weapon_entities.get_mut(*weapon_entity) {
+            if let Some((_, mut visibility)) = weapon_entity.get_mut(&mut weapon_entities) {
                 if is_active {
-                    commands.entity(*weapon_entity)
+                    commands.entity(weapon_entity.entity())
                         .insert(ActiveWeapon);
                     *visibility = Visibility::Visible;
                 } else {
-                    commands.entity(*weapon_entity)
+                    commands.entity(weapon_entity.entity())
                         .remove::<ActiveWeapon>();
                     *visibility = Visibility::Hidden;
                 }
@@ -892,30 +1529,280 @@ pub fn weapon_inventory_system(
     }
 }
 
+// Non rollback system to apply the tint of the weapon on its sprite
+pub fn weapon_tint_system(
+    query: Query<(&Children, &WeaponTint), Changed<WeaponTint>>,
+    mut query_sprite: Query<&mut Sprite>,
+) {
+    for (childs, tint) in query.iter() {
+        for child in childs.iter() {
+            if let Ok(mut sprite) = query_sprite.get_mut(*child) {
+                sprite.color = tint.0;
+            }
+        }
+    }
+}
+
+// Hot reload of the weapons config, dev only as it touch the rollback state outside of the rollback schedule
 pub fn weapons_config_update_system(
-    _asset_server: Res<AssetServer>,
-    
+    mut commands: Commands,
+    global_assets: Res<GlobalAsset>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    sprint_sheet_assets: Res<Assets<SpriteSheetConfig>>,
+
     weapons_config: Res<Assets<WeaponsConfig>>,
 
     mut ev_asset: EventReader<AssetEvent<WeaponsConfig>>,
 
-    mut query_weapons: Query<(&Children, Entity, &mut Weapon)>,
+    mut query_weapons: Query<(Entity, Option<&Children>, &mut Weapon, &mut WeaponState, &mut WeaponModesState, &mut Transform)>,
+    mut query_inventory: Query<(&mut WeaponInventory, Option<&Backpack>)>,
 ) {
 
     for event in ev_asset.read() {
-        if let AssetEvent::Modified { id } = event {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(weapons_config) = weapons_config.get(*id) else {
+            continue;
+        };
+
+        let backpacks: HashMap<Entity, Backpack> = query_inventory.iter()
+            .filter_map(|(inventory, backpack)| backpack.map(|backpack| (inventory, *backpack)))
+            .flat_map(|(inventory, backpack)| inventory.weapons.iter().map(move |(link, _)| (link.entity(), backpack)))
+            .collect();
+
+        for (entity, opt_children, mut weapon, mut weapon_state, mut modes_state, mut transform) in query_weapons.iter_mut() {
+            let Some(config) = weapons_config.weapons.get(&weapon.config.name) else {
+                continue;
+            };
 
-            if let Some(weapons_config) = weapons_config.get(*id) {
-                for (_childs, _entity, mut weapon) in query_weapons.iter_mut() {
-                    if let Some(config) = weapons_config.0.get(&weapon.config.name) {
-                        weapon.config = config.config.clone();
-                        weapon.sprite_config = config.sprite_config.clone();
+            sync_weapon_state(&config.config, &mut weapon_state, &mut modes_state, backpacks.get(&entity));
+
+            let sprite = &config.sprite_config;
+            let sprite_changed = sprite.name != weapon.sprite_config.name || sprite.index != weapon.sprite_config.index;
+            if sprite_changed {
+                if let (Some(map_layers), Some(animation_handle)) = (global_assets.spritesheets.get(&sprite.name), global_assets.animations.get(&sprite.name)) {
+                    commands.entity(entity).insert(
+                        AnimationBundle::new(map_layers.clone(), animation_handle.clone(), sprite.index, bmap!("body" => String::new())));
+
+                    if let Some(children) = opt_children {
+                        for child in children.iter() {
+                            commands.entity(*child).despawn_recursive();
+                        }
                     }
-                
+                    if let Some(spritesheet_config) = map_layers.get("body").and_then(|handle| sprint_sheet_assets.get(handle)) {
+                        create_child_sprite(&mut commands, &asset_server, &mut texture_atlas_layouts, entity, spritesheet_config, 0);
+                    }
+                } else {
+                    warn!("no sprite sheet {} for the weapon {}", sprite.name, weapon.config.name);
                 }
             }
 
+            transform.translation.x = sprite.weapon_offset.x;
+            transform.translation.y = sprite.weapon_offset.y;
+
+            match sprite.tint {
+                Some((r, g, b)) => { commands.entity(entity).insert(WeaponTint(Color::srgb(r, g, b))); },
+                None => { commands.entity(entity).remove::<WeaponTint>(); },
+            }
+
+            // A weapon that became melee start with a new durability
+            match (weapon.config.melee(), config.config.melee()) {
+                (None, Some(melee)) => { commands.entity(entity).insert(MeleeState::new(melee)); },
+                (Some(_), None) => { commands.entity(entity).remove::<MeleeState>(); },
+                _ => {}
+            }
+
+            weapon.config = config.config.clone();
+            weapon.sprite_config = config.sprite_config.clone();
+            weapon.audio_config = config.audio_config.clone();
+        }
+
+        // The inventories keep their own copy of the weapons
+        for (mut inventory, _) in query_inventory.iter_mut() {
+            for (_, weapon) in inventory.weapons.iter_mut() {
+                if let Some(config) = weapons_config.weapons.get(&weapon.config.name) {
+                    weapon.config = config.config.clone();
+                    weapon.sprite_config = config.sprite_config.clone();
+                    weapon.audio_config = config.audio_config.clone();
+                }
+            }
+        }
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fire_direction_follow_the_aim() {
+        assert_eq!(fire_direction(IVec2::new(0, 200), &FacingDirection::Left), Vec2::Y);
+        assert_eq!(fire_direction(IVec2::new(-30, 0), &FacingDirection::Right), Vec2::NEG_X);
+    }
+
+    #[test]
+    fn test_fire_direction_without_aim_use_the_facing() {
+        assert_eq!(fire_direction(IVec2::ZERO, &FacingDirection::Left), Vec2::NEG_X);
+        assert_eq!(fire_direction(IVec2::ZERO, &FacingDirection::Right), Vec2::X);
+        assert!(fire_direction(IVec2::ZERO, &FacingDirection::Right).is_finite());
+    }
+
+    fn rifle(mag_limit: u32) -> WeaponConfig {
+        let mode = FiringModeConfig {
+            firing_rate: 10.0,
+            firing_mode: FiringMode::Automatic {},
+            spread: 0.0,
+            recoil: 0.0,
+            bullet_type: BulletType::default(),
+            range: 0.0,
+            reload_time_seconds: 1.0,
+            mag: MagBulletConfig::Mag { mag_size: 30, mag_limit },
+            overheat: None,
+            trail: None,
+        };
+        WeaponConfig {
+            name: "rifle".to_string(),
+            kind: WeaponKind::Firearm,
+            default_firing_mode: "auto".to_string(),
+            firing_modes: [("auto".to_string(), mode)].into_iter().collect(),
+        }
+    }
+
+    fn inventory(active: usize) -> WeaponInventory {
+        let weapons = (0..4).map(|index| {
+            let mut config = rifle(4);
+            config.name = format!("weapon-{}", index);
+            let sprite_config = WeaponSpriteConfig { name: config.name.clone(), index, weapon_offset: Vec2::ZERO, bullet_offset_left: Vec2::ZERO, bullet_offset_right: Vec2::ZERO, tint: None };
+            (EntityLink::new(Entity::from_raw(index as u32)), Weapon { config, sprite_config, audio_config: default(), rarity: default() })
+        }).collect();
+        WeaponInventory { active_weapon_index: active, weapons, ..default() }
+    }
+
+    fn names(inventory: &WeaponInventory) -> Vec<&str> {
+        inventory.weapons.iter().map(|(_, weapon)| weapon.config.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_move_slot_shift_the_weapons_between() {
+        let mut inventory = inventory(0);
+        assert!(inventory.move_slot(0, 2));
+        assert_eq!(names(&inventory), vec!["weapon-1", "weapon-2", "weapon-0", "weapon-3"]);
+        assert!(inventory.move_slot(3, 1));
+        assert_eq!(names(&inventory), vec!["weapon-1", "weapon-3", "weapon-2", "weapon-0"]);
+    }
+
+    #[test]
+    fn test_move_slot_keep_the_active_weapon() {
+        // Moved itself, moved over from before, moved over from after and untouched
+        for (active, from, to) in [(1, 1, 3), (2, 0, 3), (1, 3, 0), (3, 0, 1)] {
+            let mut inventory = inventory(active);
+            let before = inventory.active_weapon().unwrap().1.config.name.clone();
+            assert!(inventory.move_slot(from, to));
+            assert_eq!(inventory.active_weapon().unwrap().1.config.name, before);
         }
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_move_slot_refuse_the_broken_moves() {
+        let mut inventory = inventory(1);
+        assert!(!inventory.move_slot(2, 2));
+        assert!(!inventory.move_slot(0, 4));
+        assert!(!inventory.move_slot(5, 0));
+        assert_eq!(names(&inventory), vec!["weapon-0", "weapon-1", "weapon-2", "weapon-3"]);
+        assert_eq!(inventory.active_weapon_index, 1);
+    }
+
+    fn reserve_after_sync(mag_quantity: u32, backpack: Option<&Backpack>) -> u32 {
+        let config = rifle(4);
+        let mut weapon_state = WeaponState { last_fire_frame: 0, active_mode: "auto".to_string() };
+        let mut modes_state = WeaponModesState::default();
+        modes_state.modes.insert("auto".to_string(), WeaponModeState { mag_ammo: 30, mag_quantity, mag_size: 30, ..Default::default() });
+        sync_weapon_state(&config, &mut weapon_state, &mut modes_state, backpack);
+        modes_state.modes["auto"].mag_quantity
+    }
+
+    #[test]
+    fn test_sync_keep_the_room_of_the_backpack() {
+        let backpack = Backpack { extra_mags_percent: 50 };
+        assert_eq!(reserve_after_sync(6, Some(&backpack)), 6);
+        // Still clamped to the limit with the backpack
+        assert_eq!(reserve_after_sync(9, Some(&backpack)), 6);
+        assert_eq!(reserve_after_sync(6, None), 4);
+    }
+
+    fn overheat() -> OverheatConfig {
+        OverheatConfig { heat_per_shot: 40.0, dissipation_per_second: 60.0, cooldown_seconds: 1.0 }
+    }
+
+    #[test]
+    fn test_overheat_at_the_max_heat() {
+        let simulation = SimulationConfig { tick_rate: 60, time_scale: 1.0 };
+        let mut state = WeaponModeState::default();
+        assert!(!state.add_heat(&overheat(), 10, &simulation));
+        assert!(!state.add_heat(&overheat(), 11, &simulation));
+        assert!(!state.is_overheated(11));
+        // Clamped to the max
+        assert!(state.add_heat(&overheat(), 12, &simulation));
+        assert_eq!(state.heat_ratio(), 1.0);
+        assert!(state.is_overheated(12));
+        assert!(state.is_overheated(71));
+        assert!(!state.is_overheated(72));
+    }
+
+    #[test]
+    fn test_heat_dissipate_only_outside_the_cooldown() {
+        let simulation = SimulationConfig { tick_rate: 60, time_scale: 1.0 };
+        let mut state = WeaponModeState::default();
+        state.add_heat(&overheat(), 0, &simulation);
+        state.dissipate_heat(&overheat(), 1, &simulation);
+        assert_eq!(state.heat, 39.0);
+
+        state.add_heat(&overheat(), 2, &simulation);
+        state.add_heat(&overheat(), 3, &simulation);
+        state.dissipate_heat(&overheat(), 30, &simulation);
+        assert_eq!(state.heat, MAX_HEAT);
+        // Back to cold once the cooldown is over
+        state.dissipate_heat(&overheat(), 63, &simulation);
+        assert_eq!(state.heat, 0.0);
+        assert!(state.overheat.is_none());
+        state.dissipate_heat(&overheat(), 64, &simulation);
+        assert_eq!(state.heat, 0.0);
+    }
+
+    fn player_animation() -> AnimationMapConfig {
+        let path = format!("{}/../../assets/ZombieShooter/Sprites/Character/player_animation.ron", env!("CARGO_MANIFEST_DIR"));
+        let bytes = std::fs::read(&path).unwrap();
+        ron::de::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_player_reload_has_mag_triggers() {
+        let config = player_animation();
+        assert!(config.animations.contains_key(RELOAD_ANIMATION));
+        assert_eq!(config.triggers_at(RELOAD_ANIMATION, 0).collect::<Vec<_>>(), vec!["mag_out"]);
+        assert_eq!(config.triggers_at(RELOAD_ANIMATION, 1).collect::<Vec<_>>(), vec![MAG_IN_TRIGGER]);
+        assert_eq!(config.triggers_at("Run", 1).count(), 0);
+        // Second frame of a two frames animation, in the middle of the reload
+        assert_eq!(config.trigger_fraction(RELOAD_ANIMATION, MAG_IN_TRIGGER), Some(0.5));
+        assert_eq!(config.trigger_fraction("Idle", MAG_IN_TRIGGER), None);
+    }
+
+    #[test]
+    fn test_mag_in_at_the_trigger_of_the_reload() {
+        let simulation = SimulationConfig::default();
+        let mut inventory = WeaponInventory::default();
+        assert!(!inventory.is_mag_in(100, Some(0.5)));
+
+        inventory.start_reload(100, 1.0, &simulation);
+        let duration = inventory.reload_timer.unwrap().duration;
+        assert!(!inventory.is_mag_in(100 + duration / 2 - 1, Some(0.5)));
+        assert!(inventory.is_mag_in(100 + duration / 2, Some(0.5)));
+        assert!(!inventory.is_reloading_over(100 + duration / 2));
+        // Without the trigger the ammo wait the end of the reload
+        assert!(!inventory.is_mag_in(100 + duration - 1, None));
+    }
+}
@@ -0,0 +1,325 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_ggrs::ConfirmedFrameCount;
+use ggrs::PlayerHandle;
+
+use crate::{camera::{grading::VignettePulse, shake::CameraShake}, character::player::{LocalPlayer, Player}, collider::{sweep_collision_hit, Collider, ColliderShape, Wall}, frame::{confirmed_frame, FrameCount}, plugins::AppState, rules::{GameMode, GameRules}};
+
+use super::{Bullet, BulletType};
+
+// Hostile bullets passing close to a local player without hitting, only presentation.
+// Only the bullets fired on a confirmed frame and not bounced since are used, their line
+// is known so their predicted position is as good as the confirmed one and it is compared
+// with the predicted position of the player, both of the same frame. They are put in a
+// grid of their own, each local player look at the ones around and a bullet reaching the
+// closest point of its line this frame, near but not on the player and without a wall
+// between, is a near miss. It is sent once per bullet for the whizz, the camera shake and
+// the pulse of the vignette. The enemies have no projectile for now, only the bullets of
+// the other players can be hostile.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct NearMissSettings {
+    pub enabled: bool,
+    // Line of the bullet closer than this to the player
+    pub radius: f32,
+    // Closer than this it is most likely a hit, the collision decide
+    pub min_distance: f32,
+    // Bullets looked at around the player, more than a bullet travel in a frame
+    pub detect_distance: f32,
+    pub shake_trauma: f32,
+    pub vignette_pulse: f32,
+}
+
+impl Default for NearMissSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            radius: 40.0,
+            min_distance: 10.0,
+            detect_distance: 200.0,
+            shake_trauma: 0.3,
+            vignette_pulse: 0.3,
+        }
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct NearMissEvent {
+    pub bullet: Entity,
+    pub player: PlayerHandle,
+    // From the line of the bullet to the player
+    pub distance: f32,
+}
+
+// Bullets bucketed by their position, the steering grid is for the obstacles
+#[derive(Default)]
+struct BulletGrid {
+    cells: HashMap<IVec2, Vec<(Entity, Vec2)>>,
+}
+
+impl BulletGrid {
+    const CELL_SIZE: f32 = 128.0;
+
+    fn cell(position: Vec2) -> IVec2 {
+        (position / Self::CELL_SIZE).floor().as_ivec2()
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    fn insert(&mut self, entity: Entity, position: Vec2) {
+        self.cells.entry(Self::cell(position)).or_default().push((entity, position));
+    }
+
+    // Bullets within `distance` of the position, sorted by entity
+    fn query(&self, position: Vec2, distance: f32) -> Vec<(Entity, Vec2)> {
+        let min = Self::cell(position - Vec2::splat(distance));
+        let max = Self::cell(position + Vec2::splat(distance));
+
+        let mut result = vec![];
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                if let Some(bucket) = self.cells.get(&IVec2::new(x, y)) {
+                    result.extend(bucket.iter().filter(|(_, p)| p.distance(position) <= distance));
+                }
+            }
+        }
+        result.sort_by_key(|(entity, _)| entity.index());
+        result
+    }
+}
+
+#[derive(Resource, Default)]
+struct NearMissState {
+    grid: BulletGrid,
+    // Bullets already sent, never twice
+    missed: HashSet<Entity>,
+}
+
+// A bullet of another player can only hurt with the friendly fire, always on in the versus
+fn is_hostile(rules: Option<&GameRules>, bullet: &Bullet, player: PlayerHandle) -> bool {
+    bullet.player_handle != player
+        && rules.map_or(false, |rules| rules.friendly_fire || rules.mode == GameMode::Deathmatch)
+}
+
+// Where the bullet was on the confirmed frame, the transform is the one of the predicted
+// frame. A bullet move in a straight line at its velocity until a ricochet so it is found
+// back along it, none for a bullet that bounced, the bounce could be in the predicted frames.
+pub(crate) fn confirmed_position(bullet: &Bullet, position: Vec2, frames_ahead: u32) -> Option<Vec2> {
+    if frames_ahead == 0 {
+        return Some(position);
+    }
+    if let BulletType::Ricochet { bounces, .. } = bullet.bullet_type {
+        if bullet.bounces_left != bounces {
+            return None;
+        }
+    }
+    Some(position - bullet.velocity * frames_ahead as f32)
+}
+
+// Distance of the line of the bullet to the player when it pass it this frame. None when
+// it is not reached this frame, too close or too far, or when a wall is between the
+// bullet and the point it pass or between that point and the player.
+fn near_miss_distance(
+    bullet_position: Vec2,
+    velocity: Vec2,
+    player_position: Vec2,
+    settings: &NearMissSettings,
+    walls: &[(&Transform, &Collider)],
+) -> Option<f32> {
+    let speed = velocity.length();
+    if speed <= 0.0 {
+        return None;
+    }
+    let direction = velocity / speed;
+    let to_player = player_position - bullet_position;
+    // Ahead on its line, reached before the next frame
+    let along = to_player.dot(direction);
+    if along < 0.0 || along > speed {
+        return None;
+    }
+    let distance = direction.perp_dot(to_player).abs();
+    if distance <= settings.min_distance || distance > settings.radius {
+        return None;
+    }
+
+    let passing = bullet_position + direction * along;
+    let ray = Collider { shape: ColliderShape::Circle { radius: 0.0 }, offset: Vec2::ZERO };
+    let occluded = walls.iter().any(|(transform, collider)| {
+        sweep_collision_hit(bullet_position, passing, &ray, transform, collider).is_some()
+            || sweep_collision_hit(passing, player_position, &ray, transform, collider).is_some()
+    });
+    if occluded {
+        return None;
+    }
+    Some(distance)
+}
+
+
+// SYSTEMS
+
+fn detect_near_misses(
+    settings: Res<NearMissSettings>,
+    rules: Option<Res<GameRules>>,
+    frame: Res<FrameCount>,
+    confirmed: Option<Res<ConfirmedFrameCount>>,
+    mut state: ResMut<NearMissState>,
+    mut near_misses: EventWriter<NearMissEvent>,
+    local_query: Query<(&Player, &GlobalTransform), With<LocalPlayer>>,
+    bullet_query: Query<(Entity, &Bullet, &GlobalTransform)>,
+    wall_query: Query<(&Transform, &Collider), With<Wall>>,
+) {
+    let state = state.as_mut();
+    state.missed.retain(|entity| bullet_query.contains(*entity));
+    if !settings.enabled {
+        return;
+    }
+
+    let Some(confirmed_frame) = confirmed_frame(&frame, confirmed) else {
+        return;
+    };
+
+    let frames_ahead = frame.frame.saturating_sub(confirmed_frame);
+    state.grid.clear();
+    for (entity, bullet, transform) in bullet_query.iter() {
+        if bullet.created_at > confirmed_frame || state.missed.contains(&entity) {
+            continue;
+        }
+        // The predicted position, the same frame as the one of the players
+        let position = transform.translation().truncate();
+        if confirmed_position(bullet, position, frames_ahead).is_some() {
+            state.grid.insert(entity, position);
+        }
+    }
+
+    let walls: Vec<(&Transform, &Collider)> = wall_query.iter().collect();
+
+    for (player, transform) in local_query.iter() {
+        let position = transform.translation().truncate();
+        for (bullet_entity, bullet_position) in state.grid.query(position, settings.detect_distance) {
+            let Ok((_, bullet, _)) = bullet_query.get(bullet_entity) else {
+                continue;
+            };
+            if state.missed.contains(&bullet_entity) || !is_hostile(rules.as_deref(), bullet, player.handle) {
+                continue;
+            }
+            let Some(distance) = near_miss_distance(bullet_position, bullet.velocity, position, &settings, &walls) else {
+                continue;
+            };
+
+            state.missed.insert(bullet_entity);
+            near_misses.send(NearMissEvent { bullet: bullet_entity, player: player.handle, distance });
+        }
+    }
+}
+
+// Closer the miss, stronger the effect
+fn near_miss_effects(
+    settings: Res<NearMissSettings>,
+    mut near_misses: EventReader<NearMissEvent>,
+    mut shake: ResMut<CameraShake>,
+    mut pulse: ResMut<VignettePulse>,
+) {
+    for event in near_misses.read() {
+        let closeness = 1.0 - (event.distance / settings.radius.max(1.0)).clamp(0.0, 1.0);
+        let strength = 0.5 + closeness * 0.5;
+        shake.add_trauma(settings.shake_trauma * strength);
+        pulse.add(settings.vignette_pulse * strength);
+    }
+}
+
+fn clear_near_miss_state(mut state: ResMut<NearMissState>) {
+    *state = NearMissState::default();
+}
+
+
+pub struct NearMissPlugin;
+
+impl Plugin for NearMissPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NearMissSettings>()
+            .register_type::<NearMissSettings>()
+            .init_resource::<NearMissState>()
+            .add_event::<NearMissEvent>()
+            .add_systems(Update, (detect_near_misses, near_miss_effects).chain().run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), clear_near_miss_state);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapons::{KillWeapon, WeaponRarity};
+
+    fn bullet(bullet_type: BulletType, bounces_left: u8) -> Bullet {
+        Bullet {
+            velocity: Vec2::new(10.0, 0.0),
+            bullet_type,
+            damage: 10.0,
+            range: 1000.0,
+            distance_traveled: 0.0,
+            player_handle: 0,
+            created_at: 0,
+            bounces_left,
+            weapon: KillWeapon { name: "Pistol".to_string(), rarity: WeaponRarity::Common },
+        }
+    }
+
+    #[test]
+    fn test_confirmed_position_is_back_along_the_line() {
+        let standard = bullet(BulletType::Standard { damage: 10.0, speed: 10.0 }, 0);
+        assert_eq!(confirmed_position(&standard, Vec2::new(100.0, 5.0), 0), Some(Vec2::new(100.0, 5.0)));
+        assert_eq!(confirmed_position(&standard, Vec2::new(100.0, 5.0), 3), Some(Vec2::new(70.0, 5.0)));
+    }
+
+    #[test]
+    fn test_bounced_ricochet_is_only_used_when_confirmed() {
+        let ricochet = BulletType::Ricochet { damage: 10.0, speed: 10.0, bounces: 2, damage_retention: 0.5 };
+        assert_eq!(confirmed_position(&bullet(ricochet, 2), Vec2::ZERO, 2), Some(Vec2::new(-20.0, 0.0)));
+        assert_eq!(confirmed_position(&bullet(ricochet, 1), Vec2::ZERO, 2), None);
+        assert_eq!(confirmed_position(&bullet(ricochet, 1), Vec2::ZERO, 0), Some(Vec2::ZERO));
+    }
+
+    #[test]
+    fn test_bullet_grid_query_is_sorted_and_bounded() {
+        let mut grid = BulletGrid::default();
+        grid.insert(Entity::from_raw(3), Vec2::new(150.0, 0.0));
+        grid.insert(Entity::from_raw(1), Vec2::new(-150.0, 0.0));
+        grid.insert(Entity::from_raw(2), Vec2::new(500.0, 0.0));
+
+        let found: Vec<_> = grid.query(Vec2::ZERO, 200.0).iter().map(|(entity, _)| entity.index()).collect();
+        assert_eq!(found, vec![1, 3]);
+    }
+
+    fn wall() -> (Transform, Collider) {
+        (
+            Transform::from_translation(Vec3::new(50.0, 15.0, 0.0)),
+            Collider { shape: ColliderShape::Rectangle { width: 10.0, height: 10.0 }, offset: Vec2::ZERO },
+        )
+    }
+
+    #[test]
+    fn test_near_miss_distance_when_passing_the_player() {
+        let settings = NearMissSettings::default();
+        let velocity = Vec2::new(100.0, 0.0);
+        assert_eq!(near_miss_distance(Vec2::ZERO, velocity, Vec2::new(50.0, 30.0), &settings, &[]), Some(30.0));
+        // Behind, after the next frame, on the player or too far
+        assert_eq!(near_miss_distance(Vec2::ZERO, velocity, Vec2::new(-50.0, 30.0), &settings, &[]), None);
+        assert_eq!(near_miss_distance(Vec2::ZERO, velocity, Vec2::new(150.0, 30.0), &settings, &[]), None);
+        assert_eq!(near_miss_distance(Vec2::ZERO, velocity, Vec2::new(50.0, 5.0), &settings, &[]), None);
+        assert_eq!(near_miss_distance(Vec2::ZERO, velocity, Vec2::new(50.0, 60.0), &settings, &[]), None);
+    }
+
+    #[test]
+    fn test_no_near_miss_through_a_wall() {
+        let settings = NearMissSettings::default();
+        let (transform, collider) = wall();
+        let walls = [(&transform, &collider)];
+        let velocity = Vec2::new(100.0, 0.0);
+        // The wall is between the line and the player over it, not for the one under it
+        assert_eq!(near_miss_distance(Vec2::ZERO, velocity, Vec2::new(50.0, 30.0), &settings, &walls), None);
+        assert_eq!(near_miss_distance(Vec2::ZERO, velocity, Vec2::new(50.0, -30.0), &settings, &walls), Some(30.0));
+    }
+}
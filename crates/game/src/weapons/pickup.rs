@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+
+use crate::{global_asset::GlobalAsset, plugins::AppState, trade::TradePickup};
+
+use super::{wall::WallWeapon, WeaponRarity, WeaponsConfig};
+
+// Look of the weapons that can be picked up in the world, only presentation. The sprite
+// take the color of the rarity of its weapon, a glow pulse behind it and the rare ones
+// get a beam going up so they are seen from across the arena. The visuals are children
+// of the pickup and are never part of the rollback. The trade drops get the same glow
+// and beam from the rarity of their kind but keep their own color.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct PickupRaritySettings {
+    pub enabled: bool,
+    // Size of the glow, multiplied by the one of the sprite
+    pub glow_scale: f32,
+    pub glow_alpha: f32,
+    pub beam_width: f32,
+    pub beam_height: f32,
+    pub beam_alpha: f32,
+    // Cycles per second of the pulse
+    pub pulse_speed: f32,
+}
+
+impl Default for PickupRaritySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            glow_scale: 1.8,
+            glow_alpha: 0.35,
+            beam_width: 8.0,
+            beam_height: 400.0,
+            beam_alpha: 0.25,
+            pulse_speed: 0.8,
+        }
+    }
+}
+
+// Put on a pickup once its visuals are spawned
+#[derive(Component)]
+struct PickupRarity(WeaponRarity);
+
+#[derive(Component)]
+struct PickupGlow {
+    base_alpha: f32,
+}
+
+
+// SYSTEMS
+
+fn spawn_pickup_visuals(
+    mut commands: Commands,
+    settings: Res<PickupRaritySettings>,
+    weapons_asset: Res<Assets<WeaponsConfig>>,
+    global_assets: Res<GlobalAsset>,
+    mut pickup_query: Query<(Entity, &WallWeapon, &mut Sprite), Without<PickupRarity>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let Some(weapons_config) = weapons_asset.get(&global_assets.weapons) else {
+        return;
+    };
+
+    for (entity, wall, mut sprite) in pickup_query.iter_mut() {
+        let rarity = weapons_config.weapons.get(&wall.weapon).map_or(WeaponRarity::default(), |weapon| weapon.rarity);
+        let size = sprite.custom_size.unwrap_or(Vec2::splat(32.0));
+        sprite.color = rarity.color();
+        spawn_rarity_visuals(&mut commands, entity, rarity, size, &settings);
+    }
+}
+
+fn spawn_trade_pickup_visuals(
+    mut commands: Commands,
+    settings: Res<PickupRaritySettings>,
+    pickup_query: Query<(Entity, &TradePickup, &Sprite), Without<PickupRarity>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    for (entity, pickup, sprite) in pickup_query.iter() {
+        let size = sprite.custom_size.unwrap_or(Vec2::splat(16.0));
+        spawn_rarity_visuals(&mut commands, entity, pickup.kind.rarity(), size, &settings);
+    }
+}
+
+// Glow behind the pickup and the beam for the rare ones, in the color of the rarity
+fn spawn_rarity_visuals(commands: &mut Commands, entity: Entity, rarity: WeaponRarity, size: Vec2, settings: &PickupRaritySettings) {
+    let color = rarity.color();
+    commands.entity(entity).insert(PickupRarity(rarity)).with_children(|parent| {
+        parent.spawn((
+            Sprite::from_color(color.with_alpha(settings.glow_alpha), size * settings.glow_scale),
+            Transform::from_translation(Vec3::new(0.0, 0.0, -0.1)),
+            PickupGlow { base_alpha: settings.glow_alpha },
+        ));
+        if rarity != WeaponRarity::Common {
+            // From the pickup going up, the bottom on its center
+            parent.spawn((
+                Sprite::from_color(color.with_alpha(settings.beam_alpha), Vec2::new(settings.beam_width, settings.beam_height)),
+                Transform::from_translation(Vec3::new(0.0, settings.beam_height / 2.0, -0.2)),
+                PickupGlow { base_alpha: settings.beam_alpha },
+            ));
+        }
+    });
+}
+
+fn pulse_pickup_glow(
+    time: Res<Time>,
+    settings: Res<PickupRaritySettings>,
+    mut glow_query: Query<(&PickupGlow, &mut Sprite)>,
+) {
+    let pulse = 0.75 + 0.25 * (time.elapsed_secs() * settings.pulse_speed * std::f32::consts::TAU).sin();
+    for (glow, mut sprite) in glow_query.iter_mut() {
+        sprite.color.set_alpha(glow.base_alpha * pulse);
+    }
+}
+
+
+pub struct WeaponPickupPlugin;
+
+impl Plugin for WeaponPickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PickupRaritySettings>()
+            .register_type::<PickupRaritySettings>()
+            .add_systems(Update, ((spawn_pickup_visuals, spawn_trade_pickup_visuals), pulse_pickup_glow).chain().run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,147 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+
+use crate::{character::{dash::DashState, movement::Velocity, player::LocalPlayer}, plugins::AppState};
+
+use super::ActiveWeapon;
+
+// Sway and bob of the weapon sprite of the local player, presentation only. The offset
+// is put on the sprite child after the weapon transform was synced by the simulation,
+// the muzzle use the transform of the weapon so the shots never move with it.
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct WeaponSwaySettings {
+    pub enabled: bool,
+    // Pixels the sprite lag behind per unit of velocity
+    pub velocity_factor: f32,
+    // Pixels the sprite lag sideway per radian the aim turned in a second
+    pub aim_factor: f32,
+    pub max_offset: f32,
+    // Bob while walking, in pixels and in cycles per second at full speed
+    pub bob_amplitude: f32,
+    pub bob_frequency: f32,
+    // Speed considered as full speed for the bob
+    pub bob_speed: f32,
+    // Kick of the sprite when a dash end, it spring back in place
+    pub dash_settle: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl Default for WeaponSwaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            velocity_factor: 0.01,
+            aim_factor: 0.6,
+            max_offset: 3.0,
+            bob_amplitude: 0.8,
+            bob_frequency: 2.0,
+            bob_speed: 300.0,
+            dash_settle: 60.0,
+            stiffness: 180.0,
+            damping: 18.0,
+        }
+    }
+}
+
+// On the sprite of the weapon, the translation it had before any sway
+#[derive(Component)]
+struct WeaponSwaySprite {
+    base_translation: Vec3,
+}
+
+// On the local player
+#[derive(Component, Default)]
+struct WeaponSway {
+    // Spring of the offset, in world space
+    offset: Vec2,
+    speed: Vec2,
+    bob_phase: f32,
+    last_aim_angle: Option<f32>,
+    was_dashing: bool,
+}
+
+
+// SYSTEMS
+
+fn update_weapon_sway(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<WeaponSwaySettings>,
+    mut q_player: Query<(Entity, &Children, &Velocity, Option<&DashState>, Option<&mut WeaponSway>), With<LocalPlayer>>,
+    q_weapon: Query<(&Children, &Transform), With<ActiveWeapon>>,
+    mut q_sprite: Query<(Entity, &mut Transform, Option<&WeaponSwaySprite>), (With<Sprite>, Without<ActiveWeapon>)>,
+) {
+    let dt = time.delta_secs().min(0.1);
+
+    for (entity, children, velocity, dash, sway) in q_player.iter_mut() {
+        let Some(mut sway) = sway else {
+            commands.entity(entity).insert(WeaponSway::default());
+            continue;
+        };
+
+        for (weapon_children, weapon_transform) in children.iter().filter_map(|child| q_weapon.get(*child).ok()) {
+            let aim_angle = weapon_transform.rotation.to_euler(EulerRot::XYZ).2;
+            // Radians per second the aim turned since the last frame
+            let aim_speed = match sway.last_aim_angle {
+                Some(last) if dt > 0.0 => ((aim_angle - last + PI).rem_euclid(TAU) - PI) / dt,
+                _ => 0.0,
+            };
+            sway.last_aim_angle = Some(aim_angle);
+
+            let is_dashing = dash.map_or(false, |dash| dash.is_dashing());
+            if sway.was_dashing && !is_dashing {
+                let direction = dash.map_or(Vec2::ZERO, |dash| dash.dash_direction.normalize_or_zero());
+                sway.speed += direction * settings.dash_settle;
+            }
+            sway.was_dashing = is_dashing;
+
+            // Behind the movement and the turn of the aim
+            let aim_direction = Vec2::from_angle(aim_angle);
+            let target = if settings.enabled {
+                (-velocity.0 * settings.velocity_factor - aim_direction.perp() * aim_speed * settings.aim_factor)
+                    .clamp_length_max(settings.max_offset)
+            } else {
+                Vec2::ZERO
+            };
+
+            let acceleration = (target - sway.offset) * settings.stiffness - sway.speed * settings.damping;
+            sway.speed += acceleration * dt;
+            let speed = sway.speed;
+            sway.offset += speed * dt;
+            sway.offset = sway.offset.clamp_length_max(settings.max_offset * 2.0);
+
+            let speed_ratio = (velocity.0.length() / settings.bob_speed).min(1.0);
+            sway.bob_phase = (sway.bob_phase + dt * settings.bob_frequency * speed_ratio * TAU) % TAU;
+            let bob = if settings.enabled { Vec2::Y * sway.bob_phase.sin().abs() * settings.bob_amplitude * speed_ratio } else { Vec2::ZERO };
+
+            // The sprite is a child of the turned weapon, the offset is brought in its space
+            let local_offset = weapon_transform.rotation.inverse() * (sway.offset + bob).extend(0.0);
+
+            for child in weapon_children.iter() {
+                let Ok((sprite_entity, mut transform, opt_sprite)) = q_sprite.get_mut(*child) else {
+                    continue;
+                };
+                let Some(sprite) = opt_sprite else {
+                    commands.entity(sprite_entity).insert(WeaponSwaySprite { base_translation: transform.translation });
+                    continue;
+                };
+                transform.translation = sprite.base_translation + local_offset;
+            }
+        }
+    }
+}
+
+
+pub struct WeaponSwayPlugin;
+
+impl Plugin for WeaponSwayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WeaponSwaySettings>()
+            .register_type::<WeaponSwaySettings>()
+            .add_systems(Update, update_weapon_sway.run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{hud::graphics::GraphicsPreferences, plugins::AppState};
+
+use super::{BulletTrailConfig, TrailParticleConfig};
+
+// Trails of the bullets, presentation only. The firing mode give the look in the
+// weapons config, the bullet carry it and every frame a piece of line is left between
+// its last and its new position, with sparks thrown around. A rollback can move a
+// bullet far back, no piece is drawn for a jump that long.
+
+// Longer than a bullet travel in a frame
+const MAX_SEGMENT_LENGTH: f32 = 150.0;
+// Under the bullet
+const TRAIL_Z_OFFSET: f32 = -0.2;
+
+// On the bullets of a mode with a trail
+#[derive(Component, Clone, Debug)]
+pub struct BulletTrail(pub BulletTrailConfig);
+
+// Where the last piece ended
+#[derive(Component, Default)]
+struct TrailCursor {
+    last: Option<Vec3>,
+    // Sparks owed since the last one
+    particles: f32,
+}
+
+#[derive(Component)]
+struct TrailFade {
+    timer: Timer,
+    alpha: f32,
+    // Sparks only, units per second
+    velocity: Vec2,
+}
+
+fn rgb((r, g, b): (f32, f32, f32), alpha: f32) -> Color {
+    Color::srgba(r, g, b, alpha)
+}
+
+fn spawn_particles(commands: &mut Commands, config: &TrailParticleConfig, from: Vec3, to: Vec3, count: u32) {
+    let mut rng = rand::thread_rng();
+    let direction = (to - from).truncate().normalize_or_zero();
+    for _ in 0..count {
+        let position = from.lerp(to, rng.gen_range(0.0..1.0));
+        // Mostly away from the line, a bit backward
+        let side = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+        let velocity = (direction.perp() * side - direction * rng.gen_range(0.0..0.5)) * config.speed * rng.gen_range(0.5..1.0);
+        commands.spawn((
+            Sprite::from_color(rgb(config.color, 1.0), Vec2::splat(config.size)),
+            Transform::from_translation(position),
+            TrailFade {
+                timer: Timer::from_seconds(config.lifetime_seconds, TimerMode::Once),
+                alpha: 1.0,
+                velocity,
+            },
+        ));
+    }
+}
+
+
+// SYSTEMS
+
+fn spawn_trail_pieces(
+    mut commands: Commands,
+    time: Res<Time>,
+    preferences: Option<Res<GraphicsPreferences>>,
+    mut q_bullet: Query<(Entity, &GlobalTransform, &BulletTrail, Option<&mut TrailCursor>)>,
+) {
+    for (entity, transform, trail, opt_cursor) in q_bullet.iter_mut() {
+        let position = transform.translation() + Vec3::Z * TRAIL_Z_OFFSET;
+        let Some(mut cursor) = opt_cursor else {
+            commands.entity(entity).insert(TrailCursor { last: Some(position), particles: 0.0 });
+            continue;
+        };
+        let Some(last) = cursor.last.replace(position) else {
+            continue;
+        };
+
+        let offset = (position - last).truncate();
+        let length = offset.length();
+        if length <= f32::EPSILON || length > MAX_SEGMENT_LENGTH {
+            continue;
+        }
+
+        let config = &trail.0;
+        commands.spawn((
+            Sprite::from_color(rgb(config.color, config.alpha), Vec2::new(length, config.width)),
+            Transform::from_translation(last.lerp(position, 0.5)).with_rotation(Quat::from_rotation_z(offset.to_angle())),
+            TrailFade {
+                timer: Timer::from_seconds(config.fade_seconds, TimerMode::Once),
+                alpha: config.alpha,
+                velocity: Vec2::ZERO,
+            },
+        ));
+
+        if let Some(particles) = &config.particles {
+            let per_second = preferences.as_ref().map_or(particles.per_second, |preferences| preferences.particles(particles.per_second));
+            cursor.particles += per_second * time.delta_secs();
+            let count = cursor.particles.floor();
+            cursor.particles -= count;
+            spawn_particles(&mut commands, particles, last, position, count as u32);
+        }
+    }
+}
+
+fn fade_trail_pieces(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_fade: Query<(Entity, &mut TrailFade, &mut Sprite, &mut Transform)>,
+) {
+    for (entity, mut fade, mut sprite, mut transform) in q_fade.iter_mut() {
+        fade.timer.tick(time.delta());
+        if fade.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let alpha = fade.alpha * fade.timer.fraction_remaining();
+        sprite.color = sprite.color.with_alpha(alpha);
+        transform.translation += (fade.velocity * time.delta_secs()).extend(0.0);
+    }
+}
+
+// Nothing left flying into the next match
+fn clear_trail_pieces(
+    mut commands: Commands,
+    q_fade: Query<Entity, With<TrailFade>>,
+) {
+    for entity in q_fade.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+
+pub struct BulletTrailPlugin;
+
+impl Plugin for BulletTrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (spawn_trail_pieces, fade_trail_pieces).run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), clear_trail_pieces);
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+
+use super::FiringMode;
+
+// Trigger of a weapon, one state machine for every firing mode. The pulls are found
+// from the trigger held at the last step so a tap lasting a single frame is never
+// missed, and a pull that land during a burst or a cooldown is queued to fire as soon
+// as the weapon can instead of being dropped.
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TriggerPhase {
+    #[default]
+    Idle,
+    // Held after its shot, manual, shotgun and burst wait for a release
+    Firing,
+    BurstActive { shots_left: u32, next_shot: u32 },
+    // No shot before the frame
+    Cooldown { until: u32 },
+}
+
+#[derive(Component, Reflect, Clone, Debug, Default)]
+pub struct TriggerState {
+    pub phase: TriggerPhase,
+    // Trigger held at the last step
+    pub held: bool,
+    // A pull waiting for the end of the burst or of the cooldown
+    pub queued: bool,
+}
+
+impl TriggerState {
+    // Advance the trigger by one frame, true when a shot is fired on this frame
+    pub fn step(&mut self, mode: &FiringMode, pressed: bool, frame: u32, frames_per_shot: u32) -> bool {
+        let pulled = pressed && !self.held;
+        self.held = pressed;
+        // Holding is enough for the automatic, the other modes need a pull per shot
+        let automatic = matches!(mode, FiringMode::Automatic {});
+
+        match self.phase {
+            TriggerPhase::Cooldown { until } if frame < until => {
+                if pulled && !automatic {
+                    self.queued = true;
+                }
+                return false;
+            }
+            TriggerPhase::BurstActive { shots_left, next_shot } => {
+                if pulled {
+                    self.queued = true;
+                }
+                if frame < next_shot {
+                    return false;
+                }
+                self.shoot_burst(mode, shots_left, frame, frames_per_shot);
+                return true;
+            }
+            _ => {}
+        }
+
+        let start = if automatic { pressed } else { pulled || std::mem::take(&mut self.queued) };
+        if !start {
+            self.phase = if pressed { TriggerPhase::Firing } else { TriggerPhase::Idle };
+            return false;
+        }
+
+        match mode {
+            FiringMode::Burst { pellets_per_shot, .. } => self.shoot_burst(mode, (*pellets_per_shot).max(1), frame, frames_per_shot),
+            _ => self.phase = TriggerPhase::Cooldown { until: frame + frames_per_shot },
+        }
+        true
+    }
+
+    // Fire one shot of a burst that has `shots_left` shots to go
+    fn shoot_burst(&mut self, mode: &FiringMode, shots_left: u32, frame: u32, frames_per_shot: u32) {
+        let cooldown_frames = match mode {
+            FiringMode::Burst { cooldown_frames, .. } => *cooldown_frames,
+            _ => 0,
+        };
+        self.phase = if shots_left > 1 {
+            TriggerPhase::BurstActive { shots_left: shots_left - 1, next_shot: frame + frames_per_shot }
+        } else {
+            TriggerPhase::Cooldown { until: frame + cooldown_frames.max(frames_per_shot) }
+        };
+    }
+
+    // Reload, weapon or mode switch, the burst is cut and the queued pull forgotten
+    // but the weapon still wait for its next shot
+    pub fn interrupt(&mut self) {
+        self.queued = false;
+        if let TriggerPhase::BurstActive { next_shot, .. } = self.phase {
+            self.phase = TriggerPhase::Cooldown { until: next_shot };
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BURST: FiringMode = FiringMode::Burst { pellets_per_shot: 3, cooldown_frames: 20 };
+    const FRAMES_PER_SHOT: u32 = 4;
+
+    // Frames where a shot is fired for the trigger pressed on the given frames
+    fn shots(mode: FiringMode, frames: u32, pressed: impl Fn(u32) -> bool) -> Vec<u32> {
+        let mut trigger = TriggerState::default();
+        (0..frames).filter(|&frame| trigger.step(&mode, pressed(frame), frame, FRAMES_PER_SHOT)).collect()
+    }
+
+    #[test]
+    fn test_burst_tap_fires_the_whole_burst() {
+        assert_eq!(shots(BURST, 40, |frame| frame == 0), vec![0, 4, 8]);
+    }
+
+    #[test]
+    fn test_burst_hold_fires_a_single_burst() {
+        assert_eq!(shots(BURST, 60, |_| true), vec![0, 4, 8]);
+    }
+
+    #[test]
+    fn test_burst_mash_queues_one_burst_after_the_cooldown() {
+        // A tap every other frame, the pulls during the burst and its cooldown start
+        // one burst when the cooldown is over
+        assert_eq!(shots(BURST, 40, |frame| frame % 2 == 0 && frame < 12), vec![0, 4, 8, 28, 32, 36]);
+    }
+
+    #[test]
+    fn test_manual_tap_during_fire_rate_is_queued() {
+        let manual = FiringMode::Manual {};
+        assert_eq!(shots(manual, 10, |frame| frame == 0 || frame == 2), vec![0, 4]);
+    }
+
+    #[test]
+    fn test_manual_hold_fires_once() {
+        assert_eq!(shots(FiringMode::Manual {}, 20, |_| true), vec![0]);
+    }
+
+    #[test]
+    fn test_automatic_hold_follows_fire_rate() {
+        assert_eq!(shots(FiringMode::Automatic {}, 13, |_| true), vec![0, 4, 8, 12]);
+    }
+
+    #[test]
+    fn test_automatic_release_stops_without_queue() {
+        assert_eq!(shots(FiringMode::Automatic {}, 20, |frame| frame < 2 || frame == 3), vec![0]);
+    }
+
+    #[test]
+    fn test_interrupt_cuts_the_burst() {
+        let mut trigger = TriggerState::default();
+        assert!(trigger.step(&BURST, true, 0, FRAMES_PER_SHOT));
+        trigger.step(&BURST, false, 1, FRAMES_PER_SHOT);
+        trigger.step(&BURST, true, 2, FRAMES_PER_SHOT);
+        trigger.interrupt();
+        assert!(!trigger.queued);
+        assert_eq!(trigger.phase, TriggerPhase::Cooldown { until: 4 });
+        assert!(!trigger.step(&BURST, false, 4, FRAMES_PER_SHOT));
+        assert_eq!(trigger.phase, TriggerPhase::Idle);
+    }
+}
@@ -1,9 +1,12 @@
 use bevy::prelude::*;
-use utils::math::calculate_time_remaining_seconds;
+use utils::frame::SimulationConfig;
 
-use crate::{character::player::LocalPlayer, frame::FrameCount, plugins::AppState};
+use crate::{character::player::LocalPlayer, frame::FrameCount, hud::{HudAnchor, HudSlot}, localization::Localization, plugins::AppState};
 
-use super::{WeaponInventory, WeaponModeState, WeaponModesState, WeaponState};
+use super::{melee::MeleeState, Weapon, WeaponInventory, WeaponModeState, WeaponModesState, WeaponState, WeaponTint};
+
+const HEAT_BAR_WIDTH: f32 = 80.0;
+const OVERHEAT_COLOR: Color = Color::srgb(1.0, 0.25, 0.1);
 
 
 #[derive(Component)]
@@ -15,6 +18,13 @@ struct AmmoText;
 #[derive(Component)]
 struct ReloadingText;
 
+// Only shown for the modes that can overheat
+#[derive(Component)]
+struct HeatBar;
+
+#[derive(Component)]
+struct HeatBarFill;
+
 
 
 fn setup_weapon_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -29,72 +39,100 @@ fn setup_weapon_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
             ..Default::default()
         },
         TextLayout::new_with_justify(JustifyText::Center),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(37.0),
-            left: Val::Px(5.0),
-            ..default()
-        },
+        HudSlot::new(HudAnchor::BottomLeft, 2),
     ));
 
 
     commands.spawn((
         CurrentWeaponText,
-        Text::new("Weapon: "),
+        Text::new(""),
         TextFont {
             font: font.clone(),
             font_size: 16.0,
             ..Default::default()
         },
         TextLayout::new_with_justify(JustifyText::Center),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(20.0),
-            left: Val::Px(5.0),
-            ..default()
-        },
+        HudSlot::new(HudAnchor::BottomLeft, 1),
     ));
 
+    // The heat bar follow the ammo on the same line
     commands.spawn((
-        AmmoText,
-        Text::new("Ammo: "),
-        TextFont {
-            font: font,
-            font_size: 16.0,
-            ..Default::default()
-        },
-        TextLayout::new_with_justify(JustifyText::Center),
         Node {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(3.0),
-            left: Val::Px(5.0),
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(10.0),
             ..default()
         },
-    ));
+        HudSlot::new(HudAnchor::BottomLeft, 0),
+    )).with_children(|row| {
+        row.spawn((
+            AmmoText,
+            Text::new(""),
+            TextFont {
+                font: font,
+                font_size: 16.0,
+                ..Default::default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+        ));
+
+        row.spawn((
+            HeatBar,
+            Node {
+                width: Val::Px(HEAT_BAR_WIDTH),
+                height: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+        )).with_children(|parent| {
+            parent.spawn((
+                HeatBarFill,
+                Node {
+                    width: Val::Percent(0.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(OVERHEAT_COLOR),
+            ));
+        });
+    });
 }
 
 fn update_weapons_text(
     frame: Res<FrameCount>,
+    simulation: Res<SimulationConfig>,
+    localization: Res<Localization>,
     q_player: Query<&WeaponInventory, With<LocalPlayer>>,
-    weapon_query: Query<(&WeaponState, &WeaponModesState)>,
+    weapon_query: Query<(&WeaponState, &WeaponModesState, Option<&MeleeState>)>,
     mut q_weapon: Query<&mut Text, (With<CurrentWeaponText>, Without<AmmoText>)>,
     mut q_ammo: Query<&mut Text, (With<AmmoText>, Without<CurrentWeaponText>)>,
     mut q_reloading: Query<&mut Text, (With<ReloadingText>, Without<CurrentWeaponText>, Without<AmmoText>)>,
 ) {
     if let Ok(inventory) = q_player.get_single() {
-        let active_weapon = inventory.active_weapon();
-        if let Ok((state, modes_state)) = weapon_query.get(active_weapon.0) {
+        let Some(active_weapon) = inventory.active_weapon() else {
+            return;
+        };
+        if let Some((state, modes_state, opt_melee)) = active_weapon.0.get(&weapon_query) {
             let active_weapon_state = modes_state.modes.get(&state.active_mode).unwrap();
             if let Ok(mut text) = q_weapon.get_single_mut() {
-                text.0 = format!("Weapon: {} - {}", active_weapon.1.config.name, state.active_mode);
+                text.0 = localization.format("hud.weapon", &[("weapon", &active_weapon.1.config.name), ("mode", &state.active_mode)]);
             }
             if let Ok(mut text) = q_ammo.get_single_mut() {
-                text.0 = format!("Ammo: {} / {}", active_weapon_state.mag_ammo, active_weapon_state.mag_quantity)
+                text.0 = match opt_melee {
+                    Some(MeleeState { durability: Some(durability), .. }) => localization.format("hud.durability", &[("durability", durability)]),
+                    Some(_) => localization.text("hud.melee"),
+                    None => localization.format("hud.ammo", &[("ammo", &active_weapon_state.mag_ammo), ("mag", &active_weapon_state.mag_quantity)]),
+                }
             }
 
             if let Ok(mut text) = q_reloading.get_single_mut() {
-                text.0 = if inventory.is_reloading() {
-                    format!("{:.2}s", calculate_time_remaining_seconds(inventory.reloading_ending_frame.unwrap(), frame.frame))
+                text.0 = if active_weapon_state.is_overheated(frame.frame) {
+                    let seconds = active_weapon_state.overheat.map_or(0.0, |timer| timer.remaining_seconds(frame.frame, &simulation));
+                    localization.format("hud.overheated", &[("seconds", &format!("{:.2}", seconds))])
+                } else if inventory.is_reloading() {
+                    let seconds = inventory.reload_timer.map_or(0.0, |timer| timer.remaining_seconds(frame.frame, &simulation));
+                    localization.format("hud.reloading", &[("seconds", &format!("{:.2}", seconds))])
                 } else {
                     format!("")
                 };
@@ -103,6 +141,68 @@ fn update_weapons_text(
     }
 }
 
+fn update_heat_bar(
+    frame: Res<FrameCount>,
+    time: Res<Time>,
+    q_player: Query<&WeaponInventory, With<LocalPlayer>>,
+    weapon_query: Query<(&Weapon, &WeaponState, &WeaponModesState)>,
+    mut q_bar: Query<&mut Visibility, With<HeatBar>>,
+    mut q_fill: Query<(&mut Node, &mut BackgroundColor), With<HeatBarFill>>,
+) {
+    let Ok(mut visibility) = q_bar.get_single_mut() else {
+        return;
+    };
+    let active = q_player.get_single().ok()
+        .and_then(|inventory| inventory.active_weapon())
+        .and_then(|(link, _)| link.get(&weapon_query))
+        .filter(|(weapon, state, _)| weapon.config.firing_modes.get(&state.active_mode).map_or(false, |config| config.overheat.is_some()))
+        .and_then(|(_, state, modes_state)| modes_state.modes.get(&state.active_mode));
+    let Some(mode_state) = active else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+
+    let Ok((mut node, mut color)) = q_fill.get_single_mut() else {
+        return;
+    };
+    if mode_state.is_overheated(frame.frame) {
+        // Full and blinking for the whole cooldown
+        node.width = Val::Percent(100.0);
+        color.0 = OVERHEAT_COLOR.with_alpha(if (time.elapsed_secs() * 6.0) as u32 % 2 == 0 { 1.0 } else { 0.3 });
+    } else {
+        let ratio = mode_state.heat_ratio().clamp(0.0, 1.0);
+        node.width = Val::Percent(ratio * 100.0);
+        color.0 = Color::srgb(1.0, 0.85, 0.2).mix(&OVERHEAT_COLOR, ratio);
+    }
+}
+
+// Presentation only, the weapon pulse red while it is overheated
+fn overheat_glow_system(
+    frame: Res<FrameCount>,
+    time: Res<Time>,
+    weapon_query: Query<(&WeaponState, &WeaponModesState, &Children, Option<&WeaponTint>)>,
+    mut query_sprite: Query<&mut Sprite>,
+) {
+    for (state, modes_state, children, opt_tint) in weapon_query.iter() {
+        let base = opt_tint.map_or(Color::WHITE, |tint| tint.0);
+        let color = match modes_state.modes.get(&state.active_mode) {
+            Some(mode_state) if mode_state.is_overheated(frame.frame) => {
+                let pulse = (time.elapsed_secs() * 8.0).sin() * 0.5 + 0.5;
+                base.mix(&OVERHEAT_COLOR, 0.4 + 0.6 * pulse)
+            }
+            _ => base,
+        };
+        for child in children.iter() {
+            if let Ok(mut sprite) = query_sprite.get_mut(*child) {
+                if sprite.color != color {
+                    sprite.color = color;
+                }
+            }
+        }
+    }
+}
+
 
 #[derive(Default)]
 pub struct WeaponDebugUIPlugin;
@@ -110,6 +210,6 @@ pub struct WeaponDebugUIPlugin;
 impl Plugin for WeaponDebugUIPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(AppState::InGame), setup_weapon_ui);
-        app.add_systems(Update, update_weapons_text.run_if(in_state(AppState::InGame)));
+        app.add_systems(Update, (update_weapons_text, update_heat_bar, overheat_glow_system).run_if(in_state(AppState::InGame)));
     }
 }
\ No newline at end of file
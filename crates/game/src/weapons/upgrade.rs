@@ -0,0 +1,102 @@
+use animation::SpriteSheetConfig;
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+
+use crate::{character::player::{input::PreviousInput, jjrs::PeerConfig, Player}, collider::spatial::{ObstacleKind, SteeringObstacle}, global_asset::GlobalAsset, interaction::{find_interactable_in_range, Interactable}, localization::LocalizedText, points::PlayerPoints};
+
+use super::{replace_weapon_for_player, WeaponInventory, WeaponModesState, WeaponsConfig};
+
+
+const UPGRADE_STATION_RADIUS: f32 = 80.0;
+
+// Pack-a-punch like machine, convert the held weapon to its upgraded variant
+#[derive(Component, Clone, Debug)]
+pub struct UpgradeStation {
+    pub cost: u32,
+}
+
+pub fn spawn_upgrade_station(
+    commands: &mut Commands,
+    position: Vec3,
+    cost: u32,
+) -> Entity {
+    commands.spawn((
+        Sprite::from_color(Color::srgb(0.5, 0.1, 0.7), Vec2::new(60.0, 60.0)),
+        Transform::from_translation(position),
+        UpgradeStation { cost },
+        Interactable {
+            radius: UPGRADE_STATION_RADIUS,
+            prompt: LocalizedText::new("interaction.upgrade_weapon").with_arg("cost", cost),
+        },
+        SteeringObstacle { kind: ObstacleKind::Prop, radius: 30.0 },
+    )).add_rollback().id()
+}
+
+
+pub fn rollback_upgrade_station_system(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<PeerConfig>>,
+
+    weapons_asset: Res<Assets<WeaponsConfig>>,
+    global_assets: Res<GlobalAsset>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    sprint_sheet_assets: Res<Assets<SpriteSheetConfig>>,
+
+    station_query: Query<(Entity, &Transform, &Interactable), (With<UpgradeStation>, With<Rollback>)>,
+    station_config_query: Query<&UpgradeStation>,
+    mut player_query: Query<(Entity, &Transform, &Player, &mut WeaponInventory, &mut PlayerPoints, &PreviousInput), With<Rollback>>,
+    weapon_query: Query<&WeaponModesState>,
+) {
+    let Some(weapons_config) = weapons_asset.get(&global_assets.weapons) else {
+        return;
+    };
+
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, _, player, ..)| player.handle);
+
+    for (player_entity, transform, player, inventory, points, previous_input) in players.iter_mut() {
+        let (input, _input_status) = inputs[player.handle];
+
+        if !previous_input.interact_just_pressed(&input) {
+            continue;
+        }
+
+        let Some(station_entity) = find_interactable_in_range(transform.translation.truncate(), station_query.iter()) else {
+            continue;
+        };
+        let Ok(station) = station_config_query.get(station_entity) else {
+            continue;
+        };
+
+        let Some((weapon_entity, weapon)) = inventory.active_weapon().cloned() else {
+            continue;
+        };
+        let Some(upgrade) = weapons_config.weapons.get(&weapon.config.name).and_then(|w| w.upgrade.as_ref()).and_then(|k| weapons_config.weapons.get(k)) else {
+            continue;
+        };
+        let Some(modes_state) = weapon_entity.get(&weapon_query) else {
+            continue;
+        };
+
+        if !points.spend(station.cost) {
+            continue;
+        }
+
+        let index = inventory.active_weapon_index;
+        replace_weapon_for_player(
+            &mut commands,
+            &global_assets,
+            &asset_server,
+            &mut texture_atlas_layouts,
+            &sprint_sheet_assets,
+            *player_entity,
+            index,
+            upgrade.clone(),
+            modes_state,
+            inventory,
+        );
+
+        info!("Player {} upgraded {} to {}", player.handle, weapon.config.name, upgrade.config.name);
+    }
+}
@@ -0,0 +1,100 @@
+use animation::SpriteSheetConfig;
+use bevy::prelude::*;
+use bevy_ggrs::{AddRollbackCommandExtension, PlayerInputs, Rollback};
+
+use crate::{character::player::{input::PreviousInput, jjrs::PeerConfig, Player}, global_asset::GlobalAsset, interaction::{find_interactable_in_range, Interactable}, localization::LocalizedText, points::PlayerPoints};
+
+use super::{spawn_weapon_for_player, WeaponInventory, WeaponsConfig};
+
+
+const WALL_WEAPON_RADIUS: f32 = 70.0;
+
+// Weapon hanging on a wall, bought with points and equipped right away
+#[derive(Component, Clone, Debug)]
+pub struct WallWeapon {
+    // Key of the weapon in the WeaponsConfig
+    pub weapon: String,
+    pub cost: u32,
+}
+
+pub fn spawn_wall_weapon(
+    commands: &mut Commands,
+    position: Vec3,
+    weapon: &str,
+    cost: u32,
+) -> Entity {
+    commands.spawn((
+        Sprite::from_color(Color::srgb(0.2, 0.5, 0.8), Vec2::new(50.0, 20.0)),
+        Transform::from_translation(position),
+        WallWeapon { weapon: weapon.to_string(), cost },
+        Interactable {
+            radius: WALL_WEAPON_RADIUS,
+            prompt: LocalizedText::new("interaction.buy_weapon").with_arg("weapon", weapon).with_arg("cost", cost),
+        },
+    )).add_rollback().id()
+}
+
+
+pub fn rollback_wall_weapon_system(
+    mut commands: Commands,
+    inputs: Res<PlayerInputs<PeerConfig>>,
+
+    weapons_asset: Res<Assets<WeaponsConfig>>,
+    global_assets: Res<GlobalAsset>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    sprint_sheet_assets: Res<Assets<SpriteSheetConfig>>,
+
+    wall_query: Query<(Entity, &Transform, &Interactable), (With<WallWeapon>, With<Rollback>)>,
+    wall_config_query: Query<&WallWeapon>,
+    mut player_query: Query<(Entity, &Transform, &Player, &mut WeaponInventory, &mut PlayerPoints, &PreviousInput), With<Rollback>>,
+) {
+    let Some(weapons_config) = weapons_asset.get(&global_assets.weapons) else {
+        return;
+    };
+
+    let mut players: Vec<_> = player_query.iter_mut().collect();
+    players.sort_by_key(|(_, _, player, ..)| player.handle);
+
+    for (player_entity, transform, player, inventory, points, previous_input) in players.iter_mut() {
+        let (input, _input_status) = inputs[player.handle];
+
+        if !previous_input.interact_just_pressed(&input) {
+            continue;
+        }
+
+        let Some(wall_entity) = find_interactable_in_range(transform.translation.truncate(), wall_query.iter()) else {
+            continue;
+        };
+        let Ok(wall) = wall_config_query.get(wall_entity) else {
+            continue;
+        };
+        let Some(weapon) = weapons_config.weapons.get(&wall.weapon) else {
+            continue;
+        };
+
+        // Already carried, nothing to buy
+        if inventory.weapons.iter().any(|(_, w)| w.config.name == weapon.config.name) {
+            continue;
+        }
+
+        if !points.spend(wall.cost) {
+            continue;
+        }
+
+        inventory.clear_reloading();
+        spawn_weapon_for_player(
+            &mut commands,
+            &global_assets,
+            &asset_server,
+            &mut texture_atlas_layouts,
+            &sprint_sheet_assets,
+            true,
+            *player_entity,
+            weapon.clone(),
+            inventory,
+        );
+
+        info!("Player {} bought {} for {} points", player.handle, wall.weapon, wall.cost);
+    }
+}
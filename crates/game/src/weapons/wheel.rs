@@ -0,0 +1,165 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{hud::window_to_ui, character::player::{control::PlayerAction, input::MAX_SELECTABLE_SLOTS, LocalPlayer}, plugins::AppState, web::{pointer_position, PointerLock}};
+
+use super::WeaponInventory;
+
+// Radial weapon selection, presentation only. Holding the switch key open the wheel,
+// releasing it send the hovered slot in the inputs. A short tap still cycle the weapons.
+
+// Seconds the switch key must be held before the wheel open
+const WHEEL_OPEN_DELAY: f32 = 0.2;
+const WHEEL_RADIUS: f32 = 120.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WheelAction {
+    Cycle,
+    Select(usize),
+    // From the inventory menu, the weapon of a slot dropped on another one
+    Move { from: usize, to: usize },
+}
+
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct WeaponWheelState {
+    pub is_open: bool,
+    pub hold_time: f32,
+    pub hovered: Option<usize>,
+    // Waiting to be written in the next local input
+    pub pending: Option<WheelAction>,
+}
+
+impl WeaponWheelState {
+    pub fn take_pending(&mut self) -> Option<WheelAction> {
+        self.pending.take()
+    }
+}
+
+#[derive(Component)]
+struct WeaponWheelSlot(usize);
+
+
+fn setup_weapon_wheel_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+    for slot in 0..MAX_SELECTABLE_SLOTS {
+        commands.spawn((
+            WeaponWheelSlot(slot),
+            Text::new(""),
+            TextFont {
+                font: font.clone(),
+                font_size: 16.0,
+                ..Default::default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Node {
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            Visibility::Hidden,
+        ));
+    }
+}
+
+// Slot under the cursor, the wheel is split in equal sectors starting at the top going clockwise
+fn hovered_slot(cursor_from_center: Vec2, slot_count: usize) -> Option<usize> {
+    if slot_count == 0 || cursor_from_center.length() < WHEEL_RADIUS * 0.3 {
+        return None;
+    }
+
+    let angle = cursor_from_center.x.atan2(-cursor_from_center.y).rem_euclid(std::f32::consts::TAU);
+    let sector = std::f32::consts::TAU / slot_count as f32;
+    Some(((angle + sector / 2.0) / sector) as usize % slot_count)
+}
+
+fn slot_offset(slot: usize, slot_count: usize) -> Vec2 {
+    let angle = slot as f32 / slot_count as f32 * std::f32::consts::TAU;
+    // Screen space, y goes down
+    Vec2::new(angle.sin(), -angle.cos()) * WHEEL_RADIUS
+}
+
+fn weapon_wheel_input_system(
+    time: Res<Time>,
+    mut state: ResMut<WeaponWheelState>,
+    q_player: Query<(&ActionState<PlayerAction>, &WeaponInventory), With<LocalPlayer>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    pointer_lock: Res<PointerLock>,
+) {
+    let Ok((action_state, inventory)) = q_player.get_single() else {
+        return;
+    };
+    let slot_count = inventory.weapons.len().min(MAX_SELECTABLE_SLOTS);
+
+    if action_state.pressed(&PlayerAction::SwitchWeapon) {
+        state.hold_time += time.delta_secs();
+        if state.hold_time >= WHEEL_OPEN_DELAY && slot_count > 1 {
+            state.is_open = true;
+        }
+
+        if state.is_open {
+            if let Ok(window) = q_window.get_single() {
+                if let Some(cursor) = pointer_position(window, &pointer_lock) {
+                    state.hovered = hovered_slot(cursor - window.size() / 2.0, slot_count);
+                }
+            }
+        }
+    } else if action_state.just_released(&PlayerAction::SwitchWeapon) {
+        state.pending = if state.is_open {
+            state.hovered.map(WheelAction::Select)
+        } else {
+            Some(WheelAction::Cycle)
+        };
+        state.is_open = false;
+        state.hold_time = 0.0;
+        state.hovered = None;
+    }
+}
+
+fn update_weapon_wheel_ui(
+    state: Res<WeaponWheelState>,
+    q_player: Query<&WeaponInventory, With<LocalPlayer>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    ui_scale: Res<UiScale>,
+    mut q_slots: Query<(&WeaponWheelSlot, &mut Text, &mut Node, &mut Visibility, &mut BackgroundColor)>,
+) {
+    let inventory = q_player.get_single().ok();
+    let center = q_window.get_single().map_or(Vec2::ZERO, |w| window_to_ui(w.size() / 2.0, &ui_scale));
+
+    for (slot, mut text, mut node, mut visibility, mut background) in q_slots.iter_mut() {
+        let weapon = inventory.and_then(|inventory| inventory.weapons.get(slot.0).map(|w| (inventory, w)));
+
+        let Some((inventory, (_, weapon))) = weapon.filter(|_| state.is_open) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        let slot_count = inventory.weapons.len().min(MAX_SELECTABLE_SLOTS);
+        let position = center + slot_offset(slot.0, slot_count);
+
+        *visibility = Visibility::Inherited;
+        text.0 = weapon.config.name.clone();
+        node.left = Val::Px(position.x - 40.0);
+        node.top = Val::Px(position.y - 12.0);
+        background.0 = if state.hovered == Some(slot.0) {
+            Color::srgba(0.8, 0.6, 0.1, 0.8)
+        } else if inventory.active_weapon_index == slot.0 {
+            Color::srgba(0.2, 0.2, 0.2, 0.8)
+        } else {
+            Color::srgba(0.0, 0.0, 0.0, 0.6)
+        };
+    }
+}
+
+
+#[derive(Default)]
+pub struct WeaponWheelUIPlugin;
+
+impl Plugin for WeaponWheelUIPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WeaponWheelState>();
+        app.add_systems(OnEnter(AppState::InGame), setup_weapon_wheel_ui);
+        app.add_systems(Update, (weapon_wheel_input_system, update_weapon_wheel_ui).chain().run_if(in_state(AppState::InGame)));
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ggrs::Session;
+
+use crate::{character::player::{input::{BoxInput, INPUT_DOWN, INPUT_LEFT, INPUT_RIGHT, INPUT_SPRINT, INPUT_UP}, jjrs::PeerConfig}, localization::Localization, plugins::AppState};
+
+// Window losing the focus, the keyboard and the mouse stop reporting and a key held
+// at that moment would never be released. Online the simulation keep going so the
+// local player get a neutral or held input, offline the game can pause. When the
+// focus come back the pointer is somewhere else, the aim stay where it was for a moment.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum UnfocusedInputPolicy {
+    // Nothing pressed, the player stand still
+    #[default]
+    Neutral,
+    // Keep moving the way it was, nothing fired
+    Hold,
+}
+
+impl UnfocusedInputPolicy {
+    // Input sent while unfocused from the last one read with the focus
+    pub fn apply(&self, last: &BoxInput) -> BoxInput {
+        let buttons = match self {
+            UnfocusedInputPolicy::Neutral => 0,
+            UnfocusedInputPolicy::Hold => last.buttons & (INPUT_UP | INPUT_DOWN | INPUT_LEFT | INPUT_RIGHT | INPUT_SPRINT),
+        };
+        BoxInput { buttons, aim: last.aim, ..Default::default() }
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource)]
+pub struct FocusSettings {
+    pub policy: UnfocusedInputPolicy,
+    // Pause the simulation while unfocused, never in an online game
+    pub pause_offline: bool,
+    // Seconds the aim ignore the pointer once the focus is back
+    pub aim_settle_seconds: f32,
+}
+
+impl Default for FocusSettings {
+    fn default() -> Self {
+        Self {
+            policy: UnfocusedInputPolicy::Neutral,
+            pause_offline: true,
+            aim_settle_seconds: 0.25,
+        }
+    }
+}
+
+// What the keyboard and mouse source can trust
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FocusInput {
+    #[default]
+    Focused,
+    Unfocused(UnfocusedInputPolicy),
+    // The focus just came back, everything but the pointer
+    Settling,
+}
+
+#[derive(Resource, Debug)]
+pub struct WindowFocus {
+    pub focused: bool,
+    settle: Timer,
+    // The pause is ours to remove
+    paused: bool,
+}
+
+impl Default for WindowFocus {
+    fn default() -> Self {
+        Self { focused: true, settle: Timer::default(), paused: false }
+    }
+}
+
+impl WindowFocus {
+    pub fn input(&self, settings: &FocusSettings) -> FocusInput {
+        if !self.focused {
+            FocusInput::Unfocused(settings.policy)
+        } else if !self.settle.finished() {
+            FocusInput::Settling
+        } else {
+            FocusInput::Focused
+        }
+    }
+}
+
+#[derive(Component)]
+struct UnfocusedOverlay;
+
+
+// SYSTEMS
+
+fn setup_unfocused_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        UnfocusedOverlay,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        GlobalZIndex(10),
+        Visibility::Hidden,
+    )).with_children(|parent| {
+        parent.spawn((
+            Text::new(""),
+            TextFont {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: 32.0,
+                ..Default::default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+        ));
+    });
+}
+
+fn track_window_focus(
+    time: Res<Time<Real>>,
+    settings: Res<FocusSettings>,
+    session: Option<Res<Session<PeerConfig>>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut focus: ResMut<WindowFocus>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+
+    if focus.focused != window.focused {
+        focus.focused = window.focused;
+        info!("window {}", if window.focused { "focused" } else { "unfocused" });
+        if window.focused {
+            focus.settle = Timer::from_seconds(settings.aim_settle_seconds, TimerMode::Once);
+        }
+    }
+    focus.settle.tick(time.delta());
+
+    // GGRS run its frames from the virtual time, paused nothing advance
+    let online = matches!(session.as_deref(), Some(Session::P2P(_) | Session::Spectator(_)));
+    let pause = settings.pause_offline && !online && !focus.focused;
+    if pause && !focus.paused {
+        virtual_time.pause();
+        focus.paused = true;
+    } else if !pause && focus.paused {
+        virtual_time.unpause();
+        focus.paused = false;
+    }
+}
+
+fn update_unfocused_overlay(
+    settings: Res<FocusSettings>,
+    focus: Res<WindowFocus>,
+    localization: Res<Localization>,
+    mut q_overlay: Query<(&mut Visibility, &Children), With<UnfocusedOverlay>>,
+    mut q_text: Query<&mut Text>,
+) {
+    let Ok((mut visibility, children)) = q_overlay.get_single_mut() else {
+        return;
+    };
+    if focus.focused {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    let detail = if focus.paused {
+        "focus.paused"
+    } else {
+        match settings.policy {
+            UnfocusedInputPolicy::Neutral => "focus.standing_still",
+            UnfocusedInputPolicy::Hold => "focus.keep_moving",
+        }
+    };
+    let message = localization.format("focus.unfocused", &[("detail", &localization.text(detail))]);
+    for child in children.iter() {
+        if let Ok(mut text) = q_text.get_mut(*child) {
+            text.0 = message.clone();
+        }
+    }
+}
+
+
+pub struct WindowFocusPlugin;
+
+impl Plugin for WindowFocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FocusSettings>()
+            .register_type::<FocusSettings>()
+            .init_resource::<WindowFocus>()
+            .add_systems(OnEnter(AppState::InGame), setup_unfocused_overlay)
+            .add_systems(Update, (track_window_focus, update_unfocused_overlay).chain().run_if(in_state(AppState::InGame)));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::player::input::INPUT_RELOAD;
+
+    fn last_input() -> BoxInput {
+        BoxInput { buttons: INPUT_UP | INPUT_SPRINT | INPUT_RELOAD, aim: 1234, fire: true, switch_weapon: true, slot_move: 9 }
+    }
+
+    #[test]
+    fn test_neutral_policy_release_everything_but_the_aim() {
+        let input = UnfocusedInputPolicy::Neutral.apply(&last_input());
+        assert_eq!(input.buttons, 0);
+        assert_eq!(input.aim, 1234);
+        assert!(!input.fire && !input.switch_weapon);
+        assert_eq!(input.slot_move, 0);
+    }
+
+    #[test]
+    fn test_hold_policy_keep_only_the_movement() {
+        let input = UnfocusedInputPolicy::Hold.apply(&last_input());
+        assert_eq!(input.buttons, INPUT_UP | INPUT_SPRINT);
+        assert_eq!(input.aim, 1234);
+        assert!(!input.fire && !input.switch_weapon);
+    }
+
+    #[test]
+    fn test_focus_settle_before_trusting_the_pointer() {
+        let settings = FocusSettings { policy: UnfocusedInputPolicy::Hold, ..Default::default() };
+        let mut focus = WindowFocus { focused: false, ..Default::default() };
+        assert_eq!(focus.input(&settings), FocusInput::Unfocused(UnfocusedInputPolicy::Hold));
+
+        focus.focused = true;
+        focus.settle = Timer::from_seconds(settings.aim_settle_seconds, TimerMode::Once);
+        focus.settle.tick(std::time::Duration::from_secs_f32(0.1));
+        assert_eq!(focus.input(&settings), FocusInput::Settling);
+        focus.settle.tick(std::time::Duration::from_secs_f32(0.2));
+        assert_eq!(focus.input(&settings), FocusInput::Focused);
+    }
+}
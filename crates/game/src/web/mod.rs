@@ -0,0 +1,131 @@
+pub mod focus;
+pub mod touch;
+
+use bevy::{input::mouse::MouseMotion, prelude::*, window::{CursorGrabMode, PrimaryWindow, WindowFocused, WindowResized}};
+
+use focus::WindowFocusPlugin;
+use touch::{TouchControls, TouchControlsPlugin};
+
+// Browser side of the input. The pointer lock keep the mouse in the canvas while
+// playing, the browser then only report the movements so the pointer is a virtual
+// one moved by them. Every system looking at the pointer go through `pointer_position`
+// to get it in window coordinates, locked or not.
+
+pub const POINTER_LOCK_KEY: KeyCode = KeyCode::F10;
+
+#[derive(Resource, Debug, Clone)]
+pub struct PointerLock {
+    pub locked: bool,
+    // Virtual pointer while locked, in logical window coordinates like the cursor
+    pub cursor: Vec2,
+    pub sensitivity: f32,
+}
+
+impl Default for PointerLock {
+    fn default() -> Self {
+        Self {
+            locked: false,
+            cursor: Vec2::ZERO,
+            sensitivity: 1.0,
+        }
+    }
+}
+
+// Position of the pointer in the window, the virtual one while locked
+pub fn pointer_position(window: &Window, lock: &PointerLock) -> Option<Vec2> {
+    if lock.locked {
+        Some(lock.cursor)
+    } else {
+        window.cursor_position()
+    }
+}
+
+fn set_pointer_lock(window: &mut Window, lock: &mut PointerLock, locked: bool) {
+    if locked {
+        lock.cursor = window.cursor_position().unwrap_or(window.size() / 2.0);
+        window.cursor_options.grab_mode = CursorGrabMode::Locked;
+        window.cursor_options.visible = false;
+    } else {
+        window.cursor_options.grab_mode = CursorGrabMode::None;
+        window.cursor_options.visible = true;
+    }
+    lock.locked = locked;
+}
+
+
+// SYSTEMS
+
+fn toggle_pointer_lock(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut lock: ResMut<PointerLock>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Ok(mut window) = q_window.get_single_mut() else {
+        return;
+    };
+
+    // The browser release the lock by itself on escape or when the tab lose the focus
+    let released = keys.just_pressed(KeyCode::Escape) || focus_events.read().any(|event| !event.focused);
+    if lock.locked && released {
+        set_pointer_lock(&mut window, &mut lock, false);
+    } else if keys.just_pressed(POINTER_LOCK_KEY) {
+        let locked = !lock.locked;
+        set_pointer_lock(&mut window, &mut lock, locked);
+    }
+}
+
+fn move_locked_pointer(
+    mut motion_events: EventReader<MouseMotion>,
+    mut lock: ResMut<PointerLock>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let delta: Vec2 = motion_events.read().map(|event| event.delta).sum();
+    if !lock.locked {
+        return;
+    }
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+
+    let cursor = lock.cursor + delta * lock.sensitivity;
+    lock.cursor = cursor.clamp(Vec2::ZERO, window.size());
+}
+
+// The camera projection follow the window by itself, what is kept in window
+// coordinates here must be brought back inside the new size
+fn handle_canvas_resize(
+    mut resize_events: EventReader<WindowResized>,
+    mut lock: ResMut<PointerLock>,
+    mut touch: ResMut<TouchControls>,
+    q_window: Query<Entity, With<PrimaryWindow>>,
+) {
+    let Ok(primary) = q_window.get_single() else {
+        return;
+    };
+    let Some(resized) = resize_events.read().filter(|event| event.window == primary).last() else {
+        return;
+    };
+
+    let size = Vec2::new(resized.width, resized.height);
+    lock.cursor = lock.cursor.clamp(Vec2::ZERO, size);
+    // A stick held across the resize would be anchored to a point that moved
+    touch.release_all();
+}
+
+
+#[derive(Default)]
+pub struct WebInputPlugin;
+
+impl Plugin for WebInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PointerLock>();
+        app.add_plugins(TouchControlsPlugin);
+        app.add_plugins(WindowFocusPlugin);
+        app.add_systems(Update, (
+            toggle_pointer_lock,
+            move_locked_pointer.after(toggle_pointer_lock),
+            handle_canvas_resize,
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::{input::touch::Touches, prelude::*, window::PrimaryWindow};
+
+use crate::{hud::window_to_ui, plugins::AppState};
+
+// Touch controls for the phones and tablets: a stick that appear where the thumb
+// land on the left half of the screen and a fire button in the bottom right corner.
+// They feed the input of the keyboard and mouse player, the aim follow the stick.
+
+// Distance in pixels for the stick to be fully pushed
+const STICK_RADIUS: f32 = 60.0;
+const FIRE_BUTTON_RADIUS: f32 = 45.0;
+const FIRE_BUTTON_MARGIN: f32 = 40.0;
+// Stick push under which the aim is not changed
+const STICK_AIM_DEADZONE: f32 = 0.2;
+
+#[derive(Resource, Default, Debug)]
+pub struct TouchControls {
+    // A touch was seen, the controls are shown and read from then on
+    pub active: bool,
+    stick_touch: Option<u64>,
+    // Where the thumb landed, in logical window coordinates
+    stick_origin: Vec2,
+    // Push of the stick, each axis in -1..1 with y up
+    pub stick: Vec2,
+    fire_touch: Option<u64>,
+    pub fire: bool,
+    // Last direction the stick pointed to, unit vector with y up
+    pub aim: Vec2,
+}
+
+impl TouchControls {
+    // Stick or fire button held
+    pub fn in_use(&self) -> bool {
+        self.stick_touch.is_some() || self.fire_touch.is_some()
+    }
+
+    pub fn release_all(&mut self) {
+        self.stick_touch = None;
+        self.fire_touch = None;
+        self.stick = Vec2::ZERO;
+        self.fire = false;
+    }
+}
+
+fn fire_button_center(window: &Window) -> Vec2 {
+    window.size() - Vec2::splat(FIRE_BUTTON_MARGIN + FIRE_BUTTON_RADIUS)
+}
+
+
+// SYSTEMS
+
+fn update_touch_controls(
+    touches: Res<Touches>,
+    mut controls: ResMut<TouchControls>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+
+    for touch in touches.iter_just_pressed() {
+        controls.active = true;
+        let position = touch.position();
+        if controls.fire_touch.is_none() && position.distance(fire_button_center(window)) <= FIRE_BUTTON_RADIUS {
+            controls.fire_touch = Some(touch.id());
+        } else if controls.stick_touch.is_none() && position.x < window.width() / 2.0 {
+            controls.stick_touch = Some(touch.id());
+            controls.stick_origin = position;
+        }
+    }
+
+    for touch in touches.iter_just_released().chain(touches.iter_just_canceled()) {
+        if controls.stick_touch == Some(touch.id()) {
+            controls.stick_touch = None;
+        }
+        if controls.fire_touch == Some(touch.id()) {
+            controls.fire_touch = None;
+        }
+    }
+
+    let stick = controls.stick_touch
+        .and_then(|id| touches.get_pressed(id))
+        .map(|touch| (touch.position() - controls.stick_origin).clamp_length_max(STICK_RADIUS) / STICK_RADIUS)
+        .map(|offset| Vec2::new(offset.x, -offset.y))
+        .unwrap_or(Vec2::ZERO);
+    controls.stick = stick;
+    if stick.length() > STICK_AIM_DEADZONE {
+        controls.aim = stick.normalize();
+    }
+    controls.fire = controls.fire_touch.is_some();
+}
+
+
+#[derive(Component)]
+struct TouchStickBase;
+
+#[derive(Component)]
+struct TouchStickKnob;
+
+#[derive(Component)]
+struct TouchFireButton;
+
+fn circle_node(radius: f32) -> Node {
+    Node {
+        position_type: PositionType::Absolute,
+        width: Val::Px(radius * 2.0),
+        height: Val::Px(radius * 2.0),
+        ..default()
+    }
+}
+
+fn setup_touch_ui(mut commands: Commands) {
+    commands.spawn((
+        TouchStickBase,
+        circle_node(STICK_RADIUS),
+        BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.15)),
+        BorderRadius::MAX,
+        Visibility::Hidden,
+    ));
+    commands.spawn((
+        TouchStickKnob,
+        circle_node(STICK_RADIUS / 2.0),
+        BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.4)),
+        BorderRadius::MAX,
+        Visibility::Hidden,
+    ));
+    commands.spawn((
+        TouchFireButton,
+        circle_node(FIRE_BUTTON_RADIUS),
+        BackgroundColor(Color::srgba(1.0, 0.3, 0.3, 0.3)),
+        BorderRadius::MAX,
+        Visibility::Hidden,
+    ));
+}
+
+// The center is in pixels of the window like the touches, the radius in pixels of the nodes
+fn place(node: &mut Node, center: Vec2, radius: f32, ui_scale: &UiScale) {
+    let center = window_to_ui(center, ui_scale);
+    node.left = Val::Px(center.x - radius);
+    node.top = Val::Px(center.y - radius);
+}
+
+fn update_touch_ui(
+    controls: Res<TouchControls>,
+    ui_scale: Res<UiScale>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut q_base: Query<(&mut Node, &mut Visibility), (With<TouchStickBase>, Without<TouchStickKnob>, Without<TouchFireButton>)>,
+    mut q_knob: Query<(&mut Node, &mut Visibility), (With<TouchStickKnob>, Without<TouchStickBase>, Without<TouchFireButton>)>,
+    mut q_fire: Query<(&mut Node, &mut Visibility, &mut BackgroundColor), (With<TouchFireButton>, Without<TouchStickBase>, Without<TouchStickKnob>)>,
+) {
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+    let visibility = if controls.active { Visibility::Inherited } else { Visibility::Hidden };
+
+    // Resting place of the stick when no thumb is on it
+    let origin = if controls.stick_touch.is_some() {
+        controls.stick_origin
+    } else {
+        Vec2::new(FIRE_BUTTON_MARGIN + STICK_RADIUS, window.height() - FIRE_BUTTON_MARGIN - STICK_RADIUS)
+    };
+    let knob = origin + Vec2::new(controls.stick.x, -controls.stick.y) * STICK_RADIUS;
+
+    if let Ok((mut node, mut node_visibility)) = q_base.get_single_mut() {
+        place(&mut node, origin, STICK_RADIUS, &ui_scale);
+        *node_visibility = visibility;
+    }
+    if let Ok((mut node, mut node_visibility)) = q_knob.get_single_mut() {
+        place(&mut node, knob, STICK_RADIUS / 2.0, &ui_scale);
+        *node_visibility = visibility;
+    }
+    if let Ok((mut node, mut node_visibility, mut color)) = q_fire.get_single_mut() {
+        place(&mut node, fire_button_center(window), FIRE_BUTTON_RADIUS, &ui_scale);
+        *node_visibility = visibility;
+        color.0 = Color::srgba(1.0, 0.3, 0.3, if controls.fire { 0.6 } else { 0.3 });
+    }
+}
+
+
+#[derive(Default)]
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>();
+        app.add_systems(Update, update_touch_controls);
+        app.add_systems(OnEnter(AppState::InGame), setup_touch_ui);
+        app.add_systems(Update, update_touch_ui.run_if(in_state(AppState::InGame)));
+    }
+}
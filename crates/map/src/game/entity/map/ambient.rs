@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::zone::ZoneRect;
+
+// Rectangle of the map with its own ambient loop, wind outside, hum near the
+// machines, dripping in the tunnels. Only heard, it does nothing to the simulation.
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct AmbientZoneConfig {
+    // Name of the ambience, the game map it to the looping track
+    pub ambience: String,
+    pub volume: f32,
+    pub size: Vec2,
+}
+
+impl Default for AmbientZoneConfig {
+    fn default() -> Self {
+        AmbientZoneConfig {
+            ambience: "wind".to_string(),
+            volume: 1.0,
+            size: Vec2::splat(256.0),
+        }
+    }
+}
+
+impl AmbientZoneConfig {
+    // The zone is centered on its entity
+    pub fn rect(&self, center: Vec2) -> ZoneRect {
+        ZoneRect::new(center, self.size)
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct AmbientZoneComponent {
+    pub config: AmbientZoneConfig,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_is_centered_on_the_entity() {
+        let config = AmbientZoneConfig { size: Vec2::new(100.0, 40.0), ..Default::default() };
+        let rect = config.rect(Vec2::new(10.0, 10.0));
+        assert!(rect.contains(Vec2::new(60.0, 30.0)));
+        assert!(!rect.contains(Vec2::new(61.0, 10.0)));
+        assert!(!rect.contains(Vec2::new(10.0, -11.0)));
+    }
+}
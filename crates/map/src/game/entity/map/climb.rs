@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Low wall of the map the enemies can climb over. It block the players and the bullets
+// like any wall, an enemy with its path across it climb to the other side instead of
+// walking to a door, so a whole horde can pour over the barrier of an arena.
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct ClimbableWallConfig {
+    pub size: Vec2,
+    // Duration of the climb, in frames at the reference tick rate
+    pub climb_frames: u32,
+    // Detour in cells an enemy would rather walk than climb
+    pub cost: f32,
+}
+
+impl Default for ClimbableWallConfig {
+    fn default() -> Self {
+        ClimbableWallConfig {
+            size: Vec2::new(128.0, 24.0),
+            climb_frames: 45,
+            cost: 4.0,
+        }
+    }
+}
+
+impl ClimbableWallConfig {
+    // The wall is crossed along its thin side
+    pub fn is_horizontal(&self) -> bool {
+        self.size.x >= self.size.y
+    }
+
+    // Does the segment from `a` to `b` go through the wall centered on `center`
+    pub fn is_crossed(&self, center: Vec2, a: Vec2, b: Vec2) -> bool {
+        let half = self.size / 2.0;
+        let (min, max) = (center - half, center + half);
+        let delta = b - a;
+
+        // Slabs on each axis, what is left of the segment once clipped
+        let mut enter = 0.0f32;
+        let mut exit = 1.0f32;
+        for (start, d, low, high) in [(a.x, delta.x, min.x, max.x), (a.y, delta.y, min.y, max.y)] {
+            if d.abs() <= f32::EPSILON {
+                if start < low || start > high {
+                    return false;
+                }
+                continue;
+            }
+            let (t0, t1) = ((low - start) / d, (high - start) / d);
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        enter <= exit
+    }
+
+    // Where a climb started at `from` end, on the other side of the wall and `clearance`
+    // away from it, straight across its thin side
+    pub fn landing(&self, center: Vec2, from: Vec2, clearance: f32) -> Vec2 {
+        let half = self.size / 2.0;
+        if self.is_horizontal() {
+            let side = if from.y < center.y { 1.0 } else { -1.0 };
+            Vec2::new(from.x.clamp(center.x - half.x, center.x + half.x), center.y + side * (half.y + clearance))
+        } else {
+            let side = if from.x < center.x { 1.0 } else { -1.0 };
+            Vec2::new(center.x + side * (half.x + clearance), from.y.clamp(center.y - half.y, center.y + half.y))
+        }
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct ClimbableWallComponent {
+    pub config: ClimbableWallConfig,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_through_the_wall_cross_it() {
+        let wall = ClimbableWallConfig::default();
+        assert!(wall.is_crossed(Vec2::ZERO, Vec2::new(0.0, -50.0), Vec2::new(10.0, 50.0)));
+        assert!(!wall.is_crossed(Vec2::ZERO, Vec2::new(100.0, -50.0), Vec2::new(100.0, 50.0)));
+        // Stop before the wall
+        assert!(!wall.is_crossed(Vec2::ZERO, Vec2::new(0.0, -50.0), Vec2::new(0.0, -20.0)));
+    }
+
+    #[test]
+    fn test_landing_is_on_the_other_side() {
+        let wall = ClimbableWallConfig::default();
+        assert_eq!(wall.landing(Vec2::ZERO, Vec2::new(20.0, -40.0), 15.0), Vec2::new(20.0, 27.0));
+        assert_eq!(wall.landing(Vec2::ZERO, Vec2::new(500.0, 40.0), 15.0), Vec2::new(64.0, -27.0));
+
+        let vertical = ClimbableWallConfig { size: Vec2::new(24.0, 128.0), ..Default::default() };
+        assert_eq!(vertical.landing(Vec2::ZERO, Vec2::new(-40.0, 10.0), 15.0), Vec2::new(27.0, 10.0));
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::named_kind;
+
+// Crates and weak walls of the map. They block like a wall, the destructible ones take
+// damage and crumble once their health is gone, opening a shortcut.
+named_kind! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect, Serialize, Deserialize)]
+    pub enum DestructibleKind {
+        // Cover in the middle of a room
+        #[default]
+        Crate => "crate",
+        // Part of a wall that can be broken through
+        WeakWall => "weak_wall",
+    }
+}
+
+impl DestructibleKind {
+    pub fn default_health(&self) -> f32 {
+        match self {
+            DestructibleKind::Crate => 60.0,
+            DestructibleKind::WeakWall => 200.0,
+        }
+    }
+
+    // Wood burn, the walls don't
+    pub fn default_flammable(&self) -> bool {
+        match self {
+            DestructibleKind::Crate => true,
+            DestructibleKind::WeakWall => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct DestructibleConfig {
+    pub kind: DestructibleKind,
+    // Only cover when false, it never takes damage
+    pub destructible: bool,
+    pub health: f32,
+    pub size: Vec2,
+    // Lit by the fire and spread it to the props around
+    #[serde(default)]
+    pub flammable: bool,
+}
+
+impl Default for DestructibleConfig {
+    fn default() -> Self {
+        DestructibleConfig {
+            kind: DestructibleKind::Crate,
+            destructible: true,
+            health: DestructibleKind::Crate.default_health(),
+            size: Vec2::splat(32.0),
+            flammable: DestructibleKind::Crate.default_flammable(),
+        }
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct DestructibleComponent {
+    pub config: DestructibleConfig,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_round_trip() {
+        for kind in DestructibleKind::ALL {
+            assert_eq!(DestructibleKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(DestructibleKind::from_name("door"), None);
+    }
+
+    #[test]
+    fn test_only_the_crates_burn_by_default() {
+        assert!(DestructibleConfig::default().flammable);
+        assert!(!DestructibleKind::WeakWall.default_flammable());
+    }
+}
@@ -16,7 +16,7 @@ impl Default for EnemySpawnerComponent {
             min_spawn_distance: 200.0,
             max_cooldown: 300,  // 5 seconds at 60fps
             max_enemies: 3,     // Per spawner
-            enemy_types: vec!["zombie_1".to_string(), "zombie_2".to_string()],
+            enemy_types: vec!["zombie_1".to_string(), "zombie_2".to_string(), "zombie_exploder".to_string(), "zombie_shield".to_string()],
         }
     }
 }
@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::named_kind;
+
+// Equipment placed on the map, picked up by walking on it. What each item does is
+// decided by the game, the map only say which one is where.
+named_kind! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect, Serialize, Deserialize)]
+    pub enum EquipmentKind {
+        // Armor absorbing the damage before the health
+        #[default]
+        ArmorVest => "armor",
+        // Faster movement for a while
+        SpeedBoots => "boots",
+        // More reserve ammo for every weapon
+        Backpack => "backpack",
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct EquipmentSpawnComponent {
+    pub kind: EquipmentKind,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_round_trip() {
+        for kind in EquipmentKind::ALL {
+            assert_eq!(EquipmentKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(EquipmentKind::from_name("helmet"), None);
+    }
+}
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::zone::ZoneRect;
+
+// Rectangle of the map rendered with its own post-processing preset, the color grading,
+// vignette and bloom of the camera. A zone covering the whole map give it its default
+// look, smaller zones inside it change it in a tunnel or a lab. Only seen, it does
+// nothing to the simulation.
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct GradingZoneConfig {
+    // Name of the preset, the game map it to the values of its preset file
+    pub preset: String,
+    pub size: Vec2,
+}
+
+impl Default for GradingZoneConfig {
+    fn default() -> Self {
+        GradingZoneConfig {
+            preset: "outdoor".to_string(),
+            size: Vec2::splat(256.0),
+        }
+    }
+}
+
+impl GradingZoneConfig {
+    // The zone is centered on its entity
+    pub fn rect(&self, center: Vec2) -> ZoneRect {
+        ZoneRect::new(center, self.size)
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct GradingZoneComponent {
+    pub config: GradingZoneConfig,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_include_the_border() {
+        let config = GradingZoneConfig { size: Vec2::new(200.0, 100.0), ..Default::default() };
+        let rect = config.rect(Vec2::new(-50.0, 0.0));
+        assert!(rect.contains(Vec2::new(50.0, 50.0)));
+        assert!(!rect.contains(Vec2::new(50.1, 0.0)));
+        assert_eq!(rect.area(), 20000.0);
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub enum HazardConfig {
+    // Damage over time zone
+    FirePatch {
+        radius: f32,
+        damage_per_tick: f32,
+        tick_interval_frames: u32,
+    },
+    // Stun the players walking on it, then need to recharge
+    ElectricTrap {
+        radius: f32,
+        stun_frames: u32,
+        cooldown_frames: u32,
+    },
+    // Shootable barrel that explode when destroyed
+    ExplosiveBarrel {
+        health: f32,
+        blast_radius: f32,
+        blast_damage: f32,
+    },
+    // Damage the enemies crossing it, only while a switch of its circuit power it
+    ElectricFence {
+        size: Vec2,
+        damage_per_tick: f32,
+        tick_interval_frames: u32,
+        circuit: u32,
+    },
+    // Push the enemies in its area toward `push` every frame, powered like the fence
+    FanTrap {
+        size: Vec2,
+        push: Vec2,
+        circuit: u32,
+    },
+}
+
+impl HazardConfig {
+    // Circuit of the switches powering it, the others are always on
+    pub fn circuit(&self) -> Option<u32> {
+        match self {
+            HazardConfig::ElectricFence { circuit, .. } | HazardConfig::FanTrap { circuit, .. } => Some(*circuit),
+            _ => None,
+        }
+    }
+}
+
+impl Default for HazardConfig {
+    fn default() -> Self {
+        HazardConfig::FirePatch {
+            radius: 50.0,
+            damage_per_tick: 2.0,
+            tick_interval_frames: 30,
+        }
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct HazardComponent {
+    pub config: HazardConfig,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_powered_hazards_have_a_circuit() {
+        assert_eq!(HazardConfig::default().circuit(), None);
+        assert_eq!(HazardConfig::FanTrap { size: Vec2::splat(64.0), push: Vec2::X, circuit: 3 }.circuit(), Some(3));
+        assert_eq!(HazardConfig::ElectricFence { size: Vec2::splat(64.0), damage_per_tick: 10.0, tick_interval_frames: 15, circuit: 1 }.circuit(), Some(1));
+    }
+}
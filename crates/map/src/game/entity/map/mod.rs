@@ -1,5 +1,14 @@
+pub mod ambient;
+pub mod climb;
+pub mod destructible;
 pub mod door;
 pub mod player_spawn;
 pub mod enemy_spawn;
+pub mod equipment;
+pub mod grading;
+pub mod hazard;
 pub mod room;
+pub mod surface;
+pub mod switch;
 pub mod window;
+pub mod zone;
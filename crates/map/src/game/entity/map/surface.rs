@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::named_kind;
+
+use super::zone::ZoneRect;
+
+// What the ground is made of in a rectangle of the map. Only for what is seen and
+// heard, the footprints, the footsteps, it does nothing to the simulation. Outside
+// of every zone the ground is the default one.
+named_kind! {
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+    pub enum SurfaceKind {
+        #[default]
+        Ground => "ground",
+        Blood => "blood",
+        Mud => "mud",
+        Water => "water",
+        Metal => "metal",
+    }
+}
+
+impl SurfaceKind {
+    // Stick to the feet and leave prints on the next steps
+    pub fn is_sticky(&self) -> bool {
+        matches!(self, SurfaceKind::Blood | SurfaceKind::Mud)
+    }
+}
+
+#[derive(Debug, Clone, Reflect, Serialize, Deserialize)]
+pub struct SurfaceZoneConfig {
+    pub surface: SurfaceKind,
+    pub size: Vec2,
+}
+
+impl Default for SurfaceZoneConfig {
+    fn default() -> Self {
+        SurfaceZoneConfig {
+            surface: SurfaceKind::Ground,
+            size: Vec2::splat(64.0),
+        }
+    }
+}
+
+impl SurfaceZoneConfig {
+    // The zone is centered on its entity
+    pub fn rect(&self, center: Vec2) -> ZoneRect {
+        ZoneRect::new(center, self.size)
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct SurfaceZoneComponent {
+    pub config: SurfaceZoneConfig,
+}
+
+// Surface under a point from the zones and the center of each, the smallest zone
+// with the point inside win so a puddle can be put over a bigger zone
+pub fn surface_at<'a>(zones: impl Iterator<Item = (Vec2, &'a SurfaceZoneConfig)>, point: Vec2) -> SurfaceKind {
+    ZoneRect::smallest_containing(zones.map(|(center, zone)| (zone.rect(center), zone.surface)), point)
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_surface_names_round_trip() {
+        for kind in SurfaceKind::ALL {
+            assert_eq!(SurfaceKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(SurfaceKind::from_name("lava"), None);
+    }
+
+    #[test]
+    fn test_smallest_zone_win() {
+        let floor = SurfaceZoneConfig { surface: SurfaceKind::Metal, size: Vec2::splat(1000.0) };
+        let puddle = SurfaceZoneConfig { surface: SurfaceKind::Blood, size: Vec2::splat(50.0) };
+        let zones = [(Vec2::ZERO, &floor), (Vec2::new(100.0, 0.0), &puddle)];
+
+        assert_eq!(surface_at(zones.into_iter(), Vec2::new(110.0, 10.0)), SurfaceKind::Blood);
+        assert_eq!(surface_at(zones.into_iter(), Vec2::new(-100.0, 0.0)), SurfaceKind::Metal);
+        assert_eq!(surface_at(zones.into_iter(), Vec2::new(600.0, 0.0)), SurfaceKind::Ground);
+    }
+}
@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Switch of the map powering the hazards of its circuit for a while. The player pay
+// the cost to use it, after the power is gone it need to cool down before the next use.
+#[derive(Debug, Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+pub struct SwitchConfig {
+    pub circuit: u32,
+    pub cost: u32,
+    pub powered_frames: u32,
+    // Counted from the end of the power
+    pub cooldown_frames: u32,
+}
+
+impl Default for SwitchConfig {
+    fn default() -> Self {
+        SwitchConfig {
+            circuit: 0,
+            cost: 500,
+            powered_frames: 600,
+            cooldown_frames: 1800,
+        }
+    }
+}
+
+#[derive(Default, Component, Clone, Debug, Reflect)]
+pub struct SwitchComponent {
+    pub config: SwitchConfig,
+}
@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+// Rectangle of a zone of the map, centered on its entity. The zones can overlap, the
+// smallest one with the point inside win so a puddle or a tunnel can be put over a
+// bigger zone.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZoneRect {
+    pub center: Vec2,
+    pub size: Vec2,
+}
+
+impl ZoneRect {
+    pub fn new(center: Vec2, size: Vec2) -> Self {
+        Self { center, size }
+    }
+
+    // The border is inside
+    pub fn contains(&self, point: Vec2) -> bool {
+        let half = self.size / 2.0;
+        (point.x - self.center.x).abs() <= half.x && (point.y - self.center.y).abs() <= half.y
+    }
+
+    pub fn area(&self) -> f32 {
+        self.size.x * self.size.y
+    }
+
+    // Value of the smallest zone with the point inside, none outside of every zone
+    pub fn smallest_containing<T>(zones: impl IntoIterator<Item = (ZoneRect, T)>, point: Vec2) -> Option<T> {
+        zones.into_iter()
+            .filter(|(rect, _)| rect.contains(point))
+            .min_by(|(a, _), (b, _)| a.area().total_cmp(&b.area()))
+            .map(|(_, value)| value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_include_the_border() {
+        let rect = ZoneRect::new(Vec2::new(-50.0, 0.0), Vec2::new(200.0, 100.0));
+        assert!(rect.contains(Vec2::new(50.0, 50.0)));
+        assert!(!rect.contains(Vec2::new(50.1, 0.0)));
+        assert!(!rect.contains(Vec2::new(-50.0, -50.1)));
+        assert_eq!(rect.area(), 20000.0);
+    }
+
+    #[test]
+    fn test_smallest_zone_win() {
+        let zones = [
+            (ZoneRect::new(Vec2::ZERO, Vec2::splat(1000.0)), "floor"),
+            (ZoneRect::new(Vec2::new(100.0, 0.0), Vec2::splat(50.0)), "puddle"),
+        ];
+        assert_eq!(ZoneRect::smallest_containing(zones, Vec2::new(110.0, 10.0)), Some("puddle"));
+        assert_eq!(ZoneRect::smallest_containing(zones, Vec2::new(-100.0, 0.0)), Some("floor"));
+        assert_eq!(ZoneRect::smallest_containing(zones, Vec2::new(600.0, 0.0)), None);
+    }
+}
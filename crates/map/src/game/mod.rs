@@ -1 +1,3 @@
 pub mod entity;
+pub mod nav;
+pub mod tiles;
@@ -0,0 +1,347 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::prelude::*;
+use utils::cache::CacheKey;
+
+// Walkable grid baked from the wall colliders of the map, used by the enemies
+// to find a path with A*. Everything is indexed with integers so the same walls
+// always give the same grid and the same paths on all peers. An obstacle that can
+// be broken is walkable with an extra cost instead, a path go through it when
+// going around is longer than the cost.
+
+/// Cost of moving to the next cell, the extra costs are in the same unit
+pub const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// Empty cells around the walls so paths can go around the outer walls
+const GRID_MARGIN_CELLS: i32 = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub enum NavObstacle {
+    Rect { center: Vec2, half_size: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+impl NavObstacle {
+    fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            NavObstacle::Rect { center, half_size } => (*center - *half_size, *center + *half_size),
+            NavObstacle::Circle { center, radius } => (*center - Vec2::splat(*radius), *center + Vec2::splat(*radius)),
+        }
+    }
+
+    // Is the point closer than `inflate` from the obstacle
+    fn blocks(&self, point: Vec2, inflate: f32) -> bool {
+        match self {
+            NavObstacle::Rect { center, half_size } => {
+                let delta = (point - *center).abs() - *half_size;
+                delta.max(Vec2::ZERO).length() < inflate || (delta.x < 0.0 && delta.y < 0.0)
+            }
+            NavObstacle::Circle { center, radius } => point.distance(*center) < radius + inflate,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct NavGrid {
+    pub origin: Vec2,
+    pub cell_size: f32,
+    pub width: i32,
+    pub height: i32,
+    pub walkable: Vec<bool>,
+    // Extra cost to enter each cell, 0 outside of the breakable obstacles
+    pub extra_cost: Vec<u32>,
+    // Number of obstacles the grid was baked with
+    pub obstacle_count: usize,
+    // Set by the owner of the grid from what it was baked with, to know when to bake again
+    pub signature: CacheKey,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct OpenNode {
+    f: u32,
+    h: u32,
+    index: usize,
+}
+
+// Min heap on f, then h, then the cell index so ties are always resolved the same way
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.f.cmp(&self.f)
+            .then(other.h.cmp(&self.h))
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    /// Rasterize the obstacles, a cell is blocked when its center is closer than
+    /// `agent_radius` from an obstacle.
+    pub fn bake(obstacles: &[NavObstacle], cell_size: f32, agent_radius: f32) -> Self {
+        Self::bake_with_costs(obstacles, &[], cell_size, agent_radius)
+    }
+
+    /// Same as `bake` with breakable obstacles, their cells stay walkable with the
+    /// extra cost of the obstacle, the highest one where they overlap.
+    pub fn bake_with_costs(obstacles: &[NavObstacle], costly: &[(NavObstacle, u32)], cell_size: f32, agent_radius: f32) -> Self {
+        if (obstacles.is_empty() && costly.is_empty()) || cell_size <= 0.0 {
+            return Self { cell_size, ..default() };
+        }
+
+        let mut min = Vec2::splat(f32::MAX);
+        let mut max = Vec2::splat(f32::MIN);
+        for obstacle in obstacles.iter().chain(costly.iter().map(|(obstacle, _)| obstacle)) {
+            let (o_min, o_max) = obstacle.bounds();
+            min = min.min(o_min);
+            max = max.max(o_max);
+        }
+
+        let min_cell = (min / cell_size).floor().as_ivec2() - IVec2::splat(GRID_MARGIN_CELLS);
+        let max_cell = (max / cell_size).ceil().as_ivec2() + IVec2::splat(GRID_MARGIN_CELLS);
+
+        let origin = min_cell.as_vec2() * cell_size;
+        let width = max_cell.x - min_cell.x;
+        let height = max_cell.y - min_cell.y;
+
+        let mut grid = Self {
+            origin,
+            cell_size,
+            width,
+            height,
+            walkable: vec![true; (width * height) as usize],
+            extra_cost: vec![0; (width * height) as usize],
+            obstacle_count: obstacles.len() + costly.len(),
+            signature: CacheKey::default(),
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let center = grid.cell_center(IVec2::new(x, y));
+                let index = grid.index(IVec2::new(x, y));
+                if obstacles.iter().any(|o| o.blocks(center, agent_radius)) {
+                    grid.walkable[index] = false;
+                    continue;
+                }
+                grid.extra_cost[index] = costly.iter()
+                    .filter(|(o, _)| o.blocks(center, agent_radius))
+                    .map(|(_, cost)| *cost)
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+
+        grid
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.walkable.is_empty()
+    }
+
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.width + cell.x) as usize
+    }
+
+    fn cell_from_index(&self, index: usize) -> IVec2 {
+        IVec2::new(index as i32 % self.width, index as i32 / self.width)
+    }
+
+    pub fn in_bounds(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.width && cell.y < self.height
+    }
+
+    // Cell of the position, clamped to the grid
+    pub fn world_to_cell(&self, position: Vec2) -> IVec2 {
+        let cell = ((position - self.origin) / self.cell_size).floor().as_ivec2();
+        cell.clamp(IVec2::ZERO, IVec2::new(self.width - 1, self.height - 1))
+    }
+
+    pub fn cell_center(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    pub fn is_walkable(&self, cell: IVec2) -> bool {
+        self.in_bounds(cell) && self.walkable[self.index(cell)]
+    }
+
+    pub fn cell_extra_cost(&self, cell: IVec2) -> u32 {
+        if self.in_bounds(cell) { self.extra_cost[self.index(cell)] } else { 0 }
+    }
+
+    fn heuristic(a: IVec2, b: IVec2) -> u32 {
+        let d = (a - b).abs();
+        let (low, high) = (d.x.min(d.y) as u32, d.x.max(d.y) as u32);
+        DIAGONAL_COST * low + STRAIGHT_COST * (high - low)
+    }
+
+    /// A* from start to goal, return the waypoints (goal included, start excluded)
+    /// or None if no path was found in `max_iterations`.
+    pub fn find_path(&self, start: Vec2, goal: Vec2, max_iterations: u32) -> Option<Vec<Vec2>> {
+        if self.is_empty() {
+            return Some(vec![goal]);
+        }
+
+        let start_cell = self.world_to_cell(start);
+        let goal_cell = self.world_to_cell(goal);
+        if start_cell == goal_cell {
+            return Some(vec![goal]);
+        }
+
+        let start_index = self.index(start_cell);
+        let goal_index = self.index(goal_cell);
+
+        let mut g_score = vec![u32::MAX; self.walkable.len()];
+        let mut came_from = vec![usize::MAX; self.walkable.len()];
+        let mut open = BinaryHeap::new();
+
+        g_score[start_index] = 0;
+        let h = Self::heuristic(start_cell, goal_cell);
+        open.push(OpenNode { f: h, h, index: start_index });
+
+        let mut iterations = 0;
+        while let Some(node) = open.pop() {
+            if node.index == goal_index {
+                return Some(self.build_path(&came_from, goal_index, goal));
+            }
+
+            iterations += 1;
+            if iterations > max_iterations {
+                return None;
+            }
+
+            let cell = self.cell_from_index(node.index);
+            let g = g_score[node.index];
+            if node.f > g + node.h {
+                // Stale entry, a better one was already processed
+                continue;
+            }
+
+            // Fixed order of the neighbours, part of the deterministic tie-breaking
+            for (dx, dy) in [(0, 1), (1, 0), (0, -1), (-1, 0), (1, 1), (1, -1), (-1, -1), (-1, 1)] {
+                let next = cell + IVec2::new(dx, dy);
+                let next_walkable = self.is_walkable(next) || (self.in_bounds(next) && next == goal_cell);
+                if !next_walkable {
+                    continue;
+                }
+
+                let diagonal = dx != 0 && dy != 0;
+                // No corner cutting
+                if diagonal && (!self.is_walkable(cell + IVec2::new(dx, 0)) || !self.is_walkable(cell + IVec2::new(0, dy))) {
+                    continue;
+                }
+
+                let next_index = self.index(next);
+                let tentative = g + if diagonal { DIAGONAL_COST } else { STRAIGHT_COST } + self.extra_cost[next_index];
+                if tentative < g_score[next_index] {
+                    g_score[next_index] = tentative;
+                    came_from[next_index] = node.index;
+                    let h = Self::heuristic(next, goal_cell);
+                    open.push(OpenNode { f: tentative + h, h, index: next_index });
+                }
+            }
+        }
+
+        None
+    }
+
+    fn build_path(&self, came_from: &[usize], goal_index: usize, goal: Vec2) -> Vec<Vec2> {
+        let mut cells = vec![];
+        let mut current = came_from[goal_index];
+        while current != usize::MAX && came_from[current] != usize::MAX {
+            cells.push(self.cell_from_index(current));
+            current = came_from[current];
+        }
+        cells.reverse();
+
+        // Keep only the cells where the direction change
+        let mut waypoints = vec![];
+        for i in 0..cells.len() {
+            let is_turn = i + 1 == cells.len() || i == 0 || (cells[i] - cells[i - 1]) != (cells[i + 1] - cells[i]);
+            if is_turn {
+                waypoints.push(self.cell_center(cells[i]));
+            }
+        }
+        waypoints.push(goal);
+        waypoints
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall_between() -> Vec<NavObstacle> {
+        // Vertical wall at x=0 from y=-100 to y=100
+        vec![NavObstacle::Rect { center: Vec2::ZERO, half_size: Vec2::new(10.0, 100.0) }]
+    }
+
+    #[test]
+    fn test_bake_blocks_wall_cells() {
+        let grid = NavGrid::bake(&wall_between(), 20.0, 5.0);
+        assert!(!grid.is_walkable(grid.world_to_cell(Vec2::ZERO)));
+        assert!(grid.is_walkable(grid.world_to_cell(Vec2::new(100.0, 0.0))));
+        assert_eq!(grid.obstacle_count, 1);
+    }
+
+    #[test]
+    fn test_path_go_around_wall() {
+        let grid = NavGrid::bake(&wall_between(), 20.0, 5.0);
+        let path = grid.find_path(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), 10000).expect("path should exist");
+
+        assert_eq!(*path.last().unwrap(), Vec2::new(100.0, 0.0));
+        for point in path.iter().take(path.len() - 1) {
+            assert!(grid.is_walkable(grid.world_to_cell(*point)), "waypoint {:?} is not walkable", point);
+        }
+        assert!(path.iter().any(|p| p.y.abs() > 100.0), "path should go around the wall");
+    }
+
+    #[test]
+    fn test_path_is_deterministic() {
+        let grid = NavGrid::bake(&wall_between(), 20.0, 5.0);
+        let a = grid.find_path(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), 10000);
+        let b = grid.find_path(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), 10000);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_empty_grid_direct_path() {
+        let grid = NavGrid::bake(&[], 20.0, 5.0);
+        assert_eq!(grid.find_path(Vec2::ZERO, Vec2::new(50.0, 50.0), 10), Some(vec![Vec2::new(50.0, 50.0)]));
+    }
+
+    #[test]
+    fn test_costly_obstacle_is_walkable() {
+        let grid = NavGrid::bake_with_costs(&[], &wall_between().into_iter().map(|o| (o, 50)).collect::<Vec<_>>(), 20.0, 5.0);
+        assert!(grid.is_walkable(grid.world_to_cell(Vec2::ZERO)));
+        assert_eq!(grid.cell_extra_cost(grid.world_to_cell(Vec2::ZERO)), 50);
+        assert_eq!(grid.cell_extra_cost(grid.world_to_cell(Vec2::new(100.0, 0.0))), 0);
+    }
+
+    #[test]
+    fn test_path_through_cheap_obstacle() {
+        let costly = |cost| wall_between().into_iter().map(|o| (o, cost)).collect::<Vec<_>>();
+        let start = Vec2::new(-100.0, 0.0);
+        let goal = Vec2::new(100.0, 0.0);
+
+        // Cheaper to break through than to walk around the wall
+        let cheap = NavGrid::bake_with_costs(&[], &costly(STRAIGHT_COST), 20.0, 5.0);
+        let path = cheap.find_path(start, goal, 10000).expect("path should exist");
+        assert!(path.iter().all(|p| p.y.abs() <= 100.0), "path should go through the obstacle");
+
+        // Too expensive, go around like a wall
+        let expensive = NavGrid::bake_with_costs(&[], &costly(STRAIGHT_COST * 100), 20.0, 5.0);
+        let path = expensive.find_path(start, goal, 10000).expect("path should exist");
+        assert!(path.iter().any(|p| p.y.abs() > 100.0), "path should go around the obstacle");
+    }
+
+    #[test]
+    fn test_no_path_in_iterations() {
+        let grid = NavGrid::bake(&wall_between(), 20.0, 5.0);
+        assert_eq!(grid.find_path(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), 1), None);
+    }
+}
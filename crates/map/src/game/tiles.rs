@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+
+// Tile layers of the map in world tiles, read from the int grid layers of the levels.
+// Only drawn by the background, the colliders come from the wall entities.
+#[derive(Clone, Debug, Default)]
+pub struct MapTileLayer {
+    pub name: String,
+    pub tile_size: f32,
+    // World tile of the bottom left cell
+    pub origin: IVec2,
+    pub width: i32,
+    pub height: i32,
+    // Value of each cell from the bottom row, 0 is an empty cell
+    pub data: Vec<u8>,
+    // Color of each value, the value 1 is the first color
+    pub palette: Vec<Color>,
+}
+
+// Every tile layer of the levels spawned, replaced when a level spawn
+#[derive(Resource, Clone, Debug, Default)]
+pub struct MapTileLayers {
+    pub layers: Vec<MapTileLayer>,
+}
@@ -2,6 +2,9 @@ use std::ops::RangeInclusive;
 
 use bevy::prelude::Resource;
 use serde::{Deserialize, Serialize};
+use utils::schema::Versioned;
+
+pub const MAP_GENERATION_SCHEMA_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum MapGenerationMode {
@@ -10,6 +13,9 @@ pub enum MapGenerationMode {
 
 #[derive(Debug, Resource, Serialize, Deserialize)]
 pub struct MapGenerationConfig {
+    #[serde(default)]
+    pub schema_version: u32,
+
     pub map_path: String,
 
     pub seed: i32,
@@ -25,6 +31,7 @@ pub struct MapGenerationConfig {
 impl Default for MapGenerationConfig {
     fn default() -> Self {
         Self {
+            schema_version: MAP_GENERATION_SCHEMA_VERSION,
             seed: 1,
             max_width: 1000,
             max_heigth: 1000,
@@ -35,6 +42,16 @@ impl Default for MapGenerationConfig {
     }
 }
 
+impl Versioned for MapGenerationConfig {
+    const ASSET_NAME: &'static str = "map generation config";
+    const SCHEMA_VERSION: u32 = MAP_GENERATION_SCHEMA_VERSION;
+
+    fn parse_version(_version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+        // First version, nothing to migrate yet
+        ron::de::from_bytes::<Self>(bytes).map(|config| Self { schema_version: MAP_GENERATION_SCHEMA_VERSION, ..config })
+    }
+}
+
 impl MapGenerationConfig {
     pub fn get_range_x(&self, my_size: i32) -> RangeInclusive<i32> {
         -self.max_width..=(self.max_width - my_size)
@@ -44,3 +61,24 @@ impl MapGenerationConfig {
         -self.max_heigth..=(self.max_heigth - my_size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use utils::schema::{from_ron, SchemaError};
+
+    use super::*;
+
+    #[test]
+    fn test_legacy_config_is_read_as_the_current_version() {
+        let config: MapGenerationConfig = from_ron(b"(map_path: \"map.ldtk\", seed: 3, max_width: 10, max_heigth: 20, max_room: 2, mode: Basic)", "map.ron").unwrap();
+        assert_eq!(config.schema_version, MAP_GENERATION_SCHEMA_VERSION);
+        assert_eq!(config.seed, 3);
+    }
+
+    #[test]
+    fn test_future_config_is_refused() {
+        let bytes = format!("(schema_version: {}, map_path: \"\", seed: 1, max_width: 1, max_heigth: 1, max_room: 1, mode: Basic)", MAP_GENERATION_SCHEMA_VERSION + 1);
+        let error = from_ron::<MapGenerationConfig>(bytes.as_bytes(), "map.ron").unwrap_err();
+        assert!(matches!(error, SchemaError::FutureVersion { .. }));
+    }
+}
@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::ambient::{AmbientZoneComponent, AmbientZoneConfig};
+use crate::ldtk::map_const;
+
+impl AmbientZoneComponent {
+    pub fn from_field(entity_instance: &EntityInstance) -> AmbientZoneComponent {
+        let default = AmbientZoneConfig::default();
+        AmbientZoneComponent {
+            config: AmbientZoneConfig {
+                ambience: entity_instance.get_string_field(map_const::FIELD_AMBIENCE_NAME).ok().cloned().unwrap_or(default.ambience),
+                volume: entity_instance.get_float_field(map_const::FIELD_VOLUME_NAME).copied().unwrap_or(default.volume),
+                // The trigger volume is the resized entity itself
+                size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+            },
+        }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct AmbientZoneBundle {
+    #[with(AmbientZoneComponent::from_field)]
+    ambient_zone: AmbientZoneComponent,
+}
@@ -0,0 +1,27 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::climb::{ClimbableWallComponent, ClimbableWallConfig};
+use crate::ldtk::map_const;
+
+impl ClimbableWallComponent {
+    pub fn from_field(entity_instance: &EntityInstance) -> ClimbableWallComponent {
+        let default = ClimbableWallConfig::default();
+        ClimbableWallComponent {
+            config: ClimbableWallConfig {
+                // The wall is the resized entity itself
+                size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+                climb_frames: entity_instance.get_int_field(map_const::FIELD_DURATION_NAME).map_or(default.climb_frames, |v| (*v).max(1) as u32),
+                cost: entity_instance.get_float_field(map_const::FIELD_COST_NAME).copied().unwrap_or(default.cost),
+            },
+        }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct ClimbableWallBundle {
+    #[with(ClimbableWallComponent::from_field)]
+    climbable_wall: ClimbableWallComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
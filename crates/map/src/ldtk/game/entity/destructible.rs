@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::destructible::{DestructibleComponent, DestructibleConfig, DestructibleKind};
+use crate::ldtk::map_const;
+
+impl DestructibleComponent {
+    pub fn from_field(entity_instance: &EntityInstance) -> DestructibleComponent {
+        let kind = entity_instance.get_string_field(map_const::FIELD_DESTRUCTIBLE_KIND_NAME).ok()
+            .and_then(|name| DestructibleKind::from_name(name))
+            .unwrap_or_default();
+        DestructibleComponent {
+            config: DestructibleConfig {
+                kind,
+                destructible: entity_instance.get_bool_field(map_const::FIELD_DESTRUCTIBLE_NAME).copied().unwrap_or(true),
+                health: entity_instance.get_float_field(map_const::FIELD_HEALTH_NAME).copied().unwrap_or(kind.default_health()),
+                // The blocking shape is the resized entity itself
+                size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+                flammable: entity_instance.get_bool_field(map_const::FIELD_FLAMMABLE_NAME).copied().unwrap_or(kind.default_flammable()),
+            },
+        }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct DestructibleBundle {
+    #[with(DestructibleComponent::from_field)]
+    destructible: DestructibleComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
@@ -0,0 +1,22 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::equipment::{EquipmentKind, EquipmentSpawnComponent};
+use crate::ldtk::map_const;
+
+impl EquipmentSpawnComponent {
+    pub fn from_field(entity_instance: &EntityInstance) -> EquipmentSpawnComponent {
+        let kind = entity_instance.get_string_field(map_const::FIELD_EQUIPMENT_NAME).ok()
+            .and_then(|name| EquipmentKind::from_name(name))
+            .unwrap_or_default();
+        EquipmentSpawnComponent { kind }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct EquipmentSpawnBundle {
+    #[with(EquipmentSpawnComponent::from_field)]
+    equipment: EquipmentSpawnComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
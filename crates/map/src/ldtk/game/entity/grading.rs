@@ -0,0 +1,24 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::grading::{GradingZoneComponent, GradingZoneConfig};
+use crate::ldtk::map_const;
+
+impl GradingZoneComponent {
+    pub fn from_field(entity_instance: &EntityInstance) -> GradingZoneComponent {
+        let default = GradingZoneConfig::default();
+        GradingZoneComponent {
+            config: GradingZoneConfig {
+                preset: entity_instance.get_string_field(map_const::FIELD_PRESET_NAME).ok().cloned().unwrap_or(default.preset),
+                // The zone is the resized entity itself
+                size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+            },
+        }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct GradingZoneBundle {
+    #[with(GradingZoneComponent::from_field)]
+    grading_zone: GradingZoneComponent,
+}
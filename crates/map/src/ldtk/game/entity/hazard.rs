@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::hazard::{HazardComponent, HazardConfig};
+use crate::ldtk::map_const;
+
+fn float_field(entity_instance: &EntityInstance, name: &str, default: f32) -> f32 {
+    entity_instance.get_float_field(name).copied().unwrap_or(default)
+}
+
+fn int_field(entity_instance: &EntityInstance, name: &str, default: u32) -> u32 {
+    entity_instance.get_int_field(name).map_or(default, |v| (*v).max(0) as u32)
+}
+
+impl HazardComponent {
+    pub fn fire_patch_from_field(entity_instance: &EntityInstance) -> HazardComponent {
+        HazardComponent {
+            config: HazardConfig::FirePatch {
+                radius: float_field(entity_instance, map_const::FIELD_RADIUS_NAME, 50.0),
+                damage_per_tick: float_field(entity_instance, map_const::FIELD_DAMAGE_NAME, 2.0),
+                tick_interval_frames: int_field(entity_instance, map_const::FIELD_INTERVAL_NAME, 30),
+            },
+        }
+    }
+
+    pub fn electric_trap_from_field(entity_instance: &EntityInstance) -> HazardComponent {
+        HazardComponent {
+            config: HazardConfig::ElectricTrap {
+                radius: float_field(entity_instance, map_const::FIELD_RADIUS_NAME, 40.0),
+                stun_frames: int_field(entity_instance, map_const::FIELD_DURATION_NAME, 60),
+                cooldown_frames: int_field(entity_instance, map_const::FIELD_INTERVAL_NAME, 300),
+            },
+        }
+    }
+
+    // The area is the resized entity itself
+    pub fn electric_fence_from_field(entity_instance: &EntityInstance) -> HazardComponent {
+        HazardComponent {
+            config: HazardConfig::ElectricFence {
+                size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+                damage_per_tick: float_field(entity_instance, map_const::FIELD_DAMAGE_NAME, 10.0),
+                tick_interval_frames: int_field(entity_instance, map_const::FIELD_INTERVAL_NAME, 15),
+                circuit: int_field(entity_instance, map_const::FIELD_CIRCUIT_NAME, 0),
+            },
+        }
+    }
+
+    // The push is along the angle in degrees, 0 to the right
+    pub fn fan_trap_from_field(entity_instance: &EntityInstance) -> HazardComponent {
+        let angle = float_field(entity_instance, map_const::FIELD_ANGLE_NAME, 0.0).to_radians();
+        let strength = float_field(entity_instance, map_const::FIELD_STRENGTH_NAME, 4.0);
+        HazardComponent {
+            config: HazardConfig::FanTrap {
+                size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+                push: Vec2::from_angle(angle) * strength,
+                circuit: int_field(entity_instance, map_const::FIELD_CIRCUIT_NAME, 0),
+            },
+        }
+    }
+
+    pub fn explosive_barrel_from_field(entity_instance: &EntityInstance) -> HazardComponent {
+        HazardComponent {
+            config: HazardConfig::ExplosiveBarrel {
+                health: float_field(entity_instance, map_const::FIELD_HEALTH_NAME, 20.0),
+                blast_radius: float_field(entity_instance, map_const::FIELD_RADIUS_NAME, 150.0),
+                blast_damage: float_field(entity_instance, map_const::FIELD_DAMAGE_NAME, 60.0),
+            },
+        }
+    }
+}
+
+// Only the config and the sprite, the game add the rollback, the health and the collider
+// of the hazards spawned by its code when they appear
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct FirePatchBundle {
+    #[with(HazardComponent::fire_patch_from_field)]
+    hazard: HazardComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct ElectricTrapBundle {
+    #[with(HazardComponent::electric_trap_from_field)]
+    hazard: HazardComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct ExplosiveBarrelBundle {
+    #[with(HazardComponent::explosive_barrel_from_field)]
+    hazard: HazardComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct ElectricFenceBundle {
+    #[with(HazardComponent::electric_fence_from_field)]
+    hazard: HazardComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct FanTrapBundle {
+    #[with(HazardComponent::fan_trap_from_field)]
+    hazard: HazardComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
@@ -1,3 +1,11 @@
+pub mod ambient;
+pub mod climb;
+pub mod destructible;
 pub mod door;
+pub mod equipment;
+pub mod grading;
+pub mod hazard;
 pub mod player_spawn;
+pub mod surface;
+pub mod switch;
 pub mod window;
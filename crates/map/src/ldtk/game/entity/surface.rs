@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::surface::{SurfaceKind, SurfaceZoneComponent, SurfaceZoneConfig};
+use crate::ldtk::map_const;
+
+impl SurfaceZoneComponent {
+    pub fn from_field(entity_instance: &EntityInstance) -> SurfaceZoneComponent {
+        SurfaceZoneComponent {
+            config: SurfaceZoneConfig {
+                surface: entity_instance.get_string_field(map_const::FIELD_SURFACE_NAME).ok()
+                    .and_then(|name| SurfaceKind::from_name(name))
+                    .unwrap_or_default(),
+                // The zone is the resized entity itself
+                size: Vec2::new(entity_instance.width as f32, entity_instance.height as f32),
+            },
+        }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct SurfaceZoneBundle {
+    #[with(SurfaceZoneComponent::from_field)]
+    surface_zone: SurfaceZoneComponent,
+}
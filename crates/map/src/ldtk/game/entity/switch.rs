@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::game::entity::map::switch::{SwitchComponent, SwitchConfig};
+use crate::ldtk::map_const;
+
+impl SwitchComponent {
+    pub fn from_field(entity_instance: &EntityInstance) -> SwitchComponent {
+        let default = SwitchConfig::default();
+        let int_field = |name: &str, default: u32| entity_instance.get_int_field(name).map_or(default, |v| (*v).max(0) as u32);
+        SwitchComponent {
+            config: SwitchConfig {
+                circuit: int_field(map_const::FIELD_CIRCUIT_NAME, default.circuit),
+                cost: int_field(map_const::FIELD_PRICE_NAME, default.cost),
+                powered_frames: int_field(map_const::FIELD_DURATION_NAME, default.powered_frames),
+                cooldown_frames: int_field(map_const::FIELD_COOLDOWN_NAME, default.cooldown_frames),
+            },
+        }
+    }
+}
+
+#[derive(Default, Bundle, LdtkEntity)]
+pub struct HazardSwitchBundle {
+    #[with(SwitchComponent::from_field)]
+    switch: SwitchComponent,
+    #[sprite_sheet]
+    sprite_sheet: Sprite,
+}
@@ -1 +1,2 @@
 pub mod add_level_components;
+pub mod tile_layers;
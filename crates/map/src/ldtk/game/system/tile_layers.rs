@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use bevy_ecs_ldtk::{ldtk::{LayerDefinition, LayerInstance, Level, Type}, prelude::*};
+
+use crate::{game::tiles::{MapTileLayer, MapTileLayers}, ldtk::map_const};
+
+// Color of a value missing from the definition of the layer
+const UNKNOWN_VALUE_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+
+// Int grid layer of a level in world tiles, the rows of LDtk go down and the ones of
+// the world go up. None for the other layers and for the connections between levels.
+pub fn tile_layer_from_ldtk(level: &Level, layer: &LayerInstance, definition: Option<&LayerDefinition>) -> Option<MapTileLayer> {
+    if layer.layer_instance_type != Type::IntGrid || layer.identifier == map_const::LAYER_CONNECTION {
+        return None;
+    }
+    let grid_size = layer.grid_size.max(1);
+    let (width, height) = (layer.c_wid, layer.c_hei);
+    if width <= 0 || height <= 0 || layer.int_grid_csv.len() != (width * height) as usize {
+        return None;
+    }
+
+    let left = level.world_x + layer.px_total_offset_x;
+    let bottom = -(level.world_y + layer.px_total_offset_y + height * grid_size);
+    let origin = IVec2::new(left.div_euclid(grid_size), bottom.div_euclid(grid_size));
+
+    let mut data = Vec::with_capacity(layer.int_grid_csv.len());
+    for row in layer.int_grid_csv.chunks(width as usize).rev() {
+        data.extend(row.iter().map(|value| u8::try_from(*value).unwrap_or(0)));
+    }
+
+    let values = definition.map(|definition| definition.int_grid_values.as_slice()).unwrap_or_default();
+    let max_value = data.iter().copied().max().unwrap_or(0);
+    let palette = (1..=max_value)
+        .map(|value| values.iter().find(|v| v.value == value as i32).map_or(UNKNOWN_VALUE_COLOR, |v| v.color))
+        .collect();
+
+    Some(MapTileLayer {
+        name: layer.identifier.clone(),
+        tile_size: grid_size as f32,
+        origin,
+        width,
+        height,
+        data,
+        palette,
+    })
+}
+
+pub fn read_map_tile_layers(
+    mut level_events: EventReader<LevelEvent>,
+    levels: Query<&LevelIid>,
+    projects: Query<&LdtkProjectHandle>,
+    project_assets: Res<Assets<LdtkProject>>,
+    mut map_tiles: ResMut<MapTileLayers>,
+) {
+    let spawned = level_events.read().filter(|event| matches!(event, LevelEvent::Spawned(_))).count();
+    if spawned == 0 {
+        return;
+    }
+    let Some(project) = projects.get_single().ok().and_then(|handle| project_assets.get(handle)) else {
+        return;
+    };
+    let definitions = &project.json_data().defs.layers;
+
+    let mut layers = vec![];
+    for level_iid in levels.iter() {
+        let Some(level) = project.get_raw_level_by_iid(&level_iid.to_string()) else {
+            continue;
+        };
+        for layer in level.layer_instances.iter().flatten() {
+            let definition = definitions.iter().find(|definition| definition.uid == layer.layer_def_uid);
+            layers.extend(tile_layer_from_ldtk(level, layer, definition));
+        }
+    }
+    map_tiles.layers = layers;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::LdtkJson;
+    use utils::get_crate_root_path;
+
+    use crate::ldtk::loader::file::load_ldtk_json_file;
+
+    use super::*;
+
+    fn test_map() -> LdtkJson {
+        load_ldtk_json_file(get_crate_root_path!("../../assets/exemples/test_map.ldtk"))
+            .expect("Failed to deserialize JSON")
+    }
+
+    fn layer_of<'a>(json: &'a LdtkJson, identifier: &str) -> (&'a Level, &'a LayerInstance, Option<&'a LayerDefinition>) {
+        let level = &json.levels[0];
+        let layer = level.layer_instances.as_ref().unwrap().iter().find(|layer| layer.identifier == identifier).unwrap();
+        let definition = json.defs.layers.iter().find(|definition| definition.uid == layer.layer_def_uid);
+        (level, layer, definition)
+    }
+
+    #[test]
+    fn test_walls_layer_is_read() {
+        let json = test_map();
+        let (level, layer, definition) = layer_of(&json, "Walls");
+        let tiles = tile_layer_from_ldtk(level, layer, definition).unwrap();
+
+        assert_eq!(tiles.tile_size, 16.0);
+        assert_eq!((tiles.width, tiles.height), (layer.c_wid, layer.c_hei));
+        // The level is at the origin of the world, its bottom row under it
+        assert_eq!(tiles.origin, IVec2::new(0, -layer.c_hei));
+        assert_eq!(tiles.palette.len(), 1);
+
+        // The first row of LDtk is the top one, the last of the world
+        let top_left = layer.int_grid_csv[0] as u8;
+        assert_eq!(tiles.data[((tiles.height - 1) * tiles.width) as usize], top_left);
+        let bottom_right = *layer.int_grid_csv.last().unwrap() as u8;
+        assert_eq!(tiles.data[(tiles.width - 1) as usize], bottom_right);
+    }
+
+    #[test]
+    fn test_connections_and_entities_are_not_tiles() {
+        let json = test_map();
+        for identifier in [map_const::LAYER_CONNECTION, map_const::LAYER_ENTITY] {
+            let (level, layer, definition) = layer_of(&json, identifier);
+            assert!(tile_layer_from_ldtk(level, layer, definition).is_none());
+        }
+    }
+}
@@ -10,6 +10,18 @@ pub const ENTITY_CRATE_LOCATION: &str = "CrateLocation";
 pub const ENTITY_WEAPON_LOCATION: &str = "WeaponLocation";
 pub const ENTITY_WINDOW_LOCATION: &str = "Window";
 pub const ENTITY_SODA_LOCATION: &str = "SodaLocation";
+pub const ENTITY_FIRE_PATCH_LOCATION: &str = "FirePatch";
+pub const ENTITY_ELECTRIC_TRAP_LOCATION: &str = "ElectricTrap";
+pub const ENTITY_EXPLOSIVE_BARREL_LOCATION: &str = "ExplosiveBarrel";
+pub const ENTITY_ELECTRIC_FENCE_LOCATION: &str = "ElectricFence";
+pub const ENTITY_FAN_TRAP_LOCATION: &str = "FanTrap";
+pub const ENTITY_HAZARD_SWITCH_LOCATION: &str = "HazardSwitch";
+pub const ENTITY_AMBIENT_ZONE_LOCATION: &str = "AmbientZone";
+pub const ENTITY_EQUIPMENT_LOCATION: &str = "Equipment";
+pub const ENTITY_DESTRUCTIBLE_LOCATION: &str = "Destructible";
+pub const ENTITY_SURFACE_ZONE_LOCATION: &str = "SurfaceZone";
+pub const ENTITY_CLIMBABLE_WALL_LOCATION: &str = "ClimbableWall";
+pub const ENTITY_GRADING_ZONE_LOCATION: &str = "GradingZone";
 
 // pub const FIELD_BOOL_TYPE: &str = "Bool";
 // pub const FIELD_INT_TYPE: &str = "Int";
@@ -18,3 +30,22 @@ pub const FIELD_PRICE_NAME: &str = "price";
 // pub const FIELD_PRICE_TYPE: &str = FIELD_INT_TYPE;
 pub const FIELD_ELECTRIFY_NAME: &str = "electrify";
 // pub const FIELD_ELECTRIFY_TYPE: &str = FIELD_BOOL_TYPE;
+
+pub const FIELD_RADIUS_NAME: &str = "radius";
+pub const FIELD_DAMAGE_NAME: &str = "damage";
+pub const FIELD_INTERVAL_NAME: &str = "interval";
+pub const FIELD_DURATION_NAME: &str = "duration";
+pub const FIELD_HEALTH_NAME: &str = "health";
+pub const FIELD_AMBIENCE_NAME: &str = "ambience";
+pub const FIELD_VOLUME_NAME: &str = "volume";
+pub const FIELD_EQUIPMENT_NAME: &str = "equipment";
+pub const FIELD_DESTRUCTIBLE_KIND_NAME: &str = "kind";
+pub const FIELD_DESTRUCTIBLE_NAME: &str = "destructible";
+pub const FIELD_FLAMMABLE_NAME: &str = "flammable";
+pub const FIELD_CIRCUIT_NAME: &str = "circuit";
+pub const FIELD_COOLDOWN_NAME: &str = "cooldown";
+pub const FIELD_ANGLE_NAME: &str = "angle";
+pub const FIELD_STRENGTH_NAME: &str = "strength";
+pub const FIELD_SURFACE_NAME: &str = "surface";
+pub const FIELD_COST_NAME: &str = "cost";
+pub const FIELD_PRESET_NAME: &str = "preset";
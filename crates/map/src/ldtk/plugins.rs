@@ -1,15 +1,27 @@
 use bevy::prelude::*;
 use bevy_ecs_ldtk::prelude::*;
 
-use crate::game::entity::map::{
-    door::DoorComponent, player_spawn::PlayerSpawnComponent, room::RoomComponent,
-    window::WindowComponent,
-};
+use crate::game::{entity::map::{
+    ambient::AmbientZoneComponent, climb::ClimbableWallComponent, destructible::DestructibleComponent, door::DoorComponent, equipment::EquipmentSpawnComponent, grading::GradingZoneComponent, hazard::HazardComponent, player_spawn::PlayerSpawnComponent,
+    room::RoomComponent, surface::SurfaceZoneComponent, switch::SwitchComponent, window::WindowComponent,
+}, tiles::MapTileLayers};
 
 use super::{
     game::{
-        entity::{door::DoorBundle, player_spawn::PlayerSpawnBundle, window::WindowBundle},
-        system::add_level_components::add_room_component_to_ldtk_level,
+        entity::{
+            ambient::AmbientZoneBundle,
+            climb::ClimbableWallBundle,
+            destructible::DestructibleBundle,
+            door::DoorBundle,
+            equipment::EquipmentSpawnBundle,
+            grading::GradingZoneBundle,
+            hazard::{ElectricFenceBundle, ElectricTrapBundle, ExplosiveBarrelBundle, FanTrapBundle, FirePatchBundle},
+            player_spawn::PlayerSpawnBundle,
+            surface::SurfaceZoneBundle,
+            switch::HazardSwitchBundle,
+            window::WindowBundle,
+        },
+        system::{add_level_components::add_room_component_to_ldtk_level, tile_layers::read_map_tile_layers},
     },
     map_const,
 };
@@ -18,13 +30,31 @@ pub struct EntityPlugin;
 
 impl Plugin for EntityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            add_room_component_to_ldtk_level.run_if(on_event::<LevelEvent>),
-        )
+        app.init_resource::<MapTileLayers>()
+            .add_systems(
+                Update,
+                (
+                    add_room_component_to_ldtk_level.run_if(on_event::<LevelEvent>),
+                    read_map_tile_layers.run_if(on_event::<LevelEvent>),
+                ),
+            )
         .register_ldtk_entity::<PlayerSpawnBundle>(map_const::ENTITY_PLAYER_SPAWN_LOCATION)
         .register_ldtk_entity::<WindowBundle>(map_const::ENTITY_WINDOW_LOCATION)
-        .register_ldtk_entity::<DoorBundle>(map_const::ENTITY_DOOR_LOCATION);
+        .register_ldtk_entity::<DoorBundle>(map_const::ENTITY_DOOR_LOCATION)
+        .register_ldtk_entity::<FirePatchBundle>(map_const::ENTITY_FIRE_PATCH_LOCATION)
+        .register_ldtk_entity::<ElectricTrapBundle>(map_const::ENTITY_ELECTRIC_TRAP_LOCATION)
+        .register_ldtk_entity::<ExplosiveBarrelBundle>(
+            map_const::ENTITY_EXPLOSIVE_BARREL_LOCATION,
+        )
+        .register_ldtk_entity::<ElectricFenceBundle>(map_const::ENTITY_ELECTRIC_FENCE_LOCATION)
+        .register_ldtk_entity::<FanTrapBundle>(map_const::ENTITY_FAN_TRAP_LOCATION)
+        .register_ldtk_entity::<HazardSwitchBundle>(map_const::ENTITY_HAZARD_SWITCH_LOCATION)
+        .register_ldtk_entity::<AmbientZoneBundle>(map_const::ENTITY_AMBIENT_ZONE_LOCATION)
+        .register_ldtk_entity::<EquipmentSpawnBundle>(map_const::ENTITY_EQUIPMENT_LOCATION)
+        .register_ldtk_entity::<DestructibleBundle>(map_const::ENTITY_DESTRUCTIBLE_LOCATION)
+        .register_ldtk_entity::<SurfaceZoneBundle>(map_const::ENTITY_SURFACE_ZONE_LOCATION)
+        .register_ldtk_entity::<ClimbableWallBundle>(map_const::ENTITY_CLIMBABLE_WALL_LOCATION)
+        .register_ldtk_entity::<GradingZoneBundle>(map_const::ENTITY_GRADING_ZONE_LOCATION);
     }
 }
 
@@ -54,6 +84,14 @@ impl Plugin for MyWorldInspectorPlugin {
                 .register_type::<DoorComponent>()
                 .register_type::<WindowComponent>()
                 .register_type::<PlayerSpawnComponent>()
+                .register_type::<HazardComponent>()
+                .register_type::<AmbientZoneComponent>()
+                .register_type::<EquipmentSpawnComponent>()
+                .register_type::<DestructibleComponent>()
+                .register_type::<SwitchComponent>()
+                .register_type::<SurfaceZoneComponent>()
+                .register_type::<ClimbableWallComponent>()
+                .register_type::<GradingZoneComponent>()
                 .add_plugins(WorldInspectorPlugin::new());
         }
     }
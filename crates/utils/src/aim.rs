@@ -0,0 +1,292 @@
+use bevy::math::{IVec2, Vec2};
+
+// Aim is sent to the peers as a fixed point angle and a quantized magnitude packed
+// in a u16. Decoding only use integer math so all peers get the same vector whatever
+// their platform, window size or zoom level.
+
+/// Bits used by the angle, the rest is for the magnitude
+pub const AIM_ANGLE_BITS: u32 = 10;
+pub const AIM_MAGNITUDE_BITS: u32 = 16 - AIM_ANGLE_BITS;
+
+pub const AIM_ANGLE_STEPS: u32 = 1 << AIM_ANGLE_BITS;
+pub const AIM_MAGNITUDE_STEPS: u32 = (1 << AIM_MAGNITUDE_BITS) - 1;
+
+/// World distance represented by the maximum magnitude, aiming further is clamped
+pub const AIM_MAX_DISTANCE: i32 = 512;
+
+const QUARTER_STEPS: usize = (AIM_ANGLE_STEPS / 4) as usize;
+const SIN_SHIFT: u32 = 14;
+
+// sin(x) for x in [0, PI/2] in Q14, computed with a Taylor series in integer at compile time
+const fn fixed_sin_quarter(step: usize) -> i32 {
+    const ONE: i128 = 1 << 30;
+    const PI_Q30: i128 = 3373259426; // PI * 2^30
+
+    let x = (step as i128) * PI_Q30 / 2 / QUARTER_STEPS as i128;
+    let x2 = x * x / ONE;
+
+    let mut term = x;
+    let mut sum = x;
+    let mut n = 1;
+    while n < 9 {
+        term = -(term * x2 / ONE) / ((2 * n) * (2 * n + 1));
+        sum += term;
+        n += 1;
+    }
+
+    // Round to Q14
+    ((sum + (1 << 15)) >> 16) as i32
+}
+
+const fn build_sin_table() -> [i32; QUARTER_STEPS + 1] {
+    let mut table = [0; QUARTER_STEPS + 1];
+    let mut i = 0;
+    while i <= QUARTER_STEPS {
+        table[i] = fixed_sin_quarter(i);
+        i += 1;
+    }
+    table
+}
+
+const SIN_TABLE: [i32; QUARTER_STEPS + 1] = build_sin_table();
+
+/// (cos, sin) of the angle step in Q14
+pub fn fixed_cos_sin(angle_step: u32) -> (i32, i32) {
+    let step = (angle_step % AIM_ANGLE_STEPS) as usize;
+    let quadrant = step / QUARTER_STEPS;
+    let r = step % QUARTER_STEPS;
+
+    let a = SIN_TABLE[r];
+    let b = SIN_TABLE[QUARTER_STEPS - r];
+    match quadrant {
+        0 => (b, a),
+        1 => (-a, b),
+        2 => (-b, -a),
+        _ => (a, -b),
+    }
+}
+
+/// Pack a world offset from the player to the pointer.
+/// Run only on the local peer, float math is fine here.
+pub fn encode_aim(offset: Vec2) -> u16 {
+    let distance = offset.length();
+    let magnitude = ((distance / AIM_MAX_DISTANCE as f32) * AIM_MAGNITUDE_STEPS as f32)
+        .round()
+        .clamp(0.0, AIM_MAGNITUDE_STEPS as f32) as u32;
+
+    if magnitude == 0 {
+        return 0;
+    }
+
+    let angle = offset.y.atan2(offset.x).rem_euclid(std::f32::consts::TAU);
+    let angle_step = (angle / std::f32::consts::TAU * AIM_ANGLE_STEPS as f32).round() as u32 % AIM_ANGLE_STEPS;
+
+    ((angle_step << AIM_MAGNITUDE_BITS) | magnitude) as u16
+}
+
+pub fn aim_angle_step(aim: u16) -> u32 {
+    aim as u32 >> AIM_MAGNITUDE_BITS
+}
+
+pub fn aim_magnitude(aim: u16) -> u32 {
+    aim as u32 & AIM_MAGNITUDE_STEPS
+}
+
+/// True when the two aims are at most the given steps apart, the angle wrap around.
+/// Not aiming is only close to not aiming
+pub fn aim_close(a: u16, b: u16, angle_steps: u32, magnitude_steps: u32) -> bool {
+    if (aim_magnitude(a) == 0) != (aim_magnitude(b) == 0) {
+        return false;
+    }
+    let angle = (aim_angle_step(a) + AIM_ANGLE_STEPS - aim_angle_step(b)) % AIM_ANGLE_STEPS;
+    let angle = angle.min(AIM_ANGLE_STEPS - angle);
+    angle <= angle_steps && aim_magnitude(a).abs_diff(aim_magnitude(b)) <= magnitude_steps
+}
+
+/// What the 2D camera show, enough to place the pointer in the world without the
+/// matrices of the render. The camera modes only change its center and its scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraView {
+    /// World position at the center of the window
+    pub center: Vec2,
+    /// World units per logical pixel, the scale of the orthographic projection
+    pub scale: f32,
+    /// Logical size of the window
+    pub window_size: Vec2,
+}
+
+impl CameraView {
+    /// World position under a pointer in logical window coordinates, y down from the top left
+    pub fn pointer_to_world(&self, pointer: Vec2) -> Vec2 {
+        let from_center = pointer - self.window_size / 2.0;
+        self.center + Vec2::new(from_center.x, -from_center.y) * self.scale
+    }
+
+    /// Logical window coordinates of a world position, the inverse of `pointer_to_world`
+    pub fn world_to_pointer(&self, world: Vec2) -> Vec2 {
+        let from_center = (world - self.center) / self.scale.max(f32::EPSILON);
+        self.window_size / 2.0 + Vec2::new(from_center.x, -from_center.y)
+    }
+}
+
+/// World offset from the player to what is under the pointer, clamped to what the input
+/// carry. The same spot of the world give the same aim whatever the zoom or the window,
+/// it is then quantized by `encode_aim`
+pub fn pointer_aim(view: &CameraView, pointer: Vec2, player: Vec2) -> Vec2 {
+    (view.pointer_to_world(pointer) - player).clamp_length_max(AIM_MAX_DISTANCE as f32)
+}
+
+/// Deterministic world offset of the aim, zero when not aiming
+pub fn decode_aim(aim: u16) -> IVec2 {
+    let magnitude = aim_magnitude(aim) as i32;
+    if magnitude == 0 {
+        return IVec2::ZERO;
+    }
+
+    let distance = magnitude * AIM_MAX_DISTANCE / AIM_MAGNITUDE_STEPS as i32;
+    let (cos, sin) = fixed_cos_sin(aim_angle_step(aim));
+
+    IVec2::new(
+        (cos * distance) >> SIN_SHIFT,
+        (sin * distance) >> SIN_SHIFT,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sin_table_bounds() {
+        assert_eq!(SIN_TABLE[0], 0);
+        assert_eq!(SIN_TABLE[QUARTER_STEPS], 1 << SIN_SHIFT);
+        for i in 0..QUARTER_STEPS {
+            assert!(SIN_TABLE[i] <= SIN_TABLE[i + 1], "Sin table should be increasing on the first quadrant.");
+        }
+    }
+
+    #[test]
+    fn test_aim_close() {
+        let aim = |angle_step: u32, magnitude: u32| ((angle_step << AIM_MAGNITUDE_BITS) | magnitude) as u16;
+        assert!(aim_close(aim(10, 20), aim(11, 19), 1, 1));
+        assert!(!aim_close(aim(10, 20), aim(12, 20), 1, 1));
+        assert!(!aim_close(aim(10, 20), aim(10, 22), 1, 1));
+        // Around the first step
+        assert!(aim_close(aim(0, 20), aim(AIM_ANGLE_STEPS - 1, 20), 1, 1));
+        assert!(!aim_close(0, aim(0, 1), 1, 1));
+        assert!(aim_close(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_no_aim() {
+        assert_eq!(encode_aim(Vec2::ZERO), 0);
+        assert_eq!(decode_aim(0), IVec2::ZERO);
+    }
+
+    #[test]
+    fn test_round_trip_cardinal() {
+        assert_eq!(decode_aim(encode_aim(Vec2::new(AIM_MAX_DISTANCE as f32, 0.0))), IVec2::new(AIM_MAX_DISTANCE, 0));
+        assert_eq!(decode_aim(encode_aim(Vec2::new(0.0, AIM_MAX_DISTANCE as f32))), IVec2::new(0, AIM_MAX_DISTANCE));
+        assert_eq!(decode_aim(encode_aim(Vec2::new(-(AIM_MAX_DISTANCE as f32), 0.0))), IVec2::new(-AIM_MAX_DISTANCE, 0));
+        assert_eq!(decode_aim(encode_aim(Vec2::new(0.0, -(AIM_MAX_DISTANCE as f32)))), IVec2::new(0, -AIM_MAX_DISTANCE));
+    }
+
+    #[test]
+    fn test_round_trip_direction() {
+        for i in 0..360 {
+            let angle = (i as f32).to_radians();
+            let offset = Vec2::new(angle.cos(), angle.sin()) * 300.0;
+            let decoded = decode_aim(encode_aim(offset)).as_vec2();
+
+            let error = offset.normalize().angle_to(decoded.normalize()).abs();
+            assert!(error < 0.01, "Angle error {} too big for {} degrees", error, i);
+            assert!((decoded.length() - 300.0).abs() < 10.0, "Magnitude {} too far from 300", decoded.length());
+        }
+    }
+
+    #[test]
+    fn test_distance_is_clamped() {
+        let decoded = decode_aim(encode_aim(Vec2::new(10000.0, 0.0)));
+        assert_eq!(decoded, IVec2::new(AIM_MAX_DISTANCE, 0));
+    }
+
+    // From the smallest supported window to the ultrawide
+    const WINDOW_SIZES: [Vec2; 4] = [
+        Vec2::new(1280.0, 720.0),
+        Vec2::new(1920.0, 1080.0),
+        Vec2::new(2560.0, 1440.0),
+        Vec2::new(3440.0, 1440.0),
+    ];
+
+    fn assert_near(a: Vec2, b: Vec2, tolerance: f32) {
+        assert!(a.distance(b) <= tolerance, "{} too far from {}", a, b);
+    }
+
+    #[test]
+    fn test_pointer_axis() {
+        let view = CameraView { center: Vec2::new(100.0, 50.0), scale: 2.0, window_size: Vec2::new(800.0, 600.0) };
+        assert_near(view.pointer_to_world(Vec2::new(400.0, 300.0)), Vec2::new(100.0, 50.0), 0.001);
+        // The window y go down, the world one up
+        assert_near(view.pointer_to_world(Vec2::new(410.0, 290.0)), Vec2::new(120.0, 70.0), 0.001);
+        assert_near(view.pointer_to_world(Vec2::ZERO), Vec2::new(-700.0, 650.0), 0.001);
+    }
+
+    // The camera follow the player, its pointer aim the same whatever the window
+    #[test]
+    fn test_player_lock_aim() {
+        let player = Vec2::new(-340.0, 125.0);
+        for window_size in WINDOW_SIZES {
+            let view = CameraView { center: player, scale: 5.0, window_size };
+            assert_near(pointer_aim(&view, window_size / 2.0, player), Vec2::ZERO, 0.001);
+            assert_near(pointer_aim(&view, window_size / 2.0 + Vec2::new(40.0, 0.0), player), Vec2::new(200.0, 0.0), 0.001);
+            assert_near(pointer_aim(&view, window_size / 2.0 + Vec2::new(0.0, 30.0), player), Vec2::new(0.0, -150.0), 0.001);
+        }
+    }
+
+    // The camera zoom out between the players, the local one is off center
+    #[test]
+    fn test_players_lock_aim() {
+        let player = Vec2::new(-600.0, -200.0);
+        let target = player + Vec2::new(300.0, 120.0);
+        for window_size in WINDOW_SIZES {
+            for scale in [5.0, 9.5, 15.0] {
+                let view = CameraView { center: Vec2::new(0.0, 100.0), scale, window_size };
+                let on_player = view.world_to_pointer(player);
+                assert_near(pointer_aim(&view, on_player, player), Vec2::ZERO, 0.01);
+                // Pointing the same spot of the world give the same aim at every zoom
+                let on_target = view.world_to_pointer(target);
+                assert_near(pointer_aim(&view, on_target, player), target - player, 0.01);
+            }
+        }
+    }
+
+    // The camera moved away, the player can be off screen and the aim is clamped
+    #[test]
+    fn test_unlock_aim() {
+        let player = Vec2::new(0.0, 0.0);
+        for window_size in WINDOW_SIZES {
+            let view = CameraView { center: Vec2::new(5000.0, 0.0), scale: 5.0, window_size };
+            let aim = pointer_aim(&view, window_size / 2.0, player);
+            assert_near(aim, Vec2::new(AIM_MAX_DISTANCE as f32, 0.0), 0.01);
+
+            let near = view.world_to_pointer(Vec2::new(4990.0, 10.0));
+            assert_near(view.pointer_to_world(near), Vec2::new(4990.0, 10.0), 0.01);
+        }
+    }
+
+    // What the peers decode stay on the spot under the pointer, at every zoom
+    #[test]
+    fn test_quantized_aim_is_zoom_independent() {
+        let player = Vec2::new(75.0, -30.0);
+        let target = player + Vec2::new(-210.0, 260.0);
+        let decoded: Vec<IVec2> = [1.0, 5.0, 15.0].iter()
+            .map(|scale| {
+                let view = CameraView { center: Vec2::new(40.0, 10.0), scale: *scale, window_size: WINDOW_SIZES[1] };
+                decode_aim(encode_aim(pointer_aim(&view, view.world_to_pointer(target), player)))
+            })
+            .collect();
+        assert!(decoded.windows(2).all(|pair| pair[0] == pair[1]), "aim changed with the zoom: {:?}", decoded);
+        assert_near(decoded[0].as_vec2(), target - player, 10.0);
+    }
+}
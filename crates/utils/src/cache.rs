@@ -0,0 +1,158 @@
+use bevy::math::{Vec2, Vec3};
+
+// Memoization of expensive rollback systems. A rollback replay the same frames again
+// and most of the entities get the exact same inputs as the first time, so the result
+// computed the first time can be reused. Entries are stamped with the frame and a key
+// built from every input of the computation, a different input always miss the cache.
+// The cache is never part of the rollback state, it must only hold pure results.
+
+/// Number of frames kept, must be above the prediction window of the session, a
+/// rollback of the longest window still find the frame it go back to. Checked against
+/// `MAX_PREDICTION_WINDOW` of the game network module
+pub const CACHE_FRAMES: usize = 17;
+
+/// FNV-1a hash of the inputs of a computation, floats are hashed by their bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey(u64);
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+impl Default for CacheKey {
+    fn default() -> Self {
+        Self(FNV_OFFSET)
+    }
+}
+
+impl CacheKey {
+    pub fn with_u32(self, value: u32) -> Self {
+        let mut hash = self.0;
+        for byte in value.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+        Self(hash)
+    }
+
+    pub fn with_i32(self, value: i32) -> Self {
+        self.with_u32(value as u32)
+    }
+
+    pub fn with_usize(self, value: usize) -> Self {
+        let value = value as u64;
+        self.with_u32(value as u32).with_u32((value >> 32) as u32)
+    }
+
+    pub fn with_f32(self, value: f32) -> Self {
+        self.with_u32(value.to_bits())
+    }
+
+    pub fn with_vec2(self, value: Vec2) -> Self {
+        self.with_f32(value.x).with_f32(value.y)
+    }
+
+    pub fn with_vec3(self, value: Vec3) -> Self {
+        self.with_f32(value.x).with_f32(value.y).with_f32(value.z)
+    }
+
+    pub fn with_key(self, key: CacheKey) -> Self {
+        self.with_u32(key.0 as u32).with_u32((key.0 >> 32) as u32)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry<V> {
+    frame: u32,
+    key: CacheKey,
+    value: V,
+}
+
+/// Results of the last `CACHE_FRAMES` frames, one slot per frame
+#[derive(Clone, Debug)]
+pub struct FrameCache<V> {
+    entries: Vec<Option<CacheEntry<V>>>,
+    pub hits: u32,
+    pub misses: u32,
+}
+
+impl<V> Default for FrameCache<V> {
+    fn default() -> Self {
+        Self {
+            entries: (0..CACHE_FRAMES).map(|_| None).collect(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl<V> FrameCache<V> {
+    fn slot(frame: u32) -> usize {
+        frame as usize % CACHE_FRAMES
+    }
+
+    /// Value computed for this frame with the same inputs
+    pub fn get(&mut self, frame: u32, key: CacheKey) -> Option<&V> {
+        match &self.entries[Self::slot(frame)] {
+            Some(entry) if entry.frame == frame && entry.key == key => {
+                self.hits += 1;
+                Some(&entry.value)
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, frame: u32, key: CacheKey, value: V) {
+        self.entries[Self::slot(frame)] = Some(CacheEntry { frame, key, value });
+    }
+
+    /// Return the cached value or compute and store it
+    pub fn get_or_insert_with(&mut self, frame: u32, key: CacheKey, compute: impl FnOnce() -> V) -> &V {
+        if self.get(frame, key).is_none() {
+            self.insert(frame, key, compute());
+        }
+        &self.entries[Self::slot(frame)].as_ref().unwrap().value
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_depend_on_inputs() {
+        let a = CacheKey::default().with_vec2(Vec2::new(1.0, 2.0)).with_u32(3);
+        let b = CacheKey::default().with_vec2(Vec2::new(1.0, 2.0)).with_u32(3);
+        let c = CacheKey::default().with_vec2(Vec2::new(2.0, 1.0)).with_u32(3);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_hit_only_same_frame_and_key() {
+        let mut cache = FrameCache::default();
+        let key = CacheKey::default().with_u32(1);
+        cache.insert(10, key, 42);
+
+        assert_eq!(cache.get(10, key), Some(&42));
+        assert_eq!(cache.get(10, key.with_u32(2)), None);
+        assert_eq!(cache.get(11, key), None);
+        assert_eq!(cache.get(10 + CACHE_FRAMES as u32, key), None);
+        assert_eq!((cache.hits, cache.misses), (1, 3));
+    }
+
+    #[test]
+    fn test_get_or_insert_compute_once() {
+        let mut cache = FrameCache::default();
+        let key = CacheKey::default().with_f32(0.5);
+        let mut calls = 0;
+        for _ in 0..3 {
+            let value = *cache.get_or_insert_with(5, key, || { calls += 1; 7 });
+            assert_eq!(value, 7);
+        }
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::math::{IVec2, Vec2};
+
+use crate::sweep::{fixed_normalize, isqrt, saturate_i32, to_fixed, to_fixed_scalar, NORMAL_SCALE, POSITION_SCALE};
+
+// Contact between two overlapping shapes, in the same fixed point as the swept tests
+// so every peer push the characters out the same way. The shapes are given by their
+// center, a circle by its radius and a box by its half size.
+
+/// Overlap of a shape A with a shape B
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contact {
+    /// Direction to move A out of B, unit vector in Q14 (see NORMAL_SCALE)
+    pub normal: IVec2,
+    /// Distance to move A along the normal to separate them, in 1/1000 of a unit
+    pub depth: i32,
+    /// Point on the surface of B, in 1/1000 of a unit
+    pub point: IVec2,
+}
+
+impl Contact {
+    pub fn normal_vec2(&self) -> Vec2 {
+        self.normal.as_vec2() / NORMAL_SCALE as f32
+    }
+
+    pub fn depth_f32(&self) -> f32 {
+        self.depth as f32 / POSITION_SCALE
+    }
+
+    pub fn point_vec2(&self) -> Vec2 {
+        self.point.as_vec2() / POSITION_SCALE
+    }
+
+    /// Same contact seen from B
+    pub fn flip(&self) -> Self {
+        let offset = self.normal.as_i64vec2() * self.depth as i64 / NORMAL_SCALE as i64;
+        Self {
+            normal: -self.normal,
+            depth: self.depth,
+            point: IVec2::new(
+                saturate_i32(self.point.x as i128 - offset.x as i128),
+                saturate_i32(self.point.y as i128 - offset.y as i128),
+            ),
+        }
+    }
+}
+
+fn contact(normal: IVec2, depth: i128, point: (i128, i128)) -> Contact {
+    Contact { normal, depth: saturate_i32(depth), point: IVec2::new(saturate_i32(point.0), saturate_i32(point.1)) }
+}
+
+/// Circle A against circle B, touching is not a contact
+pub fn circle_circle(a: Vec2, radius_a: f32, b: Vec2, radius_b: f32) -> Option<Contact> {
+    let (ax, ay) = to_fixed(a);
+    let (bx, by) = to_fixed(b);
+    let radius = to_fixed_scalar(radius_a) + to_fixed_scalar(radius_b);
+
+    let (dx, dy) = (ax - bx, ay - by);
+    let distance_squared = dx * dx + dy * dy;
+    if distance_squared >= radius * radius {
+        return None;
+    }
+
+    // Same center, pushed along x
+    let normal = fixed_normalize(dx, dy).unwrap_or(IVec2::new(NORMAL_SCALE, 0));
+    let scale = NORMAL_SCALE as i128;
+    let radius_b = to_fixed_scalar(radius_b);
+    Some(contact(
+        normal,
+        radius - isqrt(distance_squared),
+        (bx + normal.x as i128 * radius_b / scale, by + normal.y as i128 * radius_b / scale),
+    ))
+}
+
+/// Box A against box B, touching is a contact like in the AABB test of the colliders
+pub fn aabb_aabb(a: Vec2, half_a: Vec2, b: Vec2, half_b: Vec2) -> Option<Contact> {
+    let (ax, ay) = to_fixed(a);
+    let (bx, by) = to_fixed(b);
+    let (hax, hay) = to_fixed(half_a);
+    let (hbx, hby) = to_fixed(half_b);
+
+    let (dx, dy) = (ax - bx, ay - by);
+    let overlap_x = hax + hbx - dx.abs();
+    let overlap_y = hay + hby - dy.abs();
+    if overlap_x < 0 || overlap_y < 0 {
+        return None;
+    }
+
+    // Out along the axis with the least overlap, on the face of B, in the middle of the shared part
+    let middle = |a: i128, ha: i128, b: i128, hb: i128| ((a - ha).max(b - hb) + (a + ha).min(b + hb)) / 2;
+    if overlap_x <= overlap_y {
+        let sign = if dx >= 0 { 1 } else { -1 };
+        Some(contact(IVec2::new(sign * NORMAL_SCALE, 0), overlap_x, (bx + sign as i128 * hbx, middle(ay, hay, by, hby))))
+    } else {
+        let sign = if dy >= 0 { 1 } else { -1 };
+        Some(contact(IVec2::new(0, sign * NORMAL_SCALE), overlap_y, (middle(ax, hax, bx, hbx), by + sign as i128 * hby)))
+    }
+}
+
+/// Circle A against box B, touching is not a contact
+pub fn circle_aabb(a: Vec2, radius: f32, b: Vec2, half_b: Vec2) -> Option<Contact> {
+    let (ax, ay) = to_fixed(a);
+    let (bx, by) = to_fixed(b);
+    let (hx, hy) = to_fixed(half_b);
+    let radius = to_fixed_scalar(radius);
+
+    let closest = (ax.clamp(bx - hx, bx + hx), ay.clamp(by - hy, by + hy));
+    let (dx, dy) = (ax - closest.0, ay - closest.1);
+
+    if dx == 0 && dy == 0 {
+        // Center inside the box, out by the nearest face
+        let (cx, cy) = (ax - bx, ay - by);
+        let (face_x, face_y) = (hx - cx.abs(), hy - cy.abs());
+        return Some(if face_x <= face_y {
+            let sign = if cx >= 0 { 1 } else { -1 };
+            contact(IVec2::new(sign * NORMAL_SCALE, 0), face_x + radius, (bx + sign as i128 * hx, ay))
+        } else {
+            let sign = if cy >= 0 { 1 } else { -1 };
+            contact(IVec2::new(0, sign * NORMAL_SCALE), face_y + radius, (ax, by + sign as i128 * hy))
+        });
+    }
+
+    let distance_squared = dx * dx + dy * dy;
+    if distance_squared >= radius * radius {
+        return None;
+    }
+    let normal = fixed_normalize(dx, dy).unwrap_or(IVec2::new(NORMAL_SCALE, 0));
+    Some(contact(normal, radius - isqrt(distance_squared), closest))
+}
+
+/// Box A against circle B
+pub fn aabb_circle(a: Vec2, half_a: Vec2, b: Vec2, radius: f32) -> Option<Contact> {
+    circle_aabb(b, radius, a, half_a).map(|contact| contact.flip())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circle_circle() {
+        let contact = circle_circle(Vec2::new(8.0, 0.0), 5.0, Vec2::ZERO, 5.0).unwrap();
+        assert_eq!(contact.normal, IVec2::new(NORMAL_SCALE, 0));
+        assert_eq!(contact.depth, 2000);
+        assert_eq!(contact.point, IVec2::new(5000, 0));
+        assert_eq!(circle_circle(Vec2::new(10.0, 0.0), 5.0, Vec2::ZERO, 5.0), None);
+    }
+
+    #[test]
+    fn test_circle_circle_same_center() {
+        let contact = circle_circle(Vec2::ZERO, 2.0, Vec2::ZERO, 3.0).unwrap();
+        assert_eq!(contact.normal, IVec2::new(NORMAL_SCALE, 0));
+        assert_eq!(contact.depth, 5000);
+    }
+
+    #[test]
+    fn test_aabb_aabb_least_overlap() {
+        // Overlap of 1 on x and 8 on y, pushed to the left
+        let contact = aabb_aabb(Vec2::new(-9.0, 2.0), Vec2::splat(5.0), Vec2::ZERO, Vec2::splat(5.0)).unwrap();
+        assert_eq!(contact.normal, IVec2::new(-NORMAL_SCALE, 0));
+        assert_eq!(contact.depth, 1000);
+        assert_eq!(contact.point, IVec2::new(-5000, 1000));
+
+        let contact = aabb_aabb(Vec2::new(1.0, 9.5), Vec2::splat(5.0), Vec2::ZERO, Vec2::splat(5.0)).unwrap();
+        assert_eq!(contact.normal, IVec2::new(0, NORMAL_SCALE));
+        assert_eq!(contact.depth, 500);
+        assert_eq!(aabb_aabb(Vec2::new(11.0, 0.0), Vec2::splat(5.0), Vec2::ZERO, Vec2::splat(5.0)), None);
+    }
+
+    #[test]
+    fn test_circle_aabb_outside() {
+        let contact = circle_aabb(Vec2::new(0.0, 13.0), 5.0, Vec2::ZERO, Vec2::new(20.0, 10.0)).unwrap();
+        assert_eq!(contact.normal, IVec2::new(0, NORMAL_SCALE));
+        assert_eq!(contact.depth, 2000);
+        assert_eq!(contact.point, IVec2::new(0, 10000));
+        assert_eq!(circle_aabb(Vec2::new(0.0, 16.0), 5.0, Vec2::ZERO, Vec2::new(20.0, 10.0)), None);
+    }
+
+    #[test]
+    fn test_circle_aabb_corner() {
+        let contact = circle_aabb(Vec2::new(12.0, 12.0), 5.0, Vec2::ZERO, Vec2::splat(10.0)).unwrap();
+        // sqrt(2) / 2 in Q14, truncated
+        assert_eq!(contact.normal, IVec2::splat(11586));
+        assert_eq!(contact.point, IVec2::new(10000, 10000));
+        assert_eq!(contact.depth, 5000 - 2828);
+    }
+
+    #[test]
+    fn test_circle_aabb_center_inside() {
+        let contact = circle_aabb(Vec2::new(8.0, 0.0), 1.0, Vec2::ZERO, Vec2::splat(10.0)).unwrap();
+        assert_eq!(contact.normal, IVec2::new(NORMAL_SCALE, 0));
+        assert_eq!(contact.depth, 3000);
+        assert_eq!(contact.point, IVec2::new(10000, 0));
+    }
+
+    #[test]
+    fn test_aabb_circle_flip() {
+        let contact = aabb_circle(Vec2::ZERO, Vec2::new(20.0, 10.0), Vec2::new(0.0, 13.0), 5.0).unwrap();
+        assert_eq!(contact.normal, IVec2::new(0, -NORMAL_SCALE));
+        assert_eq!(contact.depth, 2000);
+        // On the surface of the circle
+        assert_eq!(contact.point, IVec2::new(0, 8000));
+    }
+
+    #[test]
+    fn test_far_contact_saturate() {
+        // 3 million units is past the i32 of 1/1000, the point stay on its side of the world
+        let contact = circle_circle(Vec2::new(3_000_000.0, -3_000_000.0), 1.0, Vec2::new(3_000_000.5, -3_000_000.0), 1.0).unwrap();
+        assert_eq!(contact.point, IVec2::new(i32::MAX, i32::MIN));
+        assert_eq!(contact.flip().point, IVec2::new(i32::MAX, i32::MIN));
+    }
+}
\ No newline at end of file
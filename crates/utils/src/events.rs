@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_ggrs::GgrsApp;
+
+// Bevy Events are not part of the rollback snapshot, an event sent during a frame
+// that get resimulated is sent twice and an event from a mispredicted frame is never
+// cancelled. RollbackEvents is a resource registered for rollback that keep the events
+// of the last frames, keyed by the frame they were sent on. When ggrs restore a snapshot
+// the events of the resimulated frames are dropped with it and sent again.
+
+/// Number of frames of events kept by default
+pub const DEFAULT_EVENTS_FRAMES: u32 = 120;
+
+#[derive(Resource, Clone, Debug)]
+pub struct RollbackEvents<T: Clone + Send + Sync + 'static> {
+    events: Vec<(u32, T)>,
+    frames_kept: u32,
+}
+
+impl<T: Clone + Send + Sync + 'static> Default for RollbackEvents<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENTS_FRAMES)
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> RollbackEvents<T> {
+    pub fn new(frames_kept: u32) -> Self {
+        Self { events: vec![], frames_kept: frames_kept.max(1) }
+    }
+
+    /// Send an event for the given frame, events older than the kept window are dropped.
+    pub fn send(&mut self, frame: u32, event: T) {
+        let oldest = frame.saturating_sub(self.frames_kept - 1);
+        self.events.retain(|(f, _)| *f >= oldest && *f <= frame);
+        self.events.push((frame, event));
+    }
+
+    /// Events sent during the given frame, in the order they were sent.
+    pub fn read(&self, frame: u32) -> impl Iterator<Item = &T> {
+        self.events.iter().filter(move |(f, _)| *f == frame).map(|(_, e)| e)
+    }
+
+    /// Events sent after the given frame, used by the presentation systems outside
+    /// of the rollback schedule to catch up. Those can see events from a frame that
+    /// end up being resimulated.
+    pub fn read_after(&self, frame: Option<u32>) -> impl Iterator<Item = (u32, &T)> {
+        self.events.iter()
+            .filter(move |(f, _)| frame.map_or(true, |frame| *f > frame))
+            .map(|(f, e)| (*f, e))
+    }
+
+    pub fn latest_frame(&self) -> Option<u32> {
+        self.events.last().map(|(f, _)| *f)
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+pub trait RollbackEventsAppExt {
+    fn add_rollback_events<T: Clone + Send + Sync + 'static>(&mut self) -> &mut Self;
+}
+
+impl RollbackEventsAppExt for App {
+    fn add_rollback_events<T: Clone + Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.init_resource::<RollbackEvents<T>>()
+            .rollback_resource_with_clone::<RollbackEvents<T>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_only_frame_events() {
+        let mut events = RollbackEvents::<u32>::default();
+        events.send(1, 10);
+        events.send(2, 20);
+        events.send(2, 21);
+
+        assert_eq!(events.read(1).copied().collect::<Vec<_>>(), vec![10]);
+        assert_eq!(events.read(2).copied().collect::<Vec<_>>(), vec![20, 21]);
+        assert_eq!(events.read(3).count(), 0);
+    }
+
+    #[test]
+    fn test_old_frames_are_dropped() {
+        let mut events = RollbackEvents::<u32>::new(2);
+        events.send(1, 10);
+        events.send(2, 20);
+        events.send(3, 30);
+
+        assert_eq!(events.read(1).count(), 0, "Frame 1 should be out of the window.");
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn test_restore_snapshot_resend() {
+        let mut events = RollbackEvents::<u32>::default();
+        events.send(1, 10);
+        let snapshot = events.clone();
+
+        // Predicted frame
+        events.send(2, 20);
+
+        // Rollback to frame 2 and resimulate with the confirmed input
+        events = snapshot;
+        events.send(2, 25);
+
+        assert_eq!(events.read(2).copied().collect::<Vec<_>>(), vec![25]);
+        assert_eq!(events.latest_frame(), Some(2));
+    }
+
+    #[test]
+    fn test_send_in_past_frame_drop_future_events() {
+        let mut events = RollbackEvents::<u32>::default();
+        events.send(5, 50);
+        events.send(3, 30);
+
+        assert_eq!(events.read(5).count(), 0, "Events after the sent frame are from a discarded timeline.");
+        assert_eq!(events.read_after(None).map(|(f, e)| (f, *e)).collect::<Vec<_>>(), vec![(3, 30)]);
+    }
+}
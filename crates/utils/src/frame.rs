@@ -0,0 +1,180 @@
+use bevy::{prelude::Resource, reflect::Reflect};
+use serde::{Deserialize, Serialize};
+
+// Timer counted in rollback frames instead of seconds. Only the start frame and the
+// duration are stored so a timer restored by a rollback is always correct, nothing
+// need to be decremented every frame.
+
+/// Default frames per second of the rollback simulation, the durations in frames of
+/// the configs are written for this rate
+pub const FRAME_RATE: u32 = 60;
+
+/// Tick configuration of the rollback simulation, every peer of a session must use
+/// the same tick rate
+#[derive(Resource, Reflect, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Frames simulated per second of game time
+    pub tick_rate: u32,
+    /// Speed of the game time compared to the real time, below 1 for slow motion.
+    /// Only change how often the frames run, never what a frame compute
+    pub time_scale: f32,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self { tick_rate: FRAME_RATE, time_scale: 1.0 }
+    }
+}
+
+impl SimulationConfig {
+    /// Seconds of game time simulated by one frame
+    pub fn timestep(&self) -> f32 {
+        1.0 / self.tick_rate.max(1) as f32
+    }
+
+    /// Convert a duration written in frames at FRAME_RATE to the tick rate, rounded
+    /// to the closest frame, a positive duration always last at least one frame
+    pub fn frames(&self, reference_frames: u32) -> u32 {
+        if reference_frames == 0 {
+            return 0;
+        }
+        let frames = (reference_frames as u64 * self.tick_rate as u64 + FRAME_RATE as u64 / 2) / FRAME_RATE as u64;
+        (frames as u32).max(1)
+    }
+
+    /// Round up to the next frame, a positive duration always last at least one frame
+    pub fn frames_from_seconds(&self, seconds: f32) -> u32 {
+        if seconds <= 0.0 {
+            0
+        } else {
+            ((seconds * self.tick_rate as f32).ceil() as u32).max(1)
+        }
+    }
+
+    pub fn seconds(&self, frames: u32) -> f32 {
+        frames as f32 * self.timestep()
+    }
+
+    /// Frames run per second of real time, given to GGRS
+    pub fn rollback_fps(&self) -> usize {
+        ((self.tick_rate as f32 * self.time_scale).round() as usize).max(1)
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameTimer {
+    pub start_frame: u32,
+    pub duration: u32,
+}
+
+impl FrameTimer {
+    pub fn new(start_frame: u32, duration: u32) -> Self {
+        Self { start_frame, duration }
+    }
+
+    pub fn from_seconds(start_frame: u32, seconds: f32, simulation: &SimulationConfig) -> Self {
+        Self::new(start_frame, simulation.frames_from_seconds(seconds))
+    }
+
+    /// First frame where the timer is done
+    pub fn end_frame(&self) -> u32 {
+        self.start_frame.saturating_add(self.duration)
+    }
+
+    pub fn is_done(&self, frame: u32) -> bool {
+        frame >= self.end_frame()
+    }
+
+    pub fn is_active(&self, frame: u32) -> bool {
+        !self.is_done(frame)
+    }
+
+    pub fn elapsed(&self, frame: u32) -> u32 {
+        frame.saturating_sub(self.start_frame).min(self.duration)
+    }
+
+    pub fn remaining(&self, frame: u32) -> u32 {
+        self.end_frame().saturating_sub(frame).min(self.duration)
+    }
+
+    pub fn remaining_seconds(&self, frame: u32, simulation: &SimulationConfig) -> f32 {
+        simulation.seconds(self.remaining(frame))
+    }
+
+    /// Progress from 0 at the start frame to 1 at the end frame
+    pub fn fraction(&self, frame: u32) -> f32 {
+        if self.duration == 0 {
+            return 1.0;
+        }
+        self.elapsed(frame) as f32 / self.duration as f32
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_done_on_end_frame() {
+        let timer = FrameTimer::new(10, 5);
+        assert!(!timer.is_done(10));
+        assert!(!timer.is_done(14));
+        assert!(timer.is_done(15));
+        assert_eq!(timer.end_frame(), 15);
+    }
+
+    #[test]
+    fn test_remaining_and_elapsed() {
+        let timer = FrameTimer::new(10, 5);
+        assert_eq!(timer.remaining(10), 5);
+        assert_eq!(timer.remaining(14), 1);
+        assert_eq!(timer.remaining(20), 0);
+        assert_eq!(timer.elapsed(12), 2);
+        // Before the start, after a rollback to an older frame
+        assert_eq!(timer.remaining(5), 5);
+        assert_eq!(timer.elapsed(5), 0);
+    }
+
+    #[test]
+    fn test_from_seconds() {
+        let simulation = SimulationConfig::default();
+        assert_eq!(FrameTimer::from_seconds(0, 1.0, &simulation).duration, 60);
+        assert_eq!(FrameTimer::from_seconds(0, 0.001, &simulation).duration, 1);
+        assert_eq!(FrameTimer::from_seconds(0, 0.0, &simulation).duration, 0);
+        assert!(FrameTimer::from_seconds(7, 0.0, &simulation).is_done(7));
+
+        let half_rate = SimulationConfig { tick_rate: 30, ..Default::default() };
+        assert_eq!(FrameTimer::from_seconds(0, 1.0, &half_rate).duration, 30);
+    }
+
+    #[test]
+    fn test_reference_frames_scaled_to_tick_rate() {
+        let simulation = SimulationConfig::default();
+        assert_eq!(simulation.frames(45), 45);
+
+        let half_rate = SimulationConfig { tick_rate: 30, ..Default::default() };
+        assert_eq!(half_rate.frames(60), 30);
+        assert_eq!(half_rate.frames(15), 8);
+        assert_eq!(half_rate.frames(1), 1, "A positive duration should never become instant.");
+        assert_eq!(half_rate.frames(0), 0);
+        assert_eq!(half_rate.timestep(), 1.0 / 30.0);
+    }
+
+    #[test]
+    fn test_time_scale_only_change_fps() {
+        let slow_motion = SimulationConfig { time_scale: 0.25, ..Default::default() };
+        assert_eq!(slow_motion.rollback_fps(), 15);
+        assert_eq!(slow_motion.timestep(), SimulationConfig::default().timestep());
+        assert_eq!(SimulationConfig { time_scale: 0.0, ..Default::default() }.rollback_fps(), 1);
+    }
+
+    #[test]
+    fn test_fraction() {
+        let timer = FrameTimer::new(0, 4);
+        assert_eq!(timer.fraction(0), 0.0);
+        assert_eq!(timer.fraction(2), 0.5);
+        assert_eq!(timer.fraction(4), 1.0);
+        assert_eq!(FrameTimer::new(0, 0).fraction(0), 1.0);
+    }
+}
@@ -4,3 +4,14 @@ pub mod web;
 pub mod rng;
 pub mod math;
 pub mod test;
+pub mod events;
+pub mod aim;
+pub mod frame;
+pub mod sweep;
+pub mod contact;
+pub mod world;
+pub mod snapshot;
+pub mod cache;
+pub mod schema;
+pub mod persistence;
+pub mod link;
@@ -0,0 +1,142 @@
+use bevy::{
+    ecs::{entity::{Entities, EntityMapper, MapEntities}, query::{QueryData, QueryFilter, ROQueryItem}, schedule::ScheduleLabel, system::EntityCommands},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+// Entities kept in the components of other entities. A weapon of an inventory, the enemy
+// holding a player, can be despawned in the middle of a frame or by a rollback and the
+// component still point to it. The Entity carry its generation so a reused index never
+// match, the link only force the lookups through what handle a dead entity instead of an
+// unwrap or a `commands.entity` that panic, and `prune_dead_links` remove what is left.
+
+/// Link to another entity held in a component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub struct EntityLink(Entity);
+
+impl EntityLink {
+    pub fn new(entity: Entity) -> Self {
+        Self(entity)
+    }
+
+    /// The entity, alive or not, for the events and the logs
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+
+    pub fn is_alive(&self, entities: &Entities) -> bool {
+        entities.contains(self.0)
+    }
+
+    pub fn get<'w, D: QueryData, F: QueryFilter>(&self, query: &'w Query<'_, '_, D, F>) -> Option<ROQueryItem<'w, D>> {
+        query.get(self.0).ok()
+    }
+
+    pub fn get_mut<'w, D: QueryData, F: QueryFilter>(&self, query: &'w mut Query<'_, '_, D, F>) -> Option<D::Item<'w>> {
+        query.get_mut(self.0).ok()
+    }
+
+    /// Commands of the entity, none when it was despawned
+    pub fn commands<'a>(&self, commands: &'a mut Commands) -> Option<EntityCommands<'a>> {
+        commands.get_entity(self.0)
+    }
+}
+
+impl From<Entity> for EntityLink {
+    fn from(entity: Entity) -> Self {
+        Self(entity)
+    }
+}
+
+impl MapEntities for EntityLink {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.0 = entity_mapper.map_entity(self.0);
+    }
+}
+
+/// Component holding links, cleaned by `prune_dead_links`.
+pub trait EntityLinks: Component {
+    /// Is any link of the component to a despawned entity
+    fn has_dead_links(&self, is_alive: impl Fn(&EntityLink) -> bool) -> bool;
+    /// Drop the dead links, false when the component mean nothing without them and must be removed
+    fn prune(&mut self, is_alive: impl Fn(&EntityLink) -> bool) -> bool;
+}
+
+/// Remove the dead links of every `C`. Only touched when there is one so the change
+/// detection and the rollback checksum don't see every frame.
+pub fn prune_dead_links<C: EntityLinks>(
+    mut commands: Commands,
+    entities: &Entities,
+    mut query: Query<(Entity, &mut C)>,
+) {
+    let is_alive = |link: &EntityLink| link.is_alive(entities);
+    for (entity, mut component) in query.iter_mut() {
+        if !component.has_dead_links(is_alive) {
+            continue;
+        }
+        if !component.prune(is_alive) {
+            commands.entity(entity).remove::<C>();
+        }
+    }
+}
+
+pub trait EntityLinksAppExt {
+    fn prune_entity_links<C: EntityLinks>(&mut self, schedule: impl ScheduleLabel) -> &mut Self;
+}
+
+impl EntityLinksAppExt for App {
+    /// Unordered, a rollback schedule must order `prune_dead_links` itself.
+    fn prune_entity_links<C: EntityLinks>(&mut self, schedule: impl ScheduleLabel) -> &mut Self {
+        self.add_systems(schedule, prune_dead_links::<C>)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component)]
+    struct Held(Vec<EntityLink>);
+
+    impl EntityLinks for Held {
+        fn has_dead_links(&self, is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+            !self.0.iter().all(is_alive)
+        }
+
+        fn prune(&mut self, is_alive: impl Fn(&EntityLink) -> bool) -> bool {
+            self.0.retain(is_alive);
+            !self.0.is_empty()
+        }
+    }
+
+    #[test]
+    fn test_dead_links_are_pruned() {
+        let mut app = App::new();
+        app.prune_entity_links::<Held>(Update);
+        let world = app.world_mut();
+        let kept = world.spawn_empty().id();
+        let dead = world.spawn_empty().id();
+        let holder = world.spawn(Held(vec![kept.into(), dead.into()])).id();
+        let only_dead = world.spawn(Held(vec![dead.into()])).id();
+        world.despawn(dead);
+
+        app.update();
+
+        let world = app.world();
+        assert_eq!(world.entity(holder).get::<Held>().map(|held| held.0.clone()), Some(vec![EntityLink::new(kept)]));
+        assert!(world.entity(only_dead).get::<Held>().is_none());
+    }
+
+    #[test]
+    fn test_reused_index_is_not_alive() {
+        let mut world = World::new();
+        let entity = world.spawn_empty().id();
+        let link = EntityLink::new(entity);
+        world.despawn(entity);
+        let reused = world.spawn_empty().id();
+
+        assert_eq!(reused.index(), entity.index());
+        assert!(!link.is_alive(world.entities()));
+    }
+}
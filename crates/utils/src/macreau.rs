@@ -24,3 +24,58 @@ macro_rules! bmap(
      };
 );
 
+
+// Enum of kinds named in the fields of the map entities, each variant with its name.
+// Give the list of every kind in `ALL` and the conversion to and from the name.
+#[macro_export]
+macro_rules! named_kind(
+    {
+        $(#[$meta:meta])*
+        $vis:vis enum $kind:ident {
+            $( $(#[$variant_meta:meta])* $variant:ident => $name:literal ),+ $(,)?
+        }
+    } => {
+        $(#[$meta])*
+        $vis enum $kind {
+            $( $(#[$variant_meta])* $variant ),+
+        }
+
+        impl $kind {
+            pub const ALL: [$kind; [$($name),+].len()] = [$($kind::$variant),+];
+
+            // Name used in the field of the map entity
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( $kind::$variant => $name ),+
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                Self::ALL.into_iter().find(|kind| kind.name() == name)
+            }
+        }
+    };
+);
+
+
+#[cfg(test)]
+mod tests {
+    named_kind! {
+        #[derive(Debug, Clone, Copy, PartialEq, Default)]
+        enum TestKind {
+            #[default]
+            First => "first",
+            Second => "second",
+        }
+    }
+
+    #[test]
+    fn test_named_kind_round_trip() {
+        assert_eq!(TestKind::ALL, [TestKind::First, TestKind::Second]);
+        for kind in TestKind::ALL {
+            assert_eq!(TestKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(TestKind::from_name("third"), None);
+        assert_eq!(TestKind::default(), TestKind::First);
+    }
+}
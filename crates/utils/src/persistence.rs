@@ -0,0 +1,169 @@
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+// Local saves of the player, stored as RON under a key. On native each key is a file
+// in the saves directory, on the web the keys are in the local storage of the browser.
+// Nothing here is shared with the other peers, it must never be read by a rollback system.
+
+/// Directory of the saves on native, relative to the working directory
+pub const SAVE_DIR: &str = "saves";
+
+#[derive(Debug, Error)]
+pub enum PersistenceError {
+    #[error("invalid save key `{0}`, only letters, digits, `-` and `_` are allowed")]
+    InvalidKey(String),
+    #[error("could not access the save {key}: {message}")]
+    Storage { key: String, message: String },
+    #[error("the save {key} is corrupted: {source}")]
+    Parse {
+        key: String,
+        #[source]
+        source: ron::error::SpannedError,
+    },
+    #[error("could not serialize the save {key}: {source}")]
+    Serialize {
+        key: String,
+        #[source]
+        source: ron::Error,
+    },
+}
+
+fn check_key(key: &str) -> Result<(), PersistenceError> {
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(PersistenceError::InvalidKey(key.to_string()));
+    }
+    Ok(())
+}
+
+fn encode<T: Serialize>(key: &str, value: &T) -> Result<String, PersistenceError> {
+    ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|source| PersistenceError::Serialize { key: key.to_string(), source })
+}
+
+fn decode<T: DeserializeOwned>(key: &str, content: &str) -> Result<T, PersistenceError> {
+    ron::de::from_str(content).map_err(|source| PersistenceError::Parse { key: key.to_string(), source })
+}
+
+/// Write `value` under `key`, replacing the previous save
+pub fn save<T: Serialize>(key: &str, value: &T) -> Result<(), PersistenceError> {
+    check_key(key)?;
+    let content = encode(key, value)?;
+    storage::write(key, &content)
+}
+
+/// Read the save of `key`, `None` when nothing was saved yet
+pub fn load<T: DeserializeOwned>(key: &str) -> Result<Option<T>, PersistenceError> {
+    check_key(key)?;
+    storage::read(key)?.map(|content| decode(key, &content)).transpose()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use std::path::Path;
+
+    use super::{PersistenceError, SAVE_DIR};
+
+    fn error(key: &str, err: std::io::Error) -> PersistenceError {
+        PersistenceError::Storage { key: key.to_string(), message: err.to_string() }
+    }
+
+    pub fn write(key: &str, content: &str) -> Result<(), PersistenceError> {
+        write_in(Path::new(SAVE_DIR), key, content)
+    }
+
+    pub fn read(key: &str) -> Result<Option<String>, PersistenceError> {
+        read_in(Path::new(SAVE_DIR), key)
+    }
+
+    pub(super) fn write_in(dir: &Path, key: &str, content: &str) -> Result<(), PersistenceError> {
+        std::fs::create_dir_all(dir).map_err(|err| error(key, err))?;
+        // Write next to the save then rename, a crash never leave a half written save
+        let path = dir.join(format!("{}.ron", key));
+        let tmp = dir.join(format!("{}.ron.tmp", key));
+        std::fs::write(&tmp, content)
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(|err| error(key, err))
+    }
+
+    pub(super) fn read_in(dir: &Path, key: &str) -> Result<Option<String>, PersistenceError> {
+        match std::fs::read_to_string(dir.join(format!("{}.ron", key))) {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(error(key, err)),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    use super::PersistenceError;
+
+    const KEY_PREFIX: &str = "zrl.";
+
+    fn local_storage(key: &str) -> Result<web_sys::Storage, PersistenceError> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| PersistenceError::Storage { key: key.to_string(), message: "local storage is not available".into() })
+    }
+
+    pub fn write(key: &str, content: &str) -> Result<(), PersistenceError> {
+        local_storage(key)?
+            .set_item(&format!("{}{}", KEY_PREFIX, key), content)
+            .map_err(|err| PersistenceError::Storage { key: key.to_string(), message: format!("{:?}", err) })
+    }
+
+    pub fn read(key: &str) -> Result<Option<String>, PersistenceError> {
+        local_storage(key)?
+            .get_item(&format!("{}{}", KEY_PREFIX, key))
+            .map_err(|err| PersistenceError::Storage { key: key.to_string(), message: format!("{:?}", err) })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Save {
+        xp: u32,
+        unlocks: Vec<String>,
+    }
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("zrl_persistence_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = test_dir("round_trip");
+        let save = Save { xp: 120, unlocks: vec!["skin_2".into()] };
+
+        storage::write_in(&dir, "progress", &encode("progress", &save).unwrap()).unwrap();
+        let content = storage::read_in(&dir, "progress").unwrap().unwrap();
+        assert_eq!(decode::<Save>("progress", &content).unwrap(), save);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_missing_save_is_none() {
+        let dir = test_dir("missing");
+        assert!(storage::read_in(&dir, "progress").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_corrupted_save() {
+        assert!(matches!(decode::<Save>("progress", "(xp: \"a lot\")"), Err(PersistenceError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_reject_path_in_key() {
+        assert!(matches!(check_key("../progress"), Err(PersistenceError::InvalidKey(_))));
+        assert!(matches!(check_key(""), Err(PersistenceError::InvalidKey(_))));
+        assert!(check_key("progress_v1").is_ok());
+    }
+}
@@ -1,5 +1,7 @@
-use bevy::ecs::system::Resource;
+use std::marker::PhantomData;
 
+use bevy::{app::App, ecs::system::Resource, prelude::{Deref, DerefMut}};
+use bevy_ggrs::GgrsApp;
 
 
 #[derive(Debug, Resource, Clone, Copy, PartialEq, Eq, Hash)]
@@ -41,6 +43,63 @@ impl RollbackRng {
     pub fn next_f32_symmetric(&mut self) -> f32 {
         (self.next_f32() * 2.0) - 1.0
     }
+
+    /// Creates the RNG of a named stream, seeded from the shared seed and the name.
+    /// Every peer derive the same seed for a stream and the streams don't overlap.
+    pub fn derive(initial_seed: u32, name: &str) -> Self {
+        // FNV-1a of the name
+        let hash = name.bytes().fold(2166136261u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(16777619));
+        // Finalizer of murmur3 so close seeds give far apart streams
+        let mut seed = initial_seed ^ hash;
+        seed ^= seed >> 16;
+        seed = seed.wrapping_mul(0x85ebca6b);
+        seed ^= seed >> 13;
+        seed = seed.wrapping_mul(0xc2b2ae35);
+        seed ^= seed >> 16;
+        RollbackRng { seed }
+    }
+}
+
+// One RollbackRng for every consumer means a new call anywhere shift the randomness of
+// everything drawn after it. A subsystem draw from its own stream instead, a resource
+// registered for rollback on its own and derived from the shared seed with its name.
+
+/// Marker of a named RNG stream.
+pub trait RngStream: Send + Sync + 'static {
+    const NAME: &'static str;
+}
+
+#[derive(Resource, Deref, DerefMut)]
+pub struct RollbackRngStream<S: RngStream> {
+    #[deref]
+    rng: RollbackRng,
+    _stream: PhantomData<S>,
+}
+
+// Derived by hand, the derive would require the marker to be Clone and Copy
+impl<S: RngStream> Clone for RollbackRngStream<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: RngStream> Copy for RollbackRngStream<S> {}
+
+impl<S: RngStream> RollbackRngStream<S> {
+    pub fn new(initial_seed: u32) -> Self {
+        Self { rng: RollbackRng::derive(initial_seed, S::NAME), _stream: PhantomData }
+    }
+}
+
+pub trait RngStreamAppExt {
+    fn add_rng_stream<S: RngStream>(&mut self) -> &mut Self;
+}
+
+impl RngStreamAppExt for App {
+    /// The stream is inserted with the seed of the session when it start.
+    fn add_rng_stream<S: RngStream>(&mut self) -> &mut Self {
+        self.rollback_resource_with_copy::<RollbackRngStream<S>>()
+    }
 }
 
 #[cfg(test)]
@@ -139,4 +198,31 @@ mod tests {
         rng.next_f32_symmetric();
         assert_ne!(rng.seed, seed_after_f32, "Seed should change after calling next_f32_symmetric.");
     }
+
+    struct StreamA;
+    impl RngStream for StreamA {
+        const NAME: &'static str = "a";
+    }
+
+    struct StreamB;
+    impl RngStream for StreamB {
+        const NAME: &'static str = "b";
+    }
+
+    #[test]
+    fn test_rng_streams_are_independent() {
+        let mut a1 = RollbackRngStream::<StreamA>::new(1234);
+        let mut a2 = RollbackRngStream::<StreamA>::new(1234);
+        let mut b = RollbackRngStream::<StreamB>::new(1234);
+
+        assert_ne!(a1.seed, b.seed, "Streams with different names should have different seeds.");
+
+        // Drawing from another stream doesn't change the sequence of this one
+        for _ in 0..10 {
+            b.next_u32();
+        }
+        let seq1: Vec<u32> = (0..10).map(|_| a1.next_u32()).collect();
+        let seq2: Vec<u32> = (0..10).map(|_| a2.next_u32()).collect();
+        assert_eq!(seq1, seq2, "Streams with the same name and seed should produce the same sequence.");
+    }
 }
@@ -0,0 +1,177 @@
+use std::marker::PhantomData;
+
+use bevy::{app::{App, Plugin}, asset::{io::Reader, Asset, AssetApp, AssetLoader, LoadContext}};
+use serde::Deserialize;
+use thiserror::Error;
+
+// Versioning of the RON assets. Every file carry a `schema_version`, a file without
+// one was written before the versioning and is read as version 1. Older versions are
+// migrated when loaded and a version newer than the build is a hard error, an old
+// build must never silently misread a file written for a newer one.
+
+/// Version of the files written before the `schema_version` field existed
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error("could not read {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{path} is not a valid {asset} for schema version {version}: {source}")]
+    Parse {
+        path: String,
+        asset: &'static str,
+        version: u32,
+        #[source]
+        source: ron::error::SpannedError,
+    },
+    #[error("{path} use {asset} schema version {found} but this build only support up to version {supported}, update the game or convert the file back to version {supported}")]
+    FutureVersion {
+        path: String,
+        asset: &'static str,
+        found: u32,
+        supported: u32,
+    },
+}
+
+/// Asset with a versioned file format
+pub trait Versioned: Sized {
+    /// Name used in the error messages
+    const ASSET_NAME: &'static str;
+    /// Version written by this build
+    const SCHEMA_VERSION: u32;
+
+    /// Parse a file written with `version` and migrate it to the current schema,
+    /// `version` is never above `SCHEMA_VERSION`
+    fn parse_version(version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError>;
+}
+
+#[derive(Deserialize)]
+struct SchemaHeader {
+    #[serde(default)]
+    schema_version: u32,
+}
+
+/// Version of a RON file, legacy when there is no `schema_version` at the top level
+pub fn read_schema_version(bytes: &[u8]) -> u32 {
+    ron::de::from_bytes::<SchemaHeader>(bytes)
+        .map_or(LEGACY_SCHEMA_VERSION, |header| header.schema_version.max(LEGACY_SCHEMA_VERSION))
+}
+
+pub fn from_ron<T: Versioned>(bytes: &[u8], path: &str) -> Result<T, SchemaError> {
+    let version = read_schema_version(bytes);
+    if version > T::SCHEMA_VERSION {
+        return Err(SchemaError::FutureVersion {
+            path: path.to_string(),
+            asset: T::ASSET_NAME,
+            found: version,
+            supported: T::SCHEMA_VERSION,
+        });
+    }
+
+    T::parse_version(version, bytes).map_err(|source| SchemaError::Parse {
+        path: path.to_string(),
+        asset: T::ASSET_NAME,
+        version,
+        source,
+    })
+}
+
+pub struct VersionedRonLoader<T>(PhantomData<fn() -> T>);
+
+impl<T: Versioned + Asset> AssetLoader for VersionedRonLoader<T> {
+    type Asset = T;
+    type Settings = ();
+    type Error = SchemaError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<T, SchemaError> {
+        let path = load_context.path().display().to_string();
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.map_err(|source| SchemaError::Io { path: path.clone(), source })?;
+        from_ron(&bytes, &path)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// Replacement of the `RonAssetPlugin` for the versioned assets
+pub struct VersionedRonAssetPlugin<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for VersionedRonAssetPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Versioned + Asset> Plugin for VersionedRonAssetPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<T>()
+            .register_asset_loader(VersionedRonLoader::<T>(PhantomData));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        schema_version: u32,
+        speed: f32,
+    }
+
+    // Version 1 called the speed `velocity`
+    #[derive(Deserialize)]
+    struct ConfigV1 {
+        velocity: f32,
+    }
+
+    impl Versioned for Config {
+        const ASSET_NAME: &'static str = "test config";
+        const SCHEMA_VERSION: u32 = 2;
+
+        fn parse_version(version: u32, bytes: &[u8]) -> Result<Self, ron::error::SpannedError> {
+            match version {
+                1 => ron::de::from_bytes::<ConfigV1>(bytes).map(|v1| Config { schema_version: 2, speed: v1.velocity }),
+                _ => ron::de::from_bytes(bytes),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_version() {
+        assert_eq!(read_schema_version(b"(schema_version: 3, speed: 1.0)"), 3);
+        assert_eq!(read_schema_version(b"(velocity: 1.0)"), LEGACY_SCHEMA_VERSION);
+        assert_eq!(read_schema_version(b"({ \"a\": 1 })"), LEGACY_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_migrate_legacy() {
+        let config: Config = from_ron(b"(velocity: 4.0)", "test.ron").unwrap();
+        assert_eq!(config, Config { schema_version: 2, speed: 4.0 });
+    }
+
+    #[test]
+    fn test_current_version() {
+        let config: Config = from_ron(b"(schema_version: 2, speed: 5.0)", "test.ron").unwrap();
+        assert_eq!(config.speed, 5.0);
+    }
+
+    #[test]
+    fn test_future_version_is_an_error() {
+        let error = from_ron::<Config>(b"(schema_version: 3, speed: 5.0)", "test.ron").unwrap_err();
+        assert!(matches!(error, SchemaError::FutureVersion { found: 3, supported: 2, .. }));
+        assert!(error.to_string().contains("test.ron"));
+    }
+}
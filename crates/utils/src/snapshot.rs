@@ -0,0 +1,264 @@
+use std::any::Any;
+
+use bevy::{ecs::entity::{EntityHashMap, EntityMapper, MapEntities}, prelude::*};
+use bevy_ggrs::{AddRollbackCommandExtension, Rollback};
+
+// Copy of the whole rollback state of a frame kept outside of GGRS, for the features
+// that go back further than the prediction window like the practice rewind. The
+// components and resources to copy are registered on the app, the rollback entities
+// despawned since the snapshot are spawned again and the entities they reference
+// are mapped to the new ones.
+
+type SnapshotData = Box<dyn Any + Send + Sync>;
+
+#[derive(Clone, Copy)]
+struct ComponentSnapshotFns {
+    save: fn(&EntityRef) -> Option<SnapshotData>,
+    load: fn(&mut EntityWorldMut, Option<&SnapshotData>, &EntityHashMap<Entity>),
+}
+
+#[derive(Clone, Copy)]
+struct ResourceSnapshotFns {
+    save: fn(&World) -> Option<SnapshotData>,
+    load: fn(&mut World, Option<&SnapshotData>),
+}
+
+#[derive(Resource, Clone, Default)]
+pub struct SnapshotRegistry {
+    components: Vec<ComponentSnapshotFns>,
+    resources: Vec<ResourceSnapshotFns>,
+}
+
+struct SnapshotEntityMapper<'a>(&'a EntityHashMap<Entity>);
+
+impl EntityMapper for SnapshotEntityMapper<'_> {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        self.0.get(&entity).copied().unwrap_or(entity)
+    }
+}
+
+fn save_component<C: Component + Clone>(entity: &EntityRef) -> Option<SnapshotData> {
+    entity.get::<C>().map(|component| Box::new(component.clone()) as SnapshotData)
+}
+
+fn load_component<C: Component + Clone>(entity: &mut EntityWorldMut, data: Option<&SnapshotData>, _map: &EntityHashMap<Entity>) {
+    match data.and_then(|data| data.downcast_ref::<C>()) {
+        Some(component) => { entity.insert(component.clone()); },
+        None => { entity.remove::<C>(); },
+    }
+}
+
+fn load_mapped_component<C: Component + Clone + MapEntities>(entity: &mut EntityWorldMut, data: Option<&SnapshotData>, map: &EntityHashMap<Entity>) {
+    match data.and_then(|data| data.downcast_ref::<C>()) {
+        Some(component) => {
+            let mut component = component.clone();
+            component.map_entities(&mut SnapshotEntityMapper(map));
+            entity.insert(component);
+        },
+        None => { entity.remove::<C>(); },
+    }
+}
+
+fn save_resource<R: Resource + Clone>(world: &World) -> Option<SnapshotData> {
+    world.get_resource::<R>().map(|resource| Box::new(resource.clone()) as SnapshotData)
+}
+
+fn load_resource<R: Resource + Clone>(world: &mut World, data: Option<&SnapshotData>) {
+    match data.and_then(|data| data.downcast_ref::<R>()) {
+        Some(resource) => world.insert_resource(resource.clone()),
+        None => { world.remove_resource::<R>(); },
+    }
+}
+
+struct EntitySnapshot {
+    entity: Entity,
+    parent: Option<Entity>,
+    // Same order as the registry, none when the entity didn't have the component
+    components: Vec<Option<SnapshotData>>,
+}
+
+pub struct GameStateSnapshot {
+    pub frame: u32,
+    entities: Vec<EntitySnapshot>,
+    resources: Vec<Option<SnapshotData>>,
+}
+
+impl GameStateSnapshot {
+    pub fn save(world: &mut World, frame: u32) -> Self {
+        let registry = world.get_resource::<SnapshotRegistry>().cloned().unwrap_or_default();
+
+        let mut rollback_entities: Vec<Entity> = world.query_filtered::<Entity, With<Rollback>>().iter(world).collect();
+        rollback_entities.sort_by_key(|entity| entity.index());
+
+        let entities = rollback_entities.into_iter().map(|entity| {
+            let entity_ref = world.entity(entity);
+            EntitySnapshot {
+                entity,
+                parent: entity_ref.get::<Parent>().map(|parent| parent.get()),
+                components: registry.components.iter().map(|fns| (fns.save)(&entity_ref)).collect(),
+            }
+        }).collect();
+        let resources = registry.resources.iter().map(|fns| (fns.save)(world)).collect();
+
+        Self { frame, entities, resources }
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    // Put the world back in the state of the snapshot
+    pub fn restore(&self, world: &mut World) {
+        let registry = world.get_resource::<SnapshotRegistry>().cloned().unwrap_or_default();
+
+        // Spawned after the snapshot
+        let mut current: Vec<Entity> = world.query_filtered::<Entity, With<Rollback>>().iter(world).collect();
+        current.sort_by_key(|entity| entity.index());
+        for entity in current {
+            if !self.entities.iter().any(|snapshot| snapshot.entity == entity) && world.entities().contains(entity) {
+                world.entity_mut(entity).despawn_recursive();
+            }
+        }
+
+        // Despawned after the snapshot, spawned again under a new id
+        let mut map = EntityHashMap::default();
+        for snapshot in self.entities.iter() {
+            let entity = if world.entities().contains(snapshot.entity) {
+                snapshot.entity
+            } else {
+                let entity = world.spawn_empty().id();
+                world.commands().entity(entity).add_rollback();
+                entity
+            };
+            map.insert(snapshot.entity, entity);
+        }
+        world.flush();
+
+        for snapshot in self.entities.iter() {
+            let entity = map[&snapshot.entity];
+            let mut entity_mut = world.entity_mut(entity);
+            for (fns, data) in registry.components.iter().zip(snapshot.components.iter()) {
+                (fns.load)(&mut entity_mut, data.as_ref(), &map);
+            }
+
+            let parent = snapshot.parent.map(|parent| map.get(&parent).copied().unwrap_or(parent));
+            let current_parent = entity_mut.get::<Parent>().map(|parent| parent.get());
+            match parent {
+                Some(parent) if current_parent != Some(parent) => { entity_mut.set_parent(parent); },
+                None if current_parent.is_some() => { entity_mut.remove_parent(); },
+                _ => {},
+            }
+        }
+
+        for (fns, data) in registry.resources.iter().zip(self.resources.iter()) {
+            (fns.load)(world, data.as_ref());
+        }
+    }
+}
+
+pub trait SnapshotAppExt {
+    fn snapshot_component<C: Component + Clone>(&mut self) -> &mut Self;
+    // For the components holding entities, mapped when an entity is spawned again
+    fn snapshot_component_mapped<C: Component + Clone + MapEntities>(&mut self) -> &mut Self;
+    fn snapshot_resource<R: Resource + Clone>(&mut self) -> &mut Self;
+}
+
+impl SnapshotAppExt for App {
+    fn snapshot_component<C: Component + Clone>(&mut self) -> &mut Self {
+        self.init_resource::<SnapshotRegistry>();
+        self.world_mut().resource_mut::<SnapshotRegistry>().components.push(ComponentSnapshotFns {
+            save: save_component::<C>,
+            load: load_component::<C>,
+        });
+        self
+    }
+
+    fn snapshot_component_mapped<C: Component + Clone + MapEntities>(&mut self) -> &mut Self {
+        self.init_resource::<SnapshotRegistry>();
+        self.world_mut().resource_mut::<SnapshotRegistry>().components.push(ComponentSnapshotFns {
+            save: save_component::<C>,
+            load: load_mapped_component::<C>,
+        });
+        self
+    }
+
+    fn snapshot_resource<R: Resource + Clone>(&mut self) -> &mut Self {
+        self.init_resource::<SnapshotRegistry>();
+        self.world_mut().resource_mut::<SnapshotRegistry>().resources.push(ResourceSnapshotFns {
+            save: save_resource::<R>,
+            load: load_resource::<R>,
+        });
+        self
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Value(u32);
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Target(Entity);
+
+    impl MapEntities for Target {
+        fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+            self.0 = entity_mapper.map_entity(self.0);
+        }
+    }
+
+    #[derive(Resource, Clone, Debug, PartialEq)]
+    struct Counter(u32);
+
+    fn app() -> App {
+        let mut app = App::new();
+        // Normally added by the GgrsPlugin, needed by add_rollback
+        app.init_resource::<bevy_ggrs::RollbackOrdered>();
+        app.snapshot_component::<Value>()
+            .snapshot_component_mapped::<Target>()
+            .snapshot_resource::<Counter>();
+        app.insert_resource(Counter(1));
+        app
+    }
+
+    fn spawn_rollback(world: &mut World, bundle: impl Bundle) -> Entity {
+        let entity = world.spawn(bundle).id();
+        world.commands().entity(entity).add_rollback();
+        world.flush();
+        entity
+    }
+
+    #[test]
+    fn test_restore_values_and_resources() {
+        let mut app = app();
+        let world = app.world_mut();
+        let entity = spawn_rollback(world, Value(1));
+
+        let snapshot = GameStateSnapshot::save(world, 10);
+        world.entity_mut(entity).insert(Value(2));
+        world.insert_resource(Counter(5));
+        snapshot.restore(world);
+
+        assert_eq!(world.entity(entity).get::<Value>(), Some(&Value(1)));
+        assert_eq!(world.resource::<Counter>(), &Counter(1));
+    }
+
+    #[test]
+    fn test_restore_spawned_and_despawned_entities() {
+        let mut app = app();
+        let world = app.world_mut();
+        let target = spawn_rollback(world, Value(7));
+        let holder = spawn_rollback(world, Target(target));
+
+        let snapshot = GameStateSnapshot::save(world, 10);
+        world.despawn(target);
+        let spawned = spawn_rollback(world, Value(3));
+        snapshot.restore(world);
+
+        assert!(!world.entities().contains(spawned));
+        let mapped = world.entity(holder).get::<Target>().unwrap().0;
+        assert_eq!(world.entity(mapped).get::<Value>(), Some(&Value(7)));
+    }
+}
@@ -0,0 +1,248 @@
+use bevy::math::{IVec2, Vec2};
+
+// Swept tests of a moving point against a shape, done in fixed point so the time of
+// impact is the same on all peers. Positions are converted with the same precision
+// as the rounding of the simulation (1/1000).
+
+pub(crate) const POSITION_SCALE: f32 = 1000.0;
+
+/// Time of impact are returned in [0, TOI_SCALE], 0 at the start of the segment
+pub const TOI_SCALE: i128 = 1 << 16;
+
+/// Length of the normals, they are unit vectors in Q14
+pub const NORMAL_SCALE: i32 = 1 << 14;
+
+/// Impact of a swept test, the normal point out of the shape that was hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepHit {
+    pub toi: u32,
+    pub normal: IVec2,
+}
+
+pub(crate) fn to_fixed(v: Vec2) -> (i128, i128) {
+    (
+        (v.x * POSITION_SCALE).round() as i128,
+        (v.y * POSITION_SCALE).round() as i128,
+    )
+}
+
+pub(crate) fn to_fixed_scalar(v: f32) -> i128 {
+    (v * POSITION_SCALE).round() as i128
+}
+
+pub(crate) fn isqrt(value: i128) -> i128 {
+    if value < 2 {
+        return value.max(0);
+    }
+    // Newton iteration, start above the root
+    let mut x = value;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+// Fixed values that don't fit in an i32 are clamped to its range instead of wrapping
+// around to the other side of the world
+pub(crate) fn saturate_i32(value: i128) -> i32 {
+    value.clamp(i32::MIN as i128, i32::MAX as i128) as i32
+}
+
+// Unit vector in Q14, none for a null vector
+pub(crate) fn fixed_normalize(x: i128, y: i128) -> Option<IVec2> {
+    let length = isqrt(x * x + y * y);
+    if length == 0 {
+        return None;
+    }
+    let scale = NORMAL_SCALE as i128;
+    Some(IVec2::new((x * scale / length) as i32, (y * scale / length) as i32))
+}
+
+/// First time the segment come closer than `radius` from `center`
+pub fn segment_circle_toi(start: Vec2, end: Vec2, center: Vec2, radius: f32) -> Option<u32> {
+    segment_circle_hit(start, end, center, radius).map(|hit| hit.toi)
+}
+
+/// Same as `segment_circle_toi` with the normal of the circle at the impact
+pub fn segment_circle_hit(start: Vec2, end: Vec2, center: Vec2, radius: f32) -> Option<SweepHit> {
+    let (sx, sy) = to_fixed(start);
+    let (ex, ey) = to_fixed(end);
+    let (cx, cy) = to_fixed(center);
+    let r = to_fixed_scalar(radius);
+
+    let (dx, dy) = (ex - sx, ey - sy);
+    let (fx, fy) = (sx - cx, sy - cy);
+
+    // Pushed out from the center, or against the movement when on the center
+    let normal_at = |toi: i128| {
+        fixed_normalize(fx + dx * toi / TOI_SCALE, fy + dy * toi / TOI_SCALE)
+            .or_else(|| fixed_normalize(-dx, -dy))
+            .unwrap_or(IVec2::new(NORMAL_SCALE, 0))
+    };
+
+    let c = fx * fx + fy * fy - r * r;
+    if c < 0 {
+        // Already inside at the start
+        return Some(SweepHit { toi: 0, normal: normal_at(0) });
+    }
+
+    let a = dx * dx + dy * dy;
+    if a == 0 {
+        return None;
+    }
+
+    let b = 2 * (fx * dx + fy * dy);
+    if b >= 0 {
+        // Moving away from the center
+        return None;
+    }
+
+    let discriminant = b * b - 4 * a * c;
+    if discriminant < 0 {
+        return None;
+    }
+
+    let toi = (-b - isqrt(discriminant)) * TOI_SCALE / (2 * a);
+    (0..=TOI_SCALE).contains(&toi).then(|| SweepHit { toi: toi as u32, normal: normal_at(toi) })
+}
+
+/// First time the segment enter the axis aligned box, slab method
+pub fn segment_aabb_toi(start: Vec2, end: Vec2, center: Vec2, half_size: Vec2) -> Option<u32> {
+    segment_aabb_hit(start, end, center, half_size).map(|hit| hit.toi)
+}
+
+/// Same as `segment_aabb_toi` with the normal of the face that was entered
+pub fn segment_aabb_hit(start: Vec2, end: Vec2, center: Vec2, half_size: Vec2) -> Option<SweepHit> {
+    let (sx, sy) = to_fixed(start);
+    let (ex, ey) = to_fixed(end);
+    let (cx, cy) = to_fixed(center);
+    let (hx, hy) = to_fixed(half_size);
+
+    let mut t_min = 0;
+    let mut t_max = TOI_SCALE;
+    // Starting inside, pushed back along the main axis of the movement
+    let (dx, dy) = (ex - sx, ey - sy);
+    let mut normal = if dx.abs() >= dy.abs() {
+        IVec2::new(-(dx.signum() as i32) * NORMAL_SCALE, 0)
+    } else {
+        IVec2::new(0, -(dy.signum() as i32) * NORMAL_SCALE)
+    };
+
+    for (axis, s, d, min, max) in [(IVec2::X, sx, dx, cx - hx, cx + hx), (IVec2::Y, sy, dy, cy - hy, cy + hy)] {
+        if d == 0 {
+            if s < min || s > max {
+                return None;
+            }
+            continue;
+        }
+
+        let mut t1 = (min - s) * TOI_SCALE / d;
+        let mut t2 = (max - s) * TOI_SCALE / d;
+        if t1 > t2 {
+            std::mem::swap(&mut t1, &mut t2);
+        }
+
+        // The last axis entered is the face that was hit
+        if t1 > t_min {
+            t_min = t1;
+            normal = axis * -(d.signum() as i32) * NORMAL_SCALE;
+        }
+        t_max = t_max.min(t2);
+        if t_min > t_max {
+            return None;
+        }
+    }
+
+    Some(SweepHit { toi: t_min as u32, normal })
+}
+
+/// Velocity bounced on a surface with the normal of a `SweepHit`, v - 2 (v.n) n.
+/// Done in fixed point so every peer bounce the same way.
+pub fn reflect(velocity: Vec2, normal: IVec2) -> Vec2 {
+    let (vx, vy) = to_fixed(velocity);
+    let (nx, ny) = (normal.x as i128, normal.y as i128);
+    let scale = NORMAL_SCALE as i128;
+
+    let dot = vx * nx + vy * ny;
+    let rx = vx - 2 * dot * nx / (scale * scale);
+    let ry = vy - 2 * dot * ny / (scale * scale);
+    Vec2::new(rx as f32 / POSITION_SCALE, ry as f32 / POSITION_SCALE)
+}
+
+/// Point of the segment at the time of impact
+pub fn point_at_toi(start: Vec2, end: Vec2, toi: u32) -> Vec2 {
+    start + (end - start) * (toi as f32 / TOI_SCALE as f32)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isqrt() {
+        for value in [0, 1, 2, 3, 4, 15, 16, 17, 1_000_000, 999_999_999_999] {
+            let root = isqrt(value);
+            assert!(root * root <= value && (root + 1) * (root + 1) > value, "isqrt({}) = {}", value, root);
+        }
+    }
+
+    #[test]
+    fn test_segment_go_through_thin_circle() {
+        // Both ends are outside the circle, a discrete check would miss it
+        let toi = segment_circle_toi(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), Vec2::ZERO, 5.0);
+        let toi = toi.expect("segment should hit the circle");
+        let point = point_at_toi(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), toi);
+        assert!((point.x + 5.0).abs() < 0.1, "impact at {:?}", point);
+    }
+
+    #[test]
+    fn test_segment_miss_circle() {
+        assert_eq!(segment_circle_toi(Vec2::new(-100.0, 10.0), Vec2::new(100.0, 10.0), Vec2::ZERO, 5.0), None);
+        assert_eq!(segment_circle_toi(Vec2::new(-100.0, 0.0), Vec2::new(-50.0, 0.0), Vec2::ZERO, 5.0), None);
+    }
+
+    #[test]
+    fn test_segment_go_through_thin_wall() {
+        let toi = segment_aabb_toi(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), Vec2::ZERO, Vec2::new(1.0, 50.0));
+        assert_eq!(toi, Some((99 * TOI_SCALE / 200) as u32));
+    }
+
+    #[test]
+    fn test_segment_miss_aabb() {
+        assert_eq!(segment_aabb_toi(Vec2::new(-100.0, 60.0), Vec2::new(100.0, 60.0), Vec2::ZERO, Vec2::new(1.0, 50.0)), None);
+        assert_eq!(segment_aabb_toi(Vec2::new(10.0, -100.0), Vec2::new(10.0, 100.0), Vec2::ZERO, Vec2::new(1.0, 50.0)), None);
+    }
+
+    #[test]
+    fn test_aabb_hit_normal() {
+        let hit = segment_aabb_hit(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), Vec2::ZERO, Vec2::new(1.0, 50.0)).unwrap();
+        assert_eq!(hit.normal, IVec2::new(-NORMAL_SCALE, 0));
+        let hit = segment_aabb_hit(Vec2::new(0.0, 100.0), Vec2::new(5.0, -100.0), Vec2::ZERO, Vec2::new(50.0, 1.0)).unwrap();
+        assert_eq!(hit.normal, IVec2::new(0, NORMAL_SCALE));
+    }
+
+    #[test]
+    fn test_circle_hit_normal() {
+        let hit = segment_circle_hit(Vec2::new(-100.0, 0.0), Vec2::new(100.0, 0.0), Vec2::ZERO, 5.0).unwrap();
+        assert_eq!(hit.normal, IVec2::new(-NORMAL_SCALE, 0));
+    }
+
+    #[test]
+    fn test_reflect() {
+        assert_eq!(reflect(Vec2::new(3.0, -2.0), IVec2::new(0, NORMAL_SCALE)), Vec2::new(3.0, 2.0));
+        assert_eq!(reflect(Vec2::new(3.0, -2.0), IVec2::new(-NORMAL_SCALE, 0)), Vec2::new(-3.0, -2.0));
+        // Head on a diagonal surface go back the same way, within the precision of the normal
+        let normal = fixed_normalize(-1000, -1000).unwrap();
+        let reflected = reflect(Vec2::new(10.0, 10.0), normal);
+        assert!((reflected - Vec2::new(-10.0, -10.0)).length() < 0.01, "reflected {:?}", reflected);
+    }
+
+    #[test]
+    fn test_start_inside() {
+        assert_eq!(segment_circle_toi(Vec2::ZERO, Vec2::new(100.0, 0.0), Vec2::ZERO, 5.0), Some(0));
+        assert_eq!(segment_aabb_toi(Vec2::ZERO, Vec2::new(100.0, 0.0), Vec2::ZERO, Vec2::splat(5.0)), Some(0));
+    }
+}
@@ -20,7 +20,23 @@ fn update_window_size(mut window: Query<&mut Window>) {
         let browser_window = web_sys::window()?;
         let width = browser_window.inner_width().ok()?.as_f64()?;
         let height = browser_window.inner_height().ok()?.as_f64()?;
-        window.resolution.set(width as f32, height as f32);
+        // Only on a change, a resize every frame keep the canvas and the camera one
+        // frame behind the size used to turn the cursor into world coordinates
+        let (width, height) = (width as f32, height as f32);
+        if window.resolution.width() != width || window.resolution.height() != height {
+            window.resolution.set(width, height);
+        }
         Some(())
     })();
 }
+
+// Language the browser ask for, like `fr-CA`, there is nothing to ask on native
+#[cfg(target_arch = "wasm32")]
+pub fn browser_language() -> Option<String> {
+    web_sys::window()?.navigator().language()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn browser_language() -> Option<String> {
+    None
+}
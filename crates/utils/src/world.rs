@@ -0,0 +1,56 @@
+use thiserror::Error;
+
+// World coordinates of the simulation. The transforms are f32 rounded to 1/1000 of a
+// unit (`math::round`), a f32 only keep that grid up to 2^14 units from the origin:
+// past it two positions 1/1000 apart are the same float and the rounding start moving
+// things by itself. The simulation is not chunked, a map must fit in that range and
+// the game only audit it.
+
+/// Coordinates strictly inside ±MAX_EXACT_COORDINATE keep the 1/1000 grid in a f32
+pub const MAX_EXACT_COORDINATE: f32 = 16_384.0;
+
+#[derive(Debug, Error, Clone, Copy, PartialEq)]
+pub enum WorldRangeError {
+    #[error("coordinate {0} is not a finite number")]
+    NotFinite(f64),
+    #[error("coordinate {0} is past the exact range of ±{max} units", max = MAX_EXACT_COORDINATE)]
+    OutOfRange(f64),
+}
+
+/// The coordinate keep the 1/1000 grid of the simulation
+pub fn is_exact_coordinate(value: f32) -> bool {
+    value.is_finite() && value.abs() < MAX_EXACT_COORDINATE
+}
+
+pub fn check_coordinate(value: f32) -> Result<f32, WorldRangeError> {
+    if !value.is_finite() {
+        Err(WorldRangeError::NotFinite(value as f64))
+    } else if value.abs() >= MAX_EXACT_COORDINATE {
+        Err(WorldRangeError::OutOfRange(value as f64))
+    } else {
+        Ok(value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saturation_is_reported() {
+        assert_eq!(check_coordinate(16_384.0), Err(WorldRangeError::OutOfRange(16_384.0)));
+        assert_eq!(check_coordinate(-40_000.0), Err(WorldRangeError::OutOfRange(-40_000.0)));
+        assert!(matches!(check_coordinate(f32::NAN), Err(WorldRangeError::NotFinite(_))));
+        assert_eq!(check_coordinate(16_383.999), Ok(16_383.999));
+        assert!(!is_exact_coordinate(f32::INFINITY));
+    }
+
+    #[test]
+    fn test_past_the_edge_the_grid_is_lost() {
+        // Why the exact range stop there, 1/1000 apart become the same float
+        let edge = MAX_EXACT_COORDINATE * 2.0;
+        assert_eq!(edge + 0.001, edge);
+        assert_ne!(MAX_EXACT_COORDINATE - 1.0 + 0.001, MAX_EXACT_COORDINATE - 1.0);
+    }
+}
@@ -17,4 +17,44 @@ pub struct Opt {
     pub players: Option<Vec<String>>,
     #[clap(short, long, num_args = 1..)]
     pub spectators: Option<Vec<SocketAddr>>,
+    // Class of each player, in the same order as the players
+    #[clap(short, long, num_args = 1..)]
+    pub classes: Option<Vec<String>>,
+    // Name of each player, in the same order as the players
+    #[clap(long, num_args = 1..)]
+    pub names: Option<Vec<String>>,
+    // Color of each player, an index in the identity palette
+    #[clap(long, num_args = 1..)]
+    pub colors: Option<Vec<usize>>,
+    // Game mode, survival, defend, deathmatch or tutorial (offline only)
+    #[clap(long,)]
+    pub mode: Option<String>,
+    // Collision preset of `collision_presets.ron`, the one of the mode by default
+    #[clap(long,)]
+    pub collisions: Option<String>,
+    // Password of a private lobby, checked by the host
+    #[clap(long,)]
+    pub password: Option<String>,
+    // Offline only, [Backspace] rewind the game a few seconds
+    #[clap(long,)]
+    pub practice: bool,
+    // Headless recorders to wait for in a new online lobby
+    #[clap(long,)]
+    pub recorders: Option<usize>,
+    // Take a place of the online lobby as a spectator, [Enter] join at the next wave
+    #[clap(long,)]
+    pub dropin: bool,
+    // Offline only, replay the last seconds from the killer after a death
+    #[clap(long,)]
+    pub kill_cam: bool,
+    // GGRS tuning, lan, internet or high-latency, the values below override it
+    #[clap(long,)]
+    pub network_preset: Option<String>,
+    #[clap(long,)]
+    pub input_delay: Option<usize>,
+    #[clap(long,)]
+    pub max_prediction: Option<usize>,
+    // Frames between two desync checks, 0 to disable them
+    #[clap(long,)]
+    pub desync_interval: Option<u32>,
 }
\ No newline at end of file
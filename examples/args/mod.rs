@@ -8,7 +8,10 @@ mod web;
 mod cli;
 
 
-pub fn get_args() -> (u16, usize, Vec<String>, Vec<SocketAddr>, String, String) {
+// Network preset, input delay, prediction window and desync interval given to override the defaults
+pub type NetworkArgs = (Option<String>, Option<usize>, Option<usize>, Option<u32>);
+
+pub fn get_args() -> (u16, usize, Vec<String>, Vec<SocketAddr>, String, String, Vec<String>, Vec<String>, Vec<usize>, String, Option<String>, Option<String>, bool, usize, bool, bool, NetworkArgs) {
 
     #[cfg(not(target_arch = "wasm32"))]
     {
@@ -22,6 +25,17 @@ pub fn get_args() -> (u16, usize, Vec<String>, Vec<SocketAddr>, String, String)
             args.spectators.unwrap_or(vec![]),
             args.matchbox.unwrap_or(String::new()),
             args.lobby.unwrap_or(String::new()),
+            args.classes.unwrap_or(vec![]),
+            args.names.unwrap_or(vec![]),
+            args.colors.unwrap_or(vec![]),
+            args.mode.unwrap_or(String::new()),
+            args.collisions,
+            args.password,
+            args.practice,
+            args.recorders.unwrap_or(0),
+            args.dropin,
+            args.kill_cam,
+            (args.network_preset, args.input_delay, args.max_prediction, args.desync_interval),
         );
     }
     #[cfg(target_arch = "wasm32")]
@@ -35,6 +49,17 @@ pub fn get_args() -> (u16, usize, Vec<String>, Vec<SocketAddr>, String, String)
             vec![],
             args.matchbox.unwrap_or(String::new()),
             args.lobby.unwrap_or(String::new()),
+            args.classes.unwrap_or(vec![]),
+            args.names.unwrap_or(vec![]),
+            args.colors.unwrap_or(vec![]),
+            args.mode.unwrap_or(String::new()),
+            args.collisions,
+            args.password,
+            args.practice,
+            args.recorders.unwrap_or(0),
+            args.dropin,
+            args.kill_cam,
+            (args.network_preset, args.input_delay, args.max_prediction, args.desync_interval),
         );
     }
 
@@ -6,6 +6,20 @@ pub struct CanvasConfig {
     pub number_player: Option<usize>,
     pub matchbox: Option<String>,
     pub lobby: Option<String>,
+    pub classes: Option<Vec<String>>,
+    pub names: Option<Vec<String>>,
+    pub colors: Option<Vec<usize>>,
+    pub mode: Option<String>,
+    pub collisions: Option<String>,
+    pub password: Option<String>,
+    pub practice: bool,
+    pub recorders: Option<usize>,
+    pub dropin: bool,
+    pub kill_cam: bool,
+    pub network_preset: Option<String>,
+    pub input_delay: Option<usize>,
+    pub max_prediction: Option<usize>,
+    pub desync_interval: Option<u32>,
 }
 
 pub fn read_canvas_data_system() -> CanvasConfig {
@@ -22,6 +36,24 @@ pub fn read_canvas_data_system() -> CanvasConfig {
 
     config.matchbox = canvas_element.get_attribute("data-matchbox");
     config.lobby = canvas_element.get_attribute("data-lobby");
+    config.classes = canvas_element.get_attribute("data-classes")
+        .map(|classes| classes.split(',').map(|c| c.trim().to_string()).collect());
+    config.names = canvas_element.get_attribute("data-names")
+        .map(|names| names.split(',').map(|n| n.trim().to_string()).collect());
+    config.colors = canvas_element.get_attribute("data-colors")
+        .map(|colors| colors.split(',').filter_map(|c| c.trim().parse().ok()).collect());
+    config.mode = canvas_element.get_attribute("data-mode");
+    config.collisions = canvas_element.get_attribute("data-collisions");
+    config.password = canvas_element.get_attribute("data-password");
+    config.practice = canvas_element.get_attribute("data-practice").map_or(false, |practice| practice == "true");
+    config.recorders = canvas_element.get_attribute("data-recorders").and_then(|recorders| recorders.parse().ok());
+    config.dropin = canvas_element.get_attribute("data-dropin").map_or(false, |dropin| dropin == "true");
+    config.kill_cam = canvas_element.get_attribute("data-kill-cam").map_or(false, |kill_cam| kill_cam == "true");
+
+    config.network_preset = canvas_element.get_attribute("data-network-preset");
+    config.input_delay = canvas_element.get_attribute("data-input-delay").and_then(|delay| delay.parse().ok());
+    config.max_prediction = canvas_element.get_attribute("data-max-prediction").and_then(|prediction| prediction.parse().ok());
+    config.desync_interval = canvas_element.get_attribute("data-desync-interval").and_then(|interval| interval.parse().ok());
 
     if let Some(nbr_str) = canvas_element.get_attribute("data-number-player") {
         match nbr_str.parse::<usize>() {
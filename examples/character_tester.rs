@@ -2,16 +2,30 @@ mod args;
 
 use args::get_args;
 use bevy::{asset::AssetMetaCheck, prelude::*, utils::hashbrown::HashMap, window::WindowResolution};
-use game::{character::{enemy::create::spawn_enemy, movement::Velocity, player::{ control::{get_input_map, PlayerAction}, LocalPlayer, Player}}, collider::{spawn_test_wall, CollisionSettings}, frame::FrameDebugUIPlugin, global_asset::GlobalAsset, jjrs::{GggrsConnectionConfiguration, GggrsSessionConfiguration}, plugins::{AppState, BaseZombieGamePlugin}, weapons::WeaponsConfig};
+use game::{collider::preset::preset_for, character::{enemy::create::spawn_enemy, movement::Velocity, player::{ control::{get_input_map, PlayerAction}, LocalPlayer, Player}}, collider::{spawn_test_wall, CollisionSettings}, frame::FrameDebugUIPlugin, global_asset::GlobalAsset, jjrs::{GggrsConnectionConfiguration, GggrsSessionConfiguration}, lobby::DEFAULT_MAP, network::NetworkPreset, plugins::{AppState, BaseZombieGamePlugin}, rules::{GameMode, GameRules}, weapons::WeaponsConfig};
 
 use utils::{web::WebPlugin};
 
 fn main() {
     
-    let (local_port,mut nbr_player, players, _, matchbox, lobby) = get_args();
+    let (local_port,mut nbr_player, players, _, matchbox, lobby, classes, names, colors, mode, collisions, password, practice, recorders, dropin, kill_cam, (network_preset, input_delay, max_prediction, desync_interval)) = get_args();
+
+    let mode = GameMode::from_name(&mode).unwrap_or_default();
 
     if nbr_player == 0 { nbr_player = players.len() }
 
+    let mut connection = GggrsConnectionConfiguration { input_delay: 5, max_player: nbr_player, max_prediction: 12, desync_interval: 10, socket: players.len() > 1, udp_port: local_port};
+    if let Some(preset) = network_preset {
+        match NetworkPreset::from_name(&preset) {
+            Some(preset) => connection.apply_preset(preset),
+            None => eprintln!("unknown network preset {}", preset),
+        }
+    }
+    connection.input_delay = input_delay.unwrap_or(connection.input_delay);
+    connection.max_prediction = max_prediction.unwrap_or(connection.max_prediction);
+    connection.desync_interval = desync_interval.unwrap_or(connection.desync_interval);
+    connection.clamp();
+
     let window_plugin = WindowPlugin {
         primary_window: Some(Window {
             title: "zrl-character_tester".to_string(),
@@ -40,6 +54,6 @@ fn main() {
         .add_plugins(WebPlugin{})
         .add_plugins(FrameDebugUIPlugin)
         .add_plugins(BaseZombieGamePlugin::new(matchbox != ""))
-        .insert_resource(GggrsSessionConfiguration { matchbox: matchbox != "", lobby: lobby.clone(), matchbox_url: matchbox.clone(), connection: GggrsConnectionConfiguration { input_delay: 5, max_player: nbr_player, desync_interval: 10, socket: players.len() > 1, udp_port: local_port}, players: players })
+        .insert_resource(GggrsSessionConfiguration { matchbox: matchbox != "", lobby: lobby.clone(), matchbox_url: matchbox.clone(), connection, players: players, classes, names, colors, rules: GameRules { collision_preset: preset_for(mode, collisions.as_deref()), ..GameRules::from_mode(mode) }, map: DEFAULT_MAP.to_string(), seed: 12345, password, practice, recorders, recorder: false, dropin, kill_cam })
         .run();
 }
\ No newline at end of file
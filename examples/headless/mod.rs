@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use bevy::{app::{PluginGroupBuilder, ScheduleRunnerPlugin}, asset::AssetMetaCheck, prelude::*, render::{settings::{RenderCreation, WgpuSettings}, RenderPlugin}, window::ExitCondition, winit::WinitPlugin};
+use game::{jjrs::{GggrsConnectionConfiguration, GggrsSessionConfiguration}, lobby::DEFAULT_MAP, rules::GameRules};
+use utils::frame::SimulationConfig;
+
+// Common part of the headless examples, no window and no audio, the updates run at the
+// rate of the simulation.
+
+pub fn headless_plugins(simulation: &SimulationConfig) -> (PluginGroupBuilder, ScheduleRunnerPlugin) {
+    let plugins = DefaultPlugins
+        .set(AssetPlugin {
+            meta_check: AssetMetaCheck::Never,
+            ..Default::default()
+        })
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+        })
+        // The assets are still loaded, nothing is sent to a GPU
+        .set(RenderPlugin {
+            render_creation: RenderCreation::Automatic(WgpuSettings { backends: None, ..Default::default() }),
+            ..Default::default()
+        })
+        .disable::<WinitPlugin>()
+        .disable::<bevy::audio::AudioPlugin>();
+    (plugins, ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / simulation.rollback_fps() as f64)))
+}
+
+// Local synctest session of bots on the default map, the online fields are overridden
+// by the callers that join a lobby
+pub fn headless_session(connection: GggrsConnectionConfiguration, rules: GameRules, players: Vec<String>, seed: u32) -> GggrsSessionConfiguration {
+    GggrsSessionConfiguration {
+        matchbox: false,
+        lobby: String::new(),
+        matchbox_url: String::new(),
+        connection,
+        players,
+        classes: vec![],
+        names: vec![],
+        colors: vec![],
+        rules,
+        map: DEFAULT_MAP.to_string(),
+        seed,
+        password: None,
+        practice: false,
+        recorders: 0,
+        recorder: false,
+        dropin: false,
+        kill_cam: false,
+    }
+}
@@ -0,0 +1,305 @@
+mod headless;
+mod timing;
+
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::{GgrsSchedule, LocalInputs, ReadInputs};
+use clap::Parser;
+use game::{budget::SimulationBudget, character::{enemy::Enemy, player::{input::{BoxInput, INPUT_LEFT, INPUT_RIGHT}, jjrs::PeerConfig}}, frame::{increase_frame_system, FrameCount}, jjrs::{GggrsConnectionConfiguration, GggrsSessionConfiguration}, plugins::{AppState, BaseZombieGamePlugin}, rules::{GameMode, GameRules}, weapons::Bullet};
+use headless::{headless_plugins, headless_session};
+use serde::Serialize;
+use timing::{percentile, record_update_time, start_update_timer, UpdateTimes};
+use utils::{aim::encode_aim, frame::SimulationConfig};
+
+// Headless benchmark, always the same game: fixed seed and map, every wave a horde at
+// the enemy cap and bots following a script that strafe and fire without a stop. After
+// the warmup the time of each update and the frames simulated by the synctest are
+// recorded for the duration, the report is printed and optionally written as json.
+// With the thresholds the process exit with an error on a regression, for the CI.
+#[derive(Parser)]
+pub struct Opt {
+    // Seconds of game time measured, after the warmup
+    #[clap(long, default_value_t = 60.0)]
+    pub seconds: f32,
+    // Seconds of game time ignored at the start, the loading and the first spawns
+    #[clap(long, default_value_t = 2.0)]
+    pub warmup_seconds: f32,
+    #[clap(short, long, default_value_t = 2)]
+    pub number_player: usize,
+    #[clap(long, default_value_t = 12345)]
+    pub seed: u32,
+    // Enemy cap of the simulation budget, the horde fill it
+    #[clap(long, default_value_t = SimulationBudget::default().max_enemies)]
+    pub max_enemies: usize,
+    // Write the report as json to this file
+    #[clap(long)]
+    pub output: Option<String>,
+    // Fail when the average or the 99th percentile of the updates go over, in milliseconds
+    #[clap(long)]
+    pub max_average_ms: Option<f32>,
+    #[clap(long)]
+    pub max_p99_ms: Option<f32>,
+}
+
+// Divide the cooldown of the spawners, they are ready again as soon as a slot is free
+const HORDE_SPAWN_RATE: u32 = 100;
+// Frames a bot strafe one way before going back
+const STRAFE_FRAMES: u32 = 90;
+// Turn of the aim of a bot per frame, in radians, a full turn in 4 seconds at 60 Hz
+const AIM_TURN: f32 = std::f32::consts::TAU / 240.0;
+const AIM_DISTANCE: f32 = 300.0;
+
+#[derive(Resource, Clone, Debug)]
+struct BenchSettings {
+    start_frame: u32,
+    end_frame: u32,
+    number_player: usize,
+    output: Option<String>,
+    max_average_ms: Option<f32>,
+    max_p99_ms: Option<f32>,
+}
+
+#[derive(Resource, Default)]
+struct BenchStats {
+    // Inputs given to the bots since the start, the script only depend on it
+    script_step: u32,
+    // Runs of the rollback schedule in the window, the new frames and the resimulated ones
+    simulated_frames: u32,
+    resimulated_frames: u32,
+    rollbacks: u32,
+    last_simulated_frame: u32,
+    highest_frame: u32,
+    enemy_samples: Vec<usize>,
+    bullet_samples: Vec<usize>,
+    started: Option<Instant>,
+}
+
+#[derive(Serialize, Debug)]
+struct BenchReport {
+    seed: u32,
+    map: String,
+    players: usize,
+    max_enemies: usize,
+    frames: u32,
+    updates: usize,
+    average_ms: f32,
+    p50_ms: f32,
+    p99_ms: f32,
+    max_ms: f32,
+    simulated_frames: u32,
+    resimulated_frames: u32,
+    rollbacks: u32,
+    average_enemies: f32,
+    peak_enemies: usize,
+    average_bullets: f32,
+    elapsed_seconds: f32,
+}
+
+fn average(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    if count == 0 {
+        return 0.0;
+    }
+    sum / count as f32
+}
+
+// Strafe from side to side and turn the aim while firing, each bot on its own phase
+fn scripted_input(handle: usize, step: u32) -> BoxInput {
+    let step = step + handle as u32 * STRAFE_FRAMES / 2;
+    let mut input = BoxInput::default();
+    input.buttons = if (step / STRAFE_FRAMES) % 2 == 0 { INPUT_LEFT } else { INPUT_RIGHT };
+    let angle = step as f32 * AIM_TURN + handle as f32 * std::f32::consts::PI;
+    input.aim = encode_aim(Vec2::from_angle(angle) * AIM_DISTANCE);
+    input.fire = true;
+    input
+}
+
+
+// SYSTEMS
+
+// Headless has no local input reading, the script feed every handle
+fn read_scripted_inputs(
+    mut commands: Commands,
+    settings: Res<BenchSettings>,
+    mut stats: ResMut<BenchStats>,
+) {
+    let step = stats.script_step;
+    let local_inputs: HashMap<_, _> = (0..settings.number_player)
+        .map(|handle| (handle, scripted_input(handle, step)))
+        .collect();
+    stats.script_step += 1;
+
+    commands.insert_resource(LocalInputs::<PeerConfig>(local_inputs));
+}
+
+// Every run of the rollback schedule, a frame not above the highest is a resimulation
+fn count_simulated_frames(
+    frame: Res<FrameCount>,
+    settings: Res<BenchSettings>,
+    mut stats: ResMut<BenchStats>,
+) {
+    let in_window = frame.frame > settings.start_frame && frame.frame <= settings.end_frame;
+    if frame.frame <= stats.highest_frame {
+        if in_window {
+            stats.resimulated_frames += 1;
+            // The first frame replayed after going back
+            if frame.frame <= stats.last_simulated_frame {
+                stats.rollbacks += 1;
+            }
+        }
+    } else {
+        stats.highest_frame = frame.frame;
+        if in_window {
+            stats.simulated_frames += 1;
+        }
+    }
+    stats.last_simulated_frame = frame.frame;
+}
+
+// Only the updates after the warmup and until the last frame measured are timed
+fn in_bench_window(
+    frame: Res<FrameCount>,
+    settings: Res<BenchSettings>,
+    stats: Res<BenchStats>,
+) -> bool {
+    stats.highest_frame > settings.start_frame && frame.frame <= settings.end_frame
+}
+
+fn start_bench(mut stats: ResMut<BenchStats>) {
+    stats.started = Some(Instant::now());
+    info!("horde benchmark started");
+}
+
+fn sample_population(
+    frame: Res<FrameCount>,
+    settings: Res<BenchSettings>,
+    mut stats: ResMut<BenchStats>,
+    q_enemies: Query<(), With<Enemy>>,
+    q_bullets: Query<(), With<Bullet>>,
+) {
+    if frame.frame <= settings.start_frame || frame.frame > settings.end_frame {
+        return;
+    }
+    stats.enemy_samples.push(q_enemies.iter().count());
+    stats.bullet_samples.push(q_bullets.iter().count());
+}
+
+fn finish_bench(
+    frame: Res<FrameCount>,
+    settings: Res<BenchSettings>,
+    session_config: Res<GggrsSessionConfiguration>,
+    budget: Res<SimulationBudget>,
+    stats: Res<BenchStats>,
+    update_times: Res<UpdateTimes>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if stats.highest_frame < settings.end_frame {
+        return;
+    }
+
+    let mut times = update_times.times.clone();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let report = BenchReport {
+        seed: session_config.seed,
+        map: session_config.map.clone(),
+        players: settings.number_player,
+        max_enemies: budget.max_enemies,
+        frames: settings.end_frame - settings.start_frame,
+        updates: times.len(),
+        average_ms: average(times.iter().copied()),
+        p50_ms: percentile(&times, 0.5),
+        p99_ms: percentile(&times, 0.99),
+        max_ms: times.last().copied().unwrap_or(0.0),
+        simulated_frames: stats.simulated_frames,
+        resimulated_frames: stats.resimulated_frames,
+        rollbacks: stats.rollbacks,
+        average_enemies: average(stats.enemy_samples.iter().map(|count| *count as f32)),
+        peak_enemies: stats.enemy_samples.iter().copied().max().unwrap_or(0),
+        average_bullets: average(stats.bullet_samples.iter().map(|count| *count as f32)),
+        elapsed_seconds: stats.started.map_or(Duration::ZERO, |started| started.elapsed()).as_secs_f32(),
+    };
+    info!(
+        "{} frames, {} updates: average {:.2} p50 {:.2} p99 {:.2} max {:.2} ms, {} frames simulated and {} resimulated in {} rollbacks, enemies {:.1} (peak {}) bullets {:.1}",
+        report.frames, report.updates, report.average_ms, report.p50_ms, report.p99_ms, report.max_ms,
+        report.simulated_frames, report.resimulated_frames, report.rollbacks,
+        report.average_enemies, report.peak_enemies, report.average_bullets,
+    );
+
+    if let Some(path) = settings.output.as_ref() {
+        let result = serde_json::to_string_pretty(&report)
+            .map_err(|err| err.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|err| err.to_string()));
+        match result {
+            Ok(_) => info!("report written to {}", path),
+            Err(err) => error!("failed to write the report {}: {}", path, err),
+        }
+    }
+
+    let mut failures = vec![];
+    if report.peak_enemies < budget.max_enemies {
+        warn!("the horde never reached the cap, {} enemies at most for {}", report.peak_enemies, budget.max_enemies);
+    }
+    if let Some(max) = settings.max_average_ms.filter(|max| report.average_ms > *max) {
+        failures.push(format!("average update of {:.2} ms over {:.2} ms", report.average_ms, max));
+    }
+    if let Some(max) = settings.max_p99_ms.filter(|max| report.p99_ms > *max) {
+        failures.push(format!("p99 update of {:.2} ms over {:.2} ms", report.p99_ms, max));
+    }
+
+    if failures.is_empty() {
+        info!("horde benchmark done at frame {}", frame.frame);
+        exit.send(AppExit::Success);
+    } else {
+        for failure in failures.iter() {
+            error!("{}", failure);
+        }
+        error!("horde benchmark failed at frame {}", frame.frame);
+        exit.send(AppExit::from_code(1));
+    }
+}
+
+
+fn main() -> AppExit {
+    let args = Opt::parse();
+
+    let simulation = SimulationConfig::default();
+    let connection = GggrsConnectionConfiguration { input_delay: 0, max_player: args.number_player, max_prediction: 12, desync_interval: 10, socket: false, udp_port: 0 };
+
+    let start_frame = simulation.frames_from_seconds(args.warmup_seconds);
+    let end_frame = start_frame + simulation.frames_from_seconds(args.seconds).max(1);
+
+    // A single horde lasting the whole run, no boss wave and no end to the match
+    let rules = GameRules {
+        waves_to_survive: u32::MAX,
+        wave_duration_seconds: (args.warmup_seconds + args.seconds) * 2.0,
+        horde_wave_interval: 1,
+        boss_wave_interval: 0,
+        horde_spawn_rate: HORDE_SPAWN_RATE,
+        ..GameRules::from_mode(GameMode::Survival)
+    };
+
+    let settings = BenchSettings {
+        start_frame,
+        end_frame,
+        number_player: args.number_player,
+        output: args.output.clone(),
+        max_average_ms: args.max_average_ms,
+        max_p99_ms: args.max_p99_ms,
+    };
+
+    App::new()
+        .add_plugins(headless_plugins(&simulation))
+        .add_plugins(BaseZombieGamePlugin::new(false).headless().with_simulation(simulation).with_budget(SimulationBudget { max_enemies: args.max_enemies, ..Default::default() }))
+        .insert_resource(settings)
+        .init_resource::<BenchStats>()
+        .init_resource::<UpdateTimes>()
+        .add_systems(ReadInputs, read_scripted_inputs)
+        .add_systems(GgrsSchedule, count_simulated_frames.after(increase_frame_system))
+        .add_systems(OnEnter(AppState::InGame), start_bench)
+        .add_systems(First, start_update_timer.run_if(in_state(AppState::InGame)))
+        .add_systems(Last, record_update_time.run_if(in_state(AppState::InGame)).run_if(in_bench_window))
+        .add_systems(Update, (sample_population, finish_bench).chain().run_if(in_state(AppState::InGame)))
+        .insert_resource(headless_session(connection, rules, vec!["bot".to_string(); args.number_player], args.seed))
+        .run()
+}
@@ -0,0 +1,55 @@
+mod headless;
+
+use bevy::prelude::*;
+use clap::Parser;
+use game::{jjrs::{GggrsConnectionConfiguration, GggrsSessionConfiguration}, plugins::BaseZombieGamePlugin, recorder::{RecorderPlugin, RecorderSettings}, rules::{GameMode, GameRules}};
+use headless::{headless_plugins, headless_session};
+use utils::frame::SimulationConfig;
+
+// Headless recorder, join an online lobby as a spectator and write its inputs
+#[derive(Parser)]
+pub struct Opt {
+    #[clap(short, long,)]
+    pub matchbox: String,
+    // Lobby to record, with its options when it was shared by someone else
+    #[clap(long,)]
+    pub lobby: String,
+    // Players of the lobby when it has no options, the recorder is not counted
+    #[clap(short, long)]
+    pub number_player: Option<usize>,
+    #[clap(long,)]
+    pub mode: Option<String>,
+    #[clap(long,)]
+    pub password: Option<String>,
+    #[clap(short, long)]
+    pub output_dir: Option<String>,
+}
+
+fn main() {
+    let args = Opt::parse();
+
+    let mode = args.mode.as_deref().and_then(GameMode::from_name).unwrap_or_default();
+    let connection = GggrsConnectionConfiguration { input_delay: 5, max_player: args.number_player.unwrap_or(2), max_prediction: 12, desync_interval: 10, socket: true, udp_port: 0 };
+
+    let simulation = SimulationConfig::default();
+    let mut settings = RecorderSettings::default();
+    if let Some(output_dir) = args.output_dir {
+        settings.output_dir = output_dir;
+    }
+
+    App::new()
+        .add_plugins(headless_plugins(&simulation))
+        .add_plugins(BaseZombieGamePlugin::new(true).headless().with_simulation(simulation))
+        .add_plugins(RecorderPlugin)
+        .insert_resource(settings)
+        .insert_resource(GggrsSessionConfiguration {
+            matchbox: true,
+            lobby: args.lobby,
+            matchbox_url: args.matchbox,
+            password: args.password,
+            recorders: 1,
+            recorder: true,
+            ..headless_session(connection, GameRules::from_mode(mode), vec![], 12345)
+        })
+        .run();
+}
@@ -0,0 +1,322 @@
+mod headless;
+mod timing;
+
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ggrs::{LocalInputs, ReadInputs};
+use clap::Parser;
+use game::{character::{enemy::Enemy, player::{aim::AimAssistSettings, jjrs::PeerConfig, source::{InputContext, LocalInputSources}, Player}}, frame::FrameCount, jjrs::{GggrsConnectionConfiguration, GggrsSessionConfiguration}, plugins::{AppState, BaseZombieGamePlugin}, rng::{AiRng, DropsRng, SpawningRng, WeaponsRng}, rules::{GameMode, GameRules}, web::focus::FocusInput, weapons::Bullet};
+use headless::{headless_plugins, headless_session};
+use timing::{percentile, record_update_time, start_update_timer, UpdateTimes};
+use utils::{frame::SimulationConfig, rng::RollbackRng};
+
+// Headless soak test, a local synctest game played by bots for hours. Every sample the
+// entities, the memory of the process, the position of the rng streams and the time of
+// the updates are recorded, at the end the last samples are compared to the first ones
+// and the process exit with an error on a leak or a drift.
+#[derive(Parser)]
+pub struct Opt {
+    // Hours of game time to simulate
+    #[clap(long, default_value_t = 1.0)]
+    pub hours: f32,
+    // Bots playing, each one shoot the closest enemy
+    #[clap(short, long, default_value_t = 2)]
+    pub number_player: usize,
+    #[clap(long,)]
+    pub mode: Option<String>,
+    #[clap(long, default_value_t = 12345)]
+    pub seed: u32,
+    // Game time simulated per second of real time
+    #[clap(long, default_value_t = 4.0)]
+    pub speed: f32,
+    // Seconds of game time between two samples
+    #[clap(long, default_value_t = 60.0)]
+    pub sample_seconds: f32,
+    // Entities, other than the enemies and the bullets, that can be gained over the run
+    #[clap(long, default_value_t = 200)]
+    pub max_entity_growth: usize,
+    #[clap(long, default_value_t = 64.0)]
+    pub max_memory_growth_mb: f32,
+    // Slowest 1% of the updates can get this many times slower
+    #[clap(long, default_value_t = 2.0)]
+    pub max_frame_time_growth: f32,
+}
+
+// Samples compared at each end of the run, the first one is skipped it include the loading
+const WINDOW_SAMPLES: usize = 3;
+// Below this the frame time are noise, in milliseconds
+const MIN_FRAME_TIME_MS: f32 = 1.0;
+// Draws searched for the new position of a stream between two samples
+const MAX_RNG_STEPS: u64 = 50_000_000;
+
+#[derive(Resource, Clone, Debug)]
+struct SoakSettings {
+    total_frames: u32,
+    sample_frames: u32,
+    max_entity_growth: usize,
+    max_memory_growth_mb: f32,
+    max_frame_time_growth: f32,
+}
+
+// Follow a stream from its seed, the position is the number of draws. A state that is
+// never reached mean something wrote the stream without drawing from it
+#[derive(Clone, Debug)]
+struct StreamTracker {
+    name: &'static str,
+    shadow: RollbackRng,
+    position: u64,
+    lost: bool,
+}
+
+impl StreamTracker {
+    fn new(name: &'static str, start: RollbackRng) -> Self {
+        Self { name, shadow: start, position: 0, lost: false }
+    }
+
+    fn advance(&mut self, current: &RollbackRng) {
+        if self.lost {
+            return;
+        }
+        let mut steps = 0;
+        let mut shadow = self.shadow;
+        while shadow.seed != current.seed {
+            if steps == MAX_RNG_STEPS {
+                self.lost = true;
+                return;
+            }
+            shadow.next_u32();
+            steps += 1;
+        }
+        self.shadow = shadow;
+        self.position += steps;
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SoakSample {
+    frame: u32,
+    entities: usize,
+    enemies: usize,
+    bullets: usize,
+    memory_bytes: Option<u64>,
+    // Update time in milliseconds since the last sample
+    p50: f32,
+    p95: f32,
+    p99: f32,
+    max: f32,
+    rng_positions: Vec<u64>,
+}
+
+impl SoakSample {
+    // What should stay flat, the enemies and the bullets follow the waves
+    fn other_entities(&self) -> usize {
+        self.entities.saturating_sub(self.enemies + self.bullets)
+    }
+}
+
+#[derive(Resource, Default)]
+struct SoakStats {
+    samples: Vec<SoakSample>,
+    streams: Vec<StreamTracker>,
+    started: Option<Instant>,
+}
+
+// Resident memory of the process
+fn resident_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+        let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+        return Some(pages * 4096);
+    }
+    #[allow(unreachable_code)]
+    None
+}
+
+fn window_min<F: Fn(&SoakSample) -> Option<f32>>(samples: &[SoakSample], value: F) -> Option<f32> {
+    samples.iter().filter_map(value).fold(None, |min, value| Some(min.map_or(value, |min: f32| min.min(value))))
+}
+
+// Compare the end of the run to its start, every problem found
+fn evaluate(settings: &SoakSettings, stats: &SoakStats) -> Vec<String> {
+    let mut failures = vec![];
+
+    for stream in stats.streams.iter().filter(|stream| stream.lost) {
+        failures.push(format!("rng stream {} left its sequence after {} draws", stream.name, stream.position));
+    }
+
+    // The first sample include the loading
+    let samples = stats.samples.get(1..).unwrap_or(&[]);
+    if samples.len() < WINDOW_SAMPLES * 2 {
+        warn!("only {} samples, too short to look for leaks", samples.len());
+        return failures;
+    }
+    let first = &samples[..WINDOW_SAMPLES];
+    let last = &samples[samples.len() - WINDOW_SAMPLES..];
+
+    let first_entities = first.iter().map(SoakSample::other_entities).min().unwrap_or(0);
+    let last_entities = last.iter().map(SoakSample::other_entities).min().unwrap_or(0);
+    if last_entities > first_entities + settings.max_entity_growth {
+        failures.push(format!("entities leaking, {} at the start and {} at the end", first_entities, last_entities));
+    }
+
+    let memory = |sample: &SoakSample| sample.memory_bytes.map(|bytes| bytes as f32 / (1024.0 * 1024.0));
+    if let (Some(first_memory), Some(last_memory)) = (window_min(first, memory), window_min(last, memory)) {
+        if last_memory - first_memory > settings.max_memory_growth_mb {
+            failures.push(format!("memory growing, {:.1} MB at the start and {:.1} MB at the end", first_memory, last_memory));
+        }
+    }
+
+    if let (Some(first_p99), Some(last_p99)) = (window_min(first, |sample| Some(sample.p99)), window_min(last, |sample| Some(sample.p99))) {
+        if last_p99 > first_p99.max(MIN_FRAME_TIME_MS) * settings.max_frame_time_growth {
+            failures.push(format!("updates slowing down, p99 of {:.2} ms at the start and {:.2} ms at the end", first_p99, last_p99));
+        }
+    }
+
+    failures
+}
+
+
+// SYSTEMS
+
+// Headless has no local input reading, only the bots are fed
+fn read_bot_inputs(
+    mut commands: Commands,
+    mut sources: ResMut<LocalInputSources>,
+    players: Query<(&Transform, &Player)>,
+    enemies: Query<&Transform, With<Enemy>>,
+) {
+    let enemies: Vec<Vec2> = enemies.iter().map(|t| t.translation.truncate()).collect();
+    let aim_assist = AimAssistSettings::default();
+    let mut local_inputs = HashMap::new();
+
+    for (handle, source) in sources.sources.iter_mut() {
+        let context = InputContext {
+            action_state: None,
+            player_position: players.iter().find(|(_, player)| player.handle == *handle).map(|(transform, _)| transform.translation.truncate()),
+            pointer_offset: None,
+            gamepads: &[],
+            enemies: &enemies,
+            weapon_wheel: None,
+            touch: None,
+            aim_assist: &aim_assist,
+            focus: FocusInput::Focused,
+        };
+        local_inputs.insert(*handle, source.read(&context));
+    }
+
+    commands.insert_resource(LocalInputs::<PeerConfig>(local_inputs));
+}
+
+fn start_soak(
+    session_config: Res<GggrsSessionConfiguration>,
+    mut stats: ResMut<SoakStats>,
+) {
+    let seed = session_config.seed;
+    stats.streams = vec![
+        StreamTracker::new("weapons", *WeaponsRng::new(seed)),
+        StreamTracker::new("spawning", *SpawningRng::new(seed)),
+        StreamTracker::new("drops", *DropsRng::new(seed)),
+        StreamTracker::new("ai", *AiRng::new(seed)),
+    ];
+    stats.started = Some(Instant::now());
+    info!("soak test started");
+}
+
+fn sample_soak(
+    frame: Res<FrameCount>,
+    settings: Res<SoakSettings>,
+    mut stats: ResMut<SoakStats>,
+    mut update_times: ResMut<UpdateTimes>,
+    streams: (Res<WeaponsRng>, Res<SpawningRng>, Res<DropsRng>, Res<AiRng>),
+    q_entities: Query<Entity>,
+    q_enemies: Query<(), With<Enemy>>,
+    q_bullets: Query<(), With<Bullet>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let next = (stats.samples.len() as u32 + 1) * settings.sample_frames;
+    if frame.frame < next {
+        return;
+    }
+
+    let current = [**streams.0, **streams.1, **streams.2, **streams.3];
+    for (tracker, rng) in stats.streams.iter_mut().zip(current.iter()) {
+        tracker.advance(rng);
+    }
+
+    let mut times = std::mem::take(&mut update_times.times);
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let sample = SoakSample {
+        frame: frame.frame,
+        entities: q_entities.iter().count(),
+        enemies: q_enemies.iter().count(),
+        bullets: q_bullets.iter().count(),
+        memory_bytes: resident_memory(),
+        p50: percentile(&times, 0.5),
+        p95: percentile(&times, 0.95),
+        p99: percentile(&times, 0.99),
+        max: times.last().copied().unwrap_or(0.0),
+        rng_positions: stats.streams.iter().map(|stream| stream.position).collect(),
+    };
+    info!(
+        "frame {} entities {} (enemies {} bullets {}) memory {} update p50 {:.2} p95 {:.2} p99 {:.2} max {:.2} ms rng {:?}",
+        sample.frame, sample.entities, sample.enemies, sample.bullets,
+        sample.memory_bytes.map_or("?".to_string(), |bytes| format!("{:.1} MB", bytes as f32 / (1024.0 * 1024.0))),
+        sample.p50, sample.p95, sample.p99, sample.max, sample.rng_positions,
+    );
+    stats.samples.push(sample);
+
+    // A stream off its sequence won't come back
+    let lost = stats.streams.iter().any(|stream| stream.lost);
+    if !lost && frame.frame < settings.total_frames {
+        return;
+    }
+
+    let failures = evaluate(&settings, &stats);
+    let elapsed = stats.started.map_or(Duration::ZERO, |started| started.elapsed());
+    if failures.is_empty() {
+        info!("soak test passed, {} frames in {:.0}s", frame.frame, elapsed.as_secs_f32());
+        exit.send(AppExit::Success);
+    } else {
+        for failure in failures.iter() {
+            error!("{}", failure);
+        }
+        error!("soak test failed, {} frames in {:.0}s", frame.frame, elapsed.as_secs_f32());
+        exit.send(AppExit::from_code(1));
+    }
+}
+
+
+fn main() -> AppExit {
+    let args = Opt::parse();
+
+    let mode = args.mode.as_deref().and_then(GameMode::from_name).unwrap_or_default();
+    let simulation = SimulationConfig { time_scale: args.speed.max(0.1), ..Default::default() };
+    let connection = GggrsConnectionConfiguration { input_delay: 0, max_player: args.number_player, max_prediction: 12, desync_interval: 10, socket: false, udp_port: 0 };
+
+    // A long run must stay in the match, the generator is never saved
+    let rules = GameRules { waves_to_survive: u32::MAX, ..GameRules::from_mode(mode) };
+
+    let settings = SoakSettings {
+        total_frames: simulation.frames_from_seconds(args.hours * 3600.0),
+        sample_frames: simulation.frames_from_seconds(args.sample_seconds).max(1),
+        max_entity_growth: args.max_entity_growth,
+        max_memory_growth_mb: args.max_memory_growth_mb,
+        max_frame_time_growth: args.max_frame_time_growth,
+    };
+
+    App::new()
+        .add_plugins(headless_plugins(&simulation))
+        .add_plugins(BaseZombieGamePlugin::new(false).headless().with_simulation(simulation))
+        .insert_resource(settings)
+        .init_resource::<SoakStats>()
+        .init_resource::<UpdateTimes>()
+        .add_systems(ReadInputs, read_bot_inputs)
+        .add_systems(OnEnter(AppState::InGame), start_soak)
+        .add_systems(First, start_update_timer.run_if(in_state(AppState::InGame)))
+        .add_systems(Last, record_update_time.run_if(in_state(AppState::InGame)))
+        .add_systems(Update, sample_soak.run_if(in_state(AppState::InGame)))
+        .insert_resource(headless_session(connection, rules, vec!["bot".to_string(); args.number_player], args.seed))
+        .run()
+}
@@ -0,0 +1,30 @@
+use std::time::Instant;
+
+use bevy::prelude::*;
+
+// Timing of the updates, shared by the soak test and the horde benchmark
+
+// Time of each update in milliseconds, from the start of `First` to the end of `Last`
+#[derive(Resource, Default)]
+pub struct UpdateTimes {
+    pub times: Vec<f32>,
+    start: Option<Instant>,
+}
+
+pub fn start_update_timer(mut update_times: ResMut<UpdateTimes>) {
+    update_times.start = Some(Instant::now());
+}
+
+pub fn record_update_time(mut update_times: ResMut<UpdateTimes>) {
+    if let Some(start) = update_times.start.take() {
+        update_times.times.push(start.elapsed().as_secs_f32() * 1000.0);
+    }
+}
+
+pub fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    sorted[index.min(sorted.len() - 1)]
+}